@@ -1,16 +1,34 @@
+use super::heartbeat::{run_with_heartbeat, AgentActivity, Heartbeat, HeartbeatPolicy, Interruption};
+use super::regrounding;
+use super::router::{ModelRouter, ModelTier, RouteDecision};
+use super::step_extension::{StepExtension, StepExtensions};
+use super::step_forecast::{StepBudgetPrompt, StepForecaster, StepHistory};
+use super::subagents::SubagentUsage;
+use super::task_spec::TaskSpec;
+use super::token_budget::{BudgetCheck, TokenBudget};
+use super::tool_caps::{CapCheck, ToolCaps};
 use crate::config::Config;
 use crate::llm::base_client::{
-    LLMClient, LLMError, LLMMessage, LLMResponse, MessageRole, ToolCall as LLMToolCall, LLMUsage,
+    LLMClient, LLMError, LLMMessage, LLMResponse, MessageRole, ToolCall as LLMToolCall,
+    ToolDefinition, LLMUsage, with_retry_observer,
 };
-use crate::llm::{AnthropicClient, OpenAIClient};
-use crate::tools::{AgentToolResult, ToolExecutor, ToolRegistry};
+use crate::llm::capabilities;
+use crate::llm::continuation::{complete_truncated, is_truncated};
+use crate::llm::streaming::StreamEvent;
+use crate::llm::create_client;
+use crate::tools::execution::ExecutionEnvironment;
+use crate::tools::{AgentToolResult, FinalReport, ToolContext, ToolExecutor, ToolRegistry};
+use crate::utils::git_utils::{file_diff_stats, step_changes, DiffStat, FileChange, FileStats};
+use crate::utils::guards::WriteGuard;
 use crate::utils::trajectory_recorder::TrajectoryRecorder; // Added
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
 use thiserror::Error;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
 /// Errors that can occur during agent operations.
@@ -39,6 +57,35 @@ pub enum AgentError {
     #[allow(dead_code)] // Currently try_new handles this, but could be a distinct error
     #[error("No LLM client available")]
     NoLLMClient,
+    /// A step ran longer than the configured `step_timeout_secs` and was aborted.
+    #[error("Step {0} aborted after running for {1}s (step_timeout_secs exceeded)")]
+    StepTimeout(u32, u64),
+    /// The next step would have gone over the configured token budget, so it was not sent.
+    #[error("Token budget exceeded before step {0}: {1}")]
+    TokenBudgetExceeded(u32, String),
+    /// The tool calls of a step went over one of the configured `tool_caps`.
+    #[error("Tool usage cap exceeded in step {0}: {1}")]
+    ToolCapExceeded(u32, String),
+    /// The model lacks a capability the agent needs, such as tool calling.
+    #[error("Unsuitable model: {0}")]
+    MissingCapability(String),
+    /// The run's cancellation token was cancelled (e.g., Ctrl-C during an interactive turn).
+    #[error("Cancelled by the user during step {0}")]
+    Cancelled(u32),
+}
+
+impl AgentError {
+    /// What the user can do about the error, if it has a known remedy.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            AgentError::LLMError(e) => e.hint(),
+            AgentError::MissingCapability(_) => Some(
+                "Choose a model with tool calling support (--model, or the provider's model in the config). \
+                If the model does support tools, set probe_capabilities to false to skip the startup check.",
+            ),
+            _ => None,
+        }
+    }
 }
 
 /// Represents the various states an agent can be in during its execution loop.
@@ -84,6 +131,26 @@ pub struct AgentStep {
     pub error: Option<String>,
     /// Duration of this step in milliseconds.
     pub duration_ms: u128,
+    /// The model the step was routed to, when model routing is configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<RouteDecision>,
+    /// Why the agent's completion signal in this step was rejected, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation_error: Option<String>,
+    /// Size of the project's changes (against the base commit) after this step, recorded when
+    /// the step changed them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_stat: Option<DiffStat>,
+    /// The files this step changed, with the lines it added and removed in each.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_changes: Vec<FileChange>,
+    /// Time spent waiting for the LLM in this step, in milliseconds (including a failed
+    /// attempt with the cheap model, when routing escalated).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llm_duration_ms: Option<u128>,
+    /// Requests for more steps the agent made in this step, and their outcomes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub step_extensions: Vec<StepExtension>,
 }
 
 /// Records the entire execution trajectory of an agent for a given task.
@@ -106,6 +173,16 @@ pub struct AgentExecution {
     pub total_tokens_used: Option<LLMUsage>, // Changed from Option<u32>
     /// Optional error message if the agent execution failed overall.
     pub error_message: Option<String>,
+    /// What the user can do about the error, if it has a known remedy (see `LLMError::hint`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_hint: Option<String>,
+    /// Usage of the sub-agents this execution delegated to, already included in
+    /// `total_tokens_used` (see `subagents::SubagentUsage`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subagents: Vec<SubagentUsage>,
+    /// The report the agent delivered through the `final_report` tool, if it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_report: Option<Box<FinalReport>>,
 }
 
 /// Represents events that can occur during an agent's task execution.
@@ -133,6 +210,16 @@ pub enum AgentEvent {
     TaskFailed(Box<AgentExecution>),
     /// A general status update message from the agent.
     StatusUpdate(String),
+    /// Periodic signal that a step is still waiting on the LLM or on tools.
+    Heartbeat(Heartbeat),
+    /// The LLM started a tool call whose arguments are still streaming. Contains step number and tool name.
+    ToolCallStreaming(u32, String),
+    /// Preflight result for the file a streaming tool call is about to write. Contains step
+    /// number, target path and, if the write would be rejected, the reason.
+    WritePreflight(u32, PathBuf, Option<String>),
+    /// The step changed the project's files. Contains step number and the diffstat of all
+    /// changes so far against the base commit.
+    DiffStat(u32, DiffStat),
 }
 
 /// Defines the core capabilities of an agent.
@@ -147,13 +234,9 @@ pub trait Agent: Send + Sync {
     ///
     /// # Arguments
     /// * `task`: A string describing the task to be performed.
-    /// * `task_args`: Optional `serde_json::Value` containing additional arguments or context
-    ///                for the task (e.g., project path, `must_patch` flag).
-    async fn new_task(
-        &mut self,
-        task: String,
-        task_args: Option<serde_json::Value>,
-    ) -> Result<(), AgentError>;
+    /// * `spec`: Arguments of the task (e.g., project path, `must_patch` flag); use
+    ///           `TaskSpec::default()` for none.
+    async fn new_task(&mut self, task: String, spec: TaskSpec) -> Result<(), AgentError>;
 
     /// Executes the currently configured task.
     /// This involves iteratively interacting with the LLM, calling tools, and managing state
@@ -200,8 +283,6 @@ pub struct BaseAgent {
     #[allow(dead_code)]
     pub name: String,
     /// Shared application configuration.
-    #[allow(dead_code)]
-    // Config is used in try_new to init other fields, but this Arc copy isn't read later by BaseAgent methods.
     pub config: Arc<Config>,
     /// The LLM client instance used for interacting with the language model.
     pub llm_client: Arc<dyn LLMClient>,
@@ -225,6 +306,26 @@ pub struct BaseAgent {
     pub patch_path: Option<String>, // Added for patch saving
     /// Optional trajectory recorder.
     pub trajectory_recorder: Option<TrajectoryRecorder>, // Added
+    /// Routes simple steps to an inexpensive model, if `Config::routing` is set.
+    pub router: Option<ModelRouter>,
+    /// The run's scratch directory (see `utils::scratch`), if it has one.
+    pub scratch_dir: Option<PathBuf>,
+    /// Identifier of the run (see `utils::scratch::run_id`), if it has one.
+    pub run_id: Option<String>,
+    /// Where `bash` and the editor act (see `tools::execution`); the host if `None`.
+    pub execution: Option<Arc<dyn ExecutionEnvironment>>,
+    /// Restrictions on the files the editing tools may modify, if any.
+    pub write_guard: Option<Arc<WriteGuard>>,
+    /// Stops the run at the LLM call or tool calls in progress when cancelled. A cancelled
+    /// token stays cancelled; set a new one before running again.
+    pub cancellation: CancellationToken,
+    /// Steps of earlier successful runs, for the step budget forecast (see `step_forecast`).
+    pub step_history: StepHistory,
+    /// Asks the user to extend the step budget when it is running out, if set.
+    pub step_budget_prompt: Option<Arc<dyn StepBudgetPrompt>>,
+    /// The agent's requests for more steps, decided by the `request_extension` tool, if the
+    /// run allows them.
+    pub step_extensions: Option<Arc<StepExtensions>>,
 }
 
 impl BaseAgent {
@@ -249,34 +350,41 @@ impl BaseAgent {
             .get_current_provider_config()
             .map_err(|e| AgentError::ConfigError(e.to_string()))?;
 
-        let llm_client: Arc<dyn LLMClient> = match provider_name.as_str() {
-            "openai" => Arc::new(
-                OpenAIClient::new(
-                    provider_config.api_key.clone(),
-                    None,
-                    provider_config.clone(),
-                )
-                .await?,
-            ),
-            "anthropic" => Arc::new(
-                AnthropicClient::new(
-                    provider_config.api_key.clone(),
-                    None,
-                    provider_config.clone(),
-                )
-                .await?,
-            ),
-            _ => {
-                return Err(AgentError::ConfigError(format!(
-                    "Unsupported LLM provider: {}",
-                    provider_name
-                )))
+        let llm_client = create_client(provider_name, provider_config).await?;
+
+        let router = match &config.routing {
+            Some(routing) => {
+                let mut cheap_params = config
+                    .model_providers
+                    .get(&routing.cheap_provider)
+                    .cloned()
+                    .ok_or_else(|| {
+                        AgentError::ConfigError(format!(
+                            "Routing provider '{}' not found in model_providers",
+                            routing.cheap_provider
+                        ))
+                    })?;
+                if let Some(model) = &routing.cheap_model {
+                    cheap_params.model = model.clone();
+                }
+                let cheap_client = create_client(&routing.cheap_provider, &cheap_params).await?;
+                info!(
+                    cheap_model = %cheap_params.model,
+                    premium_model = %provider_config.model,
+                    "Model routing enabled"
+                );
+                Some(ModelRouter::new(
+                    routing.clone(),
+                    cheap_client,
+                    cheap_params.model,
+                    provider_config.model.clone(),
+                ))
             }
+            None => None,
         };
 
-        let all_tools_from_registry: Vec<Arc<dyn crate::tools::Tool + Send + Sync>> =
-            tool_registry.get_all_tools_arc();
-        let tool_executor = ToolExecutor::new(all_tools_from_registry);
+        // The registry may be shared with other agents; tools with state get an instance of their own.
+        let tool_executor = ToolExecutor::new(tool_registry.tools_for_run());
 
         Ok(Self {
             name: "BaseAgent".to_string(), // Specific agents can override this after creation if needed
@@ -292,9 +400,66 @@ impl BaseAgent {
             base_commit: None,
             patch_path: None, // Initialize as None
             trajectory_recorder: None,
+            router,
+            scratch_dir: None,
+            run_id: None,
+            execution: None,
+            write_guard: None,
+            cancellation: CancellationToken::new(),
+            step_history: StepHistory::default(),
+            step_budget_prompt: None,
+            step_extensions: None,
         })
     }
 
+    /// Checks that the configured model can call tools (see `llm::capabilities`), so a
+    /// misconfigured model fails before the task starts.
+    pub async fn check_capabilities(&self) -> Result<(), AgentError> {
+        let model = &self
+            .config
+            .get_current_provider_config()
+            .map_err(|e| AgentError::ConfigError(e.to_string()))?
+            .model;
+        let capabilities = capabilities::check_model(self.llm_client.as_ref(), model, self.config.probe_capabilities)
+            .await
+            .map_err(AgentError::MissingCapability)?;
+        if let Some(capabilities) = capabilities {
+            info!(
+                model = %model,
+                vision = capabilities.vision,
+                json_mode = capabilities.json_mode,
+                context_window = capabilities.context_window,
+                "Model capabilities"
+            );
+        }
+        Ok(())
+    }
+
+    /// Files and directories of the run that never belong in its patch: the scratch directory,
+    /// the patch file and the trajectory (see `git_utils::get_git_diff_excluding`).
+    pub fn diff_exclusions(&self) -> Vec<PathBuf> {
+        let mut excluded: Vec<PathBuf> = self.scratch_dir.iter().cloned().collect();
+        excluded.extend(self.patch_path.as_deref().map(PathBuf::from));
+        excluded.extend(
+            self.trajectory_recorder
+                .as_ref()
+                .map(|recorder| recorder.get_trajectory_path().to_path_buf()),
+        );
+        excluded
+    }
+
+    /// The context handed to the tool calls of the current run.
+    pub fn tool_context(&self) -> ToolContext {
+        ToolContext {
+            project_root: self.project_path.as_ref().map(PathBuf::from),
+            run_id: self.run_id.clone(),
+            path_policy: self.write_guard.clone(),
+            scratch_dir: self.scratch_dir.clone(),
+            cancellation: self.cancellation.clone(),
+            execution: self.execution.clone(),
+        }
+    }
+
     /// Sets the trajectory recorder for the agent.
     pub fn set_trajectory_recorder(&mut self, recorder: TrajectoryRecorder) {
         self.trajectory_recorder = Some(recorder);
@@ -303,6 +468,35 @@ impl BaseAgent {
     }
 }
 
+/// The error ending a run whose step `step` was interrupted.
+fn interruption_error(interruption: Interruption, step: u32) -> AgentError {
+    match interruption {
+        Interruption::TimedOut(elapsed) => AgentError::StepTimeout(step, elapsed.as_secs()),
+        Interruption::Cancelled => AgentError::Cancelled(step),
+    }
+}
+
+/// Sends one step's request to `client`, streaming if `stream` is set. A response cut off at
+/// the length limit is continued until it is complete (see `llm::continuation`).
+async fn send_llm_request(
+    client: &dyn LLMClient,
+    stream: bool,
+    messages: Vec<LLMMessage>,
+    tools: Option<Vec<ToolDefinition>>,
+    on_stream_event: &(dyn Fn(StreamEvent) + Send + Sync),
+) -> Result<LLMResponse, LLMError> {
+    let response = if stream {
+        client.chat_stream(messages.clone(), tools, None, on_stream_event).await?
+    } else {
+        client.chat(messages.clone(), tools, None).await?
+    };
+    if is_truncated(&response) {
+        complete_truncated(client, &messages, response).await
+    } else {
+        Ok(response)
+    }
+}
+
 /// The core execution loop for an agent.
 ///
 /// This function is called by a concrete `Agent` implementation (like `TraeAgent`)
@@ -332,6 +526,8 @@ pub enum StopReason {
 /// * `initial_messages`: The initial set of messages (e.g., system prompt, user task) to start the conversation.
 /// * `event_sender`: Optional sender for `AgentEvent`s to report progress.
 /// * `should_stop_fn`: Closure that takes `(&LLMResponse, current_step, max_steps)` and returns `StopReason`.
+///   Library users can implement `stop_policy::StopPolicy` and call `stop_policy::execute_with_policy`
+///   instead of passing closures.
 /// * `process_completion_fn`: Closure that takes `&LLMResponse` and returns `Option<String>` for the final result message.
 ///
 /// # Returns
@@ -365,10 +561,33 @@ where
         success: false,
         total_tokens_used: None, // Initialize as None
         error_message: None,
+        error_hint: None,
+        subagents: Vec::new(),
+        final_report: None,
     };
 
     base_agent.conversation_history = initial_messages;
     let mut current_step_number = 1;
+    let heartbeat_policy = HeartbeatPolicy::from_config(&base_agent.config);
+    let mut token_budget = base_agent.config.token_budget.clone().map(TokenBudget::new);
+    let token_budget_limit = base_agent.config.token_budget.as_ref().map(|budget| budget.max_total_tokens);
+    let mut forecaster = StepForecaster::new(base_agent.step_history.clone());
+    let mut tool_caps = base_agent
+        .config
+        .tool_caps
+        .clone()
+        .map(|config| ToolCaps::new(config).exempting(base_agent.scratch_dir.clone()));
+    // Set when a step's tool calls exceed a cap; the run stops once the step is recorded.
+    let mut cap_exceeded = false;
+    // Per-file diffstat after the last step; `None` once computing it failed (e.g., not a git
+    // repository).
+    let mut last_file_stats = base_agent.project_path.as_ref().map(|_| FileStats::new());
+    let completion_reserve = base_agent
+        .config
+        .get_current_provider_config()
+        .ok()
+        .and_then(|pc| pc.max_tokens)
+        .map_or(0, u64::from);
 
     // Record initial state if trajectory recorder is present
     // This is more like Python's start_recording which happens in TraeAgent::new_task
@@ -388,6 +607,78 @@ where
                 .await;
         }
 
+        if let Some(regrounding) = &base_agent.config.regrounding {
+            let interval = regrounding.interval_steps.max(1);
+            if current_step_number > 1 && (current_step_number - 1).is_multiple_of(interval) {
+                let template = regrounding.template.as_deref().unwrap_or(regrounding::DEFAULT_TEMPLATE);
+                let note = regrounding::note_message(
+                    template,
+                    &task_name,
+                    current_step_number - 1,
+                    &base_agent.conversation_history,
+                );
+                debug!(step = current_step_number, "Re-grounding the conversation with a state note");
+                base_agent.conversation_history.push(note);
+            }
+        }
+
+        if let Some(budget) = token_budget.as_mut() {
+            match budget.check(&base_agent.conversation_history, completion_reserve) {
+                BudgetCheck::WithinBudget => {}
+                BudgetCheck::Warning(projection) => {
+                    let message = format!("Approaching the token budget: {}", projection.describe());
+                    warn!(step = current_step_number, "{}", message);
+                    if let Some(sender) = &event_sender {
+                        _ = sender.send(AgentEvent::StatusUpdate(message)).await;
+                    }
+                }
+                BudgetCheck::Exceeded(projection) => {
+                    let budget_error =
+                        AgentError::TokenBudgetExceeded(current_step_number, projection.describe()).to_string();
+                    error!(step = current_step_number, "{}", budget_error);
+                    if let Some(sender) = &event_sender {
+                        _ = sender.send(AgentEvent::StatusUpdate(budget_error.clone())).await;
+                    }
+                    execution.error_message = Some(budget_error);
+                    break;
+                }
+            }
+        }
+
+        let tokens_used = execution.total_tokens_used.as_ref().map_or(0, |usage| u64::from(usage.total_tokens));
+        if let Some(forecast) =
+            forecaster.check(current_step_number, base_agent.max_steps, tokens_used, token_budget_limit)
+        {
+            let message = format!("Step budget: {}", forecast.describe());
+            warn!(step = current_step_number, "{}", message);
+            if let Some(sender) = &event_sender {
+                _ = sender.send(AgentEvent::StatusUpdate(message)).await;
+            }
+            let added = match base_agent.step_budget_prompt.clone() {
+                Some(prompt) => {
+                    let question = forecast.clone();
+                    tokio::task::spawn_blocking(move || prompt.extend(&question)).await.unwrap_or(0)
+                }
+                None => 0,
+            };
+            if added > 0 {
+                base_agent.max_steps += added;
+                info!(step = current_step_number, "Step budget extended by {} to {}", added, base_agent.max_steps);
+            } else {
+                let mut note = forecast.note_for_model();
+                if base_agent.step_extensions.is_some() {
+                    note.push_str(" If they cannot be enough, ask for more with the 'request_extension' tool.");
+                }
+                base_agent.conversation_history.push(LLMMessage {
+                    role: MessageRole::User,
+                    content: Some(note),
+                    name: None,
+                    tool_calls: None,
+                    tool_call_id: None,
+                });
+            }
+        }
+
         debug!(
             step = current_step_number,
             messages_count = base_agent.conversation_history.len(),
@@ -403,18 +694,117 @@ where
         }
 
         let tool_definitions = base_agent.tool_registry.get_all_tool_definitions();
-        let llm_response_result = base_agent
-            .llm_client
-            .chat(
-                base_agent.conversation_history.clone(),
-                if tool_definitions.is_empty() {
-                    None
-                } else {
-                    Some(tool_definitions)
-                },
-                None,
+        let tools = if tool_definitions.is_empty() {
+            None
+        } else {
+            Some(tool_definitions)
+        };
+        let tool_executor = &base_agent.tool_executor;
+        let tool_context = base_agent.tool_context();
+        let on_stream_event = |event: StreamEvent| {
+            let agent_event = match event {
+                StreamEvent::ToolCallStarted { name, .. } => {
+                    AgentEvent::ToolCallStreaming(current_step_number, name)
+                }
+                StreamEvent::ToolCallTarget { name, path, .. } => {
+                    let rejection = tool_executor.preflight_write(&name, &path, &tool_context).err();
+                    if let Some(reason) = &rejection {
+                        warn!(path = %path.display(), "Streaming tool call failed preflight: {}", reason);
+                    }
+                    AgentEvent::WritePreflight(current_step_number, path, rejection)
+                }
+            };
+            if let Some(sender) = &event_sender {
+                // Streaming progress is best effort; never block the response on the consumer.
+                _ = sender.try_send(agent_event);
+            }
+        };
+        let messages = base_agent.conversation_history.clone();
+        // Counted against the budget if the provider does not report usage.
+        let prompt_estimate = token_budget.as_ref().map_or(0, |budget| budget.estimate_prompt(&messages));
+        let mut route = match &base_agent.router {
+            Some(router) => {
+                let decision = router.route(current_step_number, &messages).await;
+                info!(step = current_step_number, model = %decision.model, reason = %decision.reason, "Routed step");
+                Some(decision)
+            }
+            None => None,
+        };
+        let cheap_client = match (&route, &base_agent.router) {
+            (Some(decision), Some(router)) if decision.tier == ModelTier::Cheap => {
+                Some(router.cheap_client().clone())
+            }
+            _ => None,
+        };
+        let stream = base_agent.config.stream;
+        let premium_client = &base_agent.llm_client;
+        let llm_call = async {
+            let mut cheap_error = None;
+            if let Some(client) = &cheap_client {
+                match send_llm_request(client.as_ref(), stream, messages.clone(), tools.clone(), &on_stream_event).await {
+                    Ok(response) => return (Ok(response), None),
+                    Err(e) => {
+                        warn!(step = current_step_number, "Cheap model failed, escalating to the premium model: {}", e);
+                        cheap_error = Some(e.to_string());
+                    }
+                }
+            }
+            (
+                send_llm_request(premium_client.as_ref(), stream, messages, tools, &on_stream_event).await,
+                cheap_error,
             )
-            .await;
+        };
+        // Retried requests are reported, so a slow step is not mistaken for a hung one.
+        let retry_sender = event_sender.clone();
+        let llm_call = with_retry_observer(
+            Arc::new(move |notice| {
+                if let Some(sender) = &retry_sender {
+                    _ = sender.try_send(AgentEvent::StatusUpdate(notice.to_string()));
+                }
+            }),
+            llm_call,
+        );
+        let llm_start_time = Instant::now();
+        let (llm_response_result, cheap_error) = match run_with_heartbeat(
+            llm_call,
+            current_step_number,
+            AgentActivity::WaitingForLLM,
+            step_start_time,
+            &heartbeat_policy,
+            event_sender.as_ref(),
+            &base_agent.cancellation,
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(interruption) => {
+                let timeout_error = interruption_error(interruption, current_step_number).to_string();
+                error!(step = current_step_number, "{}", timeout_error);
+                execution.error_message = Some(timeout_error.clone());
+                execution.steps.push(AgentStep {
+                    step_number: current_step_number,
+                    state: AgentState::Failed,
+                    messages_to_llm: Some(base_agent.conversation_history.clone()),
+                    llm_response: None,
+                    tool_calls_made: None,
+                    tool_results: None,
+                    reflection: None,
+                    error: Some(timeout_error),
+                    duration_ms: step_start_time.elapsed().as_millis(),
+                    route,
+                    validation_error: None,
+                    diff_stat: None,
+                    file_changes: Vec::new(),
+                    llm_duration_ms: Some(llm_start_time.elapsed().as_millis()),
+                    step_extensions: Vec::new(),
+                });
+                break;
+            }
+        };
+        if let (Some(error), Some(router)) = (&cheap_error, base_agent.router.as_mut()) {
+            router.record_failure(current_step_number);
+            route = Some(router.escalation(error));
+        }
 
         let mut agent_step = AgentStep {
             step_number: current_step_number,
@@ -426,6 +816,12 @@ where
             reflection: None, // TODO: Implement reflection if needed
             error: None,
             duration_ms: 0,
+            route,
+            validation_error: None,
+            diff_stat: None,
+            file_changes: Vec::new(),
+            llm_duration_ms: Some(llm_start_time.elapsed().as_millis()),
+            step_extensions: Vec::new(),
         };
 
         match llm_response_result {
@@ -439,6 +835,9 @@ where
                         ))
                         .await;
                 }
+                if let Some(budget) = token_budget.as_mut() {
+                    budget.record(llm_response.usage.as_ref(), prompt_estimate);
+                }
                 if let Some(new_usage) = &llm_response.usage {
                     execution.total_tokens_used = match execution.total_tokens_used.as_mut() {
                         Some(existing_usage) => {
@@ -473,6 +872,10 @@ where
                     }
                     StopReason::ValidationFailed(validation_msg) => {
                         warn!(task = %task_name, "Task completion validation failed: {}", validation_msg);
+                        if let Some(router) = base_agent.router.as_mut() {
+                            router.record_failure(current_step_number);
+                        }
+                        agent_step.validation_error = Some(validation_msg.clone());
                         base_agent.conversation_history.push(LLMMessage {
                             role: MessageRole::User,
                             content: Some(validation_msg),
@@ -534,14 +937,56 @@ where
                                 }
                             }
 
-                            let executed_tool_results = if base_agent.config.get_current_provider_config().map_or(false, |pc| pc.parallel_tool_calls) {
-                                debug!("Executing tool calls in parallel (mode)");
-                                base_agent.tool_executor.parallel_tool_calls(&tool_calls).await
-                            } else {
-                                debug!("Executing tool calls sequentially (mode)");
-                                base_agent.tool_executor.sequential_tool_calls(&tool_calls).await
+                            let parallel = base_agent.config.get_current_provider_config().is_ok_and(|pc| pc.parallel_tool_calls);
+                            let tool_execution = async {
+                                if parallel {
+                                    debug!("Executing tool calls in parallel (mode)");
+                                    base_agent.tool_executor.parallel_tool_calls(&tool_calls, &tool_context).await
+                                } else {
+                                    debug!("Executing tool calls sequentially (mode)");
+                                    base_agent.tool_executor.sequential_tool_calls(&tool_calls, &tool_context).await
+                                }
+                            };
+                            let executed_tool_results = match run_with_heartbeat(
+                                tool_execution,
+                                current_step_number,
+                                AgentActivity::RunningTools,
+                                step_start_time,
+                                &heartbeat_policy,
+                                event_sender.as_ref(),
+                                &base_agent.cancellation,
+                            )
+                            .await
+                            {
+                                Ok(results) => results,
+                                Err(interruption) => {
+                                    let timeout_error = interruption_error(interruption, current_step_number).to_string();
+                                    error!(step = current_step_number, "{}", timeout_error);
+                                    // Every tool call needs an answer for the conversation to be
+                                    // continued, as an interrupted interactive turn is.
+                                    for tool_call in &tool_calls {
+                                        base_agent.conversation_history.push(LLMMessage {
+                                            tool_call_id: Some(tool_call.id.clone()),
+                                            role: MessageRole::Tool,
+                                            name: Some(tool_call.function.name.clone()),
+                                            content: Some(format!("Not completed: {}", timeout_error)),
+                                            tool_calls: None,
+                                        });
+                                    }
+                                    agent_step.state = AgentState::Failed;
+                                    agent_step.error = Some(timeout_error.clone());
+                                    execution.error_message = Some(timeout_error);
+                                    agent_step.duration_ms = step_start_time.elapsed().as_millis();
+                                    execution.steps.push(agent_step);
+                                    break;
+                                }
                             };
                             agent_step.tool_results = Some(executed_tool_results.clone());
+                            if executed_tool_results.iter().any(|r| !r.success) {
+                                if let Some(router) = base_agent.router.as_mut() {
+                                    router.record_failure(current_step_number);
+                                }
+                            }
                             if let Some(sender) = &event_sender {
                                 for tres in &executed_tool_results {
                                     _ = sender
@@ -571,6 +1016,71 @@ where
                                 });
                             }
                             agent_step.state = AgentState::ProcessingToolResult;
+
+                            let extensions = base_agent.step_extensions.as_ref().map(|e| e.take_decisions());
+                            for extension in extensions.into_iter().flatten() {
+                                base_agent.max_steps += extension.granted;
+                                let message = format!(
+                                    "Step extension: {} of {} requested steps granted; the budget is now {} steps. Justification: {}",
+                                    extension.granted, extension.requested, base_agent.max_steps, extension.justification
+                                );
+                                info!(step = current_step_number, "{}", message);
+                                if let Some(sender) = &event_sender {
+                                    _ = sender.send(AgentEvent::StatusUpdate(message)).await;
+                                }
+                                agent_step.step_extensions.push(extension);
+                            }
+
+                            let cap_check = tool_caps.as_mut().map_or(CapCheck::WithinCaps, |caps| {
+                                caps.record(&tool_calls, agent_step.tool_results.as_deref().unwrap_or_default())
+                            });
+                            match cap_check {
+                                CapCheck::WithinCaps => {}
+                                CapCheck::Warning(message) => {
+                                    warn!(step = current_step_number, "{}", message);
+                                    if let Some(sender) = &event_sender {
+                                        _ = sender.send(AgentEvent::StatusUpdate(message.clone())).await;
+                                    }
+                                    base_agent.conversation_history.push(LLMMessage {
+                                        role: MessageRole::User,
+                                        content: Some(message),
+                                        name: None,
+                                        tool_calls: None,
+                                        tool_call_id: None,
+                                    });
+                                }
+                                CapCheck::Exceeded(usage) => {
+                                    let cap_error = AgentError::ToolCapExceeded(current_step_number, usage).to_string();
+                                    error!(step = current_step_number, "{}", cap_error);
+                                    if let Some(sender) = &event_sender {
+                                        _ = sender.send(AgentEvent::StatusUpdate(cap_error.clone())).await;
+                                    }
+                                    agent_step.state = AgentState::Failed;
+                                    agent_step.error = Some(cap_error.clone());
+                                    execution.error_message = Some(cap_error);
+                                    cap_exceeded = true;
+                                }
+                            }
+
+                            if let (Some(previous), Some(project_path)) = (&last_file_stats, &base_agent.project_path) {
+                                match file_diff_stats(project_path, base_agent.base_commit.as_deref()) {
+                                    Ok(files) if &files != previous => {
+                                        let stat = DiffStat::total(&files);
+                                        debug!(step = current_step_number, "Changes so far: {}", stat);
+                                        agent_step.file_changes = step_changes(previous, &files);
+                                        agent_step.diff_stat = Some(stat);
+                                        last_file_stats = Some(files);
+                                        if let Some(sender) = &event_sender {
+                                            _ = sender.send(AgentEvent::DiffStat(current_step_number, stat)).await;
+                                        }
+                                    }
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        debug!("Not tracking diff statistics: {}", e);
+                                        last_file_stats = None;
+                                    }
+                                }
+                            }
                         } else {
                             // No tool calls, and StopReason was Continue.
                             // This is where Python sends "It seems that you have not completed the task."
@@ -610,6 +1120,7 @@ where
             agent_step.state = AgentState::Failed;
                 agent_step.error = Some(e.to_string());
                 execution.error_message = Some(e.to_string());
+                execution.error_hint = e.hint().map(str::to_string);
                 execution.success = false;
                 agent_step.duration_ms = step_start_time.elapsed().as_millis();
                 execution.steps.push(agent_step);
@@ -625,9 +1136,14 @@ where
             recorder.record_agent_step(agent_step.clone());
         }
         execution.steps.push(agent_step);
+        if cap_exceeded {
+            break;
+        }
         current_step_number += 1;
     }
 
+    execution.final_report = FinalReport::from_steps(&execution.steps).map(Box::new);
+
     // Finalize trajectory recording
     if let Some(recorder) = base_agent.trajectory_recorder.as_mut() {
         let _ = recorder.finalize_recording(
@@ -0,0 +1,334 @@
+//! # Agent Builder
+//!
+//! Assembles a custom agent from parts — system prompt, toolset, step limit, stop condition and
+//! completion extractor — on top of `BaseAgent` and `common_execute_task_loop`, for library
+//! users who do not want to implement the whole `Agent` trait.
+//!
+//! ```ignore
+//! let mut agent = AgentBuilder::new("docs-writer", config)
+//!     .system_prompt("You write documentation for the project.")
+//!     .tools(registry)
+//!     .max_steps(10)
+//!     .build()
+//!     .await?;
+//! agent.new_task("Document the public API".to_string(), TaskSpec::default()).await?;
+//! let execution = agent.execute_task(None).await?;
+//! ```
+
+use super::base_agent::{common_execute_task_loop, Agent, AgentError, AgentEvent, AgentExecution, BaseAgent, StopReason};
+use super::stop_policy::{stop_reason, StopPolicy};
+use super::task_spec::TaskSpec;
+use crate::config::Config;
+use crate::llm::base_client::{LLMMessage, LLMResponse, MessageRole};
+use crate::llm::LLMClient;
+use crate::tools::ToolRegistry;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Decides after each LLM response whether the run is over: `(response, step, max_steps)`.
+pub type StopCondition = Box<dyn Fn(&LLMResponse, u32, u32) -> StopReason + Send + Sync>;
+
+/// Extracts the final result from the LLM response that completed the task.
+pub type CompletionExtractor = Box<dyn Fn(&LLMResponse) -> Option<String> + Send + Sync>;
+
+/// Builds a `CustomAgent`. Everything but the name and configuration is optional.
+pub struct AgentBuilder {
+    name: String,
+    config: Arc<Config>,
+    system_prompt: String,
+    tools: Option<Arc<ToolRegistry>>,
+    llm_client: Option<Arc<dyn LLMClient>>,
+    max_steps: Option<u32>,
+    stop_condition: Option<StopCondition>,
+    completion_extractor: Option<CompletionExtractor>,
+}
+
+impl AgentBuilder {
+    /// Starts a builder for an agent named `name`, using the default provider of `config`.
+    pub fn new(name: impl Into<String>, config: Arc<Config>) -> Self {
+        Self {
+            name: name.into(),
+            config,
+            system_prompt: "You are a helpful AI agent. Use the provided tools to complete the task, \
+                and call 'task_done' when it is complete."
+                .to_string(),
+            tools: None,
+            llm_client: None,
+            max_steps: None,
+            stop_condition: None,
+            completion_extractor: None,
+        }
+    }
+
+    /// Sets the system prompt sent at the start of every task.
+    pub fn system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = prompt.into();
+        self
+    }
+
+    /// Sets the tools the agent may call (default: the standard `ToolRegistry`).
+    pub fn tools(mut self, registry: Arc<ToolRegistry>) -> Self {
+        self.tools = Some(registry);
+        self
+    }
+
+    /// Uses `client` instead of a client for the default provider of the configuration.
+    pub fn llm_client(mut self, client: Arc<dyn LLMClient>) -> Self {
+        self.llm_client = Some(client);
+        self
+    }
+
+    /// Sets the maximum number of steps per task (default: `Config::max_steps`).
+    pub fn max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Sets when the run is over (default: `default_stop_condition`).
+    pub fn stop_condition(
+        mut self,
+        condition: impl Fn(&LLMResponse, u32, u32) -> StopReason + Send + Sync + 'static,
+    ) -> Self {
+        self.stop_condition = Some(Box::new(condition));
+        self
+    }
+
+    /// Sets how the final result is taken from the last response (default:
+    /// `default_completion_extractor`).
+    pub fn completion_extractor(
+        mut self,
+        extractor: impl Fn(&LLMResponse) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.completion_extractor = Some(Box::new(extractor));
+        self
+    }
+
+    /// Sets both the stop condition and the completion extractor from `policy`, with its
+    /// validation.
+    pub fn stop_policy(mut self, policy: impl StopPolicy + 'static) -> Self {
+        let policy = Arc::new(policy);
+        let extractor = Arc::clone(&policy);
+        self.stop_condition = Some(Box::new(move |response, step, max_steps| {
+            stop_reason(policy.as_ref(), response, step, max_steps)
+        }));
+        self.completion_extractor = Some(Box::new(move |response| extractor.final_result(response)));
+        self
+    }
+
+    /// Creates the agent and its LLM client.
+    pub async fn build(self) -> Result<CustomAgent, AgentError> {
+        let tools = self.tools.unwrap_or_else(|| Arc::new(ToolRegistry::default()));
+        let mut base_agent = BaseAgent::try_new(self.config, tools).await?;
+        base_agent.name = self.name.clone();
+        if let Some(client) = self.llm_client {
+            base_agent.llm_client = client;
+        }
+        base_agent.check_capabilities().await?;
+        if let Some(max_steps) = self.max_steps {
+            base_agent.max_steps = max_steps;
+        }
+        Ok(CustomAgent {
+            name: self.name,
+            base_agent,
+            system_prompt: self.system_prompt,
+            stop_condition: self.stop_condition.unwrap_or_else(|| Box::new(default_stop_condition)),
+            completion_extractor: self
+                .completion_extractor
+                .unwrap_or_else(|| Box::new(default_completion_extractor)),
+        })
+    }
+}
+
+/// Stops at the step limit, when `task_done` is called, or when the model answers without
+/// calling any tool.
+pub fn default_stop_condition(response: &LLMResponse, step: u32, max_steps: u32) -> StopReason {
+    let Some(choice) = response.choices.first() else {
+        return StopReason::Continue;
+    };
+    match &choice.message.tool_calls {
+        Some(calls) if calls.iter().any(|c| c.function.name == "task_done") => StopReason::TaskCompleted,
+        Some(calls) if !calls.is_empty() => {
+            if step >= max_steps {
+                StopReason::MaxStepsReached
+            } else {
+                StopReason::Continue
+            }
+        }
+        _ => StopReason::TaskCompleted,
+    }
+}
+
+/// The `task_done` summary if there is one, otherwise the text of the response.
+pub fn default_completion_extractor(response: &LLMResponse) -> Option<String> {
+    let message = &response.choices.first()?.message;
+    let summary = message
+        .tool_calls
+        .iter()
+        .flatten()
+        .filter(|c| c.function.name == "task_done")
+        .find_map(|c| {
+            let args: Value = serde_json::from_str(&c.function.arguments).ok()?;
+            args.get("summary").and_then(Value::as_str).map(str::to_string)
+        });
+    summary.or_else(|| message.content.clone())
+}
+
+/// An agent assembled with `AgentBuilder`.
+pub struct CustomAgent {
+    name: String,
+    base_agent: BaseAgent,
+    system_prompt: String,
+    stop_condition: StopCondition,
+    completion_extractor: CompletionExtractor,
+}
+
+impl CustomAgent {
+    fn start_messages(&mut self, task: &str) {
+        let mut user_message = task.to_string();
+        if let Some(project_path) = &self.base_agent.project_path {
+            user_message.push_str(&format!("\n\n[Project root path]: {}", project_path));
+        }
+        self.base_agent.conversation_history = vec![
+            text_message(MessageRole::System, self.system_prompt.clone()),
+            text_message(MessageRole::User, user_message),
+        ];
+    }
+}
+
+fn text_message(role: MessageRole, content: String) -> LLMMessage {
+    LLMMessage {
+        role,
+        content: Some(content),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
+
+#[async_trait]
+impl Agent for CustomAgent {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Sets up `task`; of the `spec`, only `project_path` is used.
+    async fn new_task(&mut self, task: String, spec: TaskSpec) -> Result<(), AgentError> {
+        if let Some(path) = spec.project_path {
+            self.base_agent.project_path = Some(path);
+        }
+        self.start_messages(&task);
+        self.base_agent.current_task = Some(task);
+        Ok(())
+    }
+
+    async fn execute_task(
+        &mut self,
+        event_sender: Option<mpsc::Sender<AgentEvent>>,
+    ) -> Result<AgentExecution, AgentError> {
+        if self.base_agent.current_task.is_none() {
+            return Err(AgentError::TaskSetupFailed(
+                "No task has been set. Call new_task first.".to_string(),
+            ));
+        }
+        let messages = self.base_agent.conversation_history.clone();
+        common_execute_task_loop(
+            &mut self.base_agent,
+            messages,
+            event_sender,
+            &self.stop_condition,
+            &self.completion_extractor,
+        )
+        .await
+    }
+
+    /// Runs the task with the history of the previous turns, and returns the new messages.
+    async fn execute_interactive_turn(
+        &mut self,
+        event_sender: Option<mpsc::Sender<AgentEvent>>,
+    ) -> Result<Vec<LLMMessage>, AgentError> {
+        let before = self.base_agent.conversation_history.len();
+        self.execute_task(event_sender).await?;
+        Ok(self.base_agent.conversation_history.get(before..).unwrap_or_default().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModelParameters;
+    use crate::llm::OpenAIClient;
+    use crate::tools::TaskDoneTool;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_built_agent_runs_with_its_own_prompt_and_stop_condition() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "gpt-test",
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": {"name": "task_done", "arguments": "{\"summary\": \"Wrote the docs\"}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }]
+            })))
+            .mount(&server)
+            .await;
+        let config: Config = serde_json::from_value(json!({
+            "default_provider": "openai",
+            "max_steps": 30,
+            "probe_capabilities": false,
+            "model_providers": {
+                "openai": {"model": "gpt-test", "api_key": "key"}
+            }
+        }))
+        .unwrap();
+        let params: ModelParameters = serde_json::from_value(json!({"model": "gpt-test"})).unwrap();
+        let client = OpenAIClient::new(Some("key".to_string()), Some(server.uri()), params)
+            .await
+            .unwrap();
+        let mut registry = ToolRegistry::new();
+        registry.register(TaskDoneTool::new());
+
+        let mut agent = AgentBuilder::new("docs-writer", Arc::new(config))
+            .system_prompt("You write documentation.")
+            .tools(Arc::new(registry))
+            .llm_client(Arc::new(client))
+            .max_steps(3)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(agent.get_name(), "docs-writer");
+        assert_eq!(agent.base_agent.max_steps, 3);
+
+        agent
+            .new_task("Document the API".to_string(), TaskSpec::in_project("/repo"))
+            .await
+            .unwrap();
+        let execution = agent.execute_task(None).await.unwrap();
+        assert!(execution.success, "{:?}", execution.error_message);
+        assert_eq!(execution.steps.len(), 1);
+        assert_eq!(execution.final_result.as_deref(), Some("Wrote the docs"));
+
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["messages"][0]["content"], "You write documentation.");
+        assert_eq!(body["messages"][1]["content"], "Document the API\n\n[Project root path]: /repo");
+    }
+}
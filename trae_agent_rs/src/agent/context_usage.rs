@@ -0,0 +1,176 @@
+//! # Context Usage
+//!
+//! Breaks the conversation sent to the model down by what fills it — the system prompt, the
+//! task instructions, the history by role, tool calls and tool results — with estimated token
+//! counts, for the interactive `/context` command and `trae run --show-context-usage`. Sizes
+//! are estimated like the token budget does (`token_budget::estimate_tokens`).
+
+use super::token_budget::estimate_tokens;
+use crate::config::TokenBudgetConfig;
+use crate::llm::base_client::{LLMMessage, MessageRole};
+use crate::utils::usage::group_thousands;
+use serde::Serialize;
+
+/// Width of the longest bar of the histogram, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// One category of the conversation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContextCategory {
+    pub name: &'static str,
+    pub messages: usize,
+    pub tokens: u64,
+}
+
+/// Estimated context consumption of a conversation, by category.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ContextUsage {
+    /// Categories in conversation order; empty ones are left out.
+    pub categories: Vec<ContextCategory>,
+    pub total_tokens: u64,
+}
+
+impl ContextUsage {
+    /// Measures `messages` with the characters-per-token ratios of `budget`, or the defaults.
+    pub fn measure(messages: &[LLMMessage], budget: Option<&TokenBudgetConfig>) -> Self {
+        let (chars_per_token, tool_chars_per_token) = budget.map_or(
+            (crate::config::default_chars_per_token(), crate::config::default_chars_per_token()),
+            |b| (b.chars_per_token, b.tool_result_chars_per_token),
+        );
+        let names = [
+            "System prompt",
+            "Instructions",
+            "User messages",
+            "Assistant messages",
+            "Tool calls",
+            "Tool results",
+        ];
+        let mut counts = [(0usize, 0usize); 6];
+        let first_user = messages.iter().position(|m| m.role == MessageRole::User);
+        for (i, message) in messages.iter().enumerate() {
+            let text = message.content.as_deref().map_or(0, |c| c.chars().count());
+            let index = match message.role {
+                MessageRole::System if i == 0 => 0,
+                // Later system messages are notes the agent adds, such as re-grounding.
+                MessageRole::System => 1,
+                MessageRole::User if Some(i) == first_user => 1,
+                MessageRole::User => 2,
+                MessageRole::Assistant => 3,
+                MessageRole::Tool => 5,
+            };
+            counts[index].0 += 1;
+            counts[index].1 += text;
+            if let Some(calls) = &message.tool_calls {
+                counts[4].0 += calls.len();
+                counts[4].1 += calls
+                    .iter()
+                    .map(|c| c.function.name.len() + c.function.arguments.chars().count())
+                    .sum::<usize>();
+            }
+        }
+        let categories: Vec<ContextCategory> = names
+            .iter()
+            .zip(counts)
+            .enumerate()
+            .filter(|(_, (_, (messages, _)))| *messages > 0)
+            .map(|(i, (name, (messages, chars)))| ContextCategory {
+                name,
+                messages,
+                tokens: estimate_tokens(chars, if i == 5 { tool_chars_per_token } else { chars_per_token }),
+            })
+            .collect();
+        let total_tokens = categories.iter().map(|c| c.tokens).sum();
+        Self { categories, total_tokens }
+    }
+
+    /// Formats the breakdown as a histogram, one line per category.
+    pub fn format(&self) -> String {
+        if self.categories.is_empty() {
+            return "The conversation is empty.".to_string();
+        }
+        let largest = self.categories.iter().map(|c| c.tokens).max().unwrap_or(0).max(1);
+        let mut lines: Vec<String> = self
+            .categories
+            .iter()
+            .map(|c| {
+                let bar = (c.tokens as usize * BAR_WIDTH).div_ceil(largest as usize);
+                let share = c.tokens as f64 * 100.0 / self.total_tokens.max(1) as f64;
+                format!(
+                    "  {:<19} {:>8} tokens {:>5.1}%  {:<width$}  ({} message{})",
+                    c.name,
+                    group_thousands(c.tokens),
+                    share,
+                    "#".repeat(bar),
+                    c.messages,
+                    if c.messages == 1 { "" } else { "s" },
+                    width = BAR_WIDTH
+                )
+            })
+            .collect();
+        lines.push(format!("  Total: ~{} tokens (estimated)", group_thousands(self.total_tokens)));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::{ToolCall, ToolCallFunction};
+
+    fn message(role: MessageRole, content: &str) -> LLMMessage {
+        LLMMessage {
+            role,
+            content: Some(content.to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    #[test]
+    fn test_conversation_is_broken_down_by_category() {
+        let mut call = message(MessageRole::Assistant, "Let me look.");
+        call.tool_calls = Some(vec![ToolCall {
+            id: "call_1".to_string(),
+            tool_type: "function".to_string(),
+            function: ToolCallFunction {
+                name: "bash".to_string(),
+                arguments: "{\"command\": \"ls\"}".to_string(),
+            },
+        }]);
+        let messages = vec![
+            message(MessageRole::System, &"s".repeat(400)),
+            message(MessageRole::User, &"t".repeat(80)),
+            call,
+            message(MessageRole::Tool, &"r".repeat(2000)),
+            message(MessageRole::User, "thanks"),
+        ];
+
+        let usage = ContextUsage::measure(&messages, None);
+        let summary: Vec<(&str, usize, u64)> =
+            usage.categories.iter().map(|c| (c.name, c.messages, c.tokens)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("System prompt", 1, 100),
+                ("Instructions", 1, 20),
+                ("User messages", 1, 2),
+                ("Assistant messages", 1, 3),
+                ("Tool calls", 1, 6),
+                ("Tool results", 1, 500),
+            ]
+        );
+        assert_eq!(usage.total_tokens, 631);
+
+        let budget: TokenBudgetConfig =
+            serde_json::from_value(serde_json::json!({"max_total_tokens": 1000, "tool_result_chars_per_token": 2.0}))
+                .unwrap();
+        assert_eq!(ContextUsage::measure(&messages, Some(&budget)).categories[5].tokens, 1000);
+
+        let text = usage.format();
+        assert!(text.contains("Tool results"));
+        assert!(text.contains(&format!("{}  (1 message)", "#".repeat(BAR_WIDTH))));
+        assert!(text.ends_with("Total: ~631 tokens (estimated)"));
+        assert_eq!(ContextUsage::measure(&[], None).format(), "The conversation is empty.");
+    }
+}
@@ -0,0 +1,240 @@
+//! # Heartbeat
+//!
+//! Watches long-running phases of an agent step (waiting for the LLM, running tools) and
+//! emits periodic `AgentEvent::Heartbeat` events so front-ends can show that the agent is
+//! still alive. Steps that run past a warning threshold are flagged as possibly stuck, and an
+//! optional timeout aborts the step altogether; so does cancelling the run's cancellation
+//! token (Ctrl-C in interactive sessions).
+
+use super::base_agent::AgentEvent;
+use crate::config::Config;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Why a watched phase was stopped before it finished.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interruption {
+    /// The step ran past `HeartbeatPolicy::abort_after`; holds the step's running time.
+    TimedOut(Duration),
+    /// The cancellation token was cancelled.
+    Cancelled,
+}
+
+/// What the agent is doing while a heartbeat is emitted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AgentActivity {
+    /// Waiting for the LLM to answer.
+    WaitingForLLM,
+    /// Executing the tool calls requested by the LLM.
+    RunningTools,
+}
+
+impl std::fmt::Display for AgentActivity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentActivity::WaitingForLLM => write!(f, "waiting for LLM"),
+            AgentActivity::RunningTools => write!(f, "running tools"),
+        }
+    }
+}
+
+/// Payload of an `AgentEvent::Heartbeat`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    /// The step that is still running.
+    pub step: u32,
+    /// What the step is currently doing.
+    pub activity: AgentActivity,
+    /// Time since the step started.
+    pub elapsed: Duration,
+    /// Whether the step has exceeded the stuck-step warning threshold.
+    pub stuck: bool,
+}
+
+/// Timing thresholds for heartbeats, stuck-step warnings and step timeouts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeartbeatPolicy {
+    /// How often a heartbeat is emitted while a phase is still running.
+    pub interval: Duration,
+    /// Step duration after which heartbeats are flagged as `stuck` and a warning is logged.
+    pub stuck_after: Duration,
+    /// Step duration after which the step is aborted. `None` never aborts.
+    pub abort_after: Option<Duration>,
+}
+
+impl HeartbeatPolicy {
+    /// Builds the policy from the heartbeat settings in `config`.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            interval: Duration::from_secs(config.heartbeat_interval_secs.max(1)),
+            stuck_after: Duration::from_secs(config.stuck_step_warning_secs),
+            abort_after: config.step_timeout_secs.map(Duration::from_secs),
+        }
+    }
+}
+
+/// Formats a duration as e.g. `4m 05s` or `42s` for console output.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Drives `future` to completion while emitting heartbeats for `step`.
+///
+/// # Arguments
+/// * `future`: The phase being watched (an LLM call or a batch of tool calls).
+/// * `step`: The current step number.
+/// * `activity`: What `future` is doing, reported in heartbeats.
+/// * `step_started`: When the step began; thresholds apply to the whole step, not the phase.
+/// * `policy`: Heartbeat timing.
+/// * `event_sender`: Where heartbeats are sent, if anywhere.
+/// * `cancel`: Stops the phase when cancelled.
+///
+/// # Returns
+/// The future's output, or why it was interrupted: the step exceeded `policy.abort_after` or
+/// `cancel` was cancelled. An interrupted `future` is dropped (cancelling any in-flight
+/// request and killing running commands).
+pub async fn run_with_heartbeat<F: Future>(
+    future: F,
+    step: u32,
+    activity: AgentActivity,
+    step_started: Instant,
+    policy: &HeartbeatPolicy,
+    event_sender: Option<&mpsc::Sender<AgentEvent>>,
+    cancel: &CancellationToken,
+) -> Result<F::Output, Interruption> {
+    tokio::pin!(future);
+    let mut ticker = tokio::time::interval_at(Instant::now() + policy.interval, policy.interval);
+    let abort = async {
+        match policy.abort_after {
+            Some(limit) => tokio::time::sleep(limit.saturating_sub(step_started.elapsed())).await,
+            None => std::future::pending::<()>().await,
+        }
+    };
+    tokio::pin!(abort);
+    let mut warned = false;
+
+    loop {
+        tokio::select! {
+            output = &mut future => return Ok(output),
+            _ = &mut abort => {
+                let elapsed = step_started.elapsed();
+                warn!(step, "Step aborted after {} while {}", format_elapsed(elapsed), activity);
+                return Err(Interruption::TimedOut(elapsed));
+            }
+            _ = cancel.cancelled() => {
+                warn!(step, "Step cancelled while {}", activity);
+                return Err(Interruption::Cancelled);
+            }
+            _ = ticker.tick() => {
+                let elapsed = step_started.elapsed();
+                let stuck = elapsed >= policy.stuck_after;
+                if stuck && !warned {
+                    warn!(step, "Step has been running for {} ({})", format_elapsed(elapsed), activity);
+                    warned = true;
+                }
+                if let Some(sender) = event_sender {
+                    // Heartbeats are best-effort; never block the step on a slow consumer.
+                    let _ = sender.try_send(AgentEvent::Heartbeat(Heartbeat {
+                        step,
+                        activity,
+                        elapsed,
+                        stuck,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(abort_after: Option<Duration>) -> HeartbeatPolicy {
+        HeartbeatPolicy {
+            interval: Duration::from_secs(30),
+            stuck_after: Duration::from_secs(60),
+            abort_after,
+        }
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_secs(42)), "42s");
+        assert_eq!(format_elapsed(Duration::from_secs(245)), "4m 05s");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeats_flag_stuck_steps() {
+        let (tx, mut rx) = mpsc::channel(10);
+        let started = Instant::now();
+        let result = run_with_heartbeat(
+            async {
+                tokio::time::sleep(Duration::from_secs(95)).await;
+                7
+            },
+            3,
+            AgentActivity::WaitingForLLM,
+            started,
+            &policy(None),
+            Some(&tx),
+            &CancellationToken::new(),
+        )
+        .await;
+        assert_eq!(result, Ok(7));
+
+        let mut beats = Vec::new();
+        while let Ok(AgentEvent::Heartbeat(beat)) = rx.try_recv() {
+            beats.push(beat);
+        }
+        assert_eq!(beats.len(), 3);
+        assert_eq!(beats[0].step, 3);
+        assert!(!beats[0].stuck);
+        assert!(beats[1].stuck);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_step_is_aborted_after_timeout() {
+        let result = run_with_heartbeat(
+            std::future::pending::<()>(),
+            1,
+            AgentActivity::RunningTools,
+            Instant::now(),
+            &policy(Some(Duration::from_secs(120))),
+            None,
+            &CancellationToken::new(),
+        )
+        .await;
+        assert_eq!(result, Err(Interruption::TimedOut(Duration::from_secs(120))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_step_is_stopped_when_cancelled() {
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            canceller.cancel();
+        });
+        let result = run_with_heartbeat(
+            std::future::pending::<()>(),
+            1,
+            AgentActivity::RunningTools,
+            Instant::now(),
+            &policy(Some(Duration::from_secs(120))),
+            None,
+            &cancel,
+        )
+        .await;
+        assert_eq!(result, Err(Interruption::Cancelled));
+    }
+}
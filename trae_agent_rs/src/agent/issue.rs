@@ -0,0 +1,268 @@
+//! # Issues
+//!
+//! An `Issue` is the problem a task resolves, with the metadata issue trackers keep apart from
+//! the description: title, reproduction steps, environment, linked files, URL and labels. It is
+//! rendered into the first user message by `Issue::render`, and its title and URL are recorded
+//! in the trajectory for triage, PR descriptions and evaluation bookkeeping.
+//!
+//! Issues are given with `trae run --issue <file>`, as JSON or as Markdown with the usual
+//! sections of an issue template (`## Steps to reproduce`, `## Environment`, `## Linked files`).
+//! In task arguments, a plain string is read as an issue with only a body, as before.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// An issue to resolve.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "IssueRepr")]
+pub struct Issue {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The description of the issue.
+    #[serde(default)]
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub repro_steps: Vec<String>,
+    /// Versions, platform and configuration the issue occurs with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// Files the issue is known to concern, relative to the project root.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub linked_files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+}
+
+/// The accepted forms of an issue: free text, or the fields of `Issue`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IssueRepr {
+    Text(String),
+    Fields {
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        body: String,
+        #[serde(default)]
+        repro_steps: Vec<String>,
+        #[serde(default)]
+        environment: Option<String>,
+        #[serde(default)]
+        linked_files: Vec<String>,
+        #[serde(default)]
+        url: Option<String>,
+        #[serde(default)]
+        labels: Vec<String>,
+    },
+}
+
+impl From<IssueRepr> for Issue {
+    fn from(repr: IssueRepr) -> Self {
+        match repr {
+            IssueRepr::Text(body) => Issue {
+                body,
+                ..Issue::default()
+            },
+            IssueRepr::Fields {
+                title,
+                body,
+                repro_steps,
+                environment,
+                linked_files,
+                url,
+                labels,
+            } => Issue {
+                title,
+                body,
+                repro_steps,
+                environment,
+                linked_files,
+                url,
+                labels,
+            },
+        }
+    }
+}
+
+/// Markdown section headings, compared in lowercase without `#`s.
+const REPRO_HEADINGS: &[&str] = &["steps to reproduce", "reproduction steps", "reproduction", "to reproduce"];
+const ENVIRONMENT_HEADINGS: &[&str] = &["environment", "versions"];
+const FILES_HEADINGS: &[&str] = &["linked files", "relevant files", "files"];
+
+impl Issue {
+    /// Reads an issue file: JSON if its name ends in `.json`, Markdown otherwise.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read issue file {}", path.display()))?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).with_context(|| format!("Invalid issue file {}", path.display()))
+        } else {
+            Ok(Self::from_markdown(&text))
+        }
+    }
+
+    /// Parses an issue written in Markdown. A leading `# Heading` or `Title:` line is the title;
+    /// list items under reproduction and linked-files headings become steps and files; other
+    /// sections stay in the body.
+    pub fn from_markdown(text: &str) -> Self {
+        let mut issue = Issue::default();
+        let mut lines = text.trim().lines().peekable();
+        if let Some(first) = lines.peek() {
+            let title = first
+                .strip_prefix("# ")
+                .or_else(|| first.strip_prefix("Title:"))
+                .map(|t| t.trim().to_string());
+            if title.is_some() {
+                issue.title = title;
+                lines.next();
+            }
+        }
+
+        enum Section {
+            Body,
+            Repro,
+            Environment,
+            Files,
+        }
+        let mut section = Section::Body;
+        let mut body = Vec::new();
+        let mut environment = Vec::new();
+        for line in lines {
+            if line.starts_with("##") {
+                let heading = line.trim_start_matches('#').trim().trim_end_matches(':').to_lowercase();
+                section = if REPRO_HEADINGS.contains(&heading.as_str()) {
+                    Section::Repro
+                } else if ENVIRONMENT_HEADINGS.contains(&heading.as_str()) {
+                    Section::Environment
+                } else if FILES_HEADINGS.contains(&heading.as_str()) {
+                    Section::Files
+                } else {
+                    body.push(line);
+                    Section::Body
+                };
+                continue;
+            }
+            match section {
+                Section::Body => body.push(line),
+                Section::Environment => environment.push(line),
+                Section::Repro | Section::Files => {
+                    let Some(item) = list_item(line) else {
+                        continue;
+                    };
+                    if matches!(section, Section::Repro) {
+                        issue.repro_steps.push(item.to_string());
+                    } else {
+                        issue.linked_files.push(item.trim_matches('`').to_string());
+                    }
+                }
+            }
+        }
+        issue.body = body.join("\n").trim().to_string();
+        let environment = environment.join("\n").trim().to_string();
+        issue.environment = (!environment.is_empty()).then_some(environment);
+        issue
+    }
+
+    /// A one-line description, used as the task when the issue is given without one.
+    pub fn summary(&self) -> String {
+        match &self.title {
+            Some(title) => format!("Resolve the issue: {}", title),
+            None => self.body.lines().next().unwrap_or_default().trim().to_string(),
+        }
+    }
+
+    /// The issue as presented in the prompt. An issue with only a body is its body.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if let Some(title) = &self.title {
+            out.push_str(&format!("Title: {}\n", title));
+        }
+        if let Some(url) = &self.url {
+            out.push_str(&format!("URL: {}\n", url));
+        }
+        if !self.labels.is_empty() {
+            out.push_str(&format!("Labels: {}\n", self.labels.join(", ")));
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(self.body.trim_end());
+        if !self.repro_steps.is_empty() {
+            out.push_str("\n\nSteps to reproduce:");
+            for (i, step) in self.repro_steps.iter().enumerate() {
+                out.push_str(&format!("\n{}. {}", i + 1, step));
+            }
+        }
+        if let Some(environment) = &self.environment {
+            out.push_str(&format!("\n\nEnvironment:\n{}", environment.trim_end()));
+        }
+        if !self.linked_files.is_empty() {
+            out.push_str("\n\nLinked files:");
+            for file in &self.linked_files {
+                out.push_str(&format!("\n- {}", file));
+            }
+        }
+        out.trim().to_string()
+    }
+}
+
+/// The text of a Markdown list item (`- `, `* ` or `1. `), if `line` is one.
+fn list_item(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) {
+        return Some(item.trim());
+    }
+    let (number, item) = line.split_once(". ")?;
+    (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit())).then(|| item.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_issue_from_markdown_json_and_text() {
+        let markdown = "# Pagination skips the last page\n\
+            The last page of results is never returned.\n\n\
+            ## Steps to reproduce\n\
+            1. Create 21 items\n\
+            2. Request page 3 with a page size of 10\n\n\
+            ## Environment\n\
+            v2.3.1 on Linux\n\n\
+            ## Linked files\n\
+            - `src/pagination.rs`\n";
+        let issue = Issue::from_markdown(markdown);
+        assert_eq!(issue.title.as_deref(), Some("Pagination skips the last page"));
+        assert_eq!(issue.body, "The last page of results is never returned.");
+        assert_eq!(issue.repro_steps, vec!["Create 21 items", "Request page 3 with a page size of 10"]);
+        assert_eq!(issue.environment.as_deref(), Some("v2.3.1 on Linux"));
+        assert_eq!(issue.linked_files, vec!["src/pagination.rs"]);
+        assert_eq!(issue.summary(), "Resolve the issue: Pagination skips the last page");
+        assert_eq!(
+            issue.render(),
+            "Title: Pagination skips the last page\n\n\
+            The last page of results is never returned.\n\n\
+            Steps to reproduce:\n1. Create 21 items\n2. Request page 3 with a page size of 10\n\n\
+            Environment:\nv2.3.1 on Linux\n\n\
+            Linked files:\n- src/pagination.rs"
+        );
+
+        let from_json: Issue = serde_json::from_value(json!({
+            "title": "Crash on empty input",
+            "body": "It panics.",
+            "url": "https://example.com/issues/7",
+            "labels": ["bug"]
+        }))
+        .unwrap();
+        assert!(from_json.render().starts_with("Title: Crash on empty input\nURL: https://example.com/issues/7\nLabels: bug\n\nIt panics."));
+
+        // Free text, as task arguments gave it so far, is rendered unchanged.
+        let text: Issue = serde_json::from_value(json!("Specific bug description here.")).unwrap();
+        assert_eq!(text.render(), "Specific bug description here.");
+        assert_eq!(text.summary(), "Specific bug description here.");
+    }
+}
@@ -5,7 +5,27 @@
 //! and `TraeAgent` as the specific implementation for software engineering tasks.
 
 pub mod base_agent;
+#[allow(dead_code)] // Library API for embedders; the CLI only runs TraeAgent
+pub mod builder;
+pub mod context_usage;
+pub mod heartbeat;
+pub mod issue;
+pub mod profile;
+pub mod regrounding;
+pub mod router;
+pub mod step_extension;
+pub mod step_forecast;
+pub mod step_stream;
+#[allow(dead_code)] // Library API for embedders; the CLI only runs TraeAgent
+pub mod stop_policy;
+#[allow(dead_code)] // Library API for embedders that delegate to sub-agents; the CLI does not
+pub mod subagents;
+pub mod task_spec;
+pub mod token_budget;
+pub mod tool_caps;
 pub mod trae_agent_rs; // trae_agent_rs to avoid conflict with potential crate name
 
 pub use base_agent::{Agent, AgentError, AgentExecution};
+pub use issue::Issue;
+pub use task_spec::TaskSpec;
 pub use trae_agent_rs::TraeAgent;
@@ -0,0 +1,267 @@
+//! # Run Profile
+//!
+//! Where the time of a run went, for `trae run --profile`: the steps' total split into
+//! waiting for the LLM, running tools and the agent's own work (history bookkeeping, diff
+//! statistics, events), the latency percentiles of each model, and the slowest tool calls.
+//! Built from the timings recorded in the steps (`duration_ms`, `llm_duration_ms`) and their
+//! tool results (`duration_ms`).
+
+use super::base_agent::AgentExecution;
+use serde::Serialize;
+
+/// Slowest tool calls listed.
+const SLOWEST_TOOL_CALLS: usize = 5;
+
+/// LLM latency of one model.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelLatency {
+    pub model: String,
+    pub calls: usize,
+    pub p50_ms: u128,
+    pub p90_ms: u128,
+    pub p99_ms: u128,
+    pub max_ms: u128,
+}
+
+/// One tool call and how long it took.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolCallTiming {
+    pub step: u32,
+    pub tool: String,
+    pub duration_ms: u128,
+    pub success: bool,
+}
+
+/// Where the time of a run went.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunProfile {
+    pub total_ms: u128,
+    pub llm_ms: u128,
+    pub tools_ms: u128,
+    /// The rest of the steps' time.
+    pub overhead_ms: u128,
+    /// Models by the time spent waiting for them, longest first.
+    pub models: Vec<ModelLatency>,
+    pub slowest_tool_calls: Vec<ToolCallTiming>,
+}
+
+/// The `percent` percentile of `sorted` (nearest rank).
+fn percentile(sorted: &[u128], percent: usize) -> u128 {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank.min(sorted.len()) - 1]
+}
+
+fn format_ms(ms: u128) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    }
+}
+
+impl RunProfile {
+    /// Summarizes the timings recorded in `execution`'s steps.
+    pub fn from_execution(execution: &AgentExecution) -> Self {
+        let mut total_ms = 0;
+        let mut llm_ms = 0;
+        let mut tools_ms = 0;
+        let mut latencies: Vec<(String, Vec<u128>)> = Vec::new();
+        let mut tool_calls = Vec::new();
+        for step in &execution.steps {
+            total_ms += step.duration_ms;
+            if let Some(llm) = step.llm_duration_ms {
+                llm_ms += llm;
+                let model = step
+                    .llm_response
+                    .as_ref()
+                    .map(|response| response.model.clone())
+                    .or_else(|| step.route.as_ref().map(|route| route.model.clone()))
+                    .unwrap_or_else(|| "unknown".to_string());
+                match latencies.iter_mut().find(|(name, _)| *name == model) {
+                    Some((_, times)) => times.push(llm),
+                    None => latencies.push((model, vec![llm])),
+                }
+            }
+            for result in step.tool_results.iter().flatten() {
+                let Some(duration_ms) = result.duration_ms else {
+                    continue;
+                };
+                tools_ms += duration_ms;
+                let tool = step
+                    .tool_calls_made
+                    .iter()
+                    .flatten()
+                    .find(|call| call.id == result.tool_call_id)
+                    .map_or_else(|| "unknown".to_string(), |call| call.function.name.clone());
+                tool_calls.push(ToolCallTiming {
+                    step: step.step_number,
+                    tool,
+                    duration_ms,
+                    success: result.success,
+                });
+            }
+        }
+
+        let mut models: Vec<ModelLatency> = latencies
+            .into_iter()
+            .map(|(model, mut times)| {
+                times.sort_unstable();
+                ModelLatency {
+                    model,
+                    calls: times.len(),
+                    p50_ms: percentile(&times, 50),
+                    p90_ms: percentile(&times, 90),
+                    p99_ms: percentile(&times, 99),
+                    max_ms: times[times.len() - 1],
+                }
+            })
+            .collect();
+        models.sort_by_key(|m| std::cmp::Reverse(m.p50_ms * m.calls as u128));
+        tool_calls.sort_by_key(|call| std::cmp::Reverse(call.duration_ms));
+        tool_calls.truncate(SLOWEST_TOOL_CALLS);
+
+        RunProfile {
+            total_ms,
+            llm_ms,
+            tools_ms,
+            overhead_ms: total_ms.saturating_sub(llm_ms + tools_ms),
+            models,
+            slowest_tool_calls: tool_calls,
+        }
+    }
+
+    /// Formats the profile as an indented report.
+    pub fn format(&self) -> String {
+        let share = |ms: u128| ms as f64 * 100.0 / self.total_ms.max(1) as f64;
+        let mut lines = vec![
+            format!("  Total:           {:>8}", format_ms(self.total_ms)),
+            format!("  LLM calls:       {:>8} {:>5.1}%", format_ms(self.llm_ms), share(self.llm_ms)),
+            format!("  Tool execution:  {:>8} {:>5.1}%", format_ms(self.tools_ms), share(self.tools_ms)),
+            format!("  Agent overhead:  {:>8} {:>5.1}%", format_ms(self.overhead_ms), share(self.overhead_ms)),
+        ];
+        if !self.models.is_empty() {
+            lines.push("  LLM latency by model:".to_string());
+            for m in &self.models {
+                lines.push(format!(
+                    "    {}: {} call{}, p50 {}, p90 {}, p99 {}, max {}",
+                    m.model,
+                    m.calls,
+                    if m.calls == 1 { "" } else { "s" },
+                    format_ms(m.p50_ms),
+                    format_ms(m.p90_ms),
+                    format_ms(m.p99_ms),
+                    format_ms(m.max_ms)
+                ));
+            }
+        }
+        if !self.slowest_tool_calls.is_empty() {
+            lines.push("  Slowest tool calls:".to_string());
+            for call in &self.slowest_tool_calls {
+                lines.push(format!(
+                    "    {:>8}  {} (step {}){}",
+                    format_ms(call.duration_ms),
+                    call.tool,
+                    call.step,
+                    if call.success { "" } else { ", failed" }
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::base_agent::{AgentState, AgentStep};
+    use crate::llm::base_client::{LLMResponse, ToolCall, ToolCallFunction};
+    use crate::tools::AgentToolResult;
+
+    fn step(step_number: u32, duration_ms: u128, llm: (&str, u128), tools: &[(&str, u128)]) -> AgentStep {
+        AgentStep {
+            step_number,
+            state: AgentState::ProcessingToolResult,
+            messages_to_llm: None,
+            llm_response: Some(LLMResponse {
+                id: "r".to_string(),
+                object: "chat.completion".to_string(),
+                created: 0,
+                model: llm.0.to_string(),
+                choices: Vec::new(),
+                usage: None,
+                rate_limit: None,
+            }),
+            tool_calls_made: Some(
+                tools
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (name, _))| ToolCall {
+                        id: format!("call_{}", i),
+                        tool_type: "function".to_string(),
+                        function: ToolCallFunction {
+                            name: name.to_string(),
+                            arguments: "{}".to_string(),
+                        },
+                    })
+                    .collect(),
+            ),
+            tool_results: Some(
+                tools
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (_, ms))| AgentToolResult {
+                        tool_call_id: format!("call_{}", i),
+                        success: true,
+                        result: None,
+                        error: None,
+                        duration_ms: Some(*ms),
+                        approval: None,
+                    })
+                    .collect(),
+            ),
+            reflection: None,
+            error: None,
+            duration_ms,
+            route: None,
+            validation_error: None,
+            diff_stat: None,
+            file_changes: Vec::new(),
+            llm_duration_ms: Some(llm.1),
+            step_extensions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_profile_splits_time_and_ranks_models_and_tool_calls() {
+        let execution = AgentExecution {
+            task: "t".to_string(),
+            start_time: 0,
+            end_time: None,
+            steps: vec![
+                step(1, 3000, ("gpt-4o", 1000), &[("bash", 1500), ("str_replace_based_edit_tool", 100)]),
+                step(2, 2500, ("gpt-4o", 2000), &[]),
+                step(3, 1000, ("gpt-4o-mini", 300), &[("bash", 600)]),
+            ],
+            final_result: None,
+            success: true,
+            total_tokens_used: None,
+            error_message: None,
+            error_hint: None,
+            subagents: Vec::new(),
+            final_report: None,
+        };
+        let profile = RunProfile::from_execution(&execution);
+        assert_eq!((profile.total_ms, profile.llm_ms, profile.tools_ms, profile.overhead_ms), (6500, 3300, 2200, 1000));
+        assert_eq!(profile.models[0].model, "gpt-4o");
+        assert_eq!((profile.models[0].calls, profile.models[0].p50_ms, profile.models[0].max_ms), (2, 1000, 2000));
+        assert_eq!(profile.models[0].p90_ms, 2000);
+        let slowest: Vec<(&str, u128)> =
+            profile.slowest_tool_calls.iter().map(|c| (c.tool.as_str(), c.duration_ms)).collect();
+        assert_eq!(slowest, vec![("bash", 1500), ("bash", 600), ("str_replace_based_edit_tool", 100)]);
+
+        let report = profile.format();
+        assert!(report.contains("  LLM calls:           3.3s  50.8%"), "{}", report);
+        assert!(report.contains("    gpt-4o-mini: 1 call, p50 300ms, p90 300ms, p99 300ms, max 300ms"), "{}", report);
+        assert!(report.contains("       1.5s  bash (step 1)"), "{}", report);
+    }
+}
@@ -0,0 +1,221 @@
+//! # Context Re-grounding
+//!
+//! Builds the state note that `Config::regrounding` periodically adds to the conversation:
+//! the objective, the current plan item, the files modified so far and the outstanding test
+//! failures, all derived from the conversation itself.
+
+use crate::llm::base_client::{LLMMessage, MessageRole};
+use serde_json::Value;
+
+/// Template used when `RegroundingConfig::template` is not set.
+pub const DEFAULT_TEMPLATE: &str = "[State after {step} steps — keep working toward the objective.]
+Objective: {task}
+Current plan item: {plan_item}
+Files modified so far: {modified_files}
+Outstanding test failures: {test_failures}";
+
+/// Maximum number of failure lines carried into the note.
+const MAX_FAILURE_LINES: usize = 10;
+/// Maximum length of the plan item and of each failure line, in characters.
+const MAX_LINE_CHARS: usize = 300;
+
+/// Task state reconstructed from a conversation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunState {
+    /// The most recent thought recorded with the `sequential_thinking` tool.
+    pub plan_item: Option<String>,
+    /// Files changed through the edit tools, in order of first change.
+    pub modified_files: Vec<String>,
+    /// Failure lines from the output of the most recent test command.
+    pub test_failures: Vec<String>,
+}
+
+impl RunState {
+    /// Reconstructs the state from the tool calls and results in `messages`.
+    pub fn from_messages(messages: &[LLMMessage]) -> Self {
+        let mut state = RunState::default();
+        let mut last_test_call: Option<&str> = None;
+        for message in messages {
+            for call in message.tool_calls.iter().flatten() {
+                let args: Value = serde_json::from_str(&call.function.arguments).unwrap_or(Value::Null);
+                match call.function.name.as_str() {
+                    "sequential_thinking" => {
+                        if let Some(thought) = args["thought"].as_str() {
+                            state.plan_item = Some(clip(thought.trim()));
+                        }
+                    }
+                    "str_replace_based_edit_tool" => {
+                        let edits = matches!(
+                            args["command"].as_str(),
+                            Some("create" | "str_replace" | "insert")
+                        );
+                        if let (true, Some(path)) = (edits, args["path"].as_str()) {
+                            state.add_modified_file(path);
+                        }
+                    }
+                    "json_edit_tool" if args["operation"].as_str() != Some("view") => {
+                        if let Some(path) = args["file_path"].as_str() {
+                            state.add_modified_file(path);
+                        }
+                    }
+                    "bash" if args["command"].as_str().is_some_and(is_test_command) => {
+                        last_test_call = Some(&call.id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(call_id) = last_test_call {
+            let output = messages
+                .iter()
+                .find(|m| m.role == MessageRole::Tool && m.tool_call_id.as_deref() == Some(call_id))
+                .and_then(|m| m.content.as_deref())
+                .unwrap_or_default();
+            state.test_failures = failure_lines(output);
+        }
+        state
+    }
+
+    fn add_modified_file(&mut self, path: &str) {
+        if !self.modified_files.iter().any(|p| p == path) {
+            self.modified_files.push(path.to_string());
+        }
+    }
+}
+
+/// Renders the re-grounding note for `step` from `template` (see `DEFAULT_TEMPLATE`).
+pub fn render_note(template: &str, task: &str, step: u32, state: &RunState) -> String {
+    let modified_files = if state.modified_files.is_empty() {
+        "none".to_string()
+    } else {
+        state.modified_files.join(", ")
+    };
+    let test_failures = if state.test_failures.is_empty() {
+        "none known".to_string()
+    } else {
+        format!(
+            "from the last test run:\n  {}",
+            state.test_failures.join("\n  ")
+        )
+    };
+    template
+        .replace("{task}", task)
+        .replace("{step}", &step.to_string())
+        .replace("{plan_item}", state.plan_item.as_deref().unwrap_or("not recorded"))
+        .replace("{modified_files}", &modified_files)
+        .replace("{test_failures}", &test_failures)
+}
+
+/// Builds the note message added to the conversation.
+pub fn note_message(template: &str, task: &str, step: u32, messages: &[LLMMessage]) -> LLMMessage {
+    LLMMessage {
+        role: MessageRole::System,
+        content: Some(render_note(template, task, step, &RunState::from_messages(messages))),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
+
+fn is_test_command(command: &str) -> bool {
+    command
+        .split(|c: char| c.is_whitespace() || matches!(c, ';' | '&' | '|'))
+        .any(|word| matches!(word, "test" | "pytest" | "jest" | "vitest" | "tox" | "nox" | "ctest"))
+}
+
+fn failure_lines(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            line.starts_with("FAIL")
+                || line.contains(" FAILED")
+                || line.contains("panicked at")
+                || line.starts_with("ERROR")
+        })
+        .take(MAX_FAILURE_LINES)
+        .map(clip)
+        .collect()
+}
+
+fn clip(text: &str) -> String {
+    if text.chars().count() > MAX_LINE_CHARS {
+        let cut: String = text.chars().take(MAX_LINE_CHARS).collect();
+        format!("{}...", cut)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::{ToolCall, ToolCallFunction};
+    use serde_json::json;
+
+    fn assistant_call(id: &str, name: &str, args: Value) -> LLMMessage {
+        LLMMessage {
+            role: MessageRole::Assistant,
+            content: None,
+            name: None,
+            tool_calls: Some(vec![ToolCall {
+                id: id.to_string(),
+                tool_type: "function".to_string(),
+                function: ToolCallFunction {
+                    name: name.to_string(),
+                    arguments: args.to_string(),
+                },
+            }]),
+            tool_call_id: None,
+        }
+    }
+
+    fn tool_result(id: &str, content: &str) -> LLMMessage {
+        LLMMessage {
+            role: MessageRole::Tool,
+            content: Some(content.to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: Some(id.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_state_from_conversation() {
+        let messages = vec![
+            assistant_call("c1", "sequential_thinking", json!({"thought": "Fix the parser first"})),
+            tool_result("c1", "ok"),
+            assistant_call("c2", "str_replace_based_edit_tool", json!({"command": "view", "path": "/p/a.rs"})),
+            assistant_call("c3", "str_replace_based_edit_tool", json!({"command": "str_replace", "path": "/p/a.rs"})),
+            assistant_call("c4", "json_edit_tool", json!({"operation": "set", "file_path": "/p/c.json"})),
+            assistant_call("c5", "bash", json!({"command": "cd /p && cargo test"})),
+            tool_result("c5", "test parse::empty ... FAILED\nthread 'parse::empty' panicked at src/a.rs:3:5\ntest result: FAILED"),
+            assistant_call("c6", "str_replace_based_edit_tool", json!({"command": "insert", "path": "/p/a.rs"})),
+            assistant_call("c7", "bash", json!({"command": "ls"})),
+            tool_result("c7", "FAILED to list"),
+        ];
+        let state = RunState::from_messages(&messages);
+        assert_eq!(state.plan_item.as_deref(), Some("Fix the parser first"));
+        assert_eq!(state.modified_files, vec!["/p/a.rs", "/p/c.json"]);
+        assert_eq!(
+            state.test_failures,
+            vec![
+                "test parse::empty ... FAILED",
+                "thread 'parse::empty' panicked at src/a.rs:3:5",
+                "test result: FAILED"
+            ]
+        );
+
+        let note = render_note(DEFAULT_TEMPLATE, "Fix parsing", 10, &state);
+        assert!(note.starts_with("[State after 10 steps"));
+        assert!(note.contains("Files modified so far: /p/a.rs, /p/c.json"));
+        assert!(note.contains("Outstanding test failures: from the last test run:\n  test parse::empty ... FAILED"));
+    }
+
+    #[test]
+    fn test_empty_state_and_custom_template() {
+        let state = RunState::from_messages(&[]);
+        let note = render_note("{task} | {plan_item} | {modified_files} | {test_failures}", "T", 5, &state);
+        assert_eq!(note, "T | not recorded | none | none known");
+    }
+}
@@ -0,0 +1,316 @@
+//! # Model Routing
+//!
+//! Decides, step by step, whether the agent's next LLM call can go to an inexpensive model or
+//! needs the premium (default) one. Configured through `Config::routing`; the decision for
+//! each step is recorded in its `AgentStep::route`.
+
+use crate::config::RoutingConfig;
+use crate::llm::base_client::{LLMClient, LLMMessage, MessageRole};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Number of trailing conversation messages shown to the step classifier.
+const CLASSIFIER_CONTEXT_MESSAGES: usize = 6;
+/// Maximum characters of each message shown to the step classifier.
+const CLASSIFIER_MESSAGE_CHARS: usize = 600;
+
+const CLASSIFIER_PROMPT: &str = "You classify the next step of a software engineering agent. \
+Reply with exactly one word: THINK (plan or reason about what to do), EXAMINE (read files, \
+search code or inspect command output), EDIT (change files) or VERIFY (run tests or check a fix).";
+
+/// Which model serves a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelTier {
+    Cheap,
+    Premium,
+}
+
+/// The model chosen for one step, and why.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteDecision {
+    pub tier: ModelTier,
+    /// Name of the model the step was sent to.
+    pub model: String,
+    pub reason: String,
+}
+
+/// Kind of work the agent is about to do, as tagged by the step classifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepPhase {
+    Think,
+    Examine,
+    Edit,
+    Verify,
+}
+
+impl StepPhase {
+    /// Parses the classifier's reply, tolerating surrounding punctuation and case.
+    pub fn parse(reply: &str) -> Option<Self> {
+        let word = reply
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .find(|w| !w.is_empty())?;
+        match word.to_ascii_uppercase().as_str() {
+            "THINK" => Some(Self::Think),
+            "EXAMINE" => Some(Self::Examine),
+            "EDIT" => Some(Self::Edit),
+            "VERIFY" => Some(Self::Verify),
+            _ => None,
+        }
+    }
+
+    /// Whether steps of this phase can be served by the cheap model.
+    fn is_simple(self) -> bool {
+        matches!(self, Self::Think | Self::Examine)
+    }
+}
+
+impl fmt::Display for StepPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Think => "THINK",
+            Self::Examine => "EXAMINE",
+            Self::Edit => "EDIT",
+            Self::Verify => "VERIFY",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Routes steps between a cheap and a premium model.
+pub struct ModelRouter {
+    config: RoutingConfig,
+    cheap_client: Arc<dyn LLMClient>,
+    cheap_model: String,
+    premium_model: String,
+    last_failure_step: Option<u32>,
+}
+
+impl ModelRouter {
+    pub fn new(
+        config: RoutingConfig,
+        cheap_client: Arc<dyn LLMClient>,
+        cheap_model: String,
+        premium_model: String,
+    ) -> Self {
+        Self {
+            config,
+            cheap_client,
+            cheap_model,
+            premium_model,
+            last_failure_step: None,
+        }
+    }
+
+    pub fn cheap_client(&self) -> &Arc<dyn LLMClient> {
+        &self.cheap_client
+    }
+
+    /// Notes that `step` failed, so the next `failure_cooldown_steps` steps use the premium model.
+    pub fn record_failure(&mut self, step: u32) {
+        self.last_failure_step = Some(step);
+    }
+
+    /// Decision for a step that escalated to the premium model after the cheap model failed.
+    pub fn escalation(&self, error: &str) -> RouteDecision {
+        self.premium(format!("escalated after the cheap model failed: {}", error))
+    }
+
+    /// Chooses the model for `step`, whose prompt is `messages`.
+    pub async fn route(&self, step: u32, messages: &[LLMMessage]) -> RouteDecision {
+        if let Some(reason) = self.premium_reason(step, messages) {
+            return self.premium(reason);
+        }
+        if !self.config.classify_steps {
+            return self.cheap("short context, no recent failures".to_string());
+        }
+        match self.classify(messages).await {
+            Some(phase) if phase.is_simple() => self.cheap(format!("{} step", phase)),
+            Some(phase) => self.premium(format!("{} step", phase)),
+            None => self.premium("step could not be classified".to_string()),
+        }
+    }
+
+    /// Why `step` must use the premium model regardless of its phase, if it must.
+    fn premium_reason(&self, step: u32, messages: &[LLMMessage]) -> Option<String> {
+        if step <= 1 {
+            return Some("first step (planning)".to_string());
+        }
+        if let Some(failed) = self.last_failure_step {
+            if step.saturating_sub(failed) <= self.config.failure_cooldown_steps {
+                return Some(format!("recent failure at step {}", failed));
+            }
+        }
+        let tokens = estimate_tokens(messages);
+        if tokens > self.config.max_simple_context_tokens {
+            return Some(format!("long context (~{} tokens)", tokens));
+        }
+        None
+    }
+
+    async fn classify(&self, messages: &[LLMMessage]) -> Option<StepPhase> {
+        let request = vec![
+            text_message(MessageRole::System, CLASSIFIER_PROMPT.to_string()),
+            text_message(MessageRole::User, classifier_transcript(messages)),
+        ];
+        match self.cheap_client.chat(request, None, None).await {
+            Ok(response) => response
+                .choices
+                .first()
+                .and_then(|choice| choice.message.content.as_deref())
+                .and_then(StepPhase::parse),
+            Err(e) => {
+                warn!("Step classification failed: {}", e);
+                None
+            }
+        }
+    }
+
+    fn cheap(&self, reason: String) -> RouteDecision {
+        RouteDecision {
+            tier: ModelTier::Cheap,
+            model: self.cheap_model.clone(),
+            reason,
+        }
+    }
+
+    fn premium(&self, reason: String) -> RouteDecision {
+        RouteDecision {
+            tier: ModelTier::Premium,
+            model: self.premium_model.clone(),
+            reason,
+        }
+    }
+}
+
+/// Rough prompt size in tokens (about four characters per token).
+fn estimate_tokens(messages: &[LLMMessage]) -> usize {
+    let chars: usize = messages
+        .iter()
+        .map(|m| {
+            m.content.as_deref().map_or(0, str::len)
+                + m.tool_calls.as_ref().map_or(0, |calls| {
+                    calls.iter().map(|c| c.function.arguments.len()).sum()
+                })
+        })
+        .sum();
+    chars / 4
+}
+
+/// The end of the conversation, condensed for the step classifier.
+fn classifier_transcript(messages: &[LLMMessage]) -> String {
+    let start = messages.len().saturating_sub(CLASSIFIER_CONTEXT_MESSAGES);
+    let mut transcript = String::from("Recent conversation:\n");
+    for message in &messages[start..] {
+        let mut text: String = message
+            .content
+            .as_deref()
+            .unwrap_or_default()
+            .chars()
+            .take(CLASSIFIER_MESSAGE_CHARS)
+            .collect();
+        if let Some(calls) = &message.tool_calls {
+            let names: Vec<&str> = calls.iter().map(|c| c.function.name.as_str()).collect();
+            text.push_str(&format!(" [tool calls: {}]", names.join(", ")));
+        }
+        transcript.push_str(&format!("{:?}: {}\n", message.role, text));
+    }
+    transcript.push_str("\nWhat kind of step comes next? Answer THINK, EXAMINE, EDIT or VERIFY.");
+    transcript
+}
+
+fn text_message(role: MessageRole, content: String) -> LLMMessage {
+    LLMMessage {
+        role,
+        content: Some(content),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        default_classify_steps, default_failure_cooldown_steps, default_max_simple_context_tokens,
+        ModelParameters,
+    };
+    use crate::llm::OpenAIClient;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn router_with_reply(server: &MockServer, reply: &str) -> ModelRouter {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "chatcmpl-route",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "gpt-4o-mini",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": reply},
+                    "finish_reason": "stop"
+                }]
+            })))
+            .mount(server)
+            .await;
+        let params: ModelParameters =
+            serde_json::from_value(json!({"model": "gpt-4o-mini"})).unwrap();
+        let client = OpenAIClient::new(Some("key".to_string()), Some(server.uri()), params)
+            .await
+            .unwrap();
+        let config = RoutingConfig {
+            cheap_provider: "openai".to_string(),
+            cheap_model: None,
+            max_simple_context_tokens: default_max_simple_context_tokens(),
+            failure_cooldown_steps: default_failure_cooldown_steps(),
+            classify_steps: default_classify_steps(),
+        };
+        ModelRouter::new(config, Arc::new(client), "gpt-4o-mini".to_string(), "gpt-4o".to_string())
+    }
+
+    #[test]
+    fn test_parse_step_phase() {
+        assert_eq!(StepPhase::parse("EXAMINE"), Some(StepPhase::Examine));
+        assert_eq!(StepPhase::parse("  think."), Some(StepPhase::Think));
+        assert_eq!(StepPhase::parse("**VERIFY**"), Some(StepPhase::Verify));
+        assert_eq!(StepPhase::parse("I am not sure"), None);
+    }
+
+    #[tokio::test]
+    async fn test_route_by_phase_failures_and_context() {
+        let server = MockServer::start().await;
+        let mut router = router_with_reply(&server, "EXAMINE").await;
+        let messages = vec![text_message(MessageRole::User, "Fix the failing test".to_string())];
+
+        assert_eq!(router.route(1, &messages).await.tier, ModelTier::Premium);
+        let decision = router.route(2, &messages).await;
+        assert_eq!(decision.tier, ModelTier::Cheap);
+        assert_eq!(decision.model, "gpt-4o-mini");
+        assert_eq!(decision.reason, "EXAMINE step");
+
+        router.record_failure(3);
+        let decision = router.route(5, &messages).await;
+        assert_eq!((decision.tier, decision.reason.as_str()), (ModelTier::Premium, "recent failure at step 3"));
+        assert_eq!(router.route(6, &messages).await.tier, ModelTier::Cheap);
+
+        let long = vec![text_message(MessageRole::User, "x".repeat(100_000))];
+        assert!(router.route(7, &long).await.reason.starts_with("long context"));
+    }
+
+    #[tokio::test]
+    async fn test_complex_phase_uses_premium_model() {
+        let server = MockServer::start().await;
+        let router = router_with_reply(&server, "EDIT").await;
+        let messages = vec![text_message(MessageRole::User, "Rename the function".to_string())];
+        let decision = router.route(2, &messages).await;
+        assert_eq!(decision.tier, ModelTier::Premium);
+        assert_eq!(decision.model, "gpt-4o");
+        assert_eq!(decision.reason, "EDIT step");
+    }
+}
@@ -0,0 +1,153 @@
+//! # Step Extensions
+//!
+//! Lets the agent ask for more steps with the `request_extension` tool instead of failing one
+//! step short of a fix. The `step_extensions` config decides each request: granted
+//! automatically, denied, or put to the user; grants never exceed `max_extra_steps` over the
+//! run. The tool decides and queues the outcome; the agent loop then raises the step budget
+//! and records the outcome in the step, and so in the trajectory.
+
+use crate::config::{ExtensionPolicy, StepExtensionConfig};
+use crate::utils::permissions::confirm_on_terminal;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// How a request for more steps was decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtensionDecision {
+    /// Granted, possibly fewer steps than requested because of the cap.
+    Granted,
+    /// Denied by the policy or the user.
+    Denied,
+    /// Denied because the run has already been granted `max_extra_steps`.
+    CapReached,
+}
+
+/// A request for more steps and its outcome.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepExtension {
+    pub requested: u32,
+    pub granted: u32,
+    pub justification: String,
+    pub decision: ExtensionDecision,
+}
+
+impl StepExtension {
+    /// The outcome, as told to the model.
+    pub fn describe(&self) -> String {
+        match self.decision {
+            ExtensionDecision::Granted if self.granted < self.requested => format!(
+                "Granted {} of the {} steps requested; the run may not be extended further. \
+                Use them to finish the task.",
+                self.granted, self.requested
+            ),
+            ExtensionDecision::Granted => format!("Granted {} more steps. Use them to finish the task.", self.granted),
+            ExtensionDecision::Denied => {
+                "The request was denied. Finish the task within the remaining steps.".to_string()
+            }
+            ExtensionDecision::CapReached => "The request was denied: the run has already been extended as far \
+                as it may be. Finish the task within the remaining steps."
+                .to_string(),
+        }
+    }
+}
+
+/// Asks the user whether to grant a request for more steps. Called from a blocking thread.
+pub trait ExtensionPrompt: Send + Sync {
+    fn confirm(&self, steps: u32, justification: &str) -> bool;
+}
+
+/// Asks on the terminal with `confirm_on_terminal`.
+pub struct TerminalExtensionPrompt;
+
+impl ExtensionPrompt for TerminalExtensionPrompt {
+    fn confirm(&self, steps: u32, justification: &str) -> bool {
+        confirm_on_terminal(&format!("The agent asks for {} more steps: {}\nGrant them?", steps, justification))
+    }
+}
+
+#[derive(Debug, Default)]
+struct ExtensionState {
+    /// Steps granted so far over the run.
+    granted: u32,
+    /// Outcomes not yet applied by the agent loop.
+    pending: Vec<StepExtension>,
+}
+
+/// The requests for more steps of one run, shared by the tool and the agent loop.
+pub struct StepExtensions {
+    config: StepExtensionConfig,
+    prompt: Option<Arc<dyn ExtensionPrompt>>,
+    state: Mutex<ExtensionState>,
+}
+
+impl StepExtensions {
+    /// Requests are decided by `config`; with the `ask` policy, `prompt` asks the user, and
+    /// they are denied without one.
+    pub fn new(config: StepExtensionConfig, prompt: Option<Arc<dyn ExtensionPrompt>>) -> Self {
+        Self {
+            config,
+            prompt,
+            state: Mutex::new(ExtensionState::default()),
+        }
+    }
+
+    /// Decides a request for `steps` more steps and queues the outcome for the agent loop.
+    /// May block on the user.
+    pub fn request(&self, steps: u32, justification: &str) -> StepExtension {
+        let mut state = self.state.lock().unwrap();
+        let available = self.config.max_extra_steps.saturating_sub(state.granted);
+        let granted = if available == 0 {
+            None
+        } else {
+            let approved = match self.config.policy {
+                ExtensionPolicy::Grant => true,
+                ExtensionPolicy::Deny => false,
+                ExtensionPolicy::Ask => self.prompt.as_ref().is_some_and(|p| p.confirm(steps.min(available), justification)),
+            };
+            Some(if approved { steps.min(available) } else { 0 })
+        };
+        let extension = StepExtension {
+            requested: steps,
+            granted: granted.unwrap_or(0),
+            justification: justification.to_string(),
+            decision: match granted {
+                None => ExtensionDecision::CapReached,
+                Some(0) => ExtensionDecision::Denied,
+                Some(_) => ExtensionDecision::Granted,
+            },
+        };
+        state.granted += extension.granted;
+        state.pending.push(extension.clone());
+        extension
+    }
+
+    /// The outcomes decided since the last call.
+    pub fn take_decisions(&self) -> Vec<StepExtension> {
+        std::mem::take(&mut self.state.lock().unwrap().pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grants_are_capped_over_the_run() {
+        let config = StepExtensionConfig {
+            policy: ExtensionPolicy::Grant,
+            max_extra_steps: 8,
+        };
+        let extensions = StepExtensions::new(config.clone(), None);
+        assert_eq!(extensions.request(5, "one more test to fix").granted, 5);
+        let partial = extensions.request(5, "the last test");
+        assert_eq!((partial.granted, partial.decision), (3, ExtensionDecision::Granted));
+        assert!(partial.describe().starts_with("Granted 3 of the 5 steps requested"));
+        assert_eq!(extensions.request(1, "again").decision, ExtensionDecision::CapReached);
+        assert_eq!(extensions.take_decisions().len(), 3);
+        assert!(extensions.take_decisions().is_empty());
+
+        let ask = StepExtensions::new(StepExtensionConfig { policy: ExtensionPolicy::Ask, ..config }, None);
+        assert_eq!(ask.request(2, "no one to ask").decision, ExtensionDecision::Denied);
+    }
+}
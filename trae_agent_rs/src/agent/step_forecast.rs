@@ -0,0 +1,207 @@
+//! # Step Budget Forecast
+//!
+//! Warns before a run hits `max_steps` instead of letting it die at `MaxStepsReached`. Once
+//! 80% of the steps (or of the token budget) are used, the remaining budget is checked
+//! against what the run needs: the steps earlier successful runs in the project took (from
+//! the usage ledger) and the tokens this run spends per step. The user is warned, the model is
+//! told how many steps remain, and with a prompt set (confirm mode, interactive sessions) the
+//! user is asked whether to grant more steps.
+
+use crate::utils::ledger::LedgerEntry;
+use crate::utils::permissions::ask_on_terminal;
+
+/// Share of the step or token budget after which the forecast is made.
+pub const WARN_FRACTION: f64 = 0.8;
+
+/// Steps taken by earlier successful runs of the project.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepHistory {
+    /// Sorted.
+    steps: Vec<usize>,
+}
+
+impl StepHistory {
+    /// The successful runs in `entries` of `project`, or all of them without a project.
+    pub fn from_ledger(entries: &[LedgerEntry], project: Option<&str>) -> Self {
+        let mut steps: Vec<usize> = entries
+            .iter()
+            .filter(|entry| entry.success && (project.is_none() || entry.project.as_deref() == project))
+            .map(|entry| entry.steps)
+            .collect();
+        steps.sort_unstable();
+        Self { steps }
+    }
+
+    /// Median steps of the runs, if there were any.
+    pub fn typical_steps(&self) -> Option<usize> {
+        (!self.steps.is_empty()).then(|| self.steps[self.steps.len() / 2])
+    }
+}
+
+/// Where a run stands against its budgets, and whether they are likely to be enough.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub steps_used: u32,
+    pub max_steps: u32,
+    pub tokens_used: u64,
+    pub token_budget: Option<u64>,
+    /// Median steps of earlier successful runs in the project.
+    pub typical_steps: Option<usize>,
+}
+
+impl Forecast {
+    fn tokens_per_step(&self) -> u64 {
+        self.tokens_used / u64::from(self.steps_used.max(1))
+    }
+
+    /// Tokens used once every remaining step has been taken at this run's rate so far.
+    pub fn projected_tokens(&self) -> u64 {
+        self.tokens_per_step() * u64::from(self.max_steps)
+    }
+
+    /// Whether the remaining budget covers what runs like this one usually need.
+    pub fn likely_sufficient(&self) -> bool {
+        let steps_ok = self.typical_steps.is_none_or(|typical| typical <= self.max_steps as usize);
+        let tokens_ok = self.token_budget.is_none_or(|budget| self.projected_tokens() <= budget);
+        steps_ok && tokens_ok
+    }
+
+    /// One-line explanation for the user.
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "{} of {} steps used ({} left)",
+            self.steps_used,
+            self.max_steps,
+            self.max_steps.saturating_sub(self.steps_used)
+        );
+        if let Some(budget) = self.token_budget {
+            text.push_str(&format!(
+                "; ~{} tokens per step projects ~{} of the {}-token budget",
+                self.tokens_per_step(),
+                self.projected_tokens(),
+                budget
+            ));
+        }
+        if let Some(typical) = self.typical_steps {
+            text.push_str(&format!("; successful runs in this project took {} steps (median)", typical));
+        }
+        text.push_str(if self.likely_sufficient() {
+            "; the budget is likely sufficient"
+        } else {
+            "; the budget is likely NOT sufficient"
+        });
+        text
+    }
+
+    /// The note telling the model to prioritize finishing.
+    pub fn note_for_model(&self) -> String {
+        format!(
+            "Note: {} of your {} steps are used; {} remain. Prioritize completing the task within them: \
+             finish the essential change, verify it, and report. Leave optional cleanups out.",
+            self.steps_used,
+            self.max_steps,
+            self.max_steps.saturating_sub(self.steps_used)
+        )
+    }
+}
+
+/// Asks the user whether to grant a run more steps. Called from a blocking thread.
+pub trait StepBudgetPrompt: Send + Sync {
+    /// The number of steps to add; 0 keeps the budget.
+    fn extend(&self, forecast: &Forecast) -> u32;
+}
+
+/// Asks on the terminal with `ask_on_terminal`.
+pub struct TerminalStepBudgetPrompt;
+
+impl StepBudgetPrompt for TerminalStepBudgetPrompt {
+    fn extend(&self, forecast: &Forecast) -> u32 {
+        let question = format!(
+            "Step budget: {}.\nAdd steps? Enter a number, or nothing to keep the budget: ",
+            forecast.describe()
+        );
+        ask_on_terminal(&question).and_then(|reply| reply.trim().parse().ok()).unwrap_or(0)
+    }
+}
+
+/// Decides when a run's forecast is due: once per budget, after `WARN_FRACTION` of it.
+#[derive(Debug, Clone)]
+pub struct StepForecaster {
+    history: StepHistory,
+    /// The step budget the forecast was last made for; made again when it is extended.
+    warned_for: Option<u32>,
+}
+
+impl StepForecaster {
+    pub fn new(history: StepHistory) -> Self {
+        Self {
+            history,
+            warned_for: None,
+        }
+    }
+
+    /// The forecast before step `step`, if 80% of a budget has just been reached.
+    pub fn check(&mut self, step: u32, max_steps: u32, tokens_used: u64, token_budget: Option<u64>) -> Option<Forecast> {
+        if self.warned_for == Some(max_steps) {
+            return None;
+        }
+        let steps_used = step.saturating_sub(1);
+        let steps_due = f64::from(steps_used) >= WARN_FRACTION * f64::from(max_steps);
+        let tokens_due = token_budget.is_some_and(|budget| tokens_used as f64 >= WARN_FRACTION * budget as f64);
+        if !steps_due && !tokens_due {
+            return None;
+        }
+        self.warned_for = Some(max_steps);
+        Some(Forecast {
+            steps_used,
+            max_steps,
+            tokens_used,
+            token_budget,
+            typical_steps: self.history.typical_steps(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn entry(project: &str, success: bool, steps: usize) -> LedgerEntry {
+        LedgerEntry {
+            timestamp: 0,
+            task: "t".to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            project: Some(project.to_string()),
+            success,
+            steps,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            cost: None,
+            trajectory: None,
+            labels: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_forecast_is_made_once_per_budget_at_eighty_percent() {
+        let ledger = [entry("/app", true, 30), entry("/app", true, 40), entry("/app", false, 99), entry("/lib", true, 5)];
+        let history = StepHistory::from_ledger(&ledger, Some("/app"));
+        assert_eq!(history.typical_steps(), Some(40));
+
+        let mut forecaster = StepForecaster::new(history);
+        assert_eq!(forecaster.check(8, 10, 1000, None), None);
+        let forecast = forecaster.check(9, 10, 8000, Some(20_000)).unwrap();
+        assert_eq!((forecast.steps_used, forecast.projected_tokens()), (8, 10_000));
+        assert!(!forecast.likely_sufficient(), "successful runs took 40 steps");
+        assert!(forecast.describe().starts_with("8 of 10 steps used (2 left)"), "{}", forecast.describe());
+        assert_eq!(forecaster.check(10, 10, 9000, Some(20_000)), None);
+
+        // Extending the budget re-arms the forecast, and the token budget can trigger it too.
+        assert_eq!(forecaster.check(10, 50, 9000, Some(20_000)), None);
+        let forecast = forecaster.check(11, 50, 16_000, Some(20_000)).unwrap();
+        assert_eq!(forecast.projected_tokens(), 80_000);
+        assert!(!forecast.likely_sufficient());
+    }
+}
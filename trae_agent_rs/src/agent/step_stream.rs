@@ -0,0 +1,111 @@
+//! # Step Streams
+//!
+//! Exposes an agent run as a `Stream` of `AgentStepUpdate`s, for embedders that consume
+//! progress with backpressure instead of wiring up an `AgentEvent` channel themselves. The run
+//! is driven by polling the stream: when the consumer stops polling, the agent stops at its
+//! next event.
+
+use super::base_agent::{AgentError, AgentEvent, AgentExecution};
+use futures::future::BoxFuture;
+use futures::stream::{self, Stream};
+use tokio::sync::mpsc;
+
+/// One item of an agent run's stream.
+#[derive(Debug)]
+pub enum AgentStepUpdate {
+    /// Progress of the run, as sent on the event channel.
+    Event(AgentEvent),
+    /// The outcome of the run; always the last item.
+    Finished(Result<AgentExecution, AgentError>),
+}
+
+struct StreamState<'a> {
+    run: Option<BoxFuture<'a, Result<AgentExecution, AgentError>>>,
+    events: mpsc::Receiver<AgentEvent>,
+    events_open: bool,
+    outcome: Option<Result<AgentExecution, AgentError>>,
+}
+
+/// Turns a run and the receiving end of its event channel into a stream: the run's events,
+/// then its outcome. Events still queued when the run finishes are delivered before it.
+pub fn step_stream<'a>(
+    run: BoxFuture<'a, Result<AgentExecution, AgentError>>,
+    events: mpsc::Receiver<AgentEvent>,
+) -> impl Stream<Item = AgentStepUpdate> + 'a {
+    let state = StreamState {
+        run: Some(run),
+        events,
+        events_open: true,
+        outcome: None,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            match state.run.as_mut() {
+                Some(run) if state.events_open => {
+                    tokio::select! {
+                        biased;
+                        event = state.events.recv() => match event {
+                            Some(event) => return Some((AgentStepUpdate::Event(event), state)),
+                            None => state.events_open = false,
+                        },
+                        outcome = run => {
+                            state.run = None;
+                            state.outcome = Some(outcome);
+                        }
+                    }
+                }
+                Some(run) => {
+                    let outcome = run.await;
+                    state.run = None;
+                    state.outcome = Some(outcome);
+                }
+                None => {
+                    if state.events_open {
+                        if let Some(event) = state.events.recv().await {
+                            return Some((AgentStepUpdate::Event(event), state));
+                        }
+                        state.events_open = false;
+                    }
+                    let outcome = state.outcome.take()?;
+                    return Some((AgentStepUpdate::Finished(outcome), state));
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{FutureExt, StreamExt};
+
+    #[tokio::test]
+    async fn test_stream_yields_events_then_the_outcome() {
+        // A channel of one: the run can only get ahead of the consumer by a single event.
+        let (tx, rx) = mpsc::channel(1);
+        let run = async move {
+            for step in 1..=3 {
+                tx.send(AgentEvent::StepBegin(step)).await.unwrap();
+            }
+            tx.send(AgentEvent::StatusUpdate("done".to_string())).await.unwrap();
+            Err(AgentError::MaxStepsReached(3))
+        }
+        .boxed();
+
+        let updates: Vec<AgentStepUpdate> = step_stream(run, rx).collect().await;
+        let steps: Vec<u32> = updates
+            .iter()
+            .filter_map(|u| match u {
+                AgentStepUpdate::Event(AgentEvent::StepBegin(step)) => Some(*step),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(steps, vec![1, 2, 3]);
+        assert_eq!(updates.len(), 5);
+        assert!(matches!(updates[3], AgentStepUpdate::Event(AgentEvent::StatusUpdate(_))));
+        assert!(matches!(
+            updates[4],
+            AgentStepUpdate::Finished(Err(AgentError::MaxStepsReached(3)))
+        ));
+    }
+}
@@ -0,0 +1,126 @@
+//! # Stop Policies
+//!
+//! When a run is over, whether its work is acceptable, and what its final result is, as one
+//! trait for library users. Every method has a default (stop on `task_done`, on an answer
+//! without tool calls, or at the step limit), so a policy usually overrides only what it
+//! changes, e.g. a validation that keeps the agent working until the tests cover enough:
+//!
+//! ```ignore
+//! struct CoverageGate { project: PathBuf }
+//!
+//! impl StopPolicy for CoverageGate {
+//!     fn validate(&self, _response: &LLMResponse) -> Result<(), String> {
+//!         match measure_coverage(&self.project) {
+//!             coverage if coverage >= 80.0 => Ok(()),
+//!             coverage => Err(format!("Coverage is {:.1}%; raise it to 80% before finishing.", coverage)),
+//!         }
+//!     }
+//! }
+//!
+//! let agent = AgentBuilder::new("test-writer", config).stop_policy(CoverageGate { project }).build().await?;
+//! ```
+
+use super::base_agent::{common_execute_task_loop, AgentError, AgentEvent, AgentExecution, BaseAgent, StopReason};
+use super::builder::{default_completion_extractor, default_stop_condition};
+use crate::llm::base_client::{LLMMessage, LLMResponse};
+use tokio::sync::mpsc;
+
+/// Decides when a run is over and what it produced.
+pub trait StopPolicy: Send + Sync {
+    /// Whether the run is over after `response`, the response of step `step` of `max_steps`.
+    fn should_stop(&self, response: &LLMResponse, step: u32, max_steps: u32) -> StopReason {
+        default_stop_condition(response, step, max_steps)
+    }
+
+    /// Checks the work once `should_stop` considers the task completed. An error is sent to
+    /// the model, and the run goes on.
+    fn validate(&self, _response: &LLMResponse) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// The final result, taken from the response that completed the task.
+    fn final_result(&self, response: &LLMResponse) -> Option<String> {
+        default_completion_extractor(response)
+    }
+}
+
+/// The policy's decision on `response`: `should_stop`, then `validate` if the task is completed.
+pub fn stop_reason<P: StopPolicy + ?Sized>(policy: &P, response: &LLMResponse, step: u32, max_steps: u32) -> StopReason {
+    match policy.should_stop(response, step, max_steps) {
+        StopReason::TaskCompleted => match policy.validate(response) {
+            Ok(()) => StopReason::TaskCompleted,
+            Err(message) => StopReason::ValidationFailed(message),
+        },
+        reason => reason,
+    }
+}
+
+/// Runs `common_execute_task_loop` with the decisions of `policy`.
+pub async fn execute_with_policy<P: StopPolicy + ?Sized>(
+    base_agent: &mut BaseAgent,
+    initial_messages: Vec<LLMMessage>,
+    event_sender: Option<mpsc::Sender<AgentEvent>>,
+    policy: &P,
+) -> Result<AgentExecution, AgentError> {
+    common_execute_task_loop(
+        base_agent,
+        initial_messages,
+        event_sender,
+        &|response, step, max_steps| stop_reason(policy, response, step, max_steps),
+        &|response| policy.final_result(response),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Accepts the work on the second attempt.
+    struct SecondTime(AtomicU32);
+
+    impl StopPolicy for SecondTime {
+        fn validate(&self, _response: &LLMResponse) -> Result<(), String> {
+            match self.0.fetch_add(1, Ordering::Relaxed) {
+                0 => Err("Coverage is 61%; raise it to 80%.".to_string()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn response(tool: Option<&str>) -> LLMResponse {
+        let tool_calls = tool.map(|name| {
+            json!([{"id": "call_1", "type": "function", "function": {"name": name, "arguments": "{\"summary\": \"Added tests\"}"}}])
+        });
+        serde_json::from_value(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1,
+            "model": "gpt-test",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Working on it", "tool_calls": tool_calls},
+                "finish_reason": "stop"
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validation_runs_only_on_completion_and_can_reject_it() {
+        let policy = SecondTime(AtomicU32::new(0));
+        assert_eq!(stop_reason(&policy, &response(Some("bash")), 1, 10), StopReason::Continue);
+        assert_eq!(policy.0.load(Ordering::Relaxed), 0);
+        assert_eq!(stop_reason(&policy, &response(Some("bash")), 10, 10), StopReason::MaxStepsReached);
+
+        let done = response(Some("task_done"));
+        assert_eq!(
+            stop_reason(&policy, &done, 2, 10),
+            StopReason::ValidationFailed("Coverage is 61%; raise it to 80%.".to_string())
+        );
+        assert_eq!(stop_reason(&policy, &done, 3, 10), StopReason::TaskCompleted);
+        assert_eq!(policy.final_result(&done).as_deref(), Some("Added tests"));
+    }
+}
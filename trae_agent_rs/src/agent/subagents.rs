@@ -0,0 +1,151 @@
+//! # Sub-agent Limits and Cost Attribution
+//!
+//! An agent that delegates part of its task to a child agent (for example, one built with
+//! `AgentBuilder`) must not let delegation multiply spend unnoticed. Before spawning, the
+//! parent checks the nesting depth with `check_depth`; after the child finishes, its execution
+//! is folded into the parent's with `AgentExecution::absorb_subagent`, which adds the child's
+//! tokens to the parent's total and keeps a per-sub-agent breakdown.
+
+use super::base_agent::AgentExecution;
+use crate::llm::base_client::LLMUsage;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// How deep sub-agents may nest by default: the top-level agent is at depth 0, so its children
+/// are at depth 1 and their children at depth 2.
+pub const DEFAULT_MAX_SUBAGENT_DEPTH: u32 = 2;
+
+/// Errors of delegating to a sub-agent.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SubagentError {
+    #[error("Sub-agent nesting depth {depth} exceeds the limit of {max_depth}")]
+    DepthExceeded { depth: u32, max_depth: u32 },
+}
+
+/// Checks whether an agent at `parent_depth` may spawn a sub-agent.
+///
+/// # Returns
+/// The depth of the sub-agent.
+pub fn check_depth(parent_depth: u32, max_depth: u32) -> Result<u32, SubagentError> {
+    let depth = parent_depth + 1;
+    if depth > max_depth {
+        return Err(SubagentError::DepthExceeded { depth, max_depth });
+    }
+    Ok(depth)
+}
+
+/// What one sub-agent run cost, as recorded in its parent's execution.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubagentUsage {
+    /// The name the parent gave the sub-agent.
+    pub name: String,
+    /// Nesting depth of the sub-agent (1 for a direct child of the top-level agent).
+    pub depth: u32,
+    pub task: String,
+    pub steps: usize,
+    pub success: bool,
+    /// Tokens used by the sub-agent itself, excluding its own sub-agents (listed separately).
+    pub usage: Option<LLMUsage>,
+}
+
+impl AgentExecution {
+    /// Folds the execution of a finished sub-agent into this one: its tokens, including those
+    /// of its own sub-agents, are added to `total_tokens_used`, and it and its sub-agents are
+    /// listed in `subagents`.
+    ///
+    /// # Arguments
+    /// * `depth`: The depth of the sub-agent, as returned by `check_depth`.
+    pub fn absorb_subagent(&mut self, name: &str, depth: u32, child: AgentExecution) {
+        self.total_tokens_used = add_usage(self.total_tokens_used.take(), child.total_tokens_used.as_ref());
+
+        // The child's total includes its descendants; its own share is what remains.
+        let mut own = child.total_tokens_used.clone();
+        for grandchild in &child.subagents {
+            own = own.map(|usage| subtract_usage(usage, grandchild.usage.as_ref()));
+        }
+        self.subagents.push(SubagentUsage {
+            name: name.to_string(),
+            depth,
+            task: child.task,
+            steps: child.steps.len(),
+            success: child.success,
+            usage: own,
+        });
+        self.subagents.extend(child.subagents);
+    }
+}
+
+fn add_usage(total: Option<LLMUsage>, other: Option<&LLMUsage>) -> Option<LLMUsage> {
+    match (total, other) {
+        (Some(a), Some(b)) => Some(LLMUsage {
+            prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+            completion_tokens: Some(a.completion_tokens.unwrap_or(0) + b.completion_tokens.unwrap_or(0)),
+            total_tokens: a.total_tokens + b.total_tokens,
+        }),
+        (a, b) => a.or_else(|| b.cloned()),
+    }
+}
+
+fn subtract_usage(usage: LLMUsage, other: Option<&LLMUsage>) -> LLMUsage {
+    let Some(other) = other else {
+        return usage;
+    };
+    LLMUsage {
+        prompt_tokens: usage.prompt_tokens.saturating_sub(other.prompt_tokens),
+        completion_tokens: usage
+            .completion_tokens
+            .map(|tokens| tokens.saturating_sub(other.completion_tokens.unwrap_or(0))),
+        total_tokens: usage.total_tokens.saturating_sub(other.total_tokens),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execution(task: &str, total_tokens: u32) -> AgentExecution {
+        AgentExecution {
+            task: task.to_string(),
+            start_time: 0,
+            end_time: Some(1),
+            steps: Vec::new(),
+            final_result: None,
+            success: true,
+            total_tokens_used: Some(LLMUsage {
+                prompt_tokens: total_tokens - 10,
+                completion_tokens: Some(10),
+                total_tokens,
+            }),
+            error_message: None,
+            error_hint: None,
+            subagents: Vec::new(),
+            final_report: None,
+        }
+    }
+
+    #[test]
+    fn test_depth_is_limited_and_child_usage_is_attributed() {
+        assert_eq!(check_depth(0, DEFAULT_MAX_SUBAGENT_DEPTH), Ok(1));
+        assert_eq!(check_depth(1, DEFAULT_MAX_SUBAGENT_DEPTH), Ok(2));
+        assert_eq!(
+            check_depth(2, DEFAULT_MAX_SUBAGENT_DEPTH),
+            Err(SubagentError::DepthExceeded { depth: 3, max_depth: 2 })
+        );
+
+        let mut child = execution("Write the tests", 300);
+        child.absorb_subagent("fixture-writer", 2, execution("Write fixtures", 100));
+        assert_eq!(child.total_tokens_used.as_ref().unwrap().total_tokens, 400);
+
+        let mut parent = execution("Fix the bug", 1000);
+        parent.absorb_subagent("tester", 1, child);
+        let total = parent.total_tokens_used.as_ref().unwrap();
+        assert_eq!((total.total_tokens, total.completion_tokens), (1400, Some(30)));
+
+        let breakdown: Vec<(&str, u32, u32)> = parent
+            .subagents
+            .iter()
+            .map(|s| (s.name.as_str(), s.depth, s.usage.as_ref().unwrap().total_tokens))
+            .collect();
+        assert_eq!(breakdown, vec![("tester", 1, 300), ("fixture-writer", 2, 100)]);
+    }
+}
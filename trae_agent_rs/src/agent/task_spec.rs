@@ -0,0 +1,95 @@
+//! # Task Specifications
+//!
+//! `TaskSpec` carries the arguments of a task given to `Agent::new_task`. It deserializes from
+//! the JSON object used for task arguments so far (`{"project_path": ..., "must_patch": true}`),
+//! including `must_patch` given as the string `"true"`, and keeps any unknown keys in `extra`.
+
+use super::issue::Issue;
+use crate::utils::attachments::Attachment;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Arguments of a task.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskSpec {
+    /// The issue the task resolves; when set, the prompt presents the task as this issue. A
+    /// string is read as an issue with only a body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<Issue>,
+    /// Project the agent works in; defaults to the configured working directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_path: Option<String>,
+    /// Whether completion requires a non-empty patch.
+    #[serde(default, deserialize_with = "bool_or_string")]
+    pub must_patch: bool,
+    /// Commit to diff against instead of `HEAD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_commit: Option<String>,
+    /// Where the patch is saved after the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch_path: Option<String>,
+    /// Files included in the first user message (`--attach`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Labels of the run (`--label team=infra`), recorded in the trajectory header and the
+    /// usage ledger for reporting.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Any other arguments; recorded in the trajectory header.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl TaskSpec {
+    /// A task in the project at `project_path`.
+    pub fn in_project(project_path: impl Into<String>) -> Self {
+        Self {
+            project_path: Some(project_path.into()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Accepts `true`/`false` as well as the strings `"true"`/`"false"` (case-insensitive).
+fn bool_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Bool(b) => Ok(b),
+        Value::String(s) => Ok(s.eq_ignore_ascii_case("true")),
+        Value::Null => Ok(false),
+        other => Err(serde::de::Error::custom(format!("expected a boolean, got {}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_task_spec_reads_the_json_task_arguments() {
+        let spec: TaskSpec = serde_json::from_value(json!({
+            "project_path": "/repo",
+            "must_patch": "TRUE",
+            "base_commit": "abc123",
+            "ticket": "ENG-7"
+        }))
+        .unwrap();
+        assert_eq!(
+            spec,
+            TaskSpec {
+                project_path: Some("/repo".to_string()),
+                must_patch: true,
+                base_commit: Some("abc123".to_string()),
+                extra: BTreeMap::from([("ticket".to_string(), json!("ENG-7"))]),
+                ..TaskSpec::default()
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&spec).unwrap(),
+            json!({"project_path": "/repo", "must_patch": true, "base_commit": "abc123", "ticket": "ENG-7"})
+        );
+        assert!(serde_json::from_value::<TaskSpec>(json!({"must_patch": 1})).is_err());
+        assert_eq!(serde_json::from_value::<TaskSpec>(json!({})).unwrap(), TaskSpec::default());
+    }
+}
@@ -0,0 +1,214 @@
+//! # Token Budget
+//!
+//! Enforces `Config::token_budget` before each LLM request rather than after it. The projection
+//! for the next step is the tokens already used plus what the next prompt will cost: the
+//! history the model has already seen, the tool results it has not seen yet (which can be much
+//! larger than anything before them), and the completion the provider may return.
+
+use crate::config::TokenBudgetConfig;
+use crate::llm::base_client::{LLMMessage, LLMUsage, MessageRole};
+
+/// What the next request is projected to cost, in tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetProjection {
+    /// Tokens used by the requests made so far.
+    pub used: u64,
+    /// Estimated size of the conversation up to and including the last assistant message.
+    pub history: u64,
+    /// Estimated size of the tool results added since the last assistant message.
+    pub pending_tool_results: u64,
+    /// Tokens reserved for the completion (the provider's `max_tokens`).
+    pub completion_reserve: u64,
+    /// The configured budget.
+    pub budget: u64,
+}
+
+impl BudgetProjection {
+    /// Total tokens used once the next request has been answered.
+    pub fn projected_total(&self) -> u64 {
+        self.used + self.history + self.pending_tool_results + self.completion_reserve
+    }
+
+    /// One-line explanation, e.g. for warnings and the run's error message.
+    pub fn describe(&self) -> String {
+        format!(
+            "~{} of {} tokens after the next step ({} used, ~{} history, ~{} new tool output, {} reserved for the reply)",
+            self.projected_total(),
+            self.budget,
+            self.used,
+            self.history,
+            self.pending_tool_results,
+            self.completion_reserve
+        )
+    }
+}
+
+/// Outcome of checking the next request against the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetCheck {
+    WithinBudget,
+    /// The next step crosses `warn_fraction` of the budget for the first time.
+    Warning(BudgetProjection),
+    /// The next step would exceed the budget.
+    Exceeded(BudgetProjection),
+}
+
+/// Tracks token usage of one run against its budget.
+#[derive(Debug, Clone)]
+pub struct TokenBudget {
+    config: TokenBudgetConfig,
+    used: u64,
+    warned: bool,
+}
+
+impl TokenBudget {
+    pub fn new(config: TokenBudgetConfig) -> Self {
+        Self {
+            config,
+            used: 0,
+            warned: false,
+        }
+    }
+
+    /// Adds the usage reported for a request; falls back to `estimate` if none was reported.
+    pub fn record(&mut self, usage: Option<&LLMUsage>, estimate: u64) {
+        self.used += usage.map_or(estimate, |usage| u64::from(usage.total_tokens));
+    }
+
+    /// Projects the cost of sending `messages` with room for `completion_reserve` tokens.
+    pub fn project(&self, messages: &[LLMMessage], completion_reserve: u64) -> BudgetProjection {
+        let seen = messages
+            .iter()
+            .rposition(|m| m.role == MessageRole::Assistant)
+            .map_or(0, |i| i + 1);
+        let (history, pending) = messages.split_at(seen);
+        let pending_tool_results: u64 = pending
+            .iter()
+            .filter(|m| m.role == MessageRole::Tool)
+            .map(|m| self.estimate_message(m))
+            .sum();
+        let other_pending: u64 = pending
+            .iter()
+            .filter(|m| m.role != MessageRole::Tool)
+            .map(|m| self.estimate_message(m))
+            .sum();
+        BudgetProjection {
+            used: self.used,
+            history: history.iter().map(|m| self.estimate_message(m)).sum::<u64>() + other_pending,
+            pending_tool_results,
+            completion_reserve,
+            budget: self.config.max_total_tokens,
+        }
+    }
+
+    /// Checks whether the next request fits in the budget. A warning is reported only once.
+    pub fn check(&mut self, messages: &[LLMMessage], completion_reserve: u64) -> BudgetCheck {
+        let projection = self.project(messages, completion_reserve);
+        let total = projection.projected_total();
+        if total > self.config.max_total_tokens {
+            return BudgetCheck::Exceeded(projection);
+        }
+        let threshold = (self.config.max_total_tokens as f64 * self.config.warn_fraction) as u64;
+        if !self.warned && total >= threshold {
+            self.warned = true;
+            return BudgetCheck::Warning(projection);
+        }
+        BudgetCheck::WithinBudget
+    }
+
+    /// Estimated prompt size of `messages`, in tokens.
+    pub fn estimate_prompt(&self, messages: &[LLMMessage]) -> u64 {
+        messages.iter().map(|m| self.estimate_message(m)).sum()
+    }
+
+    fn estimate_message(&self, message: &LLMMessage) -> u64 {
+        let chars = message.content.as_deref().map_or(0, |c| c.chars().count())
+            + message.tool_calls.as_ref().map_or(0, |calls| {
+                calls
+                    .iter()
+                    .map(|c| c.function.name.len() + c.function.arguments.chars().count())
+                    .sum()
+            });
+        let chars_per_token = if message.role == MessageRole::Tool {
+            self.config.tool_result_chars_per_token
+        } else {
+            self.config.chars_per_token
+        };
+        estimate_tokens(chars, chars_per_token)
+    }
+}
+
+/// Estimated number of tokens of `chars` characters at `chars_per_token`.
+pub fn estimate_tokens(chars: usize, chars_per_token: f64) -> u64 {
+    (chars as f64 / chars_per_token.max(0.1)).ceil() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{default_chars_per_token, default_budget_warn_fraction};
+
+    fn message(role: MessageRole, content: &str) -> LLMMessage {
+        let tool_call_id = (role == MessageRole::Tool).then(|| "c1".to_string());
+        LLMMessage {
+            role,
+            content: Some(content.to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id,
+        }
+    }
+
+    fn budget(max_total_tokens: u64) -> TokenBudget {
+        TokenBudget::new(TokenBudgetConfig {
+            max_total_tokens,
+            chars_per_token: default_chars_per_token(),
+            tool_result_chars_per_token: 2.0,
+            warn_fraction: default_budget_warn_fraction(),
+        })
+    }
+
+    #[test]
+    fn test_projection_counts_pending_tool_results() {
+        let mut budget = budget(1_000);
+        budget.record(None, 100);
+        let messages = vec![
+            message(MessageRole::User, &"u".repeat(400)),
+            message(MessageRole::Assistant, &"a".repeat(40)),
+            message(MessageRole::Tool, &"t".repeat(200)),
+        ];
+        let projection = budget.project(&messages, 50);
+        assert_eq!(projection.history, 110);
+        assert_eq!(projection.pending_tool_results, 100);
+        assert_eq!(projection.projected_total(), 360);
+        assert!(projection.describe().starts_with("~360 of 1000 tokens"));
+    }
+
+    #[test]
+    fn test_warns_once_then_stops_before_exceeding() {
+        let mut budget = budget(1_000);
+        let small = vec![message(MessageRole::User, &"u".repeat(400))];
+        assert_eq!(budget.check(&small, 0), BudgetCheck::WithinBudget);
+
+        budget.record(
+            Some(&LLMUsage {
+                prompt_tokens: 600,
+                completion_tokens: Some(100),
+                total_tokens: 700,
+            }),
+            0,
+        );
+        assert!(matches!(budget.check(&small, 0), BudgetCheck::Warning(_)));
+        assert_eq!(budget.check(&small, 0), BudgetCheck::WithinBudget);
+
+        let big_tool_output = vec![
+            message(MessageRole::User, "u"),
+            message(MessageRole::Assistant, "a"),
+            message(MessageRole::Tool, &"t".repeat(1_000)),
+        ];
+        match budget.check(&big_tool_output, 0) {
+            BudgetCheck::Exceeded(projection) => assert_eq!(projection.pending_tool_results, 500),
+            other => panic!("expected the budget to be exceeded, got {:?}", other),
+        }
+    }
+}
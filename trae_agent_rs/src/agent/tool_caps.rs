@@ -0,0 +1,176 @@
+//! # Tool Usage Caps
+//!
+//! Enforces `Config::tool_caps`: per-run limits on bash invocations, bytes of tool output shown
+//! to the model, and file writes. A pathological run — a loop of shell commands, a flood of
+//! output, endless rewrites — is caught early in unattended batch mode. The model is warned once
+//! per cap when it crosses `warn_fraction` of it, and the run stops when a cap is exceeded.
+
+use crate::config::ToolCapsConfig;
+use crate::llm::base_client::ToolCall;
+use crate::tools::AgentToolResult;
+use crate::utils::guards::write_target_for_tool_call;
+use std::path::PathBuf;
+
+/// Outcome of recording a step's tool calls against the caps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapCheck {
+    WithinCaps,
+    /// One or more caps were crossed `warn_fraction` of for the first time; the message is for
+    /// the model.
+    Warning(String),
+    /// A cap was exceeded.
+    Exceeded(String),
+}
+
+/// One cap: its name for messages, its limit and the amount used so far.
+#[derive(Debug, Clone)]
+struct Cap {
+    what: &'static str,
+    limit: Option<u64>,
+    used: u64,
+    warned: bool,
+}
+
+impl Cap {
+    fn new(what: &'static str, limit: Option<u64>) -> Self {
+        Self {
+            what,
+            limit,
+            used: 0,
+            warned: false,
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("{} of {} {}", self.used, self.limit.unwrap_or(0), self.what)
+    }
+}
+
+/// Tracks tool usage of one run against its caps.
+#[derive(Debug, Clone)]
+pub struct ToolCaps {
+    warn_fraction: f64,
+    bash_invocations: Cap,
+    output_bytes: Cap,
+    file_writes: Cap,
+    /// Writes below this directory (the run's scratch directory) are not counted.
+    exempt_dir: Option<PathBuf>,
+}
+
+impl ToolCaps {
+    pub fn new(config: ToolCapsConfig) -> Self {
+        Self {
+            warn_fraction: config.warn_fraction,
+            bash_invocations: Cap::new("bash invocations", config.max_bash_invocations.map(u64::from)),
+            output_bytes: Cap::new("bytes of tool output", config.max_tool_output_bytes),
+            file_writes: Cap::new("file writes", config.max_file_writes.map(u64::from)),
+            exempt_dir: None,
+        }
+    }
+
+    /// Does not count file writes below `dir`.
+    pub fn exempting(mut self, dir: Option<PathBuf>) -> Self {
+        self.exempt_dir = dir;
+        self
+    }
+
+    /// Adds the tool calls of a step and their results, and checks the caps.
+    pub fn record(&mut self, calls: &[ToolCall], results: &[AgentToolResult]) -> CapCheck {
+        for call in calls {
+            if call.function.name == "bash" {
+                self.bash_invocations.used += 1;
+            }
+            let arguments = serde_json::from_str(&call.function.arguments).unwrap_or_default();
+            let target = write_target_for_tool_call(&call.function.name, &arguments);
+            let exempt = |target: &PathBuf| self.exempt_dir.as_ref().is_some_and(|dir| target.starts_with(dir));
+            if target.is_some_and(|target| !exempt(&target)) {
+                self.file_writes.used += 1;
+            }
+        }
+        for result in results {
+            let shown = result.result.as_deref().or(result.error.as_deref()).unwrap_or_default();
+            self.output_bytes.used += shown.len() as u64;
+        }
+
+        let caps = [&mut self.bash_invocations, &mut self.output_bytes, &mut self.file_writes];
+        if let Some(exceeded) = caps.iter().find(|cap| cap.limit.is_some_and(|limit| cap.used > limit)) {
+            return CapCheck::Exceeded(format!("{} used", exceeded.describe()));
+        }
+        let mut warnings = Vec::new();
+        for cap in caps {
+            let Some(limit) = cap.limit else {
+                continue;
+            };
+            if !cap.warned && cap.used as f64 >= limit as f64 * self.warn_fraction {
+                cap.warned = true;
+                warnings.push(cap.describe());
+            }
+        }
+        if warnings.is_empty() {
+            CapCheck::WithinCaps
+        } else {
+            CapCheck::Warning(format!(
+                "[Tool usage warning]: This run has used {} allowed. The run is stopped when a limit is \
+                exceeded, so work toward completing the task with the remaining budget.",
+                warnings.join(", ")
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::ToolCallFunction;
+    use serde_json::json;
+
+    fn call(id: &str, name: &str, arguments: serde_json::Value) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            tool_type: "function".to_string(),
+            function: ToolCallFunction {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+
+    fn result(id: &str, output: &str) -> AgentToolResult {
+        AgentToolResult {
+            tool_call_id: id.to_string(),
+            success: true,
+            result: Some(output.to_string()),
+            error: None,
+            duration_ms: None,
+            approval: None,
+        }
+    }
+
+    #[test]
+    fn test_caps_warn_once_then_stop() {
+        let config: ToolCapsConfig =
+            serde_json::from_value(json!({"max_bash_invocations": 5, "max_file_writes": 1})).unwrap();
+        let mut caps = ToolCaps::new(config).exempting(Some(PathBuf::from("/repo/.trae/runs/1/scratch")));
+        let bash = |id: &str| call(id, "bash", json!({"command": "ls"}));
+
+        let step: Vec<ToolCall> = (0..3).map(|i| bash(&i.to_string())).collect();
+        assert_eq!(caps.record(&step, &[result("0", "a")]), CapCheck::WithinCaps);
+        match caps.record(&[bash("3")], &[result("3", "b")]) {
+            CapCheck::Warning(message) => assert!(message.contains("4 of 5 bash invocations"), "{}", message),
+            other => panic!("expected a warning, got {:?}", other),
+        }
+        // Viewing a file is not a write; the warning is not repeated.
+        let view = call("4", "str_replace_based_edit_tool", json!({"command": "view", "path": "/repo/a.rs"}));
+        assert_eq!(caps.record(&[view], &[result("4", "fn a() {}")]), CapCheck::WithinCaps);
+        // Writes to the scratch directory are not counted.
+        let scratch = call("s", "str_replace_based_edit_tool", json!({"command": "create", "path": "/repo/.trae/runs/1/scratch/repro.py", "file_text": ""}));
+        assert_eq!(caps.record(&[scratch], &[]), CapCheck::WithinCaps);
+
+        let edit = |id: &str| call(id, "str_replace_based_edit_tool", json!({"command": "create", "path": "/repo/b.rs", "file_text": ""}));
+        assert!(matches!(caps.record(&[edit("5")], &[]), CapCheck::Warning(_)));
+        assert_eq!(
+            caps.record(&[edit("6")], &[]),
+            CapCheck::Exceeded("2 of 1 file writes used".to_string())
+        );
+    }
+}
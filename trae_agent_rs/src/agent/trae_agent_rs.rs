@@ -1,14 +1,32 @@
 use super::base_agent::{common_execute_task_loop, Agent, AgentError, AgentEvent, BaseAgent};
-use crate::config::Config;
+use super::context_usage::ContextUsage;
+use super::step_extension::StepExtensions;
+use super::step_forecast::{StepBudgetPrompt, StepHistory};
+use super::step_stream::{step_stream, AgentStepUpdate};
+use super::task_spec::TaskSpec;
+use crate::config::{output_language_instruction, project_conventions, Config};
 use crate::llm::base_client::{LLMMessage, LLMResponse, MessageRole};
+use crate::tools::execution::{ExecutionEnvironment, HostEnvironment};
+use crate::tools::final_report_tool::FINAL_REPORT_TOOL;
 use crate::tools::ToolRegistry;
+use crate::utils::attachments::format_attachments;
+use crate::utils::approval::ApprovalGate;
+use crate::utils::audit_log::AuditLog;
+use crate::utils::environment::RunEnvironment;
+use crate::utils::guards::WriteGuard;
+use crate::utils::permissions::CommandPermissions;
+use crate::utils::reproduction::Reproduction;
+use crate::utils::scratch::RunScratch;
 use crate::utils::trajectory_recorder::TrajectoryRecorder; // Added
 use async_trait::async_trait;
+use futures::Stream;
 use serde_json::Value;
 use std::collections::HashMap; // Added
 use std::path::PathBuf; // Added
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
 /// The Trae Agent implementation, specialized for software engineering tasks.
@@ -18,6 +36,13 @@ use tracing::{debug, error, info, warn};
 /// (including patch validation if required).
 pub struct TraeAgent {
     base_agent: BaseAgent,
+    /// Reproduction phase for the task, if enabled. Its recorded command is re-run when the
+    /// agent signals completion.
+    reproduction: Option<Arc<Reproduction>>,
+    /// Outcome of the environment setup commands, added to the system prompt.
+    setup_summary: Option<String>,
+    /// The run's scratch directory, described in the system prompt.
+    scratch: Option<RunScratch>,
 }
 
 impl TraeAgent {
@@ -37,6 +62,7 @@ impl TraeAgent {
         trajectory_file_path: Option<PathBuf>, // Added
     ) -> Result<Self, AgentError> {
         let mut base_agent = BaseAgent::try_new(config.clone(), tool_registry).await?; // Cloned config for recorder
+        base_agent.check_capabilities().await?;
 
         if let Some(path) = trajectory_file_path {
             match TrajectoryRecorder::new(Some(path)) {
@@ -52,13 +78,152 @@ impl TraeAgent {
             }
         }
 
-        Ok(Self { base_agent })
+        Ok(Self {
+            base_agent,
+            reproduction: None,
+            setup_summary: None,
+            scratch: None,
+        })
+    }
+
+    /// Restricts the files this agent may modify through its file editing tools.
+    ///
+    /// # Arguments
+    /// * `guard`: The guard to enforce, or `None` to lift any restriction.
+    pub fn set_write_guard(&mut self, guard: Option<Arc<WriteGuard>>) {
+        self.base_agent.write_guard = guard;
+    }
+
+    /// Asks the user before each shell command the agent runs, unless it was allowed before.
+    ///
+    /// # Arguments
+    /// * `permissions`: The permissions to consult, or `None` to run commands without asking.
+    pub fn set_command_permissions(&mut self, permissions: Option<Arc<CommandPermissions>>) {
+        self.base_agent.tool_executor.set_command_permissions(permissions);
+    }
+
+    /// Records the agent's commands, file writes and network calls in `audit_log`.
+    pub fn set_audit_log(&mut self, audit_log: Option<Arc<AuditLog>>) {
+        self.base_agent.tool_executor.set_audit_log(audit_log);
+    }
+
+    /// Gates the agent's commands and file writes by the approval policy in `gate`.
+    pub fn set_approval_gate(&mut self, gate: Option<Arc<ApprovalGate>>) {
+        self.base_agent.tool_executor.set_approval_gate(gate);
+    }
+
+    /// Runs `bash` and the editor in `execution` (see `tools::execution`) instead of the host.
+    pub fn set_execution_environment(&mut self, execution: Option<Arc<dyn ExecutionEnvironment>>) {
+        self.base_agent.execution = execution;
+    }
+
+    /// Forecasts the step budget from `history` once most of it is used, and asks `prompt`,
+    /// if given, whether to extend it (see `step_forecast`).
+    pub fn set_step_budget_forecast(&mut self, history: StepHistory, prompt: Option<Arc<dyn StepBudgetPrompt>>) {
+        self.base_agent.step_history = history;
+        self.base_agent.step_budget_prompt = prompt;
+    }
+
+    /// Lets the agent ask for more steps; `extensions` must be the one the run's
+    /// `RequestExtensionTool` decides with (see `step_extension`).
+    pub fn set_step_extensions(&mut self, extensions: Option<Arc<StepExtensions>>) {
+        self.base_agent.step_extensions = extensions;
+    }
+
+    /// Executes the current task as a stream: its events as they happen, then its outcome.
+    ///
+    /// The run only advances while the stream is polled, and at most `buffer` events are
+    /// queued ahead of the consumer. This is a wrapper around `execute_task` with an event
+    /// channel, which remains available.
+    ///
+    /// # Arguments
+    /// * `buffer`: Capacity of the event channel between the run and the stream.
+    pub fn execute_task_stream(&mut self, buffer: usize) -> impl Stream<Item = AgentStepUpdate> + '_ {
+        let (event_tx, event_rx) = mpsc::channel(buffer.max(1));
+        step_stream(self.execute_task(Some(event_tx)), event_rx)
     }
 
+    /// Estimated context consumption of the conversation the agent currently holds (the
+    /// current task, or the last interactive turn), by category.
+    pub fn context_usage(&self) -> ContextUsage {
+        ContextUsage::measure(
+            &self.base_agent.conversation_history,
+            self.base_agent.config.token_budget.as_ref(),
+        )
+    }
+
+    /// Records the environment the run executes in into the trajectory, if one is recorded.
+    pub fn set_run_environment(&mut self, environment: RunEnvironment) {
+        if let Some(recorder) = self.base_agent.trajectory_recorder.as_mut() {
+            recorder.set_environment(environment);
+        }
+    }
+
+    /// Enables (or disables) the reproduction phase for subsequent tasks.
+    ///
+    /// The `record_reproduction` tool for the same `Reproduction` must be registered in the
+    /// agent's tool registry so the agent can record its command.
+    pub fn set_reproduction(&mut self, reproduction: Option<Arc<Reproduction>>) {
+        self.reproduction = reproduction;
+    }
+
+    /// Files and directories of the run left out of its patch (see `BaseAgent::diff_exclusions`).
+    pub fn diff_exclusions(&self) -> Vec<PathBuf> {
+        self.base_agent.diff_exclusions()
+    }
+
+    /// Sets the run's scratch directory (see `utils::scratch`), which the system prompt tells
+    /// the agent to use for temporary files.
+    pub fn set_scratch(&mut self, scratch: Option<RunScratch>) {
+        self.base_agent.scratch_dir = scratch.as_ref().map(|s| s.dir().to_path_buf());
+        self.base_agent.run_id = scratch.as_ref().map(|s| s.id().to_string());
+        self.scratch = scratch;
+    }
+
+    /// Sets the token that interrupts the next run or turn when cancelled.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.base_agent.cancellation = token;
+    }
+
+    /// Sets the summary of the environment setup commands run before the task (see
+    /// `utils::setup_hooks`), so the agent knows what is already installed.
+    pub fn set_setup_summary(&mut self, summary: Option<String>) {
+        self.setup_summary = summary;
+    }
 
     /// Generates the system prompt specific to the `TraeAgent`.
-    /// This prompt instructs the LLM on its role as a software engineering agent.
+    /// This prompt instructs the LLM on its role as a software engineering agent, gives it the
+    /// current date and time, and the language to answer in when `output_language` is configured.
     fn get_system_prompt(&self) -> String {
+        let mut prompt = Self::base_system_prompt();
+        prompt.push_str(&format!("\n\nThe current date and time is {}.", crate::utils::clock::now_utc()));
+        if let Some(summary) = &self.setup_summary {
+            prompt.push_str("\n\n");
+            prompt.push_str(summary);
+        }
+        if let Some(scratch) = &self.scratch {
+            prompt.push_str("\n\n");
+            prompt.push_str(&scratch.prompt_note());
+        }
+        let project_root = PathBuf::from(self.base_agent.project_path.as_deref().unwrap_or("."));
+        if let Some(conventions) = project_conventions(&self.base_agent.config, &project_root) {
+            prompt.push_str("\n\n");
+            prompt.push_str(&conventions);
+        }
+        if self.requires_final_report() {
+            prompt.push_str(
+                "\n\nWhen the work is finished, deliver your report to the user with the 'final_report' tool \
+                (what you changed, how you verified it, and the risks that remain) before calling 'task_done'.",
+            );
+        }
+        if let Some(language) = &self.base_agent.config.output_language {
+            prompt.push_str("\n\n");
+            prompt.push_str(&output_language_instruction(language));
+        }
+        prompt
+    }
+
+    fn base_system_prompt() -> String {
         // TODO: Consider loading this prompt from a configuration file or template
         // for easier modification and versioning.
         "You are an expert AI software engineering agent. \
@@ -74,7 +239,7 @@ impl TraeAgent {
     /// - Checking if maximum steps have been reached.
     /// - Detecting if the `task_done` tool was called by the LLM.
     /// - If `must_patch` is true and `task_done` was called, validating that a non-empty patch
-    ///   (excluding test files) was generated.
+    ///   (excluding test files and `diff_exclusions`) was generated.
     ///
     /// # Returns
     /// A `StopReason` enum indicating why the agent should stop or if it should continue.
@@ -85,6 +250,7 @@ impl TraeAgent {
         must_patch: bool,
         project_path: Option<&str>,
         base_commit: Option<&str>,
+        diff_exclusions: &[PathBuf],
     ) -> super::base_agent::StopReason { // Changed return type
         if current_step_number >= max_steps {
             warn!("Max steps reached, forcing stop.");
@@ -121,7 +287,7 @@ impl TraeAgent {
             // If must_patch is true, validate the patch
             if must_patch {
                 if let Some(proj_p) = project_path {
-                    match crate::utils::git_utils::get_git_diff(proj_p, base_commit) {
+                    match crate::utils::git_utils::get_git_diff_excluding(proj_p, base_commit, diff_exclusions) {
                         Ok(model_patch) => {
                             let patch = crate::utils::git_utils::remove_patches_to_tests(&model_patch);
                             if patch.trim().is_empty() {
@@ -177,6 +343,29 @@ impl TraeAgent {
         llm_response.choices[0].message.content.clone()
     }
 
+    /// Whether the agent must deliver a `final_report` before its task counts as completed:
+    /// whenever the tool is available.
+    fn requires_final_report(&self) -> bool {
+        self.base_agent.tool_registry.get_tool(FINAL_REPORT_TOOL).is_some()
+    }
+
+    /// Re-runs the recorded reproduction command once completion has been signaled.
+    ///
+    /// # Returns
+    /// `TaskCompleted` if the command now succeeds, otherwise `ValidationFailed` with its output.
+    fn verify_reproduction(reproduction: &Reproduction, execution: &dyn ExecutionEnvironment) -> super::base_agent::StopReason {
+        match reproduction.verify(execution) {
+            Ok(run) => {
+                info!(log = %run.log_path.display(), "Reproduction command passed during verification.");
+                super::base_agent::StopReason::TaskCompleted
+            }
+            Err(message) => {
+                warn!("Reproduction verification failed; task not considered done.");
+                super::base_agent::StopReason::ValidationFailed(message)
+            }
+        }
+    }
+
     // --- Methods for Interactive Mode ---
 
     /// Determines if an interactive turn should stop.
@@ -195,7 +384,7 @@ impl TraeAgent {
 
         let message = &llm_response.choices[0].message;
         if message.tool_calls.is_none()
-            || message.tool_calls.as_ref().map_or(true, |tc| tc.is_empty())
+            || message.tool_calls.as_ref().is_none_or(|tc| tc.is_empty())
         {
             // If there are no tool calls, this is a direct response to the user, so the turn is "complete".
             return super::base_agent::StopReason::TaskCompleted;
@@ -232,9 +421,10 @@ impl TraeAgent {
                     "Attempting to save git diff to patch_path: {}",
                     patch_path_str
                 );
-                match crate::utils::git_utils::get_git_diff(
+                match crate::utils::git_utils::get_git_diff_excluding(
                     project_path_str,
                     self.base_agent.base_commit.as_deref(),
+                    &self.base_agent.diff_exclusions(),
                 ) {
                     Ok(diff_content) => {
                         match std::fs::write(patch_path_str, diff_content) {
@@ -280,53 +470,63 @@ impl Agent for TraeAgent {
     ///
     /// Sets up the initial conversation history with a system prompt and the user's task,
     /// and processes task-specific arguments like `project_path` and `must_patch`.
-    async fn new_task(&mut self, task: String, task_args: Option<Value>) -> Result<(), AgentError> {
+    async fn new_task(&mut self, task: String, spec: TaskSpec) -> Result<(), AgentError> {
         info!(agent_name = %self.get_name(), task = %task, "Received new task");
         self.base_agent.current_task = Some(task.clone());
         self.base_agent.conversation_history.clear();
 
         let mut recorder_extra_args = HashMap::new();
 
-        if let Some(args) = &task_args { // Borrow args
-            if let Some(path_val) = args.get("project_path") {
-                if let Some(path_str) = path_val.as_str() {
-                    self.base_agent.project_path = Some(path_str.to_string());
-                    recorder_extra_args.insert("project_path".to_string(), path_str.to_string());
-                    debug!("Set project path to: {}", path_str);
-                }
-            }
-            if let Some(patch_val) = args.get("must_patch") {
-                if let Some(patch_bool) = patch_val.as_bool() {
-                    self.base_agent.must_patch = patch_bool;
-                    debug!("Set must_patch to: {}", patch_bool);
-                } else if let Some(patch_str) = patch_val.as_str() {
-                    self.base_agent.must_patch = patch_str.eq_ignore_ascii_case("true");
-                    debug!(
-                        "Set must_patch (from string) to: {}",
-                        self.base_agent.must_patch
-                    );
-                }
-            }
-            if let Some(bc_val) = args.get("base_commit") {
-                if let Some(bc_str) = bc_val.as_str() {
-                    self.base_agent.base_commit = Some(bc_str.to_string());
-                    recorder_extra_args.insert("base_commit".to_string(), bc_str.to_string());
-                    debug!("Set base_commit to: {}", bc_str);
-                }
+        if let Some(project_path) = &spec.project_path {
+            self.base_agent.project_path = Some(project_path.clone());
+            recorder_extra_args.insert("project_path".to_string(), project_path.clone());
+            debug!("Set project path to: {}", project_path);
+        }
+        self.base_agent.must_patch = spec.must_patch;
+        if let Some(base_commit) = &spec.base_commit {
+            self.base_agent.base_commit = Some(base_commit.clone());
+            recorder_extra_args.insert("base_commit".to_string(), base_commit.clone());
+            debug!("Set base_commit to: {}", base_commit);
+        }
+        if let Some(patch_path) = &spec.patch_path {
+            self.base_agent.patch_path = Some(patch_path.clone());
+            debug!("Set patch_path to: {}", patch_path);
+        }
+        for (key, value) in &spec.extra {
+            let value = value.as_str().map_or_else(|| value.to_string(), str::to_string);
+            recorder_extra_args.insert(key.clone(), value);
+        }
+        if let Some(scratch) = &self.scratch {
+            recorder_extra_args.insert("scratch_dir".to_string(), scratch.dir().display().to_string());
+        }
+        if let Some(reproduction) = &self.reproduction {
+            recorder_extra_args.insert(
+                "reproduction_scratch_dir".to_string(),
+                reproduction.scratch_dir().display().to_string(),
+            );
+        }
+        recorder_extra_args.insert("must_patch".to_string(), self.base_agent.must_patch.to_string());
+        if let Some(issue) = &spec.issue {
+            if let Some(title) = &issue.title {
+                recorder_extra_args.insert("issue_title".to_string(), title.clone());
             }
-            if let Some(pp_val) = args.get("patch_path") {
-                if let Some(pp_str) = pp_val.as_str() {
-                    self.base_agent.patch_path = Some(pp_str.to_string());
-                    // Not typically needed for trajectory_extra_args, but can be added if desired
-                    debug!("Set patch_path to: {}", pp_str);
-                }
+            if let Some(url) = &issue.url {
+                recorder_extra_args.insert("issue_url".to_string(), url.clone());
             }
-            // Add other relevant args to recorder_extra_args if needed
-            recorder_extra_args.insert("must_patch".to_string(), self.base_agent.must_patch.to_string());
+        }
+        if !spec.attachments.is_empty() {
+            let names: Vec<&str> = spec.attachments.iter().map(|a| a.name.as_str()).collect();
+            recorder_extra_args.insert("attachments".to_string(), names.join(", "));
+        }
+        // Recorded so `trae replay --same-seed` can reuse it.
+        if let Some(seed) = self.base_agent.config.get_current_provider_config().ok().and_then(|pc| pc.seed) {
+            recorder_extra_args.insert("seed".to_string(), seed.to_string());
         }
 
         // Start trajectory recording if recorder is available
         if let Some(recorder) = self.base_agent.trajectory_recorder.as_mut() {
+            recorder.set_tool_contracts(self.base_agent.tool_registry.determinism_contracts());
+            recorder.set_labels(spec.labels.clone());
             let _ = recorder.start_recording(
                 task.clone(),
                 self.base_agent.llm_client.get_provider_name(), // Assuming LLMClient has such a method
@@ -345,29 +545,23 @@ impl Agent for TraeAgent {
         });
 
         let mut user_message_content = String::new();
-        let problem_statement_text = if let Some(args_val) = &task_args {
-            if let Some(issue_val) = args_val.get("issue") {
-                issue_val.as_str().map(|s| s.to_string())
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        if let Some(issue_text) = problem_statement_text {
+        if let Some(issue) = &spec.issue {
             user_message_content.push_str(&format!(
                 "[Problem statement]: We're currently solving the following issue within our repository. Here's the issue text:\n{}\n",
-                issue_text
+                issue.render()
             ));
         } else {
             // Fallback to using the main task string if 'issue' is not provided
             user_message_content.push_str(&format!("[Problem statement]: {}\n", task));
         }
+        user_message_content.push_str(&format_attachments(&spec.attachments));
 
         if let Some(project_path) = &self.base_agent.project_path {
             user_message_content.push_str(&format!("\n[Project root path]: {}\n", project_path));
         }
+        if let Some(reproduction) = &self.reproduction {
+            user_message_content.push_str(&format!("\n{}\n", reproduction.instructions()));
+        }
         // Ensure there's a blank line if both problem statement and project path are present.
         // The format! macro for project_path already adds a newline at the start if user_message_content is not empty.
 
@@ -413,6 +607,11 @@ impl Agent for TraeAgent {
         // For simplicity here, let's clone it if it exists.
         let project_path_cloned_opt: Option<String> = self.base_agent.project_path.clone();
         let base_commit_cloned_opt: Option<String> = self.base_agent.base_commit.clone();
+        let reproduction = self.reproduction.clone();
+        let execution = self.base_agent.execution.clone();
+        let diff_exclusions = self.base_agent.diff_exclusions();
+        // Set once the agent has called `final_report`, if it has to.
+        let report_delivered = AtomicBool::new(!self.requires_final_report());
 
         let execution_result = common_execute_task_loop(
             &mut self.base_agent,
@@ -420,16 +619,44 @@ impl Agent for TraeAgent {
             event_sender,
             // Pass closures that call the static methods, using captured values
             &|llm_response, step, max_steps| {
-                TraeAgent::fn_should_stop(
+                let reports = llm_response.choices[0].message.tool_calls.iter().flatten();
+                if reports.into_iter().any(|call| call.function.name == FINAL_REPORT_TOOL) {
+                    report_delivered.store(true, Ordering::Relaxed);
+                }
+                let reason = TraeAgent::fn_should_stop(
                     llm_response,
                     step,
                     max_steps,
                     must_patch_val,
                     project_path_cloned_opt.as_deref(),
                     base_commit_cloned_opt.as_deref(), // Pass captured base_commit
-                )
+                    &diff_exclusions,
+                );
+                match (&reason, &reproduction) {
+                    (super::base_agent::StopReason::TaskCompleted, _) if !report_delivered.load(Ordering::Relaxed) => {
+                        super::base_agent::StopReason::ValidationFailed(format!(
+                            "ERROR! Deliver your report with the '{}' tool (summary, changes, verification, \
+                            risks) before completing the task.",
+                            FINAL_REPORT_TOOL
+                        ))
+                    }
+                    (super::base_agent::StopReason::TaskCompleted, Some(reproduction)) => {
+                        TraeAgent::verify_reproduction(reproduction, execution.as_deref().unwrap_or(&HostEnvironment))
+                    }
+                    _ => reason,
+                }
+            },
+            &|llm_response| {
+                let result = TraeAgent::fn_process_llm_response_for_completion(llm_response);
+                match reproduction.as_ref().and_then(|r| r.command()) {
+                    Some(command) => Some(format!(
+                        "{}\nReproduction command `{}` passed.",
+                        result.unwrap_or_default(),
+                        command
+                    )),
+                    None => result,
+                }
             },
-            &|llm_response| TraeAgent::fn_process_llm_response_for_completion(llm_response),
         )
         .await;
 
@@ -548,6 +775,12 @@ mod tests {
                 api_version: None,
                 candidate_count: None,
                 stop_sequences: None,
+                seed: None,
+                extra_headers: None,
+                requests_per_minute: None,
+                tokens_per_minute: None,
+                openrouter: None,
+                network: Default::default(),
             },
         );
         Arc::new(Config {
@@ -557,6 +790,35 @@ mod tests {
             lakeview_config: None,  // Added
             enable_lakeview: false, // Added (or true, depending on test needs)
             working_dir: Some("/tmp".to_string()),
+            output_language: None,
+            heartbeat_interval_secs: crate::config::default_heartbeat_interval_secs(),
+            stuck_step_warning_secs: crate::config::default_stuck_step_warning_secs(),
+            step_timeout_secs: None,
+            network: Default::default(),
+            stream: false,
+            routing: None,
+            regrounding: None,
+            tool_conflict_policy: Default::default(),
+            token_budget: None,
+            tool_caps: None,
+            setup_commands: Vec::new(),
+            setup_timeout_secs: crate::config::default_setup_timeout_secs(),
+            teardown_commands: Vec::new(),
+            teardown_timeout_secs: crate::config::default_teardown_timeout_secs(),
+            check_for_updates: false,
+            probe_capabilities: false,
+            confirm_commands: false,
+            databases: HashMap::new(),
+            binary_patches: Default::default(),
+            pricing: Default::default(),
+            changelog: None,
+            step_extensions: None,
+            audit_log: None,
+            sandbox: None,
+            approval: None,
+            test_command: None,
+            protected_paths: Vec::new(),
+            instruction_files: Vec::new(),
         })
     }
 
@@ -575,16 +837,14 @@ mod tests {
             .expect("Failed to create agent");
 
         let task_desc = "Test task: create a file.".to_string();
-        let mut task_args_map = serde_json::Map::new();
-        task_args_map.insert(
-            "project_path".to_string(),
-            Value::String("/test/path".to_string()),
-        );
-        task_args_map.insert("must_patch".to_string(), Value::Bool(true));
-        let task_args_val = Value::Object(task_args_map);
+        let spec = TaskSpec {
+            project_path: Some("/test/path".to_string()),
+            must_patch: true,
+            ..TaskSpec::default()
+        };
 
         agent
-            .new_task(task_desc.clone(), Some(task_args_val))
+            .new_task(task_desc.clone(), spec)
             .await
             .unwrap();
 
@@ -611,6 +871,135 @@ mod tests {
             .contains("[Project root path]: /test/path"));
     }
 
+    #[tokio::test]
+    async fn test_reproduction_phase_instructions_and_verification() {
+        let project = tempfile::tempdir().unwrap();
+        let reproduction = Arc::new(Reproduction::new(project.path()).unwrap());
+        let mut agent = TraeAgent::try_new(create_test_config(), create_test_tool_registry(), None)
+            .await
+            .expect("Failed to create agent");
+        agent.set_reproduction(Some(reproduction.clone()));
+        agent.new_task("Fix the bug".to_string(), TaskSpec::default()).await.unwrap();
+
+        let user_message = agent.base_agent.conversation_history[1].content.as_deref().unwrap();
+        assert!(user_message.contains("[Reproduction phase]"));
+        assert!(user_message.contains(&reproduction.scratch_dir().display().to_string()));
+
+        assert!(matches!(
+            TraeAgent::verify_reproduction(&reproduction, &HostEnvironment),
+            super::super::base_agent::StopReason::ValidationFailed(_)
+        ));
+        reproduction.record("true");
+        assert_eq!(
+            TraeAgent::verify_reproduction(&reproduction, &HostEnvironment),
+            super::super::base_agent::StopReason::TaskCompleted
+        );
+        std::fs::remove_dir_all(reproduction.scratch_dir()).unwrap();
+    }
+
+    /// Counts its calls in the instance, so a shared instance would show calls of other runs.
+    #[derive(Default)]
+    struct CallCounterTool {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::tools::Tool for CallCounterTool {
+        fn get_name(&self) -> String {
+            "call_counter".to_string()
+        }
+        fn get_description(&self) -> String {
+            "Counts calls".to_string()
+        }
+        fn get_parameters(&self) -> Vec<crate::tools::base::ToolParameter> {
+            Vec::new()
+        }
+        async fn execute(
+            &self,
+            _arguments: serde_json::Value,
+            context: &crate::tools::ToolContext,
+        ) -> Result<crate::tools::base::ToolExecResult, crate::tools::ToolError> {
+            let calls = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            tokio::task::yield_now().await;
+            let root = context.project_root.as_ref().map(|root| root.display().to_string()).unwrap_or_default();
+            Ok(crate::tools::base::ToolExecResult::new_success(Some(format!("{} {}", calls, root)), None))
+        }
+        fn for_run(&self) -> Option<Arc<dyn crate::tools::Tool + Send + Sync>> {
+            Some(Arc::new(Self::default()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_agents_sharing_a_registry_do_not_leak_state() {
+        let mut registry = ToolRegistry::new();
+        registry.register(BashTool::new());
+        registry.register(CallCounterTool::default());
+        let registry = Arc::new(registry);
+        let config = create_test_config();
+        let projects = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+
+        let runs = projects.iter().enumerate().map(|(i, project)| {
+            let (config, registry) = (config.clone(), registry.clone());
+            let project = project.path().display().to_string();
+            async move {
+                let mut agent = TraeAgent::try_new(config, registry, None).await.unwrap();
+                let spec = TaskSpec {
+                    project_path: Some(project.clone()),
+                    ..TaskSpec::default()
+                };
+                agent.new_task(format!("Task {}", i), spec).await.unwrap();
+                let call = |name: &str, arguments: &str| crate::llm::base_client::ToolCall {
+                    id: format!("call_{}", i),
+                    tool_type: "function".to_string(),
+                    function: crate::llm::base_client::ToolCallFunction {
+                        name: name.to_string(),
+                        arguments: arguments.to_string(),
+                    },
+                };
+                let context = agent.base_agent.tool_context();
+                let executor = &agent.base_agent.tool_executor;
+                let mut outputs = Vec::new();
+                for _ in 0..3 {
+                    outputs.push(executor.execute_tool_call(&call("call_counter", "{}"), &context).await.result.unwrap());
+                }
+                let pwd = executor.execute_tool_call(&call("bash", r#"{"command": "pwd"}"#), &context).await;
+                (agent, project, outputs, pwd.result.unwrap_or_default())
+            }
+        });
+        let results = futures::future::join_all(runs).await;
+
+        for (i, (agent, project, outputs, pwd)) in results.iter().enumerate() {
+            let expected: Vec<String> = (1..=3).map(|n| format!("{} {}", n, project)).collect();
+            assert_eq!(outputs, &expected, "each run counts only its own calls");
+            let canonical = std::fs::canonicalize(project).unwrap();
+            assert!(pwd.contains(&canonical.display().to_string()), "{}", pwd);
+            let history = &agent.base_agent.conversation_history;
+            let task_message = history[1].content.as_deref().unwrap();
+            assert!(task_message.contains(&format!("Task {}", i)));
+            assert!(task_message.contains(project.as_str()));
+            assert!(!task_message.contains(&format!("Task {}", 1 - i)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_system_prompt_includes_output_language() {
+        let mut config = (*create_test_config()).clone();
+        config.output_language = Some("Japanese".to_string());
+        let mut agent = TraeAgent::try_new(Arc::new(config), create_test_tool_registry(), None)
+            .await
+            .expect("Failed to create agent");
+        agent.new_task("Explain main.rs".to_string(), TaskSpec::default()).await.unwrap();
+
+        let system_prompt = agent.base_agent.conversation_history[0]
+            .content
+            .as_deref()
+            .unwrap();
+        assert!(system_prompt.starts_with("You are an expert AI software engineering agent."));
+        assert!(system_prompt.contains("Respond to the user in Japanese"));
+        assert!(system_prompt.contains("The current date and time is 2"));
+        assert!(system_prompt.contains("Keep tool calls, tool arguments, code"));
+    }
+
     // Tests for fn_should_stop
     mod test_fn_should_stop {
         use super::*; // To get TraeAgent and its methods, LLMResponse etc.
@@ -636,6 +1025,7 @@ mod tests {
                     finish_reason: Some("stop".to_string()),
                 }],
                 usage: None,
+                rate_limit: None,
                 // system_fingerprint: None, // Removed field
             }
         }
@@ -654,28 +1044,28 @@ mod tests {
         #[test]
         fn test_stop_max_steps_reached() {
             let response = mock_llm_response(Some("hello".to_string()), None);
-            let reason = TraeAgent::fn_should_stop(&response, 5, 5, false, None, None);
+            let reason = TraeAgent::fn_should_stop(&response, 5, 5, false, None, None, &[]);
             assert_eq!(reason, StopReason::MaxStepsReached);
         }
 
         #[test]
         fn test_stop_by_task_done_tool_no_patch_required() {
             let response = mock_llm_response(None, Some(vec![task_done_tool_call()]));
-            let reason = TraeAgent::fn_should_stop(&response, 1, 5, false, None, None);
+            let reason = TraeAgent::fn_should_stop(&response, 1, 5, false, None, None, &[]);
             assert_eq!(reason, StopReason::TaskCompleted);
         }
 
         #[test]
         fn test_stop_by_textual_completion_no_patch_required() {
             let response = mock_llm_response(Some("The task completed successfully.".to_string()), None);
-            let reason = TraeAgent::fn_should_stop(&response, 1, 5, false, None, None);
+            let reason = TraeAgent::fn_should_stop(&response, 1, 5, false, None, None, &[]);
             assert_eq!(reason, StopReason::TaskCompleted);
         }
 
         #[test]
         fn test_continue_if_no_completion_signal() {
             let response = mock_llm_response(Some("Working on it.".to_string()), None);
-            let reason = TraeAgent::fn_should_stop(&response, 1, 5, false, None, None);
+            let reason = TraeAgent::fn_should_stop(&response, 1, 5, false, None, None, &[]);
             assert_eq!(reason, StopReason::Continue);
         }
 
@@ -685,7 +1075,7 @@ mod tests {
         #[test]
         fn test_stop_by_task_done_must_patch_no_project_path() {
             let response = mock_llm_response(None, Some(vec![task_done_tool_call()]));
-            let reason = TraeAgent::fn_should_stop(&response, 1, 5, true, None, None); // must_patch = true, no project_path
+            let reason = TraeAgent::fn_should_stop(&response, 1, 5, true, None, None, &[]); // must_patch = true, no project_path
             assert_eq!(
                 reason,
                 StopReason::ValidationFailed(
@@ -698,7 +1088,7 @@ mod tests {
         #[test]
         fn test_stop_by_textual_completion_must_patch_no_project_path() {
             let response = mock_llm_response(Some("Done.".to_string()), None);
-            let reason = TraeAgent::fn_should_stop(&response, 1, 5, true, None, None); // must_patch = true, no project_path
+            let reason = TraeAgent::fn_should_stop(&response, 1, 5, true, None, None, &[]); // must_patch = true, no project_path
              assert_eq!(
                 reason,
                 StopReason::ValidationFailed(
@@ -738,7 +1128,7 @@ mod tests {
                 "issue": issue_text
             });
 
-            agent.new_task(task_desc.clone(), Some(task_args_val)).await.unwrap();
+            agent.new_task(task_desc.clone(), serde_json::from_value(task_args_val).unwrap()).await.unwrap();
 
             let user_message = agent.base_agent.conversation_history.iter().find(|m| m.role == MessageRole::User).unwrap();
             let expected_problem_statement = format!("[Problem statement]: We're currently solving the following issue within our repository. Here's the issue text:\n{}\n", issue_text);
@@ -764,7 +1154,7 @@ mod tests {
                 "project_path": project_path_text
             }); // No "issue" field
 
-            agent.new_task(task_desc.clone(), Some(task_args_val)).await.unwrap();
+            agent.new_task(task_desc.clone(), serde_json::from_value(task_args_val).unwrap()).await.unwrap();
 
             let user_message = agent.base_agent.conversation_history.iter().find(|m| m.role == MessageRole::User).unwrap();
             let expected_problem_statement = format!("[Problem statement]: {}\n", task_desc); // Fallback
@@ -874,7 +1264,7 @@ mod tests {
                 "project_path": "/tmp/dummy",
                 "patch_path": "/tmp/output.patch"
             });
-            agent.new_task("test".to_string(), Some(task_args)).await.unwrap();
+            agent.new_task("test".to_string(), serde_json::from_value(task_args).unwrap()).await.unwrap();
             assert_eq!(agent.base_agent.patch_path, Some("/tmp/output.patch".to_string()));
         }
     }
@@ -3,101 +3,782 @@
 //! Handles command-line argument parsing and dispatching to appropriate handlers
 //! for the Trae Rust Agent. It uses the `clap` crate for parsing.
 
-use crate::config::Config;
+mod mcp_server;
+mod slash_commands;
+
+use crate::config::{self, Config};
+use crate::recipes;
+use crate::utils::patch_format::{self, PatchFormat};
 use clap::{Parser, Subcommand};
+use slash_commands::{CopyTarget, SlashCommand};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about)]
+#[command(
+    long_about = "Trae Agent is an LLM-based agent for general purpose software engineering tasks.\n\n\
+    It reads a natural-language task, explores the project with its tools (shell, file editor, \
+    JSON editor, structured thinking), makes changes, and reports the result. Runs can be recorded \
+    as trajectories and summarized with Lakeview.\n\n\
+    Configuration is read from trae_config.json (or a Python-style trae_config.yaml); command-line \
+    flags override the file, and API keys fall back to <PROVIDER>_API_KEY environment variables."
+)]
+#[command(after_long_help = "Run `trae examples` for copy-pasteable invocations of common workflows.")]
 #[command(propagate_version = true)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Only print results; suppress progress and all diagnostics except errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Print more diagnostics on stderr (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Also write detailed logs to this file, rotated daily
+    ///
+    /// Example: --log-file logs/trae.log
+    #[arg(long, global = true)]
+    pub log_file: Option<String>,
+    /// Print without colors or syntax highlighting (also set by the NO_COLOR environment variable)
+    #[arg(long, global = true)]
+    pub no_color: bool,
+}
+
+/// Format of the result printed on stdout by `trae run`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Human-readable summary.
+    #[default]
+    Text,
+    /// A single JSON document, suitable for piping.
+    Json,
 }
 
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)] // Parsed once per process; boxing RunArgs gains nothing
 pub enum Commands {
     /// Run a task using Trae Agent
+    #[command(
+        long_about = "Run a single task to completion without interaction.\n\n\
+        The agent works in --working-dir (default: the current directory) until it calls task_done \
+        or reaches --max-steps. With --must-patch the agent may only finish once it has produced a \
+        non-empty patch outside test files; --patch-path saves that patch.",
+        after_long_help = recipes::examples_help_for("run")
+    )]
     Run(RunArgs),
     /// Start an interactive session with Trae Agent
+    #[command(
+        long_about = "Start a conversational session.\n\n\
+        Each line you enter becomes a short task for the agent. Special commands: `config` shows \
+        the active configuration, `clear_history` forgets the conversation, `exit` or `quit` leaves. \
+        `/cd <path>` sets the project directory the agent works in, `/project` shows it and `/diff` \
+        shows its uncommitted changes.",
+        after_long_help = recipes::examples_help_for("interactive")
+    )]
     Interactive(InteractiveArgs),
     /// Show current configuration settings
+    #[command(
+        long_about = "Print the configuration that would be used for a run, after applying defaults.",
+        after_long_help = recipes::examples_help_for("show-config")
+    )]
     ShowConfig(ShowConfigArgs),
     /// Show available tools and their descriptions
     Tools(ToolsArgs),
+    /// Upgrade a dependency and let the agent fix the resulting build breakage
+    #[command(
+        long_about = "Bump a dependency in Cargo.toml, build and test the project, and let the agent \
+        fix any breakage.\n\n\
+        In each iteration the agent may only modify the files that fail to compile plus the manifest, \
+        within a change budget (--max-files, --max-writes). Iterations stop when the build and tests \
+        pass or --max-iterations is reached.",
+        after_long_help = recipes::examples_help_for("upgrade")
+    )]
+    Upgrade(UpgradeArgs),
+    /// Rename a symbol across the codebase, via LSP when available
+    #[command(
+        long_about = "Rename an identifier everywhere it is used.\n\n\
+        If a language server for the project is installed (rust-analyzer, pylsp, \
+        typescript-language-server), its rename is applied directly. Otherwise, or if the result does \
+        not build, the agent performs the rename and the build is re-checked. Every changed site is \
+        reported.",
+        after_long_help = recipes::examples_help_for("refactor")
+    )]
+    Refactor(RefactorArgs),
+    /// Explain a diff hunk by hunk, with risk notes
+    #[command(
+        long_about = "Explain the working tree diff, the staged diff (--staged), a revision range \
+        (--range) or a patch file (--file), hunk by hunk, with a risk rating per hunk and overall \
+        risk notes.",
+        after_long_help = recipes::examples_help_for("explain-diff")
+    )]
+    ExplainDiff(ExplainDiffArgs),
+    /// Convert a trajectory file from the Python implementation to the Rust schema
+    #[command(after_long_help = recipes::examples_help_for("import-trajectory"))]
+    ImportTrajectory(ImportTrajectoryArgs),
+    /// Re-run the task recorded in a trajectory file
+    #[command(
+        long_about = "Run the task recorded in a trajectory again, with the same provider, model, step \
+        limit, project directory, base commit and --must-patch setting.\n\n\
+        With --same-seed the sampling seed recorded in the trajectory is reused, which helps reproduce \
+        flaky agent behavior with providers that support deterministic sampling.",
+        after_long_help = recipes::examples_help_for("replay")
+    )]
+    Replay(ReplayArgs),
+    /// Generate the Lakeview summary of a recorded run
+    #[command(
+        long_about = "Run Lakeview over the steps recorded in a trajectory, without running the agent \
+        again, and write the summary next to the trajectory. Uses the lakeview_config of the \
+        configuration file, whether or not enable_lakeview is set; useful for runs where Lakeview \
+        was disabled or failed.",
+        after_long_help = recipes::examples_help_for("summarize")
+    )]
+    Summarize(SummarizeArgs),
+    /// Print example invocations for common workflows
+    Examples(ExamplesArgs),
+    /// Query past runs recorded in the usage ledger
+    #[command(
+        subcommand,
+        long_about = "Query the usage ledger every `trae run` appends to: when it ran, the model, \
+        the outcome, steps, tokens, estimated cost and labels.\n\n\
+        The ledger is ~/.trae/usage_ledger.jsonl, or the file named by TRAE_USAGE_LEDGER, which \
+        several users can share for per-team reporting.",
+        after_long_help = recipes::examples_help_for("runs")
+    )]
+    Runs(RunsCommand),
+    /// Check configuration files
+    #[command(
+        subcommand,
+        long_about = "Check configuration files without running anything.\n\n\
+        `trae config validate` reports unknown keys (with the closest known key when one looks \
+        like a typo), values of the wrong type and provider names missing from model_providers, \
+        and exits non-zero if it finds any.",
+        after_long_help = recipes::examples_help_for("config")
+    )]
+    Config(ConfigCommand),
+    /// Store provider API keys in the OS keychain
+    #[command(
+        subcommand,
+        long_about = "Keep provider API keys in the OS keychain (macOS Keychain, Windows Credential \
+        Manager, the Secret Service on Linux) instead of plaintext config files.\n\n\
+        A stored key is used when neither --api-key, the config file nor the provider's \
+        environment variable (e.g., OPENAI_API_KEY) provides one. Set TRAE_NO_KEYCHAIN to never \
+        consult the keychain.",
+        after_long_help = recipes::examples_help_for("auth")
+    )]
+    Auth(AuthCommand),
+    /// Update trae to the latest release
+    #[command(
+        long_about = "Replace this binary with the latest GitHub release for this platform.\n\n\
+        The release archive is only installed if its SHA-256 checksum matches the one published \
+        with the release. This catches corrupted downloads, not a tampered release: no signature \
+        is verified. With --check, only report whether a newer release exists. To be told \
+        about new releases when running tasks, set \"check_for_updates\": true in the config.",
+        after_long_help = recipes::examples_help_for("self-update")
+    )]
+    SelfUpdate(SelfUpdateArgs),
+    /// Serve the agent's tools over MCP for editors and other agents
+    #[command(
+        long_about = "Speak the Model Context Protocol on stdin/stdout, so an editor or another agent \
+        can use Trae as a backend.\n\n\
+        The built-in tools (bash, the file editor, search, ...) are offered as MCP tools working in \
+        --working-dir, together with `run_task`, which runs a whole task with `trae run` and returns \
+        its JSON report. Diagnostics go to stderr; stdout carries only protocol messages.",
+        after_long_help = recipes::examples_help_for("mcp-serve")
+    )]
+    McpServe(McpServeArgs),
 }
 
 #[derive(Parser, Debug)]
 pub struct RunArgs {
-    #[arg(index = 1)]
-    pub task: String,
+    /// The task to perform, in natural language; `-` reads it from stdin
+    ///
+    /// Example: "Fix the off-by-one error in src/pagination.rs"
+    #[arg(index = 1, required_unless_present_any = ["task_file", "issue"], conflicts_with = "task_file")]
+    pub task: Option<String>,
+    /// Read the task (e.g., a multi-paragraph issue text) from a file instead of the command line
+    ///
+    /// Example: --task-file issue.md
+    #[arg(long, value_name = "PATH")]
+    pub task_file: Option<PathBuf>,
+    /// LLM provider to use (overrides the config file)
+    ///
+    /// Example: --provider anthropic
     #[arg(short, long)]
     pub provider: Option<String>,
+    /// Model name for the selected provider
+    ///
+    /// Example: --model gpt-4o
     #[arg(short, long)]
     pub model: Option<String>,
+    /// API key for the selected provider (falls back to the config file, then <PROVIDER>_API_KEY)
     #[arg(short, long)]
     pub api_key: Option<String>,
+    /// Maximum number of agent steps
+    ///
+    /// Example: --max-steps 50
     #[arg(long)]
     pub max_steps: Option<u32>,
+    /// Project directory the agent works in (default: a directory mentioned in the task, or the
+    /// git repository containing the current directory; confirmed in confirm mode)
+    ///
+    /// Example: --working-dir /path/to/repo
     #[arg(short, long)]
     pub working_dir: Option<String>,
+    /// Only allow the agent to finish once it has produced a non-test patch
     #[arg(long, short = 'M', alias = "must-patch")]
     pub must_patch: bool,
+    /// Configuration file (JSON, or Python-style YAML)
+    ///
+    /// Example: --config-file trae_config.yaml
     #[arg(long, default_value = "trae_config.json")]
     pub config_file: String,
+    /// Record the run's trajectory to this file
+    ///
+    /// Example: --trajectory-file trajectories/run.json
     #[arg(short, long)]
     pub trajectory_file: Option<String>,
+    /// Save the resulting git diff to this file
+    ///
+    /// Example: --patch-path model.patch
     #[arg(long, short = 'P', alias = "patch-path")]
     pub patch_path: Option<String>,
+    /// Format of the patch saved with --patch-path: git (default), unified, or json with each file's hunks
+    ///
+    /// Example: --patch-format json
+    #[arg(long, value_enum, default_value_t = PatchFormat::Git)]
+    pub patch_format: PatchFormat,
+    /// Commit to diff against when producing the patch (default: uncommitted changes)
+    ///
+    /// Example: --base-commit 4f2a9c1
     #[arg(long, alias = "base-commit")]
     pub base_commit: Option<String>,
+    /// If the project is not a git repository, create a throwaway one for the run so patch
+    /// saving, --must-patch and checkpoints work; it is removed when the run ends
+    #[arg(long)]
+    pub init_git: bool,
+    /// Language for the agent's answers and the Lakeview summary (overrides `output_language`)
+    ///
+    /// Example: --lang Chinese
+    #[arg(long)]
+    pub lang: Option<String>,
+    /// Format of the result printed on stdout; progress always goes to stderr
+    ///
+    /// Example: --output json
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+    /// Abort the run if a single step takes longer than this many seconds (overrides `step_timeout_secs`)
+    ///
+    /// Example: --step-timeout 900
+    #[arg(long)]
+    pub step_timeout: Option<u64>,
+    /// Stream LLM responses and report tool calls while they are being generated (overrides `stream`)
+    ///
+    /// Example: --stream
+    #[arg(long)]
+    pub stream: bool,
+    /// Have the agent write and run a script reproducing the issue before fixing it; the
+    /// recorded reproduction command must pass before the task counts as done
+    ///
+    /// Example: --reproduce
+    #[arg(long)]
+    pub reproduce: bool,
+    /// Package the patch, trajectory, Lakeview summary, verification logs and a manifest into a .tar.gz archive
+    ///
+    /// Example: --bundle artifacts/run.tar.gz
+    #[arg(long, value_name = "PATH")]
+    pub bundle: Option<PathBuf>,
+    /// Sampling seed for providers that support it (overrides `seed`); recorded in the trajectory
+    ///
+    /// Example: --seed 42
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// After a successful run, commit the changes on a new branch; `{summary}` in the message is
+    /// replaced by the agent's summary, which also becomes the commit body
+    ///
+    /// Example: --commit-on-success "fix: {summary}"
+    #[arg(long, value_name = "TEMPLATE")]
+    pub commit_on_success: Option<String>,
+    /// With --commit-on-success, commit on the current branch instead of a new one
+    #[arg(long, requires = "commit_on_success")]
+    pub allow_current_branch: bool,
+    /// Skip the post-mortem written when a run runs out of steps or its completion is rejected
+    #[arg(long)]
+    pub no_post_mortem: bool,
+    /// Skip the Lakeview summary for this run, even if `enable_lakeview` is set
+    #[arg(long)]
+    pub no_lakeview: bool,
+    /// Run this shell command in the project before the agent starts, after the configured
+    /// `setup_commands`; can be repeated
+    ///
+    /// Example: --setup-command "pip install -e ."
+    #[arg(long = "setup-command", value_name = "COMMAND")]
+    pub setup_commands: Vec<String>,
+    /// Run this shell command in the project when the run ends, whether it succeeded or not,
+    /// after the configured `teardown_commands`; can be repeated
+    ///
+    /// Example: --teardown-command "docker compose down"
+    #[arg(long = "teardown-command", value_name = "COMMAND")]
+    pub teardown_commands: Vec<String>,
+    /// Put the final patch on the system clipboard when the run ends
+    #[arg(long)]
+    pub copy_patch: bool,
+    /// Include this file in the task's first message; can be repeated. Files larger than
+    /// --attach-max-bytes are summarized to their beginning, end and error lines
+    ///
+    /// Example: --attach design.md --attach error.log
+    #[arg(long = "attach", value_name = "PATH")]
+    pub attachments: Vec<PathBuf>,
+    /// Size limit of each attached file, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_ATTACHMENT_MAX_BYTES)]
+    pub attach_max_bytes: usize,
+    /// Print what filled the agent's context at the end of the run (system prompt, history by
+    /// role, tool results), with estimated token counts
+    #[arg(long)]
+    pub show_context_usage: bool,
+    /// Print where the run's time went at the end: LLM calls, tool execution and agent
+    /// overhead, LLM latency percentiles per model, and the slowest tool calls
+    #[arg(long)]
+    pub profile: bool,
+    /// Also print the outcome as GitHub Actions annotations: an error if the run or its
+    /// verification failed, a warning on each file whose format changed (on stderr with
+    /// --output json)
+    #[arg(long)]
+    pub gha_annotations: bool,
+    /// Issue the task resolves, as JSON (title, body, repro_steps, environment, linked_files, url,
+    /// labels) or Markdown; without a task, the task is to resolve the issue
+    ///
+    /// Example: --issue issue.md
+    #[arg(long, value_name = "FILE")]
+    pub issue: Option<PathBuf>,
+    /// Label the run for reporting, as key=value (repeatable); recorded in the trajectory and
+    /// the usage ledger
+    ///
+    /// Example: --label team=infra --label ticket=OPS-12
+    #[arg(long = "label", value_name = "KEY=VALUE", value_parser = ledger::parse_label)]
+    pub labels: Vec<(String, String)>,
+    /// Allow no network access except to the configured LLM endpoints, which must be local
+    /// (e.g. http://localhost:11434/v1): tools that need the network are removed, and shell
+    /// commands run without network where the system supports it (Linux `unshare`)
+    #[arg(long)]
+    pub offline: bool,
 }
 
 #[derive(Parser, Debug)]
 pub struct InteractiveArgs {
+    /// LLM provider to use (overrides the config file)
+    ///
+    /// Example: --provider openai
     #[arg(short, long)]
     pub provider: Option<String>,
+    /// Model name for the selected provider
     #[arg(short, long)]
     pub model: Option<String>,
+    /// API key for the selected provider
     #[arg(short, long)]
     pub api_key: Option<String>,
+    /// Configuration file (JSON, or Python-style YAML)
     #[arg(long, default_value = "trae_config.json")]
     pub config_file: String,
+    /// Maximum number of agent steps per session
     #[arg(long, default_value_t = 20)]
     pub max_steps: u32,
+    /// Project directory the agent works in (default: the git repository containing the current
+    /// directory; change it with /cd)
+    ///
+    /// Example: --working-dir /path/to/repo
+    #[arg(short, long)]
+    pub working_dir: Option<String>,
+    /// Record the session's trajectory to this file
     #[arg(short, long)]
     pub trajectory_file: Option<String>,
+    /// Language for the agent's answers (overrides `output_language`)
+    ///
+    /// Example: --lang ja
+    #[arg(long)]
+    pub lang: Option<String>,
+    /// Ask before each shell command the agent runs (sets `confirm_commands`); answers can be
+    /// remembered for the session or, in .trae/permissions.json, for the project
+    #[arg(long)]
+    pub confirm: bool,
+    /// Allow no network access except to the configured LLM endpoints, which must be local
+    /// (e.g. http://localhost:11434/v1): tools that need the network are removed, and shell
+    /// commands run without network where the system supports it (Linux `unshare`)
+    #[arg(long)]
+    pub offline: bool,
 }
 
 #[derive(Parser, Debug)]
 pub struct ShowConfigArgs {
     #[arg(long, default_value = "trae_config.json")]
     pub config_file: String,
+    /// Print every value of the merged system, user and project configs with the file it came from
+    #[arg(long)]
+    pub print_effective_config: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ToolsArgs {
+    /// Show where each tool comes from (built-in, or the namespace of an external backend)
+    #[arg(long)]
+    pub source: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct UpgradeArgs {
+    /// Name of the dependency to upgrade (as it appears in Cargo.toml)
+    #[arg(long)]
+    pub package: String,
+    /// New version requirement for the dependency
+    #[arg(long)]
+    pub to: String,
+    #[arg(short, long)]
+    pub provider: Option<String>,
+    #[arg(short, long)]
+    pub model: Option<String>,
+    #[arg(short, long)]
+    pub api_key: Option<String>,
+    /// Maximum agent steps per fix iteration
+    #[arg(long)]
+    pub max_steps: Option<u32>,
+    #[arg(short, long)]
+    pub working_dir: Option<String>,
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+    /// Manifest to edit (defaults to Cargo.toml in the working directory)
+    #[arg(long)]
+    pub manifest: Option<String>,
+    /// Command used to check that the project builds
+    #[arg(long, default_value = "cargo build --all-targets")]
+    pub build_cmd: String,
+    /// Command used to run the tests once the build succeeds
+    #[arg(long, default_value = "cargo test")]
+    pub test_cmd: String,
+    /// Skip running the test command
+    #[arg(long)]
+    pub no_tests: bool,
+    /// Maximum number of build-and-fix iterations
+    #[arg(long, default_value_t = 5)]
+    pub max_iterations: u32,
+    /// Change budget: maximum number of distinct files the agent may modify per iteration
+    #[arg(long, default_value_t = 20)]
+    pub max_files: usize,
+    /// Change budget: maximum number of write operations the agent may perform per iteration
+    #[arg(long, default_value_t = 100)]
+    pub max_writes: usize,
+}
+
+#[derive(Parser, Debug)]
+pub struct RefactorArgs {
+    /// Symbol rename in the form OldName=NewName
+    #[arg(long)]
+    pub rename: String,
+    #[arg(short, long)]
+    pub provider: Option<String>,
+    #[arg(short, long)]
+    pub model: Option<String>,
+    #[arg(short, long)]
+    pub api_key: Option<String>,
+    #[arg(long)]
+    pub max_steps: Option<u32>,
+    #[arg(short, long)]
+    pub working_dir: Option<String>,
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+    /// Command used to verify the project still compiles (defaults to `cargo build` for Cargo projects)
+    #[arg(long)]
+    pub build_cmd: Option<String>,
+    /// Skip the language server and use the agent directly
+    #[arg(long)]
+    pub no_lsp: bool,
+    /// Maximum number of agent edit-and-verify iterations
+    #[arg(long, default_value_t = 3)]
+    pub max_iterations: u32,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExplainDiffArgs {
+    /// Revision range to explain (e.g., HEAD~3..)
+    #[arg(long, conflicts_with_all = ["staged", "file"])]
+    pub range: Option<String>,
+    /// Explain staged changes instead of the working tree
+    #[arg(long, conflicts_with = "file")]
+    pub staged: bool,
+    /// Explain a patch file instead of the repository state
+    #[arg(long)]
+    pub file: Option<String>,
+    #[arg(short, long)]
+    pub provider: Option<String>,
+    #[arg(short, long)]
+    pub model: Option<String>,
+    #[arg(short, long)]
+    pub api_key: Option<String>,
+    #[arg(short, long)]
+    pub working_dir: Option<String>,
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+    /// Print the raw structured explanation as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Also print the high- and medium-risk hunks and the risk notes as GitHub Actions
+    /// annotations on the diff (on stderr with --json)
+    #[arg(long)]
+    pub gha_annotations: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExamplesArgs {
+    /// Show only the recipe with this name
+    #[arg(index = 1)]
+    pub name: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct SelfUpdateArgs {
+    /// Only check whether a newer release is available
+    #[arg(long)]
+    pub check: bool,
+    /// Install the latest release even if it is not newer than this binary
+    #[arg(long)]
+    pub force: bool,
+    /// Configuration file, for proxy and CA settings (JSON, or Python-style YAML)
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct McpServeArgs {
+    /// Project the tools work in (default: the current directory)
+    #[arg(short, long)]
+    pub working_dir: Option<String>,
+    /// Configuration file for the runs started by `run_task` (JSON, or Python-style YAML)
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+    /// Only offer the tools, not `run_task`
+    #[arg(long)]
+    pub no_run_task: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RunsCommand {
+    /// List recorded runs, oldest first, with totals
+    List(RunsListArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct RunsListArgs {
+    /// Only runs with this label, as key=value (repeatable; all must match)
+    ///
+    /// Example: --label team=infra
+    #[arg(long = "label", value_name = "KEY=VALUE", value_parser = ledger::parse_label)]
+    pub labels: Vec<(String, String)>,
+    /// Only the most recent N matching runs
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+    /// Ledger file to read (default: TRAE_USAGE_LEDGER or ~/.trae/usage_ledger.jsonl)
+    #[arg(long, value_name = "PATH")]
+    pub ledger: Option<PathBuf>,
+    /// Print the matching entries as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Check a config file for unknown keys, typos and missing providers
+    Validate(ConfigValidateArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct ConfigValidateArgs {
+    /// Config file to check
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuthCommand {
+    /// Store the API key of a provider in the OS keychain (read from stdin)
+    Login(AuthLoginArgs),
+    /// Show where each provider's API key comes from
+    Status(AuthStatusArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct AuthLoginArgs {
+    /// Provider the key is for, as named in model_providers (e.g., openai)
+    #[arg(index = 1)]
+    pub provider: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct AuthStatusArgs {
+    /// Config file whose providers are listed
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct ImportTrajectoryArgs {
+    /// Trajectory file written by the Python trae-agent
+    #[arg(index = 1)]
+    pub input: String,
+    /// Where to write the converted trajectory (defaults to <input>.rs.json)
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct SummarizeArgs {
+    /// Trajectory file of the run to summarize
+    #[arg(index = 1)]
+    pub trajectory: PathBuf,
+    /// Configuration file (JSON, or Python-style YAML); its lakeview_config selects the model
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+    /// Where to write the summary (default: <trajectory>.lakeview.md next to the trajectory)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
 }
 
 #[derive(Parser, Debug)]
-pub struct ToolsArgs {} // No arguments needed for listing tools
+pub struct ReplayArgs {
+    /// Trajectory file of the run to replay
+    #[arg(index = 1)]
+    pub trajectory: PathBuf,
+    /// Reuse the sampling seed recorded in the trajectory
+    #[arg(long)]
+    pub same_seed: bool,
+    /// Re-run the recorded tool calls in --working-dir (a copy of the project) instead of
+    /// asking the model again; side-effecting tools return their recorded results
+    #[arg(long, requires = "working_dir")]
+    pub re_execute: bool,
+    /// Configuration file (JSON, or Python-style YAML)
+    #[arg(long, default_value = "trae_config.json")]
+    pub config_file: String,
+    /// Project directory to run in (default: the one recorded in the trajectory)
+    #[arg(short, long)]
+    pub working_dir: Option<String>,
+    /// Record the replay's trajectory to this file
+    #[arg(short, long)]
+    pub trajectory_file: Option<String>,
+    /// Format of the result printed on stdout
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+}
 
-use crate::agent::base_agent::AgentEvent; // Removed AgentStep, AgentExecution
-use crate::agent::{Agent, TraeAgent};
+use crate::agent::base_agent::{AgentEvent, AgentExecution};
+use crate::agent::context_usage::ContextUsage;
+use crate::agent::profile::RunProfile;
+use crate::agent::step_extension::{ExtensionPrompt, StepExtensions, TerminalExtensionPrompt};
+use crate::agent::step_forecast::{StepBudgetPrompt, StepHistory, TerminalStepBudgetPrompt};
+use crate::agent::step_stream::AgentStepUpdate;
+use futures::StreamExt;
+use crate::agent::{Agent, Issue, TaskSpec, TraeAgent};
 use crate::llm::base_client::LLMMessage;
-use crate::llm::LLMClient; // Restored LLMClient for Lakeview type annotations
+use crate::llm::create_client;
 use crate::llm::MessageRole; // Added import for MessageRole
                              // OpenAIClient is used by TraeAgent internally, not directly needed here for handle_interactive
                              // LLMClient is used by TraeAgent internally
                              // Tool specific imports (BashTool, EditTool etc.) are not needed as ToolRegistry handles them.
-use crate::tools::ToolRegistry;
+use crate::tools::execution::{DockerEnvironment, ExecutionEnvironment, HostEnvironment};
+use crate::tools::{
+    BashTool, CheckpointTool, DbQueryTool, GetSnippetTool, LogInspectTool, ReadMoreTool, ReproductionTool,
+    RequestExtensionTool, RestoreCheckpointTool, SaveSnippetTool, ToolContext, ToolRegistry,
+};
+use crate::utils::attachments::{Attachment, DEFAULT_ATTACHMENT_MAX_BYTES};
+use crate::utils::approval::{ApprovalGate, TerminalApprovalPrompt};
+use crate::utils::audit_log::{AuditEntry, AuditLog};
+use crate::utils::auto_commit::{self, AutoCommit};
+use crate::utils::changelog::{self, Changelog};
+use crate::utils::bundle::RunBundle;
+use crate::utils::checkpoints::CheckpointStore;
+use crate::utils::cleanup::{CleanupReport, RunCleanup};
+use crate::utils::cost::{CostBreakdown, CostTracker};
+use crate::utils::file_integrity::{self, IntegrityIssue};
+use crate::utils::gha;
+use crate::utils::environment::RunEnvironment;
+use crate::utils::git_utils::{NestedRepoChange, NestedRepoKind};
+use crate::utils::guards::WriteGuard;
+use crate::utils::highlight::{self, Stream};
+use crate::utils::keychain;
+use crate::utils::ledger::{self, LedgerEntry};
+use crate::utils::offline;
+use crate::utils::permissions::{CommandPermissions, TerminalPrompt, PERMISSIONS_FILE};
+use crate::utils::post_mortem;
+use crate::utils::project_inference::{confirm_inferred, infer_project_path};
+use crate::utils::reproduction::Reproduction;
+use crate::utils::result_store::ResultStore;
+use crate::utils::scratch::{self, RunScratch};
+use crate::utils::self_update;
+use crate::utils::snippet_store::SnippetStore;
+use crate::utils::throwaway_repo::{is_git_work_tree, ThrowawayRepo};
+use crate::utils::trajectory_recorder::{record_cleanup, record_cost, Trajectory};
+use crate::utils::usage::UsageTracker;
 
 // Removed: mod cli_tools_handler;
 
+use anyhow::Context;
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+/// Resolves the task text of `trae run` from the positional argument, `-` (stdin) or `--task-file`.
+///
+/// # Returns
+/// The task with surrounding whitespace removed, or an error if it cannot be read or is empty.
+fn resolve_task(
+    task: Option<&str>,
+    task_file: Option<&Path>,
+    stdin: impl std::io::Read,
+) -> anyhow::Result<String> {
+    let text = match (task, task_file) {
+        (_, Some(path)) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read task file {}", path.display()))?,
+        (Some("-"), None) => {
+            let mut text = String::new();
+            let mut stdin = stdin;
+            stdin
+                .read_to_string(&mut text)
+                .context("Failed to read the task from stdin")?;
+            text
+        }
+        (Some(task), None) => task.to_string(),
+        (None, None) => anyhow::bail!("No task given; pass it as an argument, `-` for stdin, or --task-file"),
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        anyhow::bail!("The task is empty");
+    }
+    Ok(text.to_string())
+}
 
 pub async fn handle_run(args: RunArgs) -> anyhow::Result<()> {
-    info!("Starting 'run' command with task: {}", args.task);
+    let issue = args.issue.as_deref().map(Issue::load).transpose()?;
+    let task = match &issue {
+        Some(issue) if args.task.is_none() && args.task_file.is_none() => issue.summary(),
+        _ => resolve_task(
+            args.task.as_deref(),
+            args.task_file.as_deref(),
+            std::io::stdin().lock(),
+        )?,
+    };
+    info!("Starting 'run' command with task: {}", task);
+    crate::utils::crash::set_config(Path::new(&args.config_file), args.working_dir.as_deref().map(Path::new));
+    let attachments = args
+        .attachments
+        .iter()
+        .map(|path| Attachment::load(path, args.attach_max_bytes))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for attachment in attachments.iter().filter(|a| a.summarized) {
+        info!(
+            "Attachment {} ({} bytes) was summarized to fit --attach-max-bytes",
+            attachment.name, attachment.original_bytes
+        );
+    }
 
     let config = match Config::load(
         &args.config_file,
@@ -107,7 +788,26 @@ pub async fn handle_run(args: RunArgs) -> anyhow::Result<()> {
         args.max_steps,
         args.working_dir.clone(),
     ) {
-        Ok(cfg) => Arc::new(cfg),
+        Ok(mut cfg) => {
+            if let Some(lang) = args.lang.clone() {
+                cfg.output_language = Some(lang);
+            }
+            if let Some(step_timeout) = args.step_timeout {
+                cfg.step_timeout_secs = Some(step_timeout);
+            }
+            if args.stream {
+                cfg.stream = true;
+            }
+            if let Some(seed) = args.seed {
+                if let Some(provider) = cfg.model_providers.get_mut(&cfg.default_provider) {
+                    provider.seed = Some(seed);
+                }
+            }
+            cfg.setup_commands.extend(args.setup_commands.iter().cloned());
+            cfg.teardown_commands.extend(args.teardown_commands.iter().cloned());
+            apply_inferred_project(&mut cfg, Some(&task), args.working_dir.is_some());
+            Arc::new(cfg)
+        }
         Err(e) => {
             error!("Failed to load configuration: {:?}", e);
             return Err(e);
@@ -117,16 +817,120 @@ pub async fn handle_run(args: RunArgs) -> anyhow::Result<()> {
         "Configuration loaded successfully. Default provider: {}",
         config.default_provider
     );
+    if args.offline {
+        enable_offline_mode(&config)?;
+    }
 
-    let tool_registry = Arc::new(ToolRegistry::default());
+    let project_root = match &config.working_dir {
+        Some(wd) => PathBuf::from(wd),
+        None => std::env::current_dir()?,
+    };
+    // Kept until the end of the run, when the throwaway repository is removed.
+    let _throwaway_repo = throwaway_repo_for(&project_root, args.init_git);
+    let run_scratch = match RunScratch::create(&project_root, &scratch::run_id(args.trajectory_file.as_deref().map(Path::new))) {
+        Ok(run_scratch) => {
+            info!("Scratch directory: {}", run_scratch.dir().display());
+            Some(run_scratch)
+        }
+        Err(e) => {
+            warn!("Running without a scratch directory: {:#}", e);
+            None
+        }
+    };
+
+    let reproduction = if args.reproduce {
+        let reproduction = match &run_scratch {
+            Some(run_scratch) => Reproduction::in_dir(project_root.clone(), run_scratch.dir().join("reproduction"))?,
+            None => Reproduction::new(project_root.clone())?,
+        };
+        let reproduction = Arc::new(reproduction);
+        info!(
+            "Reproduction phase enabled; scratch directory: {}",
+            reproduction.scratch_dir().display()
+        );
+        Some(reproduction)
+    } else {
+        None
+    };
+
+    let trajectory_path_buf = args.trajectory_file.clone().map(PathBuf::from);
+    // Background processes and temp directories left by the run are removed when it ends.
+    let cleanup = Arc::new(RunCleanup::new());
+
+    let bash = |tool: BashTool| if args.offline { tool.without_network() } else { tool };
+    let mut tool_registry = ToolRegistry::default();
+    tool_registry.set_conflict_policy(config.tool_conflict_policy);
+    if let Some(reproduction) = &reproduction {
+        tool_registry.register(ReproductionTool::new(reproduction.clone()));
+    }
+    // Clipped command outputs are kept in full so the agent can page through them.
+    match ResultStore::for_run(trajectory_path_buf.as_deref()) {
+        Ok(store) => {
+            if trajectory_path_buf.is_none() {
+                cleanup.track_path(store.dir());
+            }
+            let store = Arc::new(store);
+            tool_registry.register(bash(BashTool::with_result_store(store.clone()).track_processes(cleanup.clone())));
+            tool_registry.register(ReadMoreTool::new(store));
+        }
+        Err(e) => {
+            warn!("Clipped tool outputs will not be stored: {:#}", e);
+            tool_registry.register(bash(BashTool::new().track_processes(cleanup.clone())));
+        }
+    }
+    match SnippetStore::for_run(trajectory_path_buf.as_deref()) {
+        Ok(store) => {
+            if trajectory_path_buf.is_none() {
+                cleanup.track_path(store.dir());
+            }
+            let store = Arc::new(store);
+            tool_registry.register(SaveSnippetTool::new(store.clone()));
+            tool_registry.register(GetSnippetTool::new(store));
+        }
+        Err(e) => warn!("Snippet tools are unavailable: {:#}", e),
+    }
+    // Checkpoints need git; projects that are not repositories run without them.
+    if let Some(project_path) = &config.working_dir {
+        match CheckpointStore::new(Path::new(project_path)) {
+            Ok(store) => {
+                let store = Arc::new(store);
+                tool_registry.register(CheckpointTool::new(store.clone()));
+                tool_registry.register(RestoreCheckpointTool::new(store));
+            }
+            Err(e) => debug!("Checkpoint tools are unavailable: {:#}", e),
+        }
+    }
+    // With a remote database configured, `--offline` removes the tool below.
+    if !config.databases.is_empty() {
+        tool_registry.register(DbQueryTool::new().with_databases(config.databases.clone()));
+    }
+    let step_extensions = config.step_extensions.clone().map(|extension_config| {
+        let prompt = (extension_config.policy == config::ExtensionPolicy::Ask)
+            .then(|| Arc::new(TerminalExtensionPrompt) as Arc<dyn ExtensionPrompt>);
+        Arc::new(StepExtensions::new(extension_config, prompt))
+    });
+    if let Some(extensions) = &step_extensions {
+        tool_registry.register(RequestExtensionTool::new(extensions.clone()));
+    }
+    // Log summaries use the default provider, which `--offline` requires to be local.
+    if let Ok(provider_config) = config.get_current_provider_config() {
+        match create_client(&config.default_provider, provider_config).await {
+            Ok(client) => tool_registry.register(
+                LogInspectTool::new().with_summarizer(client, provider_config.base_url.as_deref()),
+            ),
+            Err(e) => debug!("Log summaries are unavailable: {}", e),
+        }
+    }
+    if args.offline {
+        remove_network_tools(&mut tool_registry);
+    }
+    let tool_registry = Arc::new(tool_registry);
     info!(
         "ToolRegistry initialized with {} tools.",
         tool_registry.get_all_tools_arc().len()
     );
 
-    let trajectory_path_buf = args.trajectory_file.map(PathBuf::from);
-
-    let mut agent = match TraeAgent::try_new(config.clone(), tool_registry.clone(), trajectory_path_buf).await {
+    let mut agent = match TraeAgent::try_new(config.clone(), tool_registry.clone(), trajectory_path_buf.clone()).await {
         Ok(ag) => ag,
         Err(e) => {
             error!("Failed to create TraeAgent: {:?}", e);
@@ -134,487 +938,2982 @@ pub async fn handle_run(args: RunArgs) -> anyhow::Result<()> {
         }
     };
     info!("TraeAgent created successfully: {}", agent.get_name());
+    agent.set_reproduction(reproduction.clone());
+    let run_id = run_scratch.as_ref().map(|s| s.id().to_string());
+    agent.set_scratch(run_scratch);
+    // The container is removed when the last handle to it is dropped, at the end of the run.
+    let sandbox: Option<Arc<dyn ExecutionEnvironment>> = match &config.sandbox {
+        Some(sandbox_config) => {
+            let sandbox = DockerEnvironment::start(sandbox_config, &project_root, run_id.as_deref(), args.offline).await?;
+            info!("Commands and file edits run in {}", sandbox.describe());
+            Some(Arc::new(sandbox))
+        }
+        None => None,
+    };
+    agent.set_execution_environment(sandbox.clone());
+    let execution: &dyn ExecutionEnvironment = sandbox.as_deref().unwrap_or(&HostEnvironment);
+    agent.set_command_permissions(command_permissions(&config, &project_root));
+    agent.set_write_guard(protected_paths_guard(&config, &project_root));
+    let audit_log = open_audit_log(&config)?;
+    agent.set_audit_log(audit_log.clone());
+    // Hook commands are audited as part of the run, like the agent's own commands.
+    let hook_context = ToolContext {
+        run_id: run_id.clone(),
+        ..ToolContext::for_project(&project_root)
+    };
+    agent.set_approval_gate(approval_gate(&config));
+    let (step_history, step_prompt) = step_budget_forecast(&config, config.confirm_commands);
+    agent.set_step_budget_forecast(step_history, step_prompt);
+    agent.set_step_extensions(step_extensions);
 
-    let mut task_agent_args = serde_json::Map::new();
-    if let Some(wd) = &config.working_dir {
-        task_agent_args.insert(
-            "project_path".to_string(),
-            serde_json::Value::String(wd.clone()),
-        );
-    }
-    task_agent_args.insert(
-        "must_patch".to_string(),
-        serde_json::Value::Bool(args.must_patch),
+    let environment = RunEnvironment::capture(
+        config.working_dir.as_deref().map(Path::new),
+        Some(Path::new(&args.config_file)),
     );
+    debug!(environment = ?environment, "Captured run environment");
+    agent.set_run_environment(environment.clone());
 
-    let patch_path_clone_for_task_args = args.patch_path.clone();
-    if let Some(pp) = patch_path_clone_for_task_args {
-        task_agent_args.insert("patch_path".to_string(), serde_json::Value::String(pp));
-    }
-    if let Some(bc) = args.base_commit.clone() {
-        task_agent_args.insert("base_commit".to_string(), serde_json::Value::String(bc));
+    if !config.setup_commands.is_empty() {
+        use crate::utils::setup_hooks::{run_hook_commands, setup_summary};
+        if !crate::utils::logging::is_quiet() {
+            eprintln!("Running {} setup command(s)...", config.setup_commands.len());
+        }
+        let outcomes = run_hook_commands(
+            &config.setup_commands,
+            &project_root,
+            std::time::Duration::from_secs(config.setup_timeout_secs),
+            execution,
+        )
+        .await;
+        for outcome in &outcomes {
+            if outcome.success {
+                info!("Setup command '{}' succeeded in {:?}", outcome.command, outcome.duration);
+            } else {
+                warn!("Setup command '{}' failed: {}", outcome.command, outcome.output.trim());
+            }
+            if let Some(audit_log) = &audit_log {
+                let entry = AuditEntry::for_hook("setup_commands", &outcome.command, &hook_context);
+                let error = (!outcome.success).then_some(outcome.output.as_str());
+                audit_log.record(&entry.with_outcome(outcome.success, error, Some(outcome.duration.as_millis())));
+            }
+        }
+        agent.set_setup_summary(Some(setup_summary(&outcomes)));
     }
 
-    if let Err(e) = agent
-        .new_task(
-            args.task.clone(),
-            Some(serde_json::Value::Object(task_agent_args)),
-        )
-        .await
-    {
+    let spec = TaskSpec {
+        project_path: config.working_dir.clone(),
+        must_patch: args.must_patch,
+        base_commit: args.base_commit.clone(),
+        patch_path: args.patch_path.clone(),
+        attachments,
+        issue,
+        labels: args.labels.iter().cloned().collect(),
+        ..TaskSpec::default()
+    };
+    if let Err(e) = agent.new_task(task.clone(), spec).await {
         error!("Failed to setup new task for agent: {:?}", e);
         return Err(anyhow::anyhow!("Task setup failed: {}", e));
     }
-    info!("New task '{}' initialized for agent.", args.task);
+    info!("New task '{}' initialized for agent.", task);
 
     let (event_tx, mut event_rx) = mpsc::channel(100);
 
+    let quiet = crate::utils::logging::is_quiet();
     let console_updater_task = tokio::spawn(async move {
+        // Progress is a diagnostic: it goes to stderr so stdout only carries the result.
         while let Some(event) = event_rx.recv().await {
-            match event {
-                AgentEvent::StepBegin(step_num) => {
-                    println!("\n[AGENT EVENT] Step {} Starting...", step_num);
-                }
-                AgentEvent::StepStateChange(_step_num, new_state) => {
-                    println!("[AGENT EVENT] State: {:?}", new_state);
-                }
-                AgentEvent::LLMRequestSent(_step_num, messages) => {
-                    if let Some(last_msg) = messages.last() {
-                        let content_preview = last_msg.content.as_deref().unwrap_or_default();
-                        println!(
-                            "[AGENT EVENT] Sending to LLM (last msg role: {:?}): {:.100}...",
-                            last_msg.role, content_preview
-                        );
-                    }
-                }
-                AgentEvent::LLMResponseReceived(_step_num, response) => {
-                    if let Some(choice) = response.choices.first() {
-                        let content_preview = choice.message.content.as_deref().unwrap_or_default();
-                        println!("[AGENT EVENT] LLM Response: {:.100}...", content_preview);
-                        if let Some(tool_calls) = &choice.message.tool_calls {
-                            if !tool_calls.is_empty() {
-                                println!(
-                                    "[AGENT EVENT] LLM requested {} tool call(s).",
-                                    tool_calls.len()
-                                );
-                            }
-                        }
-                    }
-                }
-                AgentEvent::ToolCallAttempt(_step_num, tool_call) => {
-                    println!(
-                        "[AGENT EVENT] Tool call: {} with args {:.100}...",
-                        tool_call.function.name, tool_call.function.arguments
-                    );
-                }
-                AgentEvent::ToolCallResult(_step_num, tool_result) => {
-                    let result_preview = tool_result
-                        .result
-                        .as_deref()
-                        .or(tool_result.error.as_deref())
-                        .unwrap_or_default();
-                    println!(
-                        "[AGENT EVENT] Tool result (ID: {}): Success: {}, Details: {:.100}...",
-                        tool_result.tool_call_id, tool_result.success, result_preview
-                    );
-                }
-                AgentEvent::TaskCompleted(_) | AgentEvent::TaskFailed(_) => { /* Final summary handles this */
-                }
-                AgentEvent::StatusUpdate(msg) => {
-                    println!("[AGENT EVENT] Status: {}", msg);
-                }
+            crate::utils::crash::record_event(&event);
+            if quiet {
+                continue;
             }
+            render_agent_event(event);
         }
     });
 
-    let execution_result = match agent.execute_task(Some(event_tx)).await {
+    let execution_outcome = agent.execute_task(Some(event_tx)).await;
+
+    // `execute_task` consumed `event_tx`, so console_updater_task finishes once it has
+    // processed the remaining events.
+    if let Err(e) = console_updater_task.await {
+        error!("Console updater task panicked or was cancelled: {:?}", e);
+    }
+
+    let context_usage = args.show_context_usage.then(|| agent.context_usage());
+
+    if let Ok(execution) = &execution_outcome {
+        record_in_ledger(&config, execution, &args.labels, trajectory_path_buf.as_deref());
+    }
+
+    // Cleanup runs whether the task succeeded or not.
+    let cleanup_report = finish_run_cleanup(
+        &config,
+        &cleanup,
+        &project_root,
+        trajectory_path_buf.as_deref(),
+        execution,
+        audit_log.as_deref(),
+        &hook_context,
+    )
+    .await;
+
+    let execution_result = match execution_outcome {
         Ok(exec_res) => {
             info!("Task execution process finished by agent logic.");
             exec_res
         }
         Err(e) => {
             error!("Task execution failed with an error: {:?}", e);
-            // `agent` is dropped here, closing `event_tx`.
-            // `console_updater_task` will complete once it processes remaining events.
-            // We await it *after* this match block.
+            if let Some(hint) = e.hint() {
+                return Err(anyhow::anyhow!("Task execution error: {}\nHint: {}", e, hint));
+            }
             return Err(anyhow::anyhow!("Task execution error: {}", e));
         }
     };
 
-    // Drop agent explicitly here if not already out of scope, to ensure event_tx is dropped.
-    // Actually, agent is dropped after the match if Ok, or before return if Err.
-    // So event_tx will be dropped, allowing console_updater_task to finish.
-    if let Err(e) = console_updater_task.await {
-        error!("Console updater task panicked or was cancelled: {:?}", e);
-    }
+    // Patch export, Lakeview and the post-mortem are independent; they run concurrently so
+    // the summary is not held up by the slowest of them.
+    let diff_exclusions = agent.diff_exclusions();
+    let patch_export = {
+        let project_path = config.working_dir.clone();
+        let base_commit = args.base_commit.clone();
+        let exclusions = diff_exclusions.clone();
+        let save_to = args.patch_path.clone().filter(|_| execution_result.success || args.must_patch);
+        let format = args.patch_format;
+        let include_binary = config.binary_patches == config::BinaryPatches::Include;
+        let copy = args.copy_patch;
+        async move {
+            tokio::task::spawn_blocking(move || {
+                let save_to = save_to.as_deref();
+                export_patch(project_path.as_deref(), base_commit.as_deref(), &exclusions, save_to, format, include_binary, copy)
+            })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Patch export task failed: {}", e);
+                (None, false, Vec::new())
+            })
+        }
+    };
 
-    println!("\n--- Task Execution Summary ---");
-    println!("Task: {}", execution_result.task);
-    println!("Success: {}", execution_result.success);
-    if let Some(end_time) = execution_result.end_time {
-        println!(
-            "Execution Time: {}s",
-            end_time - execution_result.start_time
-        );
-    } else {
-        println!("Execution Time: Not available (task did not set end time)");
-    }
-    println!("Total Steps: {}", execution_result.steps.len());
-    if let Some(tokens) = &execution_result.total_tokens_used { // Borrow tokens
+    let lakeview = async {
+        if !config.enable_lakeview {
+            return None;
+        }
+        if args.no_lakeview {
+            info!("Lakeview disabled for this run (--no-lakeview).");
+            return None;
+        }
+        if config.lakeview_config.is_none() {
+            info!("Lakeview enabled but no specific lakeview_config found. Skipping summary.");
+            return None;
+        }
+        info!("Lakeview enabled, attempting to generate summary...");
+        match generate_lakeview_summary(&config, &execution_result).await {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                error!("Failed to generate Lakeview summary: {:#}", e);
+                None
+            }
+        }
+    };
+
+    let post_mortem = async {
+        if args.no_post_mortem {
+            return None;
+        }
+        write_post_mortem(&config, &execution_result, trajectory_path_buf.as_deref()).await
+    };
+
+    let ((saved_patch_path, patch_copied, binary_files), lakeview_summary, post_mortem) =
+        tokio::join!(patch_export, lakeview, post_mortem);
+
+    let mut report = RunReport::new(
+        &execution_result,
+        saved_patch_path.clone(),
+        lakeview_summary.clone(),
+    );
+    report.context_usage = context_usage;
+    report.profile = args.profile.then(|| RunProfile::from_execution(&execution_result));
+    report.cost = run_cost(&config, &execution_result, trajectory_path_buf.as_deref());
+    report.post_mortem = post_mortem;
+    report.cleanup = cleanup_report;
+    report.reproduction_command = reproduction.as_ref().and_then(|r| r.command());
+    report.environment = Some(environment);
+    report.binary_files = binary_files;
+    report.nested_repos = nested_repo_changes(
+        config.working_dir.as_deref(),
+        args.base_commit.as_deref(),
+        saved_patch_path.as_deref(),
+    );
+    report.integrity_issues = integrity_issues(config.working_dir.as_deref(), args.base_commit.as_deref());
+
+    if let Some(bundle_path) = &args.bundle {
+        // The current diff of the project, so the patch is included even without --patch-path.
+        let include_binary = config.binary_patches == config::BinaryPatches::Include;
+        let patch = config.working_dir.as_deref().map(|project_path| {
+            crate::utils::git_utils::get_patch_excluding(
+                project_path,
+                args.base_commit.as_deref(),
+                &diff_exclusions,
+                include_binary,
+            )
+        });
+        if let Some(Ok(patch)) = &patch {
+            report.binary_files = patch.binary_files.clone();
+        }
+        let patch = patch.map(|patch| patch.map(|patch| patch.diff));
+        match write_run_bundle(
+            bundle_path,
+            &report,
+            &execution_result,
+            patch,
+            trajectory_path_buf.as_deref(),
+            reproduction.as_deref(),
+        ) {
+            Ok(()) => {
+                info!("Run bundle saved to {}", bundle_path.display());
+                report.bundle_path = Some(bundle_path.display().to_string());
+            }
+            Err(e) => error!("Failed to write run bundle to {}: {:#}", bundle_path.display(), e),
+        }
+    }
+
+    // Before the commit, so a changelog file entry is part of it.
+    let changelog = match (&config.changelog, &config.working_dir) {
+        (Some(changelog_config), Some(project_path)) if execution_result.success => write_changelog(
+            changelog_config,
+            Path::new(project_path),
+            &execution_result,
+            args.base_commit.as_deref(),
+            &diff_exclusions,
+            trajectory_path_buf.as_deref(),
+            &mut report,
+        ),
+        _ => None,
+    };
+
+    // Committed last, so the patch and bundle above still see the changes as uncommitted.
+    if let Some(template) = &args.commit_on_success {
+        if !execution_result.success {
+            info!("Task did not succeed; skipping --commit-on-success.");
+        } else if let Some(project_path) = &config.working_dir {
+            let changelog = changelog
+                .as_ref()
+                .filter(|_| config.changelog.as_ref().is_some_and(|c| c.commit))
+                .map(Changelog::to_markdown);
+            match commit_run_changes(
+                Path::new(project_path),
+                template,
+                &execution_result,
+                changelog.as_deref(),
+                args.allow_current_branch,
+            ) {
+                Ok(commit) => {
+                    info!(branch = %commit.branch, commit = %commit.commit, "Committed the run's changes");
+                    report.commit = Some(commit);
+                }
+                Err(e) => error!("Failed to commit the run's changes: {:#}", e),
+            }
+        } else {
+            error!("Cannot commit the run's changes, project working directory not known.");
+        }
+    }
+
+    match args.output {
+        OutputFormat::Text => {
+            print_run_summary(
+                &execution_result,
+                saved_patch_path.as_deref(),
+                lakeview_summary.as_deref(),
+            );
+            if let Some(post_mortem) = &report.post_mortem {
+                println!("\n--- Post-mortem ---");
+                println!("{}", post_mortem);
+            }
+            if let Some(bundle_path) = &report.bundle_path {
+                println!("Bundle saved to: {}", bundle_path);
+            }
+            if let Some(pr_body_path) = &report.pr_body_path {
+                println!("Pull request body saved to: {}", pr_body_path);
+            }
+            if let Some(commit) = &report.commit {
+                println!("Committed {} on branch {}", commit.commit, commit.branch);
+            }
+            if patch_copied {
+                println!("Patch copied to the clipboard.");
+            }
+            if !report.binary_files.is_empty() {
+                println!("Binary files left out of the patch: {}", report.binary_files.join(", "));
+            }
+            for repo in &report.nested_repos {
+                println!("{}", describe_nested_repo_change(repo));
+            }
+            if !report.integrity_issues.is_empty() {
+                println!("\n--- File Integrity ---");
+                println!("Files whose encoding or line endings changed; check that this was intended:");
+                for issue in &report.integrity_issues {
+                    println!("  {}", issue.describe());
+                }
+            }
+            if let Some(usage) = &report.context_usage {
+                println!("\n--- Context Usage ---");
+                println!("{}", usage.format());
+            }
+            if let Some(cost) = &report.cost {
+                println!("\n--- Cost ---");
+                println!("{}", cost.format());
+            }
+            if let Some(profile) = &report.profile {
+                println!("\n--- Profile ---");
+                println!("{}", profile.format());
+            }
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+    if args.gha_annotations {
+        let annotations = gha::run_annotations(report.success, report.error_message, &report.integrity_issues);
+        print_annotations(&annotations, args.output == OutputFormat::Json);
+    }
+
+    print_update_notice(&config).await;
+
+    // Trajectory is now saved internally by TrajectoryRecorder when finalize_recording is called
+    // within the agent's execution loop if a path was provided during agent initialization.
+    // This explicit block is no longer needed.
+    // if let Some(traj_path_str) = args.trajectory_file { ... }
+
+    Ok(())
+}
+
+/// Saves the run's patch to `save_to` (`--patch-path`) and copies it to the clipboard
+/// (`--copy-patch`), as requested. The saved patch is written in `format`; the copy is
+/// always the git diff, ready for `git apply`. Binary changes are included as git binary
+/// patches if `include_binary`, otherwise left out and returned. Failures are logged and do
+/// not fail the run.
+///
+/// # Returns
+/// The path the patch was saved to, if it was, whether it was copied, and the binary files
+/// left out of it.
+fn export_patch(
+    project_path: Option<&str>,
+    base_commit: Option<&str>,
+    exclusions: &[PathBuf],
+    save_to: Option<&str>,
+    format: PatchFormat,
+    include_binary: bool,
+    copy: bool,
+) -> (Option<String>, bool, Vec<String>) {
+    if save_to.is_none() && !copy {
+        return (None, false, Vec::new());
+    }
+    let Some(project_path) = project_path else {
+        error!("Cannot save or copy the patch, project working directory not known.");
+        return (None, false, Vec::new());
+    };
+    let patch = match crate::utils::git_utils::get_patch_excluding(project_path, base_commit, exclusions, include_binary) {
+        Ok(patch) => patch,
+        Err(e) => {
+            error!("Failed to get git diff for the patch: {}", e);
+            return (None, false, Vec::new());
+        }
+    };
+    let diff = patch.diff;
+    if !patch.binary_files.is_empty() {
+        warn!(
+            "Binary file changes left out of the patch (binary_patches is \"list\"): {}",
+            patch.binary_files.join(", ")
+        );
+    }
+
+    let saved = save_to.and_then(|path| match std::fs::write(path, patch_format::render(&diff, format)) {
+        Ok(()) => {
+            info!("Patch file saved to {}", path);
+            Some(path.to_string())
+        }
+        Err(e) => {
+            error!("Failed to write patch file to {}: {}", path, e);
+            None
+        }
+    });
+    let copied = copy
+        && match crate::utils::clipboard::copy_text(&diff) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Could not copy the patch to the clipboard: {}", e);
+                false
+            }
+        };
+    (saved, copied, patch.binary_files)
+}
+
+/// The changes in the project's submodules and nested repositories, which its patch leaves
+/// out. With a saved patch, each one's diff is saved next to it (see `nested_patch_path`).
+fn nested_repo_changes(project_path: Option<&str>, base_commit: Option<&str>, saved_patch: Option<&str>) -> Vec<NestedRepoChange> {
+    let Some(project_path) = project_path else {
+        return Vec::new();
+    };
+    let mut changes = match crate::utils::git_utils::nested_repo_changes(project_path, base_commit) {
+        Ok(changes) => changes,
+        Err(e) => {
+            debug!("Submodules and nested repositories not checked for changes: {:#}", e);
+            return Vec::new();
+        }
+    };
+    for repo in changes.iter_mut().filter(|repo| !repo.diff.is_empty()) {
+        let Some(saved_patch) = saved_patch else { break };
+        let path = nested_patch_path(Path::new(saved_patch), &repo.path);
+        match std::fs::write(&path, &repo.diff) {
+            Ok(()) => repo.patch_file = Some(path.display().to_string()),
+            Err(e) => error!("Failed to write the patch of {} to {}: {}", repo.path, path.display(), e),
+        }
+    }
+    changes
+}
+
+/// Changed files of the project whose encoding, line endings or trailing newline differ from
+/// the base commit (see `utils::file_integrity`). Empty if they could not be checked.
+fn integrity_issues(project_path: Option<&str>, base_commit: Option<&str>) -> Vec<IntegrityIssue> {
+    let Some(project_path) = project_path else {
+        return Vec::new();
+    };
+    let checked = crate::utils::git_utils::file_diff_stats(project_path, base_commit)
+        .and_then(|files| file_integrity::check_changed_files(Path::new(project_path), base_commit, &files));
+    match checked {
+        Ok(issues) => {
+            for issue in &issues {
+                warn!("File format changed: {}", issue.describe());
+            }
+            issues
+        }
+        Err(e) => {
+            debug!("File encodings and line endings not checked: {:#}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Where the patch of the nested repository `repo` goes: `model.patch` and `vendor/lib`
+/// give `model.vendor-lib.patch`.
+fn nested_patch_path(saved_patch: &Path, repo: &str) -> PathBuf {
+    let stem = saved_patch.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match saved_patch.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, repo.replace('/', "-"), ext.to_string_lossy()),
+        None => format!("{}.{}", stem, repo.replace('/', "-")),
+    };
+    saved_patch.with_file_name(name)
+}
+
+/// One line about a changed submodule or nested repository for the run summary.
+fn describe_nested_repo_change(repo: &NestedRepoChange) -> String {
+    let kind = match repo.kind {
+        NestedRepoKind::Submodule => "Submodule",
+        NestedRepoKind::Nested => "Nested repository",
+    };
+    let mut line = format!("{} {}: {} file(s) changed", kind, repo.path, repo.files_changed);
+    if repo.pointer_changed {
+        let short = |commit: &Option<String>| commit.as_deref().map_or("none", |c| &c[..c.len().min(12)]).to_string();
+        line.push_str(&format!(
+            ", pointer moved {} -> {}",
+            short(&repo.recorded_commit),
+            short(&repo.head_commit)
+        ));
+    }
+    if let Some(patch_file) = &repo.patch_file {
+        line.push_str(&format!(", patch saved to {}", patch_file));
+    }
+    line
+}
+
+/// The tokens and cost of the run's steps, also written into its trajectory. `None` if no
+/// step reported usage.
+fn run_cost(config: &Config, execution: &AgentExecution, trajectory: Option<&Path>) -> Option<CostBreakdown> {
+    let cost = CostTracker::from_execution(execution, config.pricing.clone(), &configured_model(config)).breakdown();
+    if cost.steps.is_empty() {
+        return None;
+    }
+    if let Some(path) = trajectory.filter(|p| p.exists()) {
+        if let Err(e) = record_cost(path, &cost) {
+            warn!("Failed to record the cost in the trajectory: {:#}", e);
+        }
+    }
+    Some(cost)
+}
+
+/// The model of the default provider.
+fn configured_model(config: &Config) -> String {
+    config
+        .get_current_provider_config()
+        .map_or_else(|_| "unknown_model".to_string(), |pc| pc.model.clone())
+}
+
+/// Appends the run to the usage ledger. Failing to do so does not fail the run.
+fn record_in_ledger(config: &Config, execution: &AgentExecution, labels: &[(String, String)], trajectory: Option<&Path>) {
+    let Some(path) = ledger::default_ledger_path() else {
+        warn!("The run is not recorded in the usage ledger: HOME and {} are not set", ledger::LEDGER_ENV);
+        return;
+    };
+    let model = configured_model(config);
+    let mut entry = LedgerEntry::for_run(execution, &config.default_provider, &model, labels.iter().cloned().collect());
+    // Priced with the configured prices, step by step.
+    let cost = CostTracker::from_execution(execution, config.pricing.clone(), &model).breakdown();
+    if !cost.steps.is_empty() {
+        entry.cost = cost.total.cost;
+    }
+    entry.project = config.working_dir.clone();
+    entry.trajectory = trajectory.map(|p| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf()).display().to_string());
+    match ledger::append(&path, &entry) {
+        Ok(()) => debug!("Run recorded in the usage ledger {}", path.display()),
+        Err(e) => warn!("Failed to record the run in the usage ledger: {:#}", e),
+    }
+}
+
+/// Result of `trae run --output json`.
+#[derive(serde::Serialize, Debug)]
+struct RunReport<'a> {
+    task: &'a str,
+    success: bool,
+    steps: usize,
+    execution_time_secs: Option<u64>,
+    total_tokens_used: Option<&'a crate::llm::base_client::LLMUsage>,
+    final_result: Option<&'a str>,
+    error_message: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_hint: Option<&'a str>,
+    patch_path: Option<String>,
+    lakeview_summary: Option<String>,
+    /// Binary files changed by the run but left out of the patch (`binary_patches: "list"`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    binary_files: Vec<String>,
+    /// Changed files whose encoding, line endings or trailing newline differ from the base.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    integrity_issues: Vec<IntegrityIssue>,
+    /// Submodules and nested repositories with changes, whose patches are kept separately.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    nested_repos: Vec<NestedRepoChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reproduction_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bundle_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pr_body_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    environment: Option<RunEnvironment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit: Option<AutoCommit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_mortem: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cleanup: Option<CleanupReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context_usage: Option<ContextUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<RunProfile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost: Option<CostBreakdown>,
+}
+
+impl<'a> RunReport<'a> {
+    fn new(
+        execution: &'a AgentExecution,
+        patch_path: Option<String>,
+        lakeview_summary: Option<String>,
+    ) -> Self {
+        RunReport {
+            task: &execution.task,
+            success: execution.success,
+            steps: execution.steps.len(),
+            execution_time_secs: execution.end_time.map(|end| end - execution.start_time),
+            total_tokens_used: execution.total_tokens_used.as_ref(),
+            final_result: execution.final_result.as_deref(),
+            error_message: execution.error_message.as_deref(),
+            error_hint: execution.error_hint.as_deref(),
+            patch_path,
+            lakeview_summary,
+            binary_files: Vec::new(),
+            integrity_issues: Vec::new(),
+            nested_repos: Vec::new(),
+            reproduction_command: None,
+            bundle_path: None,
+            pr_body_path: None,
+            environment: None,
+            commit: None,
+            post_mortem: None,
+            cleanup: None,
+            context_usage: None,
+            profile: None,
+            cost: None,
+        }
+    }
+}
+
+/// Prints an agent event as a one-line progress message on stderr.
+fn render_agent_event(event: AgentEvent) {
+    match event {
+        AgentEvent::StepBegin(step_num) => {
+            eprintln!("\n[AGENT EVENT] Step {} Starting...", step_num);
+        }
+        AgentEvent::StepStateChange(_step_num, new_state) => {
+            eprintln!("[AGENT EVENT] State: {:?}", new_state);
+        }
+        AgentEvent::LLMRequestSent(_step_num, messages) => {
+            if let Some(last_msg) = messages.last() {
+                let content_preview = last_msg.content.as_deref().unwrap_or_default();
+                eprintln!(
+                    "[AGENT EVENT] Sending to LLM (last msg role: {:?}): {:.100}...",
+                    last_msg.role, content_preview
+                );
+            }
+        }
+        AgentEvent::LLMResponseReceived(_step_num, response) => {
+            if let Some(choice) = response.choices.first() {
+                let content_preview = choice.message.content.as_deref().unwrap_or_default();
+                eprintln!("[AGENT EVENT] LLM Response: {:.100}...", content_preview);
+                if let Some(tool_calls) = &choice.message.tool_calls {
+                    if !tool_calls.is_empty() {
+                        eprintln!(
+                            "[AGENT EVENT] LLM requested {} tool call(s).",
+                            tool_calls.len()
+                        );
+                    }
+                }
+            }
+        }
+        AgentEvent::ToolCallAttempt(_step_num, tool_call) => {
+            eprintln!(
+                "[AGENT EVENT] Tool call: {} with args {:.100}...",
+                tool_call.function.name, tool_call.function.arguments
+            );
+        }
+        AgentEvent::ToolCallResult(_step_num, tool_result) => {
+            let result_preview = tool_result
+                .result
+                .as_deref()
+                .or(tool_result.error.as_deref())
+                .unwrap_or_default();
+            // File views and diffs are shown as highlighted previews rather than cut at 100 characters.
+            if let Some(path) = result_preview
+                .strip_prefix("Here's the content of ")
+                .and_then(|rest| rest.lines().next())
+                .and_then(|header| header.strip_suffix(':'))
+            {
+                eprint!(
+                    "[AGENT EVENT] Tool result (ID: {}):\n{}",
+                    tool_result.tool_call_id,
+                    highlight::preview_file_view(result_preview, path, Stream::Stderr)
+                );
+                return;
+            }
+            if result_preview.starts_with("diff --git ") {
+                let lines: Vec<&str> = result_preview.lines().collect();
+                let shown = lines[..lines.len().min(highlight::PREVIEW_LINES)].join("\n") + "\n";
+                eprint!(
+                    "[AGENT EVENT] Tool result (ID: {}):\n{}",
+                    tool_result.tool_call_id,
+                    highlight::highlight_diff(&shown, Stream::Stderr)
+                );
+                if lines.len() > highlight::PREVIEW_LINES {
+                    eprintln!("... ({} more lines)", lines.len() - highlight::PREVIEW_LINES);
+                }
+                return;
+            }
+            eprintln!(
+                "[AGENT EVENT] Tool result (ID: {}): Success: {}, Details: {:.100}...",
+                tool_result.tool_call_id, tool_result.success, result_preview
+            );
+        }
+        AgentEvent::TaskCompleted(_) | AgentEvent::TaskFailed(_) => { /* Final summary handles this */
+        }
+        AgentEvent::StatusUpdate(msg) => {
+            eprintln!("[AGENT EVENT] Status: {}", msg);
+        }
+        AgentEvent::ToolCallStreaming(_step_num, tool_name) => {
+            eprintln!("[AGENT EVENT] Agent is calling {}...", tool_name);
+        }
+        AgentEvent::WritePreflight(_step_num, path, rejection) => match rejection {
+            None => eprintln!("[AGENT EVENT] Agent is writing file {}...", path.display()),
+            Some(reason) => eprintln!(
+                "[AGENT EVENT] Warning: pending write to {} will be rejected: {}",
+                path.display(),
+                reason
+            ),
+        },
+        AgentEvent::DiffStat(_step_num, stat) => {
+            eprintln!("[AGENT EVENT] Changes so far: {}", stat);
+        }
+        AgentEvent::Heartbeat(beat) => {
+            let elapsed = crate::agent::heartbeat::format_elapsed(beat.elapsed);
+            if beat.stuck {
+                eprintln!(
+                    "[AGENT EVENT] Warning: step {} has been running for {} ({}); it may be stuck",
+                    beat.step, elapsed, beat.activity
+                );
+            } else {
+                eprintln!(
+                    "[AGENT EVENT] Step {} still {} ({})",
+                    beat.step, beat.activity, elapsed
+                );
+            }
+        }
+    }
+}
+
+/// Sets `working_dir` to the inferred project directory (see `project_inference`) unless
+/// `--working-dir` was given, asking first in confirm mode. `Config::load` has set it to the
+/// current directory then, which declining keeps.
+fn apply_inferred_project(config: &mut Config, task: Option<&str>, working_dir_given: bool) {
+    if working_dir_given {
+        return;
+    }
+    let Ok(cwd) = std::env::current_dir() else {
+        return;
+    };
+    let Some(inferred) = infer_project_path(task, &cwd) else {
+        return;
+    };
+    if config.confirm_commands && std::io::stdin().is_terminal() && !confirm_inferred(&inferred) {
+        info!("Inferred project directory declined; using the current directory.");
+        return;
+    }
+    info!(path = %inferred.path.display(), source = %inferred.source, "Inferred the project directory");
+    if inferred.path != cwd && !crate::utils::logging::is_quiet() {
+        eprintln!("Project directory: {} (from {})", inferred.path.display(), inferred.source);
+    }
+    config.working_dir = Some(inferred.path.display().to_string());
+}
+
+/// Returns the command permissions of the project at `project_root` if `confirm_commands`
+/// is set, so the user is asked before each shell command.
+fn command_permissions(config: &Config, project_root: &Path) -> Option<Arc<CommandPermissions>> {
+    if !config.confirm_commands {
+        return None;
+    }
+    command_permissions_for(project_root)
+}
+
+/// The write guard keeping the editing tools off the `protected_paths` config and, with
+/// `confirm_commands`, off the saved command permissions, if there is anything to protect.
+fn protected_paths_guard(config: &Config, project_root: &Path) -> Option<Arc<WriteGuard>> {
+    protected_paths_guard_for(&config.protected_paths, config.confirm_commands, project_root)
+}
+
+fn protected_paths_guard_for(
+    protected_paths: &[String],
+    confirm_commands: bool,
+    project_root: &Path,
+) -> Option<Arc<WriteGuard>> {
+    if protected_paths.is_empty() && !confirm_commands {
+        return None;
+    }
+    let guard = protected_paths
+        .iter()
+        .fold(WriteGuard::new(), |guard, path| guard.with_protected_path(project_root.join(path)));
+    // Otherwise the agent could allow itself commands for the next session.
+    let guard = if confirm_commands {
+        guard.with_protected_path(project_root.join(PERMISSIONS_FILE))
+    } else {
+        guard
+    };
+    Some(Arc::new(guard))
+}
+
+/// Opens the audit log of the `audit_log` config, if set.
+fn open_audit_log(config: &Config) -> anyhow::Result<Option<Arc<AuditLog>>> {
+    config
+        .audit_log
+        .as_ref()
+        .map(|audit| AuditLog::open(audit).map(Arc::new))
+        .transpose()
+}
+
+/// The gate of the `approval` config, if set, asking on the terminal.
+fn approval_gate(config: &Config) -> Option<Arc<ApprovalGate>> {
+    config
+        .approval
+        .clone()
+        .map(|approval| Arc::new(ApprovalGate::new(approval, Arc::new(TerminalApprovalPrompt))))
+}
+
+fn command_permissions_for(project_root: &Path) -> Option<Arc<CommandPermissions>> {
+    match CommandPermissions::load(project_root, Arc::new(TerminalPrompt)) {
+        Ok(permissions) => Some(Arc::new(permissions)),
+        Err(e) => {
+            // Without the remembered permissions, still ask about every command.
+            warn!("Ignoring the saved command permissions: {:#}", e);
+            Some(Arc::new(CommandPermissions::in_memory(Arc::new(TerminalPrompt))))
+        }
+    }
+}
+
+/// Creates a throwaway repository in `project_root` if it is not a git work tree and
+/// `init_git` is set; otherwise warns what is unavailable without one.
+fn throwaway_repo_for(project_root: &Path, init_git: bool) -> Option<ThrowawayRepo> {
+    if is_git_work_tree(project_root) {
+        return None;
+    }
+    if !init_git {
+        warn!(
+            "{} is not a git repository (or git is not installed): patch saving, --must-patch and checkpoints are unavailable. Pass --init-git to track the run's changes in a throwaway repository.",
+            project_root.display()
+        );
+        return None;
+    }
+    match ThrowawayRepo::init(project_root) {
+        Ok(repo) => {
+            info!("Created a throwaway git repository for the run: {}", repo.git_dir().display());
+            Some(repo)
+        }
+        Err(e) => {
+            warn!("Could not create a throwaway git repository: {:#}", e);
+            None
+        }
+    }
+}
+
+/// The step history of the project in the usage ledger, and the prompt offering more steps
+/// when `ask` is set and stdin is a terminal.
+fn step_budget_forecast(config: &Config, ask: bool) -> (StepHistory, Option<Arc<dyn StepBudgetPrompt>>) {
+    let history = match ledger::default_ledger_path().map(|path| ledger::read(&path)) {
+        Some(Ok(entries)) => StepHistory::from_ledger(&entries, config.working_dir.as_deref()),
+        Some(Err(e)) => {
+            warn!("The step forecast ignores the usage ledger: {:#}", e);
+            StepHistory::default()
+        }
+        None => StepHistory::default(),
+    };
+    let prompt: Option<Arc<dyn StepBudgetPrompt>> =
+        (ask && std::io::stdin().is_terminal()).then(|| Arc::new(TerminalStepBudgetPrompt) as _);
+    (history, prompt)
+}
+
+/// Runs the teardown commands from `project_root` in `execution` and removes what the run
+/// left behind, then records what was cleaned in the trajectory, and the teardown commands in
+/// `audit_log` for the run of `context`. Returns `None` if there was nothing to clean up.
+async fn finish_run_cleanup(
+    config: &Config,
+    cleanup: &RunCleanup,
+    project_root: &Path,
+    trajectory_path: Option<&Path>,
+    execution: &dyn ExecutionEnvironment,
+    audit_log: Option<&AuditLog>,
+    context: &ToolContext,
+) -> Option<CleanupReport> {
+    if !config.teardown_commands.is_empty() && !crate::utils::logging::is_quiet() {
+        eprintln!("Running {} teardown command(s)...", config.teardown_commands.len());
+    }
+    let report = cleanup
+        .run(
+            &config.teardown_commands,
+            project_root,
+            std::time::Duration::from_secs(config.teardown_timeout_secs),
+            execution,
+        )
+        .await;
+    if let Some(audit_log) = audit_log {
+        for record in &report.teardown {
+            let entry = AuditEntry::for_hook("teardown_commands", &record.command, context);
+            audit_log.record(&entry.with_outcome(record.success, record.output.as_deref(), None));
+        }
+    }
+    if report.is_empty() {
+        return None;
+    }
+    info!(
+        teardown = report.teardown.len(),
+        terminated_process_groups = report.terminated_process_groups.len(),
+        removed_paths = report.removed_paths.len(),
+        "Cleaned up after the run"
+    );
+    if !report.terminated_process_groups.is_empty() && !crate::utils::logging::is_quiet() {
+        eprintln!(
+            "Stopped {} background process group(s) left running by the agent",
+            report.terminated_process_groups.len()
+        );
+    }
+    if let Some(path) = trajectory_path.filter(|p| p.exists()) {
+        if let Err(e) = record_cleanup(path, &report) {
+            warn!("Failed to record the cleanup in the trajectory: {:#}", e);
+        }
+    }
+    Some(report)
+}
+
+/// Builds the changelog of a successful run and writes it where `changelog_config` asks: as a
+/// pull request body next to the trajectory, and as an entry of the project's changelog file.
+/// The changelog is returned for the commit message; failures to write it are only logged.
+fn write_changelog(
+    changelog_config: &config::ChangelogConfig,
+    project_path: &Path,
+    execution: &AgentExecution,
+    base_commit: Option<&str>,
+    diff_exclusions: &[PathBuf],
+    trajectory_path: Option<&Path>,
+    report: &mut RunReport,
+) -> Option<Changelog> {
+    let mut files = match crate::utils::git_utils::file_diff_stats(&project_path.to_string_lossy(), base_commit) {
+        Ok(files) => files,
+        Err(e) => {
+            warn!("Failed to compute the diffstat for the changelog: {:#}", e);
+            return None;
+        }
+    };
+    let excluded: Vec<PathBuf> = diff_exclusions
+        .iter()
+        .map(|p| std::path::absolute(p).unwrap_or_else(|_| p.clone()))
+        .collect();
+    files.retain(|path, _| {
+        let path = std::path::absolute(project_path.join(path)).unwrap_or_default();
+        !excluded.iter().any(|excluded| path.starts_with(excluded))
+    });
+    let changelog = Changelog::from_execution(execution, files);
+
+    if changelog_config.pr_body {
+        match trajectory_path {
+            Some(trajectory_path) => {
+                let path = changelog::pr_body_path(trajectory_path);
+                match std::fs::write(&path, changelog.to_markdown()) {
+                    Ok(()) => report.pr_body_path = Some(path.display().to_string()),
+                    Err(e) => warn!("Failed to write the pull request body to {}: {}", path.display(), e),
+                }
+            }
+            None => warn!("No trajectory file; the pull request body is not written."),
+        }
+    }
+    if let Some(file) = &changelog_config.file {
+        let path = project_path.join(file);
+        match changelog::add_entry(&path, &changelog.file_entry()) {
+            Ok(()) => info!("Added the run to {}", path.display()),
+            Err(e) => warn!("Failed to add the run to the changelog: {:#}", e),
+        }
+    }
+    Some(changelog)
+}
+
+/// Commits the changes of a successful run for `--commit-on-success`, on a new branch named
+/// after the commit subject unless `allow_current_branch` is set. `changelog` is appended to
+/// the message.
+fn commit_run_changes(
+    project_path: &Path,
+    template: &str,
+    execution: &AgentExecution,
+    changelog: Option<&str>,
+    allow_current_branch: bool,
+) -> anyhow::Result<AutoCommit> {
+    let summary = match &execution.final_report {
+        Some(report) => Some(report.to_markdown()),
+        None => auto_commit::task_done_summary(execution),
+    };
+    let mut message = auto_commit::commit_message(template, summary.as_deref(), &execution.task);
+    if let Some(changelog) = changelog {
+        message = format!("{}\n\n{}", message, changelog);
+    }
+    let branch = (!allow_current_branch).then(|| {
+        let subject = message.lines().next().unwrap_or_default();
+        auto_commit::branch_name(subject, execution.start_time)
+    });
+    auto_commit::commit_changes(project_path, &message, branch.as_deref())
+}
+
+/// Generates the post-mortem of a run that ran out of steps or had its completion rejected,
+/// using the default provider, and saves it next to the trajectory. Returns `None` for other
+/// runs, or if the post-mortem could not be generated.
+async fn write_post_mortem(
+    config: &Config,
+    execution: &AgentExecution,
+    trajectory_path: Option<&Path>,
+) -> Option<String> {
+    let kind = post_mortem::failure_kind(execution)?;
+    info!("Run failed ({:?}); generating a post-mortem...", kind);
+    let provider_config = config.get_current_provider_config().ok()?;
+    let report = match create_client(&config.default_provider, provider_config).await {
+        Ok(client) => {
+            post_mortem::generate_post_mortem(
+                execution,
+                &kind,
+                client,
+                config.output_language.as_deref(),
+            )
+            .await
+        }
+        Err(e) => Err(e.into()),
+    };
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Failed to generate the post-mortem: {}", e);
+            return None;
+        }
+    };
+    if let Some(trajectory_path) = trajectory_path {
+        let path = post_mortem::post_mortem_path(trajectory_path);
+        match std::fs::write(&path, &report) {
+            Ok(()) => info!("Post-mortem saved to {}", path.display()),
+            Err(e) => error!("Failed to save the post-mortem to {}: {}", path.display(), e),
+        }
+    }
+    Some(report)
+}
+
+/// Writes the `--bundle` archive for a finished run.
+///
+/// `patch` is the diff of the project, if it has a working directory. The trajectory is the
+/// recorded trajectory file if there is one, otherwise the execution summary.
+fn write_run_bundle(
+    bundle_path: &Path,
+    report: &RunReport<'_>,
+    execution: &AgentExecution,
+    patch: Option<anyhow::Result<String>>,
+    trajectory_path: Option<&Path>,
+    reproduction: Option<&Reproduction>,
+) -> anyhow::Result<()> {
+    let mut bundle = RunBundle::new();
+
+    match patch {
+        Some(Ok(diff)) if !diff.trim().is_empty() => bundle.add_bytes("patch.diff", "patch", diff),
+        Some(Ok(_)) => info!("No changes to include in the bundle patch."),
+        Some(Err(e)) => warn!("Could not compute the patch for the bundle: {:#}", e),
+        None => {}
+    }
+
+    for repo in report.nested_repos.iter().filter(|repo| !repo.diff.is_empty()) {
+        bundle.add_bytes(&format!("nested/{}.diff", repo.path), "nested_patch", repo.diff.clone());
+    }
+
+    match trajectory_path.filter(|p| p.is_file()) {
+        Some(path) => bundle.add_file("trajectory.json", "trajectory", path)?,
+        None => bundle.add_bytes(
+            "trajectory.json",
+            "execution_summary",
+            serde_json::to_vec_pretty(execution)?,
+        ),
+    }
+
+    if let Some(summary) = &report.lakeview_summary {
+        bundle.add_bytes("lakeview_summary.md", "lakeview_summary", summary.clone());
+    }
+    if let Some(post_mortem) = &report.post_mortem {
+        bundle.add_bytes("post_mortem.md", "post_mortem", post_mortem.clone());
+    }
+
+    if let Some(reproduction) = reproduction {
+        for log in reproduction.log_files() {
+            let name = format!(
+                "verification/{}",
+                log.file_name().unwrap_or_default().to_string_lossy()
+            );
+            bundle.add_file(&name, "verification_log", &log)?;
+        }
+    }
+
+    bundle.write(bundle_path, &serde_json::to_value(report)?)?;
+    info!("Bundle contains {} file(s) besides the manifest.", bundle.entries().len());
+    Ok(())
+}
+
+/// Prints the human-readable result of `trae run` on stdout.
+fn print_run_summary(
+    execution_result: &AgentExecution,
+    patch_path: Option<&str>,
+    lakeview_summary: Option<&str>,
+) {
+    println!("\n--- Task Execution Summary ---");
+    println!("Task: {}", execution_result.task);
+    println!("Success: {}", execution_result.success);
+    if let Some(end_time) = execution_result.end_time {
+        println!(
+            "Execution Time: {}s",
+            end_time - execution_result.start_time
+        );
+    } else {
+        println!("Execution Time: Not available (task did not set end time)");
+    }
+    println!("Total Steps: {}", execution_result.steps.len());
+    if let Some(tokens) = &execution_result.total_tokens_used { // Borrow tokens
         println!("Total Tokens Used: {:?}", tokens); // Use {:?} for debug printing
     }
-    if let Some(ref res) = execution_result.final_result {
-        println!("Final Result: {}", res);
+    if let Some(report) = &execution_result.final_report {
+        println!("\n--- Final Report ---");
+        println!("{}", highlight::render_markdown(&report.to_markdown(), Stream::Stdout));
+    } else if let Some(ref res) = execution_result.final_result {
+        println!("Final Result: {}", highlight::render_markdown(res, Stream::Stdout));
+    }
+    if let Some(ref err_msg) = execution_result.error_message {
+        println!("Error Message: {}", err_msg);
+    }
+    if let Some(hint) = &execution_result.error_hint {
+        println!("Hint: {}", hint);
+    }
+    if let Some(patch_path) = patch_path {
+        println!("Patch file saved to: {}", patch_path);
+    }
+    let http_metrics = crate::utils::http::http_metrics();
+    if http_metrics.requests > 0 {
+        println!("HTTP Connections: {}", http_metrics);
+    }
+    if let Some(summary) = lakeview_summary {
+        println!("\n--- Lakeview Summary ---");
+        println!("{}", summary);
+    }
+}
+
+pub async fn handle_interactive(args: InteractiveArgs) -> anyhow::Result<()> {
+    info!("Starting 'interactive' command session.");
+    crate::utils::crash::set_config(Path::new(&args.config_file), args.working_dir.as_deref().map(Path::new));
+
+    let config = match Config::load(
+        // Removed mut
+        &args.config_file,
+        args.provider.clone(),
+        args.model.clone(),
+        args.api_key.clone(),
+        Some(args.max_steps), // Pass max_steps for interactive mode from args
+        args.working_dir.clone(),
+    ) {
+        Ok(mut cfg) => {
+            if let Some(lang) = args.lang.clone() {
+                cfg.output_language = Some(lang);
+            }
+            if args.confirm {
+                cfg.confirm_commands = true;
+            }
+            apply_inferred_project(&mut cfg, None, args.working_dir.is_some());
+            cfg
+        }
+        Err(e) => {
+            error!("Failed to load configuration: {:?}", e);
+            return Err(e);
+        }
+    };
+    info!(
+        "Initial configuration loaded. Default provider: {}",
+        config.default_provider
+    );
+    if args.offline {
+        enable_offline_mode(&config)?;
+    }
+    print_update_notice(&config).await;
+
+    // LLM Client is initialized by the Agent itself based on the config.
+    // No need to initialize a separate one here if it's not used directly by handle_interactive.
+
+    // --- Tool Registry and Agent Initialization ---
+    let mut tool_registry = ToolRegistry::default();
+    if args.offline {
+        tool_registry.register(BashTool::new().without_network());
+        remove_network_tools(&mut tool_registry);
+    }
+    let tool_registry = Arc::new(tool_registry);
+    info!(
+        "ToolRegistry initialized with {} tools.",
+        tool_registry.get_all_tools_arc().len()
+    );
+
+    // We need an Arc<Config> for the agent
+    let agent_config = Arc::new(config.clone()); // Removed mut, config is already Arc-able
+
+    let trajectory_path_buf = args.trajectory_file.map(PathBuf::from);
+
+    let mut agent = TraeAgent::try_new(agent_config.clone(), tool_registry.clone(), trajectory_path_buf).await?;
+    info!("TraeAgent created for interactive mode.");
+    let permissions_root = match &agent_config.working_dir {
+        Some(wd) => PathBuf::from(wd),
+        None => std::env::current_dir()?,
+    };
+    agent.set_command_permissions(command_permissions(&agent_config, &permissions_root));
+    agent.set_write_guard(protected_paths_guard(&agent_config, &permissions_root));
+    agent.set_audit_log(open_audit_log(&agent_config)?);
+    agent.set_approval_gate(approval_gate(&agent_config));
+    let (step_history, step_prompt) = step_budget_forecast(&agent_config, true);
+    agent.set_step_budget_forecast(step_history, step_prompt);
+
+    let mut rl = DefaultEditor::new().expect("Failed to create rustyline editor");
+    if PathBuf::from(".trae_history.txt").exists() {
+        let _ = rl.load_history(".trae_history.txt");
+    }
+
+    let mut conversation_history: Vec<LLMMessage> = Vec::new();
+    let mut session = InteractiveSession {
+        project_path: agent_config.working_dir.as_ref().map(PathBuf::from),
+        usage: UsageTracker::with_pricing(agent_config.pricing.clone()),
+        task: None,
+        confirm_commands: agent_config.confirm_commands,
+        protected_paths: agent_config.protected_paths.clone(),
+        last_reply: None,
+    };
+    let configured_model = agent_config
+        .get_current_provider_config()
+        .map(|p| p.model.clone())
+        .unwrap_or_default();
+
+    println!("Trae Interactive Mode. Type 'exit' or 'quit' to leave.");
+    println!("Special commands: config, clear_history, load_config <path> (TODO), /cd <path>, /project, /diff, /cost, /task, /help");
+    if let Some(path) = &session.project_path {
+        println!("Project: {}", path.display());
+    }
+
+    loop {
+        let prompt = if session.task.is_some() { "trae[task]> " } else { "trae> " };
+        let readline = rl.readline(prompt);
+        match readline {
+            Ok(line) => {
+                let _ = rl.add_history_entry(line.as_str());
+                let user_input = line.trim();
+
+                if user_input.is_empty() {
+                    continue;
+                }
+
+                if let Some(command) = slash_commands::parse(user_input) {
+                    run_slash_command(command, &mut session, &mut agent, &configured_model).await;
+                    continue;
+                }
+
+                if user_input == "exit" || user_input == "quit" {
+                    break;
+                } else if user_input == "config" {
+                    println!("Current configuration:\n{:#?}", agent_config); // Show agent's current config
+                    continue;
+                } else if user_input == "clear_history" {
+                    conversation_history.clear();
+                    println!("Conversation history cleared.");
+                    // Also clear agent's internal history if it maintains one separately for interactive
+                    // For now, assuming TraeAgent's new_task or equivalent will handle this.
+                    // We might need a specific agent.clear_conversation_context() method.
+                    // For this iteration, clearing `conversation_history` here is the main step.
+                    // The agent will get a fresh history on the next proper input.
+                    continue;
+                } else if user_input.starts_with("load_config ") {
+                    // TODO: Implement config reloading
+                    // This would involve:
+                    // 1. Parsing the path.
+                    // 2. Calling Config::load with the new path.
+                    // 3. If successful, re-initializing llm_client, agent_config, and agent.
+                    // 4. Clearing conversation_history.
+                    // 5. Handling errors gracefully.
+                    println!(
+                        "Config reloading is not yet implemented. Current config remains active."
+                    );
+                    continue;
+                }
+
+                // Add user message to history
+                conversation_history.push(LLMMessage {
+                    role: MessageRole::User,
+                    content: Some(user_input.to_string()),
+                    name: None,
+                    tool_calls: None,
+                    tool_call_id: None,
+                });
+
+                // Create a task for the agent for this turn
+                // The "task" is just the user's current input.
+                // Agent arguments might be relevant for interactive mode, but keeping it simple for now.
+                let spec = session.project_path.as_ref().map_or_else(TaskSpec::default, |path| {
+                    TaskSpec::in_project(path.display().to_string())
+                });
+                if let Err(e) = agent.new_task(user_input.to_string(), spec).await {
+                    error!(
+                        "Failed to set new task for agent in interactive mode: {:?}",
+                        e
+                    );
+                    println!("Error setting up task. Please try again.");
+                    conversation_history.pop(); // Remove the last user message if task setup failed
+                    continue;
+                }
+
+                // Execute a "turn" or "step" of the agent.
+                // This requires a new method on the Agent trait / TraeAgent.
+                // For now, let's assume `execute_interactive_turn` which takes current history
+                // and returns new messages from the agent for this turn.
+                // It should use `agent.messages` which `new_task` would have set up.
+
+                // The existing `execute_task` is designed for full task completion.
+                // We need a version that processes one conversational exchange.
+                // This might involve a refactor of `common_execute_task_loop` or a new loop.
+
+                // Placeholder for actual agent interaction logic for one turn:
+                println!("Sending to agent: '{}'", user_input);
+                // >>> This is where the call to agent.execute_interactive_turn() would go <<<
+                // >>> It would update `conversation_history` with the agent's response <<<
+
+                // Tool activity is shown as it happens, and the turn's token usage is collected.
+                // Printing blocks while a command confirmation is being asked.
+                let (event_tx, mut event_rx) = mpsc::channel(100);
+                let fallback_model = configured_model.clone();
+                let pricing = session.usage.pricing().clone();
+                let event_renderer = tokio::task::spawn_blocking(move || {
+                    let mut turn_usage = UsageTracker::with_pricing(pricing);
+                    while let Some(event) = event_rx.blocking_recv() {
+                        match event {
+                            AgentEvent::LLMResponseReceived(_, response) => {
+                                if let Some(usage) = &response.usage {
+                                    let model = if response.model.is_empty() {
+                                        &fallback_model
+                                    } else {
+                                        &response.model
+                                    };
+                                    turn_usage.record(model, usage);
+                                }
+                            }
+                            AgentEvent::ToolCallAttempt(_, call) => {
+                                let _terminal = crate::utils::permissions::lock_terminal();
+                                println!("  -> {}", crate::utils::tool_activity::describe_call(&call));
+                            }
+                            AgentEvent::ToolCallResult(_, result) => {
+                                let _terminal = crate::utils::permissions::lock_terminal();
+                                println!("     {}", crate::utils::tool_activity::describe_result(&result));
+                            }
+                            _ => {}
+                        }
+                    }
+                    turn_usage
+                });
+                // Ctrl-C during the turn interrupts it; what the turn did so far stays in the
+                // conversation. (At the prompt, rustyline reads Ctrl-C as a key instead.)
+                let cancel = CancellationToken::new();
+                agent.set_cancellation_token(cancel.clone());
+                let interrupt = tokio::spawn({
+                    let cancel = cancel.clone();
+                    async move {
+                        if tokio::signal::ctrl_c().await.is_ok() {
+                            cancel.cancel();
+                        }
+                    }
+                });
+                let turn_result = agent.execute_interactive_turn(Some(event_tx)).await;
+                interrupt.abort();
+                if cancel.is_cancelled() {
+                    println!("Turn interrupted. What the agent did so far is kept in the conversation.");
+                }
+                if let Ok(turn_usage) = event_renderer.await {
+                    session.usage.merge(&turn_usage);
+                    println!(
+                        "[Turn: {} | Session: {}]",
+                        turn_usage.format_summary(),
+                        session.usage.format_summary()
+                    );
+                }
+                match turn_result {
+                    Ok(new_messages) => {
+                        if new_messages.is_empty() {
+                            println!("Agent processed the input but produced no new messages for the conversation.");
+                        }
+                        for msg in new_messages {
+                            if msg.role == MessageRole::Assistant {
+                                if let Some(content) = &msg.content {
+                                    println!("Agent: {}", highlight::render_markdown(content, Stream::Stdout));
+                                    session.last_reply = Some(content.clone());
+                                } else if msg.tool_calls.is_some() {
+                                    // In interactive mode, we might not want to show raw tool calls directly,
+                                    // but the LLM's subsequent response after tool execution is what matters.
+                                    // This part of the code will be reached if the turn ended AFTER tool calls
+                                    // but before a final summarising LLM response.
+                                    // The `execute_interactive_turn` is designed to give a final textual response.
+                                    println!("Agent: (Thinking/Using tools...)");
+                                }
+                            }
+                            // Add all messages from the turn (thoughts, tool use, final response) to history
+                            conversation_history.push(msg);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error during agent's interactive turn: {:?}", e);
+                        println!("Agent Error: {}", e);
+                        if let Some(hint) = e.hint() {
+                            println!("Hint: {}", hint);
+                        }
+                        // Optionally, remove the last user message from history if the turn failed critically
+                        // For now, keep it to see the context of the error.
+                    }
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                break;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(err) => {
+                println!("Error reading line: {:?}", err);
+                break;
+            }
+        }
+    }
+
+    let _ = rl.save_history(".trae_history.txt");
+    println!("Exiting interactive session.");
+    Ok(())
+}
+
+/// State of an interactive session that slash commands act on.
+struct InteractiveSession {
+    /// Sent with every turn so the agent (and its tool calls) work in this directory; `/cd`
+    /// changes it.
+    project_path: Option<PathBuf>,
+    /// Token usage of all turns so far.
+    usage: UsageTracker,
+    /// The task started with `/task start`, until `/task done`.
+    task: Option<SessionTask>,
+    /// Whether shell commands are confirmed (`confirm_commands`).
+    confirm_commands: bool,
+    /// The `protected_paths` config, guarded again in the project `/cd` changes to.
+    protected_paths: Vec<String>,
+    /// The agent's last answer (or the result of the last task), for `/copy`.
+    last_reply: Option<String>,
+}
+
+/// A full agent run started with `/task start`. Its history is its own execution, kept apart
+/// from the chat turns of the session.
+struct SessionTask {
+    description: String,
+    execution: AgentExecution,
+}
+
+/// Executes a slash command of an interactive session.
+async fn run_slash_command(
+    command: SlashCommand,
+    session: &mut InteractiveSession,
+    agent: &mut TraeAgent,
+    configured_model: &str,
+) {
+    let project_path = &mut session.project_path;
+    match command {
+        SlashCommand::Cd(arg) => {
+            match slash_commands::resolve_project_dir(project_path.as_deref(), &arg) {
+                Ok(path) => {
+                    println!("Project set to {}", path.display());
+                    if session.confirm_commands {
+                        // Remembered permissions belong to the project.
+                        agent.set_command_permissions(command_permissions_for(&path));
+                    }
+                    agent.set_write_guard(protected_paths_guard_for(
+                        &session.protected_paths,
+                        session.confirm_commands,
+                        &path,
+                    ));
+                    *project_path = Some(path);
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+        SlashCommand::Project => match project_path {
+            Some(path) => println!("Project: {}", path.display()),
+            None => println!("No project set. Use /cd <path> to set one."),
+        },
+        SlashCommand::Diff => {
+            let Some(path) = project_path.as_ref() else {
+                println!("No project set. Use /cd <path> to set one.");
+                return;
+            };
+            match crate::utils::git_utils::get_git_diff(&path.to_string_lossy(), None) {
+                Ok(diff) if diff.trim().is_empty() => println!("No uncommitted changes."),
+                Ok(diff) => print!("{}", highlight::highlight_diff(&diff, Stream::Stdout)),
+                Err(e) => println!("Cannot show the diff of {}: {:#}", path.display(), e),
+            }
+        }
+        SlashCommand::Cost => println!("Session usage:\n{}", session.usage.format_breakdown()),
+        SlashCommand::Context => println!("Context usage:\n{}", agent.context_usage().format()),
+        SlashCommand::Copy(target) => {
+            let text = match target {
+                CopyTarget::Reply => match &session.last_reply {
+                    Some(reply) => reply.clone(),
+                    None => {
+                        println!("The agent has not answered yet.");
+                        return;
+                    }
+                },
+                CopyTarget::Patch => {
+                    let Some(path) = project_path.as_ref() else {
+                        println!("No project set. Use /cd <path> to set one.");
+                        return;
+                    };
+                    match crate::utils::git_utils::get_git_diff(&path.to_string_lossy(), None) {
+                        Ok(diff) => diff,
+                        Err(e) => {
+                            println!("Cannot get the diff of {}: {:#}", path.display(), e);
+                            return;
+                        }
+                    }
+                }
+            };
+            match crate::utils::clipboard::copy_text(&text) {
+                Ok(lines) => println!("Copied {} line(s) to the clipboard.", lines),
+                Err(e) => println!("{}", e),
+            }
+        }
+        SlashCommand::TaskStart(description) => {
+            run_session_task(agent, session, &description, configured_model).await
+        }
+        SlashCommand::TaskDone => match session.task.take() {
+            Some(task) => println!(
+                "Closed task '{}': {} after {} step(s).",
+                task.description,
+                if task.execution.success { "succeeded" } else { "did not succeed" },
+                task.execution.steps.len()
+            ),
+            None => println!("No task is open. Use /task start <description> to start one."),
+        },
+        SlashCommand::Help => println!("{}", slash_commands::HELP),
+        SlashCommand::Unknown(name) => {
+            println!("Unknown command /{}. Available commands:\n{}", name, slash_commands::HELP)
+        }
+    }
+}
+
+/// Runs `description` as a full agent task for `/task start`, with its events shown as they
+/// happen, and keeps it open in the session until `/task done`.
+async fn run_session_task(
+    agent: &mut TraeAgent,
+    session: &mut InteractiveSession,
+    description: &str,
+    configured_model: &str,
+) {
+    if description.is_empty() {
+        println!("Usage: /task start <description>");
+        return;
+    }
+    if let Some(task) = &session.task {
+        println!("Task '{}' is still open. Use /task done to close it first.", task.description);
+        return;
+    }
+    let spec = session.project_path.as_ref().map_or_else(TaskSpec::default, |path| {
+        TaskSpec::in_project(path.display().to_string())
+    });
+    if let Err(e) = agent.new_task(description.to_string(), spec).await {
+        error!("Failed to set up the interactive task: {:?}", e);
+        println!("Error setting up task: {}", e);
+        return;
+    }
+
+    let mut task_usage = UsageTracker::with_pricing(session.usage.pricing().clone());
+    let mut outcome = None;
+    let mut updates = std::pin::pin!(agent.execute_task_stream(100));
+    while let Some(update) = updates.next().await {
+        match update {
+            AgentStepUpdate::Event(event) => {
+                if let AgentEvent::LLMResponseReceived(_, response) = &event {
+                    if let Some(usage) = &response.usage {
+                        let model = if response.model.is_empty() {
+                            configured_model
+                        } else {
+                            &response.model
+                        };
+                        task_usage.record(model, usage);
+                    }
+                }
+                crate::utils::crash::record_event(&event);
+                render_agent_event(event);
+            }
+            AgentStepUpdate::Finished(result) => outcome = Some(result),
+        }
+    }
+    session.usage.merge(&task_usage);
+    println!(
+        "[Task: {} | Session: {}]",
+        task_usage.format_summary(),
+        session.usage.format_summary()
+    );
+    match outcome.expect("the step stream ends with the run's outcome") {
+        Ok(execution) => {
+            print_run_summary(&execution, None, None);
+            println!("Use /task done to close the task and return to chat.");
+            if let Some(result) = &execution.final_result {
+                session.last_reply = Some(result.clone());
+            }
+            session.task = Some(SessionTask {
+                description: description.to_string(),
+                execution,
+            });
+        }
+        Err(e) => {
+            error!("Interactive task failed: {:?}", e);
+            println!("Task failed: {}", e);
+            if let Some(hint) = e.hint() {
+                println!("Hint: {}", hint);
+            }
+        }
+    }
+}
+
+pub async fn handle_show_config(args: ShowConfigArgs) -> anyhow::Result<()> {
+    if args.print_effective_config {
+        let config_layers = config::layers::read_layers(Path::new(&args.config_file), None)?;
+        if config_layers.is_empty() {
+            println!("No config files found; built-in defaults apply.");
+            return Ok(());
+        }
+        println!("--- Effective Configuration (lowest precedence first) ---");
+        for layer in &config_layers {
+            println!("{}: {}", layer.name, layer.path.display());
+        }
+        println!();
+        println!("{}", config::layers::describe(&config::layers::merge(&config_layers), &config_layers));
+        println!("\nEnvironment variables and command-line options override these values.");
+        return Ok(());
+    }
+    println!("Attempting to load config from: {}", args.config_file);
+    let config = Config::load(&args.config_file, None, None, None, None, None)?;
+
+    println!("\n--- Configuration ---");
+    println!("Default Provider: {}", config.default_provider);
+    println!("Max Steps: {}", config.max_steps);
+    if let Some(wd) = &config.working_dir {
+        println!("Working Directory: {}", wd);
+    } else {
+        println!("Working Directory: Not set (will use current directory)");
+    }
+    println!(
+        "Output Language: {}",
+        config.output_language.as_deref().unwrap_or("Not set (model default)")
+    );
+    println!("Stream Responses: {}", config.stream);
+    println!("Confirm Commands: {}", config.confirm_commands);
+    match &config.routing {
+        Some(routing) => println!(
+            "Model Routing: simple steps to {}{}",
+            routing.cheap_provider,
+            routing
+                .cheap_model
+                .as_deref()
+                .map(|m| format!(" ({})", m))
+                .unwrap_or_default()
+        ),
+        None => println!("Model Routing: Disabled"),
+    }
+    match &config.token_budget {
+        Some(budget) => println!("Token Budget: {} tokens", budget.max_total_tokens),
+        None => println!("Token Budget: None"),
+    }
+    match &config.tool_caps {
+        Some(caps) => {
+            let cap = |limit: Option<u64>| limit.map_or_else(|| "unlimited".to_string(), |n| n.to_string());
+            println!(
+                "Tool Caps: {} bash invocations, {} output bytes, {} file writes",
+                cap(caps.max_bash_invocations.map(u64::from)),
+                cap(caps.max_tool_output_bytes),
+                cap(caps.max_file_writes.map(u64::from))
+            );
+        }
+        None => println!("Tool Caps: None"),
+    }
+    if !config.setup_commands.is_empty() {
+        println!("Setup Commands: {}", config.setup_commands.join("; "));
+    }
+    if !config.teardown_commands.is_empty() {
+        println!("Teardown Commands: {}", config.teardown_commands.join("; "));
+    }
+    println!("Check For Updates: {}", config.check_for_updates);
+    println!("Probe Capabilities: {}", config.probe_capabilities);
+
+    println!("\nModel Providers:");
+    for (name, provider_config) in &config.model_providers {
+        println!("  Provider: {}", name);
+        println!("    Model: {}", provider_config.model);
+        println!(
+            "    API Key: {}",
+            provider_config.api_key.as_deref().unwrap_or("Not set")
+        );
+        if let Some(mt) = provider_config.max_tokens {
+            println!("    Max Tokens: {}", mt);
+        }
+        println!("    Temperature: {}", provider_config.temperature);
+        println!("    Top P: {}", provider_config.top_p);
+        if let Some(tk) = provider_config.top_k {
+            println!("    Top K: {}", tk);
+        }
+        if let Some(seed) = provider_config.seed {
+            println!("    Seed: {}", seed);
+        }
+        println!(
+            "    Parallel Tool Calls: {}",
+            provider_config.parallel_tool_calls
+        );
+    }
+    println!("--- End Configuration ---");
+    Ok(())
+}
+
+pub async fn handle_tools_command(args: ToolsArgs) -> anyhow::Result<()> {
+    println!("\n--- Available Tools ---");
+
+    let registry = ToolRegistry::default(); // Create a default registry to list tools
+    let tools = registry.list_tools();
+
+    if tools.is_empty() {
+        println!("No tools are currently registered.");
+    } else {
+        // Simple table-like format
+        // Determine max tool name length for alignment
+        let max_name_len = tools.iter().map(|t| t.name.len()).max().unwrap_or(20);
+        let max_source_len = tools
+            .iter()
+            .map(|t| t.source.to_string().len())
+            .max()
+            .unwrap_or(8);
+
+        if args.source {
+            println!(
+                "{:<width$} | {:<source_width$} | Description",
+                "Tool Name",
+                "Source",
+                width = max_name_len,
+                source_width = max_source_len
+            );
+            println!(
+                "{:-<width$}-|-{:-<source_width$}-|----------------------------------",
+                "-",
+                "-",
+                width = max_name_len,
+                source_width = max_source_len
+            );
+        } else {
+            println!("{:<width$} | Description", "Tool Name", width = max_name_len);
+            println!("{:-<width$}-|----------------------------------", "-", width = max_name_len);
+        }
+
+        for entry in tools {
+            let description = entry.tool.get_description();
+            // Basic wrapping for description if too long (very naive)
+            let short_desc = if description.chars().count() > 70 {
+                format!("{}...", description.chars().take(67).collect::<String>())
+            } else {
+                description
+            };
+            if args.source {
+                println!(
+                    "{:<width$} | {:<source_width$} | {}",
+                    entry.name,
+                    entry.source.to_string(),
+                    short_desc,
+                    width = max_name_len,
+                    source_width = max_source_len
+                );
+            } else {
+                println!("{:<width$} | {}", entry.name, short_desc, width = max_name_len);
+            }
+        }
+    }
+    println!("--- End Available Tools ---");
+    Ok(())
+}
+
+pub async fn handle_upgrade(args: UpgradeArgs) -> anyhow::Result<()> {
+    use crate::utils::dependency_upgrade::{
+        build_fix_prompt, bump_cargo_dependency, parse_error_files, run_checks,
+    };
+    use crate::utils::git_utils::{list_changed_files, restore_files};
+    use crate::utils::guards::ChangeBudget;
+
+    info!("Starting 'upgrade' command: {} -> {}", args.package, args.to);
+
+    let config = Arc::new(Config::load(
+        &args.config_file,
+        args.provider.clone(),
+        args.model.clone(),
+        args.api_key.clone(),
+        args.max_steps,
+        args.working_dir.clone(),
+    )?);
+    let project_path = config
+        .working_dir
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Project working directory not known."))?;
+    let project_root = PathBuf::from(&project_path);
+    let manifest_path = args
+        .manifest
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| project_root.join("Cargo.toml"));
+    let manifest_path = if manifest_path.is_absolute() {
+        manifest_path
+    } else {
+        project_root.join(manifest_path)
+    };
+
+    let manifest = std::fs::read_to_string(&manifest_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read manifest {}: {}", manifest_path.display(), e))?;
+    let bumped = bump_cargo_dependency(&manifest, &args.package, &args.to)?;
+    std::fs::write(&manifest_path, bumped)?;
+    println!(
+        "Bumped {} to {} in {}",
+        args.package,
+        args.to,
+        manifest_path.display()
+    );
+
+    let tool_registry = Arc::new(ToolRegistry::default());
+    let test_cmd = (!args.no_tests).then_some(args.test_cmd.as_str());
+
+    for iteration in 1..=args.max_iterations {
+        println!("\n--- Upgrade iteration {}/{} ---", iteration, args.max_iterations);
+
+        let outcome = run_checks(&args.build_cmd, test_cmd, &project_root)?;
+        if outcome.success {
+            println!("`{}` succeeded. Upgrade complete.", outcome.command);
+            return Ok(());
+        }
+
+        let mut allowed_files = parse_error_files(&outcome.output, &project_root);
+        allowed_files.push(manifest_path.clone());
+        allowed_files.sort();
+        allowed_files.dedup();
+        println!(
+            "`{}` failed. Files the agent may modify:\n{}",
+            outcome.command,
+            allowed_files
+                .iter()
+                .map(|p| format!("  {}", p.display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+
+        let guard = Arc::new(
+            WriteGuard::new()
+                .with_protected_path(project_root.join(".git"))
+                .with_allowed_paths(&allowed_files)
+                .with_budget(ChangeBudget {
+                    max_files: Some(args.max_files),
+                    max_writes: Some(args.max_writes),
+                }),
+        );
+
+        let changed_before = list_changed_files(&project_path)
+            .context("The upgrade needs a git repository to keep the agent to the allowed files")?;
+
+        let mut agent = TraeAgent::try_new(config.clone(), tool_registry.clone(), None)
+            .await
+            .map_err(|e| anyhow::anyhow!("Agent creation failed: {}", e))?;
+        agent.set_write_guard(Some(guard.clone()));
+
+        let task = build_fix_prompt(&args.package, &args.to, &outcome, &allowed_files);
+        agent
+            .new_task(task, TaskSpec::in_project(project_path.clone()))
+            .await
+            .map_err(|e| anyhow::anyhow!("Task setup failed: {}", e))?;
+
+        match agent.execute_task(None).await {
+            Ok(execution) => {
+                println!(
+                    "Agent finished iteration {} (success: {}, steps: {}).",
+                    iteration,
+                    execution.success,
+                    execution.steps.len()
+                );
+            }
+            Err(e) => {
+                error!("Agent failed during upgrade iteration {}: {:?}", iteration, e);
+                println!("Agent failed during iteration {}: {}", iteration, e);
+            }
+        }
+
+        for path in guard.touched_files() {
+            println!("  edited {}", path.display());
+        }
+
+        // Changes made through `bash` bypass the guard, so files outside the allowed set that
+        // were clean before the iteration are put back before the next check.
+        let changed_after = list_changed_files(&project_path)?;
+        let newly_changed: Vec<PathBuf> = changed_after
+            .into_iter()
+            .filter(|p| !changed_before.contains(p))
+            .collect();
+        let out_of_scope = guard.out_of_scope_changes(&newly_changed);
+        if !out_of_scope.is_empty() {
+            warn!("Agent modified files outside the allowed set: {:?}", out_of_scope);
+            restore_files(&project_path, &out_of_scope)?;
+            println!("Reverted files modified outside the allowed set:");
+            for path in out_of_scope {
+                println!("  {}", path.display());
+            }
+        }
+    }
+
+    let final_outcome = run_checks(&args.build_cmd, test_cmd, &project_root)?;
+    if final_outcome.success {
+        println!("`{}` succeeded after the final iteration.", final_outcome.command);
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "Upgrade of {} to {} did not converge after {} iterations: `{}` failed",
+        args.package,
+        args.to,
+        args.max_iterations,
+        final_outcome.command
+    ))
+}
+
+/// Attempts the rename through a language server.
+///
+/// # Returns
+/// The edited sites, or an error describing why the LSP rename was not possible.
+async fn try_lsp_rename(
+    project_root: &std::path::Path,
+    occurrences: &[crate::utils::lsp::EditSite],
+    old_name: &str,
+    new_name: &str,
+) -> anyhow::Result<Vec<crate::utils::lsp::EditSite>> {
+    use crate::utils::lsp::{apply_workspace_edit, detect_language_server, LspLauncher};
+    use crate::utils::refactor::{language_id_for, pick_rename_anchor};
+    use crate::utils::supervisor::{RestartPolicy, Supervisor};
+
+    let (server, server_args) = detect_language_server(project_root)
+        .ok_or_else(|| anyhow::anyhow!("no language server found for this project"))?;
+    let anchor = pick_rename_anchor(occurrences, old_name)
+        .ok_or_else(|| anyhow::anyhow!("no occurrence to anchor the rename"))?;
+    let language_id = language_id_for(&anchor.path).unwrap_or("plaintext");
+    let anchor_line = std::fs::read_to_string(&anchor.path)?
+        .lines()
+        .nth(anchor.line - 1)
+        .unwrap_or_default()
+        .to_string();
+    let character = anchor_line[..anchor.column - 1].encode_utf16().count();
+
+    println!("Using language server '{}' for rename.", server);
+    let launcher = LspLauncher {
+        command: server,
+        args: server_args,
+        root: project_root.to_path_buf(),
+        documents: vec![(anchor.path.clone(), language_id.to_string())],
+    };
+    // The supervisor restarts the server if it crashes between attempts.
+    let supervisor = Supervisor::new(launcher, RestartPolicy::default());
+
+    // Servers may reject requests while still indexing, so retry a few times.
+    let mut workspace_edit = None;
+    for attempt in 1..=5 {
+        let mut client = supervisor.acquire().await?;
+        match client.rename(&anchor.path, anchor.line - 1, character, new_name).await {
+            Ok(Some(edit)) => {
+                workspace_edit = Some(edit);
+                break;
+            }
+            Ok(None) => info!("Language server returned no rename edit (attempt {})", attempt),
+            Err(e) => info!("Language server rename failed (attempt {}): {}", attempt, e),
+        }
+        drop(client);
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    }
+    let restarts = supervisor.restarts().await;
+    if restarts > 0 {
+        info!("The language server was restarted {} time(s) during the rename", restarts);
+    }
+    if let Some(client) = supervisor.into_handle() {
+        client.shutdown().await;
+    }
+
+    let edit = workspace_edit
+        .ok_or_else(|| anyhow::anyhow!("language server did not produce a rename edit"))?;
+    apply_workspace_edit(&edit)
+}
+
+pub async fn handle_refactor(args: RefactorArgs) -> anyhow::Result<()> {
+    use crate::utils::dependency_upgrade::run_check;
+    use crate::utils::refactor::{
+        build_rename_prompt, changed_sites, find_occurrences, format_sites, parse_rename_spec, snapshot_files,
+    };
+
+    let (old_name, new_name) = parse_rename_spec(&args.rename)?;
+    info!("Starting 'refactor' command: rename {} -> {}", old_name, new_name);
+
+    let config = Arc::new(Config::load(
+        &args.config_file,
+        args.provider.clone(),
+        args.model.clone(),
+        args.api_key.clone(),
+        args.max_steps,
+        args.working_dir.clone(),
+    )?);
+    let project_path = config
+        .working_dir
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Project working directory not known."))?;
+    let project_root = PathBuf::from(&project_path);
+
+    let before = find_occurrences(&project_root, &old_name);
+    let before_files = snapshot_files(&before);
+    if before.is_empty() {
+        return Err(anyhow::anyhow!("No occurrences of '{}' found in {}", old_name, project_path));
+    }
+    println!("Found {} occurrence(s) of '{}'.", before.len(), old_name);
+
+    let build_cmd = args.build_cmd.clone().or_else(|| {
+        project_root
+            .join("Cargo.toml")
+            .exists()
+            .then(|| "cargo build --all-targets".to_string())
+    });
+    let check_build = |root: &std::path::Path| -> anyhow::Result<Option<String>> {
+        match &build_cmd {
+            Some(cmd) => {
+                let outcome = run_check(cmd, root)?;
+                Ok((!outcome.success).then_some(outcome.output))
+            }
+            None => Ok(None),
+        }
+    };
+
+    let mut lsp_sites = Vec::new();
+    let mut done = false;
+    if !args.no_lsp {
+        match try_lsp_rename(&project_root, &before, &old_name, &new_name).await {
+            Ok(sites) => {
+                println!("Language server renamed {} site(s).", sites.len());
+                lsp_sites = sites;
+                done = check_build(&project_root)?.is_none();
+                if !done {
+                    println!("Build fails after the LSP rename; continuing with the agent.");
+                }
+            }
+            Err(e) => {
+                println!("LSP rename unavailable ({}); falling back to the agent.", e);
+            }
+        }
+    }
+
+    let tool_registry = Arc::new(ToolRegistry::default());
+    let mut iteration = 0;
+    while !done && iteration < args.max_iterations {
+        iteration += 1;
+        let remaining = find_occurrences(&project_root, &old_name);
+        let build_failure = check_build(&project_root)?;
+        if remaining.is_empty() && build_failure.is_none() {
+            done = true;
+            break;
+        }
+        println!(
+            "\n--- Refactor iteration {}/{}: {} occurrence(s) remaining ---",
+            iteration,
+            args.max_iterations,
+            remaining.len()
+        );
+
+        let mut agent = TraeAgent::try_new(config.clone(), tool_registry.clone(), None)
+            .await
+            .map_err(|e| anyhow::anyhow!("Agent creation failed: {}", e))?;
+        let task = build_rename_prompt(
+            &old_name,
+            &new_name,
+            &remaining,
+            &project_root,
+            build_failure.as_deref(),
+        );
+        agent
+            .new_task(task, TaskSpec::in_project(project_path.clone()))
+            .await
+            .map_err(|e| anyhow::anyhow!("Task setup failed: {}", e))?;
+        let agent_succeeded = match agent.execute_task(None).await {
+            Ok(execution) => execution.success,
+            Err(e) => {
+                error!("Agent failed during refactor iteration {}: {:?}", iteration, e);
+                false
+            }
+        };
+
+        // The agent may deliberately leave same-named but unrelated identifiers alone.
+        if agent_succeeded && check_build(&project_root)?.is_none() {
+            done = true;
+        }
+    }
+
+    let mut changed = lsp_sites;
+    for site in changed_sites(&before, &before_files, &old_name, &new_name) {
+        if !changed.contains(&site) {
+            changed.push(site);
+        }
+    }
+    changed.sort();
+    println!("\n--- Refactor Summary ---");
+    println!("Renamed '{}' to '{}' at {} site(s):", old_name, new_name, changed.len());
+    for site in format_sites(&changed, &project_root) {
+        println!("  {}", site);
+    }
+    let remaining = find_occurrences(&project_root, &old_name);
+    if !remaining.is_empty() {
+        println!("Occurrences of '{}' left unchanged:", old_name);
+        for site in format_sites(&remaining, &project_root) {
+            println!("  {}", site);
+        }
+    }
+
+    if !done {
+        return Err(anyhow::anyhow!(
+            "Rename was not verified after {} iteration(s)",
+            args.max_iterations
+        ));
+    }
+    Ok(())
+}
+
+pub async fn handle_explain_diff(args: ExplainDiffArgs) -> anyhow::Result<()> {
+    use crate::utils::diff_explainer::{explain_diff, render_explanation};
+    use crate::utils::git_utils::get_git_diff_with_args;
+
+    let config = Config::load(
+        &args.config_file,
+        args.provider.clone(),
+        args.model.clone(),
+        args.api_key.clone(),
+        None,
+        args.working_dir.clone(),
+    )?;
+    let project_path = config
+        .working_dir
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Project working directory not known."))?;
+
+    let diff = if let Some(file) = &args.file {
+        std::fs::read_to_string(file)
+            .map_err(|e| anyhow::anyhow!("Failed to read patch file {}: {}", file, e))?
+    } else if let Some(range) = &args.range {
+        get_git_diff_with_args(&project_path, &[range])?
+    } else if args.staged {
+        get_git_diff_with_args(&project_path, &["--cached"])?
+    } else {
+        get_git_diff_with_args(&project_path, &[])?
+    };
+    if diff.trim().is_empty() {
+        println!("No changes to explain.");
+        return Ok(());
+    }
+
+    let provider_config = config.get_current_provider_config()?;
+    let client = create_client(&config.default_provider, provider_config).await?;
+    let (hunks, explanation) = explain_diff(&diff, client).await?;
+
+    if args.json {
+        let raw: Vec<serde_json::Value> = hunks
+            .iter()
+            .map(|hunk| {
+                let explained = explanation.hunks.iter().find(|h| h.id == hunk.id);
+                serde_json::json!({
+                    "file": hunk.file,
+                    "header": hunk.header,
+                    "explanation": explained.map(|h| h.explanation.as_str()),
+                    "risk": explained.map(|h| h.risk.as_str()),
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "summary": explanation.summary,
+                "hunks": raw,
+                "risk_notes": explanation.risk_notes,
+            }))?
+        );
+    } else {
+        println!("{}", render_explanation(&hunks, &explanation));
+    }
+    if args.gha_annotations {
+        print_annotations(&gha::review_annotations(&hunks, &explanation), args.json);
+    }
+    Ok(())
+}
+
+/// Prints GitHub Actions annotations, on stderr if stdout carries a JSON document. The runner
+/// reads workflow commands from both streams.
+fn print_annotations(annotations: &[gha::Annotation], to_stderr: bool) {
+    for annotation in annotations {
+        if to_stderr {
+            eprintln!("{}", annotation.to_command());
+        } else {
+            println!("{}", annotation.to_command());
+        }
+    }
+}
+
+pub async fn handle_import_trajectory(args: ImportTrajectoryArgs) -> anyhow::Result<()> {
+    use crate::utils::trajectory_import::{is_python_trajectory, load_any_trajectory};
+
+    let content = std::fs::read_to_string(&args.input)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", args.input, e))?;
+    if !is_python_trajectory(&serde_json::from_str(&content)?) {
+        return Err(anyhow::anyhow!(
+            "{} does not look like a Python trajectory (it may already use the Rust schema)",
+            args.input
+        ));
+    }
+    let trajectory = load_any_trajectory(std::path::Path::new(&args.input))?;
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        let input = PathBuf::from(&args.input);
+        input.with_extension("rs.json").to_string_lossy().to_string()
+    });
+    std::fs::write(&output, serde_json::to_string_pretty(&trajectory)?)?;
+    println!(
+        "Imported {} step(s) from {} into {}",
+        trajectory.steps.len(),
+        args.input,
+        output
+    );
+    Ok(())
+}
+
+/// Runs Lakeview over `execution` with the model, tags and prompts of `lakeview_config`.
+async fn generate_lakeview_summary(config: &Config, execution: &AgentExecution) -> anyhow::Result<String> {
+    let lv_config = config
+        .lakeview_config
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No lakeview_config in the configuration"))?;
+    let mut params = config
+        .model_providers
+        .get(&lv_config.model_provider)
+        .cloned()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Lakeview configured for provider '{}', but its parameters are not found in model_providers",
+                lv_config.model_provider
+            )
+        })?;
+    params.model = lv_config.model_name.clone();
+    let settings = crate::utils::lakeview::LakeviewSettings::from_config(lv_config)?;
+
+    let client = create_client(&lv_config.model_provider, &params)
+        .await
+        .map_err(|e| anyhow::anyhow!("Lakeview client error: {}", e))?;
+    crate::utils::lakeview::generate_summary(execution, client, &params, &settings, config.output_language.as_deref())
+        .await
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// Where `trae summarize` writes the summary by default: `<trajectory stem>.lakeview.md` next
+/// to the trajectory.
+fn default_summary_path(trajectory: &Path) -> PathBuf {
+    let stem = trajectory.file_stem().and_then(|s| s.to_str()).unwrap_or("trajectory");
+    trajectory.with_file_name(format!("{}.lakeview.md", stem))
+}
+
+pub async fn handle_summarize(args: SummarizeArgs) -> anyhow::Result<()> {
+    use crate::utils::trajectory_import::load_any_trajectory;
+
+    let trajectory = load_any_trajectory(&args.trajectory)?;
+    let config = Config::load(&args.config_file, None, None, None, None, None)
+        .with_context(|| format!("Failed to load configuration from {}", args.config_file))?;
+    if config.lakeview_config.is_none() {
+        anyhow::bail!(
+            "{} has no lakeview_config; add one naming the provider and model to summarize with",
+            args.config_file
+        );
+    }
+    info!(trajectory = %args.trajectory.display(), steps = trajectory.steps.len(), "Summarizing recorded run");
+    let summary = generate_lakeview_summary(&config, &trajectory.to_execution()).await?;
+
+    let output = args.output.clone().unwrap_or_else(|| default_summary_path(&args.trajectory));
+    std::fs::write(&output, &summary).with_context(|| format!("Failed to write {}", output.display()))?;
+    println!("{}", summary);
+    println!("Summary written to {}", output.display());
+    Ok(())
+}
+
+/// Builds the arguments of a `trae run` that repeats the task recorded in `trajectory`.
+fn replay_run_args(trajectory: &Trajectory, args: &ReplayArgs) -> anyhow::Result<RunArgs> {
+    let header = &trajectory.header;
+    let extra_arg = |key: &str| {
+        header
+            .extra_args
+            .as_ref()
+            .and_then(|extra| extra.get(key))
+            .cloned()
+    };
+    let seed = if args.same_seed {
+        let seed = extra_arg("seed").ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} does not record a seed; the original run did not set one",
+                args.trajectory.display()
+            )
+        })?;
+        Some(
+            seed.parse::<u64>()
+                .with_context(|| format!("Invalid seed '{}' in trajectory", seed))?,
+        )
+    } else {
+        None
+    };
+
+    Ok(RunArgs {
+        task: Some(header.task.clone()),
+        task_file: None,
+        provider: Some(header.provider.clone()),
+        model: Some(header.model.clone()),
+        api_key: None,
+        max_steps: Some(header.max_steps),
+        working_dir: args.working_dir.clone().or_else(|| extra_arg("project_path")),
+        must_patch: extra_arg("must_patch").is_some_and(|v| v.eq_ignore_ascii_case("true")),
+        config_file: args.config_file.clone(),
+        trajectory_file: args.trajectory_file.clone(),
+        patch_path: None,
+        patch_format: PatchFormat::Git,
+        base_commit: extra_arg("base_commit"),
+        lang: None,
+        output: args.output,
+        step_timeout: None,
+        stream: false,
+        reproduce: extra_arg("reproduction_scratch_dir").is_some(),
+        bundle: None,
+        seed,
+        commit_on_success: None,
+        allow_current_branch: false,
+        no_post_mortem: false,
+        no_lakeview: false,
+        setup_commands: Vec::new(),
+        teardown_commands: Vec::new(),
+        copy_patch: false,
+        attachments: Vec::new(),
+        attach_max_bytes: DEFAULT_ATTACHMENT_MAX_BYTES,
+        show_context_usage: false,
+        profile: false,
+        gha_annotations: false,
+        init_git: false,
+        issue: None,
+        labels: header.labels.clone().into_iter().collect(),
+        offline: false,
+    })
+}
+
+pub async fn handle_replay(args: ReplayArgs) -> anyhow::Result<()> {
+    use crate::utils::trajectory_import::load_any_trajectory;
+
+    let trajectory = load_any_trajectory(&args.trajectory)?;
+    if args.re_execute {
+        return re_execute_replay(&trajectory, &args).await;
+    }
+    let run_args = replay_run_args(&trajectory, &args)?;
+    info!(
+        trajectory = %args.trajectory.display(),
+        seed = ?run_args.seed,
+        "Replaying recorded task"
+    );
+    handle_run(run_args).await
+}
+
+/// Result of `trae replay --re-execute --output json`.
+#[derive(serde::Serialize, Debug)]
+struct ReExecuteReport {
+    re_executed: usize,
+    recorded: usize,
+    diverged: usize,
+    calls: Vec<crate::utils::replay::ReplayedCall>,
+}
+
+/// Replays the recorded tool calls in the `--working-dir` copy of the project.
+async fn re_execute_replay(trajectory: &Trajectory, args: &ReplayArgs) -> anyhow::Result<()> {
+    use crate::utils::replay::{re_execute, ReplaySource};
+
+    let recorded_root = trajectory
+        .header
+        .extra_args
+        .as_ref()
+        .and_then(|extra| extra.get("project_path"))
+        .map(String::as_str);
+    let working_dir = args
+        .working_dir
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("--re-execute requires --working-dir"))?;
+    let copy = std::fs::canonicalize(working_dir)
+        .with_context(|| format!("Working directory {} does not exist", working_dir))?;
+    if recorded_root.is_some_and(|root| std::fs::canonicalize(root).is_ok_and(|root| root == copy)) {
+        anyhow::bail!(
+            "--working-dir is the project the run was recorded in; re-executing would change it. Pass a copy of the project."
+        );
+    }
+    let copy = copy.to_string_lossy().into_owned();
+
+    info!(trajectory = %args.trajectory.display(), working_dir = %copy, "Re-executing recorded tool calls");
+    let calls = re_execute(trajectory, &ToolRegistry::default(), recorded_root, &copy).await;
+    let re_executed = calls.iter().filter(|c| c.source == ReplaySource::ReExecuted).count();
+    let report = ReExecuteReport {
+        re_executed,
+        recorded: calls.len() - re_executed,
+        diverged: calls.iter().filter(|c| c.diverged).count(),
+        calls,
+    };
+
+    match args.output {
+        OutputFormat::Text => {
+            for call in &report.calls {
+                let outcome = match (call.source, call.diverged) {
+                    (ReplaySource::Recorded, _) => "recorded",
+                    (ReplaySource::ReExecuted, false) => "re-executed, same result",
+                    (ReplaySource::ReExecuted, true) => "re-executed, DIVERGED",
+                };
+                println!("step {:>3}  {:<30} {}", call.step, call.tool, outcome);
+                if call.diverged {
+                    let text = call.result.error.as_deref().or(call.result.result.as_deref()).unwrap_or("");
+                    for line in text.lines().take(5) {
+                        println!("          {}", line);
+                    }
+                }
+            }
+            println!(
+                "{} tool calls: {} re-executed ({} diverged), {} returned recorded results",
+                report.calls.len(),
+                report.re_executed,
+                report.diverged,
+                report.recorded
+            );
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_examples(args: ExamplesArgs) -> anyhow::Result<()> {
+    match &args.name {
+        Some(name) => {
+            let recipe = recipes::find_recipe(name).ok_or_else(|| {
+                let names: Vec<&str> = recipes::all_recipes().iter().map(|r| r.name).collect();
+                anyhow::anyhow!("Unknown example '{}'. Available: {}", name, names.join(", "))
+            })?;
+            println!("{}", recipes::format_recipe(recipe));
+        }
+        None => {
+            for recipe in recipes::all_recipes() {
+                println!("{}", recipes::format_recipe(recipe));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies `--offline`: every LLM endpoint of the run must be local (see `utils::offline`).
+fn enable_offline_mode(config: &Config) -> anyhow::Result<()> {
+    offline::check_config(config).map_err(anyhow::Error::msg)?;
+    offline::enable();
+    if !offline::network_isolation_available() {
+        warn!(
+            "--offline: shell commands cannot be cut off from the network on this system \
+            (`unshare --user --net` is unavailable); only the LLM endpoints and tools are restricted"
+        );
+    }
+    Ok(())
+}
+
+/// Removes the tools that need the network from `registry`, for `--offline`.
+fn remove_network_tools(registry: &mut ToolRegistry) {
+    let removed = registry.remove_network_tools();
+    if !removed.is_empty() {
+        info!("--offline: removed tools that need the network: {}", removed.join(", "));
+    }
+}
+
+/// Prints a notice on stderr if `check_for_updates` is set and a newer release exists.
+async fn print_update_notice(config: &Config) {
+    if !config.check_for_updates || crate::utils::logging::is_quiet() || offline::is_offline() {
+        return;
+    }
+    let Ok(client) = self_update::http_client(&config.network) else {
+        return;
+    };
+    if let Some(notice) = self_update::update_notice(&client).await {
+        eprintln!("\n{}", notice);
+    }
+}
+
+pub async fn handle_self_update(args: SelfUpdateArgs) -> anyhow::Result<()> {
+    let network = match Config::load(&args.config_file, None, None, None, None, None) {
+        Ok(config) => config.network,
+        Err(e) => {
+            debug!("Using default network settings: {:#}", e);
+            Default::default()
+        }
+    };
+    let client = self_update::http_client(&network)?;
+    let release = self_update::fetch_release(&client, self_update::LATEST_RELEASE_URL).await?;
+    let current = self_update::current_version();
+    if args.check {
+        if release.is_newer() {
+            println!("trae {} is available (current: {}): {}", release.version(), current, release.html_url);
+        } else {
+            println!("trae {} is up to date.", current);
+        }
+        return Ok(());
+    }
+    if !release.is_newer() && !args.force {
+        println!("trae {} is up to date (latest release: {}).", current, release.version());
+        return Ok(());
+    }
+
+    let asset = release
+        .asset_for(std::env::consts::OS, std::env::consts::ARCH, self_update::TARGET_ENV)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Release {} has no binary for {}-{}; install it with cargo instead",
+                release.tag_name,
+                std::env::consts::ARCH,
+                std::env::consts::OS
+            )
+        })?;
+    let target = std::env::current_exe().context("Cannot locate the running binary")?;
+    eprintln!("Downloading {}...", asset.name);
+    let binary = self_update::download_verified(&client, &release, asset).await?;
+    self_update::install_binary(&binary, &target)?;
+    println!("Updated {} from {} to {}.", target.display(), current, release.version());
+    Ok(())
+}
+
+pub async fn handle_mcp_serve(args: McpServeArgs) -> anyhow::Result<()> {
+    let project = match &args.working_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::current_dir().context("Cannot determine the current directory")?,
+    };
+    let project = project
+        .canonicalize()
+        .with_context(|| format!("Cannot access the project directory {}", project.display()))?;
+    let run_task = if args.no_run_task {
+        None
+    } else {
+        // Runs read the config relative to the client's directory, like the server does.
+        let config_file = std::path::absolute(&args.config_file).unwrap_or_else(|_| PathBuf::from(&args.config_file));
+        Some(mcp_server::RunTaskCommand {
+            program: std::env::current_exe().context("Cannot locate the running binary")?,
+            args: vec!["--config-file".to_string(), config_file.display().to_string()],
+        })
+    };
+    let audit_log = match Config::load(&args.config_file, None, None, None, None, None) {
+        Ok(config) => open_audit_log(&config)?,
+        Err(e) => {
+            debug!("Serving without the config file: {:#}", e);
+            None
+        }
+    };
+    info!("Serving MCP for {}", project.display());
+    let server = mcp_server::McpServer::new(&ToolRegistry::default(), ToolContext::for_project(&project), run_task)
+        .with_audit_log(audit_log);
+    server
+        .serve(tokio::io::BufReader::new(tokio::io::stdin()), tokio::io::stdout())
+        .await
+}
+
+pub async fn handle_runs(command: RunsCommand) -> anyhow::Result<()> {
+    let RunsCommand::List(args) = command;
+    let path = match args.ledger.clone().or_else(ledger::default_ledger_path) {
+        Some(path) => path,
+        None => anyhow::bail!("No usage ledger: HOME and {} are not set", ledger::LEDGER_ENV),
+    };
+    let mut entries: Vec<LedgerEntry> = ledger::read(&path)?
+        .into_iter()
+        .filter(|entry| entry.has_labels(&args.labels))
+        .collect();
+    if let Some(limit) = args.limit {
+        entries.drain(..entries.len().saturating_sub(limit));
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
     }
-    if let Some(ref err_msg) = execution_result.error_message {
-        println!("Error Message: {}", err_msg);
+    if entries.is_empty() {
+        println!("No matching runs in {}.", path.display());
+        return Ok(());
     }
+    println!("{}", format_runs_table(&entries));
+    Ok(())
+}
 
-    if let Some(patch_p_ref) = args.patch_path.as_ref() {
-        if execution_result.success || args.must_patch {
-            if let Some(proj_path) = &config.working_dir {
-                match crate::utils::git_utils::get_git_diff(proj_path, args.base_commit.as_deref())
-                {
-                    Ok(diff_content) => {
-                        if let Err(e_write) = std::fs::write(patch_p_ref, diff_content) {
-                            error!("Failed to write patch file to {}: {}", patch_p_ref, e_write);
-                        } else {
-                            info!("Patch file saved to {}", patch_p_ref);
-                            println!("Patch file saved to: {}", patch_p_ref);
-                        }
-                    }
-                    Err(e_diff) => {
-                        error!("Failed to get git diff for patch file: {}", e_diff);
-                    }
-                }
-            } else {
-                error!("Cannot save patch file, project working directory not known.");
-            }
-        }
+pub async fn handle_config(command: ConfigCommand) -> anyhow::Result<()> {
+    let ConfigCommand::Validate(args) = command;
+    let issues = crate::config::validate::validate_file(Path::new(&args.config_file))?;
+    if issues.is_empty() {
+        println!("{} is valid.", args.config_file);
+        return Ok(());
     }
+    for issue in &issues {
+        println!("{}: {}", args.config_file, issue);
+    }
+    anyhow::bail!("{} issue(s) found in {}", issues.len(), args.config_file)
+}
 
-    if config.enable_lakeview {
-        if let Some(lv_config) = &config.lakeview_config {
-            info!("Lakeview enabled, attempting to generate summary...");
-            if let Some(lv_model_params_ref) = config.model_providers.get(&lv_config.model_provider)
-            {
-                let mut specific_lv_params = lv_model_params_ref.clone();
-                specific_lv_params.model = lv_config.model_name.clone();
-
-                let lakeview_llm_client_result: Result<Option<Arc<dyn LLMClient>>, anyhow::Error> =
-                    match lv_config.model_provider.as_str() {
-                        "openai" => crate::llm::OpenAIClient::new(
-                            specific_lv_params.api_key.clone(),
-                            None,
-                            specific_lv_params.clone(),
-                        )
-                        .await
-                        .map(|client| Some(Arc::new(client) as Arc<dyn LLMClient>))
-                        .map_err(|e| anyhow::anyhow!("Lakeview OpenAI client error: {}", e)),
-                        "anthropic" => crate::llm::AnthropicClient::new(
-                            specific_lv_params.api_key.clone(),
-                            None,
-                            specific_lv_params.clone(),
-                        )
-                        .await
-                        .map(|client| Some(Arc::new(client) as Arc<dyn LLMClient>))
-                        .map_err(|e| anyhow::anyhow!("Lakeview Anthropic client error: {}", e)),
-                        _ => {
-                            error!(
-                                "Unsupported Lakeview LLM provider: {}",
-                                lv_config.model_provider
-                            );
-                            Ok(None)
-                        }
-                    };
-
-                match lakeview_llm_client_result {
-                    Ok(Some(client)) => {
-                        match crate::utils::lakeview::generate_summary(
-                            &execution_result,
-                            client,
-                            &specific_lv_params,
-                        )
-                        .await
-                        {
-                            Ok(summary) => {
-                                println!("\n--- Lakeview Summary ---");
-                                println!("{}", summary);
-                            }
-                            Err(e) => {
-                                error!("Failed to generate Lakeview summary: {:?}", e);
-                                println!("\nFailed to generate Lakeview summary: {}", e);
-                            }
-                        }
-                    }
-                    Ok(None) => {
-                        warn!("Skipping Lakeview summary due to unsupported provider or client creation issue.");
-                    }
-                    Err(e) => {
-                        error!("Failed to create LLM client for Lakeview: {:?}", e);
-                        println!("\nFailed to create LLM client for Lakeview summary: {}", e);
-                    }
+pub async fn handle_auth(command: AuthCommand) -> anyhow::Result<()> {
+    match command {
+        AuthCommand::Login(args) => {
+            if std::io::stdin().is_terminal() {
+                eprint!("API key for {}: ", args.provider);
+                std::io::Write::flush(&mut std::io::stderr())?;
+            }
+            let mut api_key = String::new();
+            std::io::stdin().read_line(&mut api_key)?;
+            let api_key = api_key.trim();
+            if api_key.is_empty() {
+                anyhow::bail!("No API key given");
+            }
+            keychain::store_api_key(&args.provider, api_key)?;
+            println!("Stored the API key for {} in the OS keychain.", args.provider);
+        }
+        AuthCommand::Status(args) => {
+            // The files themselves, before any fallback fills in a key.
+            let merged = config::layers::read_layers(Path::new(&args.config_file), None)
+                .map(|layers| config::layers::merge(&layers))
+                .unwrap_or_default();
+            let mut providers: Vec<String> = merged
+                .get("model_providers")
+                .and_then(serde_json::Value::as_object)
+                .map(|providers| providers.keys().cloned().collect())
+                .unwrap_or_default();
+            for provider in ["openai", "anthropic"] {
+                if !providers.iter().any(|p| p == provider) {
+                    providers.push(provider.to_string());
+                }
+            }
+            for provider in providers {
+                let mut sources = Vec::new();
+                let in_config = merged
+                    .pointer(&format!("/model_providers/{}/api_key", provider))
+                    .and_then(serde_json::Value::as_str)
+                    .is_some_and(|key| !key.is_empty());
+                if in_config {
+                    sources.push("config file".to_string());
+                }
+                let env_var = config::api_key_env_var(&provider);
+                if std::env::var_os(&env_var).is_some() {
+                    sources.push(format!("environment ({})", env_var));
+                }
+                let mut note = String::new();
+                match keychain::lookup_api_key(&provider) {
+                    Ok(Some(_)) => sources.push("OS keychain".to_string()),
+                    Ok(None) => {}
+                    Err(e) => note = format!(" [OS keychain unavailable: {}]", e.root_cause()),
+                }
+                match sources.split_first() {
+                    None => println!("{}: not set{}", provider, note),
+                    Some((used, [])) => println!("{}: {}{}", provider, used, note),
+                    Some((used, others)) => println!("{}: {} (also: {}){}", provider, used, others.join(", "), note),
                 }
-            } else {
-                warn!("Lakeview configured for provider '{}', but its parameters are not found in model_providers.", lv_config.model_provider);
             }
-        } else {
-            info!("Lakeview enabled but no specific lakeview_config found. Skipping summary.");
         }
     }
-
-    // Trajectory is now saved internally by TrajectoryRecorder when finalize_recording is called
-    // within the agent's execution loop if a path was provided during agent initialization.
-    // This explicit block is no longer needed.
-    // if let Some(traj_path_str) = args.trajectory_file { ... }
-
     Ok(())
 }
 
-pub async fn handle_interactive(args: InteractiveArgs) -> anyhow::Result<()> {
-    info!("Starting 'interactive' command session.");
+/// Ledger entries as a table, followed by their totals.
+fn format_runs_table(entries: &[LedgerEntry]) -> String {
+    use crate::utils::usage::group_thousands;
 
-    let config = match Config::load(
-        // Removed mut
-        &args.config_file,
-        args.provider.clone(),
-        args.model.clone(),
-        args.api_key.clone(),
-        Some(args.max_steps), // Pass max_steps for interactive mode from args
-        None, // Interactive mode doesn't take working_dir directly at start, uses default
-    ) {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            error!("Failed to load configuration: {:?}", e);
-            return Err(e);
+    let mut lines = vec![format!(
+        "{:<16}  {:<7}  {:<24}  {:>5}  {:>11}  {:>9}  {:<24}  {}",
+        "STARTED (UTC)", "RESULT", "MODEL", "STEPS", "TOKENS", "COST", "LABELS", "TASK"
+    )];
+    let mut total_tokens = 0;
+    let mut total_cost = Some(0.0);
+    for entry in entries {
+        let tokens = entry.prompt_tokens + entry.completion_tokens;
+        total_tokens += tokens;
+        total_cost = total_cost.zip(entry.cost).map(|(a, b)| a + b);
+        let labels: Vec<String> = entry.labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let task: String = entry.task.lines().next().unwrap_or_default().chars().take(60).collect();
+        lines.push(format!(
+            "{:<16}  {:<7}  {:<24}  {:>5}  {:>11}  {:>9}  {:<24}  {}",
+            &crate::utils::clock::format_utc(entry.timestamp)[..16],
+            if entry.success { "ok" } else { "failed" },
+            entry.model,
+            entry.steps,
+            group_thousands(tokens),
+            entry.cost.map_or("-".to_string(), |c| format!("${:.4}", c)),
+            labels.join(","),
+            task
+        ));
+    }
+    let succeeded = entries.iter().filter(|e| e.success).count();
+    lines.push(format!(
+        "\n{} run(s), {} succeeded; {} tokens, {}",
+        entries.len(),
+        succeeded,
+        group_thousands(total_tokens),
+        match total_cost {
+            Some(cost) => format!("~${:.4}", cost),
+            None => "cost unknown for some models".to_string(),
         }
-    };
-    info!(
-        "Initial configuration loaded. Default provider: {}",
-        config.default_provider
-    );
-
-    // LLM Client is initialized by the Agent itself based on the config.
-    // No need to initialize a separate one here if it's not used directly by handle_interactive.
+    ));
+    lines.join("\n")
+}
 
-    // --- Tool Registry and Agent Initialization ---
-    let tool_registry = Arc::new(ToolRegistry::default());
-    info!(
-        "ToolRegistry initialized with {} tools.",
-        tool_registry.get_all_tools_arc().len()
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // We need an Arc<Config> for the agent
-    let agent_config = Arc::new(config.clone()); // Removed mut, config is already Arc-able
+    #[test]
+    fn test_resolve_task_sources() {
+        let no_stdin = std::io::empty();
+        assert_eq!(
+            resolve_task(Some(" Fix the bug \n"), None, no_stdin).unwrap(),
+            "Fix the bug"
+        );
 
-    let trajectory_path_buf = args.trajectory_file.map(PathBuf::from);
+        let issue = "Title: \"quoted\" crash\n\nSecond paragraph with 'quotes'.\n";
+        assert_eq!(
+            resolve_task(Some("-"), None, issue.as_bytes()).unwrap(),
+            issue.trim()
+        );
 
-    let mut agent = TraeAgent::try_new(agent_config.clone(), tool_registry.clone(), trajectory_path_buf).await?;
-    info!("TraeAgent created for interactive mode.");
+        let dir = tempfile::tempdir().unwrap();
+        let task_file = dir.path().join("issue.md");
+        std::fs::write(&task_file, issue).unwrap();
+        assert_eq!(
+            resolve_task(None, Some(&task_file), std::io::empty()).unwrap(),
+            issue.trim()
+        );
 
-    let mut rl = DefaultEditor::new().expect("Failed to create rustyline editor");
-    if PathBuf::from(".trae_history.txt").exists() {
-        let _ = rl.load_history(".trae_history.txt");
+        assert!(resolve_task(Some("-"), None, "  \n".as_bytes()).is_err());
+        assert!(resolve_task(None, Some(&dir.path().join("missing.md")), std::io::empty()).is_err());
     }
 
-    let mut conversation_history: Vec<LLMMessage> = Vec::new();
-
-    println!("Trae Interactive Mode. Type 'exit' or 'quit' to leave.");
-    println!("Special commands: config, clear_history, load_config <path> (TODO)");
+    #[test]
+    fn test_run_requires_task_or_task_file() {
+        assert!(Cli::try_parse_from(["trae", "run"]).is_err());
+        assert!(Cli::try_parse_from(["trae", "run", "-"]).is_ok());
+        assert!(Cli::try_parse_from(["trae", "run", "--task-file", "issue.md"]).is_ok());
+        assert!(Cli::try_parse_from(["trae", "run", "--issue", "issue.json"]).is_ok());
+        assert!(Cli::try_parse_from(["trae", "run", "task", "--task-file", "issue.md"]).is_err());
+        assert!(Cli::try_parse_from(["trae", "run", "task", "--allow-current-branch"]).is_err());
+        assert!(Cli::try_parse_from([
+            "trae", "run", "task", "--commit-on-success", "fix: {summary}", "--allow-current-branch"
+        ])
+        .is_ok());
+    }
 
-    loop {
-        let readline = rl.readline("trae> ");
-        match readline {
-            Ok(line) => {
-                let _ = rl.add_history_entry(line.as_str());
-                let user_input = line.trim();
+    #[tokio::test]
+    async fn test_offline_removes_tools_that_reach_remote_hosts() {
+        let provider = |base_url: &str| -> config::ModelParameters {
+            serde_json::from_value(serde_json::json!({ "api_key": "key", "model": "gpt-4o", "base_url": base_url })).unwrap()
+        };
+        let registry = |database: &str, base_url: &str| {
+            let provider = provider(base_url);
+            let database = database.to_string();
+            async move {
+                let client = create_client("openai", &provider).await.unwrap();
+                let mut registry = ToolRegistry::default();
+                let databases = std::collections::HashMap::from([("app".to_string(), database)]);
+                registry.register(DbQueryTool::new().with_databases(databases));
+                registry.register(LogInspectTool::new().with_summarizer(client, provider.base_url.as_deref()));
+                registry
+            }
+        };
 
-                if user_input.is_empty() {
-                    continue;
-                }
+        let mut remote = registry("postgres://app@db.example.com/app", "https://api.openai.com/v1").await;
+        assert_eq!(remote.remove_network_tools(), ["db_query", "log_inspect"]);
+        assert!(remote.get_tool("db_query").is_none() && remote.get_tool("bash").is_some());
 
-                if user_input == "exit" || user_input == "quit" {
-                    break;
-                } else if user_input == "config" {
-                    println!("Current configuration:\n{:#?}", agent_config); // Show agent's current config
-                    continue;
-                } else if user_input == "clear_history" {
-                    conversation_history.clear();
-                    println!("Conversation history cleared.");
-                    // Also clear agent's internal history if it maintains one separately for interactive
-                    // For now, assuming TraeAgent's new_task or equivalent will handle this.
-                    // We might need a specific agent.clear_conversation_context() method.
-                    // For this iteration, clearing `conversation_history` here is the main step.
-                    // The agent will get a fresh history on the next proper input.
-                    continue;
-                } else if user_input.starts_with("load_config ") {
-                    // TODO: Implement config reloading
-                    // This would involve:
-                    // 1. Parsing the path.
-                    // 2. Calling Config::load with the new path.
-                    // 3. If successful, re-initializing llm_client, agent_config, and agent.
-                    // 4. Clearing conversation_history.
-                    // 5. Handling errors gracefully.
-                    println!(
-                        "Config reloading is not yet implemented. Current config remains active."
-                    );
-                    continue;
-                }
+        let mut local = registry("postgres://app@localhost/app", "http://localhost:11434/v1").await;
+        assert!(local.remove_network_tools().is_empty());
+        let mut sqlite = registry("fixtures/app.db", "http://127.0.0.1:8000/v1").await;
+        assert!(sqlite.remove_network_tools().is_empty());
+    }
 
-                // Add user message to history
-                conversation_history.push(LLMMessage {
-                    role: MessageRole::User,
-                    content: Some(user_input.to_string()),
-                    name: None,
-                    tool_calls: None,
-                    tool_call_id: None,
-                });
+    #[test]
+    fn test_project_is_inferred_unless_working_dir_is_given() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        let config_file = dir.path().join("missing_config.json");
+        let config_file = config_file.to_str().unwrap();
+        let task = format!("Fix the parser in {}/src/lib.rs", dir.path().display());
 
-                // Create a task for the agent for this turn
-                // The "task" is just the user's current input.
-                // Agent arguments might be relevant for interactive mode, but keeping it simple for now.
-                if let Err(e) = agent.new_task(user_input.to_string(), None).await {
-                    error!(
-                        "Failed to set new task for agent in interactive mode: {:?}",
-                        e
-                    );
-                    println!("Error setting up task. Please try again.");
-                    conversation_history.pop(); // Remove the last user message if task setup failed
-                    continue;
-                }
+        let mut config = Config::load(config_file, None, None, None, None, None).unwrap();
+        apply_inferred_project(&mut config, Some(&task), false);
+        let inferred = dir.path().join("src").canonicalize().unwrap();
+        assert_eq!(config.working_dir, Some(inferred.display().to_string()));
 
-                // Execute a "turn" or "step" of the agent.
-                // This requires a new method on the Agent trait / TraeAgent.
-                // For now, let's assume `execute_interactive_turn` which takes current history
-                // and returns new messages from the agent for this turn.
-                // It should use `agent.messages` which `new_task` would have set up.
+        let mut config = Config::load(config_file, None, None, None, None, Some("/work".to_string())).unwrap();
+        apply_inferred_project(&mut config, Some(&task), true);
+        assert_eq!(config.working_dir.as_deref(), Some("/work"));
+    }
 
-                // The existing `execute_task` is designed for full task completion.
-                // We need a version that processes one conversational exchange.
-                // This might involve a refactor of `common_execute_task_loop` or a new loop.
+    #[test]
+    fn test_run_labels_and_runs_table() {
+        let cli = Cli::try_parse_from(["trae", "run", "task", "--label", "team=infra", "--label", "ticket=OPS-12"]).unwrap();
+        let Commands::Run(args) = cli.command else {
+            panic!("expected the run command");
+        };
+        assert_eq!(args.labels, vec![("team".to_string(), "infra".to_string()), ("ticket".to_string(), "OPS-12".to_string())]);
+        assert!(Cli::try_parse_from(["trae", "run", "task", "--label", "infra"]).is_err());
+        assert!(Cli::try_parse_from(["trae", "runs", "list", "--label", "team=infra", "--limit", "5"]).is_ok());
 
-                // Placeholder for actual agent interaction logic for one turn:
-                println!("Sending to agent: '{}'", user_input);
-                // >>> This is where the call to agent.execute_interactive_turn() would go <<<
-                // >>> It would update `conversation_history` with the agent's response <<<
+        let entry: LedgerEntry = serde_json::from_value(serde_json::json!({
+            "timestamp": 1_792_152_000, "task": "Fix the flaky deploy test\nDetails", "provider": "openai",
+            "model": "gpt-4o", "success": true, "steps": 7, "prompt_tokens": 12000, "completion_tokens": 800,
+            "cost": 0.038, "labels": {"team": "infra"}
+        }))
+        .unwrap();
+        let table = format_runs_table(&[entry.clone(), LedgerEntry { cost: None, success: false, ..entry }]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[1].starts_with("2026-10-16 12:00  ok       gpt-4o"), "{}", table);
+        assert!(lines[1].contains("12,800    $0.0380  team=infra"), "{}", table);
+        assert!(lines[1].ends_with("Fix the flaky deploy test"), "{}", table);
+        assert!(lines[2].contains("failed"));
+        assert_eq!(lines[4], "2 run(s), 1 succeeded; 25,600 tokens, cost unknown for some models");
+    }
 
-                // (No event sender for interactive mode for now, could be added for more detailed output)
-                match agent.execute_interactive_turn(None).await {
-                    Ok(new_messages) => {
-                        if new_messages.is_empty() {
-                            println!("Agent processed the input but produced no new messages for the conversation.");
-                        }
-                        for msg in new_messages {
-                            if msg.role == MessageRole::Assistant {
-                                if let Some(content) = &msg.content {
-                                    println!("Agent: {}", content);
-                                } else if msg.tool_calls.is_some() {
-                                    // In interactive mode, we might not want to show raw tool calls directly,
-                                    // but the LLM's subsequent response after tool execution is what matters.
-                                    // This part of the code will be reached if the turn ended AFTER tool calls
-                                    // but before a final summarising LLM response.
-                                    // The `execute_interactive_turn` is designed to give a final textual response.
-                                    println!("Agent: (Thinking/Using tools...)");
-                                }
-                            }
-                            // Add all messages from the turn (thoughts, tool use, final response) to history
-                            conversation_history.push(msg);
-                        }
-                    }
-                    Err(e) => {
-                        error!("Error during agent's interactive turn: {:?}", e);
-                        println!("Agent Error: {}", e);
-                        // Optionally, remove the last user message from history if the turn failed critically
-                        // For now, keep it to see the context of the error.
-                    }
+    #[test]
+    fn test_replay_run_args_reuse_recorded_settings() {
+        let trajectory: Trajectory = serde_json::from_value(serde_json::json!({
+            "header": {
+                "version": "1.0",
+                "task": "Fix the bug",
+                "provider": "openai",
+                "model": "gpt-4o",
+                "max_steps": 30,
+                "timestamp": 0,
+                "extra_args": {
+                    "project_path": "/repo",
+                    "base_commit": "abc123",
+                    "must_patch": "true",
+                    "seed": "42"
                 }
-            }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                break;
-            }
-            Err(err) => {
-                println!("Error reading line: {:?}", err);
-                break;
-            }
-        }
-    }
+            },
+            "steps": [],
+            "success": false,
+            "final_result": null,
+            "total_tokens": null
+        }))
+        .unwrap();
 
-    let _ = rl.save_history(".trae_history.txt");
-    println!("Exiting interactive session.");
-    Ok(())
-}
+        let Commands::Replay(args) =
+            Cli::try_parse_from(["trae", "replay", "run.json", "--same-seed"]).unwrap().command
+        else {
+            panic!("expected the replay subcommand");
+        };
+        let run_args = replay_run_args(&trajectory, &args).unwrap();
+        assert_eq!(run_args.task.as_deref(), Some("Fix the bug"));
+        assert_eq!(run_args.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(run_args.max_steps, Some(30));
+        assert_eq!(run_args.working_dir.as_deref(), Some("/repo"));
+        assert_eq!(run_args.base_commit.as_deref(), Some("abc123"));
+        assert!(run_args.must_patch);
+        assert_eq!(run_args.seed, Some(42));
 
-pub async fn handle_show_config(args: ShowConfigArgs) -> anyhow::Result<()> {
-    println!("Attempting to load config from: {}", args.config_file);
-    let config = Config::load(&args.config_file, None, None, None, None, None)?;
+        let Commands::Replay(args) =
+            Cli::try_parse_from(["trae", "replay", "run.json", "-w", "/other"]).unwrap().command
+        else {
+            panic!("expected the replay subcommand");
+        };
+        let run_args = replay_run_args(&trajectory, &args).unwrap();
+        assert_eq!(run_args.working_dir.as_deref(), Some("/other"));
+        assert_eq!(run_args.seed, None);
 
-    println!("\n--- Configuration ---");
-    println!("Default Provider: {}", config.default_provider);
-    println!("Max Steps: {}", config.max_steps);
-    if let Some(wd) = &config.working_dir {
-        println!("Working Directory: {}", wd);
-    } else {
-        println!("Working Directory: Not set (will use current directory)");
+        let mut unseeded = trajectory.clone();
+        unseeded.header.extra_args.as_mut().unwrap().remove("seed");
+        let Commands::Replay(args) =
+            Cli::try_parse_from(["trae", "replay", "run.json", "--same-seed"]).unwrap().command
+        else {
+            panic!("expected the replay subcommand");
+        };
+        assert!(replay_run_args(&unseeded, &args).is_err());
+
+        // Re-executing tool calls needs a copy of the project to run them in.
+        assert!(Cli::try_parse_from(["trae", "replay", "run.json", "--re-execute"]).is_err());
+        assert!(Cli::try_parse_from(["trae", "replay", "run.json", "--re-execute", "-w", "/copy"]).is_ok());
     }
 
-    println!("\nModel Providers:");
-    for (name, provider_config) in &config.model_providers {
-        println!("  Provider: {}", name);
-        println!("    Model: {}", provider_config.model);
-        println!(
-            "    API Key: {}",
-            provider_config.api_key.as_deref().unwrap_or("Not set")
-        );
-        if let Some(mt) = provider_config.max_tokens {
-            println!("    Max Tokens: {}", mt);
-        }
-        println!("    Temperature: {}", provider_config.temperature);
-        println!("    Top P: {}", provider_config.top_p);
-        if let Some(tk) = provider_config.top_k {
-            println!("    Top K: {}", tk);
-        }
-        println!(
-            "    Parallel Tool Calls: {}",
-            provider_config.parallel_tool_calls
-        );
+    #[tokio::test]
+    async fn test_upgrade_fails_when_the_final_tests_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"demo\"\n\n[dependencies]\nserde = \"1.0\"\n").unwrap();
+        let config_file = dir.path().join("trae_config.json");
+        std::fs::write(
+            &config_file,
+            serde_json::json!({
+                "default_provider": "openai",
+                "model_providers": {"openai": {"model": "gpt-4o", "api_key": "key"}}
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let upgrade_args = |extra: &[&str]| {
+            let mut argv = vec![
+                "trae", "upgrade", "--package", "serde", "--to", "2.0", "--max-iterations", "0",
+                "--build-cmd", "true", "--test-cmd", "exit 1",
+                "-w", dir.path().to_str().unwrap(), "--config-file", config_file.to_str().unwrap(),
+            ];
+            argv.extend_from_slice(extra);
+            let Commands::Upgrade(args) = Cli::try_parse_from(argv).unwrap().command else {
+                panic!("expected the upgrade subcommand");
+            };
+            args
+        };
+
+        // The build passes, but the tests still fail after the last iteration.
+        let error = handle_upgrade(upgrade_args(&[])).await.unwrap_err();
+        assert!(error.to_string().contains("`exit 1` failed"), "{}", error);
+        assert!(handle_upgrade(upgrade_args(&["--no-tests"])).await.is_ok());
     }
-    println!("--- End Configuration ---");
-    Ok(())
-}
 
-pub async fn handle_tools_command(_args: ToolsArgs) -> anyhow::Result<()> {
-    println!("\n--- Available Tools ---");
+    #[tokio::test]
+    async fn test_summarize_writes_summary_next_to_trajectory() {
+        use serde_json::json;
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
 
-    let registry = ToolRegistry::default(); // Create a default registry to list tools
-    let tools = registry.get_all_tools_arc();
+        let server = MockServer::start().await;
+        let completion = |content: &str| {
+            ResponseTemplate::new(200).set_body_json(json!({
+                "id": "resp", "object": "chat.completion", "created": 1, "model": "gpt-test",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": content}, "finish_reason": "stop"}]
+            }))
+        };
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("Here is the task the agent is performing"))
+            .respond_with(completion("is fixing the parser.</task><details>Edits parse_config.</details>"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("Sure. The tags are"))
+            .respond_with(completion("WRITE_FIX</tags>"))
+            .mount(&server)
+            .await;
 
-    if tools.is_empty() {
-        println!("No tools are currently registered.");
-    } else {
-        // Simple table-like format
-        // Determine max tool name length for alignment
-        let max_name_len = tools.iter().map(|t| t.get_name().len()).max().unwrap_or(20);
+        let dir = tempfile::tempdir().unwrap();
+        let trajectory = dir.path().join("run.json");
+        std::fs::write(
+            &trajectory,
+            json!({
+                "header": {"version": "1.0", "task": "Fix the parser", "provider": "openai", "model": "gpt-4o",
+                    "max_steps": 10, "timestamp": 0, "extra_args": null},
+                "steps": [{
+                    "step_number": 1, "state": "Completed", "duration_ms": 10,
+                    "llm_response": {"id": "r1", "object": "chat.completion", "created": 1, "model": "gpt-4o",
+                        "choices": [{"index": 0, "message": {"role": "assistant", "content": "I will fix parse_config."}, "finish_reason": "stop"}]}
+                }],
+                "success": true, "final_result": "Fixed.", "total_tokens": null
+            })
+            .to_string(),
+        )
+        .unwrap();
 
-        println!("{:<width$} | Description", "Tool Name", width = max_name_len);
-        println!("{:-<width$}-|----------------------------------", "-", width = max_name_len);
+        // Every provider `create_client` knows can summarize, OpenRouter included.
+        for provider in ["openai", "openrouter"] {
+            let config_file = dir.path().join("trae_config.json");
+            std::fs::write(
+                &config_file,
+                json!({
+                    "default_provider": provider,
+                    "enable_lakeview": false,
+                    "model_providers": {provider: {"model": "gpt-4o", "api_key": "key", "base_url": server.uri(), "max_retries": 0}},
+                    "lakeview_config": {"model_provider": provider, "model_name": "gpt-test"}
+                })
+                .to_string(),
+            )
+            .unwrap();
 
-        for tool in tools {
-            let name = tool.get_name();
-            let description = tool.get_description();
-            // Basic wrapping for description if too long (very naive)
-            let short_desc = if description.len() > 70 {
-                format!("{}...", &description[..67])
-            } else {
-                description
+            let Commands::Summarize(args) = Cli::try_parse_from([
+                "trae",
+                "summarize",
+                trajectory.to_str().unwrap(),
+                "--config-file",
+                config_file.to_str().unwrap(),
+            ])
+            .unwrap()
+            .command
+            else {
+                panic!("expected the summarize subcommand");
             };
-            println!("{:<width$} | {}", name, short_desc, width = max_name_len);
+            handle_summarize(args).await.unwrap();
+            let summary = std::fs::read_to_string(dir.path().join("run.lakeview.md")).unwrap();
+            assert!(summary.contains("Step 1: 📝 The agent is fixing the parser."), "{}", summary);
+            assert!(summary.contains("Final Agent Message: Fixed."));
         }
     }
-    println!("--- End Available Tools ---");
-    Ok(())
 }
@@ -0,0 +1,308 @@
+//! # MCP Server
+//!
+//! `trae mcp-serve` speaks the Model Context Protocol over stdio, so editors and other agents
+//! can use Trae as a backend: its built-in tools are listed and called as MCP tools against
+//! the served project, and `run_task` runs a whole agent task. Messages are JSON-RPC 2.0, one
+//! per line. Requests are handled concurrently, so a long `run_task` does not block other
+//! calls; each task runs as a `trae run --output json` child process, keeping its progress
+//! output off the protocol stream.
+
+use crate::tools::{Tool, ToolContext, ToolRegistry};
+use crate::utils::audit_log::{AuditEntry, AuditLog};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// The protocol version answered when the client does not ask for one.
+const PROTOCOL_VERSION: &str = "2024-11-05";
+/// Tools that only make sense inside the agent loop.
+const AGENT_ONLY_TOOLS: &[&str] = &["task_done", "final_report"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// How `run_task` starts a `trae run`.
+#[derive(Debug, Clone)]
+pub struct RunTaskCommand {
+    /// The trae binary.
+    pub program: PathBuf,
+    /// Arguments passed to every run, e.g. `--config-file`.
+    pub args: Vec<String>,
+}
+
+/// Serves the tools of one project; see the module docs.
+pub struct McpServer {
+    tools: BTreeMap<String, Arc<dyn Tool + Send + Sync>>,
+    context: ToolContext,
+    /// `None` disables `run_task`.
+    run_task: Option<RunTaskCommand>,
+    audit_log: Option<Arc<AuditLog>>,
+}
+
+impl McpServer {
+    /// A server for the project of `context`, with the tools of `registry` (one instance per
+    /// session, see `Tool::for_run`).
+    pub fn new(registry: &ToolRegistry, context: ToolContext, run_task: Option<RunTaskCommand>) -> Self {
+        let tools = registry
+            .tools_for_run()
+            .into_iter()
+            .map(|tool| (tool.get_name(), tool))
+            .filter(|(name, _)| !AGENT_ONLY_TOOLS.contains(&name.as_str()))
+            .collect();
+        Self {
+            tools,
+            context,
+            run_task,
+            audit_log: None,
+        }
+    }
+
+    /// Records the commands, file writes and network calls of tool calls in `audit_log`.
+    /// Tasks run by `run_task` keep their own audit log, from the same config file.
+    pub fn with_audit_log(self, audit_log: Option<Arc<AuditLog>>) -> Self {
+        Self { audit_log, ..self }
+    }
+
+    /// Reads requests from `reader` until it closes, writing responses to `writer`.
+    pub async fn serve<R, W>(self, reader: R, writer: W) -> anyhow::Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let server = Arc::new(self);
+        let writer = Arc::new(Mutex::new(writer));
+        let mut lines = reader.lines();
+        let mut handlers = tokio::task::JoinSet::new();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (server, writer) = (server.clone(), writer.clone());
+            handlers.spawn(async move {
+                let Some(response) = server.handle_line(&line).await else {
+                    return;
+                };
+                let mut writer = writer.lock().await;
+                let written = async {
+                    writer.write_all(format!("{}\n", response).as_bytes()).await?;
+                    writer.flush().await
+                };
+                if let Err(e) = written.await {
+                    warn!("Failed to write an MCP response: {}", e);
+                }
+            });
+        }
+        // Answer the requests still in flight before the client's input closed.
+        while handlers.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    /// The response to one message, `None` for notifications.
+    async fn handle_line(&self, line: &str) -> Option<Value> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &format!("Parse error: {}", e))),
+        };
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return id.map(|id| error_response(id, INVALID_REQUEST, "Missing method"));
+        };
+        debug!(method, "MCP request");
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.dispatch(method, params).await;
+        // Notifications get no response, not even an error.
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": params.get("protocolVersion").and_then(Value::as_str).unwrap_or(PROTOCOL_VERSION),
+                "capabilities": {"tools": {"listChanged": false}},
+                "serverInfo": {"name": "trae", "version": env!("CARGO_PKG_VERSION")},
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({"tools": self.tool_list()})),
+            "tools/call" => {
+                let name = params
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+                let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+                self.call_tool(name, arguments).await
+            }
+            method if method.starts_with("notifications/") => Ok(Value::Null),
+            method => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        }
+    }
+
+    fn tool_list(&self) -> Vec<Value> {
+        let mut tools: Vec<Value> = self
+            .tools
+            .values()
+            .map(|tool| {
+                let definition = tool.get_json_definition().function;
+                json!({
+                    "name": definition.name,
+                    "description": definition.description,
+                    "inputSchema": definition.parameters,
+                })
+            })
+            .collect();
+        if self.run_task.is_some() {
+            tools.push(json!({
+                "name": "run_task",
+                "description": "Runs a software engineering task with the Trae agent in the served project \
+                    (or `working_dir`) until it is done, and returns the run report as JSON.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "task": {"type": "string", "description": "The task, in natural language."},
+                        "working_dir": {"type": "string", "description": "Project directory (default: the served project)."},
+                        "max_steps": {"type": "integer", "description": "Maximum agent steps."},
+                        "provider": {"type": "string", "description": "LLM provider (default: the configured one)."},
+                        "model": {"type": "string", "description": "Model (default: the configured one)."},
+                    },
+                    "required": ["task"],
+                },
+            }));
+        }
+        tools
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, (i64, String)> {
+        if name == "run_task" {
+            if let Some(command) = &self.run_task {
+                return Ok(self.run_task(command, &arguments).await);
+            }
+        }
+        let tool = self
+            .tools
+            .get(name)
+            .ok_or((INVALID_PARAMS, format!("Unknown tool: {}", name)))?;
+        let audit = self
+            .audit_log
+            .as_ref()
+            .and_then(|log| Some((log, AuditEntry::for_call(tool.as_ref(), &arguments, &self.context)?)));
+        let start = std::time::Instant::now();
+        let (text, is_error) = match tool.execute(arguments, &self.context).await {
+            Ok(result) if result.error_code == 0 => {
+                let text = [result.output, result.error].into_iter().flatten().collect::<Vec<_>>().join("\n");
+                (text, false)
+            }
+            Ok(result) => (result.error.or(result.output).unwrap_or_default(), true),
+            Err(e) => (e.to_string(), true),
+        };
+        if let Some((log, entry)) = audit {
+            let error = is_error.then_some(text.as_str());
+            log.record(&entry.with_outcome(!is_error, error, Some(start.elapsed().as_millis())));
+        }
+        Ok(tool_result(&text, is_error))
+    }
+
+    async fn run_task(&self, command: &RunTaskCommand, arguments: &Value) -> Value {
+        let text = |key: &str| arguments.get(key).and_then(Value::as_str).filter(|v| !v.trim().is_empty());
+        let Some(task) = text("task") else {
+            return tool_result("'task' is required.", true);
+        };
+        let working_dir = match text("working_dir") {
+            Some(dir) => self.context.resolve_path(dir),
+            None => self.context.resolve_path("."),
+        };
+        let mut args = command.args.clone();
+        args.extend(["--working-dir".to_string(), working_dir.display().to_string()]);
+        args.extend(["--output".to_string(), "json".to_string()]);
+        if let Some(max_steps) = arguments.get("max_steps").and_then(Value::as_u64) {
+            args.extend(["--max-steps".to_string(), max_steps.to_string()]);
+        }
+        for key in ["provider", "model"] {
+            if let Some(value) = text(key) {
+                args.extend([format!("--{}", key), value.to_string()]);
+            }
+        }
+        args.extend(["--".to_string(), task.to_string()]);
+
+        let output = tokio::process::Command::new(&command.program)
+            .arg("run")
+            .args(&args)
+            // Prompts (permissions, step extensions) cannot reach the user; they are declined.
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await;
+        match output {
+            Ok(output) => {
+                let report = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if output.status.success() {
+                    tool_result(&report, false)
+                } else {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let detail = if report.is_empty() { stderr.trim() } else { report.as_str() };
+                    tool_result(&format!("The run failed ({}): {}", output.status, detail), true)
+                }
+            }
+            Err(e) => tool_result(&format!("Failed to start {}: {}", command.program.display(), e), true),
+        }
+    }
+}
+
+fn tool_result(text: &str, is_error: bool) -> Value {
+    json!({"content": [{"type": "text", "text": text}], "isError": is_error})
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serves_tool_list_and_calls_over_json_rpc() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "needle in a haystack\n").unwrap();
+        let server = McpServer::new(&ToolRegistry::default(), ToolContext::for_project(dir.path()), None);
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26"}}"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"search","arguments":{"pattern":"needle"}}}"#,
+            r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"task_done","arguments":{}}}"#,
+            r#"{"jsonrpc":"2.0","id":5,"method":"resources/list"}"#,
+            "not json",
+        ]
+        .join("\n");
+        let (client, server_end) = tokio::io::duplex(1 << 20);
+        server.serve(input.as_bytes(), server_end).await.unwrap();
+
+        let mut output = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut { client }, &mut output).await.unwrap();
+        let responses: BTreeMap<String, Value> = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .map(|response| (response["id"].to_string(), response))
+            .collect();
+        assert_eq!(responses.len(), 6, "{}", output);
+        assert_eq!(responses["1"]["result"]["protocolVersion"], "2025-03-26");
+        let tools = responses["2"]["result"]["tools"].as_array().unwrap();
+        assert!(tools.iter().any(|tool| tool["name"] == "search" && tool["inputSchema"]["type"] == "object"));
+        assert!(!tools.iter().any(|tool| tool["name"] == "task_done" || tool["name"] == "run_task"));
+        let call = &responses["3"]["result"];
+        assert_eq!(call["isError"], false);
+        assert!(call["content"][0]["text"].as_str().unwrap().contains("1: needle in a haystack"), "{}", call);
+        assert_eq!(responses["4"]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses["5"]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses["null"]["error"]["code"], PARSE_ERROR);
+    }
+}
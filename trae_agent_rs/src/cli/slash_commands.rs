@@ -0,0 +1,152 @@
+//! # Interactive Slash Commands
+//!
+//! Parses the `/...` commands understood by `trae interactive`, which act on the session
+//! (such as its project directory) instead of being sent to the agent as a chat turn.
+
+use std::path::{Path, PathBuf};
+
+/// A slash command entered in an interactive session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlashCommand {
+    /// `/cd <path>`: set or switch the project directory.
+    Cd(String),
+    /// `/project`: show the project directory.
+    Project,
+    /// `/diff`: show the uncommitted changes in the project.
+    Diff,
+    /// `/cost`: show the session's token usage and estimated cost by model.
+    Cost,
+    /// `/context`: show what fills the conversation sent to the model.
+    Context,
+    /// `/copy [patch]`: put the agent's last answer, or the project's diff, on the clipboard.
+    Copy(CopyTarget),
+    /// `/task start <description>`: run the description as a full agent task.
+    TaskStart(String),
+    /// `/task done`: close the current task and return to chat.
+    TaskDone,
+    /// `/help`: list the slash commands.
+    Help,
+    /// Any other `/word`; carries the command name.
+    Unknown(String),
+}
+
+/// What `/copy` puts on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyTarget {
+    /// The agent's last answer, or the result of the last task.
+    Reply,
+    /// The uncommitted changes in the project.
+    Patch,
+}
+
+/// One-line descriptions of the slash commands, shown by `/help`.
+pub const HELP: &str = "\
+/cd <path>   Set the project directory the agent works in
+/project     Show the project directory
+/diff        Show uncommitted changes in the project
+/cost        Show tokens used and estimated cost by model
+/context     Show what fills the agent's context, by category
+/copy [patch]
+             Copy the agent's last answer (or the project's diff) to the clipboard
+/task start <description>
+             Run a full agent task in the project, showing its progress
+/task done   Close the current task and return to chat
+/help        Show this list";
+
+/// Parses `input` as a slash command, or returns `None` if it does not start with `/`.
+pub fn parse(input: &str) -> Option<SlashCommand> {
+    let rest = input.trim().strip_prefix('/')?;
+    let (name, arg) = match rest.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (rest, ""),
+    };
+    Some(match name {
+        "cd" => SlashCommand::Cd(arg.to_string()),
+        "project" => SlashCommand::Project,
+        "diff" => SlashCommand::Diff,
+        "cost" => SlashCommand::Cost,
+        "context" => SlashCommand::Context,
+        "copy" => match arg {
+            "" => SlashCommand::Copy(CopyTarget::Reply),
+            "patch" | "diff" => SlashCommand::Copy(CopyTarget::Patch),
+            _ => SlashCommand::Unknown(format!("copy {}", arg)),
+        },
+        "task" => match arg.split_once(char::is_whitespace).unwrap_or((arg, "")) {
+            ("start", description) => SlashCommand::TaskStart(description.trim().to_string()),
+            ("done", _) => SlashCommand::TaskDone,
+            _ => SlashCommand::Unknown(format!("task {}", arg).trim_end().to_string()),
+        },
+        "help" => SlashCommand::Help,
+        other => SlashCommand::Unknown(other.to_string()),
+    })
+}
+
+/// Resolves the argument of `/cd` to an absolute, existing directory.
+///
+/// Relative paths are resolved against the current project directory (or the process's
+/// working directory without one), and a leading `~` expands to the home directory.
+pub fn resolve_project_dir(current: Option<&Path>, arg: &str) -> Result<PathBuf, String> {
+    if arg.is_empty() {
+        return Err("Usage: /cd <path>".to_string());
+    }
+    let expanded = match arg.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = std::env::var("HOME").map_err(|_| "HOME is not set".to_string())?;
+            PathBuf::from(format!("{}{}", home, rest))
+        }
+        _ => PathBuf::from(arg),
+    };
+    let path = match current {
+        Some(base) if expanded.is_relative() => base.join(expanded),
+        _ => expanded,
+    };
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("Cannot use {}: {}", path.display(), e))?;
+    if !path.is_dir() {
+        return Err(format!("{} is not a directory", path.display()));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_slash_commands() {
+        assert_eq!(parse("fix the bug"), None);
+        assert_eq!(parse("/cd  ../other "), Some(SlashCommand::Cd("../other".to_string())));
+        assert_eq!(parse("/cd"), Some(SlashCommand::Cd(String::new())));
+        assert_eq!(parse(" /diff"), Some(SlashCommand::Diff));
+        assert_eq!(parse("/project"), Some(SlashCommand::Project));
+        assert_eq!(parse("/cost"), Some(SlashCommand::Cost));
+        assert_eq!(parse("/context"), Some(SlashCommand::Context));
+        assert_eq!(parse("/copy"), Some(SlashCommand::Copy(CopyTarget::Reply)));
+        assert_eq!(parse("/copy patch"), Some(SlashCommand::Copy(CopyTarget::Patch)));
+        assert_eq!(parse("/copy all"), Some(SlashCommand::Unknown("copy all".to_string())));
+        assert_eq!(parse("/nope x"), Some(SlashCommand::Unknown("nope".to_string())));
+        assert_eq!(
+            parse("/task start  fix the flaky test "),
+            Some(SlashCommand::TaskStart("fix the flaky test".to_string()))
+        );
+        assert_eq!(parse("/task start"), Some(SlashCommand::TaskStart(String::new())));
+        assert_eq!(parse("/task done"), Some(SlashCommand::TaskDone));
+        assert_eq!(parse("/task"), Some(SlashCommand::Unknown("task".to_string())));
+    }
+
+    #[test]
+    fn test_resolve_project_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("file.txt"), "").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+
+        assert_eq!(resolve_project_dir(Some(&root), "sub").unwrap(), root.join("sub"));
+        assert_eq!(resolve_project_dir(Some(&root.join("sub")), "..").unwrap(), root);
+        assert_eq!(resolve_project_dir(None, root.to_str().unwrap()).unwrap(), root);
+        assert!(resolve_project_dir(Some(&root), "file.txt").unwrap_err().contains("not a directory"));
+        assert!(resolve_project_dir(Some(&root), "missing").is_err());
+        assert!(resolve_project_dir(Some(&root), "").is_err());
+    }
+}
@@ -2,16 +2,26 @@
 //!
 //! Defines structures and logic for loading and managing configuration
 //! for the Trae Rust Agent. Configuration can be loaded from a JSON file,
-//! environment variables, and command-line arguments.
+//! environment variables, and command-line arguments. JSON files are merged from system,
+//! user and project layers (see `layers`). YAML files in the layout used by the Python
+//! trae-agent are accepted as well (see `python_compat`).
+
+pub mod layers;
+pub mod project_file;
+mod python_compat;
+pub mod validate;
+
+pub(crate) use python_compat::is_yaml_path;
 
 use anyhow::{Context, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use tracing::warn;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
 
 /// Defines the parameters for a specific Large Language Model.
+#[allow(dead_code)] // Some fields mirror the Python config and are not consumed by every client yet
 #[derive(Deserialize, Debug, Clone)]
 pub struct ModelParameters {
     /// Optional API key for the LLM provider.
@@ -38,24 +48,135 @@ pub struct ModelParameters {
     pub candidate_count: Option<u32>, // Python uses int
     #[serde(default)]
     pub stop_sequences: Option<Vec<String>>,
+    /// Sampling seed, sent to providers that support deterministic sampling (OpenAI `seed`).
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Extra HTTP headers sent with every request to this provider (e.g., gateway credentials).
+    #[serde(default)]
+    pub extra_headers: Option<HashMap<String, String>>,
+    /// Client-side request quota, per minute. Shared by all clients in the process that use
+    /// the same endpoint and model, so concurrent runs stay under the provider's limit together.
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// Client-side token quota (prompt and completion), per minute; shared like
+    /// `requests_per_minute`.
+    #[serde(default)]
+    pub tokens_per_minute: Option<u64>,
+    /// App attribution and provider routing, for the `openrouter` provider.
+    #[serde(default)]
+    pub openrouter: Option<OpenRouterOptions>,
+    /// Network settings, copied from `Config::network` when the configuration is loaded.
+    #[serde(skip)]
+    pub network: NetworkConfig,
+}
+
+/// OpenRouter-specific settings of a provider entry.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct OpenRouterOptions {
+    /// Sent as `HTTP-Referer`, which OpenRouter uses to attribute requests to an app.
+    #[serde(default)]
+    pub site_url: Option<String>,
+    /// Sent as `X-Title`, the app name shown in OpenRouter's rankings.
+    #[serde(default)]
+    pub app_name: Option<String>,
+    /// Which upstream providers serve the model (OpenRouter's `provider` preferences).
+    #[serde(default)]
+    pub provider: Option<ProviderRouting>,
+}
+
+/// OpenRouter provider routing preferences, sent as the request's `provider` object.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ProviderRouting {
+    /// Providers to try first, in order (e.g., ["Anthropic", "Together"]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+    /// Only use these providers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<String>,
+    /// Never use these providers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+    /// Whether other providers may serve the request when those in `order` fail.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+    /// Only use providers that support every parameter of the request (e.g., tools).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_parameters: Option<bool>,
+    /// "allow" or "deny" providers that may store prompts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_collection: Option<String>,
+    /// Prefer providers by "price", "throughput" or "latency".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+}
+
+/// Proxy, TLS and timeout settings applied to every outgoing HTTP client.
+///
+/// Without an explicit proxy, the standard `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY`
+/// environment variables are honoured.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct NetworkConfig {
+    /// Proxy for all traffic, e.g. "http://proxy:3128" or "socks5://127.0.0.1:1080".
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Proxy for plain HTTP traffic only.
+    #[serde(default)]
+    pub http_proxy: Option<String>,
+    /// Proxy for HTTPS traffic only.
+    #[serde(default)]
+    pub https_proxy: Option<String>,
+    /// Comma-separated hosts, domains or CIDR ranges that bypass the proxy.
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+    /// Path to a PEM file with additional trusted CA certificates (e.g., a corporate root).
+    #[serde(default)]
+    pub ca_bundle: Option<String>,
+    /// Timeout for establishing a connection, in seconds.
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    /// Timeout for a whole request, including reading the response, in seconds.
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>,
+    /// Most idle connections kept open per host (default: no limit).
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long an idle pooled connection is kept before it is closed, in seconds (default: 90).
+    #[serde(default)]
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// Interval of TCP and HTTP/2 keep-alive probes on open connections, in seconds (default: 30).
+    #[serde(default)]
+    pub keep_alive_secs: Option<u64>,
 }
 
-fn default_max_tokens_openai() -> Option<u32> {
+pub(crate) fn default_max_tokens_openai() -> Option<u32> {
     Some(128000)
 }
-fn default_temperature() -> f32 {
+pub(crate) fn default_temperature() -> f32 {
     0.5
 }
-fn default_top_p() -> f32 {
+pub(crate) fn default_top_p() -> f32 {
     1.0
 }
-fn default_parallel_tool_calls() -> bool {
+pub(crate) fn default_parallel_tool_calls() -> bool {
     false // Aligning with Python's default
 }
 pub fn default_max_retries() -> u32 { // Made public
     10 // From Python's ModelParameters default
 }
 
+/// The environment variable holding the API key of `provider` (e.g., `OPENAI_API_KEY`).
+pub fn api_key_env_var(provider: &str) -> String {
+    match provider {
+        "openai" => "OPENAI_API_KEY".to_string(),
+        "anthropic" => "ANTHROPIC_API_KEY".to_string(),
+        "azure" => "AZURE_API_KEY".to_string(), // from Python's env_var_map
+        "openrouter" => "OPENROUTER_API_KEY".to_string(), // from Python's env_var_map
+        "doubao" => "DOUBAO_API_KEY".to_string(), // from Python's env_var_map
+        "google" => "GOOGLE_API_KEY".to_string(), // from Python's env_var_map
+        _ => format!("{}_API_KEY", provider.to_uppercase()),
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     pub default_provider: String,
@@ -68,6 +189,135 @@ pub struct Config {
     pub enable_lakeview: bool,
     #[serde(skip)]
     pub working_dir: Option<String>,
+    /// Natural language the agent and Lakeview summaries should answer in (e.g., "Chinese", "ja").
+    /// Tool calls, code and commands stay in their original language.
+    #[serde(default)]
+    pub output_language: Option<String>,
+    /// Seconds between heartbeat events while an LLM call or tool execution is in progress.
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    /// Step duration (seconds) after which the step is reported as possibly stuck.
+    #[serde(default = "default_stuck_step_warning_secs")]
+    pub stuck_step_warning_secs: u64,
+    /// Proxy, CA bundle and timeout settings for all HTTP clients.
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// Optional step duration (seconds) after which the step is aborted and the task fails.
+    #[serde(default)]
+    pub step_timeout_secs: Option<u64>,
+    /// Stream LLM responses, so tool calls are reported (and file writes pre-checked)
+    /// while the model is still generating their arguments.
+    #[serde(default)]
+    pub stream: bool,
+    /// Ask the user before each shell command the agent runs. Commands can be allowed for
+    /// the session or, in `.trae/permissions.json`, for the project.
+    #[serde(default)]
+    pub confirm_commands: bool,
+    /// Optional routing of simple steps to an inexpensive model.
+    #[serde(default)]
+    pub routing: Option<RoutingConfig>,
+    /// Optional periodic reminder of the task state for long runs.
+    #[serde(default)]
+    pub regrounding: Option<RegroundingConfig>,
+    /// What to do when an external tool is registered under a name that is already taken.
+    #[serde(default)]
+    pub tool_conflict_policy: ToolConflictPolicy,
+    /// Token budget for a run, checked before each LLM request.
+    #[serde(default)]
+    pub token_budget: Option<TokenBudgetConfig>,
+    /// Per-run caps on tool usage, checked after each step's tool calls.
+    #[serde(default)]
+    pub tool_caps: Option<ToolCapsConfig>,
+    /// Shell commands run once in the project before the agent starts (e.g., "npm ci"); their
+    /// outcome is summarized in the system prompt.
+    #[serde(default)]
+    pub setup_commands: Vec<String>,
+    /// Maximum time for each setup command, in seconds.
+    #[serde(default = "default_setup_timeout_secs")]
+    pub setup_timeout_secs: u64,
+    /// Shell commands run in the project when the run ends, whether it succeeded or not
+    /// (e.g., "docker compose down"); recorded in the trajectory.
+    #[serde(default)]
+    pub teardown_commands: Vec<String>,
+    /// Maximum time for each teardown command, in seconds.
+    #[serde(default = "default_teardown_timeout_secs")]
+    pub teardown_timeout_secs: u64,
+    /// Print a notice when a newer release is available (checked at most once a day).
+    #[serde(default)]
+    pub check_for_updates: bool,
+    /// Check at startup that a model missing from the capability catalog can call tools, with
+    /// one short live request (see `llm::capabilities`).
+    #[serde(default = "default_probe_capabilities")]
+    pub probe_capabilities: bool,
+    /// Databases the `db_query` tool may read, by name: a Postgres connection string
+    /// (`postgres://...` or libpq `host=... dbname=...`) or the path to a SQLite file.
+    /// Connection strings stay out of the conversation, and their passwords out of crash
+    /// bundles and `--print-effective-config`.
+    #[serde(default)]
+    pub databases: HashMap<String, String>,
+    /// How binary file changes go into the saved patch: as git binary patches (`include`), or
+    /// left out of it and listed in the run report and bundle manifest (`list`).
+    #[serde(default)]
+    pub binary_patches: BinaryPatches,
+    /// Prices in US dollars per million tokens by model name prefix, e.g.
+    /// `{"my-model": {"input_per_mtok": 0.5, "output_per_mtok": 1.5}}`, used for the run's cost
+    /// before the built-in list prices.
+    #[serde(default)]
+    pub pricing: crate::utils::usage::Pricing,
+    /// Where the "What changed and why" changelog of a successful run goes (see
+    /// `utils::changelog`); none of these by default.
+    #[serde(default)]
+    pub changelog: Option<ChangelogConfig>,
+    /// Lets the agent ask for more steps with the `request_extension` tool, if set.
+    #[serde(default)]
+    pub step_extensions: Option<StepExtensionConfig>,
+    /// Records every command, file write and network call of the agent in an audit log (see
+    /// `utils::audit_log`), if set.
+    #[serde(default)]
+    pub audit_log: Option<AuditLogConfig>,
+    /// Runs `bash` and the file editing tool inside a Docker container (see
+    /// `tools::execution`), if set.
+    #[serde(default)]
+    pub sandbox: Option<SandboxConfig>,
+    /// Which commands and file writes run without asking, need the user's approval, or are
+    /// refused (see `utils::approval`), if set.
+    #[serde(default)]
+    pub approval: Option<ApprovalConfig>,
+    /// The command that runs the project's tests, which the system prompt tells the agent to use.
+    #[serde(default)]
+    pub test_command: Option<String>,
+    /// Files and directories the editing tools may not modify, relative to the project (e.g.,
+    /// "migrations/").
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+    /// Files of project conventions (e.g., "CONTRIBUTING.md") added to the system prompt,
+    /// relative to the project.
+    #[serde(default)]
+    pub instruction_files: Vec<String>,
+}
+
+/// How the saved patch handles changes to binary files.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryPatches {
+    /// Include them as git binary patches, which `git apply` understands.
+    #[default]
+    Include,
+    /// Leave them out of the patch and list them separately.
+    List,
+}
+
+/// How the tool registry resolves an external tool whose name is already taken.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolConflictPolicy {
+    /// Refuse to register the new tool and report the conflict.
+    #[default]
+    Error,
+    /// Keep the tool already registered and skip the new one.
+    KeepExisting,
+    /// Replace the registered tool with the new one.
+    Replace,
 }
 
 /// Configuration specific to the Lakeview summarization feature.
@@ -77,14 +327,333 @@ pub struct LakeviewConfig {
     pub model_provider: String,
     /// The specific model name from the provider to be used for summaries.
     pub model_name: String,
+    /// Extra step tags, such as MIGRATION or SECURITY; a tag named like a built-in one
+    /// replaces it.
+    #[serde(default)]
+    pub tags: Vec<LakeviewTag>,
+    /// Use only `tags`, without the built-in ones.
+    #[serde(default)]
+    pub replace_default_tags: bool,
+    /// Template file replacing the prompt that describes the task of a step. Relative paths
+    /// are relative to the config file.
+    #[serde(default)]
+    pub extractor_prompt_file: Option<PathBuf>,
+    /// Template file replacing the prompt that tags a step; `{tags}` is replaced with the list
+    /// of tags and their descriptions.
+    #[serde(default)]
+    pub tagger_prompt_file: Option<PathBuf>,
+}
+
+impl LakeviewConfig {
+    /// Makes relative template paths relative to `config_dir`, the config file's directory.
+    fn resolve_paths(&mut self, config_dir: &Path) {
+        for file in [&mut self.extractor_prompt_file, &mut self.tagger_prompt_file].into_iter().flatten() {
+            if file.is_relative() {
+                *file = config_dir.join(&*file);
+            }
+        }
+    }
 }
 
-fn default_max_steps() -> u32 {
+/// A tag Lakeview may give a step.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LakeviewTag {
+    /// Name the model answers with, e.g. "MIGRATION".
+    pub name: String,
+    /// Shown for the tag in summaries.
+    pub emoji: String,
+    /// Tells the model when the tag applies.
+    pub description: String,
+}
+
+/// Routing of simple steps to an inexpensive model (see `agent::router`).
+///
+/// A step is sent to the cheap model only if its context is short, no failure happened in
+/// the last few steps and, when classification is enabled, the cheap model tags it THINK or
+/// EXAMINE. Everything else, including the first step, uses the default provider.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RoutingConfig {
+    /// Provider (a key of `model_providers`) that serves simple steps.
+    pub cheap_provider: String,
+    /// Model to use with `cheap_provider`, if not the one configured for it.
+    #[serde(default)]
+    pub cheap_model: Option<String>,
+    /// Estimated prompt size, in tokens, above which steps always use the premium model.
+    #[serde(default = "default_max_simple_context_tokens")]
+    pub max_simple_context_tokens: usize,
+    /// Number of steps after a failure (a failed tool call, a rejected completion or an LLM
+    /// error) that use the premium model.
+    #[serde(default = "default_failure_cooldown_steps")]
+    pub failure_cooldown_steps: u32,
+    /// Ask the cheap model to tag each step as THINK, EXAMINE, EDIT or VERIFY. Without
+    /// classification, every short step without recent failures goes to the cheap model.
+    #[serde(default = "default_classify_steps")]
+    pub classify_steps: bool,
+}
+
+pub(crate) fn default_max_simple_context_tokens() -> usize {
+    16_000
+}
+pub(crate) fn default_failure_cooldown_steps() -> u32 {
+    2
+}
+pub(crate) fn default_classify_steps() -> bool {
+    true
+}
+
+/// Periodic re-grounding: every `interval_steps` steps, a compact note with the objective, the
+/// current plan item, the files modified so far and any outstanding test failures is added to
+/// the conversation, so the model does not drift from the task in long runs.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RegroundingConfig {
+    /// Number of steps between notes.
+    #[serde(default = "default_regrounding_interval_steps")]
+    pub interval_steps: u32,
+    /// Custom note template. Placeholders: `{task}`, `{step}`, `{plan_item}`,
+    /// `{modified_files}` and `{test_failures}`.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+pub(crate) fn default_regrounding_interval_steps() -> u32 {
+    10
+}
+
+/// Caps the tokens a run may use. Before each step the agent projects what the next request
+/// will cost, including tool output the model has not seen yet, and stops the run instead of
+/// sending a request that would go over the budget.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TokenBudgetConfig {
+    /// Maximum tokens (prompt and completion, over all requests) for the run.
+    pub max_total_tokens: u64,
+    /// Characters per token used to estimate the size of messages.
+    #[serde(default = "default_chars_per_token")]
+    pub chars_per_token: f64,
+    /// Characters per token used for tool results. Command output and code often tokenize
+    /// less efficiently than prose, so this can be set lower than `chars_per_token`.
+    #[serde(default = "default_chars_per_token")]
+    pub tool_result_chars_per_token: f64,
+    /// Fraction of the budget at which the user is warned once.
+    #[serde(default = "default_budget_warn_fraction")]
+    pub warn_fraction: f64,
+}
+
+/// Per-run caps on tool usage (see `agent::tool_caps`). The model is warned once per cap when it
+/// reaches `warn_fraction` of it, and the run stops when a cap is exceeded. Unset caps are not
+/// enforced.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ToolCapsConfig {
+    /// Maximum number of `bash` tool calls.
+    #[serde(default)]
+    pub max_bash_invocations: Option<u32>,
+    /// Maximum bytes of tool output added to the conversation.
+    #[serde(default)]
+    pub max_tool_output_bytes: Option<u64>,
+    /// Maximum number of file-modifying edit tool calls.
+    #[serde(default)]
+    pub max_file_writes: Option<u32>,
+    /// Fraction of a cap at which the model is warned.
+    #[serde(default = "default_budget_warn_fraction")]
+    pub warn_fraction: f64,
+}
+
+/// Where the changelog of a successful run is appended. Only done when the run succeeded.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ChangelogConfig {
+    /// Append it to the message of the `--commit-on-success` commit.
+    #[serde(default)]
+    pub commit: bool,
+    /// Write it as a pull request body next to the trajectory (`run.json` -> `run.pr.md`).
+    #[serde(default)]
+    pub pr_body: bool,
+    /// Add it as an entry to this changelog file, relative to the project (e.g., "CHANGELOG.md").
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+/// Where the audit log of tool calls goes; a file, syslog or both.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AuditLogConfig {
+    /// Append JSON lines to this file (e.g., "~/.trae/audit.jsonl").
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Send each entry to the local syslog daemon (Unix only).
+    #[serde(default)]
+    pub syslog: bool,
+}
+
+/// The Docker container a run's commands and file edits happen in.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SandboxConfig {
+    /// The image to run (e.g., "python:3.12"); it needs a POSIX `sh`.
+    pub image: String,
+    /// Extra bind mounts in `docker run --volume` form (e.g., "/data:/data:ro"). The project
+    /// is always mounted at its own path.
+    #[serde(default)]
+    pub mounts: Vec<String>,
+    /// The container's network; `--offline` makes it `none` regardless.
+    #[serde(default)]
+    pub network: SandboxNetwork,
+    /// The Docker CLI, or a compatible one such as "podman".
+    #[serde(default = "default_docker_cli")]
+    pub docker: String,
+}
+
+/// The network of a sandbox container.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxNetwork {
+    /// No network at all.
+    #[default]
+    None,
+    /// Docker's default bridge network.
+    Bridge,
+    /// The host's network.
+    Host,
+}
+
+fn default_docker_cli() -> String {
+    "docker".to_string()
+}
+
+/// The approval policy of tool calls: lists of command patterns and path globs whose calls run
+/// without asking, are asked about, or are refused.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ApprovalConfig {
+    /// What happens to commands and file writes that match no list.
+    #[serde(default)]
+    pub default: ApprovalPolicy,
+    /// `bash` command patterns, where `*` matches anything (e.g., "git push*").
+    #[serde(default)]
+    pub commands: ApprovalLists,
+    /// Globs of the files written, gitignore-style and relative to the project (e.g., "migrations/").
+    #[serde(default)]
+    pub paths: ApprovalLists,
+}
+
+/// Patterns by policy; `deny` is checked first, then `ask`, then `auto`.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ApprovalLists {
+    #[serde(default)]
+    pub auto: Vec<String>,
+    #[serde(default)]
+    pub ask: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// What happens to a tool call under the approval policy.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalPolicy {
+    /// Run it without asking.
+    #[default]
+    Auto,
+    /// Ask the user first.
+    Ask,
+    /// Refuse it.
+    Deny,
+}
+
+/// How the agent's requests for more steps are decided (see `agent::step_extension`).
+#[derive(Deserialize, Debug, Clone)]
+pub struct StepExtensionConfig {
+    #[serde(default)]
+    pub policy: ExtensionPolicy,
+    /// Most steps granted over the whole run, whatever the policy.
+    #[serde(default = "default_max_extra_steps")]
+    pub max_extra_steps: u32,
+}
+
+/// Who decides a request for more steps.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtensionPolicy {
+    /// Grant it automatically, up to `max_extra_steps`.
+    #[default]
+    Grant,
+    /// Deny it.
+    Deny,
+    /// Ask the user on the terminal.
+    Ask,
+}
+
+pub(crate) fn default_max_extra_steps() -> u32 {
+    20
+}
+
+pub(crate) fn default_probe_capabilities() -> bool {
+    true
+}
+
+pub(crate) fn default_setup_timeout_secs() -> u64 {
+    600
+}
+
+pub(crate) fn default_teardown_timeout_secs() -> u64 {
+    120
+}
+
+pub(crate) fn default_chars_per_token() -> f64 {
+    4.0
+}
+pub(crate) fn default_budget_warn_fraction() -> f64 {
+    0.8
+}
+
+/// Builds the instruction appended to prompts when an output language is configured.
+///
+/// # Arguments
+/// * `language`: The language name or code, as given by the user.
+pub fn output_language_instruction(language: &str) -> String {
+    format!(
+        "Respond to the user in {language}: write your explanations, reasoning summaries and final \
+        answer in {language}. Keep tool calls, tool arguments, code, identifiers, commands and file \
+        contents in their original language."
+    )
+}
+
+/// Most characters of one instruction file put in the system prompt.
+const MAX_INSTRUCTION_FILE_CHARS: usize = 20_000;
+
+/// Builds the system prompt paragraphs of the project's conventions: its `test_command` and
+/// the contents of its `instruction_files`. Files that cannot be read are left out with a warning.
+///
+/// # Arguments
+/// * `project_root`: What relative instruction files are resolved against.
+pub fn project_conventions(config: &Config, project_root: &Path) -> Option<String> {
+    let mut paragraphs = Vec::new();
+    if let Some(command) = &config.test_command {
+        paragraphs.push(format!("Run the project's tests with `{}`.", command));
+    }
+    for file in &config.instruction_files {
+        let path = project_root.join(file);
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                let mut text = text.trim_end().to_string();
+                if text.chars().count() > MAX_INSTRUCTION_FILE_CHARS {
+                    text = text.chars().take(MAX_INSTRUCTION_FILE_CHARS).collect::<String>() + "\n[truncated]";
+                }
+                paragraphs.push(format!("Follow the project's instructions in {}:\n{}", path.display(), text));
+            }
+            Err(e) => warn!("Skipping the instruction file {}: {}", path.display(), e),
+        }
+    }
+    (!paragraphs.is_empty()).then(|| paragraphs.join("\n\n"))
+}
+
+pub(crate) fn default_max_steps() -> u32 {
     20
 }
-fn default_enable_lakeview() -> bool {
+pub(crate) fn default_enable_lakeview() -> bool {
     true
 }
+pub(crate) fn default_heartbeat_interval_secs() -> u64 {
+    30
+}
+pub(crate) fn default_stuck_step_warning_secs() -> u64 {
+    240
+}
 
 /// Main configuration structure for the Trae Agent.
 ///
@@ -101,7 +670,8 @@ impl Config {
     /// 4. Default values coded in the application (lowest).
     ///
     /// # Arguments
-    /// * `config_file_path`: Path to the JSON configuration file (e.g., "trae_config.json").
+    /// * `config_file_path`: Path to the JSON configuration file (e.g., "trae_config.json"),
+    ///   or to a Python-style YAML configuration (e.g., "trae_config.yaml").
     /// * `cli_provider`: Optional LLM provider name from CLI.
     /// * `cli_model`: Optional model name from CLI for the default provider.
     /// * `cli_api_key`: Optional API key from CLI for the default provider.
@@ -118,12 +688,41 @@ impl Config {
         cli_max_steps: Option<u32>,
         cli_working_dir: Option<String>,
     ) -> Result<Self> {
-        let path = Path::new(config_file_path);
-        let mut loaded_config: Config = if path.exists() {
-            let config_str = fs::read_to_string(path)
-                .with_context(|| format!("Failed to read config file at: {}", config_file_path))?;
-            serde_json::from_str(&config_str)
-                .with_context(|| format!("Failed to parse config file: {}", config_file_path))?
+        let mut path = Path::new(config_file_path).to_path_buf();
+        // Fall back to a Python-style `trae_config.yaml` next to a missing JSON config.
+        if !path.exists() && !python_compat::is_yaml_path(&path) {
+            let yaml_path = path.with_extension("yaml");
+            if yaml_path.exists() {
+                info!("Config file {} not found, using {}", config_file_path, yaml_path.display());
+                path = yaml_path;
+            }
+        }
+        let is_yaml = python_compat::is_yaml_path(&path);
+        // A YAML config is complete on its own; JSON configs build on the system and user layers.
+        let config_layers = if is_yaml { Vec::new() } else { layers::read_layers(&path, cli_working_dir.as_deref().map(Path::new))? };
+        let mut loaded_config: Config = if is_yaml && path.exists() {
+            let config_str = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config file at: {}", path.display()))?;
+            let (config, warnings) = python_compat::parse_python_yaml_config(&config_str)
+                .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+            for warning in warnings {
+                warn!("{}: {}", path.display(), warning);
+            }
+            config
+        } else if !config_layers.is_empty() {
+            // Misspelled keys would otherwise be dropped without a word (see `validate`).
+            for layer in &config_layers {
+                for issue in validate::unknown_fields(&layer.value) {
+                    warn!("{}: {}", layer.path.display(), issue);
+                }
+            }
+            let merged = layers::merge(&config_layers);
+            let sources: Vec<String> = config_layers.iter().map(|layer| layer.path.display().to_string()).collect();
+            for issue in validate::provider_references(&merged) {
+                warn!("{}: {}", sources.join(" + "), issue);
+            }
+            serde_json::from_value(merged)
+                .with_context(|| format!("Failed to parse config file: {}", sources.join(" + ")))?
         } else {
             warn!(
                 "Config file not found at: {}. Using default values and environment variables.",
@@ -146,6 +745,12 @@ impl Config {
                     api_version: None,
                     candidate_count: None,
                     stop_sequences: None,
+                    seed: None,
+                    extra_headers: None,
+                    requests_per_minute: None,
+                    tokens_per_minute: None,
+                    openrouter: None,
+                    network: Default::default(),
                 },
             );
             default_providers.insert(
@@ -163,6 +768,12 @@ impl Config {
                     api_version: None,
                     candidate_count: None,
                     stop_sequences: None,
+                    seed: None,
+                    extra_headers: None,
+                    requests_per_minute: None,
+                    tokens_per_minute: None,
+                    openrouter: None,
+                    network: Default::default(),
                 },
             );
             Config {
@@ -172,8 +783,40 @@ impl Config {
                 lakeview_config: None,                      // Added
                 enable_lakeview: default_enable_lakeview(), // Added
                 working_dir: None,
+                output_language: None,
+                heartbeat_interval_secs: default_heartbeat_interval_secs(),
+                stuck_step_warning_secs: default_stuck_step_warning_secs(),
+                step_timeout_secs: None,
+                network: Default::default(),
+                stream: false,
+                routing: None,
+                regrounding: None,
+                tool_conflict_policy: Default::default(),
+                token_budget: None,
+                tool_caps: None,
+                setup_commands: Vec::new(),
+                setup_timeout_secs: default_setup_timeout_secs(),
+                teardown_commands: Vec::new(),
+                teardown_timeout_secs: default_teardown_timeout_secs(),
+                check_for_updates: false,
+                probe_capabilities: default_probe_capabilities(),
+                confirm_commands: false,
+                databases: HashMap::new(),
+                binary_patches: Default::default(),
+                pricing: Default::default(),
+                changelog: None,
+                step_extensions: None,
+                audit_log: None,
+                sandbox: None,
+                approval: None,
+                test_command: None,
+                protected_paths: Vec::new(),
+                instruction_files: Vec::new(),
             }
         };
+        if let (Some(lakeview), Some(dir)) = (&mut loaded_config.lakeview_config, path.parent()) {
+            lakeview.resolve_paths(dir);
+        }
 
         // Override with CLI arguments or environment variables
         if let Some(provider_name) = cli_provider {
@@ -194,6 +837,12 @@ impl Config {
                         api_version: None,
                         candidate_count: None,
                         stop_sequences: None,
+                        seed: None,
+                        extra_headers: None,
+                        requests_per_minute: None,
+                        tokens_per_minute: None,
+                        openrouter: None,
+                        network: Default::default(),
                     },
                     "anthropic" => ModelParameters {
                         api_key: None,
@@ -208,6 +857,32 @@ impl Config {
                         api_version: None,
                         candidate_count: None,
                         stop_sequences: None,
+                        seed: None,
+                        extra_headers: None,
+                        requests_per_minute: None,
+                        tokens_per_minute: None,
+                        openrouter: None,
+                        network: Default::default(),
+                    },
+                    "openrouter" => ModelParameters {
+                        api_key: None,
+                        model: "openai/gpt-4o".to_string(),
+                        max_tokens: Some(4096),
+                        temperature: default_temperature(),
+                        top_p: default_top_p(),
+                        top_k: None,
+                        parallel_tool_calls: default_parallel_tool_calls(),
+                        max_retries: default_max_retries(),
+                        base_url: None, // The client defaults to https://openrouter.ai/api/v1
+                        api_version: None,
+                        candidate_count: None,
+                        stop_sequences: None,
+                        seed: None,
+                        extra_headers: None,
+                        requests_per_minute: None,
+                        tokens_per_minute: None,
+                        openrouter: None,
+                        network: Default::default(),
                     },
                     // TODO: Add cases for other providers like Azure, Google, etc. if they have specific defaults
                     _ => {
@@ -226,6 +901,12 @@ impl Config {
                             api_version: None,
                             candidate_count: None,
                             stop_sequences: None,
+                            seed: None,
+                            extra_headers: None,
+                            requests_per_minute: None,
+                            tokens_per_minute: None,
+                            openrouter: None,
+                            network: Default::default(),
                         }
                     }
                 };
@@ -266,7 +947,7 @@ impl Config {
             provider_config.model = model;
         }
 
-        // API Key Precedence: CLI > Config File > Environment Variable
+        // API Key Precedence: CLI > Config File > Environment Variable > OS keychain
         let mut final_api_key = cli_api_key.clone();
 
         if final_api_key.is_none() {
@@ -276,24 +957,14 @@ impl Config {
         }
 
         if final_api_key.is_none() {
-            let env_var_name_owned: String;
-            let env_var_name_ref: &str = match loaded_config.default_provider.as_str() {
-                "openai" => "OPENAI_API_KEY",
-                "anthropic" => "ANTHROPIC_API_KEY",
-                "azure" => "AZURE_API_KEY", // from Python's env_var_map
-                "openrouter" => "OPENROUTER_API_KEY", // from Python's env_var_map
-                "doubao" => "DOUBAO_API_KEY", // from Python's env_var_map
-                "google" => "GOOGLE_API_KEY", // from Python's env_var_map
-                _ => {
-                    env_var_name_owned =
-                        format!("{}_API_KEY", loaded_config.default_provider.to_uppercase());
-                    &env_var_name_owned
-                }
-            };
-            if let Ok(env_key) = std::env::var(env_var_name_ref) {
+            if let Ok(env_key) = std::env::var(api_key_env_var(&loaded_config.default_provider)) {
                 final_api_key = Some(env_key);
             }
         }
+
+        if final_api_key.is_none() {
+            final_api_key = crate::utils::keychain::api_key(&loaded_config.default_provider);
+        }
         provider_config.api_key = final_api_key;
         // If still no API key and it's required, this might be an issue later, handled by LLM client.
 
@@ -301,6 +972,17 @@ impl Config {
             loaded_config.max_steps = max_steps;
         }
 
+        // Every provider client is built from its ModelParameters, so they carry the network settings.
+        for params in loaded_config.model_providers.values_mut() {
+            params.network = loaded_config.network.clone();
+        }
+
+        // An empty language (e.g., `"output_language": ""`) means "no preference".
+        loaded_config.output_language = loaded_config
+            .output_language
+            .take()
+            .filter(|lang| !lang.trim().is_empty());
+
         loaded_config.working_dir = cli_working_dir.or_else(|| {
             std::env::current_dir()
                 .ok()
@@ -378,6 +1060,51 @@ mod tests {
         fs::remove_file("test_config_1.json").unwrap();
     }
 
+    #[test]
+    fn test_load_output_language() {
+        let config_content = r#"
+        {
+            "default_provider": "openai",
+            "output_language": "Chinese",
+            "model_providers": { "openai": { "model": "gpt-4o" } }
+        }
+        "#;
+        create_test_config_file("test_config_lang.json", config_content);
+        let config = Config::load("test_config_lang.json", None, None, None, None, None).unwrap();
+        fs::remove_file("test_config_lang.json").unwrap();
+        assert_eq!(config.output_language.as_deref(), Some("Chinese"));
+
+        create_test_config_file(
+            "test_config_lang_empty.json",
+            r#"{"default_provider": "openai", "output_language": " ", "model_providers": {"openai": {"model": "gpt-4o"}}}"#,
+        );
+        let config =
+            Config::load("test_config_lang_empty.json", None, None, None, None, None).unwrap();
+        fs::remove_file("test_config_lang_empty.json").unwrap();
+        assert!(config.output_language.is_none());
+    }
+
+    #[test]
+    fn test_network_settings_apply_to_all_providers() {
+        let config_content = r#"
+        {
+            "default_provider": "openai",
+            "network": { "proxy": "socks5://127.0.0.1:1080", "request_timeout_secs": 90 },
+            "model_providers": {
+                "openai": { "model": "gpt-4o" },
+                "anthropic": { "model": "claude-2" }
+            }
+        }
+        "#;
+        create_test_config_file("test_config_network.json", config_content);
+        let config = Config::load("test_config_network.json", None, None, None, None, None).unwrap();
+        fs::remove_file("test_config_network.json").unwrap();
+        for params in config.model_providers.values() {
+            assert_eq!(params.network.proxy.as_deref(), Some("socks5://127.0.0.1:1080"));
+            assert_eq!(params.network.request_timeout_secs, Some(90));
+        }
+    }
+
     #[test]
     fn test_cli_overrides() {
         let config_content = r#"
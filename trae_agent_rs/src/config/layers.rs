@@ -0,0 +1,189 @@
+//! # Config Layers
+//!
+//! A JSON configuration is merged from up to four files, each overriding the one before:
+//!
+//! 1. system: `/etc/trae/config.json` (or `TRAE_SYSTEM_CONFIG`)
+//! 2. repo: the repository's `.trae/project.toml` (see `project_file`)
+//! 3. user: `$XDG_CONFIG_HOME/trae/config.json`, else `~/.config/trae/config.json`
+//!    (or `TRAE_USER_CONFIG`)
+//! 4. project: the `--config-file` (default `./trae_config.json`)
+//!
+//! Objects are merged key by key, so API keys can live in the user config while a project
+//! sets only its provider and step limit; any other value replaces the one below it. Setting
+//! `TRAE_SYSTEM_CONFIG` or `TRAE_USER_CONFIG` to an empty string skips that layer.
+
+use super::project_file;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Overrides the location of the system layer.
+pub const SYSTEM_CONFIG_ENV: &str = "TRAE_SYSTEM_CONFIG";
+/// Overrides the location of the user layer.
+pub const USER_CONFIG_ENV: &str = "TRAE_USER_CONFIG";
+
+/// One configuration file that takes part in the merge.
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    /// "system", "repo", "user" or "project".
+    pub name: &'static str,
+    pub path: PathBuf,
+    pub value: Value,
+}
+
+/// Where the system layer is read from, unless disabled.
+pub fn system_config_path() -> Option<PathBuf> {
+    match std::env::var_os(SYSTEM_CONFIG_ENV) {
+        Some(path) if path.is_empty() => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(PathBuf::from("/etc/trae/config.json")),
+    }
+}
+
+/// Where the user layer is read from, unless disabled or there is no home directory.
+pub fn user_config_path() -> Option<PathBuf> {
+    match std::env::var_os(USER_CONFIG_ENV) {
+        Some(path) if path.is_empty() => return None,
+        Some(path) => return Some(PathBuf::from(path)),
+        None => {}
+    }
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("trae").join("config.json"))
+}
+
+/// Reads the layers that exist, lowest precedence first: the system config, the project
+/// file of the repository containing `working_dir` (default: the current directory), the
+/// user config, then `project` if it exists.
+///
+/// # Returns
+/// The layers, or an error naming the first file that cannot be read or is not valid.
+pub fn read_layers(project: &Path, working_dir: Option<&Path>) -> Result<Vec<ConfigLayer>> {
+    let working_dir = match working_dir {
+        Some(dir) => Some(dir.to_path_buf()),
+        None => std::env::current_dir().ok(),
+    };
+    let candidates = [
+        ("system", system_config_path()),
+        ("repo", working_dir.as_deref().and_then(project_file::find)),
+        ("user", user_config_path()),
+        ("project", Some(project.to_path_buf())),
+    ];
+    let mut layers = Vec::new();
+    for (name, path) in candidates {
+        let Some(path) = path.filter(|path| path.is_file()) else {
+            continue;
+        };
+        if name == "repo" {
+            layers.push(project_file::read_layer(&path)?);
+            continue;
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file at: {}", path.display()))?;
+        let value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        layers.push(ConfigLayer { name, path, value });
+    }
+    Ok(layers)
+}
+
+/// Merges `layers` in order: objects key by key, anything else replaced by the later layer.
+pub fn merge(layers: &[ConfigLayer]) -> Value {
+    let mut merged = Value::Object(Default::default());
+    for layer in layers {
+        merge_into(&mut merged, &layer.value);
+    }
+    merged
+}
+
+fn merge_into(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge_into(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// Every value of `merged` with the layer it came from, one per line, as
+/// `path = value  (layer: file)`. Secrets are redacted.
+pub fn describe(merged: &Value, layers: &[ConfigLayer]) -> String {
+    let mut redacted = merged.clone();
+    crate::utils::crash::redact(&mut redacted);
+    let mut lines = Vec::new();
+    describe_value(&redacted, "", "", layers, &mut lines);
+    lines.join("\n")
+}
+
+fn describe_value(value: &Value, path: &str, pointer: &str, layers: &[ConfigLayer], lines: &mut Vec<String>) {
+    if let Value::Object(map) = value {
+        if !map.is_empty() {
+            for (key, child) in map {
+                let child_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                let child_pointer = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                describe_value(child, &child_path, &child_pointer, layers, lines);
+            }
+            return;
+        }
+    }
+    let source = layers
+        .iter()
+        .rev()
+        .find(|layer| layer.value.pointer(pointer).is_some())
+        .map(|layer| format!("{}: {}", layer.name, layer.path.display()))
+        .unwrap_or_default();
+    lines.push(format!("{} = {}  ({})", path, value, source));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_layers_merge_with_provenance() {
+        let layer = |name, value| ConfigLayer {
+            name,
+            path: PathBuf::from(format!("/{}.json", name)),
+            value,
+        };
+        let layers = [
+            layer("system", json!({ "max_steps": 10, "network": { "proxy": "http://proxy:3128" } })),
+            layer(
+                "user",
+                json!({ "model_providers": { "openai": { "api_key": "sk-secret", "model": "gpt-4o" } } }),
+            ),
+            layer(
+                "project",
+                json!({ "default_provider": "openai", "max_steps": 30, "model_providers": { "openai": { "model": "gpt-4o-mini" } } }),
+            ),
+        ];
+        let merged = merge(&layers);
+        assert_eq!(
+            merged,
+            json!({
+                "max_steps": 30,
+                "network": { "proxy": "http://proxy:3128" },
+                "model_providers": { "openai": { "api_key": "sk-secret", "model": "gpt-4o-mini" } },
+                "default_provider": "openai"
+            })
+        );
+        assert_eq!(
+            describe(&merged, &layers),
+            "max_steps = 30  (project: /project.json)\n\
+             network.proxy = \"http://proxy:3128\"  (system: /system.json)\n\
+             model_providers.openai.api_key = \"[REDACTED]\"  (user: /user.json)\n\
+             model_providers.openai.model = \"gpt-4o-mini\"  (project: /project.json)\n\
+             default_provider = \"openai\"  (project: /project.json)"
+        );
+    }
+}
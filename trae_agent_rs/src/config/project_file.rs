@@ -0,0 +1,144 @@
+//! # Project File
+//!
+//! A repository can commit `.trae/project.toml` so everyone running the agent in it gets the
+//! same defaults without per-user setup:
+//!
+//! ```toml
+//! provider = "anthropic"
+//! model = "claude-sonnet-4-20250514"
+//! test_command = "cargo test"
+//! setup_commands = ["cargo fetch"]
+//! protected_paths = ["migrations/", "Cargo.lock"]
+//! instruction_files = ["CONTRIBUTING.md"]
+//! ```
+//!
+//! It is found by looking up from the working directory to the root of its git repository,
+//! and becomes the "repo" config layer (see `layers`), between the system and user configs:
+//! a user's own config still overrides it. Paths in it are relative to the repository root.
+
+use super::layers::ConfigLayer;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+
+/// Where the file lives, relative to the repository root.
+pub const PROJECT_FILE: &str = ".trae/project.toml";
+
+/// The keys of `.trae/project.toml`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct ProjectFile {
+    /// Becomes `default_provider`.
+    provider: Option<String>,
+    /// The model of `provider`.
+    model: Option<String>,
+    test_command: Option<String>,
+    setup_commands: Option<Vec<String>>,
+    protected_paths: Option<Vec<String>>,
+    instruction_files: Option<Vec<String>>,
+}
+
+/// The project file of the repository containing `start`, if it has one: the nearest
+/// `.trae/project.toml` in `start` or its ancestors, up to the first one with a `.git`.
+pub fn find(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let candidate = dir.join(PROJECT_FILE);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// Reads the project file at `path` as a config layer.
+///
+/// # Returns
+/// The layer, or an error naming the file if it cannot be read, is not valid TOML, has an
+/// unknown key, or names a model without a provider.
+pub fn read_layer(path: &Path) -> Result<ConfigLayer> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let file: ProjectFile = toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))?;
+    // `.trae/project.toml` -> the repository root.
+    let root = path.parent().and_then(Path::parent).unwrap_or(Path::new("."));
+    let value = to_config_value(file, root).with_context(|| format!("Invalid {}", path.display()))?;
+    Ok(ConfigLayer {
+        name: "repo",
+        path: path.to_path_buf(),
+        value,
+    })
+}
+
+/// The config keys of `file`, with its paths made absolute against `root`.
+fn to_config_value(file: ProjectFile, root: &Path) -> Result<Value> {
+    let mut config = Map::new();
+    match (file.provider, file.model) {
+        (Some(provider), model) => {
+            if let Some(model) = model {
+                config.insert("model_providers".to_string(), json!({ provider.clone(): { "model": model } }));
+            }
+            config.insert("default_provider".to_string(), json!(provider));
+        }
+        (None, Some(_)) => anyhow::bail!("`model` needs a `provider` to apply to"),
+        (None, None) => {}
+    }
+    if let Some(command) = file.test_command {
+        config.insert("test_command".to_string(), json!(command));
+    }
+    if let Some(commands) = file.setup_commands {
+        config.insert("setup_commands".to_string(), json!(commands));
+    }
+    let absolute = |paths: Vec<String>| -> Vec<String> { paths.iter().map(|p| root.join(p).display().to_string()).collect() };
+    if let Some(paths) = file.protected_paths {
+        config.insert("protected_paths".to_string(), json!(absolute(paths)));
+    }
+    if let Some(paths) = file.instruction_files {
+        config.insert("instruction_files".to_string(), json!(absolute(paths)));
+    }
+    Ok(Value::Object(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::layers::merge;
+
+    #[test]
+    fn test_project_file_is_found_and_sits_under_the_user_config() {
+        let repo = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(repo.path().join(".git")).unwrap();
+        std::fs::create_dir_all(repo.path().join(".trae")).unwrap();
+        std::fs::create_dir_all(repo.path().join("crates/core")).unwrap();
+        let path = repo.path().join(PROJECT_FILE);
+        std::fs::write(
+            &path,
+            "provider = \"anthropic\"\nmodel = \"claude-sonnet-4-20250514\"\ntest_command = \"cargo test\"\n\
+             protected_paths = [\"migrations/\"]\ninstruction_files = [\"CONTRIBUTING.md\"]\n",
+        )
+        .unwrap();
+        assert_eq!(find(&repo.path().join("crates/core")), Some(path.clone()));
+
+        let repo_layer = read_layer(&path).unwrap();
+        let user_layer = ConfigLayer {
+            name: "user",
+            path: PathBuf::from("/user.json"),
+            value: json!({ "model_providers": { "anthropic": { "api_key": "sk-ant" } }, "test_command": "make test" }),
+        };
+        let merged = merge(&[repo_layer, user_layer]);
+        assert_eq!(merged["default_provider"], "anthropic");
+        assert_eq!(
+            merged["model_providers"]["anthropic"],
+            json!({ "model": "claude-sonnet-4-20250514", "api_key": "sk-ant" })
+        );
+        assert_eq!(merged["test_command"], "make test");
+        assert_eq!(merged["protected_paths"], json!([repo.path().join("migrations/").display().to_string()]));
+
+        std::fs::write(&path, "model = \"gpt-4o\"\n").unwrap();
+        assert!(read_layer(&path).is_err());
+        std::fs::write(&path, "test_comand = \"cargo test\"\n").unwrap();
+        assert!(format!("{:#}", read_layer(&path).unwrap_err()).contains("test_comand"));
+    }
+}
@@ -0,0 +1,408 @@
+//! # Python Config Compatibility
+//!
+//! Maps the YAML layout used by the Python trae-agent (`trae_config.yaml`) onto the
+//! Rust `Config`. The Python layout separates providers (credentials and endpoints),
+//! models (sampling parameters bound to a provider), agents, and Lakeview; the Rust
+//! layout keys everything by provider. Options without a Rust counterpart are reported
+//! as warnings instead of failing the load.
+
+use super::{
+    default_enable_lakeview, default_heartbeat_interval_secs, default_max_retries, default_max_steps, default_max_tokens_openai,
+    default_parallel_tool_calls, default_stuck_step_warning_secs, default_temperature, default_top_p, Config, LakeviewConfig,
+    ModelParameters,
+};
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Keys of a Python model entry that map onto `ModelParameters`.
+const SUPPORTED_MODEL_KEYS: &[&str] = &[
+    "model_provider",
+    "model",
+    "max_tokens",
+    "temperature",
+    "top_p",
+    "top_k",
+    "parallel_tool_calls",
+    "max_retries",
+    "candidate_count",
+    "stop_sequences",
+    "seed",
+];
+
+/// Keys of a Python provider entry that map onto `ModelParameters`.
+const SUPPORTED_PROVIDER_KEYS: &[&str] = &["api_key", "provider", "base_url", "api_version"];
+
+/// Keys of the Python agent section that map onto `Config`.
+const SUPPORTED_AGENT_KEYS: &[&str] = &["model", "max_steps", "enable_lakeview"];
+
+/// Returns true if `path` names a YAML file.
+pub fn is_yaml_path(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("yaml") || e.eq_ignore_ascii_case("yml"))
+}
+
+fn as_object<'a>(
+    value: Option<&'a Value>,
+    section: &str,
+) -> Result<Option<&'a Map<String, Value>>> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Object(map)) => Ok(Some(map)),
+        Some(_) => Err(anyhow::anyhow!("'{}' must be a mapping", section)),
+    }
+}
+
+fn warn_unsupported_keys(
+    map: &Map<String, Value>,
+    supported: &[&str],
+    section: &str,
+    warnings: &mut Vec<String>,
+) {
+    let mut keys: Vec<&String> = map
+        .keys()
+        .filter(|k| !supported.contains(&k.as_str()))
+        .collect();
+    keys.sort();
+    for key in keys {
+        warnings.push(format!(
+            "Unsupported option '{}.{}' is ignored",
+            section, key
+        ));
+    }
+}
+
+fn get_u32(map: &Map<String, Value>, key: &str) -> Option<u32> {
+    map.get(key).and_then(Value::as_u64).map(|v| v as u32)
+}
+
+fn get_f32(map: &Map<String, Value>, key: &str) -> Option<f32> {
+    map.get(key).and_then(Value::as_f64).map(|v| v as f32)
+}
+
+fn get_string(map: &Map<String, Value>, key: &str) -> Option<String> {
+    map.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+/// Builds `ModelParameters` for a Python model entry combined with its provider entry.
+fn model_parameters(
+    model: &Map<String, Value>,
+    provider: Option<&Map<String, Value>>,
+) -> Option<ModelParameters> {
+    Some(ModelParameters {
+        api_key: provider.and_then(|p| get_string(p, "api_key")),
+        model: get_string(model, "model")?,
+        max_tokens: get_u32(model, "max_tokens").or_else(default_max_tokens_openai),
+        temperature: get_f32(model, "temperature").unwrap_or_else(default_temperature),
+        top_p: get_f32(model, "top_p").unwrap_or_else(default_top_p),
+        top_k: get_u32(model, "top_k"),
+        parallel_tool_calls: model
+            .get("parallel_tool_calls")
+            .and_then(Value::as_bool)
+            .unwrap_or_else(default_parallel_tool_calls),
+        max_retries: get_u32(model, "max_retries").unwrap_or_else(default_max_retries),
+        base_url: provider.and_then(|p| get_string(p, "base_url")),
+        api_version: provider.and_then(|p| get_string(p, "api_version")),
+        candidate_count: get_u32(model, "candidate_count"),
+        stop_sequences: model
+            .get("stop_sequences")
+            .and_then(Value::as_array)
+            .map(|seqs| {
+                seqs.iter()
+                    .filter_map(|s| s.as_str().map(str::to_string))
+                    .collect()
+            }),
+        seed: model.get("seed").and_then(Value::as_u64),
+        extra_headers: None,
+        requests_per_minute: None,
+        tokens_per_minute: None,
+        openrouter: None,
+        network: Default::default(),
+    })
+}
+
+/// Parses a Python-style YAML configuration into a Rust `Config`.
+///
+/// # Arguments
+/// * `yaml`: The contents of a `trae_config.yaml` file.
+///
+/// # Returns
+/// The mapped `Config` and a list of warnings about options that were ignored.
+pub fn parse_python_yaml_config(yaml: &str) -> Result<(Config, Vec<String>)> {
+    let root: Value = serde_yaml::from_str(yaml).context("Invalid YAML configuration")?;
+    let root = root
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("YAML configuration must be a mapping"))?;
+    let mut warnings = Vec::new();
+
+    for key in root.keys() {
+        if !["agents", "agent", "model_providers", "models", "lakeview"].contains(&key.as_str()) {
+            warnings.push(format!("Unsupported section '{}' is ignored", key));
+        }
+    }
+
+    let providers = as_object(root.get("model_providers"), "model_providers")?
+        .ok_or_else(|| anyhow::anyhow!("YAML configuration has no 'model_providers' section"))?;
+    let models = as_object(root.get("models"), "models")?
+        .ok_or_else(|| anyhow::anyhow!("YAML configuration has no 'models' section"))?;
+
+    // The Python CLI runs `trae_agent`; older files used a single `agent` mapping.
+    let agent = match as_object(root.get("agents"), "agents")? {
+        Some(agents) => {
+            for name in agents.keys().filter(|n| n.as_str() != "trae_agent") {
+                warnings.push(format!(
+                    "Agent '{}' is not supported; only 'trae_agent' is used",
+                    name
+                ));
+            }
+            as_object(agents.get("trae_agent"), "agents.trae_agent")?
+        }
+        None => as_object(root.get("agent"), "agent")?,
+    };
+    let agent_section = if root.contains_key("agents") {
+        "agents.trae_agent"
+    } else {
+        "agent"
+    };
+    if let Some(agent) = agent {
+        warn_unsupported_keys(agent, SUPPORTED_AGENT_KEYS, agent_section, &mut warnings);
+    }
+
+    let model_entry = |name: &str, section: &str| -> Result<&Map<String, Value>> {
+        as_object(models.get(name), &format!("models.{}", name))?
+            .ok_or_else(|| anyhow::anyhow!("{} refers to unknown model '{}'", section, name))
+    };
+    let provider_of = |model: &Map<String, Value>| get_string(model, "model_provider");
+
+    let mut model_names: Vec<&String> = models.keys().collect();
+    model_names.sort();
+    for name in &model_names {
+        if let Some(model) = as_object(models.get(*name), &format!("models.{}", name))? {
+            warn_unsupported_keys(
+                model,
+                SUPPORTED_MODEL_KEYS,
+                &format!("models.{}", name),
+                &mut warnings,
+            );
+        }
+    }
+    let mut provider_names: Vec<&String> = providers.keys().collect();
+    provider_names.sort();
+    for name in &provider_names {
+        if let Some(provider) =
+            as_object(providers.get(*name), &format!("model_providers.{}", name))?
+        {
+            warn_unsupported_keys(
+                provider,
+                SUPPORTED_PROVIDER_KEYS,
+                &format!("model_providers.{}", name),
+                &mut warnings,
+            );
+        }
+    }
+
+    let agent_model_name = agent.and_then(|a| get_string(a, "model"));
+    let agent_model = match &agent_model_name {
+        Some(name) => Some(model_entry(name, agent_section)?),
+        None => None,
+    };
+
+    // For each provider, use the agent's model if it targets that provider, otherwise the
+    // first model (by name) that does.
+    let mut model_providers = HashMap::new();
+    for provider_name in &provider_names {
+        let provider = as_object(providers.get(*provider_name), "model_providers")?;
+        let chosen = agent_model
+            .filter(|m| provider_of(m).as_deref() == Some(provider_name.as_str()))
+            .or_else(|| {
+                model_names.iter().find_map(|name| {
+                    models
+                        .get(*name)
+                        .and_then(Value::as_object)
+                        .filter(|m| provider_of(m).as_deref() == Some(provider_name.as_str()))
+                })
+            });
+        match chosen.and_then(|m| model_parameters(m, provider)) {
+            Some(params) => {
+                model_providers.insert(provider_name.to_string(), params);
+            }
+            None => warnings.push(format!(
+                "Provider '{}' has no model configured and is ignored",
+                provider_name
+            )),
+        }
+    }
+    if model_providers.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No usable model providers found in YAML configuration"
+        ));
+    }
+
+    let default_provider = match agent_model.and_then(provider_of) {
+        Some(provider) => provider,
+        None => {
+            let mut names: Vec<&String> = model_providers.keys().collect();
+            names.sort();
+            names[0].clone()
+        }
+    };
+
+    let lakeview_config = match as_object(root.get("lakeview"), "lakeview")? {
+        Some(lakeview) => {
+            warn_unsupported_keys(lakeview, &["model"], "lakeview", &mut warnings);
+            match get_string(lakeview, "model") {
+                Some(name) => {
+                    let model = model_entry(&name, "lakeview")?;
+                    Some(LakeviewConfig {
+                        model_provider: provider_of(model)
+                            .unwrap_or_else(|| default_provider.clone()),
+                        model_name: get_string(model, "model").unwrap_or_default(),
+                        tags: Vec::new(),
+                        replace_default_tags: false,
+                        extractor_prompt_file: None,
+                        tagger_prompt_file: None,
+                    })
+                }
+                None => None,
+            }
+        }
+        None => None,
+    };
+
+    let config = Config {
+        default_provider,
+        max_steps: agent
+            .and_then(|a| get_u32(a, "max_steps"))
+            .unwrap_or_else(default_max_steps),
+        model_providers,
+        lakeview_config,
+        enable_lakeview: agent
+            .and_then(|a| a.get("enable_lakeview"))
+            .and_then(Value::as_bool)
+            .unwrap_or_else(default_enable_lakeview),
+        working_dir: None,
+        output_language: None,
+        heartbeat_interval_secs: default_heartbeat_interval_secs(),
+        stuck_step_warning_secs: default_stuck_step_warning_secs(),
+        step_timeout_secs: None,
+        network: Default::default(),
+        stream: false,
+        routing: None,
+        regrounding: None,
+        tool_conflict_policy: Default::default(),
+        token_budget: None,
+        tool_caps: None,
+        setup_commands: Vec::new(),
+        setup_timeout_secs: super::default_setup_timeout_secs(),
+        teardown_commands: Vec::new(),
+        teardown_timeout_secs: super::default_teardown_timeout_secs(),
+        check_for_updates: false,
+        probe_capabilities: super::default_probe_capabilities(),
+        confirm_commands: false,
+        databases: HashMap::new(),
+        binary_patches: Default::default(),
+        pricing: Default::default(),
+        changelog: None,
+        step_extensions: None,
+        audit_log: None,
+        sandbox: None,
+        approval: None,
+        test_command: None,
+        protected_paths: Vec::new(),
+        instruction_files: Vec::new(),
+    };
+    Ok((config, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PYTHON_CONFIG: &str = r#"
+agents:
+  trae_agent:
+    enable_lakeview: true
+    model: trae_agent_model
+    max_steps: 200
+    tools:
+      - bash
+      - str_replace_based_edit_tool
+
+lakeview:
+  model: lakeview_model
+
+model_providers:
+  anthropic:
+    api_key: sk-ant
+    provider: anthropic
+  openai:
+    api_key: sk-openai
+    provider: openai
+    base_url: https://example.com/v1
+
+models:
+  trae_agent_model:
+    model_provider: anthropic
+    model: claude-sonnet-4-20250514
+    max_tokens: 4096
+    temperature: 0.5
+    top_p: 1
+    top_k: 0
+    max_retries: 10
+    parallel_tool_calls: true
+    supports_tool_calling: true
+  lakeview_model:
+    model_provider: openai
+    model: gpt-4o-mini
+    max_tokens: 2048
+
+mcp_servers: {}
+"#;
+
+    #[test]
+    fn test_parse_python_yaml_config() {
+        let (config, warnings) = parse_python_yaml_config(PYTHON_CONFIG).unwrap();
+        assert_eq!(config.default_provider, "anthropic");
+        assert_eq!(config.max_steps, 200);
+        assert!(config.enable_lakeview);
+
+        let anthropic = &config.model_providers["anthropic"];
+        assert_eq!(anthropic.model, "claude-sonnet-4-20250514");
+        assert_eq!(anthropic.api_key.as_deref(), Some("sk-ant"));
+        assert_eq!(anthropic.max_tokens, Some(4096));
+        assert_eq!(anthropic.top_k, Some(0));
+        assert!(anthropic.parallel_tool_calls);
+
+        let openai = &config.model_providers["openai"];
+        assert_eq!(openai.model, "gpt-4o-mini");
+        assert_eq!(openai.base_url.as_deref(), Some("https://example.com/v1"));
+
+        let lakeview = config.lakeview_config.unwrap();
+        assert_eq!(lakeview.model_provider, "openai");
+        assert_eq!(lakeview.model_name, "gpt-4o-mini");
+
+        assert_eq!(
+            warnings,
+            vec![
+                "Unsupported section 'mcp_servers' is ignored",
+                "Unsupported option 'agents.trae_agent.tools' is ignored",
+                "Unsupported option 'models.trae_agent_model.supports_tool_calling' is ignored",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_agent_model_is_an_error() {
+        let yaml = "agents:\n  trae_agent:\n    model: missing\nmodel_providers:\n  openai:\n    api_key: k\nmodels:\n  m:\n    model_provider: openai\n    model: gpt-4o\n";
+        let err = parse_python_yaml_config(yaml).unwrap_err();
+        assert!(err.to_string().contains("unknown model 'missing'"));
+    }
+
+    #[test]
+    fn test_is_yaml_path() {
+        assert!(is_yaml_path(std::path::Path::new("trae_config.yaml")));
+        assert!(is_yaml_path(std::path::Path::new("conf.YML")));
+        assert!(!is_yaml_path(std::path::Path::new("trae_config.json")));
+    }
+}
@@ -0,0 +1,320 @@
+//! # Config Validation
+//!
+//! Serde ignores keys it does not know, so a misspelled key (`modle_providers`, `max_step`)
+//! silently falls back to the default. This module checks a JSON config against the fields
+//! the config structs actually read, suggesting the closest known field for each unknown one,
+//! and checks that the provider names it refers to are configured. `Config::load` logs the
+//! issues as warnings; `trae config validate` reports them and fails.
+//!
+//! Unknown keys are a property of each file, but provider references are checked against the
+//! merged layers (see `layers`), since a project may name a provider the user config defines.
+
+use super::{layers, python_compat};
+use super::{
+    ApprovalConfig, ApprovalLists, AuditLogConfig, ChangelogConfig, Config, LakeviewConfig, LakeviewTag, ModelParameters, NetworkConfig, OpenRouterOptions, ProviderRouting,
+    RegroundingConfig, RoutingConfig, SandboxConfig, StepExtensionConfig, TokenBudgetConfig, ToolCapsConfig,
+};
+use crate::utils::usage::ModelPrice;
+use anyhow::{Context, Result};
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Smallest similarity (Jaro-Winkler, 0 to 1) for a known field to be suggested.
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// A problem found in a config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    /// Dotted path of the offending key, e.g. `model_providers.openai.modle`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// A struct of the config file.
+#[derive(Debug, Clone, Copy)]
+enum Section {
+    Approval,
+    ApprovalLists,
+    AuditLog,
+    Changelog,
+    Config,
+    Provider,
+    Lakeview,
+    LakeviewTag,
+    Network,
+    OpenRouter,
+    Price,
+    ProviderRouting,
+    Routing,
+    Regrounding,
+    Sandbox,
+    StepExtensions,
+    TokenBudget,
+    ToolCaps,
+}
+
+/// How a section is nested under its key.
+#[derive(Debug, Clone, Copy)]
+enum Nesting {
+    /// The key holds the section itself.
+    One,
+    /// The key holds a map of names to sections (e.g., `model_providers`).
+    Map,
+    /// The key holds a list of sections (e.g., Lakeview `tags`).
+    List,
+}
+
+impl Section {
+    /// The keys serde reads for this section.
+    fn fields(self) -> &'static [&'static str] {
+        match self {
+            Section::Approval => fields_of::<ApprovalConfig>(),
+            Section::ApprovalLists => fields_of::<ApprovalLists>(),
+            Section::AuditLog => fields_of::<AuditLogConfig>(),
+            Section::Changelog => fields_of::<ChangelogConfig>(),
+            Section::Config => fields_of::<Config>(),
+            Section::Provider => fields_of::<ModelParameters>(),
+            Section::Lakeview => fields_of::<LakeviewConfig>(),
+            Section::LakeviewTag => fields_of::<LakeviewTag>(),
+            Section::Network => fields_of::<NetworkConfig>(),
+            Section::OpenRouter => fields_of::<OpenRouterOptions>(),
+            Section::Price => fields_of::<ModelPrice>(),
+            Section::ProviderRouting => fields_of::<ProviderRouting>(),
+            Section::Routing => fields_of::<RoutingConfig>(),
+            Section::Regrounding => fields_of::<RegroundingConfig>(),
+            Section::Sandbox => fields_of::<SandboxConfig>(),
+            Section::StepExtensions => fields_of::<StepExtensionConfig>(),
+            Section::TokenBudget => fields_of::<TokenBudgetConfig>(),
+            Section::ToolCaps => fields_of::<ToolCapsConfig>(),
+        }
+    }
+
+    /// The section nested under `key`, if it is one.
+    fn child(self, key: &str) -> Option<(Section, Nesting)> {
+        match (self, key) {
+            (Section::Config, "model_providers") => Some((Section::Provider, Nesting::Map)),
+            (Section::Config, "lakeview_config") => Some((Section::Lakeview, Nesting::One)),
+            (Section::Config, "network") => Some((Section::Network, Nesting::One)),
+            (Section::Config, "routing") => Some((Section::Routing, Nesting::One)),
+            (Section::Config, "regrounding") => Some((Section::Regrounding, Nesting::One)),
+            (Section::Config, "token_budget") => Some((Section::TokenBudget, Nesting::One)),
+            (Section::Config, "tool_caps") => Some((Section::ToolCaps, Nesting::One)),
+            (Section::Config, "pricing") => Some((Section::Price, Nesting::Map)),
+            (Section::Config, "changelog") => Some((Section::Changelog, Nesting::One)),
+            (Section::Config, "step_extensions") => Some((Section::StepExtensions, Nesting::One)),
+            (Section::Config, "audit_log") => Some((Section::AuditLog, Nesting::One)),
+            (Section::Config, "sandbox") => Some((Section::Sandbox, Nesting::One)),
+            (Section::Config, "approval") => Some((Section::Approval, Nesting::One)),
+            (Section::Approval, "commands" | "paths") => Some((Section::ApprovalLists, Nesting::One)),
+            (Section::Provider, "openrouter") => Some((Section::OpenRouter, Nesting::One)),
+            (Section::OpenRouter, "provider") => Some((Section::ProviderRouting, Nesting::One)),
+            (Section::Lakeview, "tags") => Some((Section::LakeviewTag, Nesting::List)),
+            _ => None,
+        }
+    }
+}
+
+/// The field names the derived `Deserialize` of `T` reads, captured by handing it a
+/// deserializer that records what `deserialize_struct` is asked for.
+fn fields_of<T: DeserializeOwned>() -> &'static [&'static str] {
+    struct Probe<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for Probe<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields captured"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+            ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(Probe(&mut fields));
+    fields
+}
+
+/// Keys of a JSON config that no config struct reads, with the closest known key, if one is
+/// close.
+pub fn unknown_fields(value: &Value) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    check_section(value, Section::Config, "", &mut issues);
+    issues
+}
+
+/// Provider names a JSON config refers to that are not configured in `model_providers`.
+pub fn provider_references(value: &Value) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let providers = value.get("model_providers").and_then(Value::as_object);
+    let references = [
+        ("default_provider", value.get("default_provider")),
+        ("lakeview_config.model_provider", value.pointer("/lakeview_config/model_provider")),
+        ("routing.cheap_provider", value.pointer("/routing/cheap_provider")),
+    ];
+    for (path, reference) in references {
+        let Some(name) = reference.and_then(Value::as_str) else {
+            continue;
+        };
+        if providers.is_some_and(|providers| !providers.contains_key(name)) {
+            let configured: Vec<&str> = providers.into_iter().flat_map(|p| p.keys()).map(String::as_str).collect();
+            let mut message = format!("provider '{}' is not configured in model_providers", name);
+            match closest(name, &configured) {
+                Some(suggestion) => message.push_str(&format!("; did you mean '{}'?", suggestion)),
+                None => message.push_str(&format!(" (configured: {})", configured.join(", "))),
+            }
+            issues.push(ConfigIssue { path: path.to_string(), message });
+        }
+    }
+    issues
+}
+
+/// Checks the config file at `path`, merged with the system and user layers. Syntax errors
+/// and values of the wrong type are errors; the other problems are returned as issues.
+/// Python-style YAML configs are checked by their converter, whose warnings are returned as
+/// issues.
+pub fn validate_file(path: &Path) -> Result<Vec<ConfigIssue>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read config file at: {}", path.display()))?;
+    if python_compat::is_yaml_path(path) {
+        let (_, warnings) = python_compat::parse_python_yaml_config(&text)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        return Ok(warnings
+            .into_iter()
+            .map(|message| ConfigIssue {
+                path: path.display().to_string(),
+                message,
+            })
+            .collect());
+    }
+    let value: Value =
+        serde_json::from_str(&text).with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    let merged = layers::merge(&layers::read_layers(path, None)?);
+    serde_json::from_value::<Config>(merged.clone())
+        .with_context(|| format!("Invalid config file: {}", path.display()))?;
+    let mut issues = unknown_fields(&value);
+    issues.extend(provider_references(&merged));
+    Ok(issues)
+}
+
+fn check_section(value: &Value, section: Section, prefix: &str, issues: &mut Vec<ConfigIssue>) {
+    let Some(object) = value.as_object() else {
+        return;
+    };
+    let fields = section.fields();
+    for (key, child) in object {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        if !fields.contains(&key.as_str()) {
+            let message = match closest(key, fields) {
+                Some(suggestion) => format!("unknown field, ignored; did you mean '{}'?", suggestion),
+                None => "unknown field, ignored".to_string(),
+            };
+            issues.push(ConfigIssue { path, message });
+            continue;
+        }
+        match section.child(key) {
+            Some((nested, Nesting::One)) => check_section(child, nested, &path, issues),
+            Some((nested, Nesting::Map)) => {
+                for (name, entry) in child.as_object().into_iter().flatten() {
+                    check_section(entry, nested, &format!("{}.{}", path, name), issues);
+                }
+            }
+            Some((nested, Nesting::List)) => {
+                for (index, entry) in child.as_array().into_iter().flatten().enumerate() {
+                    check_section(entry, nested, &format!("{}[{}]", path, index), issues);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// The candidate most similar to `name`, if any is similar enough to be a likely typo.
+fn closest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(name, candidate), *candidate))
+        .filter(|(score, _)| *score >= SUGGESTION_THRESHOLD)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn validate_value(value: &Value) -> Vec<ConfigIssue> {
+        let mut issues = unknown_fields(value);
+        issues.extend(provider_references(value));
+        issues
+    }
+
+    #[test]
+    fn test_unknown_fields_and_provider_references() {
+        let config = json!({
+            "default_provider": "opneai",
+            "max_step": 20,
+            "modle_providers": {},
+            "model_providers": {
+                "openai": { "model": "gpt-4o", "modle": "gpt-4o-mini", "requests_per_minute": 60 }
+            },
+            "lakeview_config": {
+                "model_provider": "openai",
+                "model_name": "gpt-4o-mini",
+                "tags": [{ "name": "TEST", "emoji": "🧪", "descripton": "runs tests" }]
+            },
+            "network": { "proxy": "http://proxy:3128", "xyzzy": true }
+        });
+        let issues: Vec<String> = validate_value(&config).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            issues,
+            [
+                "max_step: unknown field, ignored; did you mean 'max_steps'?",
+                "modle_providers: unknown field, ignored; did you mean 'model_providers'?",
+                "model_providers.openai.modle: unknown field, ignored; did you mean 'model'?",
+                "lakeview_config.tags[0].descripton: unknown field, ignored; did you mean 'description'?",
+                "network.xyzzy: unknown field, ignored",
+                "default_provider: provider 'opneai' is not configured in model_providers; did you mean 'openai'?",
+            ]
+        );
+
+        let valid = json!({
+            "default_provider": "openai",
+            "model_providers": { "openai": { "model": "gpt-4o" } },
+            "enable_lakeview": false
+        });
+        assert!(validate_value(&valid).is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trae_config.json");
+        let mut config = valid.clone();
+        config["max_step"] = json!(20);
+        fs::write(&path, config.to_string()).unwrap();
+        assert_eq!(validate_file(&path).unwrap().len(), 1);
+        config["max_steps"] = json!("twenty");
+        fs::write(&path, config.to_string()).unwrap();
+        assert!(validate_file(&path).is_err());
+    }
+}
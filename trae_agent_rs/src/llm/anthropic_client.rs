@@ -7,8 +7,10 @@ use super::base_client::{
     ToolChoice, // Removed ToolCall
     ToolDefinition,
 };
+use super::tool_limits::ToolLimits;
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use crate::utils::http::shared_http_client;
 use reqwest::Client as HttpClient;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -129,7 +131,10 @@ const ANTHROPIC_API_VERSION: &str = "2023-06-01";
 #[allow(dead_code)] // This client is a stub
 #[derive(Debug)]
 pub struct AnthropicClient {
+    /// Pooled client shared with every other client that has the same network settings.
     http_client: HttpClient,
+    /// API key, version and content-type headers, added to each request.
+    headers: HeaderMap,
     base_url: String,
     model_parameters: ModelParameters,
 }
@@ -166,13 +171,12 @@ impl LLMClient for AnthropicClient {
         );
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-        let http_client = HttpClient::builder()
-            .default_headers(headers)
-            .build()
-            .map_err(LLMError::Network)?;
+        let http_client = shared_http_client(&_model_parameters.network)
+            .map_err(|e| LLMError::Other(format!("{:#}", e)))?;
 
         Ok(Self {
             http_client,
+            headers,
             base_url: _base_url.unwrap_or_else(|| DEFAULT_ANTHROPIC_API_BASE.to_string()),
             model_parameters: _model_parameters,
         })
@@ -186,6 +190,7 @@ impl LLMClient for AnthropicClient {
         _tools: Option<Vec<ToolDefinition>>,
         _tool_choice: Option<ToolChoice>,
     ) -> Result<LLMResponse, LLMError> {
+        let _tools = ToolLimits::ANTHROPIC.enforce(_tools)?;
         // TODO: Implement actual chat call as detailed in previous comments
         warn!("AnthropicClient chat is not yet fully implemented. Returning placeholder error.");
         Err(LLMError::Other(
@@ -1,6 +1,11 @@
+use super::rate_limit::RateLimitInfo;
+use super::streaming::StreamEvent;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
 // Re-export ModelParameters from config and alias it for clarity within LLM context if needed,
@@ -17,7 +22,6 @@ pub enum LLMError {
     #[error("API error: {0}")]
     ApiError(String),
     /// Error during parsing of the LLM's response (e.g., malformed JSON).
-    #[allow(dead_code)] // May become used with more complex error handling or other clients
     #[error("Failed to parse response: {0}")]
     ParsingError(serde_json::Error),
     /// Required API key was not provided.
@@ -27,11 +31,51 @@ pub enum LLMError {
     #[allow(dead_code)] // Currently checked in BaseAgent::try_new, but could be checked by client itself
     #[error("Unsupported model or provider")]
     UnsupportedModel,
+    /// The tool list breaks a limit of the provider (see `tool_limits`).
+    #[error("Invalid tool configuration: {0}")]
+    ToolConfig(String),
+    /// The provider rejected the API key.
+    #[error("Invalid API key: {0}")]
+    InvalidApiKey(String),
+    /// The prompt and the requested completion do not fit the model's context window.
+    #[error("Context length exceeded: {0}")]
+    ContextLengthExceeded(String),
+    /// The provider is temporarily overloaded.
+    #[error("Provider overloaded: {0}")]
+    Overloaded(String),
+    /// Requests are being rate limited (after any retries).
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+    /// The account has run out of credits or quota.
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
     /// Any other type of error.
     #[error("Other error: {0}")]
     Other(String),
 }
 
+impl LLMError {
+    /// What the user can do about the error, for errors with a known remedy.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            LLMError::NoApiKey | LLMError::InvalidApiKey(_) => Some(
+                "Check the provider's api_key in the config file, or its API key environment variable \
+                (e.g. OPENAI_API_KEY, ANTHROPIC_API_KEY).",
+            ),
+            LLMError::ContextLengthExceeded(_) => Some(
+                "Reduce max_tokens in the provider's config, or shorten the task and its attachments; \
+                a token_budget stops runs before their conversation outgrows the model.",
+            ),
+            LLMError::Overloaded(_) => Some("The provider is overloaded; try again shortly, or use another model."),
+            LLMError::RateLimited(_) => Some(
+                "Increase max_retries in the provider's config, or lower the request rate (e.g. fewer parallel runs).",
+            ),
+            LLMError::QuotaExceeded(_) => Some("Check the billing and usage limits of the provider account."),
+            _ => None,
+        }
+    }
+}
+
 /// Represents the role of a message in a conversation.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -189,6 +233,9 @@ pub struct LLMResponse {
     /// Optional token usage information for the request.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<LLMUsage>,
+    /// Rate-limit state reported in the response headers, if the provider sent any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitInfo>,
     // TODO: Consider how to best represent provider-specific fields if they diverge significantly.
     // For Anthropic, content is directly an array of ContentBlock
     // and stop_reason is top-level. This struct is more OpenAI-like.
@@ -196,6 +243,7 @@ pub struct LLMResponse {
 }
 
 /// Represents the result of a tool execution, formatted for inclusion in an LLM message.
+#[allow(dead_code)] // Tool results are currently sent as `LLMMessage`s with role `tool`
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ToolResult {
     /// The ID of the tool call this result corresponds to.
@@ -244,6 +292,249 @@ pub trait LLMClient: Send + Sync {
         tool_choice: Option<ToolChoice>, // Added for OpenAI
     ) -> Result<LLMResponse, LLMError>;
 
+    /// Sends a chat request and streams the response, reporting tool calls through `on_event`
+    /// while their arguments are still being generated.
+    ///
+    /// The default implementation falls back to `chat` without reporting any events;
+    /// providers that support streaming override this.
+    ///
+    /// # Returns
+    /// The complete response, as `chat` would return it.
+    async fn chat_stream(
+        &self,
+        messages: Vec<LLMMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        _on_event: &(dyn Fn(StreamEvent) + Send + Sync),
+    ) -> Result<LLMResponse, LLMError> {
+        self.chat(messages, tools, tool_choice).await
+    }
+
+    /// Sends a chat request whose answer must be a JSON document matching `schema`.
+    ///
+    /// The default implementation adds the schema to the conversation as an instruction and
+    /// parses the reply; providers with native structured output support override this.
+    ///
+    /// # Arguments
+    /// * `messages`: The conversation to send.
+    /// * `schema_name`: A short identifier for the schema (used by providers that require one).
+    /// * `schema`: A JSON Schema describing the expected answer.
+    ///
+    /// # Returns
+    /// The parsed JSON answer, or an `LLMError` if the request fails or the reply is not JSON.
+    async fn chat_structured(
+        &self,
+        messages: Vec<LLMMessage>,
+        schema_name: &str,
+        schema: &serde_json::Value,
+    ) -> Result<serde_json::Value, LLMError> {
+        let mut messages = messages;
+        messages.push(LLMMessage {
+            role: MessageRole::User,
+            content: Some(format!(
+                "Respond only with a JSON document named `{}` that conforms to this JSON Schema, \
+                with no surrounding prose:\n{}",
+                schema_name, schema
+            )),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        });
+        let response = self.chat(messages, None, None).await?;
+        parse_json_response(&response)
+    }
+
     // Optional: A method to get provider name
     fn get_provider_name(&self) -> String;
 }
+
+/// Extracts and parses the JSON document in the first choice of `response`.
+///
+/// Markdown code fences around the JSON are tolerated.
+pub fn parse_json_response(response: &LLMResponse) -> Result<serde_json::Value, LLMError> {
+    let content = response
+        .choices
+        .first()
+        .and_then(|c| c.message.content.as_deref())
+        .ok_or_else(|| LLMError::Other("LLM response contained no content".to_string()))?
+        .trim();
+    let content = content
+        .strip_prefix("```json")
+        .or_else(|| content.strip_prefix("```"))
+        .map(|inner| inner.trim_end().trim_end_matches("```").trim())
+        .unwrap_or(content);
+    serde_json::from_str(content).map_err(LLMError::ParsingError)
+}
+
+/// Wait before the first retry of a failed LLM request; doubled for each further attempt.
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest wait before retrying an LLM request, including waits the provider asks for.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A failed attempt of an LLM request, about to be retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryNotice {
+    pub provider: String,
+    /// The number of the retry, starting at 1.
+    pub attempt: u32,
+    pub max_retries: u32,
+    pub delay: Duration,
+    /// Why the attempt failed.
+    pub reason: String,
+}
+
+impl std::fmt::Display for RetryNotice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} request failed ({}); retrying in {:.1}s (attempt {}/{})",
+            self.provider,
+            self.reason,
+            self.delay.as_secs_f64(),
+            self.attempt,
+            self.max_retries
+        )
+    }
+}
+
+tokio::task_local! {
+    /// Receives the retries of the LLM requests made within `with_retry_observer`.
+    static RETRY_OBSERVER: Arc<dyn Fn(&RetryNotice) + Send + Sync>;
+}
+
+/// Runs `future`, passing every retry of an LLM request it makes to `observer` (e.g. to show
+/// the user why a step is taking long).
+pub async fn with_retry_observer<F: Future>(observer: Arc<dyn Fn(&RetryNotice) + Send + Sync>, future: F) -> F::Output {
+    RETRY_OBSERVER.scope(observer, future).await
+}
+
+/// The failure of one attempt of an LLM request.
+#[derive(Debug)]
+pub struct AttemptError {
+    pub error: LLMError,
+    /// Whether another attempt may succeed: rate limits, server errors and dropped connections.
+    pub retryable: bool,
+    /// The wait the provider asked for before the next attempt.
+    pub retry_after: Option<Duration>,
+}
+
+impl AttemptError {
+    /// The failure of a request rejected with HTTP `status`; `error` is its typed error.
+    pub fn from_status(status: u16, error: LLMError, retry_after: Option<Duration>) -> Self {
+        // A 429 for an exhausted quota or an oversized prompt fails the same way every time.
+        let retryable = matches!(status, 429 | 500 | 502 | 503 | 504 | 529)
+            && !matches!(error, LLMError::QuotaExceeded(_) | LLMError::ContextLengthExceeded(_) | LLMError::InvalidApiKey(_));
+        Self {
+            error,
+            retryable,
+            retry_after,
+        }
+    }
+
+    /// The failure of a request that got no response.
+    pub fn from_network(error: reqwest::Error) -> Self {
+        let retryable = error.is_connect() || error.is_timeout() || is_connection_reset(&error);
+        Self {
+            error: LLMError::Network(error),
+            retryable,
+            retry_after: None,
+        }
+    }
+}
+
+impl From<LLMError> for AttemptError {
+    fn from(error: LLMError) -> Self {
+        Self {
+            error,
+            retryable: false,
+            retry_after: None,
+        }
+    }
+}
+
+/// Whether `error` was caused by the connection being reset or closed mid-request.
+fn is_connection_reset(error: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::BrokenPipe
+            );
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// How often and how long to retry failed LLM requests; shared by the clients.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// The policy for a client configured with `params` (its `max_retries`).
+    pub fn from_parameters(params: &ModelParameters) -> Self {
+        Self {
+            max_retries: params.max_retries,
+            initial_backoff: INITIAL_RETRY_BACKOFF,
+            max_backoff: MAX_RETRY_BACKOFF,
+        }
+    }
+
+    /// The wait before retry `attempt` (from 1): the provider's `retry_after` if it gave one,
+    /// otherwise an exponential backoff with jitter, so parallel runs do not retry in lockstep.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(wait) = retry_after {
+            return wait.min(self.max_backoff);
+        }
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_backoff);
+        // Between half and all of the backoff.
+        backoff / 2 + backoff.mul_f64(jitter() / 2.0)
+    }
+
+    /// Runs `attempt` until it succeeds, fails for good, or `max_retries` retries have failed,
+    /// waiting between attempts. Retries are logged and passed to the observer of the task
+    /// (see `with_retry_observer`).
+    pub async fn run<T, F, Fut>(&self, provider: &str, mut attempt: F) -> Result<T, LLMError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, AttemptError>>,
+    {
+        let mut retries = 0;
+        loop {
+            let failure = match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(failure) => failure,
+            };
+            if !failure.retryable || retries >= self.max_retries {
+                return Err(failure.error);
+            }
+            retries += 1;
+            let notice = RetryNotice {
+                provider: provider.to_string(),
+                attempt: retries,
+                max_retries: self.max_retries,
+                delay: self.delay(retries, failure.retry_after),
+                reason: failure.error.to_string(),
+            };
+            tracing::warn!("{}", notice);
+            _ = RETRY_OBSERVER.try_with(|observer| observer(&notice));
+            tokio::time::sleep(notice.delay).await;
+        }
+    }
+}
+
+/// A number in [0, 1) that differs between calls.
+fn jitter() -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos()));
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
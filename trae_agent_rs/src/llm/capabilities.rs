@@ -0,0 +1,230 @@
+//! # Model Capabilities
+//!
+//! What the configured model can do — tool calling, image input, JSON mode and the size of its
+//! context window — checked when an agent is created. Known models are looked up in a built-in
+//! catalog; other models get one cheap live request offering a tool. The agent cannot do
+//! anything without tool calls, so a model that lacks them fails at startup with a clear
+//! message instead of deep inside the first step.
+
+use super::base_client::{
+    FunctionDefinition, FunctionParameters, LLMClient, LLMError, LLMMessage, MessageRole, ToolDefinition,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// What a model supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ModelCapabilities {
+    pub tool_calling: bool,
+    /// Accepts images in messages.
+    pub vision: bool,
+    /// Has a native JSON output mode.
+    pub json_mode: bool,
+    /// Context window, in tokens.
+    pub context_window: u32,
+}
+
+const fn caps(tool_calling: bool, vision: bool, json_mode: bool, context_window: u32) -> ModelCapabilities {
+    ModelCapabilities {
+        tool_calling,
+        vision,
+        json_mode,
+        context_window,
+    }
+}
+
+/// Known models, keyed by model name prefix like the price table: dated variants match their
+/// base name and the longest matching prefix wins.
+const CATALOG: &[(&str, ModelCapabilities)] = &[
+    ("gpt-4o", caps(true, true, true, 128_000)),
+    ("gpt-4o-mini", caps(true, true, true, 128_000)),
+    ("gpt-4.1", caps(true, true, true, 1_047_576)),
+    ("gpt-4-turbo", caps(true, true, true, 128_000)),
+    ("gpt-4", caps(true, false, false, 8_192)),
+    ("gpt-3.5-turbo", caps(true, false, true, 16_385)),
+    ("gpt-3.5-turbo-instruct", caps(false, false, false, 4_096)),
+    ("o1", caps(true, true, true, 200_000)),
+    ("o1-mini", caps(false, false, false, 128_000)),
+    ("o1-preview", caps(false, false, false, 128_000)),
+    ("o3", caps(true, true, true, 200_000)),
+    ("o3-mini", caps(true, false, true, 200_000)),
+    ("o4-mini", caps(true, true, true, 200_000)),
+    ("claude-opus-4", caps(true, true, false, 200_000)),
+    ("claude-sonnet-4", caps(true, true, false, 200_000)),
+    ("claude-4-opus", caps(true, true, false, 200_000)),
+    ("claude-4-sonnet", caps(true, true, false, 200_000)),
+    ("claude-3-7-sonnet", caps(true, true, false, 200_000)),
+    ("claude-3-5-sonnet", caps(true, true, false, 200_000)),
+    ("claude-3-5-haiku", caps(true, false, false, 200_000)),
+    ("claude-3-opus", caps(true, true, false, 200_000)),
+    ("claude-3-haiku", caps(true, true, false, 200_000)),
+    ("claude-2", caps(false, false, false, 100_000)),
+    ("deepseek-chat", caps(true, false, true, 64_000)),
+    ("deepseek-reasoner", caps(false, false, false, 64_000)),
+];
+
+/// Looks up the capabilities of a model in the catalog.
+pub fn catalog_lookup(model: &str) -> Option<ModelCapabilities> {
+    // OpenRouter names models `vendor/model`.
+    let model = model.rsplit_once('/').map_or(model, |(_, name)| name);
+    CATALOG
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, capabilities)| *capabilities)
+}
+
+/// Outcome of the live tool calling check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolSupport {
+    Supported,
+    /// The provider rejected the tool, or the model ignored it.
+    Unsupported(String),
+    /// The check itself failed (network, authentication, ...).
+    Unknown(String),
+}
+
+/// Name of the tool offered by the live check.
+const PROBE_TOOL: &str = "ping";
+
+/// Sends one short request offering a tool and asking the model to call it.
+pub async fn probe_tool_calling(client: &dyn LLMClient) -> ToolSupport {
+    let messages = vec![LLMMessage {
+        role: MessageRole::User,
+        content: Some(format!(
+            "This is a capability check. Call the `{}` tool, with no arguments and no other text.",
+            PROBE_TOOL
+        )),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    }];
+    let tools = vec![ToolDefinition {
+        tool_type: "function".to_string(),
+        function: FunctionDefinition {
+            name: PROBE_TOOL.to_string(),
+            description: "Replies with pong.".to_string(),
+            parameters: FunctionParameters {
+                param_type: "object".to_string(),
+                properties: HashMap::new(),
+                required: Vec::new(),
+            },
+        },
+    }];
+    match client.chat(messages, Some(tools), None).await {
+        Ok(response) => {
+            let called = response
+                .choices
+                .iter()
+                .any(|choice| choice.message.tool_calls.as_ref().is_some_and(|calls| !calls.is_empty()));
+            if called {
+                ToolSupport::Supported
+            } else {
+                ToolSupport::Unsupported("the model answered without calling the offered tool".to_string())
+            }
+        }
+        Err(LLMError::ToolConfig(message)) => ToolSupport::Unsupported(message),
+        Err(LLMError::ApiError(message)) if mentions_tools(&message) => ToolSupport::Unsupported(message),
+        Err(e) => ToolSupport::Unknown(e.to_string()),
+    }
+}
+
+fn mentions_tools(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("tool") || message.contains("function")
+}
+
+/// Checks that `model` can call tools: from the catalog, or, for models it does not list and
+/// with `live` set, with `probe_tool_calling`. A live check that cannot be completed is only
+/// logged. Returns the catalog entry, if there is one.
+pub async fn check_model(
+    client: &dyn LLMClient,
+    model: &str,
+    live: bool,
+) -> Result<Option<ModelCapabilities>, String> {
+    if let Some(capabilities) = catalog_lookup(model) {
+        if !capabilities.tool_calling {
+            return Err(format!(
+                "Model '{}' does not support tool calling, which the agent needs for every step.",
+                model
+            ));
+        }
+        return Ok(Some(capabilities));
+    }
+    if !live {
+        return Ok(None);
+    }
+    match probe_tool_calling(client).await {
+        ToolSupport::Supported => Ok(None),
+        ToolSupport::Unsupported(detail) => Err(format!(
+            "Model '{}' does not appear to support tool calling, which the agent needs for every step: {}.",
+            model, detail
+        )),
+        ToolSupport::Unknown(detail) => {
+            warn!("Could not check whether model '{}' supports tool calling: {}", model, detail);
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::ModelParameters;
+    use crate::llm::OpenAIClient;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn client_answering(response: ResponseTemplate) -> (MockServer, OpenAIClient) {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+        let params: ModelParameters =
+            serde_json::from_value(json!({"model": "local-model", "max_retries": 0})).unwrap();
+        let client = OpenAIClient::new(Some("key".to_string()), Some(server.uri()), params)
+            .await
+            .unwrap();
+        (server, client)
+    }
+
+    fn completion(message: serde_json::Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({
+            "id": "chatcmpl-1", "object": "chat.completion", "created": 1, "model": "local-model",
+            "choices": [{"index": 0, "message": message, "finish_reason": "stop"}]
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_catalog_and_live_tool_calling_check() {
+        assert_eq!(catalog_lookup("gpt-4o-mini-2024-07-18").unwrap().context_window, 128_000);
+        assert!(!catalog_lookup("o1-mini").unwrap().tool_calling);
+        assert!(catalog_lookup("o1-2024-12-17").unwrap().tool_calling);
+        assert_eq!(catalog_lookup("local-model"), None);
+
+        let (_server, client) = client_answering(completion(json!({"role": "assistant", "content": "pong"}))).await;
+        assert!(check_model(&client, "o1-mini", true).await.unwrap_err().contains("does not support tool calling"));
+        assert!(check_model(&client, "gpt-4o", true).await.unwrap().is_some());
+        assert_eq!(check_model(&client, "local-model", false).await, Ok(None));
+        let error = check_model(&client, "local-model", true).await.unwrap_err();
+        assert!(error.contains("answered without calling the offered tool"), "{}", error);
+
+        let (_server, client) = client_answering(completion(json!({
+            "role": "assistant", "content": null,
+            "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "ping", "arguments": "{}"}}]
+        })))
+        .await;
+        assert_eq!(probe_tool_calling(&client).await, ToolSupport::Supported);
+
+        let (_server, client) = client_answering(ResponseTemplate::new(401).set_body_json(json!({
+            "error": {"message": "Incorrect API key provided", "type": "invalid_request_error"}
+        })))
+        .await;
+        assert!(matches!(probe_tool_calling(&client).await, ToolSupport::Unknown(_)));
+        assert_eq!(check_model(&client, "local-model", true).await, Ok(None));
+    }
+}
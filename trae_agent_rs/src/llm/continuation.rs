@@ -0,0 +1,218 @@
+//! # Length Continuations
+//!
+//! A provider that hits `max_tokens` stops mid-answer with the finish reason `length`
+//! (`max_tokens` for Anthropic), often in the middle of a tool call's arguments or of a file's
+//! contents. Instead of failing on the truncated JSON, the agent asks the model to continue
+//! where it stopped and stitches the pieces together before the response is parsed.
+
+use super::base_client::{LLMClient, LLMError, LLMMessage, LLMResponse, LLMUsage, MessageRole};
+use tracing::{info, warn};
+
+/// How many continuation requests are made for one response at most.
+pub const MAX_CONTINUATIONS: u32 = 3;
+
+/// Whether the response was cut off by the output length limit.
+pub fn is_truncated(response: &LLMResponse) -> bool {
+    response
+        .choices
+        .first()
+        .and_then(|choice| choice.finish_reason.as_deref())
+        .is_some_and(|reason| reason == "length" || reason == "max_tokens")
+}
+
+/// Requests continuations of `response` from `client` while it is truncated, and returns the
+/// stitched response. If the limit of continuations is reached, the last stitched (and still
+/// truncated) response is returned.
+///
+/// # Arguments
+/// * `messages`: The messages the truncated response answered.
+pub async fn complete_truncated(
+    client: &dyn LLMClient,
+    messages: &[LLMMessage],
+    mut response: LLMResponse,
+) -> Result<LLMResponse, LLMError> {
+    let mut continuations = 0;
+    while is_truncated(&response) {
+        if continuations == MAX_CONTINUATIONS {
+            warn!("Response is still truncated after {} continuations", MAX_CONTINUATIONS);
+            break;
+        }
+        continuations += 1;
+        info!(continuation = continuations, "Response was cut off at the length limit; requesting the rest");
+        let continuation = client.chat(continuation_messages(messages, &response), None, None).await?;
+        response = stitch(response, continuation);
+    }
+    Ok(response)
+}
+
+/// `messages` followed by the truncated answer and a request to continue it.
+fn continuation_messages(messages: &[LLMMessage], truncated: &LLMResponse) -> Vec<LLMMessage> {
+    let message = &truncated.choices[0].message;
+    let text = |role, content: String| LLMMessage {
+        role,
+        content: Some(content),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    };
+    let mut out = messages.to_vec();
+    match message.tool_calls.as_ref().and_then(|calls| calls.last()) {
+        // Cut off while writing a tool call: the call cannot be sent back as a call, since its
+        // arguments are incomplete, so it is shown as text.
+        Some(call) => {
+            out.push(text(
+                MessageRole::Assistant,
+                format!(
+                    "{}[Arguments of the `{}` tool call so far]:\n{}",
+                    message.content.as_deref().map(|c| format!("{}\n\n", c)).unwrap_or_default(),
+                    call.function.name,
+                    call.function.arguments
+                ),
+            ));
+            out.push(text(
+                MessageRole::User,
+                format!(
+                    "Your response was cut off by the output length limit while writing the arguments of the `{}` tool \
+                    call. Reply with only the rest of the arguments JSON, starting exactly after its last character, \
+                    without repeating anything and without any commentary.",
+                    call.function.name
+                ),
+            ));
+        }
+        None => {
+            out.push(text(MessageRole::Assistant, message.content.clone().unwrap_or_default()));
+            out.push(text(
+                MessageRole::User,
+                "Your response was cut off by the output length limit. Continue exactly where it stopped, without \
+                repeating anything."
+                    .to_string(),
+            ));
+        }
+    }
+    out
+}
+
+/// Appends `continuation` to the truncated `response`: to the arguments of its last tool call
+/// if it was cut off in one, to its text otherwise. A model that answers by calling the tool
+/// again, in full, replaces the truncated call.
+fn stitch(mut response: LLMResponse, continuation: LLMResponse) -> LLMResponse {
+    let Some(next) = continuation.choices.into_iter().next() else {
+        return response;
+    };
+    let choice = &mut response.choices[0];
+    let next_text = next.message.content.unwrap_or_default();
+    let next_calls = next.message.tool_calls.filter(|calls| !calls.is_empty());
+    match choice.message.tool_calls.as_mut() {
+        Some(calls) if next_text.is_empty() && next_calls.is_some() => {
+            calls.pop();
+        }
+        Some(calls) if !calls.is_empty() => {
+            if let Some(call) = calls.last_mut() {
+                call.function.arguments.push_str(&next_text);
+            }
+        }
+        _ => {
+            let content = choice.message.content.get_or_insert_with(String::new);
+            content.push_str(&next_text);
+        }
+    }
+    if let Some(calls) = next_calls {
+        choice.message.tool_calls.get_or_insert_with(Vec::new).extend(calls);
+    }
+    choice.finish_reason = next.finish_reason;
+    response.usage = match (response.usage, continuation.usage) {
+        (Some(a), Some(b)) => Some(LLMUsage {
+            prompt_tokens: a.prompt_tokens + b.prompt_tokens,
+            completion_tokens: Some(a.completion_tokens.unwrap_or(0) + b.completion_tokens.unwrap_or(0)),
+            total_tokens: a.total_tokens + b.total_tokens,
+        }),
+        (a, b) => a.or(b),
+    };
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModelParameters;
+    use crate::llm::OpenAIClient;
+    use serde_json::{json, Value};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn completion(message: Value, finish_reason: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1,
+            "model": "gpt-test",
+            "choices": [{"index": 0, "message": message, "finish_reason": finish_reason}],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_truncated_tool_call_is_continued_and_stitched() {
+        let server = MockServer::start().await;
+        for (message, finish_reason) in [
+            (json!({"role": "assistant", "content": "\"path\": \"/repo/a.txt\", \"file_"}), "length"),
+            (json!({"role": "assistant", "content": "text\": \"hello\"}"}), "stop"),
+        ] {
+            Mock::given(method("POST"))
+                .and(path("/chat/completions"))
+                .respond_with(completion(message, finish_reason))
+                .up_to_n_times(1)
+                .mount(&server)
+                .await;
+        }
+        let params: ModelParameters = serde_json::from_value(json!({"model": "gpt-test"})).unwrap();
+        let client = OpenAIClient::new(Some("key".to_string()), Some(server.uri()), params)
+            .await
+            .unwrap();
+        let truncated: LLMResponse = serde_json::from_value(json!({
+            "id": "chatcmpl-0",
+            "object": "chat.completion",
+            "created": 1,
+            "model": "gpt-test",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "str_replace_based_edit_tool", "arguments": "{\"command\": \"create\", "}
+                    }]
+                },
+                "finish_reason": "length"
+            }],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        }))
+        .unwrap();
+        assert!(is_truncated(&truncated));
+
+        let messages = vec![LLMMessage {
+            role: MessageRole::User,
+            content: Some("Create a.txt".to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        }];
+        let response = complete_truncated(&client, &messages, truncated).await.unwrap();
+        assert!(!is_truncated(&response));
+        let call = &response.choices[0].message.tool_calls.as_ref().unwrap()[0];
+        let args: Value = serde_json::from_str(&call.function.arguments).unwrap();
+        assert_eq!(
+            args,
+            json!({"command": "create", "path": "/repo/a.txt", "file_text": "hello"})
+        );
+        assert_eq!(response.usage.as_ref().unwrap().total_tokens, 45);
+
+        // The second continuation was asked with the stitched arguments so far.
+        let requests = server.received_requests().await.unwrap();
+        let body: Value = serde_json::from_slice(&requests[1].body).unwrap();
+        let shown = body["messages"][1]["content"].as_str().unwrap();
+        assert!(shown.ends_with("{\"command\": \"create\", \"path\": \"/repo/a.txt\", \"file_"), "{}", shown);
+    }
+}
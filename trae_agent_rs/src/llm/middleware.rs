@@ -0,0 +1,171 @@
+//! # LLM Middleware
+//!
+//! Request/response interceptors for the HTTP-based LLM clients. Embedders register
+//! middleware on a client (see `OpenAIClient::with_middleware`) to add headers, route
+//! requests through a gateway, sign requests or audit traffic without modifying client code.
+//!
+//! Middleware runs in registration order for requests and again in registration order for
+//! responses. Returning an error from either hook aborts the call with that error.
+
+use super::base_client::{LLMError, ModelParameters};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// An outgoing provider request, as seen by middleware before it is sent.
+#[derive(Debug, Clone)]
+pub struct LLMHttpRequest {
+    /// Provider name (e.g., "openai").
+    pub provider: String,
+    /// Full endpoint URL. Middleware may rewrite it, e.g. to target a gateway.
+    pub url: String,
+    /// Headers added on top of the client's defaults (authorization, content type).
+    pub headers: HeaderMap,
+    /// The JSON request body.
+    pub body: Value,
+}
+
+/// A provider response, as seen by middleware before it is parsed.
+#[allow(dead_code)] // Some fields are only read by embedders' middleware
+#[derive(Debug, Clone)]
+pub struct LLMHttpResponse {
+    /// Provider name (e.g., "openai").
+    pub provider: String,
+    /// The URL the request was sent to.
+    pub url: String,
+    /// HTTP status code.
+    pub status: u16,
+    /// Response headers.
+    pub headers: HeaderMap,
+    /// Raw response body.
+    pub body: String,
+}
+
+/// Intercepts the HTTP traffic of an LLM client.
+#[async_trait]
+pub trait LLMMiddleware: Send + Sync {
+    /// Called before a request is sent. May modify the URL, headers and body.
+    async fn on_request(&self, _request: &mut LLMHttpRequest) -> Result<(), LLMError> {
+        Ok(())
+    }
+
+    /// Called after a response is received, before it is parsed.
+    async fn on_response(&self, _response: &LLMHttpResponse) -> Result<(), LLMError> {
+        Ok(())
+    }
+}
+
+/// The ordered middleware registered on a client.
+#[derive(Clone, Default)]
+pub struct MiddlewareStack {
+    layers: Vec<Arc<dyn LLMMiddleware>>,
+}
+
+impl std::fmt::Debug for MiddlewareStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MiddlewareStack")
+            .field("layers", &self.layers.len())
+            .finish()
+    }
+}
+
+impl MiddlewareStack {
+    /// Builds the stack implied by the provider's model parameters: a `StaticHeaders`
+    /// layer when `extra_headers` is configured.
+    pub fn from_model_parameters(params: &ModelParameters) -> Result<Self, LLMError> {
+        let mut stack = Self::default();
+        if let Some(headers) = params.extra_headers.as_ref().filter(|h| !h.is_empty()) {
+            stack.push(Arc::new(StaticHeaders::from_pairs(headers)?));
+        }
+        Ok(stack)
+    }
+
+    /// Appends a middleware layer.
+    pub fn push(&mut self, middleware: Arc<dyn LLMMiddleware>) {
+        self.layers.push(middleware);
+    }
+
+    /// Runs every layer's `on_request` hook in order.
+    pub async fn run_request(&self, request: &mut LLMHttpRequest) -> Result<(), LLMError> {
+        for layer in &self.layers {
+            layer.on_request(request).await?;
+        }
+        Ok(())
+    }
+
+    /// Runs every layer's `on_response` hook in order.
+    pub async fn run_response(&self, response: &LLMHttpResponse) -> Result<(), LLMError> {
+        for layer in &self.layers {
+            layer.on_response(response).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds a fixed set of headers to every request. Installed automatically from a provider's
+/// `extra_headers` configuration.
+#[derive(Debug, Clone)]
+pub struct StaticHeaders {
+    headers: HeaderMap,
+}
+
+impl StaticHeaders {
+    /// Builds the middleware from header name/value pairs.
+    ///
+    /// # Returns
+    /// An `LLMError::Other` if a name or value is not a valid HTTP header.
+    pub fn from_pairs<'a>(
+        pairs: impl IntoIterator<Item = (&'a String, &'a String)>,
+    ) -> Result<Self, LLMError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| LLMError::Other(format!("Invalid header name '{}': {}", name, e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| LLMError::Other(format!("Invalid value for header '{}': {}", name, e)))?;
+            headers.insert(name, value);
+        }
+        Ok(Self { headers })
+    }
+}
+
+#[async_trait]
+impl LLMMiddleware for StaticHeaders {
+    async fn on_request(&self, request: &mut LLMHttpRequest) -> Result<(), LLMError> {
+        for (name, value) in &self.headers {
+            request.headers.insert(name.clone(), value.clone());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_static_headers_rejects_invalid_names() {
+        let mut pairs = HashMap::new();
+        pairs.insert("bad header".to_string(), "x".to_string());
+        assert!(StaticHeaders::from_pairs(&pairs).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_static_headers_are_added() {
+        let mut pairs = HashMap::new();
+        pairs.insert("X-Gateway-Key".to_string(), "secret".to_string());
+        let mut stack = MiddlewareStack::default();
+        stack.push(Arc::new(StaticHeaders::from_pairs(&pairs).unwrap()));
+
+        let mut request = LLMHttpRequest {
+            provider: "openai".to_string(),
+            url: "http://localhost/chat/completions".to_string(),
+            headers: HeaderMap::new(),
+            body: Value::Null,
+        };
+        stack.run_request(&mut request).await.unwrap();
+        assert_eq!(request.headers["x-gateway-key"], "secret");
+    }
+}
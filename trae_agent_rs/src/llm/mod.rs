@@ -2,14 +2,48 @@
 //!
 //! Provides abstractions and clients for interacting with Large Language Models (LLMs).
 //! It defines a common `LLMClient` trait and implementations for specific providers
-//! like OpenAI.
+//! like OpenAI, Anthropic and OpenRouter.
 
 pub mod anthropic_client;
 pub mod base_client;
+pub mod capabilities;
+pub mod continuation;
+pub mod middleware;
 pub mod openai_client;
+pub mod openrouter_client;
+pub mod provider_errors;
+pub mod rate_limit;
+pub mod streaming;
+pub mod tool_limits;
 
 pub use anthropic_client::AnthropicClient;
 pub use base_client::{
     LLMClient, LLMError, LLMMessage, MessageRole, ModelParameters as LLMModelParameters,
 };
 pub use openai_client::OpenAIClient;
+pub use openrouter_client::OpenRouterClient;
+
+use std::sync::Arc;
+
+/// Creates a client for `provider` using the given model parameters.
+///
+/// # Returns
+/// A shared `LLMClient`, or an `LLMError` if the provider is unknown or the client cannot be built.
+pub async fn create_client(
+    provider: &str,
+    params: &LLMModelParameters,
+) -> Result<Arc<dyn LLMClient>, LLMError> {
+    match provider {
+        "openai" => Ok(Arc::new(
+            OpenAIClient::new(params.api_key.clone(), params.base_url.clone(), params.clone()).await?,
+        )),
+        "anthropic" => Ok(Arc::new(
+            AnthropicClient::new(params.api_key.clone(), params.base_url.clone(), params.clone())
+                .await?,
+        )),
+        "openrouter" => Ok(Arc::new(
+            OpenRouterClient::new(params.api_key.clone(), params.base_url.clone(), params.clone()).await?,
+        )),
+        _ => Err(LLMError::Other(format!("Unsupported LLM provider: {}", provider))),
+    }
+}
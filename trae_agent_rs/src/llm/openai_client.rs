@@ -1,16 +1,26 @@
 use super::base_client::{
+    parse_json_response,
+    AttemptError,
     LLMClient,
     LLMError,
     LLMMessage,
     LLMResponse,
     ModelParameters,
+    RetryPolicy,
     ToolChoice,
     ToolDefinition, // Removed ToolCall
 };
+use super::middleware::{LLMHttpRequest, LLMHttpResponse, LLMMiddleware, MiddlewareStack};
+use super::rate_limit::{QuotaLimiter, QuotaTicket, RateLimitInfo, RateLimitPacer};
+use super::streaming::{SseDecoder, StreamAccumulator, StreamEvent};
+use super::tool_limits::ToolLimits;
+use super::provider_errors::error_from_response;
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::Client as HttpClient;
+use crate::utils::http::{self, shared_http_client};
+use reqwest::{Client as HttpClient, StatusCode};
 use serde::Serialize;
+use std::sync::Arc;
 use tracing::{debug, error, instrument};
 
 const DEFAULT_OPENAI_API_BASE: &str = "https://api.openai.com/v1";
@@ -29,16 +39,33 @@ struct OpenAIChatRequest<'a> {
     top_p: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
     // Add other parameters like stream, n, stop, presence_penalty, frequency_penalty, logit_bias, user if needed
 }
 
 #[derive(Debug)]
 pub struct OpenAIClient {
+    /// Pooled client shared with every other client that has the same network settings.
     http_client: HttpClient,
+    /// Authorization and content-type headers, added to each request.
+    headers: HeaderMap,
     #[allow(dead_code)] // Set in new, but primarily used for Authorization header setup.
     api_key: String,
     base_url: String,
     model_parameters: ModelParameters,
+    /// Delays requests while the provider reports an exhausted rate-limit window.
+    pacer: RateLimitPacer,
+    /// Client-side quotas (`requests_per_minute`, `tokens_per_minute`), if configured.
+    quota: Option<Arc<QuotaLimiter>>,
+    /// Request/response interceptors, run on every HTTP attempt.
+    middleware: MiddlewareStack,
 }
 
 #[async_trait] // Added
@@ -64,16 +91,25 @@ impl LLMClient for OpenAIClient {
         );
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-        let http_client = HttpClient::builder()
-            .default_headers(headers)
-            .build()
-            .map_err(LLMError::Network)?;
+        let http_client = shared_http_client(&model_parameters.network)
+            .map_err(|e| LLMError::Other(format!("{:#}", e)))?;
 
+        let base_url = base_url.unwrap_or_else(|| DEFAULT_OPENAI_API_BASE.to_string());
+        let quota = QuotaLimiter::shared(
+            &base_url,
+            &model_parameters.model,
+            model_parameters.requests_per_minute,
+            model_parameters.tokens_per_minute,
+        );
         Ok(Self {
             http_client,
+            headers,
             api_key: final_key, // Stored for potential future use, though already in headers
-            base_url: base_url.unwrap_or_else(|| DEFAULT_OPENAI_API_BASE.to_string()),
+            base_url,
+            quota,
+            middleware: MiddlewareStack::from_model_parameters(&model_parameters)?,
             model_parameters,
+            pacer: RateLimitPacer::default(),
         })
     }
 
@@ -84,6 +120,32 @@ impl LLMClient for OpenAIClient {
         tools: Option<Vec<ToolDefinition>>,
         tool_choice: Option<ToolChoice>,
     ) -> Result<LLMResponse, LLMError> {
+        let tools = ToolLimits::OPENAI.enforce(tools)?;
+        let request_payload = OpenAIChatRequest {
+            model: &self.model_parameters.model,
+            messages: &messages,
+            tools: tools.as_deref(),
+            tool_choice: tool_choice.as_ref(),
+            temperature: Some(self.model_parameters.temperature),
+            top_p: Some(self.model_parameters.top_p),
+            max_tokens: self.model_parameters.max_tokens,
+            response_format: None,
+            stream: false,
+            stream_options: None,
+            seed: self.model_parameters.seed,
+        };
+        self.send_chat_request(&request_payload).await
+    }
+
+    #[instrument(skip(self, messages, tools, on_event))]
+    async fn chat_stream(
+        &self,
+        messages: Vec<LLMMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        on_event: &(dyn Fn(StreamEvent) + Send + Sync),
+    ) -> Result<LLMResponse, LLMError> {
+        let tools = ToolLimits::OPENAI.enforce(tools)?;
         let request_payload = OpenAIChatRequest {
             model: &self.model_parameters.model,
             messages: &messages,
@@ -92,59 +154,259 @@ impl LLMClient for OpenAIClient {
             temperature: Some(self.model_parameters.temperature),
             top_p: Some(self.model_parameters.top_p),
             max_tokens: self.model_parameters.max_tokens,
+            response_format: None,
+            stream: true,
+            stream_options: Some(serde_json::json!({ "include_usage": true })),
+            seed: self.model_parameters.seed,
         };
+        self.send_streaming_chat_request(&request_payload, on_event)
+            .await
+    }
 
+    #[instrument(skip(self, messages, schema))]
+    async fn chat_structured(
+        &self,
+        messages: Vec<LLMMessage>,
+        schema_name: &str,
+        schema: &serde_json::Value,
+    ) -> Result<serde_json::Value, LLMError> {
+        let request_payload = OpenAIChatRequest {
+            model: &self.model_parameters.model,
+            messages: &messages,
+            tools: None,
+            tool_choice: None,
+            temperature: Some(self.model_parameters.temperature),
+            top_p: Some(self.model_parameters.top_p),
+            max_tokens: self.model_parameters.max_tokens,
+            response_format: Some(serde_json::json!({
+                "type": "json_schema",
+                "json_schema": { "name": schema_name, "schema": schema },
+            })),
+            stream: false,
+            stream_options: None,
+            seed: self.model_parameters.seed,
+        };
+        let response = self.send_chat_request(&request_payload).await?;
+        parse_json_response(&response)
+    }
+
+    fn get_provider_name(&self) -> String {
+        "openai".to_string()
+    }
+}
+
+impl OpenAIClient {
+    /// Registers a middleware layer that sees every request and response of this client.
+    /// Layers run in registration order, after any layer installed from `extra_headers`.
+    pub fn with_middleware(mut self, middleware: Arc<dyn LLMMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Posts a chat completion request and parses the response.
+    async fn send_chat_request(
+        &self,
+        request_payload: &OpenAIChatRequest<'_>,
+    ) -> Result<LLMResponse, LLMError> {
         debug!(payload = ?request_payload, "Sending OpenAI chat request");
+        let quota = self.acquire_quota(request_payload).await;
+        let (http_response, url, rate_limit) = self.post_chat_request(request_payload).await?;
+        let response = LLMHttpResponse {
+            provider: self.get_provider_name(),
+            url,
+            status: http_response.status().as_u16(),
+            headers: http_response.headers().clone(),
+            body: http_response.text().await.map_err(LLMError::Network)?,
+        };
+        self.middleware.run_response(&response).await?;
+        check_response_status(&response)?;
+
+        let mut llm_response: LLMResponse = serde_json::from_str(&response.body).map_err(|e| {
+            error!(error = %e, "Failed to parse OpenAI JSON response");
+            LLMError::Other(format!("JSON decoding error: {}", e))
+        })?;
 
-        let url = format!("{}/chat/completions", self.base_url);
-        let response = self
-            .http_client
-            .post(&url)
-            .json(&request_payload)
-            .send()
+        llm_response.rate_limit = rate_limit;
+        settle_quota(quota, &llm_response);
+        debug!(response_id = %llm_response.id, "Successfully parsed OpenAI response");
+        Ok(llm_response)
+    }
+
+    /// Posts a streaming chat completion request and assembles the streamed chunks.
+    ///
+    /// Middleware sees the raw event stream once it has been received completely.
+    async fn send_streaming_chat_request(
+        &self,
+        request_payload: &OpenAIChatRequest<'_>,
+        on_event: &(dyn Fn(StreamEvent) + Send + Sync),
+    ) -> Result<LLMResponse, LLMError> {
+        debug!(payload = ?request_payload, "Sending OpenAI streaming chat request");
+        let quota = self.acquire_quota(request_payload).await;
+        let (mut http_response, url, rate_limit) =
+            self.post_chat_request(request_payload).await?;
+        let status = http_response.status().as_u16();
+        let headers = http_response.headers().clone();
+
+        let mut body = String::new();
+        let mut decoder = SseDecoder::default();
+        let mut accumulator = StreamAccumulator::default();
+        let mut pending = Vec::new();
+        while let Some(chunk) = http_response.chunk().await.map_err(LLMError::Network)? {
+            pending.extend_from_slice(&chunk);
+            // Chunks may end inside a multi-byte character; keep the tail for the next one.
+            let valid_up_to = match std::str::from_utf8(&pending) {
+                Ok(_) => pending.len(),
+                Err(e) => e.valid_up_to(),
+            };
+            let text = String::from_utf8_lossy(&pending[..valid_up_to]).into_owned();
+            pending.drain(..valid_up_to);
+            body.push_str(&text);
+            if !(200..300).contains(&status) {
+                continue;
+            }
+            for payload in decoder.push(&text) {
+                if payload == "[DONE]" {
+                    continue;
+                }
+                let chunk: serde_json::Value = serde_json::from_str(&payload).map_err(|e| {
+                    error!(error = %e, "Failed to parse OpenAI stream chunk");
+                    LLMError::Other(format!("JSON decoding error in stream chunk: {}", e))
+                })?;
+                for event in accumulator.push_chunk(&chunk) {
+                    on_event(event);
+                }
+            }
+        }
+
+        let response = LLMHttpResponse {
+            provider: self.get_provider_name(),
+            url,
+            status,
+            headers,
+            body,
+        };
+        self.middleware.run_response(&response).await?;
+        check_response_status(&response)?;
+
+        let mut llm_response = accumulator.into_response();
+        llm_response.rate_limit = rate_limit;
+        settle_quota(quota, &llm_response);
+        debug!(response_id = %llm_response.id, "Successfully assembled OpenAI streamed response");
+        Ok(llm_response)
+    }
+
+    /// Waits for room in the client-side quotas, counting the request with an estimate of its
+    /// tokens: the prompt at about four characters per token, plus the completion limit.
+    async fn acquire_quota(&self, request_payload: &OpenAIChatRequest<'_>) -> Option<QuotaTicket> {
+        let quota = self.quota.as_ref()?;
+        let prompt_chars = serde_json::to_string(request_payload.messages).map_or(0, |s| s.len())
+            + request_payload.tools.map_or(0, |tools| serde_json::to_string(tools).map_or(0, |s| s.len()));
+        let estimate = prompt_chars.div_ceil(4) as u64 + u64::from(request_payload.max_tokens.unwrap_or(0));
+        Some(quota.acquire(estimate).await)
+    }
+
+    /// Sends a chat completion request, leaving the response body unread.
+    ///
+    /// Requests are paced according to the rate-limit headers of earlier responses. Requests
+    /// rejected with HTTP 429 or a server error, or whose connection failed, are retried (up
+    /// to `max_retries` times, see `RetryPolicy`) after the wait the provider asks for, or an
+    /// exponential backoff if it gives none. Middleware runs on every attempt; responses of
+    /// rejected attempts are passed to it before retrying.
+    ///
+    /// # Returns
+    /// The final HTTP response, the URL it came from and its rate-limit headers.
+    async fn post_chat_request(
+        &self,
+        request_payload: &OpenAIChatRequest<'_>,
+    ) -> Result<(reqwest::Response, String, Option<RateLimitInfo>), LLMError> {
+        let body = serde_json::to_value(request_payload).map_err(LLMError::ParsingError)?;
+        RetryPolicy::from_parameters(&self.model_parameters)
+            .run(&self.get_provider_name(), || self.attempt_chat_request(&body))
             .await
-            .map_err(LLMError::Network)?;
-
-        let status = response.status(); // Store status first
-        debug!(status = ?status, "Received OpenAI response status");
-
-        if !status.is_success() {
-            let error_body = response.text().await.map_err(LLMError::Network)?;
-            error!(error_body = %error_body, "OpenAI API error");
-            return Err(LLMError::ApiError(format!(
-                "API request failed with status {}: {}",
-                status, // Use stored status
-                error_body
-            )));
+    }
+
+    /// One attempt of `post_chat_request`.
+    async fn attempt_chat_request(
+        &self,
+        body: &serde_json::Value,
+    ) -> Result<(reqwest::Response, String, Option<RateLimitInfo>), AttemptError> {
+        self.pacer.wait_for_capacity().await;
+        let mut request = LLMHttpRequest {
+            provider: self.get_provider_name(),
+            url: format!("{}/chat/completions", self.base_url),
+            headers: HeaderMap::new(),
+            body: body.clone(),
+        };
+        self.middleware.run_request(&mut request).await?;
+
+        let http_response = http::send(
+            self.http_client
+                .post(&request.url)
+                .headers(self.headers.clone())
+                .headers(request.headers)
+                .json(&request.body),
+        )
+        .await
+        .map_err(AttemptError::from_network)?;
+        debug!(status = ?http_response.status(), "Received OpenAI response status");
+
+        let rate_limit = RateLimitInfo::from_headers(http_response.headers());
+        if let Some(info) = &rate_limit {
+            debug!(rate_limit = ?info, "OpenAI rate-limit headers");
+            self.pacer.observe(info);
+        }
+        let status = http_response.status();
+        if !(status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()) {
+            return Ok((http_response, request.url, rate_limit));
         }
 
-        let llm_response = response.json::<LLMResponse>().await.map_err(|e| {
-            error!(error = %e, "Failed to parse OpenAI JSON response");
-            // LLMError::ParsingError expects serde_json::Error. reqwest::Error can be other things.
-            // If e.is_decode() is true, it's a JSON parsing issue. Otherwise, network.
-            if e.is_decode() {
-                // Attempt to get underlying serde error if possible, or just use reqwest error string
-                LLMError::Other(format!("JSON decoding error: {}", e))
+        let rejected = LLMHttpResponse {
+            provider: request.provider,
+            url: request.url,
+            status: status.as_u16(),
+            headers: http_response.headers().clone(),
+            body: http_response.text().await.map_err(AttemptError::from_network)?,
+        };
+        self.middleware.run_response(&rejected).await?;
+        debug!(error_body = %rejected.body, "OpenAI API error");
+        let retry_after = rate_limit.as_ref().and_then(|info| {
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                info.wait_before_next_request()
             } else {
-                LLMError::Network(e)
+                info.retry_after
             }
-        })?;
+        });
+        Err(AttemptError::from_status(
+            rejected.status,
+            error_from_response(rejected.status, &rejected.body),
+            retry_after,
+        ))
+    }
+}
 
-        debug!(response_id = %llm_response.id, "Successfully parsed OpenAI response");
-        Ok(llm_response)
+/// Counts the tokens the response reports against the quota instead of the estimate.
+fn settle_quota(ticket: Option<QuotaTicket>, response: &LLMResponse) {
+    if let (Some(ticket), Some(usage)) = (ticket, &response.usage) {
+        ticket.settle(u64::from(usage.total_tokens));
     }
+}
 
-    fn get_provider_name(&self) -> String {
-        "openai".to_string()
+/// Converts a non-success HTTP status into an `LLMError` (see `provider_errors`).
+fn check_response_status(response: &LLMHttpResponse) -> Result<(), LLMError> {
+    let status = response.status;
+    if (200..300).contains(&status) {
+        return Ok(());
     }
+    error!(error_body = %response.body, "OpenAI API error");
+    Err(error_from_response(status, &response.body))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::llm::base_client::{LLMMessage, MessageRole}; // Removed ToolCallFunction
+    use crate::llm::base_client::{with_retry_observer, LLMMessage, MessageRole, RetryNotice}; // Removed ToolCallFunction
     use serde_json::json;
-    use wiremock::matchers::{bearer_token, header, method, path};
+    use wiremock::matchers::{body_partial_json, bearer_token, header, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     async fn setup_mock_server(api_key: &str) -> MockServer {
@@ -191,6 +453,12 @@ mod tests {
             api_version: None,
             candidate_count: None,
             stop_sequences: None,
+            seed: None,
+            extra_headers: None,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            openrouter: None,
+            network: Default::default(),
         }
     }
 
@@ -240,6 +508,44 @@ mod tests {
         assert_eq!(response.model, "gpt-4-test");
     }
 
+    #[tokio::test]
+    async fn test_openai_chat_sends_seed() {
+        let api_key = "test_api_key_seed";
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(json!({"seed": 42})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "chatcmpl-seed",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "gpt-4-test",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Seeded"},
+                    "finish_reason": "stop"
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut params = get_default_model_params();
+        params.seed = Some(42);
+        let client = OpenAIClient::new(Some(api_key.to_string()), Some(server.uri()), params)
+            .await
+            .unwrap();
+        let messages = vec![LLMMessage {
+            role: MessageRole::User,
+            content: Some("Hello".to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        }];
+        let response = client.chat(messages, None, None).await.unwrap();
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("Seeded"));
+    }
+
     #[tokio::test]
     async fn test_openai_chat_with_tools() {
         let api_key = "test_api_key_tools";
@@ -314,4 +620,273 @@ mod tests {
             "{\"location\": \"Boston\"}"
         );
     }
+
+    #[tokio::test]
+    async fn test_openai_chat_retries_rate_limited_request() {
+        let api_key = "test_api_key_rate_limit";
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "0")
+                    .set_body_string("Rate limit reached"),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("x-ratelimit-limit-requests", "500")
+                    .insert_header("x-ratelimit-remaining-requests", "499")
+                    .insert_header("x-ratelimit-reset-requests", "120ms")
+                    .set_body_json(json!({
+                        "id": "chatcmpl-after-429",
+                        "object": "chat.completion",
+                        "created": 1677652290,
+                        "model": "gpt-4-test",
+                        "choices": [{
+                            "index": 0,
+                            "message": { "role": "assistant", "content": "Done" },
+                            "finish_reason": "stop"
+                        }]
+                    })),
+            )
+            .mount(&server)
+            .await;
+
+        let client = OpenAIClient::new(
+            Some(api_key.to_string()),
+            Some(server.uri()),
+            get_default_model_params(),
+        )
+        .await
+        .unwrap();
+        let messages = vec![LLMMessage {
+            role: MessageRole::User,
+            content: Some("Hi".to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        }];
+
+        let response = client.chat(messages, None, None).await.unwrap();
+        assert_eq!(response.id, "chatcmpl-after-429");
+        let rate_limit = response.rate_limit.expect("rate-limit headers should be exposed");
+        assert_eq!(rate_limit.limit_requests, Some(500));
+        assert_eq!(rate_limit.remaining_requests, Some(499));
+        assert_eq!(
+            rate_limit.reset_requests,
+            Some(std::time::Duration::from_millis(120))
+        );
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_openai_chat_retries_server_errors_and_reports_retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "0").set_body_string("busy"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "chatcmpl-after-503",
+                "object": "chat.completion",
+                "created": 1677652290,
+                "model": "gpt-4-test",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "Done"}, "finish_reason": "stop"}]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = OpenAIClient::new(Some("key".to_string()), Some(server.uri()), get_default_model_params())
+            .await
+            .unwrap();
+        let message = || LLMMessage {
+            role: MessageRole::User,
+            content: Some("Hi".to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        };
+        let notices = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let observed = notices.clone();
+        let response = with_retry_observer(
+            Arc::new(move |notice: &RetryNotice| observed.lock().unwrap().push(notice.clone())),
+            client.chat(vec![message()], None, None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.id, "chatcmpl-after-503");
+        let notices = notices.lock().unwrap().clone();
+        assert_eq!(notices.iter().map(|n| n.attempt).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(notices[0].delay, std::time::Duration::ZERO);
+        assert!(notices[0].to_string().starts_with("openai request failed (Provider overloaded: busy); retrying in 0.0s (attempt 1/"));
+
+        // An exhausted quota is not retried.
+        server.reset().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(json!({
+                "error": {"message": "You exceeded your current quota.", "type": "insufficient_quota", "code": "insufficient_quota"}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let err = client.chat(vec![message()], None, None).await.unwrap_err();
+        assert!(matches!(err, LLMError::QuotaExceeded(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn test_retry_backoff_grows_with_jitter_and_honours_retry_after() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_backoff: std::time::Duration::from_secs(1),
+            max_backoff: std::time::Duration::from_secs(8),
+        };
+        for (attempt, full) in [(1, 1), (3, 4), (5, 8)] {
+            let delay = policy.delay(attempt, None);
+            let full = std::time::Duration::from_secs(full);
+            assert!(delay >= full / 2 && delay <= full, "attempt {}: {:?}", attempt, delay);
+        }
+        assert_eq!(policy.delay(1, Some(std::time::Duration::from_secs(3))), std::time::Duration::from_secs(3));
+        assert_eq!(policy.delay(1, Some(std::time::Duration::from_secs(300))), std::time::Duration::from_secs(8));
+    }
+
+    #[tokio::test]
+    async fn test_openai_chat_runs_middleware() {
+        use crate::llm::middleware::{LLMHttpRequest, LLMHttpResponse, LLMMiddleware};
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Recorder {
+            statuses: Mutex<Vec<u16>>,
+        }
+
+        #[async_trait]
+        impl LLMMiddleware for Recorder {
+            async fn on_request(&self, request: &mut LLMHttpRequest) -> Result<(), LLMError> {
+                request.body["user"] = json!("audited");
+                Ok(())
+            }
+            async fn on_response(&self, response: &LLMHttpResponse) -> Result<(), LLMError> {
+                self.statuses.lock().unwrap().push(response.status);
+                Ok(())
+            }
+        }
+
+        let api_key = "test_api_key_middleware";
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("X-Gateway-Key", "gw-secret"))
+            .and(wiremock::matchers::body_partial_json(json!({"user": "audited"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "chatcmpl-mw",
+                "object": "chat.completion",
+                "created": 1677652291,
+                "model": "gpt-4-test",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "ok" },
+                    "finish_reason": "stop"
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        let mut params = get_default_model_params();
+        params.extra_headers = Some(std::collections::HashMap::from([(
+            "X-Gateway-Key".to_string(),
+            "gw-secret".to_string(),
+        )]));
+        let recorder = Arc::new(Recorder::default());
+        let client = OpenAIClient::new(Some(api_key.to_string()), Some(server.uri()), params)
+            .await
+            .unwrap()
+            .with_middleware(recorder.clone());
+        let messages = vec![LLMMessage {
+            role: MessageRole::User,
+            content: Some("Hi".to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        }];
+
+        let response = client.chat(messages, None, None).await.unwrap();
+        assert_eq!(response.id, "chatcmpl-mw");
+        assert_eq!(*recorder.statuses.lock().unwrap(), vec![200]);
+    }
+
+    #[tokio::test]
+    async fn test_openai_chat_stream_assembles_tool_calls() {
+        let api_key = "test_api_key_stream";
+        let server = MockServer::start().await;
+        let chunks = [
+            json!({"id": "chatcmpl-stream", "model": "gpt-4-test", "created": 7, "choices": [{"index": 0, "delta": {"role": "assistant", "tool_calls": [{"index": 0, "id": "call_1", "type": "function", "function": {"name": "str_replace_based_edit_tool", "arguments": ""}}]}}]}),
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "{\"command\": \"create\", \"path\": \"/repo/a.txt\","}}]}}]}),
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": " \"file_text\": \"héllo\"}"}}]}, "finish_reason": "tool_calls"}]}),
+            json!({"choices": [], "usage": {"prompt_tokens": 5, "completion_tokens": 9, "total_tokens": 14}}),
+        ];
+        let mut body: String = chunks.iter().map(|c| format!("data: {}\n\n", c)).collect();
+        body.push_str("data: [DONE]\n\n");
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(json!({"stream": true})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "text/event-stream")
+                    .set_body_string(body),
+            )
+            .mount(&server)
+            .await;
+
+        let client = OpenAIClient::new(
+            Some(api_key.to_string()),
+            Some(server.uri()),
+            get_default_model_params(),
+        )
+        .await
+        .unwrap();
+        let messages = vec![LLMMessage {
+            role: MessageRole::User,
+            content: Some("Create a.txt".to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        }];
+        let events = std::sync::Mutex::new(Vec::new());
+        let response = client
+            .chat_stream(messages, None, None, &|event| events.lock().unwrap().push(event))
+            .await
+            .unwrap();
+
+        assert_eq!(response.id, "chatcmpl-stream");
+        assert_eq!(response.usage.as_ref().unwrap().total_tokens, 14);
+        let choice = &response.choices[0];
+        assert_eq!(choice.finish_reason.as_deref(), Some("tool_calls"));
+        let tool_calls = choice.message.tool_calls.as_ref().unwrap();
+        let args: serde_json::Value = serde_json::from_str(&tool_calls[0].function.arguments).unwrap();
+        assert_eq!(args["file_text"], "héllo");
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                StreamEvent::ToolCallStarted {
+                    index: 0,
+                    name: "str_replace_based_edit_tool".to_string()
+                },
+                StreamEvent::ToolCallTarget {
+                    index: 0,
+                    name: "str_replace_based_edit_tool".to_string(),
+                    path: std::path::PathBuf::from("/repo/a.txt"),
+                },
+            ]
+        );
+    }
 }
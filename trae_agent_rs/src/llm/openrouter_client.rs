@@ -0,0 +1,171 @@
+//! # OpenRouter Client
+//!
+//! OpenRouter serves models of many vendors behind one OpenAI-compatible API and one key, with
+//! vendor-prefixed model names such as `anthropic/claude-sonnet-4`. The client is the OpenAI
+//! client pointed at `https://openrouter.ai/api/v1`, with a middleware layer adding the app
+//! attribution headers (`HTTP-Referer`, `X-Title`) and the `provider` routing preferences of
+//! the provider entry's `openrouter` settings.
+
+use super::base_client::{LLMClient, LLMError, LLMMessage, LLMResponse, ModelParameters, ToolChoice, ToolDefinition};
+use super::middleware::{LLMHttpRequest, LLMMiddleware};
+use super::streaming::StreamEvent;
+use super::OpenAIClient;
+use crate::config::{OpenRouterOptions, ProviderRouting};
+use async_trait::async_trait;
+use reqwest::header::{HeaderName, HeaderValue};
+use std::sync::Arc;
+use tracing::error;
+
+const DEFAULT_OPENROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
+/// Attribution sent when the provider entry does not set `site_url`.
+const DEFAULT_SITE_URL: &str = "https://github.com/sxhxliang/trae-agent";
+/// Attribution sent when the provider entry does not set `app_name`.
+const DEFAULT_APP_NAME: &str = "Trae Agent";
+
+/// Adds OpenRouter's attribution headers and routing preferences to every request.
+#[derive(Debug, Clone)]
+struct OpenRouterRequests {
+    referer: HeaderValue,
+    title: HeaderValue,
+    provider: Option<serde_json::Value>,
+}
+
+impl OpenRouterRequests {
+    fn new(options: &OpenRouterOptions) -> Result<Self, LLMError> {
+        let header = |value: Option<&String>, default: &str| {
+            HeaderValue::from_str(value.map_or(default, String::as_str))
+                .map_err(|e| LLMError::Other(format!("Invalid OpenRouter attribution header: {}", e)))
+        };
+        let provider = options
+            .provider
+            .as_ref()
+            .filter(|routing| **routing != ProviderRouting::default())
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(LLMError::ParsingError)?;
+        Ok(Self {
+            referer: header(options.site_url.as_ref(), DEFAULT_SITE_URL)?,
+            title: header(options.app_name.as_ref(), DEFAULT_APP_NAME)?,
+            provider,
+        })
+    }
+}
+
+#[async_trait]
+impl LLMMiddleware for OpenRouterRequests {
+    async fn on_request(&self, request: &mut LLMHttpRequest) -> Result<(), LLMError> {
+        request.headers.insert(HeaderName::from_static("http-referer"), self.referer.clone());
+        request.headers.insert(HeaderName::from_static("x-title"), self.title.clone());
+        if let (Some(provider), Some(body)) = (&self.provider, request.body.as_object_mut()) {
+            body.insert("provider".to_string(), provider.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Client for OpenRouter; see the module docs.
+#[derive(Debug)]
+pub struct OpenRouterClient {
+    inner: OpenAIClient,
+}
+
+#[async_trait]
+impl LLMClient for OpenRouterClient {
+    async fn new(
+        api_key: Option<String>,
+        base_url: Option<String>,
+        model_parameters: ModelParameters,
+    ) -> Result<Self, LLMError> {
+        let Some(key) = api_key.or_else(|| std::env::var("OPENROUTER_API_KEY").ok()) else {
+            error!("OpenRouter API key not provided and not found in OPENROUTER_API_KEY env var.");
+            return Err(LLMError::NoApiKey);
+        };
+        let base_url = base_url.unwrap_or_else(|| DEFAULT_OPENROUTER_API_BASE.to_string());
+        let requests = OpenRouterRequests::new(&model_parameters.openrouter.clone().unwrap_or_default())?;
+        let inner = OpenAIClient::new(Some(key), Some(base_url), model_parameters)
+            .await?
+            .with_middleware(Arc::new(requests));
+        Ok(Self { inner })
+    }
+
+    async fn chat(
+        &self,
+        messages: Vec<LLMMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+    ) -> Result<LLMResponse, LLMError> {
+        self.inner.chat(messages, tools, tool_choice).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<LLMMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        on_event: &(dyn Fn(StreamEvent) + Send + Sync),
+    ) -> Result<LLMResponse, LLMError> {
+        self.inner.chat_stream(messages, tools, tool_choice, on_event).await
+    }
+
+    // `chat_structured` keeps the default, prompt-based implementation: not every model behind
+    // OpenRouter honours OpenAI's `response_format`.
+
+    fn get_provider_name(&self) -> String {
+        "openrouter".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::MessageRole;
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, bearer_token, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_requests_carry_attribution_headers_and_provider_routing() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(bearer_token("or-key"))
+            .and(header("HTTP-Referer", "https://example.com/ci"))
+            .and(header("X-Title", DEFAULT_APP_NAME))
+            .and(body_partial_json(json!({
+                "model": "anthropic/claude-sonnet-4",
+                "provider": {"order": ["Anthropic", "Google"], "allow_fallbacks": false},
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "gen-1",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "anthropic/claude-sonnet-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hi"}, "finish_reason": "stop"}],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let params: ModelParameters = serde_json::from_value(json!({
+            "model": "anthropic/claude-sonnet-4",
+            "openrouter": {
+                "site_url": "https://example.com/ci",
+                "provider": {"order": ["Anthropic", "Google"], "allow_fallbacks": false},
+            },
+        }))
+        .unwrap();
+        let client = OpenRouterClient::new(Some("or-key".to_string()), Some(server.uri()), params)
+            .await
+            .unwrap();
+        let message = LLMMessage {
+            role: MessageRole::User,
+            content: Some("Hello".to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        };
+        let response = client.chat(vec![message], None, None).await.unwrap();
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("Hi"));
+        assert_eq!(client.get_provider_name(), "openrouter");
+    }
+}
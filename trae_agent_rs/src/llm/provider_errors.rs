@@ -0,0 +1,73 @@
+//! # Provider Error Bodies
+//!
+//! OpenAI and Anthropic describe failed requests with a JSON error body. The common failures
+//! are turned into typed `LLMError` variants, each with a remediation hint (`LLMError::hint`)
+//! the CLI prints, instead of surfacing the raw response:
+//!
+//! - OpenAI: `{"error": {"message": ..., "type": ..., "code": "context_length_exceeded"}}`
+//! - Anthropic: `{"type": "error", "error": {"type": "overloaded_error", "message": ...}}`
+
+use super::base_client::LLMError;
+use reqwest::StatusCode;
+use serde_json::Value;
+
+/// Converts a non-success response into the most specific `LLMError` its status and body
+/// allow; unrecognized failures become `LLMError::ApiError` with the raw body.
+pub fn error_from_response(status: u16, body: &str) -> LLMError {
+    let parsed: Option<Value> = serde_json::from_str(body).ok();
+    let error = parsed.as_ref().and_then(|v| v.get("error"));
+    let field = |name: &str| error.and_then(|e| e.get(name)).and_then(Value::as_str).unwrap_or("");
+    let (code, kind, message) = (field("code"), field("type"), field("message"));
+    let message = if message.is_empty() { body.trim().to_string() } else { message.to_string() };
+    let lower = message.to_lowercase();
+
+    if code == "invalid_api_key" || kind == "authentication_error" || status == 401 {
+        LLMError::InvalidApiKey(message)
+    } else if code == "context_length_exceeded"
+        || lower.contains("maximum context length")
+        || lower.contains("prompt is too long")
+    {
+        LLMError::ContextLengthExceeded(message)
+    } else if code == "insufficient_quota" || kind == "insufficient_quota" {
+        LLMError::QuotaExceeded(message)
+    } else if kind == "overloaded_error" || status == 529 || status == 503 || lower.contains("overloaded") {
+        LLMError::Overloaded(message)
+    } else if status == 429 || kind == "rate_limit_error" || code == "rate_limit_exceeded" {
+        LLMError::RateLimited(message)
+    } else {
+        LLMError::ApiError(format!(
+            "API request failed with status {}: {}",
+            StatusCode::from_u16(status).map_or_else(|_| status.to_string(), |s| s.to_string()),
+            body
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_bodies_become_typed_errors_with_hints() {
+        let openai_context = r#"{"error": {"message": "This model's maximum context length is 128000 tokens.", "type": "invalid_request_error", "param": "messages", "code": "context_length_exceeded"}}"#;
+        let err = error_from_response(400, openai_context);
+        assert!(matches!(&err, LLMError::ContextLengthExceeded(m) if m.starts_with("This model's maximum context length")));
+        assert!(err.hint().unwrap().contains("max_tokens"));
+
+        let openai_key = r#"{"error": {"message": "Incorrect API key provided: sk-abc.", "type": "invalid_request_error", "code": "invalid_api_key"}}"#;
+        assert!(matches!(error_from_response(401, openai_key), LLMError::InvalidApiKey(_)));
+
+        let anthropic_overloaded = r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#;
+        let err = error_from_response(529, anthropic_overloaded);
+        assert!(matches!(&err, LLMError::Overloaded(m) if m == "Overloaded"));
+        assert!(err.hint().is_some());
+
+        let quota = r#"{"error": {"message": "You exceeded your current quota.", "type": "insufficient_quota", "code": "insufficient_quota"}}"#;
+        assert!(matches!(error_from_response(429, quota), LLMError::QuotaExceeded(_)));
+        assert!(matches!(error_from_response(429, "slow down"), LLMError::RateLimited(m) if m == "slow down"));
+
+        let other = error_from_response(500, "<html>oops</html>");
+        assert!(matches!(&other, LLMError::ApiError(m) if m == "API request failed with status 500 Internal Server Error: <html>oops</html>"));
+        assert!(other.hint().is_none());
+    }
+}
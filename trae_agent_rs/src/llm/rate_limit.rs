@@ -0,0 +1,410 @@
+//! # Rate Limits
+//!
+//! Parses the rate-limit headers returned by LLM providers and paces subsequent requests,
+//! so batch and evaluation runs wait for the limit window to reset instead of repeatedly
+//! running into HTTP 429 responses.
+//!
+//! Both OpenAI-style (`x-ratelimit-remaining-requests`, reset as a duration such as `6m0s`)
+//! and Anthropic-style (`anthropic-ratelimit-requests-remaining`, reset as an RFC 3339
+//! timestamp) headers are understood, as well as the standard `retry-after` header.
+//!
+//! Providers can also be given client-side quotas (`requests_per_minute`, `tokens_per_minute`):
+//! a `QuotaLimiter` shared by every client of the process that talks to the same endpoint and
+//! model holds requests back before the provider's own limits are reached, which keeps many
+//! concurrent runs from tripping them at once.
+
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+use tracing::info;
+
+/// Longest wait the pacer will impose before a request, to guard against bogus headers.
+const MAX_PACING_WAIT: Duration = Duration::from_secs(120);
+
+/// Rate-limit state reported by a provider alongside a response.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitInfo {
+    /// Maximum requests allowed in the current window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_requests: Option<u64>,
+    /// Requests left in the current window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_requests: Option<u64>,
+    /// Time until the request window resets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_requests: Option<Duration>,
+    /// Maximum tokens allowed in the current window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_tokens: Option<u64>,
+    /// Tokens left in the current window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_tokens: Option<u64>,
+    /// Time until the token window resets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_tokens: Option<Duration>,
+    /// Explicit wait requested by the provider (`retry-after`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    /// Extracts rate-limit information from response headers.
+    ///
+    /// # Returns
+    /// `None` if the response carries no recognised rate-limit header.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let number = |names: [&str; 2]| names.iter().find_map(|n| get(n)?.trim().parse().ok());
+        let reset = |openai: &str, anthropic: &str| {
+            get(openai)
+                .and_then(parse_reset_duration)
+                .or_else(|| get(anthropic).and_then(parse_reset_timestamp))
+        };
+
+        let info = RateLimitInfo {
+            limit_requests: number([
+                "x-ratelimit-limit-requests",
+                "anthropic-ratelimit-requests-limit",
+            ]),
+            remaining_requests: number([
+                "x-ratelimit-remaining-requests",
+                "anthropic-ratelimit-requests-remaining",
+            ]),
+            reset_requests: reset(
+                "x-ratelimit-reset-requests",
+                "anthropic-ratelimit-requests-reset",
+            ),
+            limit_tokens: number([
+                "x-ratelimit-limit-tokens",
+                "anthropic-ratelimit-tokens-limit",
+            ]),
+            remaining_tokens: number([
+                "x-ratelimit-remaining-tokens",
+                "anthropic-ratelimit-tokens-remaining",
+            ]),
+            reset_tokens: reset("x-ratelimit-reset-tokens", "anthropic-ratelimit-tokens-reset"),
+            retry_after: get("retry-after")
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .map(Duration::from_secs_f64),
+        };
+        (info != RateLimitInfo::default()).then_some(info)
+    }
+
+    /// How long to wait before the next request, measured from when these headers were
+    /// received: the `retry-after` value if present, otherwise the reset time of any
+    /// exhausted (remaining = 0) window.
+    pub fn wait_before_next_request(&self) -> Option<Duration> {
+        if self.retry_after.is_some() {
+            return self.retry_after;
+        }
+        let requests = self
+            .reset_requests
+            .filter(|_| self.remaining_requests == Some(0));
+        let tokens = self.reset_tokens.filter(|_| self.remaining_tokens == Some(0));
+        requests.max(tokens)
+    }
+}
+
+/// Parses an OpenAI-style reset duration such as `1s`, `6m0s`, `1h2m3.5s` or `20ms`.
+pub fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0_f64;
+    let mut number = String::new();
+    let mut chars = value.trim().chars().peekable();
+    let mut parsed_any = false;
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let amount: f64 = number.parse().ok()?;
+        number.clear();
+        let unit_secs = match c {
+            'h' => 3600.0,
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                0.001
+            }
+            'm' => 60.0,
+            's' => 1.0,
+            _ => return None,
+        };
+        total += amount * unit_secs;
+        parsed_any = true;
+    }
+    if !number.is_empty() {
+        // A bare number is taken as seconds.
+        total += number.parse::<f64>().ok()?;
+        parsed_any = true;
+    }
+    parsed_any.then(|| Duration::from_secs_f64(total))
+}
+
+/// Parses an RFC 3339 reset timestamp into the time remaining until it.
+fn parse_reset_timestamp(value: &str) -> Option<Duration> {
+    let reset_ms = crate::utils::trajectory_import::parse_iso_timestamp_ms(value.trim())?;
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+    Some(Duration::from_millis(reset_ms.saturating_sub(now_ms)))
+}
+
+/// Remembers the latest rate-limit state of a client and delays requests while a limit
+/// window is exhausted.
+#[derive(Debug, Default)]
+pub struct RateLimitPacer {
+    not_before: Mutex<Option<Instant>>,
+}
+
+impl RateLimitPacer {
+    /// Records the rate-limit state from the latest response.
+    pub fn observe(&self, info: &RateLimitInfo) {
+        let next = info
+            .wait_before_next_request()
+            .filter(|wait| !wait.is_zero())
+            .map(|wait| Instant::now() + wait.min(MAX_PACING_WAIT));
+        *self.not_before.lock().unwrap() = next;
+    }
+
+    /// Sleeps until the provider's limit window allows another request.
+    pub async fn wait_for_capacity(&self) {
+        let not_before = *self.not_before.lock().unwrap();
+        if let Some(deadline) = not_before {
+            let now = Instant::now();
+            if deadline > now {
+                info!(
+                    "Rate limit exhausted; pausing {:.1}s before the next LLM request",
+                    (deadline - now).as_secs_f64()
+                );
+                tokio::time::sleep_until(deadline).await;
+            }
+        }
+    }
+}
+
+/// Length of the quota window.
+const QUOTA_WINDOW: Duration = Duration::from_secs(60);
+
+/// Client-side requests-per-minute and tokens-per-minute quotas over a sliding window.
+#[derive(Debug)]
+pub struct QuotaLimiter {
+    requests_per_minute: Option<u32>,
+    tokens_per_minute: Option<u64>,
+    window: Mutex<QuotaWindow>,
+}
+
+#[derive(Debug, Default)]
+struct QuotaWindow {
+    /// Requests of the last minute: id, when it was sent and its tokens.
+    requests: VecDeque<(u64, Instant, u64)>,
+    next_id: u64,
+}
+
+/// A request counted against a `QuotaLimiter`, with its estimated tokens until `settle`.
+#[derive(Debug)]
+pub struct QuotaTicket {
+    limiter: Arc<QuotaLimiter>,
+    id: u64,
+}
+
+impl QuotaTicket {
+    /// Replaces the request's estimated tokens with those it actually used.
+    pub fn settle(self, tokens: u64) {
+        let mut window = self.limiter.window.lock().unwrap();
+        if let Some(request) = window.requests.iter_mut().find(|(id, _, _)| *id == self.id) {
+            request.2 = tokens;
+        }
+    }
+}
+
+/// The limiters of the process, by endpoint and model.
+static LIMITERS: OnceLock<Mutex<HashMap<String, Arc<QuotaLimiter>>>> = OnceLock::new();
+
+impl QuotaLimiter {
+    pub fn new(requests_per_minute: Option<u32>, tokens_per_minute: Option<u64>) -> Self {
+        Self {
+            requests_per_minute,
+            tokens_per_minute,
+            window: Mutex::new(QuotaWindow::default()),
+        }
+    }
+
+    /// The limiter shared by all clients of `base_url` and `model`, or `None` without quotas.
+    /// The first client to ask sets the quotas.
+    pub fn shared(
+        base_url: &str,
+        model: &str,
+        requests_per_minute: Option<u32>,
+        tokens_per_minute: Option<u64>,
+    ) -> Option<Arc<Self>> {
+        if requests_per_minute.is_none() && tokens_per_minute.is_none() {
+            return None;
+        }
+        let limiters = LIMITERS.get_or_init(Default::default);
+        let mut limiters = limiters.lock().unwrap();
+        let limiter = limiters
+            .entry(format!("{} {}", base_url.trim_end_matches('/'), model))
+            .or_insert_with(|| Arc::new(Self::new(requests_per_minute, tokens_per_minute)));
+        Some(limiter.clone())
+    }
+
+    /// Waits until a request of `estimated_tokens` fits in both quotas, then counts it. A
+    /// request larger than the whole token quota is let through once the window is empty.
+    pub async fn acquire(self: &Arc<Self>, estimated_tokens: u64) -> QuotaTicket {
+        let mut announced = false;
+        loop {
+            let wait = {
+                let mut window = self.window.lock().unwrap();
+                let now = Instant::now();
+                while window.requests.front().is_some_and(|(_, sent, _)| now.duration_since(*sent) >= QUOTA_WINDOW) {
+                    window.requests.pop_front();
+                }
+                match self.wait_for(&window.requests, estimated_tokens, now) {
+                    None => {
+                        let id = window.next_id;
+                        window.next_id += 1;
+                        window.requests.push_back((id, now, estimated_tokens));
+                        return QuotaTicket { limiter: self.clone(), id };
+                    }
+                    Some(wait) => wait,
+                }
+            };
+            if !announced {
+                info!("Client-side rate quota reached; pausing {:.1}s before the next LLM request", wait.as_secs_f64());
+                announced = true;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// How long until a request of `tokens` fits, or `None` if it fits now.
+    fn wait_for(&self, requests: &VecDeque<(u64, Instant, u64)>, tokens: u64, now: Instant) -> Option<Duration> {
+        // Index of the oldest request that has to leave the window first.
+        let mut must_expire: Option<usize> = None;
+        if let Some(rpm) = self.requests_per_minute {
+            let rpm = rpm.max(1) as usize;
+            if requests.len() >= rpm {
+                must_expire = Some(requests.len() - rpm);
+            }
+        }
+        if let Some(tpm) = self.tokens_per_minute {
+            let mut used: u64 = requests.iter().map(|(_, _, t)| t).sum();
+            let mut index = 0;
+            while used + tokens > tpm && index < requests.len() {
+                used -= requests[index].2;
+                index += 1;
+            }
+            if index > 0 {
+                must_expire = must_expire.max(Some(index - 1));
+            }
+        }
+        must_expire.map(|index| (requests[index].1 + QUOTA_WINDOW).saturating_duration_since(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_parse_reset_duration() {
+        assert_eq!(parse_reset_duration("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_reset_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset_duration("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(
+            parse_reset_duration("1h2m3.5s"),
+            Some(Duration::from_secs_f64(3723.5))
+        );
+        assert_eq!(parse_reset_duration("abc"), None);
+    }
+
+    #[test]
+    fn test_from_openai_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit-requests", HeaderValue::from_static("500"));
+        headers.insert("x-ratelimit-remaining-requests", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset-requests", HeaderValue::from_static("12s"));
+        headers.insert("x-ratelimit-remaining-tokens", HeaderValue::from_static("9000"));
+        headers.insert("x-ratelimit-reset-tokens", HeaderValue::from_static("30s"));
+
+        let info = RateLimitInfo::from_headers(&headers).unwrap();
+        assert_eq!(info.limit_requests, Some(500));
+        assert_eq!(info.remaining_tokens, Some(9000));
+        // Only the exhausted request window counts.
+        assert_eq!(info.wait_before_next_request(), Some(Duration::from_secs(12)));
+
+        assert!(RateLimitInfo::from_headers(&HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_from_anthropic_headers_and_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "anthropic-ratelimit-tokens-remaining",
+            HeaderValue::from_static("0"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-tokens-reset",
+            HeaderValue::from_static("2000-01-01T00:00:00Z"),
+        );
+        let info = RateLimitInfo::from_headers(&headers).unwrap();
+        // A reset time in the past means there is nothing to wait for.
+        assert_eq!(info.wait_before_next_request(), Some(Duration::ZERO));
+
+        headers.insert("retry-after", HeaderValue::from_static("7"));
+        let info = RateLimitInfo::from_headers(&headers).unwrap();
+        assert_eq!(info.wait_before_next_request(), Some(Duration::from_secs(7)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pacer_waits_for_reset() {
+        let pacer = RateLimitPacer::default();
+        pacer.observe(&RateLimitInfo {
+            remaining_requests: Some(0),
+            reset_requests: Some(Duration::from_secs(5)),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        pacer.wait_for_capacity().await;
+        assert!(start.elapsed() >= Duration::from_secs(5));
+
+        pacer.observe(&RateLimitInfo {
+            remaining_requests: Some(10),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        pacer.wait_for_capacity().await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_quota_limiter_holds_requests_within_quotas() {
+        let limiter = Arc::new(QuotaLimiter::new(Some(2), Some(1_000)));
+        let start = Instant::now();
+        limiter.acquire(100).await;
+        limiter.acquire(100).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        // A third request within the minute waits for the first to leave the window.
+        limiter.acquire(100).await;
+        assert_eq!(start.elapsed().as_secs(), 60);
+
+        // Settled usage counts instead of the estimate.
+        let limiter = Arc::new(QuotaLimiter::new(None, Some(1_000)));
+        let start = Instant::now();
+        limiter.acquire(900).await.settle(200);
+        limiter.acquire(700).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        limiter.acquire(300).await;
+        assert_eq!(start.elapsed().as_secs(), 60);
+        // Larger than the whole quota: sent once the window is empty.
+        limiter.acquire(5_000).await;
+        assert_eq!(start.elapsed().as_secs(), 120);
+
+        assert!(QuotaLimiter::shared("http://a", "m", None, None).is_none());
+        let first = QuotaLimiter::shared("http://a/", "m", Some(5), None).unwrap();
+        let second = QuotaLimiter::shared("http://a", "m", Some(9), None).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+}
@@ -0,0 +1,368 @@
+//! # Streaming Responses
+//!
+//! Assembles streamed chat completions (server-sent events in the OpenAI
+//! `chat.completion.chunk` format) back into an `LLMResponse`.
+//!
+//! Providers stream tool-call arguments as partial JSON fragments spread over many chunks.
+//! `ToolCallAssembler` concatenates those fragments per tool call and reports a
+//! `StreamEvent` as soon as a call's tool name is known, and again once the file it is
+//! about to write can be read from the incomplete arguments. This lets the agent show
+//! "writing file X…" and run preflight checks (path and write-guard validation) while the
+//! model is still generating the file content.
+
+use super::base_client::{
+    LLMMessage, LLMResponse, LLMResponseChoice, LLMUsage, MessageRole, ToolCall, ToolCallFunction,
+};
+use crate::utils::guards::write_target_for_tool_call;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Argument fields consulted to find a tool call's write target before its arguments are complete.
+const TARGET_FIELDS: [&str; 4] = ["command", "operation", "path", "file_path"];
+
+/// Progress reported while a response is being streamed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// The model started a tool call; its arguments are still being generated.
+    ToolCallStarted {
+        /// Position of the call in the response's tool call list.
+        index: usize,
+        /// Name of the tool being called.
+        name: String,
+    },
+    /// The file a tool call is going to write is known from its partial arguments.
+    ToolCallTarget {
+        /// Position of the call in the response's tool call list.
+        index: usize,
+        /// Name of the tool being called.
+        name: String,
+        /// The file the call will write.
+        path: PathBuf,
+    },
+}
+
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: String,
+    tool_type: String,
+    name: String,
+    arguments: String,
+    started: bool,
+    target_reported: bool,
+}
+
+/// Accumulates streamed tool-call deltas into complete `ToolCall`s.
+#[derive(Debug, Default)]
+pub struct ToolCallAssembler {
+    calls: BTreeMap<usize, PartialToolCall>,
+}
+
+impl ToolCallAssembler {
+    /// Applies the `delta.tool_calls` array of one stream chunk.
+    ///
+    /// # Returns
+    /// The events that became observable with this delta.
+    pub fn push(&mut self, deltas: &[Value]) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        for (position, delta) in deltas.iter().enumerate() {
+            let index = delta
+                .get("index")
+                .and_then(Value::as_u64)
+                .map_or(position, |i| i as usize);
+            let call = self.calls.entry(index).or_default();
+            if let Some(id) = delta.get("id").and_then(Value::as_str) {
+                call.id.push_str(id);
+            }
+            if let Some(tool_type) = delta.get("type").and_then(Value::as_str) {
+                call.tool_type = tool_type.to_string();
+            }
+            if let Some(function) = delta.get("function") {
+                if let Some(name) = function.get("name").and_then(Value::as_str) {
+                    call.name.push_str(name);
+                }
+                if let Some(fragment) = function.get("arguments").and_then(Value::as_str) {
+                    call.arguments.push_str(fragment);
+                }
+            }
+
+            // Providers send the tool name whole in the first delta of a call.
+            if !call.started && !call.name.is_empty() {
+                call.started = true;
+                events.push(StreamEvent::ToolCallStarted {
+                    index,
+                    name: call.name.clone(),
+                });
+            }
+            if call.started && !call.target_reported {
+                if let Some(path) = partial_write_target(&call.name, &call.arguments) {
+                    call.target_reported = true;
+                    events.push(StreamEvent::ToolCallTarget {
+                        index,
+                        name: call.name.clone(),
+                        path,
+                    });
+                }
+            }
+        }
+        events
+    }
+
+    /// Returns the assembled tool calls, ordered by index.
+    pub fn finish(self) -> Vec<ToolCall> {
+        self.calls
+            .into_values()
+            .map(|call| ToolCall {
+                id: call.id,
+                tool_type: if call.tool_type.is_empty() {
+                    "function".to_string()
+                } else {
+                    call.tool_type
+                },
+                function: ToolCallFunction {
+                    name: call.name,
+                    arguments: call.arguments,
+                },
+            })
+            .collect()
+    }
+}
+
+/// Determines the file a write tool call targets from possibly incomplete JSON arguments.
+fn partial_write_target(tool_name: &str, partial_arguments: &str) -> Option<PathBuf> {
+    let fields: Map<String, Value> = TARGET_FIELDS
+        .iter()
+        .filter_map(|field| {
+            partial_string_field(partial_arguments, field)
+                .map(|value| (field.to_string(), Value::String(value)))
+        })
+        .collect();
+    write_target_for_tool_call(tool_name, &Value::Object(fields))
+}
+
+/// Reads the value of a top-level string field from the beginning of a JSON object whose
+/// remainder has not been received yet.
+///
+/// # Returns
+/// The decoded string once its closing quote has arrived, otherwise `None`.
+pub fn partial_string_field(partial_json: &str, field: &str) -> Option<String> {
+    let key = format!("\"{}\"", field);
+    let mut search_from = 0;
+    while let Some(found) = partial_json[search_from..].find(&key) {
+        let after_key = search_from + found + key.len();
+        search_from = after_key;
+        let rest = partial_json[after_key..].trim_start();
+        let Some(rest) = rest.strip_prefix(':') else {
+            // The match was a string value, not a key.
+            continue;
+        };
+        let rest = rest.trim_start();
+        if !rest.starts_with('"') {
+            return None;
+        }
+        let mut escaped = false;
+        for (offset, c) in rest.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => return serde_json::from_str(&rest[..=offset]).ok(),
+                _ => {}
+            }
+        }
+        return None;
+    }
+    None
+}
+
+/// Splits a server-sent event stream into `data:` payloads, buffering incomplete lines.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: String,
+}
+
+impl SseDecoder {
+    /// Feeds a chunk of the response body.
+    ///
+    /// # Returns
+    /// The `data:` payloads completed by this chunk.
+    pub fn push(&mut self, chunk: &str) -> Vec<String> {
+        self.buffer.push_str(chunk);
+        let mut payloads = Vec::new();
+        while let Some(newline) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=newline).collect();
+            let line = line.trim_end_matches(['\r', '\n']);
+            if let Some(data) = line.strip_prefix("data:") {
+                payloads.push(data.trim_start().to_string());
+            }
+        }
+        payloads
+    }
+}
+
+/// Builds an `LLMResponse` from the chunks of a streamed chat completion.
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    id: String,
+    model: String,
+    created: u64,
+    content: String,
+    finish_reason: Option<String>,
+    usage: Option<LLMUsage>,
+    tool_calls: ToolCallAssembler,
+}
+
+impl StreamAccumulator {
+    /// Applies one `chat.completion.chunk` object.
+    ///
+    /// # Returns
+    /// The events that became observable with this chunk.
+    pub fn push_chunk(&mut self, chunk: &Value) -> Vec<StreamEvent> {
+        if self.id.is_empty() {
+            if let Some(id) = chunk.get("id").and_then(Value::as_str) {
+                self.id = id.to_string();
+            }
+        }
+        if let Some(model) = chunk.get("model").and_then(Value::as_str) {
+            self.model = model.to_string();
+        }
+        if let Some(created) = chunk.get("created").and_then(Value::as_u64) {
+            self.created = created;
+        }
+        if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+            self.usage = serde_json::from_value(usage.clone()).ok();
+        }
+
+        let mut events = Vec::new();
+        let Some(choice) = chunk
+            .get("choices")
+            .and_then(Value::as_array)
+            .and_then(|choices| choices.first())
+        else {
+            return events;
+        };
+        if let Some(reason) = choice.get("finish_reason").and_then(Value::as_str) {
+            self.finish_reason = Some(reason.to_string());
+        }
+        if let Some(delta) = choice.get("delta") {
+            if let Some(content) = delta.get("content").and_then(Value::as_str) {
+                self.content.push_str(content);
+            }
+            if let Some(deltas) = delta.get("tool_calls").and_then(Value::as_array) {
+                events.extend(self.tool_calls.push(deltas));
+            }
+        }
+        events
+    }
+
+    /// Returns the complete response.
+    pub fn into_response(self) -> LLMResponse {
+        let tool_calls = self.tool_calls.finish();
+        LLMResponse {
+            id: self.id,
+            object: "chat.completion".to_string(),
+            created: self.created,
+            model: self.model,
+            choices: vec![LLMResponseChoice {
+                index: 0,
+                message: LLMMessage {
+                    role: MessageRole::Assistant,
+                    content: (!self.content.is_empty()).then_some(self.content),
+                    name: None,
+                    tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                    tool_call_id: None,
+                },
+                finish_reason: self.finish_reason,
+            }],
+            usage: self.usage,
+            rate_limit: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_partial_string_field() {
+        assert_eq!(
+            partial_string_field(r#"{"command": "create", "path": "/tmp/a.rs", "file_te"#, "path"),
+            Some("/tmp/a.rs".to_string())
+        );
+        assert_eq!(partial_string_field(r#"{"command": "create", "path": "/tmp/a"#, "path"), None);
+        assert_eq!(
+            partial_string_field(r#"{"note": "path", "path": "/tmp/\"q\".txt"}"#, "path"),
+            Some("/tmp/\"q\".txt".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assembler_reports_tool_and_target_before_arguments_complete() {
+        let mut assembler = ToolCallAssembler::default();
+        let events = assembler.push(&[json!({
+            "index": 0, "id": "call_1", "type": "function",
+            "function": {"name": "str_replace_based_edit_tool", "arguments": ""}
+        })]);
+        assert_eq!(
+            events,
+            vec![StreamEvent::ToolCallStarted {
+                index: 0,
+                name: "str_replace_based_edit_tool".to_string()
+            }]
+        );
+
+        let events = assembler.push(&[json!({"index": 0, "function": {"arguments": "{\"command\": \"cre"}})]);
+        assert!(events.is_empty());
+        let events = assembler.push(&[json!({
+            "index": 0, "function": {"arguments": "ate\", \"path\": \"/repo/src/lib.rs\", \"file_text\": \"fn"}
+        })]);
+        assert_eq!(
+            events,
+            vec![StreamEvent::ToolCallTarget {
+                index: 0,
+                name: "str_replace_based_edit_tool".to_string(),
+                path: PathBuf::from("/repo/src/lib.rs"),
+            }]
+        );
+        let events = assembler.push(&[json!({"index": 0, "function": {"arguments": " main() {}\"}"}})]);
+        assert!(events.is_empty());
+
+        let calls = assembler.finish();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call_1");
+        let args: Value = serde_json::from_str(&calls[0].function.arguments).unwrap();
+        assert_eq!(args["file_text"], "fn main() {}");
+    }
+
+    #[test]
+    fn test_view_commands_have_no_target() {
+        let mut assembler = ToolCallAssembler::default();
+        let events = assembler.push(&[json!({
+            "index": 0, "id": "call_1",
+            "function": {"name": "str_replace_based_edit_tool", "arguments": "{\"command\": \"view\", \"path\": \"/repo/a.rs\"}"}
+        })]);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], StreamEvent::ToolCallStarted { .. }));
+    }
+
+    #[test]
+    fn test_sse_decoder_and_accumulator() {
+        let mut decoder = SseDecoder::default();
+        let mut payloads = decoder.push("data: {\"id\":\"c1\",\"model\":\"m\",\"created\":5,\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\ndata: {\"choices\":[{\"del");
+        payloads.extend(decoder.push("ta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\r\n\r\ndata: [DONE]\n\n"));
+        assert_eq!(payloads.len(), 3);
+        assert_eq!(payloads[2], "[DONE]");
+
+        let mut accumulator = StreamAccumulator::default();
+        for payload in &payloads[..2] {
+            accumulator.push_chunk(&serde_json::from_str(payload).unwrap());
+        }
+        let response = accumulator.into_response();
+        assert_eq!(response.id, "c1");
+        assert_eq!(response.created, 5);
+        assert_eq!(response.choices[0].message.content.as_deref(), Some("Hello"));
+        assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
+        assert!(response.choices[0].message.tool_calls.is_none());
+    }
+}
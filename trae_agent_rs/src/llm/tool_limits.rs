@@ -0,0 +1,135 @@
+//! # Provider Tool Limits
+//!
+//! Providers reject requests whose tool list breaks their constraints (too many tools, names
+//! that are too long or contain unsupported characters, oversized schemas) with an opaque
+//! `400`. Clients check the tool list against these limits before sending it: too many tools
+//! are cut down to the limit with a warning, anything else is reported as a configuration error.
+
+use super::base_client::{LLMError, ToolDefinition};
+use tracing::warn;
+
+/// Tool-list constraints of one provider.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToolLimits {
+    /// Provider name used in error messages.
+    pub provider: &'static str,
+    /// Maximum number of tools per request.
+    pub max_tools: usize,
+    /// Maximum length of a tool name; names may only use ASCII letters, digits, `_` and `-`.
+    pub max_name_len: usize,
+    /// Maximum size of one tool's serialized definition, in bytes.
+    pub max_schema_bytes: usize,
+}
+
+impl ToolLimits {
+    pub const OPENAI: ToolLimits = ToolLimits {
+        provider: "openai",
+        max_tools: 128,
+        max_name_len: 64,
+        max_schema_bytes: 32 * 1024,
+    };
+
+    pub const ANTHROPIC: ToolLimits = ToolLimits {
+        provider: "anthropic",
+        max_tools: 128,
+        max_name_len: 64,
+        max_schema_bytes: 32 * 1024,
+    };
+
+    /// Checks `tools` against the limits.
+    ///
+    /// # Returns
+    /// The tools to send: all of them, or, if there are more than `max_tools`, the first
+    /// `max_tools` in name order. An `LLMError::ToolConfig` if a tool's name or definition
+    /// cannot be sent to the provider at all.
+    pub fn enforce(&self, tools: Option<Vec<ToolDefinition>>) -> Result<Option<Vec<ToolDefinition>>, LLMError> {
+        let Some(mut tools) = tools else {
+            return Ok(None);
+        };
+        for tool in &tools {
+            let name = &tool.function.name;
+            let valid_chars = name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if name.is_empty() || name.len() > self.max_name_len || !valid_chars {
+                return Err(LLMError::ToolConfig(format!(
+                    "tool name '{}' is not accepted by {}: names must be 1-{} characters of letters, digits, '_' or '-'",
+                    name, self.provider, self.max_name_len
+                )));
+            }
+            let size = serde_json::to_vec(tool).map_err(LLMError::ParsingError)?.len();
+            if size > self.max_schema_bytes {
+                return Err(LLMError::ToolConfig(format!(
+                    "the definition of tool '{}' is {} bytes, over the {} limit of {} bytes; shorten its description or parameters",
+                    name, size, self.provider, self.max_schema_bytes
+                )));
+            }
+        }
+        if tools.len() > self.max_tools {
+            // Sorted so the same tools are kept on every request.
+            tools.sort_by(|a, b| a.function.name.cmp(&b.function.name));
+            let dropped: Vec<String> = tools
+                .drain(self.max_tools..)
+                .map(|tool| tool.function.name)
+                .collect();
+            warn!(
+                "{} tools are registered but {} accepts at most {} per request; not sending: {}",
+                self.max_tools + dropped.len(),
+                self.provider,
+                self.max_tools,
+                dropped.join(", ")
+            );
+        }
+        Ok(Some(tools))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::{FunctionDefinition, FunctionParameters};
+    use std::collections::HashMap;
+
+    fn tool(name: &str, description: &str) -> ToolDefinition {
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: FunctionParameters {
+                    param_type: "object".to_string(),
+                    properties: HashMap::new(),
+                    required: vec![],
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_too_many_tools_are_truncated_deterministically() {
+        let limits = ToolLimits {
+            max_tools: 2,
+            ..ToolLimits::OPENAI
+        };
+        let tools = vec![tool("zeta", ""), tool("alpha", ""), tool("mid", "")];
+        let kept = limits.enforce(Some(tools)).unwrap().unwrap();
+        let names: Vec<&str> = kept.iter().map(|t| t.function.name.as_str()).collect();
+        assert_eq!(names, vec!["alpha", "mid"]);
+        assert!(limits.enforce(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_tools_are_configuration_errors() {
+        let limits = ToolLimits::OPENAI;
+        let err = limits
+            .enforce(Some(vec![tool("server.read file", "")]))
+            .unwrap_err();
+        assert!(matches!(err, LLMError::ToolConfig(ref m) if m.contains("'server.read file'")));
+        assert!(limits.enforce(Some(vec![tool(&"a".repeat(65), "")])).is_err());
+
+        let err = limits
+            .enforce(Some(vec![tool("big", &"x".repeat(40 * 1024))]))
+            .unwrap_err();
+        assert!(err.to_string().contains("over the openai limit"), "{}", err);
+    }
+}
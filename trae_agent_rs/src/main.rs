@@ -8,52 +8,67 @@ mod agent;
 mod cli;
 mod config;
 mod llm;
+mod recipes;
 mod tools;
 mod utils; // Add this line
 
 use clap::Parser;
+use futures::FutureExt;
 use cli::{Cli, Commands};
-use tracing_subscriber::{fmt, EnvFilter};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing subscriber
-    // You can control the log level using the RUST_LOG environment variable.
-    // For example: RUST_LOG=trae_rust_agent=debug,info
-    // This sets the default level to info, and debug for the trae_rust_agent crate.
-    fmt::Subscriber::builder()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .with_writer(std::io::stderr) // Log to stderr
-        .init();
-
     let cli_args = Cli::parse();
 
-    match cli_args.command {
-        Commands::Run(args) => {
-            if let Err(e) = cli::handle_run(args).await {
-                eprintln!("Error running task: {:?}", e);
-                std::process::exit(1);
-            }
-        }
-        Commands::Interactive(args) => {
-            if let Err(e) = cli::handle_interactive(args).await {
-                eprintln!("Error in interactive session: {:?}", e);
-                std::process::exit(1);
-            }
+    // Diagnostics go to stderr (and optionally a rotating log file); stdout is reserved for
+    // command output. RUST_LOG, when set, overrides the level chosen by --quiet/--verbose.
+    // The guard flushes the log file on exit, so it is dropped explicitly before `process::exit`.
+    let log_guard = utils::logging::init(&utils::logging::LogOptions {
+        quiet: cli_args.quiet,
+        verbose: cli_args.verbose,
+        log_file: cli_args.log_file.clone(),
+        no_color: cli_args.no_color,
+    })?;
+    if cli_args.no_color {
+        utils::highlight::disable_color();
+    }
+    utils::crash::install_panic_hook();
+
+    // A panic ends the run only if it reaches here; panics tokio recovers from as a `JoinError`
+    // in a spawned task are not crashes.
+    let run = std::panic::AssertUnwindSafe(async move {
+        match cli_args.command {
+            Commands::Run(args) => ("Error running task", cli::handle_run(args).await),
+            Commands::Interactive(args) => ("Error in interactive session", cli::handle_interactive(args).await),
+            Commands::ShowConfig(args) => ("Error showing config", cli::handle_show_config(args).await),
+            Commands::Tools(args) => ("Error showing tools", cli::handle_tools_command(args).await),
+            Commands::Upgrade(args) => ("Error upgrading dependency", cli::handle_upgrade(args).await),
+            Commands::Refactor(args) => ("Error running refactor", cli::handle_refactor(args).await),
+            Commands::ExplainDiff(args) => ("Error explaining diff", cli::handle_explain_diff(args).await),
+            Commands::ImportTrajectory(args) => ("Error importing trajectory", cli::handle_import_trajectory(args).await),
+            Commands::Replay(args) => ("Error replaying trajectory", cli::handle_replay(args).await),
+            Commands::Summarize(args) => ("Error summarizing trajectory", cli::handle_summarize(args).await),
+            Commands::Examples(args) => ("Error showing examples", cli::handle_examples(args).await),
+            Commands::SelfUpdate(args) => ("Error updating trae", cli::handle_self_update(args).await),
+            Commands::Runs(command) => ("Error listing runs", cli::handle_runs(command).await),
+            Commands::Config(command) => ("Error validating config", cli::handle_config(command).await),
+            Commands::McpServe(args) => ("Error serving MCP", cli::handle_mcp_serve(args).await),
+            Commands::Auth(command) => ("Error managing credentials", cli::handle_auth(command).await),
         }
-        Commands::ShowConfig(args) => {
-            if let Err(e) = cli::handle_show_config(args).await {
-                eprintln!("Error showing config: {:?}", e);
-                std::process::exit(1);
-            }
+    })
+    .catch_unwind()
+    .await;
+    match run {
+        Ok((_, Ok(()))) => {}
+        Ok((label, Err(e))) => {
+            eprintln!("{}: {:?}", label, e);
+            drop(log_guard);
+            std::process::exit(1);
         }
-        Commands::Tools(args) => {
-            if let Err(e) = cli::handle_tools_command(args).await { // Corrected path
-                eprintln!("Error showing tools: {:?}", e);
-                std::process::exit(1);
-            }
+        Err(payload) => {
+            utils::crash::report_crash(payload.as_ref());
+            drop(log_guard);
+            std::process::exit(101);
         }
     }
 
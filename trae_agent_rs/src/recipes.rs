@@ -0,0 +1,230 @@
+//! # Recipes Registry
+//!
+//! A catalogue of copy-pasteable invocations for common workflows. The registry is the
+//! single source for the `trae examples` command and for the "Examples" section shown in
+//! each subcommand's `--help` output.
+
+/// A named, documented example invocation.
+#[derive(Debug, Clone, Copy)]
+pub struct Recipe {
+    /// Short identifier, used to select a recipe with `trae examples <name>`.
+    pub name: &'static str,
+    /// The subcommand this recipe demonstrates (e.g., "run").
+    pub subcommand: &'static str,
+    /// One-line title.
+    pub title: &'static str,
+    /// Longer explanation of when and why to use the recipe.
+    pub description: &'static str,
+    /// The command line, ready to paste into a shell.
+    pub command: &'static str,
+}
+
+const RECIPES: &[Recipe] = &[
+    Recipe {
+        name: "fix-issue",
+        subcommand: "run",
+        title: "Fix an issue in a local repository",
+        description: "Describe the bug or feature in plain language and point the agent at the project.",
+        command: "trae run \"Fix the panic in parse_config when the file is empty\" --working-dir /path/to/repo",
+    },
+    Recipe {
+        name: "swe-bench-patch",
+        subcommand: "run",
+        title: "Generate a patch for a SWE-bench instance",
+        description: "Require a non-test patch before the agent may finish, diff against the instance's base commit, and save the patch and trajectory for evaluation.",
+        command: "trae run --task-file problem_statement.txt --working-dir /testbed --must-patch --base-commit <base_sha> --patch-path model.patch --trajectory-file trajectory.json",
+    },
+    Recipe {
+        name: "other-provider",
+        subcommand: "run",
+        title: "Use a different provider and model",
+        description: "Override the provider and model from the config file for a single run.",
+        command: "trae run \"Add unit tests for the cache module\" --provider anthropic --model claude-sonnet-4-20250514",
+    },
+    Recipe {
+        name: "json-output",
+        subcommand: "run",
+        title: "Consume the result from a script",
+        description: "Print the result as a single JSON document on stdout; progress and logs stay on stderr, and detailed logs go to a daily-rotated file.",
+        command: "trae --quiet --log-file logs/trae.log run \"Fix the failing test in tests/api.rs\" --output json | jq .success",
+    },
+    Recipe {
+        name: "commit-fix",
+        subcommand: "run",
+        title: "Commit a verified fix on its own branch",
+        description: "After a successful run, commit the changes on a new trae/... branch, with the agent's summary as the commit body.",
+        command: "trae run \"Fix the panic in parse_config\" --must-patch --commit-on-success \"fix: {summary}\"",
+    },
+    Recipe {
+        name: "setup-env",
+        subcommand: "run",
+        title: "Install dependencies before the agent starts",
+        description: "Run setup commands once in the project (in addition to `setup_commands` from the config); the agent is told what succeeded and what failed.",
+        command: "trae run \"Fix the failing test in tests/test_api.py\" --setup-command \"pip install -e .\" --setup-command \"pip install pytest\"",
+    },
+    Recipe {
+        name: "offline",
+        subcommand: "run",
+        title: "Run against a local model with no other network access",
+        description: "Every LLM endpoint must be local: here local_config.json points the openai provider's base_url at an OpenAI-compatible server such as Ollama (http://localhost:11434/v1). Tools that need the network are removed and shell commands run without network where the system supports it.",
+        command: "trae run \"Fix the failing test in tests/test_api.py\" --config-file local_config.json --offline",
+    },
+    Recipe {
+        name: "explore",
+        subcommand: "interactive",
+        title: "Explore a codebase interactively",
+        description: "Start a conversational session to ask questions about the code and request small changes step by step.",
+        command: "trae interactive --provider openai --max-steps 30",
+    },
+    Recipe {
+        name: "python-config",
+        subcommand: "show-config",
+        title: "Inspect a config shared with the Python implementation",
+        description: "Python-style YAML configs are accepted wherever a JSON config is.",
+        command: "trae show-config --config-file trae_config.yaml",
+    },
+    Recipe {
+        name: "upgrade-dependency",
+        subcommand: "upgrade",
+        title: "Upgrade a dependency and fix the fallout",
+        description: "Bump the version in Cargo.toml, then let the agent fix compile errors, touching only the failing files and the manifest.",
+        command: "trae upgrade --package serde --to 1.0.200 --working-dir /path/to/repo",
+    },
+    Recipe {
+        name: "rename-symbol",
+        subcommand: "refactor",
+        title: "Rename a symbol across the codebase",
+        description: "Uses the project's language server when installed and falls back to the agent otherwise.",
+        command: "trae refactor --rename OldName=NewName --working-dir /path/to/repo",
+    },
+    Recipe {
+        name: "review-commits",
+        subcommand: "explain-diff",
+        title: "Explain the last three commits",
+        description: "Get a per-hunk explanation with risk notes before reviewing or merging.",
+        command: "trae explain-diff --range HEAD~3..",
+    },
+    Recipe {
+        name: "import-python-trajectory",
+        subcommand: "import-trajectory",
+        title: "Convert a trajectory from the Python implementation",
+        description: "Rewrites a Python trajectory file in the Rust recorder's schema.",
+        command: "trae import-trajectory trajectories/trajectory_20250701_100000.json -o imported.json",
+    },
+    Recipe {
+        name: "replay-flaky-run",
+        subcommand: "replay",
+        title: "Replay a run with the same sampling seed",
+        description: "Re-runs the task recorded in a trajectory with the same model, settings and seed, to reproduce flaky behavior.",
+        command: "trae replay trajectories/run.json --same-seed -t trajectories/replay.json",
+    },
+    Recipe {
+        name: "summarize-run",
+        subcommand: "summarize",
+        title: "Summarize a recorded run with Lakeview",
+        description: "Writes the step-by-step Lakeview summary of a run that had Lakeview disabled or where it failed, next to its trajectory.",
+        command: "trae summarize trajectories/run.json",
+    },
+    Recipe {
+        name: "self-update",
+        subcommand: "self-update",
+        title: "Update a release binary",
+        description: "Check for a newer release, then replace the binary with it after verifying its checksum.",
+        command: "trae self-update --check && trae self-update",
+    },
+    Recipe {
+        name: "team-costs",
+        subcommand: "runs",
+        title: "Report the runs and cost of one team",
+        description: "Label runs when starting them, then filter the usage ledger by label; point TRAE_USAGE_LEDGER at a shared file to report across users.",
+        command: "trae run \"Fix the flaky deploy test\" --label team=infra && trae runs list --label team=infra",
+    },
+    Recipe {
+        name: "validate-config",
+        subcommand: "config",
+        title: "Check a config file for typos",
+        description: "Reports unknown keys with the closest known key, wrong value types and providers missing from model_providers; exits non-zero on any issue.",
+        command: "trae config validate --config-file trae_config.json",
+    },
+    Recipe {
+        name: "keychain-api-key",
+        subcommand: "auth",
+        title: "Keep an API key out of config files",
+        description: "Stores the key in the OS keychain; runs use it when no config file or environment variable sets one.",
+        command: "trae auth login openai && trae auth status",
+    },
+    Recipe {
+        name: "ci-review",
+        subcommand: "explain-diff",
+        title: "Review a pull request in GitHub Actions",
+        description: "Explains the branch's changes and annotates risky hunks on the pull request diff.",
+        command: "trae explain-diff --range origin/main...HEAD --gha-annotations",
+    },
+    Recipe {
+        name: "mcp-backend",
+        subcommand: "mcp-serve",
+        title: "Use Trae as an MCP backend from an editor",
+        description: "Register this command as a stdio MCP server; the editor gets Trae's tools and a run_task tool for whole tasks.",
+        command: "trae mcp-serve --working-dir ~/src/my-project --config-file ~/.trae/trae_config.json",
+    },
+];
+
+/// Returns all registered recipes in display order.
+pub fn all_recipes() -> &'static [Recipe] {
+    RECIPES
+}
+
+/// Looks up a recipe by name.
+pub fn find_recipe(name: &str) -> Option<&'static Recipe> {
+    RECIPES.iter().find(|r| r.name == name)
+}
+
+/// Formats a recipe for display.
+pub fn format_recipe(recipe: &Recipe) -> String {
+    format!(
+        "# {} ({})\n# {}\n{}\n",
+        recipe.title, recipe.name, recipe.description, recipe.command
+    )
+}
+
+/// Builds the "Examples" section appended to a subcommand's long help.
+pub fn examples_help_for(subcommand: &str) -> String {
+    let examples: Vec<String> = RECIPES
+        .iter()
+        .filter(|r| r.subcommand == subcommand)
+        .map(|r| format!("  # {}\n  {}", r.title, r.command))
+        .collect();
+    if examples.is_empty() {
+        return String::new();
+    }
+    format!(
+        "Examples:\n{}\n\nRun `trae examples` for more workflows.",
+        examples.join("\n\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_recipe_names_are_unique() {
+        let names: HashSet<&str> = all_recipes().iter().map(|r| r.name).collect();
+        assert_eq!(names.len(), all_recipes().len());
+    }
+
+    #[test]
+    fn test_examples_help_for_subcommand() {
+        let help = examples_help_for("run");
+        assert!(help.starts_with("Examples:\n  # Fix an issue"));
+        assert!(help.contains("--must-patch"));
+        assert!(examples_help_for("tools").is_empty());
+    }
+
+    #[test]
+    fn test_find_recipe() {
+        assert_eq!(find_recipe("explore").unwrap().subcommand, "interactive");
+        assert!(find_recipe("nope").is_none());
+    }
+}
@@ -0,0 +1,400 @@
+//! # Archive Inspection
+//!
+//! Issues often attach a zipped reproduction project or a tarball of build artifacts. The
+//! `archive` tool lists the members of a zip, tar or tar.gz file and extracts the ones asked
+//! for into the run's scratch directory, so they stay out of the project and its patch.
+//! Sizes are capped while extracting (declared sizes can lie), and members with absolute
+//! paths, `..` components or links are skipped.
+
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// Largest member extracted, in bytes.
+const MAX_MEMBER_BYTES: u64 = 50 * 1024 * 1024;
+/// Most bytes extracted by one call.
+const MAX_TOTAL_BYTES: u64 = 200 * 1024 * 1024;
+/// Most members listed in the output.
+const MAX_LISTED_MEMBERS: usize = 500;
+
+#[derive(Deserialize, Debug)]
+struct ArchiveArgs {
+    command: String,
+    path: String,
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// A member of an archive.
+#[derive(Debug, PartialEq)]
+struct Member {
+    name: String,
+    size: u64,
+    is_dir: bool,
+}
+
+/// The relative path a member may be extracted to, or `None` if it would escape the
+/// destination.
+fn safe_path(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    let mut safe = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => safe.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!safe.as_os_str().is_empty()).then_some(safe)
+}
+
+/// Whether `name` is one of `selected`, or inside a selected directory. Nothing selected
+/// means everything is.
+fn is_selected(name: &str, selected: &[String]) -> bool {
+    selected.is_empty()
+        || selected.iter().any(|wanted| {
+            let wanted = wanted.trim_end_matches('/');
+            name.trim_end_matches('/') == wanted || name.starts_with(&format!("{}/", wanted))
+        })
+}
+
+/// Visits the members of the archive at `path`; `visit` gets each member and, for files
+/// that are not links, a reader for its content.
+fn for_each_member(
+    path: &Path,
+    format: Format,
+    mut visit: impl FnMut(Member, Option<&mut dyn Read>) -> io::Result<()>,
+) -> io::Result<()> {
+    let file = File::open(path)?;
+    match format {
+        Format::Zip => {
+            let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+            for index in 0..archive.len() {
+                let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+                let member = Member {
+                    name: entry.name().to_string(),
+                    size: entry.size(),
+                    is_dir: entry.is_dir(),
+                };
+                let readable = entry.is_file() && !entry.is_symlink();
+                visit(member, if readable { Some(&mut entry) } else { None })?;
+            }
+            Ok(())
+        }
+        Format::Tar => visit_tar(tar::Archive::new(file), visit),
+        Format::TarGz => visit_tar(tar::Archive::new(GzDecoder::new(file)), visit),
+    }
+}
+
+fn visit_tar<R: Read>(
+    mut archive: tar::Archive<R>,
+    mut visit: impl FnMut(Member, Option<&mut dyn Read>) -> io::Result<()>,
+) -> io::Result<()> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let kind = entry.header().entry_type();
+        let member = Member {
+            name: entry.path()?.to_string_lossy().into_owned(),
+            size: entry.header().size()?,
+            is_dir: kind.is_dir(),
+        };
+        visit(member, if kind.is_file() { Some(&mut entry) } else { None })?;
+    }
+    Ok(())
+}
+
+/// Lists the members of the archive, one `size  name` line each.
+fn list(path: &Path, format: Format) -> io::Result<String> {
+    let mut lines = Vec::new();
+    let mut count = 0;
+    let mut total = 0;
+    for_each_member(path, format, |member, _| {
+        count += 1;
+        total += member.size;
+        if lines.len() < MAX_LISTED_MEMBERS {
+            let size = if member.is_dir { "dir".to_string() } else { member.size.to_string() };
+            lines.push(format!("{:>12}  {}", size, member.name));
+        }
+        Ok(())
+    })?;
+    let mut out = format!("{} member(s), {} bytes uncompressed:\n{}", count, total, lines.join("\n"));
+    if count > MAX_LISTED_MEMBERS {
+        out.push_str(&format!("\n[... {} more members not listed ...]", count - MAX_LISTED_MEMBERS));
+    }
+    Ok(out)
+}
+
+/// Extracts the selected members into `destination`, enforcing the size limits.
+fn extract(path: &Path, format: Format, selected: &[String], destination: &Path) -> io::Result<String> {
+    let mut extracted = Vec::new();
+    let mut skipped = Vec::new();
+    let mut total = 0u64;
+    let mut found = vec![false; selected.len()];
+    for_each_member(path, format, |member, reader| {
+        if !is_selected(&member.name, selected) {
+            return Ok(());
+        }
+        for (index, wanted) in selected.iter().enumerate() {
+            found[index] |= is_selected(&member.name, std::slice::from_ref(wanted));
+        }
+        let Some(relative) = safe_path(&member.name) else {
+            skipped.push(format!("{} (unsafe path)", member.name));
+            return Ok(());
+        };
+        let target = destination.join(&relative);
+        if member.is_dir {
+            return std::fs::create_dir_all(&target);
+        }
+        let Some(reader) = reader else {
+            skipped.push(format!("{} (link or special file)", member.name));
+            return Ok(());
+        };
+        let budget = MAX_MEMBER_BYTES.min(MAX_TOTAL_BYTES - total);
+        if member.size > budget {
+            skipped.push(format!("{} ({} bytes, over the size limit)", member.name, member.size));
+            return Ok(());
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut output = File::create(&target)?;
+        let written = io::copy(&mut reader.take(budget + 1), &mut output)?;
+        if written > budget {
+            drop(output);
+            std::fs::remove_file(&target)?;
+            skipped.push(format!("{} (over the size limit)", member.name));
+            return Ok(());
+        }
+        total += written;
+        extracted.push(format!("{:>12}  {}", written, relative.display()));
+        Ok(())
+    })?;
+
+    let mut out = format!(
+        "Extracted {} file(s), {} bytes, to {}",
+        extracted.len(),
+        total,
+        destination.display()
+    );
+    if !extracted.is_empty() {
+        let shown = extracted.len().min(MAX_LISTED_MEMBERS);
+        out.push_str(&format!(":\n{}", extracted[..shown].join("\n")));
+        if extracted.len() > shown {
+            out.push_str(&format!("\n[... {} more files not listed ...]", extracted.len() - shown));
+        }
+    }
+    if !skipped.is_empty() {
+        out.push_str(&format!("\nSkipped:\n{}", skipped.join("\n")));
+    }
+    let missing: Vec<&str> = selected
+        .iter()
+        .zip(&found)
+        .filter(|(_, found)| !**found)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        out.push_str(&format!("\nNot in the archive: {}", missing.join(", ")));
+    }
+    Ok(out)
+}
+
+/// Lists and extracts zip and tar archives; see the module docs.
+pub struct ArchiveTool;
+
+impl ArchiveTool {
+    pub fn new() -> Self {
+        ArchiveTool
+    }
+}
+
+#[async_trait]
+impl Tool for ArchiveTool {
+    fn get_name(&self) -> String {
+        "archive".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Lists or extracts the members of a .zip, .tar or .tar.gz/.tgz file. `list` shows each member \
+            and its size; `extract` copies the given members (files or directories; all if none are given) \
+            into the run's scratch directory and reports where. Files over {} MB, more than {} MB in \
+            total, links and paths leaving the destination are skipped.",
+            MAX_MEMBER_BYTES / (1024 * 1024),
+            MAX_TOTAL_BYTES / (1024 * 1024)
+        )
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter {
+                name: "command".to_string(),
+                param_type: "string".to_string(),
+                description: "What to do with the archive.".to_string(),
+                is_required: true,
+                enum_values: Some(vec!["list".to_string(), "extract".to_string()]),
+                items: None,
+                properties: None,
+                required: vec![],
+            },
+            ToolParameter {
+                name: "path".to_string(),
+                param_type: "string".to_string(),
+                description: "Path to the archive, absolute or relative to the project.".to_string(),
+                is_required: true,
+                enum_values: None,
+                items: None,
+                properties: None,
+                required: vec![],
+            },
+            ToolParameter {
+                name: "members".to_string(),
+                param_type: "array".to_string(),
+                description: "For extract: member paths or directories to extract, as listed (default: all).".to_string(),
+                is_required: false,
+                enum_values: None,
+                items: Some(Box::new(ToolParameter {
+                    name: "member".to_string(),
+                    param_type: "string".to_string(),
+                    description: "A member path.".to_string(),
+                    is_required: false,
+                    enum_values: None,
+                    items: None,
+                    properties: None,
+                    required: vec![],
+                })),
+                properties: None,
+                required: vec![],
+            },
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: ArchiveArgs = serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+        })?;
+        let path = context.resolve_path(&args.path);
+        let Some(format) = Format::of(&path) else {
+            return Ok(ToolExecResult::new_failure(
+                format!("{} is not a .zip, .tar, .tar.gz or .tgz file", path.display()),
+                1,
+            ));
+        };
+        let destination = match (args.command.as_str(), &context.scratch_dir) {
+            ("list", _) => None,
+            ("extract", Some(scratch)) => {
+                let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                Some(scratch.join("archives").join(name))
+            }
+            ("extract", None) => {
+                return Ok(ToolExecResult::new_failure(
+                    "This run has no scratch directory to extract into.".to_string(),
+                    1,
+                ))
+            }
+            (other, _) => {
+                return Err(ToolError::InvalidArguments {
+                    tool_name: self.get_name(),
+                    message: format!("Unknown command '{}'; expected list or extract.", other),
+                })
+            }
+        };
+        let result = tokio::task::spawn_blocking(move || match destination {
+            None => list(&path, format),
+            Some(destination) => {
+                std::fs::create_dir_all(&destination)?;
+                extract(&path, format, &args.members, &destination)
+            }
+        })
+        .await
+        .map_err(|e| ToolError::InternalError(e.to_string()))?;
+        Ok(match result {
+            Ok(output) => ToolExecResult::new_success(Some(output), None),
+            Err(e) => ToolExecResult::new_failure(format!("Failed to read the archive: {}", e), 1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_list_and_extract_zip_and_tar_gz() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut zip = zip::ZipWriter::new(File::create(dir.path().join("repro.zip")).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("repro/", options).unwrap();
+        zip.start_file("repro/main.py", options).unwrap();
+        zip.write_all(b"print('hi')\n").unwrap();
+        zip.start_file("../evil.sh", options).unwrap();
+        zip.write_all(b"rm -rf /\n").unwrap();
+        zip.finish().unwrap();
+
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(dir.path().join("logs.tar.gz")).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(6);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "logs/build.log", &b"error\n"[..]).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let scratch = dir.path().join("scratch");
+        let context = ToolContext {
+            scratch_dir: Some(scratch.clone()),
+            ..ToolContext::for_project(dir.path())
+        };
+        let tool = ArchiveTool::new();
+
+        let listing = tool.execute(json!({"command": "list", "path": "repro.zip"}), &context).await.unwrap();
+        assert!(listing.output.unwrap().starts_with("3 member(s), 21 bytes uncompressed:"));
+
+        let result = tool.execute(json!({"command": "extract", "path": "repro.zip"}), &context).await.unwrap();
+        let output = result.output.unwrap();
+        assert!(output.contains("../evil.sh (unsafe path)"), "{}", output);
+        assert_eq!(std::fs::read_to_string(scratch.join("archives/repro.zip/repro/main.py")).unwrap(), "print('hi')\n");
+        assert!(!scratch.join("archives/evil.sh").exists());
+
+        let result = tool
+            .execute(json!({"command": "extract", "path": "logs.tar.gz", "members": ["logs", "missing.txt"]}), &context)
+            .await
+            .unwrap();
+        assert!(result.output.unwrap().ends_with("Not in the archive: missing.txt"));
+        assert_eq!(std::fs::read_to_string(scratch.join("archives/logs.tar.gz/logs/build.log")).unwrap(), "error\n");
+
+        let result = tool.execute(json!({"command": "extract", "path": "repro.zip"}), &ToolContext::default()).await.unwrap();
+        assert!(result.error.is_some());
+    }
+}
@@ -1,10 +1,16 @@
 use crate::llm::base_client as llm_types;
+use crate::tools::context::ToolContext;
+use crate::tools::hints::with_hint;
+use crate::utils::approval::{ApprovalDecision, ApprovalGate};
+use crate::utils::audit_log::{AuditEntry, AuditLog};
+use crate::utils::guards::write_target_for_tool_call;
+use crate::utils::permissions::CommandPermissions;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use thiserror::Error;
-use tracing::{debug, error, instrument};
+use tracing::{debug, error, instrument, warn};
 
 /// Errors that can occur during tool definition or execution.
 #[derive(Error, Debug)]
@@ -24,8 +30,13 @@ pub enum ToolError {
     FileWriteError(String), // Added for JsonEditTool
     #[error("Invalid JSON content: {0}")]
     InvalidJson(String), // Added for JsonEditTool
+    #[allow(dead_code)] // Reserved for operations that are declared but not yet supported
     #[error("Tool operation not implemented: {0}")]
     NotImplemented(String), // Added for JsonEditTool
+    /// A helper process the tool depends on (e.g., a language server) is down and could not
+    /// be restarted.
+    #[error("Backend '{backend}' is unavailable: {reason}")]
+    BackendUnavailable { backend: String, reason: String },
     #[error("Internal tool error: {0}")]
     InternalError(String), // Added for JsonEditTool & general use
     #[allow(dead_code)] // For future expansion or less common error types
@@ -92,6 +103,12 @@ pub struct ToolResult {
     /// An error message if the tool execution failed.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// How long the call took, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u128>,
+    /// The approval policy's decision on the call, if it was gated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<ApprovalDecision>,
     // TODO: Python version has an 'id' field here too, possibly for OpenAI's specific 'id' for tool message part.
     // If needed, it can be added. For now, this aligns with constructing an LLMMessage of role 'tool'.
 }
@@ -123,6 +140,18 @@ pub struct ToolParameter {
     pub is_required: bool,
 }
 
+/// Whether a tool call can be re-run during `trae replay --re-execute`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolDeterminism {
+    /// The result depends only on the arguments and the project files, so re-running the call
+    /// against a copy of the project reproduces it.
+    Deterministic,
+    /// The call reaches outside the project (processes, network, run-scoped state); replays
+    /// return the recorded result instead of running it again.
+    SideEffecting,
+}
+
 /// Defines the interface for a tool that can be executed by the agent.
 #[async_trait]
 pub trait Tool: Send + Sync {
@@ -138,10 +167,44 @@ pub trait Tool: Send + Sync {
     /// # Arguments
     /// * `arguments`: A `serde_json::Value` representing the arguments for the tool,
     ///                typically expected to be a JSON object.
+    /// * `context`: The run the call belongs to: project root, scratch directory, path policy
+    ///              and cancellation token.
     ///
     /// # Returns
     /// A `Result` containing a `ToolExecResult` on success, or a `ToolError` on failure.
-    async fn execute(&self, arguments: Value) -> Result<ToolExecResult, ToolError>;
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError>;
+
+    /// Whether replays may re-run calls of this tool. Tools are side-effecting unless they
+    /// declare otherwise.
+    fn determinism(&self) -> ToolDeterminism {
+        ToolDeterminism::SideEffecting
+    }
+
+    /// A separate instance for a new run, for tools that keep state between calls (a
+    /// persistent shell session, a language server connection). `None`, the default, means
+    /// the instance holds no such state and is shared by all runs using the registry.
+    fn for_run(&self) -> Option<std::sync::Arc<dyn Tool + Send + Sync>> {
+        None
+    }
+
+    /// Whether the tool reaches the network (web search, fetching URLs, code hosting APIs).
+    /// Such tools are removed in offline mode.
+    fn requires_network(&self) -> bool {
+        false
+    }
+
+    /// What a call with `arguments` connects to over the network (a URL, a host), if it does.
+    /// By default, every call of a tool that `requires_network`, described by its arguments.
+    fn network_target(&self, arguments: &Value) -> Option<String> {
+        self.requires_network().then(|| arguments.to_string())
+    }
+
+    /// The shell command a call with `arguments` will run, for tools that run one (`bash`,
+    /// `record_reproduction`). The executor applies the approval policy, asks the user's
+    /// permission for it and records it in the audit log.
+    fn shell_command<'a>(&self, _arguments: &'a Value) -> Option<&'a str> {
+        None
+    }
 
     /// Provides the JSON definition of the tool for the LLM.
     /// This default implementation constructs the schema based on `get_name`, `get_description`,
@@ -190,6 +253,12 @@ pub trait Tool: Send + Sync {
 /// Manages a collection of tools and executes them based on requests from the LLM.
 pub struct ToolExecutor {
     tools: HashMap<String, std::sync::Arc<dyn Tool + Send + Sync>>,
+    /// Optional confirmation asked for before shell commands run.
+    command_permissions: Option<std::sync::Arc<CommandPermissions>>,
+    /// Optional audit log of the commands, file writes and network calls executed.
+    audit_log: Option<std::sync::Arc<AuditLog>>,
+    /// Optional approval policy for commands and file writes.
+    approval: Option<std::sync::Arc<ApprovalGate>>,
 }
 
 impl ToolExecutor {
@@ -202,18 +271,83 @@ impl ToolExecutor {
         for tool in tools_list {
             tools.insert(tool.get_name(), tool);
         }
-        ToolExecutor { tools }
+        ToolExecutor {
+            tools,
+            command_permissions: None,
+            audit_log: None,
+            approval: None,
+        }
+    }
+
+    /// Installs `CommandPermissions` that are consulted before any shell command runs.
+    /// Denied commands are reported back to the LLM as failed tool results.
+    pub fn set_command_permissions(&mut self, permissions: Option<std::sync::Arc<CommandPermissions>>) {
+        self.command_permissions = permissions;
+    }
+
+    /// Records the commands, file writes and network calls executed from now on, including
+    /// blocked and denied ones, in `audit_log`.
+    pub fn set_audit_log(&mut self, audit_log: Option<std::sync::Arc<AuditLog>>) {
+        self.audit_log = audit_log;
+    }
+
+    /// Installs an `ApprovalGate` that decides, asking the user if need be, whether commands
+    /// and file writes may run. Its decisions are attached to the calls' results.
+    pub fn set_approval_gate(&mut self, gate: Option<std::sync::Arc<ApprovalGate>>) {
+        self.approval = gate;
+    }
+
+    /// Validates the target of a file-modifying tool call before its arguments are complete.
+    ///
+    /// Checks that the tool exists, that the path is absolute once resolved against the
+    /// project (as the editing tools require) and that the context's path policy would allow
+    /// it. Nothing is recorded against the change budget; the full check still runs when the
+    /// call is executed.
+    ///
+    /// # Returns
+    /// `Ok(())` if the write is expected to be accepted, or the reason it would be rejected.
+    pub fn preflight_write(
+        &self,
+        tool_name: &str,
+        path: &std::path::Path,
+        context: &ToolContext,
+    ) -> Result<(), String> {
+        if !self.tools.contains_key(tool_name) {
+            return Err(format!("Tool '{}' not found.", tool_name));
+        }
+        if !context.resolve_path(path).is_absolute() {
+            return Err(format!(
+                "The path {} is not an absolute path.",
+                path.display()
+            ));
+        }
+        context.preview_write(path)
     }
 
     /// Executes a single tool call request.
     ///
     /// # Arguments
     /// * `tool_call_request`: An `llm_types::ToolCall` struct representing the LLM's request to call a tool.
+    /// * `context`: The run the call belongs to; its path policy is checked before file writes.
     ///
     /// # Returns
-    /// A `ToolResult` to be sent back to the LLM.
-    #[instrument(skip(self, tool_call_request), fields(tool_name = %tool_call_request.function.name))]
-    pub async fn execute_tool_call(&self, tool_call_request: &llm_types::ToolCall) -> ToolResult {
+    /// A `ToolResult` to be sent back to the LLM, with the time the call took.
+    #[instrument(skip(self, tool_call_request, context), fields(tool_name = %tool_call_request.function.name))]
+    pub async fn execute_tool_call(&self, tool_call_request: &llm_types::ToolCall, context: &ToolContext) -> ToolResult {
+        let start = std::time::Instant::now();
+        let mut result = self.run_tool_call(tool_call_request, context).await;
+        result.duration_ms = Some(start.elapsed().as_millis());
+        if let Some(audit_log) = &self.audit_log {
+            let arguments = serde_json::from_str::<Value>(&tool_call_request.function.arguments).unwrap_or(Value::Null);
+            let tool = self.tools.get(&tool_call_request.function.name);
+            if let Some(entry) = tool.and_then(|tool| AuditEntry::for_call(tool.as_ref(), &arguments, context)) {
+                audit_log.record(&entry.with_outcome(result.success, result.error.as_deref(), result.duration_ms));
+            }
+        }
+        result
+    }
+
+    async fn run_tool_call(&self, tool_call_request: &llm_types::ToolCall, context: &ToolContext) -> ToolResult {
         debug!(args = %tool_call_request.function.arguments, "Attempting to execute tool");
         match self.tools.get(&tool_call_request.function.name) {
             Some(tool) => {
@@ -228,14 +362,94 @@ impl ToolExecutor {
                                 success: false,
                                 result: None,
                                 error: Some(format!("Tool arguments must parse to a JSON object or null. Parsed as: {}", args_value)),
+                                duration_ms: None,
+                                approval: None,
+                            };
+                        }
+                        // Previewed here so nobody is asked about a write the guard rejects anyway;
+                        // the change budget is only charged once the call is allowed to run.
+                        let write_target = write_target_for_tool_call(&tool_call_request.function.name, &args_value);
+                        if let Some(target) = &write_target {
+                            if let Err(reason) = context.preview_write(target) {
+                                warn!(path = %target.display(), "Tool call blocked by write guard");
+                                return ToolResult {
+                                    tool_call_id: tool_call_request.id.clone(),
+                                    success: false,
+                                    result: None,
+                                    error: Some(reason),
+                                    duration_ms: None,
+                                    approval: None,
+                                };
+                            }
+                        }
+                        let approval = match &self.approval {
+                            Some(gate) => {
+                                let command = tool.shell_command(&args_value);
+                                gate.check(&tool_call_request.function.name, command, &args_value, context).await
+                            }
+                            None => None,
+                        };
+                        if let Some(decision) = approval.as_ref().filter(|decision| !decision.approved) {
+                            warn!(subject = %decision.subject, "Tool call refused by the approval policy");
+                            let reason = if decision.asked {
+                                format!("The user did not approve {}.", decision.subject)
+                            } else {
+                                format!(
+                                    "The approval policy refuses {} (rule '{}').",
+                                    decision.subject,
+                                    decision.rule.as_deref().unwrap_or("default")
+                                )
                             };
+                            return ToolResult {
+                                tool_call_id: tool_call_request.id.clone(),
+                                success: false,
+                                result: None,
+                                error: Some(reason),
+                                duration_ms: None,
+                                approval,
+                            };
+                        }
+                        // A command the user just approved is not asked about again.
+                        let asked = approval.as_ref().is_some_and(|decision| decision.asked);
+                        if let Some(permissions) = self.command_permissions.as_ref().filter(|_| !asked) {
+                            if let Some(command) = tool.shell_command(&args_value) {
+                                if let Err(reason) = permissions.check(command).await {
+                                    warn!(command = %command, "Tool call denied by the user");
+                                    return ToolResult {
+                                        tool_call_id: tool_call_request.id.clone(),
+                                        success: false,
+                                        result: None,
+                                        error: Some(reason),
+                                        duration_ms: None,
+                                        approval: None,
+                                    };
+                                }
+                            }
+                        }
+                        if let Some(target) = &write_target {
+                            if let Err(reason) = context.check_write(target) {
+                                warn!(path = %target.display(), "Tool call blocked by write guard");
+                                return ToolResult {
+                                    tool_call_id: tool_call_request.id.clone(),
+                                    success: false,
+                                    result: None,
+                                    error: Some(reason),
+                                    duration_ms: None,
+                                    approval,
+                                };
+                            }
                         }
-                        match tool.execute(args_value).await {
+                        match tool.execute(args_value, context).await {
                             Ok(exec_result) => ToolResult {
                                 tool_call_id: tool_call_request.id.clone(),
                                 success: exec_result.error_code == 0,
                                 result: exec_result.output,
-                                error: exec_result.error,
+                                error: exec_result.error.map(|e| match exec_result.error_code {
+                                    0 => e,
+                                    _ => with_hint(&tool_call_request.function.name, e),
+                                }),
+                                duration_ms: None,
+                                approval,
                             },
                             Err(e) => {
                                 error!(error = %e, tool_name = %tool.get_name(), "Tool execution failed");
@@ -243,7 +457,9 @@ impl ToolExecutor {
                                     tool_call_id: tool_call_request.id.clone(),
                                     success: false,
                                     result: None,
-                                    error: Some(e.to_string()),
+                                    error: Some(with_hint(&tool_call_request.function.name, e.to_string())),
+                                    duration_ms: None,
+                                    approval,
                                 }
                             }
                         }
@@ -251,13 +467,15 @@ impl ToolExecutor {
                     Err(e) => {
                         // Handle case where arguments string might be empty, which is valid for tools with no args
                         if tool_call_request.function.arguments.trim().is_empty() {
-                            match tool.execute(Value::Null).await {
+                            match tool.execute(Value::Null, context).await {
                                 // Pass Value::Null for empty args
                                 Ok(exec_result) => ToolResult {
                                     tool_call_id: tool_call_request.id.clone(),
                                     success: exec_result.error_code == 0,
                                     result: exec_result.output,
                                     error: exec_result.error,
+                                    duration_ms: None,
+                                    approval: None,
                                 },
                                 Err(tool_err) => {
                                     error!(error = %tool_err, tool_name = %tool.get_name(), "Tool execution failed with empty args");
@@ -266,6 +484,8 @@ impl ToolExecutor {
                                         success: false,
                                         result: None,
                                         error: Some(tool_err.to_string()),
+                                        duration_ms: None,
+                                        approval: None,
                                     }
                                 }
                             }
@@ -275,12 +495,17 @@ impl ToolExecutor {
                                 tool_call_id: tool_call_request.id.clone(),
                                 success: false,
                                 result: None,
-                                error: Some(format!(
-                                    "Invalid JSON arguments for tool {}: {}. Arguments: {}",
-                                    tool_call_request.function.name,
-                                    e,
-                                    tool_call_request.function.arguments
+                                error: Some(with_hint(
+                                    &tool_call_request.function.name,
+                                    format!(
+                                        "Invalid JSON arguments for tool {}: {}. Arguments: {}",
+                                        tool_call_request.function.name,
+                                        e,
+                                        tool_call_request.function.arguments
+                                    ),
                                 )),
+                                duration_ms: None,
+                                approval: None,
                             }
                         }
                     }
@@ -297,6 +522,8 @@ impl ToolExecutor {
                         tool_call_request.function.name,
                         self.tools.keys()
                     )),
+                    duration_ms: None,
+                    approval: None,
                 }
             }
         }
@@ -306,10 +533,11 @@ impl ToolExecutor {
     pub async fn sequential_tool_calls(
         &self,
         tool_calls: &[llm_types::ToolCall],
+        context: &ToolContext,
     ) -> Vec<ToolResult> {
         let mut results = Vec::new();
         for call in tool_calls {
-            results.push(self.execute_tool_call(call).await);
+            results.push(self.execute_tool_call(call, context).await);
         }
         results
     }
@@ -319,10 +547,11 @@ impl ToolExecutor {
     pub async fn parallel_tool_calls(
         &self,
         tool_calls: &[llm_types::ToolCall],
+        context: &ToolContext,
     ) -> Vec<ToolResult> {
         // Placeholder: For now, just call sequential.
         // TODO: Implement actual parallel execution using something like futures::future::join_all.
         debug!("parallel_tool_calls called, but executing sequentially for now.");
-        self.sequential_tool_calls(tool_calls).await
+        self.sequential_tool_calls(tool_calls, context).await
     }
 }
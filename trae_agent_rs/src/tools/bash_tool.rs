@@ -1,10 +1,15 @@
 use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::utils::cleanup::RunCleanup;
+use crate::utils::offline;
+use crate::utils::result_store::ResultStore;
 use async_trait::async_trait;
 use serde::Deserialize;
 use serde_json::Value;
 use std::process::Stdio;
+use std::sync::Arc;
 use tokio::process::Command;
-use tracing::{debug, error, instrument};
+use tracing::{debug, error, instrument, warn};
 
 const MAX_BASH_OUTPUT_LEN: usize = 16000; // Max length for stdout/stderr before truncation
 const TRUNCATED_BASH_MESSAGE: &str = "<response clipped><NOTE>To save on context only part of this output has been shown. You might want to use file redirection or more specific commands to manage large outputs.</NOTE>";
@@ -16,11 +21,81 @@ struct BashToolArgs {
     working_directory: Option<String>,
 }
 
-pub struct BashTool;
+pub struct BashTool {
+    /// Where the full text of clipped outputs is kept for `read_more`, if anywhere.
+    result_store: Option<Arc<ResultStore>>,
+    /// Where the process groups of started commands are registered, so background processes
+    /// they leave running are terminated when the run ends.
+    cleanup: Option<Arc<RunCleanup>>,
+    /// Run commands without network access (`--offline`), where the system supports it.
+    offline: bool,
+}
 
 impl BashTool {
     pub fn new() -> Self {
-        BashTool
+        BashTool {
+            result_store: None,
+            cleanup: None,
+            offline: false,
+        }
+    }
+
+    /// Creates a bash tool that keeps the full text of clipped outputs in `store`, and tells
+    /// the agent the `result_id` to page through it with `read_more`.
+    pub fn with_result_store(store: Arc<ResultStore>) -> Self {
+        BashTool {
+            result_store: Some(store),
+            cleanup: None,
+            offline: false,
+        }
+    }
+
+    /// Runs each command in its own process group and registers it with `cleanup`.
+    pub fn track_processes(mut self, cleanup: Arc<RunCleanup>) -> Self {
+        self.cleanup = Some(cleanup);
+        self
+    }
+
+    /// Runs each command in a network namespace without interfaces, if `unshare` works on this
+    /// system (see `utils::offline`); otherwise commands run as usual.
+    pub fn without_network(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
+    /// Clips `content` like `maybe_truncate`, storing the full text first when a result store
+    /// is configured.
+    fn clip_output(&self, content: String, stream: &str) -> String {
+        if content.len() <= MAX_BASH_OUTPUT_LEN {
+            return content;
+        }
+        let Some(store) = &self.result_store else {
+            return Self::maybe_truncate(content);
+        };
+        match store.store(&content) {
+            Ok(result_id) => {
+                let mut end = MAX_BASH_OUTPUT_LEN;
+                while !content.is_char_boundary(end) {
+                    end -= 1;
+                }
+                let shown_chars = content[..end].chars().count();
+                format!(
+                    "{}<response clipped><NOTE>Only the first {} of {} characters of {} are shown. The full \
+                    output is stored as result_id \"{}\"; call read_more with this result_id and offset {} to \
+                    read the rest.</NOTE>",
+                    &content[..end],
+                    shown_chars,
+                    content.chars().count(),
+                    stream,
+                    result_id,
+                    shown_chars
+                )
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to store clipped bash output");
+                Self::maybe_truncate(content)
+            }
+        }
     }
 
     fn maybe_truncate(content: String) -> String {
@@ -102,13 +177,17 @@ impl Tool for BashTool {
         ]
     }
 
+    fn shell_command<'a>(&self, arguments: &'a Value) -> Option<&'a str> {
+        arguments.get("command").and_then(Value::as_str)
+    }
+
     // To correctly specify "command" as a required parameter for the LLM:
     // We might need to override get_json_definition or adjust ToolParameter/base definition.
     // For now, this is a known gap from the FIXME in base.rs.
     // A pragmatic approach for now: the agent's prompt can emphasize which arguments are mandatory.
 
-    #[instrument(skip(self, arguments), fields(tool_name = %self.get_name()))]
-    async fn execute(&self, arguments: Value) -> Result<ToolExecResult, ToolError> {
+    #[instrument(skip(self, arguments, context), fields(tool_name = %self.get_name()))]
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
         debug!(args = ?arguments, "Executing bash tool");
         let args: BashToolArgs = serde_json::from_value(arguments.clone()).map_err(|e| {
             ToolError::InvalidArguments {
@@ -124,23 +203,52 @@ impl Tool for BashTool {
             });
         }
 
-        let mut cmd = Command::new("sh");
-        cmd.arg("-c");
-        cmd.arg(&args.command);
+        // Commands run in the project unless told otherwise; relative directories are in it too.
+        let working_directory = match &args.working_directory {
+            Some(dir) => Some(context.resolve_path(dir)),
+            None => context.project_root.clone(),
+        };
+        let mut env = Vec::new();
+        if let Some(run_id) = &context.run_id {
+            env.push(("TRAE_RUN_ID", run_id.clone()));
+        }
+        if let Some(scratch_dir) = &context.scratch_dir {
+            env.push(("TRAE_SCRATCH_DIR", scratch_dir.display().to_string()));
+        }
+
+        let execution = context.execution();
+        let mut cmd = if execution.is_host() && self.offline && offline::network_isolation_available() {
+            let mut cmd = Command::new("unshare");
+            cmd.args(offline::ISOLATION_ARGS).arg("sh").arg("-c").arg(&args.command);
+            if let Some(dir) = &working_directory {
+                cmd.current_dir(dir);
+            }
+            cmd.envs(env.iter().map(|(key, value)| (*key, value)));
+            cmd
+        } else {
+            // A sandbox container has its own network policy.
+            execution.shell_command(&args.command, working_directory.as_deref(), &env)
+        };
         cmd.stdin(Stdio::null()); // No input to the command
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
         cmd.kill_on_drop(true); // Ensure process is killed if Child struct is dropped
-
-        if let Some(dir) = &args.working_directory {
-            cmd.current_dir(dir);
+        #[cfg(unix)]
+        if self.cleanup.is_some() && execution.is_host() {
+            // Own process group, so processes it leaves in the background can be found later.
+            cmd.process_group(0);
         }
 
-        debug!(command = %args.command, path = ?args.working_directory, "Configured bash command");
+        debug!(command = %args.command, path = ?working_directory, "Configured bash command");
 
         let child_process_result = cmd.spawn();
         let child = match child_process_result {
-            Ok(child) => child,
+            Ok(child) => {
+                if let (Some(cleanup), Some(pid), true) = (&self.cleanup, child.id(), execution.is_host()) {
+                    cleanup.track_process_group(pid);
+                }
+                child
+            }
             Err(e) => {
                 error!(error = %e, command = %args.command, "Failed to spawn command");
                 return Err(ToolError::ExecutionFailed(format!(
@@ -181,8 +289,8 @@ impl Tool for BashTool {
                 let stdout_raw = String::from_utf8_lossy(&output.stdout).to_string();
                 let stderr_raw = String::from_utf8_lossy(&output.stderr).to_string();
 
-                let stdout = Self::maybe_truncate(stdout_raw);
-                let stderr = Self::maybe_truncate(stderr_raw);
+                let stdout = self.clip_output(stdout_raw, "stdout");
+                let stderr = self.clip_output(stderr_raw, "stderr");
 
                 debug!(stdout_len = stdout.len(), stderr_len = stderr.len(), exit_code = output.status.code(), "Command executed");
 
@@ -221,7 +329,7 @@ mod tests {
     async fn test_bash_tool_echo() {
         let tool = BashTool::new();
         let args = json!({"command": "echo hello"});
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert!(result.output.unwrap().contains("STDOUT:\nhello"));
         assert_eq!(result.error_code, 0);
     }
@@ -230,7 +338,7 @@ mod tests {
     async fn test_bash_tool_error_exit_code() {
         let tool = BashTool::new();
         let args = json!({"command": "exit 123"});
-        let result = tool.execute(args).await.unwrap(); // Tool execution itself is successful
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap(); // Tool execution itself is successful
         assert!(result.error.is_some());
         assert!(result.error.unwrap().contains("123"));
         assert_eq!(result.error_code, 123);
@@ -241,19 +349,32 @@ mod tests {
         let tool = BashTool::new();
         // This command writes "error message" to stderr and "output message" to stdout
         let args = json!({"command": "echo 'output message'; >&2 echo 'error message'"});
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         let output_str = result.output.unwrap();
         assert!(output_str.contains("STDOUT:\noutput message"));
         assert!(output_str.contains("STDERR:\nerror message"));
         assert_eq!(result.error_code, 0); // Successful exit code
     }
 
+    #[tokio::test]
+    async fn test_bash_tool_without_network() {
+        let tool = BashTool::new().without_network();
+        // Interface names, one per line.
+        let args = json!({"command": "tail -n +3 /proc/net/dev | cut -d: -f1 | tr -d ' '"});
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
+        assert_eq!(result.error_code, 0);
+        if offline::network_isolation_available() {
+            let output = result.output.unwrap();
+            assert!(output.starts_with("STDOUT:\nlo\n\n"), "{}", output);
+        }
+    }
+
     #[tokio::test]
     async fn test_bash_tool_invalid_command() {
         let tool = BashTool::new();
         // A command that is likely to not exist or fail due to permissions
         let args = json!({"command": "this_command_should_not_exist_ever_12345"});
-        let result = tool.execute(args).await.unwrap(); // Tool execution is successful, command fails
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap(); // Tool execution is successful, command fails
         assert_ne!(result.error_code, 0); // Non-zero exit code
         assert!(result.error.is_some()); // Error message from the tool about exit status
     }
@@ -262,7 +383,7 @@ mod tests {
     async fn test_bash_tool_empty_command_error() {
         let tool = BashTool::new();
         let args = json!({"command": "  "}); // Empty or whitespace only command
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
         match result.err().unwrap() {
             ToolError::InvalidArguments { message, .. } => {
@@ -276,7 +397,7 @@ mod tests {
     async fn test_bash_tool_timeout_success() {
         let tool = BashTool::new();
         let args = json!({"command": "sleep 0.1; echo done", "timeout": 1});
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert!(result.output.unwrap().contains("STDOUT:\ndone"));
         assert_eq!(result.error_code, 0);
     }
@@ -286,7 +407,7 @@ mod tests {
         let tool = BashTool::new();
         // Command sleeps for 5 seconds, timeout is 1 second
         let args = json!({"command": "sleep 5; echo not_done", "timeout": 1});
-        let result = tool.execute(args).await.unwrap(); // Tool execution is successful, command times out
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap(); // Tool execution is successful, command times out
 
         assert!(result.error.is_some());
         assert!(result.error.unwrap().contains("timed out after 1 seconds"));
@@ -294,6 +415,28 @@ mod tests {
         assert!(result.output.is_none()); // No output because it was killed
     }
 
+    #[tokio::test]
+    async fn test_bash_tool_runs_in_project_with_run_variables() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::create_dir(project.path().join("src")).unwrap();
+        let context = ToolContext {
+            run_id: Some("fix-parser".to_string()),
+            scratch_dir: Some(project.path().join("scratch")),
+            ..ToolContext::for_project(project.path())
+        };
+        let tool = BashTool::new();
+
+        let args = json!({"command": "pwd; echo $TRAE_RUN_ID; echo $TRAE_SCRATCH_DIR"});
+        let output = tool.execute(args, &context).await.unwrap().output.unwrap();
+        let project_dir = project.path().canonicalize().unwrap();
+        assert!(output.contains(&format!("{}\nfix-parser\n", project_dir.display())), "{}", output);
+        assert!(output.contains(&project.path().join("scratch").display().to_string()));
+
+        let args = json!({"command": "pwd", "working_directory": "src"});
+        let output = tool.execute(args, &context).await.unwrap().output.unwrap();
+        assert!(output.contains(&project_dir.join("src").display().to_string()));
+    }
+
     #[tokio::test]
     async fn test_bash_tool_working_directory() {
         let tool = BashTool::new();
@@ -312,7 +455,7 @@ mod tests {
             "working_directory": temp_dir.path().to_str().unwrap()
         });
 
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(
             result.error_code, 0,
             "Command failed with error: {:?}",
@@ -335,7 +478,7 @@ mod tests {
         let command = format!("echo '{}'", long_string);
         let args = json!({ "command": command });
 
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(result.error_code, 0);
         let output = result.output.unwrap();
 
@@ -362,7 +505,7 @@ mod tests {
 
         let command_exact = format!("echo '{}'", exact_limit_string);
         let args_exact = json!({"command": command_exact});
-        let result_exact = tool.execute(args_exact).await.unwrap();
+        let result_exact = tool.execute(args_exact, &ToolContext::default()).await.unwrap();
         assert_eq!(result_exact.error_code, 0, "Exact limit test command failed. Error: {:?}", result_exact.error);
         let output_exact = result_exact.output.unwrap();
         assert!(!output_exact.contains(TRUNCATED_BASH_MESSAGE), "Output at exact limit was unexpectedly truncated. Output: {}", output_exact);
@@ -380,7 +523,7 @@ mod tests {
         let short_string = "c".repeat(100);
         let command_short = format!("echo '{}'", short_string);
         let args_short = json!({"command": command_short});
-        let result_short = tool.execute(args_short).await.unwrap();
+        let result_short = tool.execute(args_short, &ToolContext::default()).await.unwrap();
         assert_eq!(result_short.error_code, 0);
         let output_short = result_short.output.unwrap();
         assert!(!output_short.contains(TRUNCATED_BASH_MESSAGE));
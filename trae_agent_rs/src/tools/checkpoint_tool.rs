@@ -0,0 +1,190 @@
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::utils::checkpoints::CheckpointStore;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+#[derive(Deserialize, Debug)]
+struct CheckpointArgs {
+    name: String,
+}
+
+fn name_parameter(description: &str) -> Vec<ToolParameter> {
+    vec![ToolParameter {
+        name: "name".to_string(),
+        param_type: "string".to_string(),
+        description: description.to_string(),
+        is_required: true,
+        enum_values: None,
+        items: None,
+        properties: None,
+        required: vec![],
+    }]
+}
+
+fn parse_args(tool_name: String, arguments: Value) -> Result<CheckpointArgs, ToolError> {
+    serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+        tool_name,
+        message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+    })
+}
+
+/// Saves the project's working tree as a named checkpoint. Registered only when the project
+/// is a git repository.
+pub struct CheckpointTool {
+    store: Arc<CheckpointStore>,
+}
+
+impl CheckpointTool {
+    pub fn new(store: Arc<CheckpointStore>) -> Self {
+        CheckpointTool { store }
+    }
+}
+
+#[async_trait]
+impl Tool for CheckpointTool {
+    fn get_name(&self) -> String {
+        "checkpoint".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Saves the current state of all project files under a name, without committing. Save a \
+        checkpoint of a known-good state before a risky change (a large refactor, a dependency \
+        upgrade), so you can return to it with `restore_checkpoint` if verification regresses. \
+        Saving under an existing name replaces that checkpoint."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        name_parameter("Name of the checkpoint, e.g. `tests-passing`. Letters, digits, '-', '_' and '.' only.")
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args = parse_args(self.get_name(), arguments)?;
+        let store = self.store.clone();
+        let name = args.name.clone();
+        let checkpoint = tokio::task::spawn_blocking(move || store.create(&name))
+            .await
+            .map_err(|e| ToolError::InternalError(e.to_string()))?
+            .map_err(|e| ToolError::ExecutionFailed(format!("{:#}", e)))?;
+        info!(name = %checkpoint.name, commit = %checkpoint.commit, "Checkpoint saved");
+        Ok(ToolExecResult::new_success(
+            Some(format!(
+                "Checkpoint '{}' saved ({} files).",
+                checkpoint.name, checkpoint.files
+            )),
+            None,
+        ))
+    }
+}
+
+/// Restores the project's working tree to a checkpoint saved with `CheckpointTool`.
+pub struct RestoreCheckpointTool {
+    store: Arc<CheckpointStore>,
+}
+
+impl RestoreCheckpointTool {
+    pub fn new(store: Arc<CheckpointStore>) -> Self {
+        RestoreCheckpointTool { store }
+    }
+}
+
+#[async_trait]
+impl Tool for RestoreCheckpointTool {
+    fn get_name(&self) -> String {
+        "restore_checkpoint".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Restores all project files to a checkpoint saved with `checkpoint`: changed and deleted \
+        files are written back and files created since are removed. Use it to roll back a change \
+        that broke verification instead of undoing edits one by one. The checkpoint is kept, so \
+        you can restore it again."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        name_parameter("Name of the checkpoint to restore.")
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args = parse_args(self.get_name(), arguments)?;
+        let store = self.store.clone();
+        let name = args.name.clone();
+        let plan = tokio::task::spawn_blocking(move || store.plan_restore(&name))
+            .await
+            .map_err(|e| ToolError::InternalError(e.to_string()))?
+            .map_err(|e| ToolError::ExecutionFailed(format!("{:#}", e)))?;
+
+        // Every file the restore writes back or removes goes through the path policy, so a
+        // restore cannot reach protected files or exceed the change budget.
+        let root = self.store.project_root();
+        let targets: Vec<PathBuf> = plan
+            .summary
+            .restored
+            .iter()
+            .chain(&plan.summary.removed)
+            .map(|path| root.join(path))
+            .collect();
+        if let Err(reason) = context.check_writes(&targets) {
+            warn!(name = %args.name, "Checkpoint restore blocked by write guard");
+            return Ok(ToolExecResult::new_failure(reason, 1));
+        }
+
+        let store = self.store.clone();
+        let summary = plan.summary.clone();
+        tokio::task::spawn_blocking(move || store.apply_restore(&plan))
+            .await
+            .map_err(|e| ToolError::InternalError(e.to_string()))?
+            .map_err(|e| ToolError::ExecutionFailed(format!("{:#}", e)))?;
+        info!(name = %args.name, restored = summary.restored.len(), removed = summary.removed.len(), "Checkpoint restored");
+
+        let mut output = format!("Restored checkpoint '{}'.", args.name);
+        if summary.restored.is_empty() && summary.removed.is_empty() {
+            output.push_str(" The files already matched it.");
+        }
+        if !summary.restored.is_empty() {
+            output.push_str(&format!("\nRestored: {}", summary.restored.join(", ")));
+        }
+        if !summary.removed.is_empty() {
+            output.push_str(&format!("\nRemoved (created after the checkpoint): {}", summary.removed.join(", ")));
+        }
+        Ok(ToolExecResult::new_success(Some(output), None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::guards::WriteGuard;
+    use serde_json::json;
+    use std::process::Command;
+
+    #[tokio::test]
+    async fn test_restore_respects_protected_paths() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path();
+        for args in [&["init", "-q"][..], &["config", "user.name", "Test User"], &["config", "user.email", "test@example.com"]] {
+            assert!(Command::new("git").current_dir(root).args(args).status().unwrap().success());
+        }
+        std::fs::write(root.join("ci.yml"), "on: push\n").unwrap();
+        let store = Arc::new(CheckpointStore::new(root).unwrap());
+        store.create("start").unwrap();
+        std::fs::write(root.join("ci.yml"), "on: pull_request\n").unwrap();
+
+        let context = ToolContext {
+            path_policy: Some(Arc::new(WriteGuard::new().with_protected_path(root.join("ci.yml")))),
+            ..ToolContext::for_project(root)
+        };
+        let result = RestoreCheckpointTool::new(store)
+            .execute(json!({ "name": "start" }), &context)
+            .await
+            .unwrap();
+        assert!(result.error.unwrap().contains("protected path"));
+        assert_eq!(std::fs::read_to_string(root.join("ci.yml")).unwrap(), "on: pull_request\n");
+    }
+}
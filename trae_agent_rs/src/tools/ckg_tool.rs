@@ -0,0 +1,198 @@
+//! # Code Knowledge Graph Tool
+//!
+//! `ckg` answers structural questions about the project from the index in `utils::ckg`:
+//! where a symbol is defined, what a file defines, and where a function is called. The index
+//! is loaded from its cache and brought up to date on each call, so answers follow the
+//! agent's own edits.
+
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::utils::ckg::{default_cache_path, CodeGraph};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use tracing::warn;
+
+/// Most results listed per query.
+const MAX_RESULTS: usize = 50;
+/// Most lines of a definition's body shown with `print_body`.
+const MAX_BODY_LINES: usize = 200;
+
+#[derive(Deserialize, Debug)]
+struct CkgArgs {
+    command: String,
+    identifier: Option<String>,
+    path: Option<String>,
+    #[serde(default)]
+    print_body: bool,
+}
+
+/// Queries the code knowledge graph of the project; see the module docs.
+pub struct CkgTool;
+
+impl CkgTool {
+    pub fn new() -> Self {
+        CkgTool
+    }
+
+    fn invalid(&self, message: impl Into<String>) -> ToolError {
+        ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message: message.into(),
+        }
+    }
+}
+
+/// The index of the project at `root`, refreshed and written back to its cache.
+fn load_graph(root: &Path) -> CodeGraph {
+    let cache = default_cache_path(root);
+    let mut graph = cache.as_deref().map(CodeGraph::load).unwrap_or_default();
+    let stats = graph.refresh(root);
+    if let Some(cache) = cache.filter(|_| stats.indexed + stats.removed > 0) {
+        if let Err(e) = graph.save(&cache) {
+            warn!("Failed to cache the code index: {:#}", e);
+        }
+    }
+    graph
+}
+
+fn definitions(graph: &CodeGraph, root: &Path, identifier: &str, print_body: bool) -> String {
+    let found = graph.definitions(identifier);
+    if found.is_empty() {
+        return format!("No definition of '{}' found.", identifier);
+    }
+    let mut out = format!("{} definition(s) of '{}':", found.len(), identifier);
+    for (path, symbol) in found.iter().take(MAX_RESULTS) {
+        out.push_str(&format!("\n\n{}:{}-{} {}", path, symbol.start_line, symbol.end_line, symbol.signature));
+        if !print_body {
+            continue;
+        }
+        let Ok(source) = std::fs::read_to_string(root.join(path)) else {
+            continue;
+        };
+        let lines: Vec<&str> = source.lines().collect();
+        let end = symbol.end_line.min(lines.len()).min(symbol.start_line + MAX_BODY_LINES - 1);
+        for (n, line) in lines.iter().enumerate().take(end).skip(symbol.start_line - 1) {
+            out.push_str(&format!("\n{:>6}\t{}", n + 1, line));
+        }
+        if symbol.end_line > end {
+            out.push_str(&format!("\n[{} more line(s)]", symbol.end_line - end));
+        }
+    }
+    out
+}
+
+fn symbols_in_file(graph: &CodeGraph, path: &str) -> Option<String> {
+    let symbols = graph.symbols_in(path)?;
+    let mut out = format!("{} definition(s) in {}:", symbols.len(), path);
+    for symbol in symbols {
+        out.push_str(&format!(
+            "\n{}-{} {}{}",
+            symbol.start_line,
+            symbol.end_line,
+            "  ".repeat(symbol.depth),
+            symbol.signature
+        ));
+    }
+    Some(out)
+}
+
+fn callers(graph: &CodeGraph, identifier: &str) -> String {
+    let found = graph.callers(identifier);
+    if found.is_empty() {
+        return format!("No calls of '{}' found.", identifier);
+    }
+    let mut out = format!("{} call(s) of '{}':", found.len(), identifier);
+    for (path, call) in found.iter().take(MAX_RESULTS) {
+        let caller = call.caller.as_deref().unwrap_or("<top level>");
+        out.push_str(&format!("\n{}:{} in {}", path, call.line, caller));
+    }
+    out
+}
+
+#[async_trait]
+impl Tool for CkgTool {
+    fn get_name(&self) -> String {
+        "ckg".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Queries a code knowledge graph of the project (Rust, Python, JavaScript, TypeScript, Go), \
+        built with tree-sitter and kept up to date with your edits.\n\
+        * `definition`: where `identifier` is defined; qualify it (`Point::new`, `Cache.get`) to \
+        narrow it to a type or class, and set `print_body` to show the code.\n\
+        * `symbols_in_file`: the functions, types and methods defined in the file at `path`.\n\
+        * `callers`: where the function or method `identifier` is called, and from which definition. \
+        Calls are matched by name only."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        let param = |name: &str, param_type: &str, description: &str, is_required: bool| ToolParameter {
+            name: name.to_string(),
+            param_type: param_type.to_string(),
+            description: description.to_string(),
+            is_required,
+            enum_values: None,
+            items: None,
+            properties: None,
+            required: vec![],
+        };
+        vec![
+            ToolParameter {
+                enum_values: Some(vec![
+                    "definition".to_string(),
+                    "symbols_in_file".to_string(),
+                    "callers".to_string(),
+                ]),
+                ..param("command", "string", "The query to run.", true)
+            },
+            param("identifier", "string", "The symbol for `definition` and `callers`.", false),
+            param("path", "string", "The file for `symbols_in_file`, absolute or relative to the project.", false),
+            param("print_body", "boolean", "Show the code of each definition found by `definition`.", false),
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: CkgArgs = serde_json::from_value(arguments.clone())
+            .map_err(|e| self.invalid(format!("Failed to parse arguments: {}. Args: {:?}", e, arguments)))?;
+        let root = context.resolve_path(".");
+        let identifier = args.identifier.as_deref().map(str::trim).filter(|i| !i.is_empty());
+        let file = match (args.command.as_str(), identifier, &args.path) {
+            ("definition" | "callers", None, _) => {
+                return Err(self.invalid(format!("'identifier' is required for '{}'.", args.command)));
+            }
+            ("symbols_in_file", _, None) => return Err(self.invalid("'path' is required for 'symbols_in_file'.")),
+            ("symbols_in_file", _, Some(path)) => {
+                let path = context.resolve_path(path);
+                let relative = path.strip_prefix(&root).map_err(|_| {
+                    self.invalid(format!("{} is outside the project {}", path.display(), root.display()))
+                })?;
+                Some(relative.display().to_string())
+            }
+            ("definition" | "callers", Some(_), _) => None,
+            (other, _, _) => {
+                return Err(self.invalid(format!(
+                    "Unknown command '{}'; use definition, symbols_in_file or callers.",
+                    other
+                )));
+            }
+        };
+
+        let identifier = identifier.map(str::to_string);
+        let output = tokio::task::spawn_blocking(move || {
+            let graph = load_graph(&root);
+            match (args.command.as_str(), identifier, file) {
+                ("symbols_in_file", _, Some(file)) => symbols_in_file(&graph, &file)
+                    .unwrap_or_else(|| format!("{} is not an indexed source file.", file)),
+                ("callers", Some(identifier), _) => callers(&graph, &identifier),
+                (_, Some(identifier), _) => definitions(&graph, &root, &identifier, args.print_body),
+                _ => unreachable!("arguments are checked above"),
+            }
+        })
+        .await
+        .map_err(|e| ToolError::InternalError(e.to_string()))?;
+        Ok(ToolExecResult::new_success(Some(output), None))
+    }
+}
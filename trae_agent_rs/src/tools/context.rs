@@ -0,0 +1,108 @@
+//! # Tool Context
+//!
+//! What a tool call knows about the run it belongs to. The agent hands a `ToolContext` to
+//! every `Tool::execute`, so tools resolve paths against the project instead of trusting
+//! absolute paths from the LLM, find the run's scratch directory, respect the write policy
+//! and stop when the run is cancelled, without global state of their own.
+
+use super::execution::{ExecutionEnvironment, HostEnvironment};
+use crate::utils::guards::WriteGuard;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+/// Run facilities available to a tool call.
+#[derive(Debug, Clone, Default)]
+pub struct ToolContext {
+    /// The project the agent works on; relative paths are resolved against it.
+    pub project_root: Option<PathBuf>,
+    /// Identifier of the run (see `utils::scratch::run_id`).
+    pub run_id: Option<String>,
+    /// Protected paths, allow-list and change budget for file writes, if the run has them.
+    pub path_policy: Option<Arc<WriteGuard>>,
+    /// The run's scratch directory, for temporary files that must stay out of the patch.
+    pub scratch_dir: Option<PathBuf>,
+    /// Cancelled when the run is interrupted; long-running tools should stop early.
+    pub cancellation: CancellationToken,
+    /// Where `bash` runs commands and the editor accesses files; the host if `None`.
+    pub execution: Option<Arc<dyn ExecutionEnvironment>>,
+}
+
+impl ToolContext {
+    /// Creates a context for a run in `project_root`.
+    pub fn for_project(project_root: impl Into<PathBuf>) -> Self {
+        Self {
+            project_root: Some(project_root.into()),
+            ..Default::default()
+        }
+    }
+
+    /// The environment commands and file edits of this run happen in.
+    pub fn execution(&self) -> &dyn ExecutionEnvironment {
+        self.execution.as_deref().unwrap_or(&HostEnvironment)
+    }
+
+    /// Resolves `path` against the project root. Absolute paths, and any path when there is
+    /// no project root, are returned unchanged.
+    pub fn resolve_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        match &self.project_root {
+            Some(root) if path.is_relative() => root.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Checks a write to `path` (resolved against the project root) against the path policy,
+    /// recording it against the change budget.
+    ///
+    /// # Returns
+    /// `Ok(())` if there is no policy or it allows the write, else the reason for the LLM.
+    pub fn check_write(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        match &self.path_policy {
+            Some(policy) => policy.check_write(&self.resolve_path(path)),
+            None => Ok(()),
+        }
+    }
+
+    /// Like `check_write` for several files changed by one call: all of them are allowed and
+    /// recorded, or none is.
+    pub fn check_writes(&self, paths: &[PathBuf]) -> Result<(), String> {
+        match &self.path_policy {
+            Some(policy) => {
+                let paths: Vec<PathBuf> = paths.iter().map(|path| self.resolve_path(path)).collect();
+                policy.check_writes(&paths)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Like `check_write`, without recording anything against the change budget.
+    pub fn preview_write(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        match &self.path_policy {
+            Some(policy) => policy.preview_write(&self.resolve_path(path)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_resolve_against_project_and_policy_applies() {
+        let context = ToolContext::default();
+        assert_eq!(context.resolve_path("src/main.rs"), PathBuf::from("src/main.rs"));
+        assert!(context.check_write("/etc/passwd").is_ok());
+
+        let context = ToolContext {
+            path_policy: Some(Arc::new(WriteGuard::new().with_protected_path("/work/project/.git"))),
+            ..ToolContext::for_project("/work/project")
+        };
+        assert_eq!(context.resolve_path("src/main.rs"), PathBuf::from("/work/project/src/main.rs"));
+        assert_eq!(context.resolve_path("/tmp/notes.txt"), PathBuf::from("/tmp/notes.txt"));
+        assert!(context.preview_write("src/main.rs").is_ok());
+        // A relative path cannot slip past the policy.
+        assert!(context.check_write(".git/config").is_err());
+    }
+}
@@ -0,0 +1,434 @@
+//! # Database Queries
+//!
+//! `db_query` runs read-only SQL against a SQLite file in the project (fixture databases,
+//! caches) or against a database configured by name in `databases`, such as a Postgres
+//! connection string, and returns the rows as a capped table. SQLite files are opened
+//! read-only; Postgres queries run in a read-only transaction that is rolled back.
+
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::utils::offline::is_local_url;
+use async_trait::async_trait;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Rows returned, unless the call asks for fewer or more.
+const DEFAULT_MAX_ROWS: usize = 100;
+/// Most rows a call may ask for.
+const MAX_ROWS_LIMIT: usize = 1000;
+/// Longest cell shown, in characters.
+const MAX_CELL_CHARS: usize = 200;
+/// Longest output returned, in characters.
+const MAX_OUTPUT_CHARS: usize = 20_000;
+/// How long a query may run before it is interrupted.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, Debug)]
+struct DbQueryArgs {
+    database: String,
+    query: String,
+    max_rows: Option<usize>,
+}
+
+/// Rows read by a query: at most `max_rows`, and whether there were more.
+#[derive(Debug, Default, PartialEq)]
+struct QueryResult {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    truncated: bool,
+}
+
+impl QueryResult {
+    /// The rows as a `|`-separated table with a header, followed by the row count.
+    fn to_table(&self) -> String {
+        let mut lines = vec![self.columns.join(" | ")];
+        lines.extend(self.rows.iter().map(|row| row.join(" | ")));
+        let mut table = lines.join("\n");
+        if table.chars().count() > MAX_OUTPUT_CHARS {
+            table = table.chars().take(MAX_OUTPUT_CHARS).collect();
+            table.push_str("\n[... output clipped ...]");
+        }
+        let count = if self.truncated {
+            format!("({} rows shown; more rows match, narrow the query or raise max_rows)", self.rows.len())
+        } else {
+            format!("({} row{})", self.rows.len(), if self.rows.len() == 1 { "" } else { "s" })
+        };
+        format!("{}\n{}", table, count)
+    }
+}
+
+/// Cuts a cell to `MAX_CELL_CHARS` characters and keeps it on one line.
+fn cell(text: &str) -> String {
+    let text = text.replace('\n', "\\n");
+    if text.chars().count() > MAX_CELL_CHARS {
+        format!("{}…", text.chars().take(MAX_CELL_CHARS).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// Runs `query` against the SQLite file at `path`, opened read-only.
+fn query_sqlite(path: &Path, query: &str, max_rows: usize) -> Result<QueryResult, String> {
+    if !path.is_file() {
+        return Err(format!("No SQLite database at {}", path.display()));
+    }
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    connection
+        .pragma_update(None, "query_only", true)
+        .map_err(|e| e.to_string())?;
+    // The query is interrupted, rather than left running, if it takes too long.
+    let interrupt = connection.get_interrupt_handle();
+    let (done, finished) = std::sync::mpsc::channel::<()>();
+    std::thread::spawn(move || {
+        if finished.recv_timeout(QUERY_TIMEOUT).is_err() {
+            interrupt.interrupt();
+        }
+    });
+
+    let result = (|| {
+        let mut statement = connection.prepare(query).map_err(|e| e.to_string())?;
+        let columns: Vec<String> = statement.column_names().iter().map(|name| name.to_string()).collect();
+        let mut rows = statement.query([]).map_err(|e| e.to_string())?;
+        let mut result = QueryResult {
+            columns,
+            ..Default::default()
+        };
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            if result.rows.len() == max_rows {
+                result.truncated = true;
+                break;
+            }
+            let values = (0..result.columns.len())
+                .map(|index| match row.get_ref(index) {
+                    Ok(ValueRef::Null) => "NULL".to_string(),
+                    Ok(ValueRef::Integer(n)) => n.to_string(),
+                    Ok(ValueRef::Real(x)) => x.to_string(),
+                    Ok(ValueRef::Text(text)) => cell(&String::from_utf8_lossy(text)),
+                    Ok(ValueRef::Blob(blob)) => format!("<blob, {} bytes>", blob.len()),
+                    Err(e) => format!("<{}>", e),
+                })
+                .collect();
+            result.rows.push(values);
+        }
+        Ok(result)
+    })();
+    let _ = done.send(());
+    result
+}
+
+/// Runs `query` against the Postgres database at `connection_string`, in a read-only
+/// transaction that is rolled back. The query is wrapped to cast every column to text, so
+/// every column type renders; statements that cannot be wrapped run as they are, and their
+/// columns of other types than text, numbers and booleans show the type's name.
+async fn query_postgres(connection_string: &str, query: &str, max_rows: usize) -> Result<QueryResult, String> {
+    let connector = native_tls::TlsConnector::new().map_err(|e| e.to_string())?;
+    let (mut client, connection) =
+        tokio_postgres::connect(connection_string, postgres_native_tls::MakeTlsConnector::new(connector))
+            .await
+            .map_err(|e| format!("Failed to connect: {}", e))?;
+    let connection = tokio::spawn(connection);
+
+    let result = async {
+        let mut transaction = client
+            .build_transaction()
+            .read_only(true)
+            .start()
+            .await
+            .map_err(|e| e.to_string())?;
+        transaction
+            .batch_execute(&format!("SET LOCAL statement_timeout = {}", QUERY_TIMEOUT.as_millis()))
+            .await
+            .map_err(|e| e.to_string())?;
+        // Preparing rejects anything but a single statement and names the columns.
+        let query = query.trim().trim_end_matches(';');
+        let statement = transaction.prepare(query).await.map_err(|e| e.to_string())?;
+        let columns: Vec<String> = statement.columns().iter().map(|column| column.name().to_string()).collect();
+        // Statements that cannot be a subquery (`EXPLAIN`, `SHOW`, ...) fail to prepare when
+        // wrapped; the savepoint keeps that failure from aborting the transaction.
+        let wrapped = {
+            let savepoint = transaction.savepoint("text_columns").await.map_err(|e| e.to_string())?;
+            match savepoint.prepare(&text_columns_query(query, columns.len(), max_rows + 1)).await {
+                Ok(wrapped) => {
+                    savepoint.commit().await.map_err(|e| e.to_string())?;
+                    Some(wrapped)
+                }
+                Err(_) => {
+                    savepoint.rollback().await.map_err(|e| e.to_string())?;
+                    None
+                }
+            }
+        };
+        let rows = match &wrapped {
+            Some(wrapped) => transaction.query(wrapped, &[]).await,
+            None => {
+                let portal = transaction.bind(&statement, &[]).await.map_err(|e| e.to_string())?;
+                transaction.query_portal(&portal, (max_rows + 1) as i32).await
+            }
+        }
+        .map_err(|e| e.to_string())?;
+        let result = QueryResult {
+            truncated: rows.len() > max_rows,
+            rows: rows
+                .iter()
+                .take(max_rows)
+                .map(|row| (0..columns.len()).map(|i| postgres_cell(row, i)).collect())
+                .collect(),
+            columns,
+        };
+        transaction.rollback().await.map_err(|e| e.to_string())?;
+        Ok(result)
+    }
+    .await;
+    drop(client);
+    let _ = connection.await;
+    result
+}
+
+/// Selects the `column_count` columns of `query` as text, at most `limit` rows. The columns
+/// are renamed by position, so columns sharing a name (`a.id` and `b.id`) stay apart.
+fn text_columns_query(query: &str, column_count: usize, limit: usize) -> String {
+    let aliases: Vec<String> = (0..column_count).map(|i| format!("c{}", i)).collect();
+    let select: Vec<String> = aliases.iter().map(|alias| format!("q.{}::text", alias)).collect();
+    let alias = if aliases.is_empty() {
+        "q".to_string()
+    } else {
+        format!("q({})", aliases.join(", "))
+    };
+    // On their own lines, so a `--` comment ending the query cannot swallow the `)`.
+    format!("SELECT {} FROM (\n{}\n) AS {} LIMIT {}", select.join(", "), query, alias, limit)
+}
+
+/// Cell `index` of `row`: text as is, numbers and booleans formatted, other types by name.
+/// Rows of a wrapped query are all text.
+fn postgres_cell(row: &tokio_postgres::Row, index: usize) -> String {
+    fn show<'a, T: tokio_postgres::types::FromSql<'a> + ToString>(row: &'a tokio_postgres::Row, index: usize) -> Option<String> {
+        let value = row.try_get::<_, Option<T>>(index).ok()?;
+        Some(value.map_or_else(|| "NULL".to_string(), |value| cell(&value.to_string())))
+    }
+    show::<String>(row, index)
+        .or_else(|| show::<i64>(row, index))
+        .or_else(|| show::<i32>(row, index))
+        .or_else(|| show::<i16>(row, index))
+        .or_else(|| show::<f64>(row, index))
+        .or_else(|| show::<f32>(row, index))
+        .or_else(|| show::<bool>(row, index))
+        .unwrap_or_else(|| format!("<{}>", row.columns()[index].type_()))
+}
+
+/// Parameters a libpq `key=value` connection string can start with.
+const LIBPQ_KEYWORDS: &[&str] = &[
+    "host", "hostaddr", "port", "dbname", "user", "password", "options", "application_name",
+    "connect_timeout", "keepalives", "sslmode", "sslcert", "sslkey", "sslrootcert",
+    "channel_binding", "target_session_attrs", "load_balance_hosts",
+];
+
+/// Whether `target` is a Postgres connection string: a `postgres://` URL or a libpq
+/// `key=value` string such as `host=db dbname=shop`.
+fn is_postgres(target: &str) -> bool {
+    if target.starts_with("postgres://") || target.starts_with("postgresql://") {
+        return true;
+    }
+    target
+        .split_once('=')
+        .is_some_and(|(key, _)| LIBPQ_KEYWORDS.contains(&key.trim()))
+}
+
+/// Runs read-only SQL against SQLite files and configured databases; see the module docs.
+pub struct DbQueryTool {
+    /// Configured databases by name (see `Config::databases`).
+    databases: HashMap<String, String>,
+}
+
+impl DbQueryTool {
+    pub fn new() -> Self {
+        DbQueryTool {
+            databases: HashMap::new(),
+        }
+    }
+
+    /// Makes the databases in `databases` available by name.
+    pub fn with_databases(mut self, databases: HashMap<String, String>) -> Self {
+        self.databases = databases;
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for DbQueryTool {
+    fn get_name(&self) -> String {
+        "db_query".to_string()
+    }
+
+    /// Whether a configured database is a Postgres server on another machine.
+    fn requires_network(&self) -> bool {
+        self.databases.values().any(|target| is_postgres(target) && !is_local_url(target))
+    }
+
+    /// The Postgres server a query connects to, without its password.
+    fn network_target(&self, arguments: &Value) -> Option<String> {
+        let name = arguments.get("database").and_then(Value::as_str)?;
+        let target = self.databases.get(name).filter(|target| is_postgres(target))?;
+        Some(match reqwest::Url::parse(target) {
+            Ok(mut url) => {
+                let _ = url.set_password(None);
+                url.to_string()
+            }
+            Err(_) => format!("database '{}'", name),
+        })
+    }
+
+    fn get_description(&self) -> String {
+        let mut description = "Runs a read-only SQL query and returns the rows as a table. `database` is the \
+            path to a SQLite file (absolute or relative to the project) or the name of a configured \
+            database. Writes are refused. List SQLite tables with `SELECT name, sql FROM sqlite_master`."
+            .to_string();
+        if !self.databases.is_empty() {
+            let mut names: Vec<&str> = self.databases.keys().map(String::as_str).collect();
+            names.sort();
+            description.push_str(&format!(" Configured databases: {}.", names.join(", ")));
+        }
+        description
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        let param = |name: &str, param_type: &str, description: &str, is_required: bool| ToolParameter {
+            name: name.to_string(),
+            param_type: param_type.to_string(),
+            description: description.to_string(),
+            is_required,
+            enum_values: None,
+            items: None,
+            properties: None,
+            required: vec![],
+        };
+        vec![
+            param("database", "string", "Path to a SQLite file, or the name of a configured database.", true),
+            param("query", "string", "A single read-only SQL statement (SELECT, WITH, ...).", true),
+            param(
+                "max_rows",
+                "integer",
+                &format!("Most rows returned (default {}, at most {}).", DEFAULT_MAX_ROWS, MAX_ROWS_LIMIT),
+                false,
+            ),
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: DbQueryArgs = serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+        })?;
+        let max_rows = args.max_rows.unwrap_or(DEFAULT_MAX_ROWS).clamp(1, MAX_ROWS_LIMIT);
+        let target = self.databases.get(&args.database).unwrap_or(&args.database);
+        let result = if is_postgres(target) {
+            if !self.databases.contains_key(&args.database) {
+                return Ok(ToolExecResult::new_failure(
+                    "Postgres databases must be configured by name in `databases`; connection strings are not accepted here."
+                        .to_string(),
+                    1,
+                ));
+            }
+            query_postgres(target, &args.query, max_rows).await
+        } else {
+            let path = context.resolve_path(target);
+            let query = args.query.clone();
+            tokio::task::spawn_blocking(move || query_sqlite(&path, &query, max_rows))
+                .await
+                .map_err(|e| ToolError::InternalError(e.to_string()))?
+        };
+        Ok(match result {
+            Ok(result) => ToolExecResult::new_success(Some(result.to_table()), None),
+            Err(message) => ToolExecResult::new_failure(format!("Query failed: {}", message), 1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_sqlite_queries_are_read_only_and_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let connection = Connection::open(dir.path().join("fixtures.db")).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE users (id INTEGER, name TEXT, avatar BLOB);
+                 INSERT INTO users VALUES (1, 'alice', x'00ff'), (2, NULL, NULL), (3, 'carol', NULL);",
+            )
+            .unwrap();
+        drop(connection);
+
+        let tool = DbQueryTool::new();
+        let context = ToolContext::for_project(dir.path());
+        let run = |arguments: Value| {
+            let tool = &tool;
+            let context = &context;
+            async move { tool.execute(arguments, context).await.unwrap() }
+        };
+
+        let result = run(json!({"database": "fixtures.db", "query": "SELECT * FROM users ORDER BY id", "max_rows": 2})).await;
+        assert_eq!(
+            result.output.unwrap(),
+            "id | name | avatar\n1 | alice | <blob, 2 bytes>\n2 | NULL | NULL\n\
+             (2 rows shown; more rows match, narrow the query or raise max_rows)"
+        );
+
+        let result = run(json!({"database": "fixtures.db", "query": "DELETE FROM users"})).await;
+        assert!(result.error.unwrap().contains("readonly"));
+        let result = run(json!({"database": "missing.db", "query": "SELECT 1"})).await;
+        assert!(result.error.unwrap().contains("No SQLite database"));
+        let result = run(json!({"database": "postgres://user@localhost/app", "query": "SELECT 1"})).await;
+        assert!(result.error.is_some());
+    }
+
+    #[test]
+    fn test_postgres_connection_strings_are_recognized() {
+        assert!(is_postgres("postgres://app@localhost/shop"));
+        assert!(is_postgres("host=db.example.com dbname=shop user=app"));
+        assert!(is_postgres("dbname = shop"));
+        assert!(!is_postgres("tests/fixtures.db"));
+        assert!(!is_postgres("data/a=b.db"));
+    }
+
+    #[test]
+    fn test_postgres_columns_are_read_by_position() {
+        // Both `id` columns are kept, not merged into one key.
+        assert_eq!(
+            text_columns_query("SELECT a.id, b.id FROM a JOIN b ON a.b_id = b.id", 2, 101),
+            "SELECT q.c0::text, q.c1::text FROM (\nSELECT a.id, b.id FROM a JOIN b ON a.b_id = b.id\n) AS q(c0, c1) LIMIT 101"
+        );
+        // A trailing comment ends at the newline, before the closing parenthesis.
+        assert!(text_columns_query("SELECT 1 -- one", 1, 2).contains("SELECT 1 -- one\n) AS q(c0)"));
+    }
+
+    /// Runs against the Postgres server at `TRAE_TEST_POSTGRES_URL`; skipped if it is unset.
+    #[tokio::test]
+    async fn test_postgres_queries_that_cannot_be_wrapped() {
+        let Ok(url) = std::env::var("TRAE_TEST_POSTGRES_URL") else {
+            return;
+        };
+        let query = |sql: &'static str| {
+            let url = url.clone();
+            async move { query_postgres(&url, sql, 5).await.unwrap() }
+        };
+
+        let result = query("SELECT 1 AS id, 2 AS id, NULL AS note -- both ids").await;
+        assert_eq!(result.to_table(), "id | id | note\n1 | 2 | NULL\n(1 row)");
+        let result = query("EXPLAIN SELECT 1;").await;
+        assert_eq!(result.columns, vec!["QUERY PLAN"]);
+        assert!(result.rows[0][0].starts_with("Result"), "{:?}", result.rows);
+        let result = query("SHOW statement_timeout").await;
+        assert_eq!(result.rows, vec![vec!["30s".to_string()]]);
+        let result = query("SELECT generate_series(1, 10) AS n, true AS flag").await;
+        assert!(result.truncated && result.rows.len() == 5);
+        assert_eq!(result.rows[4], vec!["5".to_string(), "true".to_string()]);
+    }
+}
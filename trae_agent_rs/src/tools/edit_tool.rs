@@ -1,15 +1,22 @@
-use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::base::{Tool, ToolDeterminism, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use super::execution::{ExecutionEnvironment, PathKind};
+use crate::utils::outline::{
+    find_symbol, format_outline, is_type_definition, leading_comment_start, outline, OutlineLanguage,
+};
+use crate::utils::lsp::EditSite;
+use crate::utils::refactor::{find_occurrences, format_sites};
 use async_trait::async_trait;
 use serde::Deserialize;
 use serde_json::Value;
 use std::path::{Path, PathBuf};
-use tokio::fs;
 use tracing::{debug, instrument};
 
 const SNIPPET_LINES: usize = 4;
 const TAB_WIDTH: usize = 8; // Define tab width
 const MAX_VIEW_OUTPUT_LINES: usize = 200; // Max lines for view output
 const TRUNCATED_MESSAGE: &str = "\n... (output truncated due to length) ...";
+const MAX_SYMBOL_REFERENCES: usize = 20; // References listed by view_symbol
 
 #[derive(Deserialize, Debug)]
 struct EditToolArgs {
@@ -20,6 +27,7 @@ struct EditToolArgs {
     new_str: Option<String>,
     old_str: Option<String>,
     view_range: Option<Vec<i64>>,
+    symbol: Option<String>,
 }
 
 pub struct EditTool;
@@ -33,15 +41,15 @@ impl EditTool {
         text.replace('\t', &" ".repeat(TAB_WIDTH))
     }
 
-    fn validate_path_exists(&self, p: &Path, command_name: &str) -> Result<(), ToolError> {
-        if !p.exists() && command_name != "create" {
+    fn validate_path_exists(&self, p: &Path, kind: Option<PathKind>, command_name: &str) -> Result<(), ToolError> {
+        if kind.is_none() && command_name != "create" {
             return Err(ToolError::NotFound(format!(
                 "Path {} does not exist for command '{}'.",
                 p.display(),
                 command_name
             )));
         }
-        if p.exists() && command_name == "create" {
+        if kind.is_some() && command_name == "create" {
             return Err(ToolError::ExecutionFailed(format!(
                 "File already exists at: {}. Cannot overwrite files using command `create`.",
                 p.display()
@@ -50,8 +58,8 @@ impl EditTool {
         Ok(())
     }
 
-    fn validate_path_is_file(&self, p: &Path) -> Result<(), ToolError> {
-        if !p.is_file() {
+    fn validate_path_is_file(&self, p: &Path, kind: Option<PathKind>) -> Result<(), ToolError> {
+        if kind != Some(PathKind::File) {
             return Err(ToolError::InvalidArguments {
                 tool_name: self.get_name(),
                 message: format!("Path {} is not a file.", p.display()),
@@ -60,8 +68,8 @@ impl EditTool {
         Ok(())
     }
 
-    fn validate_path_is_dir(&self, p: &Path) -> Result<(), ToolError> {
-        if !p.is_dir() {
+    fn validate_path_is_dir(&self, p: &Path, kind: Option<PathKind>) -> Result<(), ToolError> {
+        if kind != Some(PathKind::Dir) {
             return Err(ToolError::InvalidArguments {
                 tool_name: self.get_name(),
                 message: format!("Path {} is not a directory.", p.display()),
@@ -70,13 +78,149 @@ impl EditTool {
         Ok(())
     }
 
+    async fn outline_file(&self, execution: &dyn ExecutionEnvironment, path: &Path, kind: Option<PathKind>) -> Result<ToolExecResult, ToolError> {
+        self.validate_path_is_file(path, kind)?;
+        let language = OutlineLanguage::from_path(path).ok_or_else(|| ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message: format!(
+                "Cannot outline {}: unsupported file type. Supported: Rust, Python, JavaScript, TypeScript, Go. Use 'view' instead.",
+                path.display()
+            ),
+        })?;
+        let content = execution.read_to_string(path).await.map_err(|e| {
+            ToolError::ExecutionFailed(format!("Failed to read file {}: {}", path.display(), e))
+        })?;
+        let symbols = outline(language, &content).map_err(|e| ToolError::ExecutionFailed(e.to_string()))?;
+
+        let output = if symbols.is_empty() {
+            format!("No definitions found in {} ({}).", path.display(), language.name())
+        } else {
+            format!(
+                "Outline of {} ({}, {} symbols; line ranges are start-end):\n{}\n\nUse 'view' with view_range to read a symbol.",
+                path.display(),
+                language.name(),
+                symbols.len(),
+                format_outline(&symbols)
+            )
+        };
+        Ok(ToolExecResult {
+            output: Some(output),
+            error: None,
+            error_code: 0,
+        })
+    }
+
+    /// Shows a function or type with its doc comment, the type definitions of the same file it
+    /// mentions, and where else in the project its name is referenced. References are searched
+    /// for on the host, where a sandbox has the project mounted at the same path.
+    async fn view_symbol(
+        &self,
+        execution: &dyn ExecutionEnvironment,
+        path: &Path,
+        kind: Option<PathKind>,
+        name: &str,
+    ) -> Result<ToolExecResult, ToolError> {
+        self.validate_path_is_file(path, kind)?;
+        let language = OutlineLanguage::from_path(path).ok_or_else(|| ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message: format!(
+                "Cannot find symbols in {}: unsupported file type. Supported: Rust, Python, JavaScript, TypeScript, Go.",
+                path.display()
+            ),
+        })?;
+        let content = execution.read_to_string(path).await.map_err(|e| {
+            ToolError::ExecutionFailed(format!("Failed to read file {}: {}", path.display(), e))
+        })?;
+        let symbols = outline(language, &content).map_err(|e| ToolError::ExecutionFailed(e.to_string()))?;
+        let symbol = find_symbol(&symbols, name).ok_or_else(|| {
+            let names: Vec<&str> = symbols.iter().filter_map(|s| s.name.as_deref()).collect();
+            ToolError::ExecutionFailed(format!(
+                "No symbol named '{}' in {}. Defined there: {}",
+                name,
+                path.display(),
+                if names.is_empty() { "nothing".to_string() } else { names.join(", ") }
+            ))
+        })?;
+
+        let first_line = leading_comment_start(&content, symbol.start_line);
+        let body: Vec<String> = content
+            .lines()
+            .enumerate()
+            .skip(first_line - 1)
+            .take(symbol.end_line + 1 - first_line)
+            .map(|(i, line)| format!("{:6}\t{}", i + 1, line))
+            .collect();
+        let mut output = format!(
+            "Symbol '{}' in {} (lines {}-{}):\n{}",
+            name,
+            path.display(),
+            first_line,
+            symbol.end_line,
+            body.join("\n")
+        );
+
+        let words: std::collections::HashSet<&str> = body
+            .iter()
+            .flat_map(|line| line.split(|c: char| !(c.is_alphanumeric() || c == '_')))
+            .collect();
+        let types: Vec<String> = symbols
+            .iter()
+            .filter(|s| is_type_definition(s) && s.start_line != symbol.start_line)
+            .filter(|s| s.name.as_deref().is_some_and(|n| words.contains(n)))
+            .map(|s| format!("{:>6}-{:<6} {}", s.start_line, s.end_line, s.signature))
+            .collect();
+        if !types.is_empty() {
+            output.push_str(&format!("\n\nTypes it uses, defined in this file:\n{}", types.join("\n")));
+        }
+
+        let short_name = symbol.name.clone().unwrap_or_default();
+        let root = project_root_of(path);
+        let references: Vec<_> = find_occurrences(&root, &short_name)
+            .into_iter()
+            .filter(|site| !(site.path == path && (first_line..=symbol.end_line).contains(&site.line)))
+            .collect();
+        if references.is_empty() {
+            output.push_str(&format!("\n\nNo other references to '{}' under {}.", short_name, root.display()));
+        } else {
+            let line_text = |site: &EditSite| {
+                std::fs::read_to_string(&site.path)
+                    .ok()
+                    .and_then(|text| text.lines().nth(site.line - 1).map(|l| l.trim().to_string()))
+                    .unwrap_or_default()
+            };
+            let shown = &references[..references.len().min(MAX_SYMBOL_REFERENCES)];
+            let listed: Vec<String> = format_sites(shown, &root)
+                .into_iter()
+                .zip(shown)
+                .map(|(location, site)| format!("{}: {}", location, line_text(site)))
+                .collect();
+            output.push_str(&format!(
+                "\n\nReferences to '{}' under {} ({}):\n{}",
+                short_name,
+                root.display(),
+                references.len(),
+                listed.join("\n")
+            ));
+            if references.len() > shown.len() {
+                output.push_str(&format!("\n... and {} more", references.len() - shown.len()));
+            }
+        }
+        Ok(ToolExecResult {
+            output: Some(output),
+            error: None,
+            error_code: 0,
+        })
+    }
+
     async fn view_file(
         &self,
+        execution: &dyn ExecutionEnvironment,
         path: &Path,
+        kind: Option<PathKind>,
         view_range: Option<&Vec<i64>>,
     ) -> Result<ToolExecResult, ToolError> {
-        self.validate_path_is_file(path)?;
-        let raw_content = fs::read_to_string(path).await.map_err(|e| {
+        self.validate_path_is_file(path, kind)?;
+        let raw_content = execution.read_to_string(path).await.map_err(|e| {
             ToolError::ExecutionFailed(format!("Failed to read file {}: {}", path.display(), e))
         })?;
         let content_expanded = Self::expand_tabs(&raw_content);
@@ -164,61 +308,45 @@ impl EditTool {
         })
     }
 
-    async fn view_dir(&self, path: &Path) -> Result<ToolExecResult, ToolError> {
-        self.validate_path_is_dir(path)?;
+    async fn view_dir(&self, execution: &dyn ExecutionEnvironment, path: &Path, kind: Option<PathKind>) -> Result<ToolExecResult, ToolError> {
+        self.validate_path_is_dir(path, kind)?;
         let mut entries = Vec::new();
 
-        let mut l1_read_dir = fs::read_dir(path).await.map_err(|e| {
+        let l1_entries = execution.list_dir(path).await.map_err(|e| {
             ToolError::ExecutionFailed(format!(
                 "Failed to read directory {}: {}",
                 path.display(),
                 e
             ))
         })?;
-        while let Some(entry) = l1_read_dir.next_entry().await.map_err(|e| {
-            ToolError::ExecutionFailed(format!(
-                "Error iterating directory {}: {}",
-                path.display(),
-                e
-            ))
-        })? {
-            let entry_path = entry.path();
-            let name = entry_path.file_name().unwrap_or_default().to_string_lossy();
+        for entry in l1_entries {
+            let name = &entry.name;
             if name.starts_with('.') {
                 continue;
             }
 
-            if entry_path.is_dir() {
+            if entry.kind == PathKind::Dir {
                 entries.push(format!("{}/ (dir)", name));
-                let mut l2_read_dir = fs::read_dir(&entry_path).await.map_err(|e| {
+                let entry_path = path.join(name);
+                let l2_entries = execution.list_dir(&entry_path).await.map_err(|e| {
                     ToolError::ExecutionFailed(format!(
                         "Failed to read subdirectory {}: {}",
                         entry_path.display(),
                         e
                     ))
                 })?;
-                while let Some(sub_entry) = l2_read_dir.next_entry().await.map_err(|e| {
-                    ToolError::ExecutionFailed(format!(
-                        "Error iterating subdirectory {}: {}",
-                        entry_path.display(),
-                        e
-                    ))
-                })? {
-                    let sub_entry_path = sub_entry.path();
-                    let sub_name = sub_entry_path
-                        .file_name()
-                        .unwrap_or_default()
-                        .to_string_lossy();
+                for sub_entry in l2_entries {
+                    let sub_name = &sub_entry.name;
                     if sub_name.starts_with('.') {
                         continue;
                     }
-                    if sub_entry_path.is_dir() {
+                    if sub_entry.kind == PathKind::Dir {
                         entries.push(format!("  {}/ (dir)", sub_name));
-                    } else if sub_entry_path.is_file() {
+                    } else if sub_entry.kind == PathKind::File {
                         entries.push(format!("  {} (file)", sub_name));
                     }
                 }
-            } else if entry_path.is_file() {
+            } else if entry.kind == PathKind::File {
                 entries.push(format!("{} (file)", name));
             }
         }
@@ -257,6 +385,15 @@ impl EditTool {
     }
 }
 
+/// The project a file belongs to: the nearest ancestor with a `.git`, else its directory.
+fn project_root_of(path: &Path) -> PathBuf {
+    let dir = path.parent().unwrap_or(path);
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
 #[async_trait]
 impl Tool for EditTool {
     fn get_name(&self) -> String {
@@ -265,7 +402,9 @@ impl Tool for EditTool {
 
     fn get_description(&self) -> String {
         "Tool for viewing, creating, and editing files. \
-        Supports viewing file/directory content (up to 2 levels for dirs), creating new files, \
+        Supports viewing file/directory content (up to 2 levels for dirs), outlining the functions, \
+        types and classes of a source file with their line ranges, viewing one symbol with its doc \
+        comment, the types it uses and its references across the project, creating new files, \
         replacing exact string occurrences in files (tabs expanded to 8 spaces for matching), \
         and inserting text at specific lines (tabs in input string also expanded). \
         File content with tabs will be converted to spaces upon edit. \
@@ -277,15 +416,20 @@ impl Tool for EditTool {
         vec![
             ToolParameter {
                 name: "command".to_string(), param_type: "string".to_string(),
-                description: "The command to run: view, create, str_replace, insert.".to_string(),
-                is_required: true, enum_values: Some(vec!["view".into(), "create".into(), "str_replace".into(), "insert".into()]),
+                description: "The command to run: view, outline, view_symbol, create, str_replace, insert.".to_string(),
+                is_required: true, enum_values: Some(vec!["view".into(), "outline".into(), "view_symbol".into(), "create".into(), "str_replace".into(), "insert".into()]),
                 items: None, properties: None, required: vec![],
             },
             ToolParameter {
                 name: "path".to_string(), param_type: "string".to_string(),
-                description: "Absolute path to the file or directory.".to_string(),
+                description: "Absolute path to the file or directory. For 'view_symbol', may also be given as `<path>::<symbol>`.".to_string(),
                 is_required: true, enum_values: None, items: None, properties: None, required: vec![],
             },
+            ToolParameter {
+                name: "symbol".to_string(), param_type: "string".to_string(),
+                description: "Function, method or type to show for 'view_symbol', e.g. `parse` or `Parser::parse`. Shows its code with doc comment, the types it uses and where it is referenced.".to_string(),
+                is_required: false, enum_values: None, items: None, properties: None, required: vec![],
+            },
             ToolParameter {
                 name: "file_text".to_string(), param_type: "string".to_string(),
                 description: "Content for the 'create' command. Tabs will be preserved as-is during creation.".to_string(),
@@ -324,8 +468,8 @@ impl Tool for EditTool {
         ]
     }
 
-    #[instrument(skip(self, arguments), fields(tool_name = %self.get_name()))]
-    async fn execute(&self, arguments: Value) -> Result<ToolExecResult, ToolError> {
+    #[instrument(skip(self, arguments, context), fields(tool_name = %self.get_name()))]
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
         debug!(args = ?arguments, "Executing edit tool");
         let args: EditToolArgs =
             serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
@@ -333,7 +477,15 @@ impl Tool for EditTool {
                 message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
             })?;
 
-        let path_buf = PathBuf::from(&args.path);
+        // `view_symbol` accepts the symbol as a `::<symbol>` suffix of the path.
+        let (path_str, symbol) = match (args.command.as_str(), &args.symbol) {
+            ("view_symbol", None) => match args.path.split_once("::") {
+                Some((path, symbol)) => (path.to_string(), Some(symbol.to_string())),
+                None => (args.path.clone(), None),
+            },
+            _ => (args.path.clone(), args.symbol.clone()),
+        };
+        let path_buf = context.resolve_path(&path_str);
         if !path_buf.is_absolute() {
             return Err(ToolError::InvalidArguments {
                 tool_name: self.get_name(),
@@ -341,28 +493,40 @@ impl Tool for EditTool {
             });
         }
 
-        self.validate_path_exists(&path_buf, &args.command)?;
+        let execution = context.execution();
+        let kind = execution.path_kind(&path_buf).await.map_err(|e| {
+            ToolError::ExecutionFailed(format!("Failed to check {}: {}", path_buf.display(), e))
+        })?;
+        self.validate_path_exists(&path_buf, kind, &args.command)?;
 
         match args.command.as_str() {
             "view" => {
-                if path_buf.is_dir() {
+                if kind == Some(PathKind::Dir) {
                     if args.view_range.is_some() {
                         return Err(ToolError::InvalidArguments {
                             tool_name: self.get_name(),
                             message: "view_range is not allowed for directory view.".to_string(),
                         });
                     }
-                    self.view_dir(&path_buf).await
+                    self.view_dir(execution, &path_buf, kind).await
                 } else {
-                    self.view_file(&path_buf, args.view_range.as_ref()).await
+                    self.view_file(execution, &path_buf, kind, args.view_range.as_ref()).await
                 }
             }
+            "outline" => self.outline_file(execution, &path_buf, kind).await,
+            "view_symbol" => {
+                let symbol = symbol.ok_or_else(|| ToolError::InvalidArguments {
+                    tool_name: self.get_name(),
+                    message: "'symbol' (or a `<path>::<symbol>` path) is required for 'view_symbol' command.".to_string(),
+                })?;
+                self.view_symbol(execution, &path_buf, kind, &symbol).await
+            }
             "create" => {
                 let content = args.file_text.ok_or_else(|| ToolError::InvalidArguments {
                     tool_name: self.get_name(),
                     message: "'file_text' is required for 'create' command.".to_string(),
                 })?;
-                fs::write(&path_buf, &content).await.map_err(|e| {
+                execution.write(&path_buf, &content).await.map_err(|e| {
                     ToolError::ExecutionFailed(format!(
                         "Failed to create file {}: {}",
                         path_buf.display(),
@@ -379,7 +543,7 @@ impl Tool for EditTool {
                 })
             }
             "str_replace" => {
-                self.validate_path_is_file(&path_buf)?;
+                self.validate_path_is_file(&path_buf, kind)?;
                 let old_s_raw = args.old_str.ok_or_else(|| ToolError::InvalidArguments {
                     tool_name: self.get_name(),
                     message: "'old_str' is required for 'str_replace'".to_string(),
@@ -389,7 +553,7 @@ impl Tool for EditTool {
                 let old_s_expanded = Self::expand_tabs(&old_s_raw);
                 let new_s_expanded = Self::expand_tabs(&new_s_raw);
 
-                let file_content_raw = fs::read_to_string(&path_buf).await.map_err(|e| {
+                let file_content_raw = execution.read_to_string(&path_buf).await.map_err(|e| {
                     ToolError::ExecutionFailed(format!(
                         "Failed to read file {}: {}",
                         path_buf.display(),
@@ -418,7 +582,7 @@ impl Tool for EditTool {
                 let new_content_expanded =
                     content_expanded.replace(&old_s_expanded, &new_s_expanded);
 
-                fs::write(&path_buf, &new_content_expanded)
+                execution.write(&path_buf, &new_content_expanded)
                     .await
                     .map_err(|e| {
                         ToolError::ExecutionFailed(format!(
@@ -458,7 +622,7 @@ impl Tool for EditTool {
                 })
             }
             "insert" => {
-                self.validate_path_is_file(&path_buf)?;
+                self.validate_path_is_file(&path_buf, kind)?;
                 let line_num_1_indexed =
                     args.insert_line
                         .ok_or_else(|| ToolError::InvalidArguments {
@@ -481,7 +645,7 @@ impl Tool for EditTool {
                 let new_lines_to_insert_expanded: Vec<String> =
                     text_to_insert_expanded.lines().map(String::from).collect();
 
-                let file_content_raw = fs::read_to_string(&path_buf).await.map_err(|e| {
+                let file_content_raw = execution.read_to_string(&path_buf).await.map_err(|e| {
                     ToolError::ExecutionFailed(format!(
                         "Failed to read file {}: {}",
                         path_buf.display(),
@@ -508,7 +672,7 @@ impl Tool for EditTool {
                 }
 
                 let new_content_expanded = lines_expanded.join("\n");
-                fs::write(&path_buf, &new_content_expanded)
+                execution.write(&path_buf, &new_content_expanded)
                     .await
                     .map_err(|e| {
                         ToolError::ExecutionFailed(format!(
@@ -553,12 +717,17 @@ impl Tool for EditTool {
             }),
         }
     }
+
+    fn determinism(&self) -> ToolDeterminism {
+        ToolDeterminism::Deterministic
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
+    use tokio::fs;
     use tokio::runtime::Runtime;
 
     fn run_async_test<F, Fut>(test_fn: F)
@@ -584,7 +753,7 @@ mod tests {
                 "path": file_path.to_str().unwrap(),
                 "file_text": "Hello, world!"
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert!(
                 result.error.is_none(),
                 "Create file should succeed. Error: {:?}",
@@ -610,7 +779,7 @@ mod tests {
                 "path": file_path.to_str().unwrap(),
                 "file_text": "new text"
             });
-            let result = tool.execute(args).await;
+            let result = tool.execute(args, &ToolContext::default()).await;
             assert!(result.is_err());
             if let Err(ToolError::ExecutionFailed(msg)) = result {
                 assert!(msg.contains("File already exists"));
@@ -634,7 +803,7 @@ mod tests {
                 "command": "view",
                 "path": file_path.to_str().unwrap()
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert!(
                 result.error.is_none(),
                 "View file should succeed. Error: {:?}",
@@ -660,7 +829,7 @@ mod tests {
                 "path": file_path.to_str().unwrap(),
                 "view_range": [2, 4]
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -678,7 +847,7 @@ mod tests {
                 "path": file_path.to_str().unwrap(),
                 "view_range": [3, -1]
             });
-            let result_to_end = tool.execute(args_to_end).await.unwrap();
+            let result_to_end = tool.execute(args_to_end, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result_to_end.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -701,16 +870,16 @@ mod tests {
             fs::write(&file_path, content).await.unwrap();
 
             let args_start_too_low = serde_json::json!({"command": "view", "path": file_path.to_str().unwrap(), "view_range": [0, 2]});
-            assert!(tool.execute(args_start_too_low).await.is_err());
+            assert!(tool.execute(args_start_too_low, &ToolContext::default()).await.is_err());
 
             let args_start_too_high = serde_json::json!({"command": "view", "path": file_path.to_str().unwrap(), "view_range": [4, 4]});
-            assert!(tool.execute(args_start_too_high).await.is_err());
+            assert!(tool.execute(args_start_too_high, &ToolContext::default()).await.is_err());
 
             let args_end_before_start = serde_json::json!({"command": "view", "path": file_path.to_str().unwrap(), "view_range": [3, 2]});
-            assert!(tool.execute(args_end_before_start).await.is_err());
+            assert!(tool.execute(args_end_before_start, &ToolContext::default()).await.is_err());
 
             let args_end_too_high = serde_json::json!({"command": "view", "path": file_path.to_str().unwrap(), "view_range": [1, 5]});
-            assert!(tool.execute(args_end_too_high).await.is_err());
+            assert!(tool.execute(args_end_too_high, &ToolContext::default()).await.is_err());
         });
     }
 
@@ -725,7 +894,7 @@ mod tests {
                 "command": "view",
                 "path": file_path.to_str().unwrap()
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -764,7 +933,7 @@ mod tests {
                 "command": "view",
                 "path": dir_path.to_str().unwrap()
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -797,7 +966,7 @@ mod tests {
                 "new_str": "Rust is amazing"
             });
 
-            let result = tool.execute(args_unique).await.unwrap();
+            let result = tool.execute(args_unique, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -823,7 +992,7 @@ mod tests {
                 "old_str": "Rust",
                 "new_str": "Monde"
             });
-            let result = tool.execute(args).await;
+            let result = tool.execute(args, &ToolContext::default()).await;
             assert!(result.is_err());
             match result.err().unwrap() {
                 ToolError::ExecutionFailed(msg) => assert!(msg.contains("not found in file")),
@@ -843,7 +1012,7 @@ mod tests {
                 "old_str": "world",
                 "new_str": "Rust"
             });
-            let result = tool.execute(args).await;
+            let result = tool.execute(args, &ToolContext::default()).await;
             assert!(result.is_err());
             match result.err().unwrap() {
                 ToolError::ExecutionFailed(msg) => assert!(msg.contains("found 3 times")),
@@ -864,7 +1033,7 @@ mod tests {
                 "old_str": "hello\t\tworld",
                 "new_str": "bye\tRust"
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -888,7 +1057,7 @@ mod tests {
                 "insert_line": 0,
                 "new_str": "NewLine 0"
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -910,7 +1079,7 @@ mod tests {
                 "insert_line": 1,
                 "new_str": "Line 2"
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -932,7 +1101,7 @@ mod tests {
                 "insert_line": 2,
                 "new_str": "Line 3"
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -954,7 +1123,7 @@ mod tests {
                 "insert_line": 1,
                 "new_str": "\tNew\tLine"
             });
-            let result = tool.execute(args).await.unwrap();
+            let result = tool.execute(args, &ToolContext::default()).await.unwrap();
             assert_eq!(
                 result.error_code, 0,
                 "Expected success. Output: {:?}, Error: {:?}",
@@ -976,7 +1145,7 @@ mod tests {
                 "command": "view",
                 "path": "relative/path.txt"
             });
-            let result = tool.execute(args).await;
+            let result = tool.execute(args, &ToolContext::default()).await;
             assert!(result.is_err());
             match result.err().unwrap() {
                 ToolError::InvalidArguments { message, .. } => {
@@ -987,6 +1156,56 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_outline_file() {
+        run_async_test(|tool, base_path| async move {
+            let file_path = base_path.join("lib.py");
+            fs::write(&file_path, "import os\n\nclass Cache:\n    def get(self, key):\n        return None\n")
+                .await
+                .unwrap();
+            let args = serde_json::json!({"command": "outline", "path": file_path.to_str().unwrap()});
+            let output = tool.execute(args, &ToolContext::default()).await.unwrap().output.unwrap();
+            assert!(output.contains("(python, 2 symbols"), "{}", output);
+            assert!(output.contains("     3-5      class Cache"), "{}", output);
+            assert!(output.contains("     4-5        def get(self, key)"), "{}", output);
+
+            let text_path = base_path.join("notes.txt");
+            fs::write(&text_path, "hello\n").await.unwrap();
+            let args = serde_json::json!({"command": "outline", "path": text_path.to_str().unwrap()});
+            match tool.execute(args, &ToolContext::default()).await.unwrap_err() {
+                ToolError::InvalidArguments { message, .. } => assert!(message.contains("unsupported file type")),
+                other => panic!("Expected InvalidArguments, got {:?}", other),
+            }
+        });
+    }
+
+    #[test]
+    fn test_view_symbol() {
+        run_async_test(|tool, base_path| async move {
+            let file_path = base_path.join("shapes.rs");
+            fs::write(
+                &file_path,
+                "pub struct Point {\n    x: i32,\n}\n\nstruct Unused;\n\nimpl Point {\n    /// Creates a point.\n    pub fn new(x: i32) -> Point {\n        Point { x }\n    }\n}\n",
+            )
+            .await
+            .unwrap();
+            fs::write(base_path.join("main.rs"), "fn main() {\n    let p = Point::new(1);\n}\n").await.unwrap();
+
+            let args = serde_json::json!({"command": "view_symbol", "path": format!("{}::Point::new", file_path.display())});
+            let output = tool.execute(args, &ToolContext::default()).await.unwrap().output.unwrap();
+            assert!(output.contains("(lines 8-11):\n     8\t    /// Creates a point.\n     9\t    pub fn new"), "{}", output);
+            assert!(output.contains("Types it uses, defined in this file:\n     1-3      pub struct Point"), "{}", output);
+            assert!(!output.contains("Unused"), "{}", output);
+            assert!(output.contains("References to 'new' under"), "{}", output);
+            assert!(output.contains("main.rs:2:20: let p = Point::new(1);"), "{}", output);
+            assert!(!output.contains("shapes.rs:9"), "{}", output);
+
+            let args = serde_json::json!({"command": "view_symbol", "path": file_path.to_str().unwrap(), "symbol": "missing"});
+            let error = tool.execute(args, &ToolContext::default()).await.unwrap_err().to_string();
+            assert!(error.contains("Defined there: Point, Unused, new"), "{}", error);
+        });
+    }
+
     #[test]
     fn test_path_not_exists_error() {
         run_async_test(|tool, base_path| async move {
@@ -995,7 +1214,7 @@ mod tests {
                 "command": "view",
                 "path": non_existent_path.to_str().unwrap()
             });
-            let result = tool.execute(args).await;
+            let result = tool.execute(args, &ToolContext::default()).await;
             assert!(result.is_err());
             match result.err().unwrap() {
                 ToolError::NotFound(msg) => {
@@ -0,0 +1,339 @@
+//! # Execution Environments
+//!
+//! Where `bash` runs its commands and the file editing tool reads and writes files. By
+//! default that is the host. With a `sandbox` config, each run gets a Docker container
+//! instead, so destructive commands cannot touch the host. The project is bind-mounted at
+//! its host path, which keeps the paths the agent sees, the patch and git working unchanged,
+//! and extra mounts and the network are as configured. The environment travels in the
+//! `ToolContext`. Besides `bash` and the editor, the reproduction command and the setup and
+//! teardown hooks run in it; the other tools still run on the host.
+
+use crate::config::{SandboxConfig, SandboxNetwork};
+use async_trait::async_trait;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// What a path is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathKind {
+    File,
+    Dir,
+    /// Exists but is neither, e.g. a socket.
+    Other,
+}
+
+/// An entry of a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub kind: PathKind,
+}
+
+/// Runs commands and accesses files for the tools; see the module docs.
+#[async_trait]
+pub trait ExecutionEnvironment: Send + Sync + fmt::Debug {
+    /// Where commands run, for logs, e.g. "the host".
+    fn describe(&self) -> String;
+
+    /// Whether this is the host, where process groups can be tracked and network isolation
+    /// applied per command.
+    fn is_host(&self) -> bool {
+        false
+    }
+
+    /// The command running `script` with `sh -c` in `working_dir`, with `env` set. Stdio is
+    /// left to the caller.
+    fn shell_command(&self, script: &str, working_dir: Option<&Path>, env: &[(&str, String)]) -> Command;
+
+    /// What `path` is, following symlinks; `None` if it does not exist.
+    async fn path_kind(&self, path: &Path) -> io::Result<Option<PathKind>>;
+
+    async fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Writes `content` to `path`, replacing it; the parent directory must exist.
+    async fn write(&self, path: &Path, content: &str) -> io::Result<()>;
+
+    /// The entries of the directory at `path`, including hidden ones, in no particular order.
+    async fn list_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+}
+
+/// The machine trae runs on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostEnvironment;
+
+fn kind_of(metadata: &std::fs::Metadata) -> PathKind {
+    if metadata.is_dir() {
+        PathKind::Dir
+    } else if metadata.is_file() {
+        PathKind::File
+    } else {
+        PathKind::Other
+    }
+}
+
+#[async_trait]
+impl ExecutionEnvironment for HostEnvironment {
+    fn describe(&self) -> String {
+        "the host".to_string()
+    }
+
+    fn is_host(&self) -> bool {
+        true
+    }
+
+    fn shell_command(&self, script: &str, working_dir: Option<&Path>, env: &[(&str, String)]) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        if let Some(dir) = working_dir {
+            cmd.current_dir(dir);
+        }
+        cmd.envs(env.iter().map(|(key, value)| (*key, value)));
+        cmd
+    }
+
+    async fn path_kind(&self, path: &Path) -> io::Result<Option<PathKind>> {
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) => Ok(Some(kind_of(&metadata))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        tokio::fs::read_to_string(path).await
+    }
+
+    async fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        tokio::fs::write(path, content).await
+    }
+
+    async fn list_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(path).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            // Symlinks are listed as what they point to, as `Path::is_dir` would.
+            let Ok(metadata) = tokio::fs::metadata(entry.path()).await else {
+                continue;
+            };
+            entries.push(DirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                kind: kind_of(&metadata),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// A Docker container started for one run and removed when dropped.
+#[derive(Debug)]
+pub struct DockerEnvironment {
+    /// The Docker CLI (`docker`, or a compatible one such as `podman`).
+    cli: String,
+    container: String,
+    image: String,
+}
+
+impl DockerEnvironment {
+    /// Starts a container from the `config` image with `project_root` mounted at its host
+    /// path as the working directory, labelled with `run_id`. `offline` cuts the network
+    /// whatever `config` says.
+    pub async fn start(config: &SandboxConfig, project_root: &Path, run_id: Option<&str>, offline: bool) -> anyhow::Result<Self> {
+        let project_root = std::path::absolute(project_root)?;
+        let args = run_args(config, &project_root, run_id, offline);
+        let output = Command::new(&config.docker)
+            .args(&args)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to run `{}`: {}. Is Docker installed?", config.docker, e))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to start the sandbox container from {}: {}",
+                config.image,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let container = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        info!(container = %container, image = %config.image, "Sandbox container started");
+        Ok(Self {
+            cli: config.docker.clone(),
+            container,
+            image: config.image.clone(),
+        })
+    }
+
+    /// A `docker exec` of `sh -c script`, with `args` as `$1`, ...
+    fn exec(&self, script: &str, args: &[&str]) -> Command {
+        let mut cmd = Command::new(&self.cli);
+        cmd.args(["exec", "-i", &self.container, "sh", "-c", script, "sh"]).args(args);
+        cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        cmd
+    }
+
+    /// Runs `script` and returns its stdout, or its stderr as an error.
+    async fn run(&self, script: &str, path: &Path, input: Option<&str>) -> io::Result<Vec<u8>> {
+        let path = path.to_string_lossy();
+        let mut cmd = self.exec(script, &[&path]);
+        if input.is_some() {
+            cmd.stdin(Stdio::piped());
+        }
+        let mut child = cmd.spawn()?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if output.status.success() {
+            return Ok(output.stdout);
+        }
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let kind = if message.contains("No such file") { io::ErrorKind::NotFound } else { io::ErrorKind::Other };
+        Err(io::Error::new(kind, message))
+    }
+}
+
+/// The `docker run` arguments of a sandbox container; see `DockerEnvironment::start`.
+fn run_args(config: &SandboxConfig, project_root: &Path, run_id: Option<&str>, offline: bool) -> Vec<String> {
+    let project = project_root.display().to_string();
+    let network = match (offline, config.network) {
+        (true, _) | (_, SandboxNetwork::None) => "none",
+        (false, SandboxNetwork::Bridge) => "bridge",
+        (false, SandboxNetwork::Host) => "host",
+    };
+    let mut args: Vec<String> = ["run", "--detach", "--rm", "--init"].map(String::from).to_vec();
+    if let Some(run_id) = run_id {
+        args.extend(["--label".to_string(), format!("trae.run_id={}", run_id)]);
+    }
+    // Files the agent creates in the project belong to the project's owner, not root.
+    #[cfg(unix)]
+    if let Ok(metadata) = std::fs::metadata(project_root) {
+        use std::os::unix::fs::MetadataExt;
+        args.extend(["--user".to_string(), format!("{}:{}", metadata.uid(), metadata.gid())]);
+    }
+    args.extend(["--network".to_string(), network.to_string()]);
+    args.extend(["--volume".to_string(), format!("{}:{}", project, project)]);
+    for mount in &config.mounts {
+        args.extend(["--volume".to_string(), mount.clone()]);
+    }
+    args.extend(["--workdir".to_string(), project]);
+    // Keeps the container alive for `docker exec`, whatever the image's entrypoint.
+    args.extend(["--entrypoint", "tail"].map(String::from));
+    args.push(config.image.clone());
+    args.extend(["-f", "/dev/null"].map(String::from));
+    args
+}
+
+impl Drop for DockerEnvironment {
+    fn drop(&mut self) {
+        let removed = std::process::Command::new(&self.cli)
+            .args(["rm", "--force", &self.container])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if !removed.is_ok_and(|status| status.success()) {
+            warn!(container = %self.container, "Failed to remove the sandbox container");
+        }
+    }
+}
+
+#[async_trait]
+impl ExecutionEnvironment for DockerEnvironment {
+    fn describe(&self) -> String {
+        format!("a Docker container ({})", self.image)
+    }
+
+    fn shell_command(&self, script: &str, working_dir: Option<&Path>, env: &[(&str, String)]) -> Command {
+        let mut cmd = Command::new(&self.cli);
+        cmd.arg("exec");
+        if let Some(dir) = working_dir {
+            cmd.arg("--workdir").arg(dir);
+        }
+        for (key, value) in env {
+            cmd.arg("--env").arg(format!("{}={}", key, value));
+        }
+        cmd.args([&self.container, "sh", "-c", script]);
+        cmd
+    }
+
+    async fn path_kind(&self, path: &Path) -> io::Result<Option<PathKind>> {
+        let script = r#"if [ -d "$1" ]; then echo dir; elif [ -f "$1" ]; then echo file; elif [ -e "$1" ]; then echo other; fi"#;
+        let output = self.run(script, path, None).await?;
+        Ok(match String::from_utf8_lossy(&output).trim() {
+            "dir" => Some(PathKind::Dir),
+            "file" => Some(PathKind::File),
+            "other" => Some(PathKind::Other),
+            _ => None,
+        })
+    }
+
+    async fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let output = self.run(r#"cat -- "$1""#, path, None).await?;
+        String::from_utf8(output).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8"))
+    }
+
+    async fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        self.run(r#"cat > "$1""#, path, Some(content)).await.map(|_| ())
+    }
+
+    async fn list_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let script = r#"[ -d "$1" ] || { echo "$1: No such file or directory" >&2; exit 1; }
+for f in "$1"/* "$1"/.[!.]* "$1"/..?*; do
+  [ -e "$f" ] || continue
+  if [ -d "$f" ]; then k=d; elif [ -f "$f" ]; then k=f; else k=o; fi
+  printf '%s %s\n' "$k" "${f##*/}"
+done"#;
+        let output = self.run(script, path, None).await?;
+        Ok(String::from_utf8_lossy(&output)
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(kind, name)| DirEntry {
+                name: name.to_string(),
+                kind: match kind {
+                    "d" => PathKind::Dir,
+                    "f" => PathKind::File,
+                    _ => PathKind::Other,
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_host_environment_and_sandbox_container_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let host = HostEnvironment;
+        host.write(&dir.path().join("a.txt"), "hello").await.unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        assert_eq!(host.path_kind(&dir.path().join("a.txt")).await.unwrap(), Some(PathKind::File));
+        assert_eq!(host.path_kind(&dir.path().join("missing")).await.unwrap(), None);
+        let mut entries = host.list_dir(dir.path()).await.unwrap();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(entries.iter().map(|e| (e.name.as_str(), e.kind)).collect::<Vec<_>>(), [("a.txt", PathKind::File), ("src", PathKind::Dir)]);
+        let output = host
+            .shell_command("cat a.txt; echo \" $GREETING\"", Some(dir.path()), &[("GREETING", "world".to_string())])
+            .output()
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello world\n");
+
+        let config = SandboxConfig {
+            image: "python:3.12".to_string(),
+            mounts: vec!["/data:/data:ro".to_string()],
+            network: SandboxNetwork::Bridge,
+            docker: "docker".to_string(),
+        };
+        let args = run_args(&config, Path::new("/work/app"), Some("run-1"), false).join(" ");
+        assert!(args.starts_with("run --detach --rm --init --label trae.run_id=run-1 "), "{}", args);
+        assert!(args.contains("--network bridge --volume /work/app:/work/app --volume /data:/data:ro --workdir /work/app"), "{}", args);
+        assert!(args.ends_with("--entrypoint tail python:3.12 -f /dev/null"), "{}", args);
+        assert!(run_args(&config, Path::new("/work/app"), None, true).join(" ").contains("--network none"));
+    }
+}
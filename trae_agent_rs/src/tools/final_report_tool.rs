@@ -0,0 +1,187 @@
+//! # Final Report
+//!
+//! The agent delivers its user-facing report through the `final_report` tool instead of free
+//! text: what it changed, how it verified the change, and the risks that remain. The report is
+//! taken from the tool call's arguments and kept as structured data in `AgentExecution`, so
+//! the CLI and commit messages render it without scraping the last assistant message.
+
+use super::base::{Tool, ToolDeterminism, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::agent::base_agent::AgentStep;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Name of the tool, as the LLM calls it.
+pub const FINAL_REPORT_TOOL: &str = "final_report";
+
+/// The agent's report on a finished task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FinalReport {
+    /// What was done, in a sentence or two.
+    pub summary: String,
+    /// The individual changes made, one per entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+    /// How the changes were verified (tests run, commands tried).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<String>,
+    /// Risks and open questions that remain.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub risks: Vec<String>,
+}
+
+impl FinalReport {
+    /// Reads a report from the arguments of a `final_report` call. `changes` and `risks` are
+    /// given one item per line; list markers are dropped.
+    ///
+    /// # Returns
+    /// The report, or a message for the LLM if the summary is missing.
+    pub fn from_arguments(arguments: &Value) -> Result<Self, String> {
+        let text = |key: &str| {
+            arguments
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let items = |key: &str| {
+            text(key)
+                .map(|list| {
+                    list.lines()
+                        .map(|line| line.trim().trim_start_matches(['-', '*', '•']).trim())
+                        .filter(|line| !line.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let summary = text("summary").ok_or("'summary' is required and must not be empty.")?;
+        Ok(Self {
+            summary,
+            changes: items("changes"),
+            verification: text("verification"),
+            risks: items("risks"),
+        })
+    }
+
+    /// The last report the agent delivered in `steps`, if any.
+    pub fn from_steps(steps: &[AgentStep]) -> Option<Self> {
+        steps
+            .iter()
+            .rev()
+            .filter_map(|step| step.llm_response.as_ref()?.choices.first()?.message.tool_calls.as_ref())
+            .flat_map(|calls| calls.iter().rev())
+            .filter(|call| call.function.name == FINAL_REPORT_TOOL)
+            .find_map(|call| {
+                let arguments: Value = serde_json::from_str(&call.function.arguments).ok()?;
+                Self::from_arguments(&arguments).ok()
+            })
+    }
+
+    /// The report as Markdown: the summary, then a section for each part that is present.
+    pub fn to_markdown(&self) -> String {
+        let mut out = self.summary.clone();
+        let list = |items: &[String]| items.iter().map(|item| format!("- {}", item)).collect::<Vec<_>>().join("\n");
+        if !self.changes.is_empty() {
+            out.push_str(&format!("\n\n**Changes**\n{}", list(&self.changes)));
+        }
+        if let Some(verification) = &self.verification {
+            out.push_str(&format!("\n\n**Verification**\n{}", verification));
+        }
+        if !self.risks.is_empty() {
+            out.push_str(&format!("\n\n**Residual risks**\n{}", list(&self.risks)));
+        }
+        out
+    }
+}
+
+/// Delivers the final report; see `FinalReport`.
+pub struct FinalReportTool;
+
+impl FinalReportTool {
+    pub fn new() -> Self {
+        FinalReportTool
+    }
+}
+
+#[async_trait]
+impl Tool for FinalReportTool {
+    fn get_name(&self) -> String {
+        FINAL_REPORT_TOOL.to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Delivers your final report to the user: what you did, the changes you made, how you \
+        verified them and the risks that remain. Call it once the work is finished, before (or \
+        together with) task_done; the report is what the user reads, so be specific."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        let text = |name: &str, description: &str, is_required: bool| ToolParameter {
+            name: name.to_string(),
+            param_type: "string".to_string(),
+            description: description.to_string(),
+            is_required,
+            enum_values: None,
+            items: None,
+            properties: None,
+            required: vec![],
+        };
+        vec![
+            text("summary", "What was done, in one or two sentences.", true),
+            text("changes", "The changes made, one per line (e.g., 'src/parser.rs: handle empty input').", false),
+            text("verification", "How the changes were verified: tests and commands run, and their outcome.", false),
+            text("risks", "Remaining risks, limitations or open questions, one per line.", false),
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let report = FinalReport::from_arguments(&arguments).map_err(|message| ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message,
+        })?;
+        Ok(ToolExecResult::new_success(
+            Some(format!(
+                "Final report recorded ({} change(s), {} risk(s)). Call task_done if the task is complete.",
+                report.changes.len(),
+                report.risks.len()
+            )),
+            None,
+        ))
+    }
+
+    fn determinism(&self) -> ToolDeterminism {
+        ToolDeterminism::Deterministic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_report_from_arguments_and_markdown() {
+        let arguments = json!({
+            "summary": "Fixed the parser crash on empty input.",
+            "changes": "- src/parser.rs: return early on empty input\n\n* tests/parser.rs: add a regression test",
+            "verification": "cargo test passes.",
+            "risks": ""
+        });
+        let report = FinalReport::from_arguments(&arguments).unwrap();
+        assert_eq!(report.changes, ["src/parser.rs: return early on empty input", "tests/parser.rs: add a regression test"]);
+        assert!(report.risks.is_empty());
+        assert_eq!(
+            report.to_markdown(),
+            "Fixed the parser crash on empty input.\n\n**Changes**\n- src/parser.rs: return early on empty input\n\
+            - tests/parser.rs: add a regression test\n\n**Verification**\ncargo test passes."
+        );
+
+        let tool = FinalReportTool::new();
+        assert!(tool.execute(arguments, &ToolContext::default()).await.is_ok());
+        assert!(tool.execute(json!({"summary": "  "}), &ToolContext::default()).await.is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! # Corrective Hints
+//!
+//! Tailored advice appended by `ToolExecutor` to common tool errors, e.g. a non-unique
+//! `old_str`, a relative path or an out-of-bounds `view_range`. Telling the model how to fix
+//! the call, rather than only that it failed, cuts down the retry loops these mistakes cause.
+
+/// Name of the file editing tool whose errors most hints concern.
+const EDIT_TOOL: &str = "str_replace_based_edit_tool";
+
+/// A hint: the tool it applies to (`None` for any), fragments of the error message it
+/// recognises, and the advice.
+struct Hint {
+    tool: Option<&'static str>,
+    patterns: &'static [&'static str],
+    advice: &'static str,
+}
+
+const HINTS: &[Hint] = &[
+    Hint {
+        tool: Some(EDIT_TOOL),
+        patterns: &["Replacement must be unique"],
+        advice: "old_str must match exactly one place in the file. Include more surrounding lines \
+            (e.g. the enclosing function signature or the lines before and after) to make it unique.",
+    },
+    Hint {
+        tool: Some(EDIT_TOOL),
+        patterns: &["not found in file"],
+        advice: "old_str must match the file exactly, including whitespace and indentation. View the \
+            relevant lines first and copy them verbatim, without the line numbers of the view output.",
+    },
+    Hint {
+        tool: None,
+        patterns: &["must be absolute", "is not an absolute path"],
+        advice: "Paths must be absolute: join the relative path with the project directory, \
+            e.g. /path/to/project/src/main.rs.",
+    },
+    Hint {
+        tool: Some(EDIT_TOOL),
+        patterns: &["Invalid start_line", "Invalid end_line", "view_range must contain"],
+        advice: "view_range is [start, end] with 1-indexed, inclusive line numbers inside the file; \
+            use -1 as end to read to the end of the file. View the file without view_range to see \
+            how many lines it has.",
+    },
+    Hint {
+        tool: Some(EDIT_TOOL),
+        patterns: &["insert_line"],
+        advice: "insert_line is the line to insert after: 0 inserts at the top of the file and the \
+            file's line count appends at the end.",
+    },
+    Hint {
+        tool: Some(EDIT_TOOL),
+        patterns: &["does not exist for command"],
+        advice: "Check the path by viewing its parent directory. Use the `create` command to make a \
+            new file.",
+    },
+    Hint {
+        tool: Some(EDIT_TOOL),
+        patterns: &["Cannot overwrite files using command `create`"],
+        advice: "Use `str_replace` or `insert` to change an existing file.",
+    },
+    Hint {
+        tool: None,
+        patterns: &["Invalid JSON arguments"],
+        advice: "Arguments must be one JSON object. Escape quotes, backslashes and newlines inside \
+            string values.",
+    },
+];
+
+/// The corrective hint for an error of `tool_name`, if the error is a known misuse.
+pub fn corrective_hint(tool_name: &str, error: &str) -> Option<&'static str> {
+    HINTS
+        .iter()
+        .find(|hint| {
+            hint.tool.is_none_or(|tool| tool == tool_name)
+                && hint.patterns.iter().any(|pattern| error.contains(pattern))
+        })
+        .map(|hint| hint.advice)
+}
+
+/// `error` with its corrective hint appended, if there is one.
+pub fn with_hint(tool_name: &str, error: String) -> String {
+    match corrective_hint(tool_name, &error) {
+        Some(advice) => format!("{}\nHint: {}", error, advice),
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hints_match_common_misuse() {
+        let duplicate = "Tool execution failed: Pattern 'x' (with tabs expanded to 8 spaces) found 2 times in file /a.rs. Replacement must be unique.";
+        assert!(with_hint(EDIT_TOOL, duplicate.to_string()).ends_with(
+            "Replacement must be unique.\nHint: old_str must match exactly one place in the file. Include more surrounding lines \
+            (e.g. the enclosing function signature or the lines before and after) to make it unique."
+        ));
+        assert!(corrective_hint(EDIT_TOOL, "Path 'src/a.rs' must be absolute.").unwrap().starts_with("Paths must be absolute"));
+        assert!(corrective_hint("json_edit_tool", "Path 'a.json' must be absolute.").is_some());
+        assert!(corrective_hint(EDIT_TOOL, "Invalid start_line 9 for file with 3 lines.").unwrap().contains("-1"));
+        // Edit-tool hints only apply to the edit tool, and unknown errors are left alone.
+        assert_eq!(corrective_hint("bash", "grep: not found in file"), None);
+        assert_eq!(with_hint("bash", "exit status 1".to_string()), "exit status 1");
+    }
+}
@@ -0,0 +1,374 @@
+//! # Image Diff
+//!
+//! Verifying a fix to a visual regression means comparing screenshots. `image_diff` compares
+//! two PNG images pixel by pixel and reports how much differs and where: the share of
+//! differing pixels and the bounding boxes of the regions that changed. It can also write a
+//! diff image (the second image dimmed, differing pixels in red) to the scratch directory.
+
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Side of the square cells differing pixels are grouped by; changes in touching cells form
+/// one region.
+const CELL: usize = 8;
+/// Most regions listed.
+const MAX_REGIONS: usize = 20;
+
+#[derive(Deserialize, Debug)]
+struct ImageDiffArgs {
+    before: String,
+    after: String,
+    #[serde(default)]
+    tolerance: u8,
+    #[serde(default)]
+    diff_image: bool,
+}
+
+/// A decoded image, as 8-bit RGBA.
+struct Image {
+    width: usize,
+    height: usize,
+    rgba: Vec<u8>,
+}
+
+impl Image {
+    fn load(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder
+            .read_info()
+            .map_err(|e| format!("{} is not a readable PNG image: {}", path.display(), e))?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader
+            .next_frame(&mut buffer)
+            .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+        buffer.truncate(frame.buffer_size());
+        let rgba = match frame.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+            png::ColorType::Indexed => return Err(format!("Unsupported indexed PNG: {}", path.display())),
+        };
+        Ok(Image {
+            width: frame.width as usize,
+            height: frame.height as usize,
+            rgba,
+        })
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> &[u8] {
+        let start = (y * self.width + x) * 4;
+        &self.rgba[start..start + 4]
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.rgba))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// A rectangle of differing pixels: the bounds (inclusive) and how many pixels in it differ.
+#[derive(Debug, Clone, PartialEq)]
+struct Region {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+    pixels: usize,
+}
+
+impl Region {
+    fn add(&mut self, other: &Region) {
+        self.left = self.left.min(other.left);
+        self.top = self.top.min(other.top);
+        self.right = self.right.max(other.right);
+        self.bottom = self.bottom.max(other.bottom);
+        self.pixels += other.pixels;
+    }
+}
+
+/// The comparison of two images over the area they share.
+struct Comparison {
+    width: usize,
+    height: usize,
+    differing: Vec<bool>,
+    regions: Vec<Region>,
+}
+
+/// Compares `before` and `after` over their common area. A pixel differs when any channel
+/// differs by more than `tolerance`.
+fn compare(before: &Image, after: &Image, tolerance: u8) -> Comparison {
+    let width = before.width.min(after.width);
+    let height = before.height.min(after.height);
+    let mut differing = vec![false; width * height];
+    let (columns, rows) = (width.div_ceil(CELL), height.div_ceil(CELL));
+    // The differing pixels of each cell, as a region of the cell.
+    let mut cells: Vec<Option<Region>> = vec![None; columns * rows];
+    for y in 0..height {
+        for x in 0..width {
+            let differs = before
+                .pixel(x, y)
+                .iter()
+                .zip(after.pixel(x, y))
+                .any(|(a, b)| a.abs_diff(*b) > tolerance);
+            if !differs {
+                continue;
+            }
+            differing[y * width + x] = true;
+            let pixel = Region {
+                left: x,
+                top: y,
+                right: x,
+                bottom: y,
+                pixels: 1,
+            };
+            match &mut cells[(y / CELL) * columns + x / CELL] {
+                Some(cell) => cell.add(&pixel),
+                cell => *cell = Some(pixel),
+            }
+        }
+    }
+
+    // Touching cells (including diagonally) form one region.
+    let mut regions = Vec::new();
+    let mut seen = vec![false; cells.len()];
+    for start in 0..cells.len() {
+        if seen[start] || cells[start].is_none() {
+            continue;
+        }
+        seen[start] = true;
+        let mut region = cells[start].clone().unwrap();
+        let mut stack = vec![start];
+        while let Some(index) = stack.pop() {
+            let (column, row) = (index % columns, index / columns);
+            for next_row in row.saturating_sub(1)..=(row + 1).min(rows - 1) {
+                for next_column in column.saturating_sub(1)..=(column + 1).min(columns - 1) {
+                    let next = next_row * columns + next_column;
+                    if let (false, Some(cell)) = (seen[next], &cells[next]) {
+                        seen[next] = true;
+                        region.add(cell);
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions.sort_by_key(|region| std::cmp::Reverse(region.pixels));
+    Comparison {
+        width,
+        height,
+        differing,
+        regions,
+    }
+}
+
+/// `after`, cropped to the compared area and dimmed, with the differing pixels in red.
+fn diff_image(after: &Image, comparison: &Comparison) -> Image {
+    let mut rgba = Vec::with_capacity(comparison.width * comparison.height * 4);
+    for y in 0..comparison.height {
+        for x in 0..comparison.width {
+            if comparison.differing[y * comparison.width + x] {
+                rgba.extend([255, 0, 0, 255]);
+            } else {
+                let p = after.pixel(x, y);
+                rgba.extend([p[0] / 3 + 170, p[1] / 3 + 170, p[2] / 3 + 170, 255]);
+            }
+        }
+    }
+    Image {
+        width: comparison.width,
+        height: comparison.height,
+        rgba,
+    }
+}
+
+/// Compares two PNG screenshots; see the module docs.
+pub struct ImageDiffTool;
+
+impl ImageDiffTool {
+    pub fn new() -> Self {
+        ImageDiffTool
+    }
+}
+
+#[async_trait]
+impl Tool for ImageDiffTool {
+    fn get_name(&self) -> String {
+        "image_diff".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Compares two PNG images (e.g., screenshots before and after a UI change) pixel by pixel. \
+        Reports the share of pixels that differ and the bounding boxes (x, y, width, height) of the \
+        regions that changed, largest first. With diff_image, also writes an image of the \
+        differences (in red) to the scratch directory."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        let param = |name: &str, param_type: &str, description: &str, is_required: bool| ToolParameter {
+            name: name.to_string(),
+            param_type: param_type.to_string(),
+            description: description.to_string(),
+            is_required,
+            enum_values: None,
+            items: None,
+            properties: None,
+            required: vec![],
+        };
+        vec![
+            param("before", "string", "Path to the first PNG image (e.g., the expected screenshot).", true),
+            param("after", "string", "Path to the second PNG image (e.g., the current screenshot).", true),
+            param(
+                "tolerance",
+                "integer",
+                "Largest difference per color channel (0-255) still counted as equal, to ignore anti-aliasing noise (default 0).",
+                false,
+            ),
+            param("diff_image", "boolean", "Write an image of the differences to the scratch directory (default false).", false),
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: ImageDiffArgs = serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+        })?;
+        let before_path = context.resolve_path(&args.before);
+        let after_path = context.resolve_path(&args.after);
+        let diff_path: Option<PathBuf> = match (&context.scratch_dir, args.diff_image) {
+            (Some(scratch), true) => {
+                let stem = after_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                Some(scratch.join("image_diff").join(format!("{}.diff.png", stem)))
+            }
+            _ => None,
+        };
+        let result = tokio::task::spawn_blocking(move || -> Result<String, String> {
+            let before = Image::load(&before_path)?;
+            let after = Image::load(&after_path)?;
+            let comparison = compare(&before, &after, args.tolerance);
+            let total = comparison.width * comparison.height;
+            let changed: usize = comparison.regions.iter().map(|region| region.pixels).sum();
+            let mut out = format!(
+                "{}: {}x{}, {}: {}x{}\n",
+                before_path.display(),
+                before.width,
+                before.height,
+                after_path.display(),
+                after.width,
+                after.height
+            );
+            if (before.width, before.height) != (after.width, after.height) {
+                out.push_str(&format!(
+                    "The sizes differ; compared the top-left {}x{} area they share.\n",
+                    comparison.width, comparison.height
+                ));
+            }
+            let percent = if total == 0 { 0.0 } else { changed as f64 * 100.0 / total as f64 };
+            out.push_str(&format!(
+                "{} of {} pixels differ ({:.2}%), tolerance {}.",
+                changed, total, percent, args.tolerance
+            ));
+            if !comparison.regions.is_empty() {
+                out.push_str(&format!(
+                    "\n{} region(s) of difference (x, y, width, height), largest first:",
+                    comparison.regions.len()
+                ));
+                for region in comparison.regions.iter().take(MAX_REGIONS) {
+                    out.push_str(&format!(
+                        "\n  ({}, {}, {}, {}): {} pixels",
+                        region.left,
+                        region.top,
+                        region.right - region.left + 1,
+                        region.bottom - region.top + 1,
+                        region.pixels
+                    ));
+                }
+                if comparison.regions.len() > MAX_REGIONS {
+                    out.push_str(&format!("\n  ... and {} smaller regions", comparison.regions.len() - MAX_REGIONS));
+                }
+            }
+            if let Some(path) = diff_path {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                diff_image(&after, &comparison).save(&path)?;
+                out.push_str(&format!("\nDiff image: {}", path.display()));
+            } else if args.diff_image {
+                out.push_str("\nNo diff image: this run has no scratch directory.");
+            }
+            Ok(out)
+        })
+        .await
+        .map_err(|e| ToolError::InternalError(e.to_string()))?;
+        Ok(match result {
+            Ok(output) => ToolExecResult::new_success(Some(output), None),
+            Err(message) => ToolExecResult::new_failure(message, 1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_differences_are_counted_and_boxed() {
+        let dir = tempfile::tempdir().unwrap();
+        let white = || Image {
+            width: 40,
+            height: 30,
+            rgba: vec![255; 40 * 30 * 4],
+        };
+        white().save(&dir.path().join("before.png")).unwrap();
+        let mut after = white();
+        // A 2x2 block and, far from it, one slightly lighter pixel.
+        for (x, y) in [(3, 4), (4, 4), (3, 5), (4, 5)] {
+            after.rgba[(y * 40 + x) * 4..][..3].copy_from_slice(&[0, 0, 0]);
+        }
+        after.rgba[(25 * 40 + 35) * 4] = 250;
+        after.save(&dir.path().join("after.png")).unwrap();
+
+        let scratch = dir.path().join("scratch");
+        let context = ToolContext {
+            scratch_dir: Some(scratch.clone()),
+            ..ToolContext::for_project(dir.path())
+        };
+        let tool = ImageDiffTool::new();
+        let output = tool
+            .execute(json!({"before": "before.png", "after": "after.png", "diff_image": true}), &context)
+            .await
+            .unwrap()
+            .output
+            .unwrap();
+        assert!(output.contains("5 of 1200 pixels differ (0.42%), tolerance 0."), "{}", output);
+        assert!(output.contains("2 region(s)"), "{}", output);
+        assert!(output.contains("(3, 4, 2, 2): 4 pixels\n  (35, 25, 1, 1): 1 pixels"), "{}", output);
+        let diff = Image::load(&scratch.join("image_diff/after.diff.png")).unwrap();
+        assert_eq!(diff.pixel(3, 4), [255, 0, 0, 255]);
+
+        let output = tool
+            .execute(json!({"before": "before.png", "after": "after.png", "tolerance": 10}), &context)
+            .await
+            .unwrap()
+            .output
+            .unwrap();
+        assert!(output.contains("4 of 1200 pixels differ"), "{}", output);
+    }
+}
@@ -7,7 +7,8 @@ use std::fs;
 use std::path::Path;
 // Removed direct Selector and PathParser imports, will use top-level jsonpath_lib::select
 
-use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::base::{Tool, ToolDeterminism, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
 
 #[derive(Deserialize, Debug)]
 struct JsonEditToolArgs {
@@ -141,13 +142,14 @@ impl Tool for JsonEditTool {
         ]
     }
 
-    async fn execute(&self, arguments: JsonValue) -> Result<ToolExecResult, ToolError> {
-        let args: JsonEditToolArgs = serde_json::from_value(arguments.clone()).map_err(|e| {
+    async fn execute(&self, arguments: JsonValue, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let mut args: JsonEditToolArgs = serde_json::from_value(arguments.clone()).map_err(|e| {
             ToolError::InvalidArguments {
                 tool_name: self.get_name(),
                 message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
             }
         })?;
+        args.file_path = context.resolve_path(&args.file_path).to_string_lossy().into_owned();
 
         let pretty = args.pretty_print.unwrap_or(true);
 
@@ -356,6 +358,10 @@ impl Tool for JsonEditTool {
             }),
         }
     }
+
+    fn determinism(&self) -> ToolDeterminism {
+        ToolDeterminism::Deterministic
+    }
 }
 
 #[cfg(test)]
@@ -380,7 +386,7 @@ mod tests {
             "file_path": temp_file.path().to_str().unwrap(),
             "pretty_print": true
         });
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(result.error_code, 0, "Expected success (error_code 0), got error: {:?}", result.error);
         let expected_output = format!("JSON content of {}:\n{}", temp_file.path().to_str().unwrap(), serde_json::to_string_pretty(&serde_json::from_str::<JsonValue>(json_content).unwrap()).unwrap());
         assert_eq!(result.output.unwrap(), expected_output);
@@ -396,7 +402,7 @@ mod tests {
             "file_path": temp_file.path().to_str().unwrap(),
             "json_path": "$.data.value"
         });
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(result.error_code, 0, "Expected success (error_code 0), got error: {:?}", result.error);
         let expected_json_match = serde_json::to_string_pretty(&JsonValue::String("target".to_string())).unwrap();
         let expected_output = format!("JSONPath '$.data.value' matches:\n{}", expected_json_match);
@@ -413,7 +419,7 @@ mod tests {
             "file_path": temp_file.path().to_str().unwrap(),
             "json_path": "$.nonexistent"
         });
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(result.error_code, 0, "Expected success (error_code 0) for path not found, got error: {:?}", result.error); // View operation is successful even if path not found
         assert_eq!(result.output.unwrap(), "No matches found for JSONPath: $.nonexistent");
     }
@@ -425,7 +431,7 @@ mod tests {
             "operation": "view",
             "file_path": "/absolute/path/to/nonexistent/file.json"
         });
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
         match result.err().unwrap() {
             ToolError::FileNotFound(path) => assert_eq!(path, "/absolute/path/to/nonexistent/file.json"),
@@ -441,7 +447,7 @@ mod tests {
             "operation": "view",
             "file_path": temp_file.path().to_str().unwrap()
         });
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
          match result.err().unwrap() {
             ToolError::InvalidJson(msg) => assert!(msg.contains("Invalid JSON in file")),
@@ -461,7 +467,7 @@ mod tests {
     //         "json_path": "$.key",
     //         "value": "new_value"
     //     });
-    //     let result = tool.execute(args).await;
+    //     let result = tool.execute(args, &ToolContext::default()).await;
     //     assert!(result.is_err());
     //     match result.err().unwrap() {
     //         ToolError::NotImplemented(msg) => assert!(msg.contains("Operation 'set' is not yet implemented")),
@@ -488,7 +494,7 @@ mod tests {
             "pretty_print": false // for easier comparison
         });
 
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(result.error_code, 0, "Expected success, got error: {:?}", result.error);
 
         let file_content = read_file_content(temp_file.path());
@@ -510,7 +516,7 @@ mod tests {
             "pretty_print": false
         });
 
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(result.error_code, 0, "Expected success, got error: {:?}", result.error);
 
         let file_content = read_file_content(temp_file.path());
@@ -531,7 +537,7 @@ mod tests {
             "pretty_print": false
         });
 
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         // jsonpath_lib replace_with will not error if path not found, it just won't change anything.
         assert_eq!(result.error_code, 0, "Expected success, got error: {:?}", result.error);
 
@@ -550,7 +556,7 @@ mod tests {
             "json_path": "$.key"
             // "value" is missing
         });
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
         match result.err().unwrap() {
             ToolError::InvalidArguments { message, .. } => assert!(message.contains("'value' is required for 'set' operation.")),
@@ -568,7 +574,7 @@ mod tests {
             "value": "new_value"
             // "json_path" is missing
         });
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
         match result.err().unwrap() {
             ToolError::InvalidArguments { message, .. } => assert!(message.contains("'json_path' is required for 'set' operation.")),
@@ -588,7 +594,7 @@ mod tests {
             "pretty_print": false
         });
 
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(result.error_code, 0, "Expected success, got error: {:?}", result.error);
 
         let file_content = read_file_content(temp_file.path());
@@ -609,7 +615,7 @@ mod tests {
             "pretty_print": false
         });
 
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert_eq!(result.error_code, 0, "Expected success, got error: {:?}", result.error);
 
         let file_content = read_file_content(temp_file.path());
@@ -630,7 +636,7 @@ mod tests {
             "pretty_print": false
         });
 
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         // jsonpath_lib::delete will not error if path not found, it just won't change anything.
         assert_eq!(result.error_code, 0, "Expected success, got error: {:?}", result.error);
 
@@ -647,7 +653,7 @@ mod tests {
             "file_path": temp_file.path().to_str().unwrap()
             // "json_path" is missing
         });
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
         match result.err().unwrap() {
             ToolError::InvalidArguments { message, .. } => assert!(message.contains("'json_path' is required for 'remove' operation.")),
@@ -668,7 +674,7 @@ mod tests {
             "value": "completed",
             "pretty_print": false
         });
-        let result_update = tool.execute(args_update_existing).await.unwrap();
+        let result_update = tool.execute(args_update_existing, &ToolContext::default()).await.unwrap();
         assert_eq!(result_update.error_code, 0, "Update existing via 'add' failed: {:?}", result_update.error);
         let file_content_update = read_file_content(temp_file_update.path());
         let expected_json_update = r#"{"name":"test","status":"completed"}"#;
@@ -685,7 +691,7 @@ mod tests {
             "value": "new_description",
             "pretty_print": false
         });
-        let result_no_create = tool.execute(args_no_create).await.unwrap();
+        let result_no_create = tool.execute(args_no_create, &ToolContext::default()).await.unwrap();
         assert_eq!(result_no_create.error_code, 0, "Add to non-existent path should succeed with no change: {:?}", result_no_create.error);
         let file_content_no_create = read_file_content(temp_file_no_create.path());
         assert_eq!(file_content_no_create.trim(), initial_json_no_create, "File content should be unchanged when adding to non-existent path.");
@@ -701,7 +707,7 @@ mod tests {
             "json_path": "$.key"
             // "value" is missing
         });
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
         match result.err().unwrap() {
             ToolError::InvalidArguments { message, .. } => assert!(message.contains("'value' is required for 'add' operation.")),
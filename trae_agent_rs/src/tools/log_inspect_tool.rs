@@ -0,0 +1,478 @@
+//! # Log Inspection
+//!
+//! Build logs and CI output are often far larger than the context window. `log_inspect` reads
+//! them from disk line by line and returns only what was asked for: the lines matching a
+//! pattern with the lines around them, the last lines, or an LLM-written summary, optionally
+//! restricted to a time window.
+
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::llm::base_client::LLMMessage;
+use crate::llm::{LLMClient, MessageRole};
+use crate::utils::offline::is_local_url;
+use async_trait::async_trait;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Lines of context shown around each match, unless the call says otherwise.
+const DEFAULT_CONTEXT_LINES: usize = 3;
+/// Matches shown, unless the call says otherwise.
+const DEFAULT_MAX_MATCHES: usize = 50;
+/// Lines returned by `tail`, unless the call says otherwise.
+const DEFAULT_TAIL_LINES: usize = 100;
+/// Longest output returned, in characters; the rest is cut with a note.
+const MAX_OUTPUT_CHARS: usize = 20_000;
+/// Most log text sent to the LLM for a summary, in characters.
+const MAX_SUMMARY_INPUT_CHARS: usize = 60_000;
+
+const SUMMARY_PROMPT: &str = "You summarize log files for a software engineering agent. \
+Report what ran, what failed and why, with the exact error messages, file paths and line \
+numbers that appear in the log. Be brief; do not speculate beyond the log.";
+
+#[derive(Deserialize, Debug)]
+struct LogInspectArgs {
+    command: String,
+    path: String,
+    pattern: Option<String>,
+    context_lines: Option<usize>,
+    max_matches: Option<usize>,
+    lines: Option<usize>,
+    since: Option<String>,
+    until: Option<String>,
+    focus: Option<String>,
+}
+
+/// Greps, tails and summarizes log files on disk; see the module docs.
+pub struct LogInspectTool {
+    summarizer: Option<Arc<dyn LLMClient>>,
+    /// The summarizer's `base_url`; `None` is the provider's public API.
+    summarizer_endpoint: Option<String>,
+}
+
+impl LogInspectTool {
+    /// Creates the tool without a summarizer; `summarize` then reports that it is unavailable.
+    pub fn new() -> Self {
+        LogInspectTool {
+            summarizer: None,
+            summarizer_endpoint: None,
+        }
+    }
+
+    /// Uses `client`, whose API is at `base_url` (the provider's public API if `None`), for
+    /// the `summarize` command.
+    pub fn with_summarizer(mut self, client: Arc<dyn LLMClient>, base_url: Option<&str>) -> Self {
+        self.summarizer = Some(client);
+        self.summarizer_endpoint = base_url.map(str::to_string);
+        self
+    }
+
+    fn invalid(&self, message: impl Into<String>) -> ToolError {
+        ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message: message.into(),
+        }
+    }
+}
+
+/// The time window lines must fall in, as normalized timestamps (see `line_timestamp`).
+#[derive(Debug, Default)]
+struct TimeWindow {
+    since: Option<String>,
+    until: Option<String>,
+}
+
+impl TimeWindow {
+    fn is_set(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    /// Whether a line stamped `timestamp` is inside the window. `until` is compared at its
+    /// own precision, so `until: 12:00` includes 12:00:59.
+    fn contains(&self, timestamp: Option<&str>) -> bool {
+        if !self.is_set() {
+            return true;
+        }
+        let Some(timestamp) = timestamp else {
+            return false;
+        };
+        let after_since = self.since.as_deref().is_none_or(|since| timestamp >= since);
+        let before_until = self
+            .until
+            .as_deref()
+            .is_none_or(|until| timestamp.get(..until.len()).unwrap_or(timestamp) <= until);
+        after_since && before_until
+    }
+}
+
+/// The timestamp a line starts with (optionally in brackets), normalized to
+/// `YYYY-MM-DD HH:MM[:SS]`: `2024-05-01T12:00:00.123Z` becomes `2024-05-01 12:00:00`.
+fn line_timestamp(line: &str) -> Option<String> {
+    let line = line.trim_start().trim_start_matches('[');
+    let bytes = line.as_bytes();
+    let digits = |range: std::ops::Range<usize>| bytes.get(range).is_some_and(|b| b.iter().all(u8::is_ascii_digit));
+    let date_time = digits(0..4)
+        && bytes.get(4) == Some(&b'-')
+        && digits(5..7)
+        && bytes.get(7) == Some(&b'-')
+        && digits(8..10)
+        && matches!(bytes.get(10), Some(b'T' | b' '))
+        && digits(11..13)
+        && bytes.get(13) == Some(&b':')
+        && digits(14..16);
+    if !date_time {
+        return None;
+    }
+    let end = if bytes.get(16) == Some(&b':') && digits(17..19) { 19 } else { 16 };
+    Some(format!("{} {}", &line[..10], &line[11..end]))
+}
+
+/// Parses a `since`/`until` argument into a normalized timestamp.
+fn parse_bound(value: &str) -> Option<String> {
+    line_timestamp(value.trim())
+}
+
+/// Reads the log at `path` line by line, calling `visit` with each line's number (from 1)
+/// and text, for the lines inside `window`. Lines without a timestamp (stack traces,
+/// continuation lines) belong to the last timestamp seen.
+///
+/// # Returns
+/// The total number of lines in the file.
+fn scan_lines(path: &Path, window: &TimeWindow, mut visit: impl FnMut(usize, String)) -> std::io::Result<usize> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buffer = Vec::new();
+    let mut number = 0;
+    let mut current_timestamp: Option<String> = None;
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            return Ok(number);
+        }
+        number += 1;
+        let line = String::from_utf8_lossy(&buffer).trim_end_matches(['\n', '\r']).to_string();
+        if window.is_set() {
+            if let Some(timestamp) = line_timestamp(&line) {
+                current_timestamp = Some(timestamp);
+            }
+        }
+        if window.contains(current_timestamp.as_deref()) {
+            visit(number, line);
+        }
+    }
+}
+
+/// The lines matching `pattern` with `context` lines around each, grep-style: `N: line` for
+/// matches and `N- line` for context, with `--` between separate excerpts.
+fn grep(
+    path: &Path,
+    window: &TimeWindow,
+    pattern: &Regex,
+    context: usize,
+    max_matches: usize,
+) -> std::io::Result<(String, usize, usize)> {
+    let mut out = Vec::new();
+    let mut before: VecDeque<(usize, String)> = VecDeque::new();
+    let mut matches = 0;
+    let mut after_remaining = 0;
+    let mut last_printed = 0;
+    let total = scan_lines(path, window, |number, line| {
+        let is_match = pattern.is_match(&line);
+        if is_match {
+            matches += 1;
+        }
+        if is_match && matches <= max_matches {
+            for (n, text) in before.drain(..) {
+                if last_printed != 0 && n > last_printed + 1 {
+                    out.push("--".to_string());
+                }
+                out.push(format!("{}- {}", n, text));
+                last_printed = n;
+            }
+            if last_printed != 0 && number > last_printed + 1 {
+                out.push("--".to_string());
+            }
+            out.push(format!("{}: {}", number, line));
+            last_printed = number;
+            after_remaining = context;
+        } else if after_remaining > 0 {
+            out.push(format!("{}- {}", number, line));
+            last_printed = number;
+            after_remaining -= 1;
+        } else if context > 0 {
+            before.push_back((number, line));
+            if before.len() > context {
+                before.pop_front();
+            }
+        }
+    })?;
+    Ok((out.join("\n"), matches, total))
+}
+
+/// The last `count` lines inside the window, as `N: line`.
+fn tail(path: &Path, window: &TimeWindow, count: usize) -> std::io::Result<(String, usize)> {
+    let mut last: VecDeque<(usize, String)> = VecDeque::with_capacity(count + 1);
+    let total = scan_lines(path, window, |number, line| {
+        last.push_back((number, line));
+        if last.len() > count {
+            last.pop_front();
+        }
+    })?;
+    let text = last.into_iter().map(|(n, line)| format!("{}: {}", n, line)).collect::<Vec<_>>().join("\n");
+    Ok((text, total))
+}
+
+/// Cuts `text` to `max_chars` characters, keeping the start and the end (where build errors
+/// usually are) with a note of what was left out.
+fn clip_middle(text: &str, max_chars: usize) -> String {
+    let length = text.chars().count();
+    if length <= max_chars {
+        return text.to_string();
+    }
+    let head = max_chars / 4;
+    let tail = max_chars - head;
+    let start: String = text.chars().take(head).collect();
+    let end: String = text.chars().skip(length - tail).collect();
+    format!("{}\n[... {} characters omitted ...]\n{}", start, length - head - tail, end)
+}
+
+#[async_trait]
+impl Tool for LogInspectTool {
+    fn get_name(&self) -> String {
+        "log_inspect".to_string()
+    }
+
+    fn requires_network(&self) -> bool {
+        self.summarizer.is_some() && !self.summarizer_endpoint.as_deref().is_some_and(is_local_url)
+    }
+
+    fn network_target(&self, arguments: &Value) -> Option<String> {
+        let client = self.summarizer.as_ref()?;
+        (arguments.get("command").and_then(Value::as_str) == Some("summarize")).then(|| match &self.summarizer_endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => format!("the {} API", client.get_provider_name()),
+        })
+    }
+
+    fn get_description(&self) -> String {
+        "Inspects a large log file (build log, CI output, server log) without reading all of it. \
+        Commands: `grep` returns the lines matching a regex `pattern` with numbered context lines; \
+        `tail` returns the last lines; `summarize` returns a short summary of what ran and failed \
+        (optionally only of the lines matching `pattern`). `since`/`until` (e.g. \"2024-05-01 12:00\") \
+        keep only lines logged in that window. Prefer this to cat or bash for logs over a few \
+        hundred lines."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        let param = |name: &str, param_type: &str, description: &str, is_required: bool, enum_values: Option<Vec<String>>| {
+            ToolParameter {
+                name: name.to_string(),
+                param_type: param_type.to_string(),
+                description: description.to_string(),
+                is_required,
+                enum_values,
+                items: None,
+                properties: None,
+                required: vec![],
+            }
+        };
+        vec![
+            param(
+                "command",
+                "string",
+                "What to do with the log.",
+                true,
+                Some(vec!["grep".to_string(), "tail".to_string(), "summarize".to_string()]),
+            ),
+            param("path", "string", "Path to the log file, absolute or relative to the project.", true, None),
+            param("pattern", "string", "Regular expression to search for (required for grep).", false, None),
+            param(
+                "context_lines",
+                "integer",
+                &format!("Lines shown before and after each match (default {}).", DEFAULT_CONTEXT_LINES),
+                false,
+                None,
+            ),
+            param(
+                "max_matches",
+                "integer",
+                &format!("Most matches shown (default {}).", DEFAULT_MAX_MATCHES),
+                false,
+                None,
+            ),
+            param("lines", "integer", &format!("Lines returned by tail (default {}).", DEFAULT_TAIL_LINES), false, None),
+            param("since", "string", "Only lines logged at or after this time, e.g. \"2024-05-01 12:00:00\".", false, None),
+            param("until", "string", "Only lines logged at or before this time.", false, None),
+            param("focus", "string", "For summarize: what to look for, e.g. \"why the tests failed\".", false, None),
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: LogInspectArgs = serde_json::from_value(arguments.clone())
+            .map_err(|e| self.invalid(format!("Failed to parse arguments: {}. Args: {:?}", e, arguments)))?;
+        let path = context.resolve_path(&args.path);
+        let bound = |value: &Option<String>, name: &str| -> Result<Option<String>, ToolError> {
+            value
+                .as_deref()
+                .map(|v| parse_bound(v).ok_or_else(|| self.invalid(format!("'{}' must look like \"2024-05-01 12:00[:00]\", got \"{}\".", name, v))))
+                .transpose()
+        };
+        let window = TimeWindow {
+            since: bound(&args.since, "since")?,
+            until: bound(&args.until, "until")?,
+        };
+        let pattern = args
+            .pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| self.invalid(format!("Invalid pattern: {}", e)))?;
+        let read_error = |e: std::io::Error| ToolError::FileReadError(format!("{}: {}", path.display(), e));
+        let window_note = if window.is_set() { " in the time window" } else { "" };
+
+        let output = match args.command.as_str() {
+            "grep" => {
+                let pattern = pattern.ok_or_else(|| self.invalid("'pattern' is required for grep."))?;
+                let max_matches = args.max_matches.unwrap_or(DEFAULT_MAX_MATCHES).max(1);
+                let (text, matches, total) = grep(
+                    &path,
+                    &window,
+                    &pattern,
+                    args.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES),
+                    max_matches,
+                )
+                .map_err(read_error)?;
+                let shown = matches.min(max_matches);
+                let header = format!(
+                    "{} matching line(s){} of {} (showing {}).",
+                    matches, window_note, total, shown
+                );
+                if matches == 0 {
+                    header
+                } else {
+                    format!("{}\n{}", header, clip_middle(&text, MAX_OUTPUT_CHARS))
+                }
+            }
+            "tail" => {
+                let count = args.lines.unwrap_or(DEFAULT_TAIL_LINES).max(1);
+                let (text, total) = tail(&path, &window, count).map_err(read_error)?;
+                format!("Last lines{} of {}:\n{}", window_note, total, clip_middle(&text, MAX_OUTPUT_CHARS))
+            }
+            "summarize" => {
+                let Some(client) = &self.summarizer else {
+                    return Ok(ToolExecResult::new_failure(
+                        "Summaries are unavailable in this run (no LLM client for log summaries); use grep or tail instead."
+                            .to_string(),
+                        1,
+                    ));
+                };
+                let text = match &pattern {
+                    Some(pattern) => {
+                        let context_lines = args.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES);
+                        let max_matches = args.max_matches.unwrap_or(usize::MAX);
+                        grep(&path, &window, pattern, context_lines, max_matches).map_err(read_error)?.0
+                    }
+                    None => {
+                        let mut lines = Vec::new();
+                        scan_lines(&path, &window, |_, line| lines.push(line)).map_err(read_error)?;
+                        lines.join("\n")
+                    }
+                };
+                if text.trim().is_empty() {
+                    return Ok(ToolExecResult::new_success(Some(format!("Nothing to summarize{}.", window_note)), None));
+                }
+                let mut request = format!("Log file: {}\n", path.display());
+                if let Some(focus) = &args.focus {
+                    request.push_str(&format!("Focus on: {}\n", focus));
+                }
+                request.push_str(&format!("\n{}", clip_middle(&text, MAX_SUMMARY_INPUT_CHARS)));
+                let message = |role, content: &str| LLMMessage {
+                    role,
+                    content: Some(content.to_string()),
+                    name: None,
+                    tool_calls: None,
+                    tool_call_id: None,
+                };
+                let response = client
+                    .chat(vec![message(MessageRole::System, SUMMARY_PROMPT), message(MessageRole::User, &request)], None, None)
+                    .await
+                    .map_err(|e| ToolError::ExecutionFailed(format!("Failed to summarize the log: {}", e)))?;
+                response
+                    .choices
+                    .first()
+                    .and_then(|choice| choice.message.content.clone())
+                    .filter(|content| !content.trim().is_empty())
+                    .ok_or_else(|| ToolError::ExecutionFailed("The log summary was empty".to_string()))?
+            }
+            other => {
+                return Err(self.invalid(format!(
+                    "Unknown command '{}'; expected grep, tail or summarize.",
+                    other
+                )))
+            }
+        };
+        Ok(ToolExecResult::new_success(Some(output), None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_grep_tail_and_time_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = "2024-05-01T11:59:58Z starting build\n\
+                   2024-05-01T11:59:59Z compiling foo\n\
+                   2024-05-01T12:00:01Z compiling bar\n\
+                   2024-05-01T12:00:02Z error[E0308]: mismatched types\n\
+                   \x20  --> src/bar.rs:3:5\n\
+                   2024-05-01T12:01:30Z build failed\n";
+        std::fs::write(dir.path().join("build.log"), log).unwrap();
+        let tool = LogInspectTool::new();
+        let context = ToolContext::for_project(dir.path());
+        let run = |arguments: Value| {
+            let tool = &tool;
+            let context = &context;
+            async move { tool.execute(arguments, context).await.map(|result| result.output.unwrap_or_default()) }
+        };
+
+        let output = run(json!({"command": "grep", "path": "build.log", "pattern": "error|failed", "context_lines": 1}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            "2 matching line(s) of 6 (showing 2).\n\
+             3- 2024-05-01T12:00:01Z compiling bar\n\
+             4: 2024-05-01T12:00:02Z error[E0308]: mismatched types\n\
+             5-    --> src/bar.rs:3:5\n\
+             6: 2024-05-01T12:01:30Z build failed"
+        );
+
+        // The continuation line belongs to the error's timestamp; `until` includes its whole minute.
+        let output = run(json!({"command": "tail", "path": "build.log", "lines": 3, "since": "2024-05-01 12:00", "until": "2024-05-01T12:00"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            "Last lines in the time window of 6:\n\
+             3: 2024-05-01T12:00:01Z compiling bar\n\
+             4: 2024-05-01T12:00:02Z error[E0308]: mismatched types\n\
+             5:    --> src/bar.rs:3:5"
+        );
+
+        assert!(run(json!({"command": "grep", "path": "build.log"})).await.is_err());
+        assert!(run(json!({"command": "tail", "path": "build.log", "since": "yesterday"})).await.is_err());
+        let summary = tool
+            .execute(json!({"command": "summarize", "path": "build.log"}), &context)
+            .await
+            .unwrap();
+        assert!(summary.error.is_some());
+    }
+}
@@ -3,31 +3,86 @@
 //! Defines the framework for tools that the agent can use, including a `Tool` trait,
 //! a `ToolExecutor` for running tools, and a `ToolRegistry` for managing available tools.
 //! Concrete tool implementations like `BashTool`, `EditTool`, etc., are also part of this module.
+//!
+//! Several agents may run concurrently in one process with one `Arc<ToolRegistry>`: the
+//! registry is not changed once shared, everything a call needs to know about its run (project
+//! root, scratch directory, cancellation) comes in its `ToolContext`, and tools that keep state
+//! between calls give each run its own instance (`Tool::for_run`, collected by
+//! `ToolRegistry::tools_for_run`). Tools built around one run's stores, such as a `BashTool`
+//! tracking processes for that run's cleanup, belong in that run's own registry.
 
+pub mod archive_tool;
 pub mod base;
 pub mod bash_tool;
+pub mod checkpoint_tool;
+pub mod ckg_tool;
+pub mod context;
+pub mod db_query_tool;
 pub mod edit_tool;
+pub mod execution;
+pub mod final_report_tool;
+pub mod hints;
+pub mod image_diff_tool;
 pub mod json_edit_tool; // Added
+pub mod log_inspect_tool;
+pub mod namespace;
+pub mod read_more_tool;
+pub mod reproduction_tool;
+pub mod request_extension_tool;
+pub mod search_tool;
 pub mod sequential_thinking_tool;
+pub mod snippet_tool;
 pub mod task_done_tool;
+pub mod wait_tool;
 
-pub use base::{Tool, ToolError, ToolExecutor, ToolResult as AgentToolResult};
+pub use archive_tool::ArchiveTool;
+pub use base::{Tool, ToolDeterminism, ToolError, ToolExecutor, ToolResult as AgentToolResult};
 pub use bash_tool::BashTool;
+pub use checkpoint_tool::{CheckpointTool, RestoreCheckpointTool};
+pub use ckg_tool::CkgTool;
+pub use context::ToolContext;
+pub use db_query_tool::DbQueryTool;
 pub use edit_tool::EditTool;
+pub use final_report_tool::{FinalReport, FinalReportTool};
+pub use image_diff_tool::ImageDiffTool;
 pub use json_edit_tool::JsonEditTool; // Added
+pub use log_inspect_tool::LogInspectTool;
+pub use namespace::{RegistryError, ToolSource};
+pub use read_more_tool::ReadMoreTool;
+pub use reproduction_tool::ReproductionTool;
+pub use request_extension_tool::RequestExtensionTool;
+pub use search_tool::SearchTool;
 pub use sequential_thinking_tool::SequentialThinkingTool;
+pub use snippet_tool::{GetSnippetTool, SaveSnippetTool};
 pub use task_done_tool::TaskDoneTool;
+pub use wait_tool::WaitTool;
 
-use std::collections::HashMap;
+use crate::config::ToolConflictPolicy;
+use namespace::NamespacedTool;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
+use tracing::warn;
+
+/// A tool in the registry, with where it came from.
+#[derive(Clone)]
+pub struct ToolEntry {
+    /// The tool's name for humans: its own name for built-ins, the dotted namespaced name
+    /// (e.g., "mcp.files.read") for external tools.
+    pub name: String,
+    pub source: ToolSource,
+    pub tool: Arc<dyn Tool + Send + Sync>,
+}
 
 /// A registry for discovering and managing available tools.
 ///
 /// Tools are registered by name, allowing the agent to look them up
-/// and get their definitions for LLM interaction.
-// TODO: Potentially load tools dynamically or from configuration in the future.
+/// and get their definitions for LLM interaction. External tools are registered under a
+/// namespace (see `namespace`), and name conflicts are resolved by the registry's
+/// `ToolConflictPolicy`.
 pub struct ToolRegistry {
-    tools: HashMap<String, Arc<dyn Tool + Send + Sync>>,
+    /// Entries keyed by the name the LLM calls the tool by.
+    tools: HashMap<String, ToolEntry>,
+    conflict_policy: ToolConflictPolicy,
 }
 
 impl ToolRegistry {
@@ -35,15 +90,94 @@ impl ToolRegistry {
     pub fn new() -> Self {
         ToolRegistry {
             tools: HashMap::new(),
+            conflict_policy: ToolConflictPolicy::default(),
         }
     }
 
-    /// Registers a tool with the registry.
+    /// Sets how `register_external` handles names that are already taken.
+    pub fn set_conflict_policy(&mut self, policy: ToolConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+
+    /// Registers a built-in tool with the registry.
+    ///
+    /// A built-in tool of the same name is replaced, which is how configured variants (such
+    /// as a `BashTool` with a result store) take the place of the defaults.
     ///
     /// # Arguments
     /// * `tool`: An instance of a type implementing the `Tool` trait.
     pub fn register<T: Tool + Send + Sync + 'static>(&mut self, tool: T) {
-        self.tools.insert(tool.get_name(), Arc::new(tool));
+        let name = tool.get_name();
+        let entry = ToolEntry {
+            name: name.clone(),
+            source: ToolSource::BuiltIn,
+            tool: Arc::new(tool),
+        };
+        if let Some(replaced) = self.tools.insert(name, entry) {
+            if replaced.source != ToolSource::BuiltIn {
+                warn!("Built-in tool replaced the {} tool '{}'", replaced.source, replaced.name);
+            }
+        }
+    }
+
+    /// Registers an external tool under `namespace` (e.g., "mcp.files"), so it is named
+    /// `<namespace>.<tool name>`.
+    ///
+    /// # Returns
+    /// `Ok(true)` if the tool was registered, `Ok(false)` if it was skipped because the name
+    /// is taken and the policy is `KeepExisting`, or a `RegistryError` for an invalid
+    /// namespace or, under the `Error` policy, a conflict.
+    #[allow(dead_code)] // Library API for embedders; the CLI has no external tool backends yet
+    pub fn register_external<T: Tool + Send + Sync + 'static>(
+        &mut self,
+        namespace: &str,
+        tool: T,
+    ) -> Result<bool, RegistryError> {
+        namespace::validate_namespace(namespace)?;
+        let tool = NamespacedTool::new(namespace, Arc::new(tool));
+        let name = tool.qualified_name().to_string();
+        let source = ToolSource::External(namespace.to_string());
+        let key = tool.get_name();
+        if let Some(existing) = self.tools.get(&key) {
+            match self.conflict_policy {
+                ToolConflictPolicy::Error => {
+                    return Err(RegistryError::Conflict {
+                        name,
+                        existing_source: existing.source.clone(),
+                        new_source: source,
+                    });
+                }
+                ToolConflictPolicy::KeepExisting => {
+                    warn!(
+                        "Skipping tool '{}' from {}: the name is taken by a {} tool",
+                        name, source, existing.source
+                    );
+                    return Ok(false);
+                }
+                ToolConflictPolicy::Replace => {
+                    warn!(
+                        "Tool '{}' from {} replaces the {} tool of the same name",
+                        name, source, existing.source
+                    );
+                }
+            }
+        }
+        self.tools.insert(
+            key,
+            ToolEntry {
+                name,
+                source,
+                tool: Arc::new(tool),
+            },
+        );
+        Ok(true)
+    }
+
+    /// All registered tools, sorted by name.
+    pub fn list_tools(&self) -> Vec<ToolEntry> {
+        let mut entries: Vec<ToolEntry> = self.tools.values().cloned().collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries
     }
 
     /// Retrieves a tool by its name.
@@ -55,20 +189,54 @@ impl ToolRegistry {
     /// An `Option` containing an `Arc` to the tool if found, otherwise `None`.
     #[allow(dead_code)] // May be useful for direct tool inspection or invocation
     pub fn get_tool(&self, name: &str) -> Option<Arc<dyn Tool + Send + Sync>> {
-        self.tools.get(name).cloned()
+        self.tools.get(name).map(|entry| entry.tool.clone())
+    }
+
+    /// The determinism contract of every registered tool, by the name the LLM calls it by.
+    /// Recorded in trajectories so replays know which calls may be re-run.
+    pub fn determinism_contracts(&self) -> BTreeMap<String, ToolDeterminism> {
+        self.tools
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.tool.determinism()))
+            .collect()
     }
 
     /// Gets the JSON definitions of all registered tools, for use with LLMs.
     pub fn get_all_tool_definitions(&self) -> Vec<crate::llm::base_client::ToolDefinition> {
         self.tools
             .values()
-            .map(|tool| tool.get_json_definition())
+            .map(|entry| entry.tool.get_json_definition())
             .collect()
     }
 
     /// Gets `Arc` references to all registered tools.
     pub fn get_all_tools_arc(&self) -> Vec<Arc<dyn Tool + Send + Sync>> {
-        self.tools.values().cloned().collect()
+        self.tools.values().map(|entry| entry.tool.clone()).collect()
+    }
+
+    /// The tools for one run: the shared instances, and a fresh instance of each tool that
+    /// keeps state between calls (see `Tool::for_run`).
+    pub fn tools_for_run(&self) -> Vec<Arc<dyn Tool + Send + Sync>> {
+        self.tools
+            .values()
+            .map(|entry| entry.tool.for_run().unwrap_or_else(|| entry.tool.clone()))
+            .collect()
+    }
+
+    /// Removes the tools that need the network (for `--offline`).
+    ///
+    /// # Returns
+    /// The names of the removed tools, sorted.
+    pub fn remove_network_tools(&mut self) -> Vec<String> {
+        let mut removed: Vec<String> = self
+            .tools
+            .values()
+            .filter(|entry| entry.tool.requires_network())
+            .map(|entry| entry.name.clone())
+            .collect();
+        self.tools.retain(|_, entry| !entry.tool.requires_network());
+        removed.sort();
+        removed
     }
 }
 
@@ -77,11 +245,19 @@ impl Default for ToolRegistry {
     fn default() -> Self {
         let mut registry = Self::new();
         // Register default tools here
+        registry.register(ArchiveTool::new());
         registry.register(BashTool::new());
+        registry.register(CkgTool::new());
+        registry.register(DbQueryTool::new());
         registry.register(EditTool::new());
+        registry.register(FinalReportTool::new());
+        registry.register(ImageDiffTool::new());
         registry.register(JsonEditTool::new()); // Added
+        registry.register(LogInspectTool::new());
+        registry.register(SearchTool::new());
         registry.register(SequentialThinkingTool::new());
         registry.register(TaskDoneTool::new());
+        registry.register(WaitTool::new());
         registry
     }
 }
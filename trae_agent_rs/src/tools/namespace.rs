@@ -0,0 +1,197 @@
+//! # Tool Namespaces
+//!
+//! External tools (from MCP servers or other plugins) are registered under a namespace such
+//! as `mcp.files`, so a server's `read` tool becomes `mcp.files.read` and cannot silently
+//! replace a built-in tool or another server's tool of the same name.
+//!
+//! Providers only accept tool names made of letters, digits, `_` and `-`, so the LLM sees the
+//! qualified name with `.` replaced by `__` (`mcp__files__read`).
+
+use super::base::{Tool, ToolDeterminism, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::llm::base_client::ToolDefinition;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Where a registered tool comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolSource {
+    /// Shipped with the agent.
+    BuiltIn,
+    /// Registered under a namespace by an external backend (e.g., "mcp.files").
+    External(String),
+}
+
+impl fmt::Display for ToolSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolSource::BuiltIn => f.write_str("built-in"),
+            ToolSource::External(namespace) => write!(f, "external ({})", namespace),
+        }
+    }
+}
+
+/// Errors raised when registering an external tool.
+#[derive(Error, Debug, PartialEq)]
+pub enum RegistryError {
+    #[error("Invalid tool namespace '{0}': use dot-separated segments of lowercase letters, digits and '-'")]
+    InvalidNamespace(String),
+    #[error("Tool '{name}' from {new_source} conflicts with the {existing_source} tool of the same name")]
+    Conflict {
+        name: String,
+        existing_source: ToolSource,
+        new_source: ToolSource,
+    },
+}
+
+/// Checks that `namespace` is one or more `.`-separated segments of `[a-z0-9-]`.
+pub fn validate_namespace(namespace: &str) -> Result<(), RegistryError> {
+    let valid = namespace.split('.').all(|segment| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(RegistryError::InvalidNamespace(namespace.to_string()))
+    }
+}
+
+/// The name the LLM sees for a qualified tool name (`mcp.files.read` -> `mcp__files__read`).
+pub fn wire_name(qualified_name: &str) -> String {
+    qualified_name.replace('.', "__")
+}
+
+/// An external tool exposed under its namespaced name.
+pub struct NamespacedTool {
+    qualified_name: String,
+    inner: Arc<dyn Tool + Send + Sync>,
+}
+
+impl NamespacedTool {
+    pub fn new(namespace: &str, inner: Arc<dyn Tool + Send + Sync>) -> Self {
+        Self {
+            qualified_name: format!("{}.{}", namespace, inner.get_name()),
+            inner,
+        }
+    }
+
+    /// The dotted name, e.g. "mcp.files.read".
+    pub fn qualified_name(&self) -> &str {
+        &self.qualified_name
+    }
+}
+
+#[async_trait]
+impl Tool for NamespacedTool {
+    fn get_name(&self) -> String {
+        wire_name(&self.qualified_name)
+    }
+
+    fn get_description(&self) -> String {
+        self.inner.get_description()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        self.inner.get_parameters()
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        self.inner.execute(arguments, context).await
+    }
+
+    fn determinism(&self) -> ToolDeterminism {
+        self.inner.determinism()
+    }
+
+    fn requires_network(&self) -> bool {
+        self.inner.requires_network()
+    }
+
+    fn network_target(&self, arguments: &Value) -> Option<String> {
+        self.inner.network_target(arguments)
+    }
+
+    fn shell_command<'a>(&self, arguments: &'a Value) -> Option<&'a str> {
+        self.inner.shell_command(arguments)
+    }
+
+    fn for_run(&self) -> Option<Arc<dyn Tool + Send + Sync>> {
+        let inner = self.inner.for_run()?;
+        Some(Arc::new(Self {
+            qualified_name: self.qualified_name.clone(),
+            inner,
+        }))
+    }
+
+    fn get_json_definition(&self) -> ToolDefinition {
+        let mut definition = self.inner.get_json_definition();
+        definition.function.name = self.get_name();
+        definition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ToolConflictPolicy;
+    use crate::tools::{SequentialThinkingTool, TaskDoneTool, ToolRegistry};
+
+    #[test]
+    fn test_namespaced_registration_and_conflicts() {
+        let mut registry = ToolRegistry::default();
+        assert_eq!(
+            registry.register_external("mcp.files", TaskDoneTool::new()),
+            Ok(true)
+        );
+        let tool = registry.get_tool("mcp__files__task_done").unwrap();
+        assert_eq!(
+            tool.get_json_definition().function.name,
+            "mcp__files__task_done"
+        );
+        assert!(registry.get_tool("task_done").is_some());
+
+        let err = registry
+            .register_external("mcp.files", TaskDoneTool::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Tool 'mcp.files.task_done' from external (mcp.files) conflicts with the external (mcp.files) tool of the same name"
+        );
+
+        registry.set_conflict_policy(ToolConflictPolicy::KeepExisting);
+        assert_eq!(
+            registry.register_external("mcp.files", TaskDoneTool::new()),
+            Ok(false)
+        );
+        registry.set_conflict_policy(ToolConflictPolicy::Replace);
+        assert_eq!(
+            registry.register_external("mcp.files", TaskDoneTool::new()),
+            Ok(true)
+        );
+
+        assert!(matches!(
+            registry.register_external("MCP files", SequentialThinkingTool::new()),
+            Err(RegistryError::InvalidNamespace(_))
+        ));
+
+        let listing = registry.list_tools();
+        let external: Vec<(&str, &ToolSource)> = listing
+            .iter()
+            .filter(|entry| entry.source != ToolSource::BuiltIn)
+            .map(|entry| (entry.name.as_str(), &entry.source))
+            .collect();
+        assert_eq!(
+            external,
+            vec![(
+                "mcp.files.task_done",
+                &ToolSource::External("mcp.files".to_string())
+            )]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::utils::result_store::{ResultStore, DEFAULT_PAGE_CHARS};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+#[derive(Deserialize, Debug)]
+struct ReadMoreArgs {
+    result_id: String,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+/// Pages through tool outputs that were clipped, using the `result_id` given in the
+/// clipped output.
+pub struct ReadMoreTool {
+    store: Arc<ResultStore>,
+}
+
+impl ReadMoreTool {
+    pub fn new(store: Arc<ResultStore>) -> Self {
+        ReadMoreTool { store }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadMoreTool {
+    fn get_name(&self) -> String {
+        "read_more".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Reads more of a tool output that was clipped. Clipped outputs name a result_id and the offset \
+        to continue from; each call returns one page of characters and the offset of the next page."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter {
+                name: "result_id".to_string(),
+                param_type: "string".to_string(),
+                description: "The result_id given in the clipped output, e.g. \"r1\".".to_string(),
+                is_required: true,
+                enum_values: None,
+                items: None,
+                properties: None,
+                required: vec![],
+            },
+            ToolParameter {
+                name: "offset".to_string(),
+                param_type: "integer".to_string(),
+                description: "Character offset to start reading at (default 0).".to_string(),
+                is_required: false,
+                enum_values: None,
+                items: None,
+                properties: None,
+                required: vec![],
+            },
+            ToolParameter {
+                name: "limit".to_string(),
+                param_type: "integer".to_string(),
+                description: format!("Maximum number of characters to return (default {}).", DEFAULT_PAGE_CHARS),
+                is_required: false,
+                enum_values: None,
+                items: None,
+                properties: None,
+                required: vec![],
+            },
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: ReadMoreArgs =
+            serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+            })?;
+        let page = self
+            .store
+            .read(
+                args.result_id.trim(),
+                args.offset,
+                args.limit.unwrap_or(DEFAULT_PAGE_CHARS),
+            )
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("{:#}", e),
+            })?;
+
+        let footer = if page.end < page.total {
+            format!(
+                "[Characters {}-{} of {}. Call read_more with offset {} to continue.]",
+                page.offset, page.end, page.total, page.end
+            )
+        } else {
+            format!(
+                "[Characters {}-{} of {}. End of result.]",
+                page.offset, page.end, page.total
+            )
+        };
+        Ok(ToolExecResult {
+            output: Some(format!("{}\n{}", page.text, footer)),
+            error: None,
+            error_code: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::BashTool;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_clipped_bash_output_can_be_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(ResultStore::new(dir.path()).unwrap());
+        let bash = BashTool::with_result_store(store.clone());
+        let output = bash
+            .execute(json!({"command": "seq 1 10000"}), &ToolContext::default())
+            .await
+            .unwrap()
+            .output
+            .unwrap();
+        assert!(output.contains("result_id \"r1\""), "{}", output);
+
+        let read_more = ReadMoreTool::new(store);
+        let tail = read_more
+            .execute(json!({"result_id": "r1", "offset": 48000}), &ToolContext::default())
+            .await
+            .unwrap()
+            .output
+            .unwrap();
+        assert!(tail.contains("9999\n10000\n"));
+        assert!(tail.ends_with("End of result.]"));
+
+        let err = read_more.execute(json!({"result_id": "r9"}), &ToolContext::default()).await.unwrap_err();
+        assert!(matches!(err, ToolError::InvalidArguments { .. }));
+    }
+}
@@ -0,0 +1,261 @@
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::utils::reproduction::Reproduction;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::info;
+
+#[derive(Deserialize, Debug)]
+struct RecordReproductionArgs {
+    command: String,
+}
+
+/// Records the command that reproduces the issue and runs it once. Registered only when
+/// the run has a reproduction phase.
+pub struct ReproductionTool {
+    reproduction: Arc<Reproduction>,
+}
+
+impl ReproductionTool {
+    pub fn new(reproduction: Arc<Reproduction>) -> Self {
+        ReproductionTool { reproduction }
+    }
+}
+
+#[async_trait]
+impl Tool for ReproductionTool {
+    fn get_name(&self) -> String {
+        "record_reproduction".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Records the shell command that reproduces the issue and runs it from the project root. \
+            Write the reproduction script in the scratch directory {} first. The script must exit with \
+            status 0 only once the issue is fixed. The recorded command is re-run automatically when you \
+            signal completion. Calling this tool again replaces the recorded command.",
+            self.reproduction.scratch_dir().display()
+        )
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        vec![ToolParameter {
+            name: "command".to_string(),
+            param_type: "string".to_string(),
+            description: "The shell command that runs the reproduction script, e.g. `python /tmp/.../reproduce.py`."
+                .to_string(),
+            is_required: true,
+            enum_values: None,
+            items: None,
+            properties: None,
+            required: vec![],
+        }]
+    }
+
+    fn shell_command<'a>(&self, arguments: &'a Value) -> Option<&'a str> {
+        arguments.get("command").and_then(Value::as_str)
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: RecordReproductionArgs =
+            serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+            })?;
+        if args.command.trim().is_empty() {
+            return Err(ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: "The reproduction command cannot be empty.".to_string(),
+            });
+        }
+
+        self.reproduction.record(&args.command);
+        info!(command = %args.command, "Reproduction command recorded");
+
+        let reproduction = self.reproduction.clone();
+        let command = args.command.clone();
+        let context = context.clone();
+        let run = tokio::task::spawn_blocking(move || reproduction.run(&command, context.execution()))
+            .await
+            .map_err(|e| ToolError::InternalError(e.to_string()))?
+            .map_err(|e| ToolError::ExecutionFailed(format!("{:#}", e)))?;
+
+        let status = match run.exit_code {
+            Some(code) => format!("exit status {}", code),
+            None if run.timed_out => "timed out".to_string(),
+            None => "terminated by a signal".to_string(),
+        };
+        let verdict = if run.passed() {
+            "The command succeeded, so it does not reproduce the issue yet. Make sure the script fails \
+            while the issue is present."
+        } else {
+            "The command fails as expected while the issue is present."
+        };
+        Ok(ToolExecResult {
+            output: Some(format!(
+                "Recorded reproduction command `{}` ({}). {}\nOutput:\n{}",
+                args.command.trim(),
+                status,
+                verdict,
+                run.output
+            )),
+            error: None,
+            error_code: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::{ToolCall, ToolCallFunction};
+    use crate::tools::execution::{DirEntry, ExecutionEnvironment, HostEnvironment, PathKind};
+    use crate::config::{ApprovalConfig, ApprovalLists, ApprovalPolicy};
+    use crate::tools::ToolExecutor;
+    use crate::utils::approval::{ApprovalGate, ApprovalPrompt};
+    use crate::utils::permissions::{CommandPermissions, PermissionAnswer, PermissionPrompt};
+    use serde_json::json;
+    use std::path::Path;
+    use tokio::process::Command;
+
+    /// Stands in for a sandbox container: commands run on the host with `IN_SANDBOX` set.
+    #[derive(Debug)]
+    struct FakeSandbox;
+
+    #[async_trait]
+    impl ExecutionEnvironment for FakeSandbox {
+        fn describe(&self) -> String {
+            "a fake sandbox".to_string()
+        }
+
+        fn shell_command(&self, script: &str, working_dir: Option<&Path>, env: &[(&str, String)]) -> Command {
+            let mut env = env.to_vec();
+            env.push(("IN_SANDBOX", "yes".to_string()));
+            HostEnvironment.shell_command(script, working_dir, &env)
+        }
+
+        async fn path_kind(&self, path: &Path) -> std::io::Result<Option<PathKind>> {
+            HostEnvironment.path_kind(path).await
+        }
+
+        async fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+            HostEnvironment.read_to_string(path).await
+        }
+
+        async fn write(&self, path: &Path, content: &str) -> std::io::Result<()> {
+            HostEnvironment.write(path, content).await
+        }
+
+        async fn list_dir(&self, path: &Path) -> std::io::Result<Vec<DirEntry>> {
+            HostEnvironment.list_dir(path).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_reproduction_runs_and_records_command() {
+        let project = tempfile::tempdir().unwrap();
+        let reproduction = Arc::new(Reproduction::new(project.path()).unwrap());
+        let tool = ReproductionTool::new(reproduction.clone());
+
+        let result = tool
+            .execute(json!({"command": "echo broken; exit 1"}), &ToolContext::default())
+            .await
+            .unwrap();
+        let output = result.output.unwrap();
+        assert!(output.contains("exit status 1"));
+        assert!(output.contains("fails as expected"));
+        assert_eq!(reproduction.command().as_deref(), Some("echo broken; exit 1"));
+
+        assert!(tool.execute(json!({"command": "  "}), &ToolContext::default()).await.is_err());
+        std::fs::remove_dir_all(reproduction.scratch_dir()).unwrap();
+    }
+
+    struct DenyingPrompt;
+
+    impl PermissionPrompt for DenyingPrompt {
+        fn ask(&self, _command: &str) -> PermissionAnswer {
+            PermissionAnswer::Deny
+        }
+    }
+
+    fn record_call(command: &str) -> ToolCall {
+        ToolCall {
+            id: "call-1".to_string(),
+            tool_type: "function".to_string(),
+            function: ToolCallFunction {
+                name: "record_reproduction".to_string(),
+                arguments: json!({ "command": command }).to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_reproduction_asks_permission_for_its_command() {
+        let project = tempfile::tempdir().unwrap();
+        let reproduction = Arc::new(Reproduction::in_dir(project.path(), project.path().join("scratch")).unwrap());
+        let mut executor = ToolExecutor::new(vec![Arc::new(ReproductionTool::new(reproduction.clone()))]);
+        executor.set_command_permissions(Some(Arc::new(CommandPermissions::in_memory(Arc::new(DenyingPrompt)))));
+
+        let result = executor
+            .execute_tool_call(&record_call("touch ran"), &ToolContext::for_project(project.path()))
+            .await;
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("did not allow the command `touch ran`"));
+        assert!(!project.path().join("ran").exists());
+        assert!(reproduction.command().is_none());
+    }
+
+    impl ApprovalPrompt for DenyingPrompt {
+        fn approve(&self, _question: &str) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_reproduction_is_subject_to_the_approval_policy() {
+        let project = tempfile::tempdir().unwrap();
+        let reproduction = Arc::new(Reproduction::in_dir(project.path(), project.path().join("scratch")).unwrap());
+        let mut executor = ToolExecutor::new(vec![Arc::new(ReproductionTool::new(reproduction.clone()))]);
+        let approval = ApprovalConfig {
+            default: ApprovalPolicy::Auto,
+            commands: ApprovalLists {
+                deny: vec!["git push*".to_string()],
+                ..ApprovalLists::default()
+            },
+            paths: ApprovalLists::default(),
+        };
+        executor.set_approval_gate(Some(Arc::new(ApprovalGate::new(approval, Arc::new(DenyingPrompt)))));
+        let context = ToolContext::for_project(project.path());
+
+        let result = executor.execute_tool_call(&record_call("exit 1 || git push origin main"), &context).await;
+        assert!(!result.success);
+        let decision = result.approval.unwrap();
+        assert_eq!((decision.policy, decision.rule.as_deref()), (ApprovalPolicy::Deny, Some("git push*")));
+        assert!(reproduction.command().is_none());
+
+        let result = executor.execute_tool_call(&record_call("exit 1"), &context).await;
+        assert!(result.success && result.approval.unwrap().approved);
+        assert_eq!(reproduction.command().as_deref(), Some("exit 1"));
+    }
+
+    #[tokio::test]
+    async fn test_record_reproduction_runs_in_the_sandbox() {
+        let project = tempfile::tempdir().unwrap();
+        let reproduction = Arc::new(Reproduction::in_dir(project.path(), project.path().join("scratch")).unwrap());
+        let tool = ReproductionTool::new(reproduction.clone());
+        let context = ToolContext {
+            execution: Some(Arc::new(FakeSandbox)),
+            ..ToolContext::for_project(project.path())
+        };
+        let command = r#"test "$IN_SANDBOX" = yes || { echo ran on the host; exit 9; }; exit 1"#;
+
+        let output = tool.execute(json!({ "command": command }), &context).await.unwrap().output.unwrap();
+        assert!(output.contains("exit status 1"), "{}", output);
+        // Verification re-runs it in the same place.
+        let err = reproduction.verify(&FakeSandbox).unwrap_err();
+        assert!(err.contains("exited with status 1"), "{}", err);
+        assert!(reproduction.verify(&HostEnvironment).unwrap_err().contains("ran on the host"));
+    }
+}
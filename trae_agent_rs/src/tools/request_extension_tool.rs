@@ -0,0 +1,80 @@
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::agent::step_extension::StepExtensions;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::info;
+
+#[derive(Deserialize, Debug)]
+struct RequestExtensionArgs {
+    steps: u32,
+    justification: String,
+}
+
+/// Asks for more steps, with a justification; see `agent::step_extension`. Registered only
+/// when the run allows step extensions.
+pub struct RequestExtensionTool {
+    extensions: Arc<StepExtensions>,
+}
+
+impl RequestExtensionTool {
+    pub fn new(extensions: Arc<StepExtensions>) -> Self {
+        RequestExtensionTool { extensions }
+    }
+}
+
+#[async_trait]
+impl Tool for RequestExtensionTool {
+    fn get_name(&self) -> String {
+        "request_extension".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Asks for more steps when the step budget is about to run out but the task is close to done. \
+        Say what remains and why it needs the steps; the request may be granted in full, in part or \
+        denied. Do not ask while the remaining steps are enough."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter {
+                name: "steps".to_string(), param_type: "integer".to_string(),
+                description: "How many more steps are needed.".to_string(),
+                is_required: true, enum_values: None, items: None, properties: None, required: vec![],
+            },
+            ToolParameter {
+                name: "justification".to_string(), param_type: "string".to_string(),
+                description: "What remains to be done and why it needs these steps.".to_string(),
+                is_required: true, enum_values: None, items: None, properties: None, required: vec![],
+            },
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: RequestExtensionArgs =
+            serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+            })?;
+        if args.steps == 0 || args.justification.trim().is_empty() {
+            return Err(ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: "Ask for at least one step, with a justification.".to_string(),
+            });
+        }
+
+        let extensions = self.extensions.clone();
+        let extension = tokio::task::spawn_blocking(move || extensions.request(args.steps, args.justification.trim()))
+            .await
+            .map_err(|e| ToolError::InternalError(e.to_string()))?;
+        info!(requested = extension.requested, granted = extension.granted, "Step extension decided");
+        Ok(ToolExecResult {
+            output: Some(extension.describe()),
+            error: None,
+            error_code: 0,
+        })
+    }
+}
@@ -0,0 +1,313 @@
+//! # Code Search
+//!
+//! `search` finds a regex or literal in the project's files, ripgrep-style, so the agent does
+//! not grep through `bash` and get pages of unbounded output. Files are walked in path order,
+//! honouring `.gitignore` (and `.ignore`) files and skipping hidden and binary files; globs
+//! narrow the files searched. Matches are grouped by file with numbered context lines, capped
+//! per file and overall, and the whole result is truncated to a fixed size.
+
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use async_trait::async_trait;
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Lines of context shown around each match, unless the call says otherwise.
+const DEFAULT_CONTEXT_LINES: usize = 2;
+/// Matches shown per file, unless the call says otherwise.
+const DEFAULT_MAX_MATCHES_PER_FILE: usize = 10;
+/// Files with matches shown, unless the call says otherwise.
+const DEFAULT_MAX_FILES: usize = 30;
+/// Longest line shown, in characters; minified files would otherwise flood the output.
+const MAX_LINE_CHARS: usize = 300;
+/// Longest output returned, in characters; the rest is cut with a note.
+const MAX_OUTPUT_CHARS: usize = 20_000;
+/// Bytes checked for a NUL to tell binary files apart.
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+#[derive(Deserialize, Debug)]
+struct SearchArgs {
+    pattern: String,
+    path: Option<String>,
+    #[serde(default)]
+    literal: bool,
+    #[serde(default)]
+    case_insensitive: bool,
+    #[serde(default)]
+    globs: Vec<String>,
+    context_lines: Option<usize>,
+    max_matches_per_file: Option<usize>,
+    max_files: Option<usize>,
+}
+
+/// The matches in one file.
+#[derive(Debug)]
+struct FileMatches {
+    /// Relative to the searched directory.
+    path: String,
+    matches: usize,
+    /// The shown matches with their context, grep-style.
+    excerpt: String,
+}
+
+/// Searches the project's files; see the module docs.
+pub struct SearchTool;
+
+impl SearchTool {
+    pub fn new() -> Self {
+        SearchTool
+    }
+
+    fn invalid(&self, message: impl Into<String>) -> ToolError {
+        ToolError::InvalidArguments {
+            tool_name: self.get_name(),
+            message: message.into(),
+        }
+    }
+}
+
+/// The matches of `pattern` in `text`, the first `max_matches` of them shown with `context`
+/// lines around each: `N: line` for matches and `N- line` for context, with `--` between
+/// separate excerpts. `None` if nothing matches.
+fn file_matches(text: &str, pattern: &Regex, context: usize, max_matches: usize) -> Option<(usize, String)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let matching: Vec<usize> = (0..lines.len()).filter(|&i| pattern.is_match(lines[i])).collect();
+    if matching.is_empty() {
+        return None;
+    }
+    let mut out = Vec::new();
+    let mut next_unprinted = 0;
+    for &i in matching.iter().take(max_matches) {
+        let start = i.saturating_sub(context).max(next_unprinted);
+        if next_unprinted != 0 && start > next_unprinted {
+            out.push("--".to_string());
+        }
+        let end = (i + context).min(lines.len() - 1);
+        for (n, line) in lines.iter().enumerate().take(end + 1).skip(start) {
+            let marker = if pattern.is_match(line) { ':' } else { '-' };
+            out.push(format!("{}{} {}", n + 1, marker, clip_line(line)));
+        }
+        next_unprinted = end + 1;
+    }
+    Some((matching.len(), out.join("\n")))
+}
+
+fn clip_line(line: &str) -> String {
+    if line.chars().count() <= MAX_LINE_CHARS {
+        return line.to_string();
+    }
+    let kept: String = line.chars().take(MAX_LINE_CHARS).collect();
+    format!("{} [... line clipped]", kept)
+}
+
+/// Searches the files under `root` selected by `globs` (gitignore-style; `!` excludes).
+///
+/// # Returns
+/// The files with matches, in path order, and the number of files searched.
+fn search(
+    root: &Path,
+    pattern: &Regex,
+    globs: &[String],
+    context: usize,
+    max_matches_per_file: usize,
+) -> Result<(Vec<FileMatches>, usize), String> {
+    let mut overrides = OverrideBuilder::new(root);
+    for glob in globs {
+        overrides.add(glob).map_err(|e| format!("Invalid glob '{}': {}", glob, e))?;
+    }
+    let overrides = overrides.build().map_err(|e| format!("Invalid globs: {}", e))?;
+    let walker = WalkBuilder::new(root)
+        .overrides(overrides)
+        // .gitignore files apply even where the project is not a git repository.
+        .require_git(false)
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .build();
+
+    let mut found = Vec::new();
+    let mut searched = 0;
+    for entry in walker.filter_map(Result::ok) {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let Ok(bytes) = std::fs::read(entry.path()) else {
+            continue;
+        };
+        if bytes.iter().take(BINARY_SNIFF_BYTES).any(|b| *b == 0) {
+            continue;
+        }
+        searched += 1;
+        let text = String::from_utf8_lossy(&bytes);
+        if let Some((matches, excerpt)) = file_matches(&text, pattern, context, max_matches_per_file) {
+            let path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            found.push(FileMatches {
+                path: path.display().to_string(),
+                matches,
+                excerpt,
+            });
+        }
+    }
+    Ok((found, searched))
+}
+
+#[async_trait]
+impl Tool for SearchTool {
+    fn get_name(&self) -> String {
+        "search".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Searches the project's files for a regular expression (or a literal string), like ripgrep. \
+        Honours .gitignore and skips hidden and binary files. Results are grouped by file, with \
+        numbered lines: `N: line` for matches and `N- line` for context. Use `globs` to narrow the \
+        files (e.g. [\"*.rs\", \"!target/**\"]). Prefer this to grep or find in bash."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        let param = |name: &str, param_type: &str, description: &str, is_required: bool| ToolParameter {
+            name: name.to_string(),
+            param_type: param_type.to_string(),
+            description: description.to_string(),
+            is_required,
+            enum_values: None,
+            items: None,
+            properties: None,
+            required: vec![],
+        };
+        vec![
+            param("pattern", "string", "Regular expression to search for (Rust regex syntax), or the text with `literal`.", true),
+            param("path", "string", "Directory or file to search, absolute or relative to the project (default: the project).", false),
+            param("literal", "boolean", "Search for the pattern as plain text instead of a regular expression.", false),
+            param("case_insensitive", "boolean", "Ignore case.", false),
+            ToolParameter {
+                items: Some(Box::new(param("glob", "string", "A glob.", false))),
+                ..param(
+                    "globs",
+                    "array",
+                    "Only search files matching these globs, gitignore-style; a leading `!` excludes.",
+                    false,
+                )
+            },
+            param(
+                "context_lines",
+                "integer",
+                &format!("Lines shown before and after each match (default {}).", DEFAULT_CONTEXT_LINES),
+                false,
+            ),
+            param(
+                "max_matches_per_file",
+                "integer",
+                &format!("Most matches shown per file (default {}).", DEFAULT_MAX_MATCHES_PER_FILE),
+                false,
+            ),
+            param(
+                "max_files",
+                "integer",
+                &format!("Most files shown (default {}).", DEFAULT_MAX_FILES),
+                false,
+            ),
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: SearchArgs = serde_json::from_value(arguments.clone())
+            .map_err(|e| self.invalid(format!("Failed to parse arguments: {}. Args: {:?}", e, arguments)))?;
+        if args.pattern.is_empty() {
+            return Err(self.invalid("'pattern' cannot be empty."));
+        }
+        let source = if args.literal { regex::escape(&args.pattern) } else { args.pattern.clone() };
+        let pattern = RegexBuilder::new(&source)
+            .case_insensitive(args.case_insensitive)
+            .build()
+            .map_err(|e| self.invalid(format!("Invalid pattern: {}", e)))?;
+        let root = context.resolve_path(args.path.as_deref().unwrap_or("."));
+        if !root.exists() {
+            return Err(ToolError::FileReadError(format!("{} does not exist", root.display())));
+        }
+        let context_lines = args.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES);
+        let max_per_file = args.max_matches_per_file.unwrap_or(DEFAULT_MAX_MATCHES_PER_FILE).max(1);
+        let max_files = args.max_files.unwrap_or(DEFAULT_MAX_FILES).max(1);
+
+        let walk_root = root.clone();
+        let (found, searched) = tokio::task::spawn_blocking(move || {
+            search(&walk_root, &pattern, &args.globs, context_lines, max_per_file)
+        })
+        .await
+        .map_err(|e| ToolError::InternalError(e.to_string()))?
+        .map_err(|message| self.invalid(message))?;
+
+        let total: usize = found.iter().map(|file| file.matches).sum();
+        let mut output = format!(
+            "{} match(es) in {} of {} file(s) searched under {}",
+            total,
+            found.len(),
+            searched,
+            root.display()
+        );
+        if found.len() > max_files {
+            output.push_str(&format!(" (showing the first {} files)", max_files));
+        }
+        output.push('.');
+        for file in found.iter().take(max_files) {
+            output.push_str(&format!("\n\n{} ({} match(es))\n{}", file.path, file.matches, file.excerpt));
+            if file.matches > max_per_file {
+                output.push_str(&format!("\n[{} more match(es) in this file]", file.matches - max_per_file));
+            }
+            if output.chars().count() > MAX_OUTPUT_CHARS {
+                let clipped: String = output.chars().take(MAX_OUTPUT_CHARS).collect();
+                output = format!("{}\n[... output truncated; narrow the search with `path` or `globs`]", clipped);
+                break;
+            }
+        }
+        Ok(ToolExecResult::new_success(Some(output), None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_search_groups_matches_and_respects_gitignore_and_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "use std::fmt;\n\nfn parse() {}\nfn other() {}\nfn parse_all() { parse() }\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/notes.md"), "parse() is documented here\n").unwrap();
+        std::fs::write(dir.path().join("target/out.rs"), "fn parse() {}\n").unwrap();
+        std::fs::write(dir.path().join("src/blob.bin"), b"parse()\0\x01").unwrap();
+
+        let tool = SearchTool::new();
+        let context = ToolContext::for_project(dir.path());
+        let output = tool
+            .execute(json!({"pattern": "parse()", "literal": true, "context_lines": 1}), &context)
+            .await
+            .unwrap()
+            .output
+            .unwrap();
+        assert!(output.starts_with("3 match(es) in 2 of 2 file(s) searched"), "{}", output);
+        assert!(output.contains("src/lib.rs (2 match(es))\n2- \n3: fn parse() {}\n4- fn other() {}\n5: fn parse_all() { parse() }"), "{}", output);
+        assert!(!output.contains("target/out.rs"), "{}", output);
+
+        let output = tool
+            .execute(json!({"pattern": "^fn \\w+", "globs": ["*.rs"], "max_matches_per_file": 1, "context_lines": 0}), &context)
+            .await
+            .unwrap()
+            .output
+            .unwrap();
+        assert!(output.contains("src/lib.rs (3 match(es))\n3: fn parse() {}\n[2 more match(es) in this file]"), "{}", output);
+        assert!(!output.contains("notes.md"), "{}", output);
+
+        assert!(tool.execute(json!({"pattern": "("}), &context).await.is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::base::{Tool, ToolDeterminism, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
 use async_trait::async_trait;
 use serde::Deserialize;
 use serde_json::Value;
@@ -91,7 +92,7 @@ impl Tool for SequentialThinkingTool {
     }
 
     #[instrument(skip(self, arguments), fields(tool_name = %self.get_name()))]
-    async fn execute(&self, arguments: Value) -> Result<ToolExecResult, ToolError> {
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<ToolExecResult, ToolError> {
         debug!(args = ?arguments, "Executing sequential_thinking tool");
         let args: SequentialThinkingArgs =
             serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
@@ -178,12 +179,16 @@ impl Tool for SequentialThinkingTool {
 
         Ok(ToolExecResult {
             output: Some(
-                serde_json::to_string_pretty(&response_data).unwrap_or_else(|_| formatted_output),
+                serde_json::to_string_pretty(&response_data).unwrap_or(formatted_output),
             ),
             error: None,
             error_code: 0,
         })
     }
+
+    fn determinism(&self) -> ToolDeterminism {
+        ToolDeterminism::Deterministic
+    }
 }
 
 #[cfg(test)]
@@ -200,7 +205,7 @@ mod tests {
             "total_thoughts": 3,
             "next_thought_needed": true
         });
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert!(result.output.is_some());
         let output_val: Value = serde_json::from_str(&result.output.unwrap()).unwrap();
 
@@ -232,7 +237,7 @@ mod tests {
             "is_revision": true,
             "revises_thought": 1
         });
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert!(result.output.is_some());
         let output_val: Value = serde_json::from_str(&result.output.unwrap()).unwrap();
         assert_eq!(output_val["is_revision"], true);
@@ -252,7 +257,7 @@ mod tests {
             "total_thoughts": 3,
             "next_thought_needed": true
         });
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
         match result.err().unwrap() {
             ToolError::InvalidArguments { message, .. } => {
@@ -271,7 +276,7 @@ mod tests {
             "next_thought_needed": false
             // "thought" is missing
         });
-        let result = tool.execute(args).await;
+        let result = tool.execute(args, &ToolContext::default()).await;
         assert!(result.is_err());
         match result.err().unwrap() {
             ToolError::InvalidArguments { message, .. } => {
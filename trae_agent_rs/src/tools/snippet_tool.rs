@@ -0,0 +1,177 @@
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::utils::snippet_store::SnippetStore;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+#[derive(Deserialize, Debug)]
+struct SaveSnippetArgs {
+    name: String,
+    content: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GetSnippetArgs {
+    name: String,
+}
+
+fn name_parameter(description: &str) -> ToolParameter {
+    ToolParameter {
+        name: "name".to_string(),
+        param_type: "string".to_string(),
+        description: description.to_string(),
+        is_required: true,
+        enum_values: None,
+        items: None,
+        properties: None,
+        required: vec![],
+    }
+}
+
+/// Stashes a named text fragment for later recall with `get_snippet`.
+pub struct SaveSnippetTool {
+    store: Arc<SnippetStore>,
+}
+
+impl SaveSnippetTool {
+    pub fn new(store: Arc<SnippetStore>) -> Self {
+        SaveSnippetTool { store }
+    }
+}
+
+#[async_trait]
+impl Tool for SaveSnippetTool {
+    fn get_name(&self) -> String {
+        "save_snippet".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Saves a piece of text (e.g., an error trace or the original version of code you are about \
+        to change) under a name, so you can recall it later with get_snippet instead of keeping it \
+        in mind. Saving under an existing name replaces that snippet."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            name_parameter("Name of the snippet: letters, digits, '_', '-' or '.', e.g. \"orig_parse_fn\"."),
+            ToolParameter {
+                name: "content".to_string(),
+                param_type: "string".to_string(),
+                description: "The text to save.".to_string(),
+                is_required: true,
+                enum_values: None,
+                items: None,
+                properties: None,
+                required: vec![],
+            },
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: SaveSnippetArgs =
+            serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+            })?;
+        let name = args.name.trim();
+        let replaced = self
+            .store
+            .save(name, &args.content)
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("{:#}", e),
+            })?;
+        let action = if replaced { "Replaced" } else { "Saved" };
+        Ok(ToolExecResult {
+            output: Some(format!(
+                "{} snippet '{}' ({} lines).",
+                action,
+                name,
+                args.content.lines().count()
+            )),
+            error: None,
+            error_code: 0,
+        })
+    }
+}
+
+/// Recalls a snippet saved with `save_snippet`.
+pub struct GetSnippetTool {
+    store: Arc<SnippetStore>,
+}
+
+impl GetSnippetTool {
+    pub fn new(store: Arc<SnippetStore>) -> Self {
+        GetSnippetTool { store }
+    }
+}
+
+#[async_trait]
+impl Tool for GetSnippetTool {
+    fn get_name(&self) -> String {
+        "get_snippet".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Returns the text of a snippet saved earlier with save_snippet. An unknown name lists the \
+        saved snippets."
+            .to_string()
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        vec![name_parameter("Name the snippet was saved under.")]
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: GetSnippetArgs =
+            serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+            })?;
+        let content = self
+            .store
+            .get(args.name.trim())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("{:#}", e),
+            })?;
+        Ok(ToolExecResult {
+            output: Some(content),
+            error: None,
+            error_code: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_save_and_get_snippet() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(SnippetStore::new(dir.path()).unwrap());
+        let save = SaveSnippetTool::new(store.clone());
+        let get = GetSnippetTool::new(store);
+
+        let output = save
+            .execute(json!({"name": "trace", "content": "line 1\nline 2"}), &ToolContext::default())
+            .await
+            .unwrap()
+            .output
+            .unwrap();
+        assert_eq!(output, "Saved snippet 'trace' (2 lines).");
+        let recalled = get.execute(json!({"name": "trace"}), &ToolContext::default()).await.unwrap().output.unwrap();
+        assert_eq!(recalled, "line 1\nline 2");
+
+        let err = get.execute(json!({"name": "other"}), &ToolContext::default()).await.unwrap_err();
+        match err {
+            ToolError::InvalidArguments { message, .. } => assert!(message.contains("Saved snippets: trace")),
+            other => panic!("Expected InvalidArguments, got {:?}", other),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::base::{Tool, ToolDeterminism, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
 use async_trait::async_trait;
 use serde::Deserialize;
 use serde_json::Value;
@@ -55,7 +56,7 @@ impl Tool for TaskDoneTool {
     // "summary" is not in the `required` list of the top-level parameters object.
     // The current default `get_json_definition` has a FIXME for this.
 
-    async fn execute(&self, arguments: Value) -> Result<ToolExecResult, ToolError> {
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<ToolExecResult, ToolError> {
         info!(args = ?arguments, tool_name = %self.get_name(), "Executing task_done tool");
 
         let args: TaskDoneArgs =
@@ -79,6 +80,10 @@ impl Tool for TaskDoneTool {
             error_code: 0,
         })
     }
+
+    fn determinism(&self) -> ToolDeterminism {
+        ToolDeterminism::Deterministic
+    }
 }
 
 #[cfg(test)]
@@ -90,7 +95,7 @@ mod tests {
     async fn test_task_done_tool_with_summary() {
         let tool = TaskDoneTool::new();
         let args = json!({"summary": "Successfully completed all objectives."});
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert!(result
             .output
             .unwrap()
@@ -102,7 +107,7 @@ mod tests {
     async fn test_task_done_tool_without_summary() {
         let tool = TaskDoneTool::new();
         let args = json!({}); // No summary argument
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert!(result
             .output
             .unwrap()
@@ -114,7 +119,7 @@ mod tests {
     async fn test_task_done_tool_empty_summary() {
         let tool = TaskDoneTool::new();
         let args = json!({"summary": ""});
-        let result = tool.execute(args).await.unwrap();
+        let result = tool.execute(args, &ToolContext::default()).await.unwrap();
         assert!(result.output.unwrap().contains("Summary: ")); // Summary is empty string
         assert_eq!(result.error_code, 0);
     }
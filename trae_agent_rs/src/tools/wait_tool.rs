@@ -0,0 +1,108 @@
+use super::base::{Tool, ToolError, ToolExecResult, ToolParameter};
+use super::context::ToolContext;
+use crate::utils::clock::now_utc;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+use tracing::info;
+
+/// Longest wait a single call may ask for, in seconds.
+pub const MAX_WAIT_SECS: f64 = 60.0;
+
+#[derive(Deserialize, Debug)]
+struct WaitArgs {
+    seconds: f64,
+    reason: Option<String>,
+}
+
+/// Pauses the agent for a bounded time, e.g. while a server starts or a build artifact is
+/// produced, and reports the current time. Used instead of `sleep` in `bash`, which is
+/// unbounded and hides the time spent from the run.
+pub struct WaitTool;
+
+impl WaitTool {
+    pub fn new() -> Self {
+        WaitTool
+    }
+}
+
+#[async_trait]
+impl Tool for WaitTool {
+    fn get_name(&self) -> String {
+        "wait".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        format!(
+            "Waits for the given number of seconds (at most {}) and returns the current date and time. \
+            Use it when polling for something that takes time, such as a server starting up or a build \
+            producing an artifact, instead of `sleep` in bash. Check the condition after each wait.",
+            MAX_WAIT_SECS
+        )
+    }
+
+    fn get_parameters(&self) -> Vec<ToolParameter> {
+        vec![
+            ToolParameter {
+                name: "seconds".to_string(), param_type: "number".to_string(),
+                description: format!("How long to wait, in seconds (0 to {}).", MAX_WAIT_SECS),
+                is_required: true, enum_values: None, items: None, properties: None, required: vec![],
+            },
+            ToolParameter {
+                name: "reason".to_string(), param_type: "string".to_string(),
+                description: "Optional: what is being waited for.".to_string(),
+                is_required: false, enum_values: None, items: None, properties: None, required: vec![],
+            },
+        ]
+    }
+
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<ToolExecResult, ToolError> {
+        let args: WaitArgs =
+            serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!("Failed to parse arguments: {}. Args: {:?}", e, arguments),
+            })?;
+        if !(0.0..=MAX_WAIT_SECS).contains(&args.seconds) {
+            return Err(ToolError::InvalidArguments {
+                tool_name: self.get_name(),
+                message: format!(
+                    "seconds must be between 0 and {}, got {}. Wait repeatedly and check in between for longer waits.",
+                    MAX_WAIT_SECS, args.seconds
+                ),
+            });
+        }
+        info!(seconds = args.seconds, reason = ?args.reason, "Waiting");
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs_f64(args.seconds)) => {}
+            _ = context.cancellation.cancelled() => {
+                return Ok(ToolExecResult::new_failure("Wait stopped: the run was cancelled.".to_string(), 1));
+            }
+        }
+        Ok(ToolExecResult {
+            output: Some(format!("Waited {} seconds. Current time: {}", args.seconds, now_utc())),
+            error: None,
+            error_code: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_is_bounded() {
+        let tool = WaitTool::new();
+        let started = tokio::time::Instant::now();
+        let result = tool.execute(json!({"seconds": 30, "reason": "server startup"}), &ToolContext::default()).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(30));
+        assert!(result.output.unwrap().starts_with("Waited 30 seconds. Current time: "));
+
+        match tool.execute(json!({"seconds": 600}), &ToolContext::default()).await.unwrap_err() {
+            ToolError::InvalidArguments { message, .. } => assert!(message.contains("between 0 and 60")),
+            other => panic!("Expected InvalidArguments, got {:?}", other),
+        }
+    }
+}
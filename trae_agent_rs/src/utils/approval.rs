@@ -0,0 +1,388 @@
+//! # Approval Gate
+//!
+//! The `approval` config sorts dangerous tool calls into those that run without asking, those
+//! the user must approve first, and those that are refused. Shell commands (of `bash`,
+//! `record_reproduction` or any tool declaring `Tool::shell_command`) are matched against its
+//! command patterns and file writes against its path globs, `deny` first, then `ask`, then
+//! `auto`; calls matching none get its `default`. A command is matched part by part, so
+//! `cargo test && git push` is as strict as `git push` alone, and so are `echo $(git push)`,
+//! `(git push)`, `{ git push; }` and `sh -c 'git push'`. Restoring a checkpoint rewrites files
+//! across the project, so it is always asked about unless the `default` refuses it.
+//!
+//! `ToolExecutor` enforces the gate before a call runs. The user is asked on the terminal, and
+//! a call nobody answers for is refused. Each decision is attached to the call's result, which
+//! puts it in the trajectory. A reproduction command is only recorded, and so re-run during
+//! verification, once its `record_reproduction` call was allowed.
+
+use super::clock::now_utc;
+use super::guards::write_target_for_tool_call;
+use super::permissions::confirm_on_terminal;
+use crate::config::{ApprovalConfig, ApprovalLists, ApprovalPolicy};
+use crate::tools::ToolContext;
+use ignore::gitignore::GitignoreBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// What a gated call does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalTarget {
+    Command,
+    FileWrite,
+    /// Restoring a checkpoint, which writes back and removes files across the project.
+    CheckpointRestore,
+}
+
+/// The gate's decision on one tool call, recorded with its result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalDecision {
+    /// When it was decided, `YYYY-MM-DD HH:MM:SS UTC`.
+    pub timestamp: String,
+    pub target: ApprovalTarget,
+    /// The command, the path written, or the checkpoint restored.
+    pub subject: String,
+    pub policy: ApprovalPolicy,
+    /// The pattern that set the policy; `None` if the default did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// Whether the call was allowed to run.
+    pub approved: bool,
+    /// Whether the user was asked.
+    pub asked: bool,
+}
+
+/// Asks the user whether a call may run. Called from a blocking thread.
+pub trait ApprovalPrompt: Send + Sync {
+    fn approve(&self, question: &str) -> bool;
+}
+
+/// Asks on the terminal with `confirm_on_terminal`.
+pub struct TerminalApprovalPrompt;
+
+impl ApprovalPrompt for TerminalApprovalPrompt {
+    fn approve(&self, question: &str) -> bool {
+        confirm_on_terminal(question)
+    }
+}
+
+/// Decides whether commands and file writes may run; see the module docs.
+pub struct ApprovalGate {
+    config: ApprovalConfig,
+    /// Held while the user is asked, so concurrent tool calls are asked about one at a time.
+    prompt: Mutex<Arc<dyn ApprovalPrompt>>,
+}
+
+impl ApprovalGate {
+    pub fn new(config: ApprovalConfig, prompt: Arc<dyn ApprovalPrompt>) -> Self {
+        Self {
+            config,
+            prompt: Mutex::new(prompt),
+        }
+    }
+
+    /// Decides on a call of `tool` with `arguments`, which runs the shell `command` if any,
+    /// asking the user if the policy says so.
+    ///
+    /// # Returns
+    /// `None` if the call is neither a command, a file write nor a checkpoint restore, else
+    /// the decision.
+    pub async fn check(
+        &self,
+        tool: &str,
+        command: Option<&str>,
+        arguments: &Value,
+        context: &ToolContext,
+    ) -> Option<ApprovalDecision> {
+        let (target, subject, policy, rule) = if let Some(command) = command {
+            let (policy, rule) = self.command_policy(command);
+            (ApprovalTarget::Command, command.trim().to_string(), policy, rule)
+        } else if let Some(path) = write_target_for_tool_call(tool, arguments) {
+            let path = context.resolve_path(path);
+            let (policy, rule) = self.path_policy(&path, context.project_root.as_deref());
+            (ApprovalTarget::FileWrite, path.display().to_string(), policy, rule)
+        } else if tool == "restore_checkpoint" {
+            let name = arguments.get("name").and_then(Value::as_str).unwrap_or_default();
+            let policy = self.config.default.max(ApprovalPolicy::Ask);
+            (ApprovalTarget::CheckpointRestore, format!("the restore of checkpoint '{}'", name), policy, None)
+        } else {
+            return None;
+        };
+        let (approved, asked) = match policy {
+            ApprovalPolicy::Auto => (true, false),
+            ApprovalPolicy::Deny => (false, false),
+            ApprovalPolicy::Ask => {
+                let question = match target {
+                    ApprovalTarget::Command => format!("Approve running `{}`?", subject),
+                    ApprovalTarget::FileWrite => format!("Approve {} writing to {}?", tool, subject),
+                    ApprovalTarget::CheckpointRestore => format!(
+                        "Approve {}? Files changed since it was saved are overwritten and files created since are removed.",
+                        subject
+                    ),
+                };
+                let prompt = self.prompt.lock().await;
+                let prompt = prompt.clone();
+                let approved = tokio::task::spawn_blocking(move || prompt.approve(&question))
+                    .await
+                    .unwrap_or(false);
+                (approved, true)
+            }
+        };
+        Some(ApprovalDecision {
+            timestamp: now_utc(),
+            target,
+            subject,
+            policy,
+            rule,
+            approved,
+            asked,
+        })
+    }
+
+    /// The strictest policy of the parts of `command`, with the pattern that set it.
+    fn command_policy(&self, command: &str) -> (ApprovalPolicy, Option<String>) {
+        command_parts(command)
+            .into_iter()
+            .map(|part| {
+                match_lists(&self.config.commands, |pattern| wildcard_match(&normalize(pattern), &part))
+                    .unwrap_or((self.config.default, None))
+            })
+            .max_by_key(|(policy, _)| *policy)
+            .unwrap_or((self.config.default, None))
+    }
+
+    /// The policy of a write to `path`; globs are relative to `project_root` for paths in it.
+    fn path_policy(&self, path: &Path, project_root: Option<&Path>) -> (ApprovalPolicy, Option<String>) {
+        let root = project_root.filter(|root| path.starts_with(root)).unwrap_or(Path::new("/"));
+        match_lists(&self.config.paths, |pattern| {
+            let mut builder = GitignoreBuilder::new(root);
+            builder.add_line(None, pattern).is_ok()
+                && builder
+                    .build()
+                    .is_ok_and(|globs| globs.matched_path_or_any_parents(path, false).is_ignore())
+        })
+        .unwrap_or((self.config.default, None))
+    }
+}
+
+/// The first pattern `matches` accepts, `deny` first, then `ask`, then `auto`.
+fn match_lists(lists: &ApprovalLists, matches: impl Fn(&str) -> bool) -> Option<(ApprovalPolicy, Option<String>)> {
+    [
+        (ApprovalPolicy::Deny, &lists.deny),
+        (ApprovalPolicy::Ask, &lists.ask),
+        (ApprovalPolicy::Auto, &lists.auto),
+    ]
+    .into_iter()
+    .find_map(|(policy, patterns)| {
+        patterns
+            .iter()
+            .find(|pattern| matches(pattern))
+            .map(|pattern| (policy, Some(pattern.clone())))
+    })
+}
+
+/// The commands of a shell line, split at `&&`, `||`, `;`, `|`, newlines, command
+/// substitutions, subshells and groups, with the script of `sh -c`/`bash -c`/`eval` split
+/// too. Whitespace is collapsed and quotes are trimmed but not parsed, which can only split a
+/// command into more parts.
+fn command_parts(command: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for part in command.split(['\n', ';', '|', '&', '`', '(', ')', '{', '}']) {
+        let part = normalize(part.trim_matches(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '$')));
+        if part.is_empty() {
+            continue;
+        }
+        if let Some(script) = shell_script(&part) {
+            parts.extend(command_parts(script));
+        }
+        parts.push(part);
+    }
+    parts
+}
+
+/// The script a shell command runs, for `sh -c <script>` (any common shell) and `eval <script>`.
+fn shell_script(command: &str) -> Option<&str> {
+    let (program, rest) = command.split_once(' ')?;
+    if program == "eval" {
+        return Some(rest);
+    }
+    let shell = Path::new(program).file_name()?.to_str()?;
+    let script = rest.strip_prefix("-c ")?;
+    ["sh", "bash", "zsh", "dash", "ksh"].contains(&shell).then_some(script)
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether `pattern` matches all of `text`, `*` matching any characters.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+    let pieces: Vec<&str> = rest.split('*').collect();
+    let (last, middle) = pieces.split_last().expect("split yields at least one piece");
+    for piece in middle {
+        match remaining.find(piece) {
+            Some(at) => remaining = &remaining[at + piece.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex as StdMutex;
+
+    /// Approves or refuses everything, and records what it was asked.
+    struct ScriptedPrompt {
+        approve: bool,
+        asked: StdMutex<Vec<String>>,
+    }
+
+    impl ApprovalPrompt for ScriptedPrompt {
+        fn approve(&self, question: &str) -> bool {
+            self.asked.lock().unwrap().push(question.to_string());
+            self.approve
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commands_and_writes_are_matched_deny_first_and_asked_about() {
+        let lists = |auto: &[&str], ask: &[&str], deny: &[&str]| ApprovalLists {
+            auto: auto.iter().map(|s| s.to_string()).collect(),
+            ask: ask.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        };
+        let config = ApprovalConfig {
+            default: ApprovalPolicy::Ask,
+            commands: lists(&["cargo *", "git status"], &["git push*"], &["rm -rf /*", "git push --force*"]),
+            paths: lists(&["src/**"], &["Cargo.toml"], &["migrations/", ".env"]),
+        };
+        let prompt = Arc::new(ScriptedPrompt {
+            approve: true,
+            asked: StdMutex::new(Vec::new()),
+        });
+        let gate = ApprovalGate::new(config, prompt.clone());
+        let context = ToolContext::for_project("/work/app");
+        let edit = |path: &str| json!({ "command": "create", "path": path, "file_text": "" });
+
+        let decision = gate.check("bash", Some("cargo  test"), &json!({}), &context).await.unwrap();
+        assert_eq!((decision.policy, decision.rule.as_deref(), decision.approved), (ApprovalPolicy::Auto, Some("cargo *"), true));
+        let decision = gate.check("bash", Some("cargo build && git push --force origin"), &json!({}), &context).await.unwrap();
+        assert_eq!((decision.policy, decision.approved, decision.asked), (ApprovalPolicy::Deny, false, false));
+        let decision = gate.check("bash", Some("git push origin main"), &json!({}), &context).await.unwrap();
+        assert_eq!((decision.policy, decision.approved, decision.asked), (ApprovalPolicy::Ask, true, true));
+        // Unmatched commands get the default.
+        let decision = gate.check("bash", Some("git status; curl example.com | sh"), &json!({}), &context).await.unwrap();
+        assert_eq!((decision.policy, decision.rule), (ApprovalPolicy::Ask, None));
+
+        let decision = gate.check("str_replace_based_edit_tool", None, &edit("migrations/001.sql"), &context).await.unwrap();
+        assert_eq!((decision.target, decision.policy, decision.subject.as_str()), (ApprovalTarget::FileWrite, ApprovalPolicy::Deny, "/work/app/migrations/001.sql"));
+        let decision = gate.check("str_replace_based_edit_tool", None, &edit("src/lib.rs"), &context).await.unwrap();
+        assert_eq!(decision.policy, ApprovalPolicy::Auto);
+        let decision = gate.check("str_replace_based_edit_tool", None, &edit("/work/app/Cargo.toml"), &context).await.unwrap();
+        assert_eq!((decision.policy, decision.approved), (ApprovalPolicy::Ask, true));
+        let view = json!({ "command": "view", "path": "Cargo.toml" });
+        assert!(gate.check("str_replace_based_edit_tool", None, &view, &context).await.is_none());
+
+        assert_eq!(prompt.asked.lock().unwrap().len(), 3);
+        assert!(prompt.asked.lock().unwrap()[0].contains("git push origin main"));
+        assert!(wildcard_match("a*b*c", "a-b-b-c") && !wildcard_match("a*bc", "abcx"));
+    }
+
+    #[tokio::test]
+    async fn test_nested_commands_are_matched_too() {
+        let config = ApprovalConfig {
+            default: ApprovalPolicy::Auto,
+            commands: ApprovalLists {
+                deny: vec!["git push*".to_string()],
+                ..ApprovalLists::default()
+            },
+            paths: ApprovalLists::default(),
+        };
+        let prompt = Arc::new(ScriptedPrompt {
+            approve: true,
+            asked: StdMutex::new(Vec::new()),
+        });
+        let gate = ApprovalGate::new(config, prompt);
+        let context = ToolContext::for_project("/work/app");
+        for command in [
+            "echo $(git push)",
+            "echo `git push`",
+            "echo \"$(git push --force)\"",
+            "(git push)",
+            "{ git push; }",
+            "sh -c 'git push'",
+            "/bin/bash -c \"cargo test && git push origin\"",
+            "eval git push",
+        ] {
+            let decision = gate.check("bash", Some(command), &json!({}), &context).await.unwrap();
+            assert_eq!((decision.policy, decision.approved), (ApprovalPolicy::Deny, false), "{}", command);
+        }
+        let decision = gate.check("bash", Some("sh -c 'git status'"), &json!({}), &context).await.unwrap();
+        assert_eq!(decision.policy, ApprovalPolicy::Auto);
+        // A restore is asked about even when the default runs everything.
+        let decision = gate.check("restore_checkpoint", None, &json!({ "name": "green" }), &context).await.unwrap();
+        assert_eq!(
+            (decision.target, decision.policy, decision.asked),
+            (ApprovalTarget::CheckpointRestore, ApprovalPolicy::Ask, true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refused_writes_do_not_use_the_change_budget() {
+        use crate::llm::base_client::{ToolCall, ToolCallFunction};
+        use crate::tools::{EditTool, ToolExecutor};
+        use crate::utils::guards::{ChangeBudget, WriteGuard};
+
+        let project = tempfile::tempdir().unwrap();
+        let config = ApprovalConfig {
+            default: ApprovalPolicy::Auto,
+            commands: ApprovalLists::default(),
+            paths: ApprovalLists {
+                deny: vec!["secrets/".to_string()],
+                ..ApprovalLists::default()
+            },
+        };
+        let prompt = Arc::new(ScriptedPrompt {
+            approve: false,
+            asked: StdMutex::new(Vec::new()),
+        });
+        let mut executor = ToolExecutor::new(vec![Arc::new(EditTool::new())]);
+        executor.set_approval_gate(Some(Arc::new(ApprovalGate::new(config, prompt))));
+        let guard = Arc::new(WriteGuard::new().with_budget(ChangeBudget {
+            max_files: None,
+            max_writes: Some(1),
+        }));
+        let context = ToolContext {
+            path_policy: Some(guard.clone()),
+            ..ToolContext::for_project(project.path())
+        };
+        let create = |path: &str| ToolCall {
+            id: "call-1".to_string(),
+            tool_type: "function".to_string(),
+            function: ToolCallFunction {
+                name: "str_replace_based_edit_tool".to_string(),
+                arguments: json!({ "command": "create", "path": project.path().join(path), "file_text": "x" }).to_string(),
+            },
+        };
+
+        let result = executor.execute_tool_call(&create("secrets/key.txt"), &context).await;
+        assert!(!result.success && !result.approval.unwrap().approved);
+        assert!(guard.touched_files().is_empty());
+        // The one write the budget allows is still there.
+        let result = executor.execute_tool_call(&create("notes.txt"), &context).await;
+        assert!(result.success, "{:?}", result.error);
+        let result = executor.execute_tool_call(&create("more.txt"), &context).await;
+        assert!(result.error.unwrap().contains("change budget"));
+    }
+}
@@ -0,0 +1,158 @@
+//! # Task Attachments
+//!
+//! Files given with `trae run --attach` are included in the task's first user message, so
+//! specs and reproduction logs do not have to be pasted into the task string. Each file is
+//! limited in size; a larger file is summarized to its beginning, its end and the lines that
+//! look like errors, which is what matters in a long log.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Default size limit of one attachment, in bytes.
+pub const DEFAULT_ATTACHMENT_MAX_BYTES: usize = 32 * 1024;
+
+/// Lines kept from the beginning and the end of a summarized file.
+const HEAD_LINES: usize = 40;
+const TAIL_LINES: usize = 80;
+/// Lines kept around each line that looks like an error.
+const ERROR_CONTEXT_LINES: usize = 2;
+
+/// Words that mark a line of a log as worth keeping (compared in lowercase).
+const ERROR_MARKERS: &[&str] = &["error", "panic", "fail", "exception", "traceback", "fatal"];
+
+/// A file attached to a task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// The name the file is presented under: its path as given.
+    pub name: String,
+    pub content: String,
+    /// Size of the file, in bytes.
+    pub original_bytes: usize,
+    /// Whether `content` is a summary of a file over the size limit.
+    #[serde(default)]
+    pub summarized: bool,
+}
+
+impl Attachment {
+    /// Reads the text file at `path`, summarizing it if it is larger than `max_bytes`.
+    pub fn load(path: &Path, max_bytes: usize) -> Result<Self> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read attachment {}", path.display()))?;
+        let original_bytes = bytes.len();
+        let text = String::from_utf8(bytes)
+            .map_err(|_| anyhow::anyhow!("Attachment {} is not a UTF-8 text file", path.display()))?;
+        let summarized = original_bytes > max_bytes;
+        let content = if summarized { summarize(&text, max_bytes) } else { text };
+        Ok(Self {
+            name: path.display().to_string(),
+            content,
+            original_bytes,
+            summarized,
+        })
+    }
+}
+
+/// Formats attachments for the task message, one fenced block per file.
+pub fn format_attachments(attachments: &[Attachment]) -> String {
+    let mut out = String::new();
+    for attachment in attachments {
+        let note = if attachment.summarized {
+            format!(
+                " (summarized from {} bytes: beginning, end and error lines)",
+                attachment.original_bytes
+            )
+        } else {
+            String::new()
+        };
+        out.push_str(&format!(
+            "\n[Attached file: {}]{}\n```\n{}\n```\n",
+            attachment.name,
+            note,
+            attachment.content.trim_end()
+        ));
+    }
+    out
+}
+
+/// Keeps the head, the tail and the error lines (with some context) of `text`, within
+/// `max_bytes`.
+fn summarize(text: &str, max_bytes: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut keep = vec![false; lines.len()];
+    for flag in keep.iter_mut().take(HEAD_LINES) {
+        *flag = true;
+    }
+    for flag in keep.iter_mut().skip(lines.len().saturating_sub(TAIL_LINES)) {
+        *flag = true;
+    }
+    for (i, line) in lines.iter().enumerate() {
+        let lower = line.to_lowercase();
+        if ERROR_MARKERS.iter().any(|marker| lower.contains(marker)) {
+            let end = (i + ERROR_CONTEXT_LINES + 1).min(lines.len());
+            for flag in &mut keep[i.saturating_sub(ERROR_CONTEXT_LINES)..end] {
+                *flag = true;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    let mut omitted = 0;
+    for (line, kept) in lines.iter().zip(&keep) {
+        if !kept {
+            omitted += 1;
+            continue;
+        }
+        if omitted > 0 {
+            out.push_str(&format!("... [{} lines omitted] ...\n", omitted));
+            omitted = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    if out.len() > max_bytes {
+        let mut end = max_bytes;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.truncate(end);
+        out.push_str("\n... [truncated] ...\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_large_logs_keep_head_tail_and_errors() {
+        let dir = tempdir().unwrap();
+        let spec = dir.path().join("design.md");
+        std::fs::write(&spec, "# Design\nUse a queue.\n").unwrap();
+        let log = dir.path().join("build.log");
+        let mut text: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+        text = text.replace("line 500\n", "error[E0308]: mismatched types\n");
+        std::fs::write(&log, &text).unwrap();
+
+        let small = Attachment::load(&spec, 1024).unwrap();
+        assert!(!small.summarized);
+        assert_eq!(small.content, "# Design\nUse a queue.\n");
+
+        let large = Attachment::load(&log, 4096).unwrap();
+        assert!(large.summarized);
+        assert_eq!(large.original_bytes, text.len());
+        assert!(large.content.len() <= 4096 + 32);
+        assert!(large.content.starts_with("line 0\n"));
+        assert!(large.content.contains("line 498\nline 499\nerror[E0308]: mismatched types\nline 501\n"));
+        assert!(large.content.contains("... [458 lines omitted] ..."));
+        assert!(large.content.ends_with("line 999\n"));
+
+        let formatted = format_attachments(&[small, large]);
+        assert!(formatted.starts_with(&format!("\n[Attached file: {}]\n```\n# Design\nUse a queue.\n```\n", spec.display())));
+        assert!(formatted.contains(&format!("(summarized from {} bytes", text.len())));
+
+        std::fs::write(dir.path().join("blob.bin"), [0xff, 0xfe, 0x00]).unwrap();
+        assert!(Attachment::load(&dir.path().join("blob.bin"), 1024).is_err());
+    }
+}
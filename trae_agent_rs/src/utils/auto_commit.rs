@@ -0,0 +1,177 @@
+//! # Auto-Commit
+//!
+//! Commits the changes of a successful run (`trae run --commit-on-success`). The commit goes
+//! on a new, dedicated branch unless committing on the current branch is explicitly allowed.
+//! Its subject comes from a template such as `"fix: {summary}"`, and its body is the summary
+//! the agent passed to `task_done`.
+
+use crate::agent::base_agent::AgentExecution;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+/// Placeholder in the subject template replaced by the first line of the summary.
+const SUMMARY_PLACEHOLDER: &str = "{summary}";
+/// Maximum length of the summary text inserted into the subject line.
+const MAX_SUBJECT_SUMMARY: usize = 72;
+
+/// A commit created for a successful run.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AutoCommit {
+    /// The branch the commit was made on.
+    pub branch: String,
+    /// The full hash of the commit.
+    pub commit: String,
+}
+
+/// Returns the summary the agent passed to its last `task_done` call, if any.
+pub fn task_done_summary(execution: &AgentExecution) -> Option<String> {
+    execution
+        .steps
+        .iter()
+        .rev()
+        .filter_map(|step| step.tool_calls_made.as_ref())
+        .flat_map(|calls| calls.iter().rev())
+        .filter(|call| call.function.name == "task_done")
+        .find_map(|call| {
+            let args: serde_json::Value = serde_json::from_str(&call.function.arguments).ok()?;
+            let summary = args.get("summary")?.as_str()?.trim();
+            (!summary.is_empty()).then(|| summary.to_string())
+        })
+}
+
+/// Builds the commit message: the template with `{summary}` replaced by the first line of
+/// the summary (or of the task, without a summary) as subject, and the summary as body.
+pub fn commit_message(template: &str, summary: Option<&str>, task: &str) -> String {
+    let headline = summary
+        .or(Some(task))
+        .and_then(|text| text.lines().map(str::trim).find(|line| !line.is_empty()))
+        .unwrap_or_default();
+    let headline: String = headline.chars().take(MAX_SUBJECT_SUMMARY).collect();
+    let subject = template.replace(SUMMARY_PLACEHOLDER, &headline);
+    match summary {
+        Some(body) => format!("{}\n\n{}", subject.trim(), body.trim()),
+        None => subject.trim().to_string(),
+    }
+}
+
+/// Derives a branch name such as `trae/fix-the-parser-1720000000` from a commit subject.
+pub fn branch_name(subject: &str, timestamp: u64) -> String {
+    let mut slug = String::new();
+    for c in subject.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.len() >= 40 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        format!("trae/run-{}", timestamp)
+    } else {
+        format!("trae/{}-{}", slug, timestamp)
+    }
+}
+
+/// Commits all changes in the project (including untracked files).
+///
+/// # Arguments
+/// * `project_path`: Root of the git repository.
+/// * `message`: The commit message.
+/// * `new_branch`: Branch to create from `HEAD` and commit on; `None` commits on the current
+///   branch.
+///
+/// # Returns
+/// The created commit, or an error if there is nothing to commit or a git command fails.
+pub fn commit_changes(project_path: &Path, message: &str, new_branch: Option<&str>) -> Result<AutoCommit> {
+    if git(project_path, &["status", "--porcelain"])?.trim().is_empty() {
+        anyhow::bail!("There are no changes to commit");
+    }
+    if let Some(branch) = new_branch {
+        git(project_path, &["checkout", "-b", branch])?;
+    }
+    git(project_path, &["add", "-A"])?;
+    git(project_path, &["commit", "-q", "-m", message])?;
+
+    let branch = git(project_path, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let commit = git(project_path, &["rev-parse", "HEAD"])?;
+    Ok(AutoCommit {
+        branch: branch.trim().to_string(),
+        commit: commit.trim().to_string(),
+    })
+}
+
+fn git(project_path: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(project_path)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to execute git {} in {}", args.join(" "), project_path.display()))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed with status {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git_repo() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for args in [
+            vec!["init", "-q", "-b", "main"],
+            vec!["config", "user.name", "Test User"],
+            vec!["config", "user.email", "test@example.com"],
+        ] {
+            git(dir.path(), &args).unwrap();
+        }
+        std::fs::write(dir.path().join("lib.rs"), "fn a() {}\n").unwrap();
+        git(dir.path(), &["add", "-A"]).unwrap();
+        git(dir.path(), &["commit", "-q", "-m", "init"]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_commit_message_and_branch_name() {
+        let message = commit_message(
+            "fix: {summary}",
+            Some("Handle empty input in the parser\n\nAdded a guard and a test."),
+            "task",
+        );
+        assert_eq!(
+            message,
+            "fix: Handle empty input in the parser\n\nHandle empty input in the parser\n\nAdded a guard and a test."
+        );
+        assert_eq!(commit_message("fix: {summary}", None, "Fix the bug\nmore"), "fix: Fix the bug");
+        assert_eq!(
+            branch_name("fix: Handle empty input!", 7),
+            "trae/fix-handle-empty-input-7"
+        );
+        assert_eq!(branch_name("!!!", 7), "trae/run-7");
+    }
+
+    #[test]
+    fn test_commit_changes_on_new_branch() {
+        let repo = git_repo();
+        assert!(commit_changes(repo.path(), "fix: nothing", Some("trae/x")).is_err());
+
+        std::fs::write(repo.path().join("lib.rs"), "fn a() { b() }\n").unwrap();
+        std::fs::write(repo.path().join("new.rs"), "fn b() {}\n").unwrap();
+        let commit = commit_changes(repo.path(), "fix: call b", Some("trae/fix-call-b")).unwrap();
+        assert_eq!(commit.branch, "trae/fix-call-b");
+        assert_eq!(git(repo.path(), &["log", "-1", "--format=%s"]).unwrap().trim(), "fix: call b");
+        assert!(git(repo.path(), &["status", "--porcelain"]).unwrap().is_empty());
+        // The original branch is untouched.
+        assert_eq!(git(repo.path(), &["log", "-1", "--format=%s", "main"]).unwrap().trim(), "init");
+    }
+}
@@ -0,0 +1,167 @@
+//! # Run Bundles
+//!
+//! Packages the artifacts of a `trae run` (patch, trajectory, Lakeview summary, verification
+//! logs) into a single `.tar.gz` archive with a machine-readable `manifest.json`, for
+//! attaching to tickets or uploading from CI.
+
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version of the manifest layout, bumped on incompatible changes.
+const MANIFEST_VERSION: u32 = 1;
+
+/// One file stored in a bundle, as listed in the manifest.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BundleEntry {
+    /// Path of the file inside the archive.
+    pub name: String,
+    /// What the file contains (e.g., "patch", "trajectory", "verification_log").
+    pub kind: String,
+    /// Size in bytes.
+    pub size: u64,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    manifest_version: u32,
+    generator: String,
+    created_at: u64,
+    run: &'a Value,
+    files: &'a [BundleEntry],
+}
+
+/// Collects artifacts and writes them as a gzip-compressed tar archive.
+#[derive(Debug, Default)]
+pub struct RunBundle {
+    files: Vec<(BundleEntry, Vec<u8>)>,
+}
+
+impl RunBundle {
+    /// Creates an empty bundle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file with the given contents.
+    pub fn add_bytes(&mut self, name: &str, kind: &str, contents: impl Into<Vec<u8>>) {
+        let contents = contents.into();
+        self.files.push((
+            BundleEntry {
+                name: name.to_string(),
+                kind: kind.to_string(),
+                size: contents.len() as u64,
+            },
+            contents,
+        ));
+    }
+
+    /// Adds a file read from disk.
+    pub fn add_file(&mut self, name: &str, kind: &str, path: &Path) -> Result<()> {
+        let contents =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        self.add_bytes(name, kind, contents);
+        Ok(())
+    }
+
+    /// The files added so far.
+    pub fn entries(&self) -> Vec<&BundleEntry> {
+        self.files.iter().map(|(entry, _)| entry).collect()
+    }
+
+    /// Writes the archive to `out_path`, with `manifest.json` as its first entry.
+    ///
+    /// # Arguments
+    /// * `run`: Machine-readable description of the run, embedded in the manifest.
+    pub fn write(&self, out_path: &Path, run: &Value) -> Result<()> {
+        let entries: Vec<BundleEntry> = self.files.iter().map(|(e, _)| e.clone()).collect();
+        let manifest = Manifest {
+            manifest_version: MANIFEST_VERSION,
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            run,
+            files: &entries,
+        };
+        let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+
+        if let Some(parent) = out_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        let file = File::create(out_path)
+            .with_context(|| format!("Failed to create bundle {}", out_path.display()))?;
+        let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        append(&mut archive, "manifest.json", &manifest_json, manifest.created_at)?;
+        for (entry, contents) in &self.files {
+            append(&mut archive, &entry.name, contents, manifest.created_at)?;
+        }
+        archive
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .with_context(|| format!("Failed to finish bundle {}", out_path.display()))?;
+        Ok(())
+    }
+}
+
+fn append<W: std::io::Write>(
+    archive: &mut tar::Builder<W>,
+    name: &str,
+    contents: &[u8],
+    mtime: u64,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    archive
+        .append_data(&mut header, name, contents)
+        .with_context(|| format!("Failed to add {} to bundle", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use serde_json::json;
+    use std::io::Read;
+
+    #[test]
+    fn test_bundle_contains_manifest_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("run.log");
+        std::fs::write(&log, "ok\n").unwrap();
+
+        let mut bundle = RunBundle::new();
+        bundle.add_bytes("patch.diff", "patch", "diff --git a/x b/x\n");
+        bundle.add_file("logs/run.log", "verification_log", &log).unwrap();
+        assert!(bundle.add_file("missing", "x", &dir.path().join("nope")).is_err());
+
+        let out = dir.path().join("out/bundle.tar.gz");
+        bundle.write(&out, &json!({"success": true})).unwrap();
+
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(&out).unwrap()));
+        let mut names = Vec::new();
+        let mut manifest = String::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().display().to_string();
+            if name == "manifest.json" {
+                entry.read_to_string(&mut manifest).unwrap();
+            }
+            names.push(name);
+        }
+        assert_eq!(names, vec!["manifest.json", "patch.diff", "logs/run.log"]);
+        let manifest: Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["run"]["success"], true);
+        assert_eq!(manifest["files"][1]["kind"], "verification_log");
+        assert_eq!(manifest["files"][1]["size"], 3);
+    }
+}
@@ -0,0 +1,152 @@
+//! # Run Changelog
+//!
+//! A short "What changed and why" section for the changes of a successful run, built from the
+//! agent's final report (or its `task_done` summary) and the diffstat of the project. Depending
+//! on the `changelog` config, it is appended to the `--commit-on-success` commit message,
+//! written next to the trajectory as a pull request body, or added as an entry to a changelog
+//! file in the project, so agent-authored changes do not go undocumented.
+
+use super::auto_commit::task_done_summary;
+use super::clock::format_utc;
+use super::git_utils::{DiffStat, FileStats};
+use crate::agent::base_agent::AgentExecution;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Files listed one by one; beyond that, only the total is given.
+const MAX_LISTED_FILES: usize = 20;
+
+/// What a run changed and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Changelog {
+    /// Why: the summary of the final report, the `task_done` summary or the task.
+    pub summary: String,
+    /// What: the individual changes of the final report.
+    pub changes: Vec<String>,
+    pub verification: Option<String>,
+    pub files: FileStats,
+    /// Start of the run, in seconds since the Unix epoch.
+    pub start_time: u64,
+}
+
+impl Changelog {
+    /// The changelog of `execution`, whose changes to the project are `files`.
+    pub fn from_execution(execution: &AgentExecution, files: FileStats) -> Self {
+        let (summary, changes, verification) = match &execution.final_report {
+            Some(report) => (report.summary.clone(), report.changes.clone(), report.verification.clone()),
+            None => (
+                task_done_summary(execution).unwrap_or_else(|| execution.task.trim().to_string()),
+                Vec::new(),
+                None,
+            ),
+        };
+        Self {
+            summary,
+            changes,
+            verification,
+            files,
+            start_time: execution.start_time,
+        }
+    }
+
+    /// The changelog as a Markdown section headed "What changed and why", for a commit message
+    /// or pull request body.
+    pub fn to_markdown(&self) -> String {
+        format!("## What changed and why\n\n{}", self.body())
+    }
+
+    /// The changelog as an entry of a changelog file, headed by the date of the run.
+    pub fn file_entry(&self) -> String {
+        let date: String = format_utc(self.start_time).chars().take(10).collect();
+        format!("## {}\n\n{}", date, self.body())
+    }
+
+    fn body(&self) -> String {
+        let mut out = self.summary.trim().to_string();
+        if !self.changes.is_empty() {
+            let changes: Vec<String> = self.changes.iter().map(|change| format!("- {}", change)).collect();
+            out.push_str(&format!("\n\n{}", changes.join("\n")));
+        }
+        if let Some(verification) = &self.verification {
+            out.push_str(&format!("\n\nVerified: {}", verification.trim()));
+        }
+        if !self.files.is_empty() {
+            let total = DiffStat::total(&self.files);
+            out.push_str(&format!(
+                "\n\nFiles changed ({}, +{} -{}):",
+                total.files_changed, total.insertions, total.deletions
+            ));
+            for (path, stat) in self.files.iter().take(MAX_LISTED_FILES) {
+                let new = if stat.untracked { ", new" } else { "" };
+                out.push_str(&format!("\n- `{}` (+{} -{}{})", path, stat.insertions, stat.deletions, new));
+            }
+            if self.files.len() > MAX_LISTED_FILES {
+                out.push_str(&format!("\n- and {} more", self.files.len() - MAX_LISTED_FILES));
+            }
+        }
+        out
+    }
+}
+
+/// Where the pull request body of a run is written: `run.json` -> `run.pr.md`.
+pub fn pr_body_path(trajectory_path: &Path) -> PathBuf {
+    trajectory_path.with_extension("pr.md")
+}
+
+/// Adds `entry` to the changelog file at `path`: below its `# ` title if it has one, otherwise
+/// at the top. A missing file is created with a "Changelog" title.
+pub fn add_entry(path: &Path, entry: &str) -> Result<()> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => "# Changelog\n".to_string(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let entry = format!("{}\n", entry.trim_end());
+    let content = match existing.split_once('\n') {
+        Some((title, rest)) if title.starts_with("# ") => {
+            format!("{}\n\n{}\n{}", title, entry, rest.trim_start_matches('\n'))
+        }
+        None if existing.starts_with("# ") => format!("{}\n\n{}", existing, entry),
+        _ => format!("{}\n{}", entry, existing),
+    };
+    std::fs::write(path, content.trim_end().to_string() + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::git_utils::FileStat;
+
+    #[test]
+    fn test_changelog_markdown_and_file_entry() {
+        let changelog = Changelog {
+            summary: "Handle empty input in the parser".to_string(),
+            changes: vec!["Return an empty AST for empty input".to_string()],
+            verification: Some("cargo test".to_string()),
+            files: FileStats::from([
+                ("src/parser.rs".to_string(), FileStat { insertions: 3, deletions: 1, untracked: false }),
+                ("tests/empty.rs".to_string(), FileStat { insertions: 9, deletions: 0, untracked: true }),
+            ]),
+            start_time: 1_760_572_800,
+        };
+        assert_eq!(
+            changelog.to_markdown(),
+            "## What changed and why\n\nHandle empty input in the parser\n\n\
+            - Return an empty AST for empty input\n\nVerified: cargo test\n\n\
+            Files changed (2, +12 -1):\n- `src/parser.rs` (+3 -1)\n- `tests/empty.rs` (+9 -0, new)"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("CHANGELOG.md");
+        std::fs::write(&path, "# Changelog\n\n## 2025-01-01\n\nOlder entry\n").unwrap();
+        add_entry(&path, &changelog.file_entry()).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# Changelog\n\n## 2025-10-16\n\nHandle empty input"), "{}", content);
+        assert!(content.ends_with("\n\n## 2025-01-01\n\nOlder entry\n"), "{}", content);
+
+        let new_path = dir.path().join("NEW.md");
+        add_entry(&new_path, "## 2025-10-16\n\nFirst").unwrap();
+        assert_eq!(std::fs::read_to_string(&new_path).unwrap(), "# Changelog\n\n## 2025-10-16\n\nFirst\n");
+    }
+}
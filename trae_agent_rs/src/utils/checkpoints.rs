@@ -0,0 +1,273 @@
+//! # Named Checkpoints
+//!
+//! Lets the agent save the state of the project's working tree under a name before a risky
+//! change and roll back to it when verification regresses, instead of undoing edits by hand.
+//!
+//! A checkpoint is a commit of the whole working tree (tracked and untracked files, honouring
+//! `.gitignore`) stored under `refs/trae/checkpoints/<name>`. It is built with a temporary index,
+//! so neither the user's index, branch nor `HEAD` is touched, and it does not show up in
+//! `git log` of any branch.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Namespace of the checkpoint refs.
+const REF_PREFIX: &str = "refs/trae/checkpoints/";
+
+/// A saved checkpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub name: String,
+    pub commit: String,
+    /// Number of files in the saved tree.
+    pub files: usize,
+}
+
+/// What restoring a checkpoint changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    /// Files that differed from the checkpoint and were written back.
+    pub restored: Vec<String>,
+    /// Files created after the checkpoint, removed.
+    pub removed: Vec<String>,
+}
+
+/// A restore worked out by `CheckpointStore::plan_restore`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestorePlan {
+    /// The checkpoint's commit.
+    pub commit: String,
+    pub summary: RestoreSummary,
+}
+
+/// Creates and restores checkpoints of one git repository.
+pub struct CheckpointStore {
+    project_root: PathBuf,
+}
+
+impl CheckpointStore {
+    /// Checkpoints for the repository at `project_root`, which must be a git work tree.
+    pub fn new(project_root: &Path) -> Result<Self> {
+        let store = Self {
+            project_root: project_root.to_path_buf(),
+        };
+        let inside = store.git(&["rev-parse", "--is-inside-work-tree"], None)?;
+        if inside.trim() != "true" {
+            anyhow::bail!("{} is not a git work tree", project_root.display());
+        }
+        Ok(store)
+    }
+
+    /// The root of the repository's work tree; checkpoint paths are relative to it.
+    pub fn project_root(&self) -> &Path {
+        &self.project_root
+    }
+
+    /// Saves the working tree as checkpoint `name`, replacing a checkpoint of the same name.
+    pub fn create(&self, name: &str) -> Result<Checkpoint> {
+        validate_name(name)?;
+        let index = self.temp_index()?;
+        let tree = self.snapshot_tree(&index.0)?;
+        let mut args = vec!["commit-tree", tree.as_str(), "-m"];
+        let message = format!("trae checkpoint: {}", name);
+        args.push(&message);
+        let head = self.git(&["rev-parse", "--verify", "-q", "HEAD"], None).ok();
+        if let Some(head) = head.as_deref().map(str::trim) {
+            args.extend(["-p", head]);
+        }
+        let commit = self.git(&args, None)?.trim().to_string();
+        self.git(&["update-ref", &format!("{}{}", REF_PREFIX, name), &commit], None)?;
+        let files = self.git(&["ls-tree", "-r", "--name-only", &commit], None)?.lines().count();
+        Ok(Checkpoint {
+            name: name.to_string(),
+            commit,
+            files,
+        })
+    }
+
+    /// Names of the saved checkpoints, sorted.
+    pub fn list(&self) -> Result<Vec<String>> {
+        let refs = self.git(&["for-each-ref", "--format=%(refname)", REF_PREFIX], None)?;
+        let mut names: Vec<String> = refs
+            .lines()
+            .filter_map(|r| r.strip_prefix(REF_PREFIX))
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Works out how to restore the working tree to checkpoint `name`: which changed and
+    /// deleted files to write back and which files created since to remove. Ignored files are
+    /// left alone. Nothing is touched, so the files can be checked before `apply_restore`.
+    pub fn plan_restore(&self, name: &str) -> Result<RestorePlan> {
+        validate_name(name)?;
+        let reference = format!("{}{}", REF_PREFIX, name);
+        let commit = match self.git(&["rev-parse", "--verify", "-q", &reference], None) {
+            Ok(commit) => commit.trim().to_string(),
+            Err(_) => {
+                let known = self.list()?;
+                anyhow::bail!(
+                    "No checkpoint named '{}'. Saved checkpoints: {}",
+                    name,
+                    if known.is_empty() { "none".to_string() } else { known.join(", ") }
+                );
+            }
+        };
+
+        let index = self.temp_index()?;
+        let current = self.snapshot_tree(&index.0)?;
+        let changes = self.git(
+            &["diff-tree", "-r", "-z", "--name-status", "--no-renames", &commit, &current],
+            None,
+        )?;
+        let mut summary = RestoreSummary::default();
+        let mut fields = changes.split('\0').filter(|field| !field.is_empty());
+        while let (Some(status), Some(path)) = (fields.next(), fields.next()) {
+            if status == "A" {
+                summary.removed.push(path.to_string());
+            } else {
+                summary.restored.push(path.to_string());
+            }
+        }
+        Ok(RestorePlan { commit, summary })
+    }
+
+    /// Restores the files of `plan`, and only those.
+    pub fn apply_restore(&self, plan: &RestorePlan) -> Result<()> {
+        for path in &plan.summary.removed {
+            let file = self.project_root.join(path);
+            std::fs::remove_file(&file).with_context(|| format!("Failed to remove {}", file.display()))?;
+        }
+        if plan.summary.restored.is_empty() {
+            return Ok(());
+        }
+        let index = self.temp_index()?;
+        self.git(&["read-tree", &plan.commit], Some(&index.0))?;
+        let mut args = vec!["checkout-index", "-f", "--"];
+        args.extend(plan.summary.restored.iter().map(String::as_str));
+        self.git(&args, Some(&index.0))?;
+        Ok(())
+    }
+
+    /// A fresh index file in the repository's git directory, removed when dropped. The name
+    /// is random, so agents sharing the repository from other containers do not collide.
+    fn temp_index(&self) -> Result<TempIndex> {
+        let name = format!("trae-checkpoint-index-{}-{:016x}", std::process::id(), random_suffix());
+        let path = self.git(&["rev-parse", "--git-path", &name], None)?;
+        let path = self.project_root.join(path.trim());
+        let _ = std::fs::remove_file(&path);
+        Ok(TempIndex(path))
+    }
+
+    /// Writes the working tree into the temporary index at `index` and returns its tree id.
+    fn snapshot_tree(&self, index: &Path) -> Result<String> {
+        if self.git(&["rev-parse", "--verify", "-q", "HEAD"], None).is_ok() {
+            self.git(&["read-tree", "HEAD"], Some(index))?;
+        }
+        self.git(&["add", "-A"], Some(index))?;
+        Ok(self.git(&["write-tree"], Some(index))?.trim().to_string())
+    }
+
+    fn git(&self, args: &[&str], index: Option<&Path>) -> Result<String> {
+        let mut command = Command::new("git");
+        command.current_dir(&self.project_root).args(args);
+        if let Some(index) = index {
+            command.env("GIT_INDEX_FILE", index);
+        }
+        let output = command
+            .output()
+            .with_context(|| format!("Failed to execute git {} in {}", args.join(" "), self.project_root.display()))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed with status {}: {}",
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// An index file used for one operation, so the user's index is never touched.
+struct TempIndex(PathBuf);
+
+impl Drop for TempIndex {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// A number that differs between calls and processes.
+fn random_suffix() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos()));
+    hasher.finish()
+}
+
+/// Checkpoint names are single ref components: letters, digits, `-`, `_` and `.`.
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.ends_with(".lock")
+        && !name.contains("..")
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        anyhow::bail!(
+            "Invalid checkpoint name '{}': use letters, digits, '-', '_' and '.' only",
+            name
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git").current_dir(dir).args(args).status().unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_checkpoint_and_restore_working_tree() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path();
+        git(root, &["init", "-q"]);
+        git(root, &["config", "user.name", "Test User"]);
+        git(root, &["config", "user.email", "test@example.com"]);
+        std::fs::write(root.join("lib.rs"), "fn a() {}\n").unwrap();
+        git(root, &["add", "-A"]);
+        git(root, &["commit", "-q", "-m", "init"]);
+
+        let store = CheckpointStore::new(root).unwrap();
+        std::fs::write(root.join("lib.rs"), "fn a() { b() }\n").unwrap();
+        std::fs::write(root.join("b.rs"), "fn b() {}\n").unwrap();
+        let checkpoint = store.create("before-refactor").unwrap();
+        assert_eq!(checkpoint.files, 2);
+        assert_eq!(store.list().unwrap(), vec!["before-refactor"]);
+
+        std::fs::write(root.join("lib.rs"), "broken").unwrap();
+        std::fs::remove_file(root.join("b.rs")).unwrap();
+        std::fs::write(root.join("c.rs"), "fn c() {}\n").unwrap();
+        let plan = store.plan_restore("before-refactor").unwrap();
+        assert_eq!(plan.summary.removed, vec!["c.rs"]);
+        assert_eq!(plan.summary.restored, vec!["b.rs", "lib.rs"]);
+        assert!(root.join("c.rs").exists());
+        store.apply_restore(&plan).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("lib.rs")).unwrap(), "fn a() { b() }\n");
+        assert!(root.join("b.rs").exists());
+        assert!(!root.join("c.rs").exists());
+
+        // The user's index and branch are untouched.
+        let status = Command::new("git").current_dir(root).args(["status", "--porcelain"]).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&status.stdout), " M lib.rs\n?? b.rs\n");
+
+        assert!(store.plan_restore("missing").unwrap_err().to_string().contains("before-refactor"));
+        assert!(store.create("../escape").is_err());
+    }
+}
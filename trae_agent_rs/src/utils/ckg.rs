@@ -0,0 +1,345 @@
+//! # Code Knowledge Graph
+//!
+//! An index of the definitions and call sites in a project's source files, built with the
+//! outline grammars, so the agent can ask where a symbol is defined, what a file defines or
+//! who calls a function without reading files blindly. The index is cached per project under
+//! `~/.trae/ckg` and brought up to date on each use: only files whose modification time or
+//! size changed are parsed again. Calls are matched by the called name alone, so callers of
+//! `Point::new` include every call of a `new`.
+
+use super::outline::{outline, OutlineLanguage};
+use anyhow::{Context, Result};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tree_sitter::{Node, Parser};
+
+/// Overrides the directory holding the cached indexes.
+pub const CKG_DIR_ENV: &str = "TRAE_CKG_DIR";
+/// Bumped when the index format changes; older caches are rebuilt.
+const INDEX_VERSION: u32 = 1;
+/// Larger files (generated code, bundles) are not indexed.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// A definition in the index.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphSymbol {
+    pub kind: String,
+    pub name: Option<String>,
+    pub signature: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub depth: usize,
+    /// The enclosing type, class or impl, e.g. `Point` for `impl Point { fn new() }`.
+    pub parent: Option<String>,
+}
+
+impl GraphSymbol {
+    /// The name qualified by the enclosing definition, e.g. `Point::new`.
+    pub fn qualified_name(&self) -> Option<String> {
+        let name = self.name.as_deref()?;
+        Some(match &self.parent {
+            Some(parent) => format!("{}::{}", parent, name),
+            None => name.to_string(),
+        })
+    }
+}
+
+/// A call of a function or method.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallSite {
+    /// The called name, without receiver or path: `helper` for `self.helper()`.
+    pub callee: String,
+    pub line: usize,
+    /// The qualified name of the innermost definition containing the call, if any.
+    pub caller: Option<String>,
+}
+
+/// The index of one file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedFile {
+    /// Modification time in nanoseconds since the Unix epoch, when indexed.
+    pub modified: u64,
+    pub size: u64,
+    pub symbols: Vec<GraphSymbol>,
+    pub calls: Vec<CallSite>,
+}
+
+/// What a refresh did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RefreshStats {
+    pub indexed: usize,
+    pub reused: usize,
+    pub removed: usize,
+}
+
+/// The index of a project, keyed by path relative to the project root.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeGraph {
+    version: u32,
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+impl Default for CodeGraph {
+    fn default() -> Self {
+        Self {
+            version: INDEX_VERSION,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+impl CodeGraph {
+    /// Loads the index cached at `path`; an empty index if there is none or it is unreadable
+    /// or outdated.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CodeGraph>(&content).ok())
+            .filter(|graph| graph.version == INDEX_VERSION)
+            .unwrap_or_default()
+    }
+
+    /// Writes the index to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let content = serde_json::to_string(self).context("Failed to serialize the code index")?;
+        std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Brings the index up to date with the source files under `root`, honouring
+    /// `.gitignore`: new and changed files are parsed, deleted ones dropped.
+    pub fn refresh(&mut self, root: &Path) -> RefreshStats {
+        let mut stats = RefreshStats::default();
+        let mut files = BTreeMap::new();
+        let walker = WalkBuilder::new(root).require_git(false).build();
+        for entry in walker.filter_map(std::result::Result::ok) {
+            let Some(language) = OutlineLanguage::from_path(entry.path()) else {
+                continue;
+            };
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() || metadata.len() > MAX_FILE_BYTES {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path()).display().to_string();
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos() as u64);
+            if let Some(file) = self.files.remove(&relative) {
+                if file.modified == modified && file.size == metadata.len() {
+                    files.insert(relative, file);
+                    stats.reused += 1;
+                    continue;
+                }
+            }
+            let Ok(source) = std::fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            let Some((symbols, calls)) = index_source(language, &source) else {
+                continue;
+            };
+            files.insert(
+                relative,
+                IndexedFile {
+                    modified,
+                    size: metadata.len(),
+                    symbols,
+                    calls,
+                },
+            );
+            stats.indexed += 1;
+        }
+        stats.removed = self.files.len();
+        self.files = files;
+        stats
+    }
+
+    /// The definitions of `name`, with their files. A qualified name (`Point::new`,
+    /// `Cache.get`) only matches definitions inside that type, class or impl.
+    pub fn definitions(&self, name: &str) -> Vec<(&str, &GraphSymbol)> {
+        let segments: Vec<&str> = name.split("::").flat_map(|s| s.split('.')).collect();
+        let Some((last, qualifiers)) = segments.split_last() else {
+            return Vec::new();
+        };
+        self.symbols()
+            .filter(|(_, symbol)| symbol.name.as_deref() == Some(*last))
+            .filter(|(_, symbol)| qualifiers.last().is_none_or(|q| symbol.parent.as_deref() == Some(*q)))
+            .collect()
+    }
+
+    /// The definitions in the file at `path`, relative to the project root.
+    pub fn symbols_in(&self, path: &str) -> Option<&[GraphSymbol]> {
+        let path = path.trim_start_matches("./");
+        self.files.get(path).map(|file| file.symbols.as_slice())
+    }
+
+    /// The calls of `name`, matched by its last segment, with their files.
+    pub fn callers(&self, name: &str) -> Vec<(&str, &CallSite)> {
+        let callee = name.rsplit(['.', ':']).next().unwrap_or(name);
+        self.files
+            .iter()
+            .flat_map(|(path, file)| file.calls.iter().map(move |call| (path.as_str(), call)))
+            .filter(|(_, call)| call.callee == callee)
+            .collect()
+    }
+
+    fn symbols(&self) -> impl Iterator<Item = (&str, &GraphSymbol)> {
+        self.files
+            .iter()
+            .flat_map(|(path, file)| file.symbols.iter().map(move |symbol| (path.as_str(), symbol)))
+    }
+}
+
+/// Where the index of the project at `root` is cached: `TRAE_CKG_DIR`, else `~/.trae/ckg`,
+/// in a file named after the project directory and a hash of its path.
+pub fn default_cache_path(root: &Path) -> Option<PathBuf> {
+    let dir = match std::env::var_os(CKG_DIR_ENV).filter(|p| !p.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".trae").join("ckg"),
+    };
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let digest = Sha256::digest(root.to_string_lossy().as_bytes());
+    let hash: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    let name = root.file_name().map_or("project".into(), |n| n.to_string_lossy());
+    Some(dir.join(format!("{}-{}.json", name, hash)))
+}
+
+/// The definitions and calls in `source`; `None` if it cannot be parsed.
+fn index_source(language: OutlineLanguage, source: &str) -> Option<(Vec<GraphSymbol>, Vec<CallSite>)> {
+    let symbols: Vec<GraphSymbol> = {
+        let outline = outline(language, source).ok()?;
+        outline
+            .iter()
+            .enumerate()
+            .map(|(i, symbol)| {
+                let parent = outline[..i]
+                    .iter()
+                    .rev()
+                    .find(|s| s.depth + 1 == symbol.depth && s.end_line >= symbol.end_line)
+                    .and_then(|s| s.name.clone().or_else(|| impl_target(&s.signature)));
+                GraphSymbol {
+                    kind: symbol.kind.clone(),
+                    name: symbol.name.clone(),
+                    signature: symbol.signature.clone(),
+                    start_line: symbol.start_line,
+                    end_line: symbol.end_line,
+                    depth: symbol.depth,
+                    parent,
+                }
+            })
+            .collect()
+    };
+
+    let mut parser = Parser::new();
+    parser.set_language(&language.grammar()).ok()?;
+    let tree = parser.parse(source, None)?;
+    let mut calls = Vec::new();
+    collect_calls(tree.root_node(), source, &mut calls);
+    for call in &mut calls {
+        call.caller = symbols
+            .iter()
+            .filter(|s| s.start_line <= call.line && call.line <= s.end_line)
+            .max_by_key(|s| s.depth)
+            .and_then(GraphSymbol::qualified_name);
+    }
+    Some((symbols, calls))
+}
+
+/// The type an impl block is for: `Point` for `impl<T> Display for Point<T>`.
+fn impl_target(signature: &str) -> Option<String> {
+    if !signature.starts_with("impl") {
+        return None;
+    }
+    let target = signature.split_whitespace().last()?;
+    let target = target.split('<').next()?.rsplit("::").next()?;
+    (!target.is_empty() && target != "impl").then(|| target.to_string())
+}
+
+fn collect_calls(node: Node, source: &str, out: &mut Vec<CallSite>) {
+    let function = match node.kind() {
+        // Rust, JavaScript, TypeScript, Go; Python.
+        "call_expression" | "call" => node.child_by_field_name("function"),
+        "new_expression" => node.child_by_field_name("constructor"),
+        _ => None,
+    };
+    if let Some(callee) = function.and_then(|f| callee_name(f, source)) {
+        out.push(CallSite {
+            callee,
+            line: node.start_position().row + 1,
+            caller: None,
+        });
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_calls(child, source, out);
+    }
+}
+
+/// The called name of a call's function node: the last identifier of a path, field or
+/// attribute expression, looking through generic arguments.
+fn callee_name(node: Node, source: &str) -> Option<String> {
+    for field in ["field", "property", "attribute", "name", "function"] {
+        if let Some(child) = node.child_by_field_name(field) {
+            return callee_name(child, source);
+        }
+    }
+    let text = node.utf8_text(source.as_bytes()).ok()?;
+    let identifier = !text.is_empty() && text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    identifier.then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_answers_definition_file_and_caller_queries() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/point.rs"),
+            "pub struct Point { x: i32 }\n\nimpl Point {\n    pub fn new(x: i32) -> Self {\n        Self { x: helper(x) }\n    }\n}\n\nfn helper(x: i32) -> i32 { x }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("app.py"),
+            "class Cache:\n    def get(self, key):\n        return self.load(key)\n\ndef main():\n    Cache().get(1)\n    helper(2)\n",
+        )
+        .unwrap();
+
+        let mut graph = CodeGraph::default();
+        assert_eq!(graph.refresh(dir.path()), RefreshStats { indexed: 2, reused: 0, removed: 0 });
+
+        let new = graph.definitions("Point::new");
+        assert_eq!(new.len(), 1);
+        assert_eq!((new[0].0, new[0].1.start_line), ("src/point.rs", 4));
+        assert_eq!(graph.definitions("Cache.get")[0].0, "app.py");
+        assert!(graph.definitions("Other::new").is_empty());
+
+        let names: Vec<_> = graph.symbols_in("src/point.rs").unwrap().iter().filter_map(|s| s.name.as_deref()).collect();
+        assert_eq!(names, ["Point", "new", "helper"]);
+
+        let callers: Vec<_> = graph.callers("helper").into_iter().map(|(path, call)| (path, call.line, call.caller.clone())).collect();
+        assert_eq!(
+            callers,
+            [("app.py", 7, Some("main".to_string())), ("src/point.rs", 5, Some("Point::new".to_string()))]
+        );
+        assert_eq!(graph.callers("load")[0].1.caller.as_deref(), Some("Cache::get"));
+
+        let cache = dir.path().join("cache/index.json");
+        graph.save(&cache).unwrap();
+        let mut cached = CodeGraph::load(&cache);
+        assert_eq!(cached, graph);
+        std::fs::remove_file(dir.path().join("app.py")).unwrap();
+        assert_eq!(cached.refresh(dir.path()), RefreshStats { indexed: 0, reused: 1, removed: 1 });
+    }
+}
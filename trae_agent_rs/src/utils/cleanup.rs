@@ -0,0 +1,193 @@
+//! # Run Cleanup
+//!
+//! At the end of a run, whether it succeeded or not, runs the configured `teardown_commands`,
+//! terminates background processes left behind by the agent's shell commands and removes the
+//! temporary directories the run created. What was cleaned is returned as a `CleanupReport`,
+//! which is recorded in the trajectory.
+
+use crate::tools::execution::ExecutionEnvironment;
+use crate::utils::setup_hooks::run_hook_commands;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Maximum length of the output kept for a failed teardown command, in characters.
+const MAX_TEARDOWN_OUTPUT_CHARS: usize = 2_000;
+
+/// One teardown command and how it went.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TeardownRecord {
+    pub command: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// End of the output, for failed commands.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// What was cleaned up at the end of a run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CleanupReport {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teardown: Vec<TeardownRecord>,
+    /// Process groups of the agent's shell commands that still had running processes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub terminated_process_groups: Vec<u32>,
+    /// Temporary files and directories that were removed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_paths: Vec<PathBuf>,
+}
+
+impl CleanupReport {
+    pub fn is_empty(&self) -> bool {
+        self.teardown.is_empty() && self.terminated_process_groups.is_empty() && self.removed_paths.is_empty()
+    }
+}
+
+/// Collects what a run leaves behind so it can be cleaned up when the run ends.
+#[derive(Debug, Default)]
+pub struct RunCleanup {
+    paths: Mutex<Vec<PathBuf>>,
+    process_groups: Mutex<Vec<u32>>,
+}
+
+impl RunCleanup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a temporary file or directory created for the run.
+    pub fn track_path(&self, path: impl Into<PathBuf>) {
+        self.paths.lock().unwrap().push(path.into());
+    }
+
+    /// Registers the process group of a shell command started by the agent.
+    pub fn track_process_group(&self, pgid: u32) {
+        let mut groups = self.process_groups.lock().unwrap();
+        if !groups.contains(&pgid) {
+            groups.push(pgid);
+        }
+    }
+
+    /// Runs `teardown_commands` in `working_dir` of `execution`, then terminates leftover
+    /// processes and removes tracked paths. Failures are logged and never abort the cleanup.
+    pub async fn run(
+        &self,
+        teardown_commands: &[String],
+        working_dir: &Path,
+        timeout: Duration,
+        execution: &dyn ExecutionEnvironment,
+    ) -> CleanupReport {
+        let mut report = CleanupReport::default();
+        for outcome in run_hook_commands(teardown_commands, working_dir, timeout, execution).await {
+            if !outcome.success {
+                warn!("Teardown command '{}' failed: {}", outcome.command, outcome.output.trim());
+            }
+            let output = (!outcome.success).then(|| tail(&outcome.output));
+            report.teardown.push(TeardownRecord {
+                command: outcome.command,
+                success: outcome.success,
+                exit_code: outcome.exit_code,
+                output,
+            });
+        }
+
+        let groups: Vec<u32> = std::mem::take(&mut *self.process_groups.lock().unwrap());
+        for pgid in groups {
+            if signal_process_group(pgid, "0").await {
+                debug!(pgid, "Terminating leftover processes of an agent command");
+                signal_process_group(pgid, "TERM").await;
+                report.terminated_process_groups.push(pgid);
+            }
+        }
+
+        let paths: Vec<PathBuf> = std::mem::take(&mut *self.paths.lock().unwrap());
+        for path in paths {
+            let removed = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else if path.exists() {
+                std::fs::remove_file(&path)
+            } else {
+                continue;
+            };
+            match removed {
+                Ok(()) => report.removed_paths.push(path),
+                Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+            }
+        }
+        report
+    }
+}
+
+/// Sends `signal` to every process in the group `pgid` with `kill`; signal "0" only checks
+/// that the group still has processes. Returns whether `kill` succeeded.
+async fn signal_process_group(pgid: u32, signal: &str) -> bool {
+    tokio::process::Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg("--")
+        .arg(format!("-{}", pgid))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
+
+fn tail(output: &str) -> String {
+    let output = output.trim();
+    let count = output.chars().count();
+    if count > MAX_TEARDOWN_OUTPUT_CHARS {
+        let tail: String = output.chars().skip(count - MAX_TEARDOWN_OUTPUT_CHARS).collect();
+        format!("...{}", tail)
+    } else {
+        output.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::execution::HostEnvironment;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_cleanup_runs_teardown_kills_leftovers_and_removes_paths() {
+        let project = tempdir().unwrap();
+        let scratch = tempdir().unwrap().keep();
+        std::fs::write(scratch.join("out.log"), "log").unwrap();
+
+        let mut sleeper = tokio::process::Command::new("sleep");
+        sleeper.arg("30").process_group(0);
+        let mut child = sleeper.spawn().unwrap();
+        let pgid = child.id().unwrap();
+
+        let cleanup = RunCleanup::new();
+        cleanup.track_path(&scratch);
+        cleanup.track_path(project.path().join("never-created"));
+        cleanup.track_process_group(pgid);
+        let teardown = vec!["touch stopped".to_string(), "echo cannot stop; exit 1".to_string()];
+        let report = cleanup.run(&teardown, project.path(), Duration::from_secs(5), &HostEnvironment).await;
+
+        assert!(project.path().join("stopped").exists());
+        assert_eq!(
+            report.teardown[1],
+            TeardownRecord {
+                command: "echo cannot stop; exit 1".to_string(),
+                success: false,
+                exit_code: Some(1),
+                output: Some("cannot stop".to_string()),
+            }
+        );
+        assert_eq!(report.terminated_process_groups, vec![pgid]);
+        assert!(!child.wait().await.unwrap().success());
+        assert_eq!(report.removed_paths, vec![scratch.clone()]);
+        assert!(!scratch.exists());
+
+        // Everything was handed over; a second run has nothing left to do.
+        assert!(cleanup.run(&[], project.path(), Duration::from_secs(5), &HostEnvironment).await.is_empty());
+    }
+}
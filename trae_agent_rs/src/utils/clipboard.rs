@@ -0,0 +1,42 @@
+//! # System Clipboard
+//!
+//! Puts results on the system clipboard for `trae run --copy-patch` and the interactive
+//! `/copy` command, so a patch or answer can be pasted straight into a code review tool.
+
+use thiserror::Error;
+
+/// Errors of copying to the clipboard.
+#[derive(Error, Debug)]
+pub enum ClipboardError {
+    #[error("Nothing to copy")]
+    Empty,
+    /// No clipboard is reachable, e.g. in a session without a display server.
+    #[error("The system clipboard is unavailable: {0}")]
+    Unavailable(String),
+}
+
+/// Copies `text` to the system clipboard.
+///
+/// # Returns
+/// The number of lines copied.
+pub fn copy_text(text: &str) -> Result<usize, ClipboardError> {
+    if text.trim().is_empty() {
+        return Err(ClipboardError::Empty);
+    }
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| ClipboardError::Unavailable(e.to_string()))?;
+    clipboard
+        .set_text(text)
+        .map_err(|e| ClipboardError::Unavailable(e.to_string()))?;
+    Ok(text.lines().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blank_text_is_not_copied() {
+        assert!(matches!(copy_text(""), Err(ClipboardError::Empty)));
+        assert!(matches!(copy_text(" \n\t"), Err(ClipboardError::Empty)));
+    }
+}
@@ -0,0 +1,136 @@
+//! # Run Cost Accounting
+//!
+//! What a run's LLM calls cost: the tokens and estimated cost of every step and of every
+//! model, priced with the config file's `pricing` before the built-in list prices (see
+//! `usage`). Printed at the end of `trae run` and written into the trajectory.
+
+use crate::agent::base_agent::AgentExecution;
+use crate::utils::usage::{format_usage, ModelUsage, Pricing, UsageTracker};
+use serde::{Deserialize, Serialize};
+
+/// The tokens and cost of one step's LLM call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepCost {
+    pub step: u32,
+    pub model: String,
+    #[serde(flatten)]
+    pub usage: ModelUsage,
+}
+
+/// The tokens and cost of a run, by step and by model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostBreakdown {
+    pub steps: Vec<StepCost>,
+    pub models: Vec<(String, ModelUsage)>,
+    pub total: ModelUsage,
+}
+
+impl CostBreakdown {
+    /// The breakdown by model, ending with the total, e.g. for the run summary.
+    pub fn format(&self) -> String {
+        let mut lines: Vec<String> = self
+            .models
+            .iter()
+            .map(|(model, usage)| format!("  {}: {} request(s), {}", model, usage.requests, format_usage(usage)))
+            .collect();
+        if let Some(step) = self
+            .steps
+            .iter()
+            .filter(|step| step.usage.cost.is_some())
+            .max_by(|a, b| a.usage.cost.partial_cmp(&b.usage.cost).unwrap_or(std::cmp::Ordering::Equal))
+        {
+            lines.push(format!("  Most expensive step: {} ({})", step.step, format_usage(&step.usage)));
+        }
+        lines.push(format!("  Total: {}", format_usage(&self.total)));
+        lines.join("\n")
+    }
+}
+
+/// Accumulates the usage of a run's steps.
+#[derive(Debug, Clone, Default)]
+pub struct CostTracker {
+    usage: UsageTracker,
+    steps: Vec<StepCost>,
+}
+
+impl CostTracker {
+    pub fn new(pricing: Pricing) -> Self {
+        Self {
+            usage: UsageTracker::with_pricing(pricing),
+            steps: Vec::new(),
+        }
+    }
+
+    /// The costs of the steps of `execution`. Steps whose response names no model are
+    /// counted for `default_model`.
+    pub fn from_execution(execution: &AgentExecution, pricing: Pricing, default_model: &str) -> Self {
+        let mut tracker = Self::new(pricing);
+        for step in &execution.steps {
+            let Some(response) = &step.llm_response else { continue };
+            let Some(usage) = &response.usage else { continue };
+            let model = if response.model.is_empty() { default_model } else { &response.model };
+            tracker.record_step(step.step_number, model, usage);
+        }
+        tracker
+    }
+
+    /// Records the usage reported for the LLM call of step `step`.
+    pub fn record_step(&mut self, step: u32, model: &str, usage: &crate::llm::base_client::LLMUsage) {
+        let usage = self.usage.record(model, usage);
+        self.steps.push(StepCost {
+            step,
+            model: model.to_string(),
+            usage,
+        });
+    }
+
+    pub fn breakdown(&self) -> CostBreakdown {
+        CostBreakdown {
+            steps: self.steps.clone(),
+            models: self.usage.by_model().iter().map(|(model, usage)| (model.clone(), usage.clone())).collect(),
+            total: self.usage.total(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::LLMUsage;
+    use crate::utils::usage::ModelPrice;
+
+    fn usage(prompt: u32, completion: u32) -> LLMUsage {
+        LLMUsage {
+            prompt_tokens: prompt,
+            completion_tokens: Some(completion),
+            total_tokens: prompt + completion,
+        }
+    }
+
+    #[test]
+    fn test_costs_per_step_and_model_with_configured_prices() {
+        let pricing = Pricing::from([
+            ("my-local".to_string(), ModelPrice { input_per_mtok: 1.0, output_per_mtok: 2.0 }),
+            // Overrides the built-in price.
+            ("gpt-4o".to_string(), ModelPrice { input_per_mtok: 5.0, output_per_mtok: 5.0 }),
+        ]);
+        let mut tracker = CostTracker::new(pricing);
+        tracker.record_step(1, "my-local-model", &usage(1_000_000, 500_000));
+        tracker.record_step(2, "gpt-4o-2024-08-06", &usage(100_000, 100_000));
+        tracker.record_step(3, "unpriced", &usage(10, 5));
+
+        let breakdown = tracker.breakdown();
+        assert_eq!(breakdown.steps[0].usage.cost, Some(2.0));
+        assert_eq!(breakdown.steps[1].usage.cost, Some(1.0));
+        assert_eq!(breakdown.steps[2].usage.cost, None);
+        assert_eq!(breakdown.total.total_tokens(), 1_700_015);
+        assert_eq!(breakdown.total.cost, None, "a model without a price makes the total unknown");
+
+        let report = breakdown.format();
+        assert!(report.contains("  my-local-model: 1 request(s), 1,500,000 tokens (1,000,000 in / 500,000 out), ~$2.0000"), "{}", report);
+        assert!(report.contains("  Most expensive step: 1 ("), "{}", report);
+        let json = serde_json::to_value(&breakdown).unwrap();
+        assert_eq!(json["steps"][1]["model"], "gpt-4o-2024-08-06");
+        assert_eq!(json["steps"][1]["prompt_tokens"], 100_000);
+    }
+}
@@ -0,0 +1,352 @@
+//! # Dependency Upgrade Utilities
+//!
+//! Helpers for the `upgrade` command: bumping a dependency version in a `Cargo.toml`
+//! manifest, running build/test commands, and locating the source files that fail to
+//! compile so the agent can be restricted to fixing only those.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Maximum number of characters of build output included in the fix prompt.
+/// The tail of the output is kept since it usually contains the summary of errors.
+const MAX_BUILD_OUTPUT_IN_PROMPT: usize = 12000;
+
+/// The result of running a build or test command.
+#[derive(Debug, Clone)]
+pub struct CheckOutcome {
+    /// The command that was run.
+    pub command: String,
+    /// Whether the command exited successfully.
+    pub success: bool,
+    /// Combined stdout and stderr of the command.
+    pub output: String,
+}
+
+/// Rewrites the version requirement of `package` in a `Cargo.toml` manifest.
+///
+/// Handles the plain form (`serde = "1.0"`), the inline table form
+/// (`serde = { version = "1.0", features = [...] }`), and dedicated tables
+/// (`[dependencies.serde]`) in any `*dependencies` section. Renamed dependencies
+/// (`package = "serde"`) are not matched.
+///
+/// # Arguments
+/// * `manifest`: The contents of the `Cargo.toml` file.
+/// * `package`: The dependency name to bump.
+/// * `version`: The new version requirement.
+///
+/// # Returns
+/// The updated manifest contents, or an error if the dependency was not found.
+pub fn bump_cargo_dependency(manifest: &str, package: &str, version: &str) -> Result<String> {
+    let mut in_dependency_section = false;
+    let mut in_package_table = false;
+    let mut replacements = 0;
+    let mut output_lines = Vec::new();
+
+    for line in manifest.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            let header = trimmed.trim_start_matches('[').trim_end_matches(']').trim();
+            in_dependency_section = header.ends_with("dependencies");
+            in_package_table = header
+                .rsplit_once('.')
+                .is_some_and(|(section, name)| section.ends_with("dependencies") && name == package);
+            output_lines.push(line.to_string());
+            continue;
+        }
+
+        let updated = if in_package_table {
+            replace_key_value(line, "version", version)
+        } else if in_dependency_section {
+            replace_dependency_line(line, package, version)
+        } else {
+            None
+        };
+
+        match updated {
+            Some(new_line) => {
+                replacements += 1;
+                output_lines.push(new_line);
+            }
+            None => output_lines.push(line.to_string()),
+        }
+    }
+
+    if replacements == 0 {
+        return Err(anyhow::anyhow!(
+            "Dependency '{}' not found in any dependencies section of the manifest",
+            package
+        ));
+    }
+
+    let mut result = output_lines.join("\n");
+    if manifest.ends_with('\n') {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+/// Rewrites a `name = ...` line inside a dependencies section if `name` is `package`.
+fn replace_dependency_line(line: &str, package: &str, version: &str) -> Option<String> {
+    let (key, value) = line.split_once('=')?;
+    if key.trim() != package {
+        return None;
+    }
+    let value = value.trim_start();
+    let indent_and_key = &line[..line.len() - value.len()];
+    if let Some(quoted) = value.strip_prefix('"') {
+        let end = quoted.find('"')?;
+        Some(format!("{}\"{}\"{}", indent_and_key, version, &quoted[end + 1..]))
+    } else if value.starts_with('{') {
+        replace_key_value(line, "version", version)
+    } else {
+        None
+    }
+}
+
+/// Replaces the quoted value of `key = "..."` within a line (including inline tables).
+fn replace_key_value(line: &str, key: &str, new_value: &str) -> Option<String> {
+    let mut search_from = 0;
+    while let Some(pos) = line[search_from..].find(key) {
+        let start = search_from + pos;
+        let preceded_ok = start == 0
+            || !line[..start]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        let rest = line[start + key.len()..].trim_start();
+        if preceded_ok {
+            if let Some(after_eq) = rest.strip_prefix('=') {
+                let after_eq = after_eq.trim_start();
+                if let Some(quoted) = after_eq.strip_prefix('"') {
+                    let value_start = line.len() - quoted.len();
+                    let value_end = value_start + quoted.find('"')?;
+                    return Some(format!(
+                        "{}{}{}",
+                        &line[..value_start],
+                        new_value,
+                        &line[value_end..]
+                    ));
+                }
+            }
+        }
+        search_from = start + key.len();
+    }
+    None
+}
+
+/// Extracts the project source files referenced by compiler errors in build output.
+///
+/// Recognizes rustc's `error...` headers followed by `--> path:line:col` locations.
+/// Warnings are ignored, as are locations outside `project_root` (e.g., the cargo registry).
+///
+/// # Returns
+/// A sorted, de-duplicated list of absolute paths under `project_root`.
+pub fn parse_error_files(output: &str, project_root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut in_error = false;
+
+    for line in output.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("error") {
+            in_error = true;
+            continue;
+        }
+        if trimmed.starts_with("warning") {
+            in_error = false;
+            continue;
+        }
+        if !in_error {
+            continue;
+        }
+        if let Some(location) = trimmed.strip_prefix("--> ") {
+            // Strip ":line:col" from the end of the location.
+            let mut parts = location.rsplitn(3, ':');
+            let (_col, _line, path) = (parts.next(), parts.next(), parts.next());
+            if let Some(path) = path {
+                let path = Path::new(path.trim());
+                let absolute = if path.is_absolute() {
+                    path.to_path_buf()
+                } else {
+                    project_root.join(path)
+                };
+                if absolute.starts_with(project_root) && !files.contains(&absolute) {
+                    files.push(absolute);
+                }
+            }
+            // Only the primary location of each error is relevant.
+            in_error = false;
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Runs a shell command in `working_dir` and captures its combined output.
+///
+/// # Returns
+/// A `CheckOutcome` describing the run, or an error if the command could not be started.
+pub fn run_check(command: &str, working_dir: &Path) -> Result<CheckOutcome> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(working_dir)
+        .output()
+        .with_context(|| format!("Failed to run '{}' in {}", command, working_dir.display()))?;
+
+    let mut combined = String::from_utf8_lossy(&output.stdout).to_string();
+    combined.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(CheckOutcome {
+        command: command.to_string(),
+        success: output.status.success(),
+        output: combined,
+    })
+}
+
+/// Runs `build_cmd` and, once it passes, `test_cmd` if there is one.
+///
+/// # Returns
+/// The first failing run, else the last run; an error if a command could not be started.
+pub fn run_checks(build_cmd: &str, test_cmd: Option<&str>, working_dir: &Path) -> Result<CheckOutcome> {
+    let outcome = run_check(build_cmd, working_dir)?;
+    match test_cmd {
+        Some(test_cmd) if outcome.success => run_check(test_cmd, working_dir),
+        _ => Ok(outcome),
+    }
+}
+
+/// Builds the task description given to the agent for one fix iteration.
+///
+/// # Arguments
+/// * `package`, `version`: The dependency being upgraded.
+/// * `outcome`: The failing build or test run.
+/// * `allowed_files`: The files the agent is permitted to modify in this iteration.
+pub fn build_fix_prompt(
+    package: &str,
+    version: &str,
+    outcome: &CheckOutcome,
+    allowed_files: &[PathBuf],
+) -> String {
+    let output = &outcome.output;
+    let output_tail = if output.len() > MAX_BUILD_OUTPUT_IN_PROMPT {
+        let mut start = output.len() - MAX_BUILD_OUTPUT_IN_PROMPT;
+        while !output.is_char_boundary(start) {
+            start += 1;
+        }
+        format!("[... earlier output truncated ...]\n{}", &output[start..])
+    } else {
+        output.clone()
+    };
+    let allowed_list = allowed_files
+        .iter()
+        .map(|p| format!("- {}", p.display()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "The dependency `{package}` was upgraded to `{version}` and `{command}` now fails.\n\
+        Fix the breakage caused by the upgrade with the smallest possible changes. \
+        Do not downgrade `{package}` and do not change unrelated code.\n\n\
+        You may only modify the following files:\n{allowed_list}\n\n\
+        Output of `{command}`:\n```\n{output_tail}\n```\n\n\
+        When the code compiles again, call `task_done`.",
+        command = outcome.command,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] }
+serde_json = "1.0"
+
+[dev-dependencies]
+tokio = "1"
+
+[build-dependencies.serde]
+version = "1.0.100"
+"#;
+
+    #[test]
+    fn test_bump_inline_table_and_dedicated_table() {
+        let bumped = bump_cargo_dependency(MANIFEST, "serde", "1.0.200").unwrap();
+        assert!(bumped.contains(r#"serde = { version = "1.0.200", features = ["derive"] }"#));
+        assert!(bumped.contains("[build-dependencies.serde]\nversion = \"1.0.200\""));
+        assert!(bumped.contains(r#"serde_json = "1.0""#));
+        // The package version must not be touched.
+        assert!(bumped.contains("version = \"0.1.0\""));
+        assert!(bumped.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_bump_plain_version() {
+        let bumped = bump_cargo_dependency(MANIFEST, "tokio", "1.40").unwrap();
+        assert!(bumped.contains(r#"tokio = "1.40""#));
+    }
+
+    #[test]
+    fn test_bump_missing_dependency() {
+        assert!(bump_cargo_dependency(MANIFEST, "rand", "0.9").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_files_ignores_warnings_and_external_paths() {
+        let output = r#"warning: unused variable: `x`
+ --> src/warn.rs:1:5
+error[E0308]: mismatched types
+  --> src/lib.rs:10:5
+   |
+note: function defined here
+  --> src/other.rs:3:4
+error: cannot find macro `json` in this scope
+ --> /home/u/.cargo/registry/src/serde-1.0.200/src/lib.rs:1:1
+error[E0599]: no method named `foo`
+ --> src/lib.rs:20:9
+"#;
+        let root = Path::new("/work/proj");
+        assert_eq!(
+            parse_error_files(output, root),
+            vec![PathBuf::from("/work/proj/src/lib.rs")]
+        );
+    }
+
+    #[test]
+    fn test_run_checks_runs_tests_after_a_passing_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let outcome = run_checks("true", Some("echo tests broke; exit 1"), dir.path()).unwrap();
+        assert!(!outcome.success);
+        assert_eq!(outcome.command, "echo tests broke; exit 1");
+        assert!(outcome.output.contains("tests broke"));
+
+        // A failing build is reported without running the tests.
+        let outcome = run_checks("exit 2", Some("touch tested"), dir.path()).unwrap();
+        assert_eq!((outcome.success, outcome.command.as_str()), (false, "exit 2"));
+        assert!(!dir.path().join("tested").exists());
+        assert!(run_checks("true", None, dir.path()).unwrap().success);
+    }
+
+    #[test]
+    fn test_build_fix_prompt_lists_allowed_files() {
+        let outcome = CheckOutcome {
+            command: "cargo build".to_string(),
+            success: false,
+            output: "error: boom".to_string(),
+        };
+        let prompt = build_fix_prompt(
+            "serde",
+            "1.0.200",
+            &outcome,
+            &[PathBuf::from("/p/Cargo.toml"), PathBuf::from("/p/src/lib.rs")],
+        );
+        assert!(prompt.contains("- /p/Cargo.toml\n- /p/src/lib.rs"));
+        assert!(prompt.contains("error: boom"));
+        assert!(prompt.contains("`cargo build` now fails"));
+    }
+}
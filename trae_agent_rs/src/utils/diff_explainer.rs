@@ -0,0 +1,327 @@
+//! # Diff Explainer
+//!
+//! Produces a structured, per-hunk explanation of a diff using an LLM, along with
+//! overall risk notes. Used by the `explain-diff` command.
+
+use crate::agent::base_agent::AgentError;
+use crate::llm::base_client::{LLMMessage, MessageRole};
+use crate::llm::LLMClient;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::info;
+
+/// Maximum characters of a single hunk sent to the LLM.
+const MAX_HUNK_CHARS: usize = 4000;
+
+const EXPLAINER_PROMPT: &str = "You are a senior code reviewer. You will be given the hunks of a diff, \
+each labelled with an id. For every hunk, explain in one or two sentences what it changes and why it \
+likely matters, and rate its risk as low, medium or high. Then give an overall summary and a list of \
+concrete risk notes (behaviour changes, missing tests, edge cases, compatibility concerns). \
+Return an entry for every hunk id.";
+
+/// A single hunk of a unified diff.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffHunk {
+    /// Identifier used to match LLM explanations to hunks (e.g., `H1`).
+    pub id: String,
+    /// The file the hunk applies to (the post-image path).
+    pub file: String,
+    /// The `@@ ... @@` header line.
+    pub header: String,
+    /// The hunk body, including context and changed lines.
+    pub body: String,
+}
+
+impl DiffHunk {
+    /// The first and last line the hunk covers in the new file, from its header
+    /// (`@@ -10,3 +12,4 @@` -> 12 to 15). A pure deletion covers the line it follows.
+    pub fn new_lines(&self) -> Option<(usize, usize)> {
+        let range = self.header.split_whitespace().find_map(|part| part.strip_prefix('+'))?;
+        let (start, count) = match range.split_once(',') {
+            Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+            None => (range.parse::<usize>().ok()?, 1),
+        };
+        let start = start.max(1);
+        Some((start, start + count.max(1) - 1))
+    }
+}
+
+/// The LLM's explanation of one hunk.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HunkExplanation {
+    pub id: String,
+    pub explanation: String,
+    pub risk: String,
+}
+
+/// The structured explanation of a whole diff.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiffExplanation {
+    pub summary: String,
+    pub hunks: Vec<HunkExplanation>,
+    #[serde(default)]
+    pub risk_notes: Vec<String>,
+}
+
+/// Splits a unified diff into hunks, tagging each with its file and a sequential id.
+pub fn split_into_hunks(diff: &str) -> Vec<DiffHunk> {
+    let mut hunks: Vec<DiffHunk> = Vec::new();
+    let mut current_file = String::new();
+    // File headers (`index`, `---`, `+++`) only appear between `diff --git` and the first hunk;
+    // inside a hunk the same prefixes are ordinary removed/added lines.
+    let mut in_file_header = false;
+
+    for line in diff.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            current_file = rest
+                .rsplit_once(" b/")
+                .map(|(_, b)| b.to_string())
+                .unwrap_or_else(|| rest.to_string());
+            in_file_header = true;
+        } else if line.starts_with("@@") {
+            in_file_header = false;
+            hunks.push(DiffHunk {
+                id: format!("H{}", hunks.len() + 1),
+                file: current_file.clone(),
+                header: line.to_string(),
+                body: String::new(),
+            });
+        } else if in_file_header {
+            if let Some(path) = line.strip_prefix("+++ ") {
+                if path != "/dev/null" {
+                    current_file = path.strip_prefix("b/").unwrap_or(path).to_string();
+                }
+            }
+        } else if let Some(hunk) = hunks.last_mut() {
+            hunk.body.push_str(line);
+            hunk.body.push('\n');
+        }
+    }
+    hunks
+}
+
+/// The JSON Schema the LLM's answer must follow.
+pub fn explanation_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "summary": { "type": "string" },
+            "hunks": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "explanation": { "type": "string" },
+                        "risk": { "type": "string", "enum": ["low", "medium", "high"] }
+                    },
+                    "required": ["id", "explanation", "risk"],
+                    "additionalProperties": false
+                }
+            },
+            "risk_notes": { "type": "array", "items": { "type": "string" } }
+        },
+        "required": ["summary", "hunks", "risk_notes"],
+        "additionalProperties": false
+    })
+}
+
+fn format_hunks_for_prompt(hunks: &[DiffHunk]) -> String {
+    hunks
+        .iter()
+        .map(|hunk| {
+            let body = if hunk.body.len() > MAX_HUNK_CHARS {
+                let mut end = MAX_HUNK_CHARS;
+                while !hunk.body.is_char_boundary(end) {
+                    end -= 1;
+                }
+                format!("{}\n[... hunk truncated ...]\n", &hunk.body[..end])
+            } else {
+                hunk.body.clone()
+            };
+            format!(
+                "<hunk id=\"{}\" file=\"{}\">\n{}\n{}</hunk>",
+                hunk.id, hunk.file, hunk.header, body
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Asks the LLM for a structured explanation of `diff`.
+///
+/// # Arguments
+/// * `diff`: A unified diff.
+/// * `llm_client`: The client used to query the model.
+///
+/// # Returns
+/// The explanation, or an `AgentError` if the diff is empty or the LLM response is unusable.
+pub async fn explain_diff(
+    diff: &str,
+    llm_client: Arc<dyn LLMClient>,
+) -> Result<(Vec<DiffHunk>, DiffExplanation), AgentError> {
+    let hunks = split_into_hunks(diff);
+    if hunks.is_empty() {
+        return Err(AgentError::TaskSetupFailed(
+            "The diff contains no hunks to explain.".to_string(),
+        ));
+    }
+    info!("Explaining diff with {} hunk(s)", hunks.len());
+
+    let messages = vec![
+        LLMMessage {
+            role: MessageRole::System,
+            content: Some(EXPLAINER_PROMPT.to_string()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        },
+        LLMMessage {
+            role: MessageRole::User,
+            content: Some(format_hunks_for_prompt(&hunks)),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        },
+    ];
+    let answer = llm_client
+        .chat_structured(messages, "diff_explanation", &explanation_schema())
+        .await?;
+    let explanation: DiffExplanation = serde_json::from_value(answer).map_err(|e| {
+        AgentError::LogicError(format!("LLM explanation did not match the schema: {}", e))
+    })?;
+    Ok((hunks, explanation))
+}
+
+/// Renders an explanation as human-readable text, grouped by file in diff order.
+pub fn render_explanation(hunks: &[DiffHunk], explanation: &DiffExplanation) -> String {
+    let mut out = format!("Summary: {}\n", explanation.summary);
+    let mut last_file: Option<&str> = None;
+    for hunk in hunks {
+        if last_file != Some(hunk.file.as_str()) {
+            out.push_str(&format!("\n{}\n", hunk.file));
+            last_file = Some(hunk.file.as_str());
+        }
+        match explanation.hunks.iter().find(|h| h.id == hunk.id) {
+            Some(h) => out.push_str(&format!(
+                "  {} [risk: {}]\n    {}\n",
+                hunk.header, h.risk, h.explanation
+            )),
+            None => out.push_str(&format!("  {} [no explanation returned]\n", hunk.header)),
+        }
+    }
+    if !explanation.risk_notes.is_empty() {
+        out.push_str("\nRisk notes:\n");
+        for note in &explanation.risk_notes {
+            out.push_str(&format!("  - {}\n", note));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModelParameters;
+    use crate::llm::OpenAIClient;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_params() -> ModelParameters {
+        ModelParameters {
+            api_key: None,
+            model: "m".to_string(),
+            max_tokens: None,
+            temperature: 0.0,
+            top_p: 1.0,
+            top_k: None,
+            parallel_tool_calls: false,
+            max_retries: 0,
+            base_url: None,
+            api_version: None,
+            candidate_count: None,
+            stop_sequences: None,
+            seed: None,
+            extra_headers: None,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            openrouter: None,
+            network: Default::default(),
+        }
+    }
+
+    const DIFF: &str = r#"diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn a() {}
+-fn b() {}
++fn b() -> u32 { 1 }
+@@ -10,2 +10,3 @@ impl X {
++    fn c() {}
+diff --git a/README.md b/README.md
+--- a/README.md
++++ b/README.md
+@@ -1 +1 @@
+-old
++new
+"#;
+
+    #[test]
+    fn test_split_into_hunks() {
+        let hunks = split_into_hunks(DIFF);
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(hunks[0].file, "src/lib.rs");
+        assert_eq!(hunks[0].body, " fn a() {}\n-fn b() {}\n+fn b() -> u32 { 1 }\n");
+        assert_eq!(hunks[1].header, "@@ -10,2 +10,3 @@ impl X {");
+        assert_eq!(hunks[2].id, "H3");
+        assert_eq!(hunks[2].file, "README.md");
+    }
+
+    #[tokio::test]
+    async fn test_explain_diff_uses_structured_output() {
+        let server = MockServer::start().await;
+        let answer = json!({
+            "summary": "Changes b's return type.",
+            "hunks": [
+                {"id": "H1", "explanation": "b now returns 1.", "risk": "medium"},
+                {"id": "H3", "explanation": "Docs tweak.", "risk": "low"}
+            ],
+            "risk_notes": ["Callers of b must handle the return value."]
+        });
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(json!({"response_format": {"type": "json_schema"}})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "x", "object": "chat.completion", "created": 0, "model": "m",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": answer.to_string()}, "finish_reason": "stop"}]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = OpenAIClient::new(Some("k".to_string()), Some(server.uri()), test_params())
+            .await
+            .unwrap();
+        let (hunks, explanation) = explain_diff(DIFF, Arc::new(client)).await.unwrap();
+        let rendered = render_explanation(&hunks, &explanation);
+
+        assert!(rendered.starts_with("Summary: Changes b's return type.\n"));
+        assert!(rendered.contains("src/lib.rs\n  @@ -1,3 +1,3 @@ [risk: medium]\n    b now returns 1."));
+        assert!(rendered.contains("@@ -10,2 +10,3 @@ impl X { [no explanation returned]"));
+        assert!(rendered.contains("  - Callers of b must handle the return value."));
+    }
+
+    #[tokio::test]
+    async fn test_explain_empty_diff_fails() {
+        let client = OpenAIClient::new(
+            Some("k".to_string()),
+            Some("http://127.0.0.1:9".to_string()),
+            test_params(),
+        )
+        .await
+        .unwrap();
+        assert!(explain_diff("", Arc::new(client)).await.is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! # Run Environment
+//!
+//! Captures where and with what a run was executed (platform, toolchain versions, the target
+//! repository's commit, the agent version and a hash of the configuration), so results can be
+//! reproduced and compared across machines. Recorded in trajectories and in `--output json`.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::process::Command;
+
+/// Environment metadata captured at the start of a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunEnvironment {
+    /// Operating system (e.g., "linux", "macos").
+    pub os: String,
+    /// CPU architecture (e.g., "x86_64", "aarch64").
+    pub arch: String,
+    /// Output of `rustc --version`, if a Rust toolchain is installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rustc_version: Option<String>,
+    /// Output of `python3 --version` (or `python --version`), if Python is installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub python_version: Option<String>,
+    /// `HEAD` commit of the target repository at the start of the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_commit: Option<String>,
+    /// Version of this agent.
+    pub agent_version: String,
+    /// SHA-256 of the configuration file, if one was read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_hash: Option<String>,
+}
+
+impl RunEnvironment {
+    /// Captures the current environment.
+    ///
+    /// # Arguments
+    /// * `project_path`: The target repository, used to read its current commit.
+    /// * `config_file`: The configuration file in use, hashed if it exists.
+    pub fn capture(project_path: Option<&Path>, config_file: Option<&Path>) -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            rustc_version: command_output("rustc", &["--version"], None),
+            python_version: command_output("python3", &["--version"], None)
+                .or_else(|| command_output("python", &["--version"], None)),
+            repo_commit: project_path
+                .and_then(|path| command_output("git", &["rev-parse", "HEAD"], Some(path))),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_hash: config_file
+                .and_then(|path| std::fs::read(path).ok())
+                .map(|contents| format!("sha256:{:x}", Sha256::digest(contents))),
+        }
+    }
+}
+
+/// Runs a command and returns its trimmed output (stdout, or stderr for tools such as older
+/// Pythons that print their version there), or `None` if it fails.
+fn command_output(program: &str, args: &[&str], dir: Option<&Path>) -> Option<String> {
+    let mut command = Command::new(program);
+    command.args(args);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command.output().ok().filter(|o| o.status.success())?;
+    let text = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+    let text = String::from_utf8_lossy(&text).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_hashes_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.json");
+        std::fs::write(&config, "{}").unwrap();
+
+        let env = RunEnvironment::capture(Some(dir.path()), Some(&config));
+        assert_eq!(env.os, std::env::consts::OS);
+        assert_eq!(env.agent_version, env!("CARGO_PKG_VERSION"));
+        // SHA-256 of "{}".
+        assert_eq!(
+            env.config_hash.as_deref(),
+            Some("sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a")
+        );
+        // Not a git repository.
+        assert!(env.repo_commit.is_none());
+    }
+}
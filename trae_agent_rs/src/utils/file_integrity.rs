@@ -0,0 +1,209 @@
+//! # File Integrity Check
+//!
+//! Editing tools can silently rewrite a file's encoding or line endings: a CRLF file saved
+//! back with LF, a BOM dropped, a trailing newline lost. Such changes turn every line of the
+//! patch into a change or break tools that expect the original format. After a run, each
+//! changed file tracked at the base commit is compared with its base version, and files whose
+//! encoding, line-ending style or trailing newline changed are reported before the user
+//! applies the patch.
+
+use super::git_utils::FileStats;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+/// How a file's text is encoded, as far as can be told from its bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Not valid UTF-8 and without a BOM, e.g. Latin-1.
+    Other,
+    /// Contains NUL bytes without a UTF-16 BOM.
+    Binary,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 with BOM",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Other => "non-UTF-8",
+            Encoding::Binary => "binary",
+        })
+    }
+}
+
+/// The line endings used in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEndings {
+    /// A single line without a line break.
+    None,
+    Lf,
+    Crlf,
+    Mixed,
+}
+
+impl fmt::Display for LineEndings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineEndings::None => "none",
+            LineEndings::Lf => "LF",
+            LineEndings::Crlf => "CRLF",
+            LineEndings::Mixed => "mixed",
+        })
+    }
+}
+
+/// The format of a file's text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TextFormat {
+    pub encoding: Encoding,
+    pub line_endings: LineEndings,
+    pub trailing_newline: bool,
+}
+
+impl TextFormat {
+    /// The format of `bytes`. Line endings of UTF-16 text are not inspected.
+    pub fn of(bytes: &[u8]) -> Self {
+        let encoding = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            Encoding::Utf8Bom
+        } else if bytes.starts_with(&[0xFF, 0xFE]) {
+            Encoding::Utf16Le
+        } else if bytes.starts_with(&[0xFE, 0xFF]) {
+            Encoding::Utf16Be
+        } else if bytes.contains(&0) {
+            Encoding::Binary
+        } else if std::str::from_utf8(bytes).is_ok() {
+            Encoding::Utf8
+        } else {
+            Encoding::Other
+        };
+        let byte_oriented = !matches!(encoding, Encoding::Utf16Le | Encoding::Utf16Be | Encoding::Binary);
+        let (mut lf, mut crlf) = (0, 0);
+        if byte_oriented {
+            for (i, _) in bytes.iter().enumerate().filter(|(_, b)| **b == b'\n') {
+                if i > 0 && bytes[i - 1] == b'\r' {
+                    crlf += 1;
+                } else {
+                    lf += 1;
+                }
+            }
+        }
+        let line_endings = match (lf, crlf) {
+            (0, 0) => LineEndings::None,
+            (_, 0) => LineEndings::Lf,
+            (0, _) => LineEndings::Crlf,
+            _ => LineEndings::Mixed,
+        };
+        Self {
+            encoding,
+            line_endings,
+            trailing_newline: byte_oriented && bytes.ends_with(b"\n"),
+        }
+    }
+}
+
+/// A changed file whose format differs from its base version.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IntegrityIssue {
+    pub path: String,
+    pub before: TextFormat,
+    pub after: TextFormat,
+}
+
+impl IntegrityIssue {
+    /// The differences, e.g. "line endings CRLF -> LF, trailing newline removed".
+    pub fn describe(&self) -> String {
+        let (before, after) = (&self.before, &self.after);
+        let mut changes = Vec::new();
+        if before.encoding != after.encoding {
+            changes.push(format!("encoding {} -> {}", before.encoding, after.encoding));
+        }
+        if ![before.line_endings, after.line_endings].contains(&LineEndings::None) && before.line_endings != after.line_endings {
+            changes.push(format!("line endings {} -> {}", before.line_endings, after.line_endings));
+        }
+        if before.trailing_newline != after.trailing_newline {
+            changes.push(
+                if after.trailing_newline { "trailing newline added" } else { "trailing newline removed" }.to_string(),
+            );
+        }
+        format!("{}: {}", self.path, changes.join(", "))
+    }
+}
+
+/// Compares the format of `before` and `after`, the contents of `path` at the base commit
+/// and now. Changes are only reported where the base version had the property to lose: line
+/// endings of a file that had line breaks, and the trailing newline of a non-empty file.
+pub fn compare(path: &str, before: &[u8], after: &[u8]) -> Option<IntegrityIssue> {
+    let (old, new) = (TextFormat::of(before), TextFormat::of(after));
+    if old.encoding == Encoding::Binary || new.encoding == Encoding::Binary {
+        return None;
+    }
+    let encoding_changed = old.encoding != new.encoding;
+    let endings_changed = old.line_endings != LineEndings::None
+        && new.line_endings != LineEndings::None
+        && old.line_endings != new.line_endings;
+    let newline_changed = !before.is_empty() && !after.is_empty() && old.trailing_newline != new.trailing_newline;
+    (encoding_changed || endings_changed || newline_changed).then(|| IntegrityIssue {
+        path: path.to_string(),
+        before: old,
+        after: new,
+    })
+}
+
+/// Checks the changed `files` of the project against `base_commit` (or `HEAD`). New and
+/// deleted files are skipped.
+pub fn check_changed_files(project_path: &Path, base_commit: Option<&str>, files: &FileStats) -> Result<Vec<IntegrityIssue>> {
+    let base = base_commit.filter(|c| !c.trim().is_empty()).unwrap_or("HEAD");
+    let mut issues = Vec::new();
+    for path in files.iter().filter(|(_, stat)| !stat.untracked).map(|(path, _)| path) {
+        let Ok(after) = std::fs::read(project_path.join(path)) else {
+            continue;
+        };
+        let Some(before) = base_version(project_path, base, path)? else {
+            continue;
+        };
+        issues.extend(compare(path, &before, &after));
+    }
+    Ok(issues)
+}
+
+/// The contents of `path` at `base`, `None` if it did not exist there.
+fn base_version(project_path: &Path, base: &str, path: &str) -> Result<Option<Vec<u8>>> {
+    let output = Command::new("git")
+        .current_dir(project_path)
+        .args(["show", &format!("{}:{}", base, path)])
+        .output()
+        .with_context(|| format!("Failed to execute git show in {}", project_path.display()))?;
+    Ok(output.status.success().then_some(output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_changes_are_reported() {
+        let issue = compare("win.txt", b"a\r\nb\r\n", b"a\nb").unwrap();
+        assert_eq!(issue.describe(), "win.txt: line endings CRLF -> LF, trailing newline removed");
+
+        let issue = compare("bom.cs", b"\xEF\xBB\xBFclass A {}\n", b"class A {}\n").unwrap();
+        assert_eq!(issue.describe(), "bom.cs: encoding UTF-8 with BOM -> UTF-8");
+        assert_eq!(compare("latin1.txt", b"caf\xE9\n", b"caf\xC3\xA9\n").unwrap().after.encoding, Encoding::Utf8);
+
+        // Ordinary edits keep the format.
+        assert!(compare("lib.rs", b"fn a() {}\r\n", b"fn a() {}\r\nfn b() {}\r\n").is_none());
+        assert!(compare("one-line.txt", b"x", b"x\ny").is_none());
+        assert!(compare("image.png", b"\x89PNG\0", b"\x89PNG\0\0").is_none());
+        assert_eq!(TextFormat::of(b"a\r\nb\n").line_endings, LineEndings::Mixed);
+    }
+}
@@ -0,0 +1,167 @@
+//! # GitHub Actions Annotations
+//!
+//! With `--gha-annotations`, results are also printed as GitHub Actions workflow commands
+//! (`::error file=src/lib.rs,line=3::message`), which the runner turns into annotations on the
+//! pull request diff and the run summary: failed runs and verification, files whose format
+//! changed, and the risky hunks found by `explain-diff`.
+
+use super::diff_explainer::{DiffExplanation, DiffHunk};
+use super::file_integrity::IntegrityIssue;
+use std::fmt;
+
+/// The severity of an annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+    Notice,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Notice => "notice",
+        })
+    }
+}
+
+/// One annotation, optionally on a file and line range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub level: Level,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub end_line: Option<usize>,
+    pub title: String,
+    pub message: String,
+}
+
+impl Annotation {
+    pub fn new(level: Level, title: &str, message: &str) -> Self {
+        Self {
+            level,
+            file: None,
+            line: None,
+            end_line: None,
+            title: title.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Places the annotation on `file`, from `line` to `end_line`.
+    pub fn on(self, file: &str, line: Option<usize>, end_line: Option<usize>) -> Self {
+        Self {
+            file: Some(file.to_string()),
+            line,
+            end_line,
+            ..self
+        }
+    }
+
+    /// The workflow command, e.g. `::warning file=a.rs,line=3,title=Risk::Unchecked index`.
+    pub fn to_command(&self) -> String {
+        let mut properties = Vec::new();
+        if let Some(file) = &self.file {
+            properties.push(format!("file={}", escape_property(file)));
+        }
+        if let Some(line) = self.line {
+            properties.push(format!("line={}", line));
+        }
+        if let Some(end_line) = self.end_line {
+            properties.push(format!("endLine={}", end_line));
+        }
+        properties.push(format!("title={}", escape_property(&self.title)));
+        format!("::{} {}::{}", self.level, properties.join(","), escape_data(&self.message))
+    }
+}
+
+/// Escapes a message: `%`, CR and LF.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a property value: as a message, plus `:` and `,`.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// The annotations of a `trae run`: an error if it failed (including a failed verification),
+/// and a warning on each file whose format changed.
+pub fn run_annotations(success: bool, error: Option<&str>, integrity_issues: &[IntegrityIssue]) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+    if !success {
+        let message = error.unwrap_or("The task was not completed.");
+        annotations.push(Annotation::new(Level::Error, "trae run failed", message));
+    }
+    for issue in integrity_issues {
+        let changes = issue.describe();
+        let message = changes.strip_prefix(&format!("{}: ", issue.path)).unwrap_or(&changes);
+        annotations.push(Annotation::new(Level::Warning, "File format changed", message).on(&issue.path, None, None));
+    }
+    annotations
+}
+
+/// The annotations of an `explain-diff` review: a warning on each high-risk hunk, a notice on
+/// each medium-risk one, and a notice for each overall risk note.
+pub fn review_annotations(hunks: &[DiffHunk], explanation: &DiffExplanation) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+    for hunk in hunks {
+        let Some(explained) = explanation.hunks.iter().find(|h| h.id == hunk.id) else {
+            continue;
+        };
+        let level = match explained.risk.to_lowercase().as_str() {
+            "high" => Level::Warning,
+            "medium" => Level::Notice,
+            _ => continue,
+        };
+        let (line, end_line) = match hunk.new_lines() {
+            Some((start, end)) => (Some(start), Some(end)),
+            None => (None, None),
+        };
+        let title = format!("Review: {} risk", explained.risk.to_lowercase());
+        annotations.push(Annotation::new(level, &title, &explained.explanation).on(&hunk.file, line, end_line));
+    }
+    for note in &explanation.risk_notes {
+        annotations.push(Annotation::new(Level::Notice, "Review: risk note", note));
+    }
+    annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::diff_explainer::{split_into_hunks, HunkExplanation};
+
+    #[test]
+    fn test_review_findings_become_escaped_workflow_commands() {
+        let diff = "diff --git a/src/a,b.rs b/src/a,b.rs\n--- a/src/a,b.rs\n+++ b/src/a,b.rs\n\
+            @@ -10,3 +12,4 @@ fn main() {\n ctx\n+added\n ctx\n ctx\n\
+            @@ -40 +43 @@\n-old\n+new\n";
+        let hunks = split_into_hunks(diff);
+        let explanation = DiffExplanation {
+            summary: "Parser changes".to_string(),
+            hunks: vec![
+                HunkExplanation { id: "H1".to_string(), explanation: "Removes a check: 100% of\ninputs".to_string(), risk: "High".to_string() },
+                HunkExplanation { id: "H2".to_string(), explanation: "Renames".to_string(), risk: "low".to_string() },
+            ],
+            risk_notes: vec!["No tests cover empty input".to_string()],
+        };
+        let commands: Vec<String> = review_annotations(&hunks, &explanation).iter().map(Annotation::to_command).collect();
+        assert_eq!(
+            commands,
+            [
+                "::warning file=src/a%2Cb.rs,line=12,endLine=15,title=Review%3A high risk::Removes a check: 100%25 of%0Ainputs",
+                "::notice title=Review%3A risk note::No tests cover empty input",
+            ]
+        );
+
+        let failed = run_annotations(false, Some("The reproduction command `./repro.sh` exited with status 1"), &[]);
+        assert_eq!(
+            failed[0].to_command(),
+            "::error title=trae run failed::The reproduction command `./repro.sh` exited with status 1"
+        );
+        assert!(run_annotations(true, None, &[]).is_empty());
+    }
+}
@@ -4,10 +4,182 @@
 //! such as retrieving diffs and processing patch content.
 
 use anyhow::{Context, Result};
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-/// Gets the git diff of the project.
+/// Size of a set of changes, as reported by `git diff --numstat`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffStat {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl fmt::Display for DiffStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} file{} changed, +{} -{}",
+            self.files_changed,
+            if self.files_changed == 1 { "" } else { "s" },
+            self.insertions,
+            self.deletions
+        )
+    }
+}
+
+/// Lines added and removed in one file, as reported by `git diff --numstat`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileStat {
+    pub insertions: usize,
+    pub deletions: usize,
+    /// The file is not tracked by git; all its lines count as added.
+    pub untracked: bool,
+}
+
+/// Per-file diffstat, keyed by path relative to the project root.
+pub type FileStats = BTreeMap<String, FileStat>;
+
+impl DiffStat {
+    /// Totals of a per-file diffstat.
+    pub fn total(files: &FileStats) -> Self {
+        files.values().fold(DiffStat::default(), |stat, file| DiffStat {
+            files_changed: stat.files_changed + 1,
+            insertions: stat.insertions + file.insertions,
+            deletions: stat.deletions + file.deletions,
+        })
+    }
+}
+
+/// Parses `git diff --numstat` output. Binary files count as changed without lines.
+pub fn parse_numstat_files(output: &str) -> FileStats {
+    let mut files = FileStats::new();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let mut fields = line.splitn(3, '\t');
+        let insertions = fields.next().and_then(|n| n.parse::<usize>().ok());
+        let deletions = fields.next().and_then(|n| n.parse::<usize>().ok());
+        let path = fields.next().unwrap_or_default().to_string();
+        files.insert(
+            path,
+            FileStat {
+                insertions: insertions.unwrap_or(0),
+                deletions: deletions.unwrap_or(0),
+                untracked: false,
+            },
+        );
+    }
+    files
+}
+
+/// Computes the per-file diffstat of the working tree against `base_commit` (or `HEAD`),
+/// counting untracked files as added.
+///
+/// # Arguments
+/// * `project_path`: Absolute path to the root of the git repository.
+/// * `base_commit`: Optional revision to diff against; `HEAD` when `None` or empty.
+pub fn file_diff_stats(project_path: &str, base_commit: Option<&str>) -> Result<FileStats> {
+    let base = base_commit.filter(|c| !c.trim().is_empty()).unwrap_or("HEAD");
+    let mut files = parse_numstat_files(&get_git_diff_with_args(project_path, &["--numstat", base])?);
+
+    let output = Command::new("git")
+        .current_dir(Path::new(project_path))
+        .args(["ls-files", "--others", "--exclude-standard", "-z"])
+        .output()
+        .with_context(|| format!("Failed to execute git ls-files in {}", project_path))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git ls-files command failed with status {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    for path in output.stdout.split(|b| *b == 0).filter(|p| !p.is_empty()) {
+        let path = String::from_utf8_lossy(path).into_owned();
+        // Unreadable or binary files count as changed without lines.
+        let insertions = std::fs::read_to_string(Path::new(project_path).join(&path))
+            .map(|content| content.lines().count())
+            .unwrap_or(0);
+        files.insert(
+            path,
+            FileStat {
+                insertions,
+                deletions: 0,
+                untracked: true,
+            },
+        );
+    }
+    Ok(files)
+}
+
+/// How a step changed a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    /// A new, untracked file appeared.
+    Created,
+    Modified,
+    /// The file no longer differs from the base.
+    Reverted,
+}
+
+/// What one step changed in one file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: FileChangeKind,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl fmt::Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            FileChangeKind::Created => "created",
+            FileChangeKind::Modified => "modified",
+            FileChangeKind::Reverted => "reverted",
+        };
+        write!(f, "{} {} +{}/-{}", kind, self.path, self.insertions, self.deletions)
+    }
+}
+
+/// Files whose diffstat differs between `before` and `after` a step, with the lines the step
+/// added and removed. The counts come from the difference of the two cumulative diffstats:
+/// fewer deletions than before means lines were put back, fewer insertions means added lines
+/// were removed again. Edits that keep a file's counts unchanged are not seen.
+pub fn step_changes(before: &FileStats, after: &FileStats) -> Vec<FileChange> {
+    let paths: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let old = before.get(path).copied().unwrap_or_default();
+            let new = after.get(path);
+            if new == Some(&old) {
+                return None;
+            }
+            let kind = match new {
+                None => FileChangeKind::Reverted,
+                Some(new) if new.untracked && !before.contains_key(path) => FileChangeKind::Created,
+                Some(_) => FileChangeKind::Modified,
+            };
+            let new = new.copied().unwrap_or_default();
+            Some(FileChange {
+                path: path.clone(),
+                kind,
+                insertions: new.insertions.saturating_sub(old.insertions) + old.deletions.saturating_sub(new.deletions),
+                deletions: new.deletions.saturating_sub(old.deletions) + old.insertions.saturating_sub(new.insertions),
+            })
+        })
+        .collect()
+}
+
+/// Paths, relative to the project root, that are never part of a generated patch: trae's own
+/// files in the project (run scratch directories, permissions).
+pub const EXCLUDED_FROM_DIFF: &[&str] = &[".trae"];
+
+/// Gets the git diff of the project, leaving out `EXCLUDED_FROM_DIFF`.
 ///
 /// If `base_commit` is `None` or an empty string, it performs a diff against the current
 /// working tree (unstaged changes). If `base_commit` is specified, it diffs
@@ -21,19 +193,130 @@ use std::process::Command;
 /// A `Result` containing the diff output as a string, or an error if the `git diff` command fails
 /// or its output is not valid UTF-8.
 pub fn get_git_diff(project_path: &str, base_commit: Option<&str>) -> Result<String> {
+    get_git_diff_excluding(project_path, base_commit, &[])
+}
+
+/// Like `get_git_diff`, also leaving out `excluded`: files and directories of the run such as
+/// its trajectory, patch file and scratch directory. Paths are absolute or relative to the
+/// project root; those outside the project are ignored.
+pub fn get_git_diff_excluding(project_path: &str, base_commit: Option<&str>, excluded: &[PathBuf]) -> Result<String> {
+    diff_excluding(project_path, base_commit, excluded, &[])
+}
+
+/// A patch of the project, as exported by `get_patch_excluding`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    pub diff: String,
+    /// Binary files that changed but were left out of `diff`.
+    pub binary_files: Vec<String>,
+}
+
+/// Like `get_git_diff_excluding`, for a patch meant to be applied elsewhere. Textconv and
+/// external diff drivers set in `.gitattributes` are not used, so hunks hold the files' real
+/// contents. Binary changes, including files `.gitattributes` marks `binary` or `-diff`, are
+/// included as git binary patches if `include_binary`; otherwise they are left out of the
+/// diff (where git would only note that they differ) and listed in `binary_files`.
+pub fn get_patch_excluding(
+    project_path: &str,
+    base_commit: Option<&str>,
+    excluded: &[PathBuf],
+    include_binary: bool,
+) -> Result<Patch> {
+    let mut flags = vec!["--no-textconv", "--no-ext-diff"];
+    if include_binary {
+        flags.push("--binary");
+        // Binary patches need the full blob ids to apply.
+        flags.push("--full-index");
+    }
+    let diff = diff_excluding(project_path, base_commit, excluded, &flags)?;
+    if include_binary {
+        return Ok(Patch {
+            diff,
+            binary_files: Vec::new(),
+        });
+    }
+    Ok(split_binary_changes(&diff))
+}
+
+/// Moves the files git reports as `Binary files ... differ` out of `diff` into the list.
+fn split_binary_changes(diff: &str) -> Patch {
+    let mut patch = Patch::default();
+    let mut sections: Vec<String> = Vec::new();
+    for line in diff.split_inclusive('\n') {
+        match sections.last_mut() {
+            Some(section) if !line.starts_with("diff --git ") => section.push_str(line),
+            _ => sections.push(line.to_string()),
+        }
+    }
+    for section in sections {
+        if section.lines().any(|line| line.starts_with("Binary files ")) {
+            let header = section.lines().next().unwrap_or_default();
+            let path = header.split_once(" b/").map_or(header, |(_, path)| path);
+            patch.binary_files.push(path.to_string());
+        } else {
+            patch.diff.push_str(&section);
+        }
+    }
+    patch
+}
+
+/// `git diff` of the whole repository against `base_commit` (or of the uncommitted changes),
+/// with `flags`, leaving out the run's files and `excluded`.
+fn diff_excluding(project_path: &str, base_commit: Option<&str>, excluded: &[PathBuf], flags: &[&str]) -> Result<String> {
+    let mut args: Vec<String> = flags.iter().map(|flag| flag.to_string()).collect();
+    args.extend(match base_commit {
+        // Ensure base_commit is not just whitespace
+        Some(commit) if !commit.trim().is_empty() => {
+            // Diff between base_commit and current HEAD
+            vec![commit.to_string(), "HEAD".to_string()]
+        }
+        // If base_commit is None or empty, it defaults to `git diff` (unstaged changes)
+        _ => Vec::new(),
+    });
+    // The whole repository, as without a pathspec, minus the exclusions (relative to the
+    // project directory, where git runs).
+    args.extend(["--".to_string(), ":/".to_string()]);
+    let project = Path::new(project_path);
+    let excluded = EXCLUDED_FROM_DIFF
+        .iter()
+        .map(PathBuf::from)
+        .chain(excluded.iter().filter_map(|path| relative_to(project, path)));
+    args.extend(excluded.map(|path| format!(":(exclude){}", path.display())));
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    get_git_diff_with_args(project_path, &args)
+}
+
+/// `path` relative to `project`, if it is inside it.
+fn relative_to(project: &Path, path: &Path) -> Option<PathBuf> {
+    if path.is_relative() {
+        return Some(path.to_path_buf());
+    }
+    if let Ok(relative) = path.strip_prefix(project) {
+        return Some(relative.to_path_buf());
+    }
+    // The same directory may be spelled differently (symlinks such as /tmp on macOS); the
+    // path itself may not exist yet, so its parent is resolved.
+    let project = project.canonicalize().ok()?;
+    let parent = path.parent()?.canonicalize().ok()?;
+    let relative = parent.join(path.file_name()?).strip_prefix(&project).ok()?.to_path_buf();
+    Some(relative)
+}
+
+/// Runs `git diff` with explicit arguments, such as `--cached` or a revision range.
+///
+/// # Arguments
+/// * `project_path`: Absolute path to the root of the git repository.
+/// * `diff_args`: Extra arguments appended after `git --no-pager diff`.
+///
+/// # Returns
+/// A `Result` containing the diff output as a string, or an error if the `git diff` command fails
+/// or its output is not valid UTF-8.
+pub fn get_git_diff_with_args(project_path: &str, diff_args: &[&str]) -> Result<String> {
     let mut cmd = Command::new("git");
     cmd.current_dir(Path::new(project_path));
     cmd.arg("--no-pager");
     cmd.arg("diff");
-
-    if let Some(commit) = base_commit {
-        if !commit.trim().is_empty() {
-            // Ensure base_commit is not just whitespace
-            cmd.arg(commit);
-            cmd.arg("HEAD"); // Diff between base_commit and current HEAD
-        }
-    }
-    // If base_commit is None or empty, it defaults to `git diff` (unstaged changes)
+    cmd.args(diff_args);
 
     let output = cmd
         .output()
@@ -51,6 +334,206 @@ pub fn get_git_diff(project_path: &str, base_commit: Option<&str>) -> Result<Str
     String::from_utf8(output.stdout).with_context(|| "git diff output was not valid UTF-8")
 }
 
+/// Puts files back as they are in `HEAD`: changed and deleted files are checked out again,
+/// and files not in `HEAD` are removed (and dropped from the index if they were added).
+///
+/// # Arguments
+/// * `project_path`: Absolute path to the root of the git repository.
+/// * `files`: Absolute paths of the files to restore, e.g. from `list_changed_files`.
+///
+/// # Returns
+/// `Ok(())` once every file is restored, or an error naming the first that could not be.
+pub fn restore_files(project_path: &str, files: &[PathBuf]) -> Result<()> {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .current_dir(Path::new(project_path))
+            .args(args)
+            .output()
+            .with_context(|| format!("Failed to execute git {} in {}", args.join(" "), project_path))
+    };
+    for file in files {
+        let relative = file.strip_prefix(project_path).unwrap_or(file).to_string_lossy().into_owned();
+        let in_head = git(&["cat-file", "-e", &format!("HEAD:{}", relative)])?.status.success();
+        let output = if in_head {
+            git(&["checkout", "HEAD", "--", &relative])?
+        } else {
+            let output = git(&["rm", "--cached", "-q", "--ignore-unmatch", "--", &relative])?;
+            if file.exists() {
+                std::fs::remove_file(file).with_context(|| format!("Failed to remove {}", file.display()))?;
+            }
+            output
+        };
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Failed to restore {}: {}",
+                file.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Lists files with uncommitted changes (modified, added, deleted, or untracked).
+///
+/// # Arguments
+/// * `project_path`: Absolute path to the root of the git repository.
+///
+/// # Returns
+/// A `Result` containing absolute paths of changed files, sorted, or an error if
+/// `git status` fails.
+pub fn list_changed_files(project_path: &str) -> Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .current_dir(Path::new(project_path))
+        .args(["status", "--porcelain", "--untracked-files=all"])
+        .output()
+        .with_context(|| format!("Failed to execute git status in {}", project_path))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!(
+            "git status command failed with status {}: {}",
+            output.status,
+            stderr
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut files: Vec<PathBuf> = stdout
+        .lines()
+        .filter(|line| line.len() > 3)
+        .map(|line| {
+            // Renames are reported as "R  old -> new"; the new path is what changed on disk.
+            let path = &line[3..];
+            let path = path.rsplit(" -> ").next().unwrap_or(path);
+            Path::new(project_path).join(path.trim_matches('"'))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// How a repository nested in the project is related to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NestedRepoKind {
+    /// A submodule listed in `.gitmodules`.
+    Submodule,
+    /// Another repository inside the project: a gitlink without `.gitmodules` entry, or an
+    /// untracked directory with its own `.git`.
+    Nested,
+}
+
+/// Changes in a repository nested in the project. `git diff` of the project shows at most
+/// a `Subproject commit` line for them, so they are collected separately.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NestedRepoChange {
+    /// Path relative to the project root.
+    pub path: String,
+    pub kind: NestedRepoKind,
+    /// Commit the project records for it (at `base_commit`, or in the index); none for
+    /// untracked repositories.
+    pub recorded_commit: Option<String>,
+    pub head_commit: Option<String>,
+    /// Its `HEAD` moved away from the recorded commit: the project's submodule pointer changed.
+    pub pointer_changed: bool,
+    pub files_changed: usize,
+    /// Where its patch was saved, next to the project's patch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch_file: Option<String>,
+    /// Working tree of the repository against the recorded commit (or its `HEAD`).
+    #[serde(skip)]
+    pub diff: String,
+}
+
+/// Finds the submodules and nested repositories of the project and their changes: against
+/// the commit the project records for them (at `base_commit` if given), or against their
+/// `HEAD` for untracked repositories. Those without changes, and uninitialized submodules,
+/// are left out. Repositories nested inside them are not searched.
+pub fn nested_repo_changes(project_path: &str, base_commit: Option<&str>) -> Result<Vec<NestedRepoChange>> {
+    let project = Path::new(project_path);
+    let submodules: BTreeSet<String> = git_output(project, &["config", "-f", ".gitmodules", "--get-regexp", "path"])
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once(' ').map(|(_, path)| path.to_string()))
+        .collect();
+    let base_commit = base_commit.filter(|commit| !commit.trim().is_empty());
+
+    let mut repos: Vec<(String, Option<String>)> = Vec::new();
+    for entry in git_output(project, &["ls-files", "--stage", "-z"])?.split('\0') {
+        // "<mode> <object> <stage>\t<path>"; gitlinks have mode 160000.
+        let Some((meta, path)) = entry.split_once('\t') else { continue };
+        let mut fields = meta.split(' ');
+        if fields.next() != Some("160000") {
+            continue;
+        }
+        let recorded = match base_commit {
+            Some(base) => git_output(project, &["ls-tree", base, "--", path])
+                .ok()
+                .and_then(|line| line.split_whitespace().nth(2).map(str::to_string)),
+            None => fields.next().map(str::to_string),
+        };
+        repos.push((path.to_string(), recorded));
+    }
+    for line in git_output(project, &["status", "--porcelain", "--untracked-files=normal"])?.lines() {
+        if let Some(dir) = line.strip_prefix("?? ").and_then(|path| path.trim_matches('"').strip_suffix('/')) {
+            if project.join(dir).join(".git").exists() {
+                repos.push((dir.to_string(), None));
+            }
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (path, recorded_commit) in repos {
+        let dir = project.join(&path);
+        if !dir.join(".git").exists() {
+            continue;
+        }
+        let head_commit = git_output(&dir, &["rev-parse", "HEAD"]).ok().map(|head| head.trim().to_string());
+        let pointer_changed = recorded_commit.is_some() && head_commit != recorded_commit;
+        let diff_against = |commit: &str| git_output(&dir, &["--no-pager", "diff", "--no-textconv", "--no-ext-diff", commit]);
+        let diff = match (&head_commit, &recorded_commit) {
+            (None, _) => String::new(),
+            // The recorded commit may not have been fetched into the repository.
+            (Some(_), Some(recorded)) if pointer_changed => diff_against(recorded).or_else(|_| diff_against("HEAD"))?,
+            (Some(_), _) => diff_against("HEAD")?,
+        };
+        let files_changed = diff.lines().filter(|line| line.starts_with("diff --git ")).count();
+        if !pointer_changed && files_changed == 0 {
+            continue;
+        }
+        changes.push(NestedRepoChange {
+            kind: if submodules.contains(&path) { NestedRepoKind::Submodule } else { NestedRepoKind::Nested },
+            path,
+            recorded_commit,
+            head_commit,
+            pointer_changed,
+            files_changed,
+            patch_file: None,
+            diff,
+        });
+    }
+    Ok(changes)
+}
+
+/// Stdout of `git args` run in `dir`.
+fn git_output(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to execute git {} in {}", args.join(" "), dir.display()))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed with status {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
 /// Removes patches related to test files or directories from a given git diff string.
 ///
 /// This function iterates through the lines of a diff. When it encounters a
@@ -210,6 +693,97 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_get_git_diff_excludes_run_artifacts() -> Result<()> {
+        let dir = tempdir()?;
+        setup_git_repo(dir.path())?;
+        commit_file(dir.path(), "file.txt", "initial content")?;
+        commit_file(dir.path(), "trajectory.json", "{}")?;
+        fs::create_dir_all(dir.path().join(".trae/runs/1/scratch"))?;
+        commit_file(dir.path(), ".trae/runs/1/scratch/repro.py", "print(1)")?;
+        fs::write(dir.path().join("file.txt"), "new content")?;
+        fs::write(dir.path().join("trajectory.json"), "{\"steps\": []}")?;
+        fs::write(dir.path().join(".trae/runs/1/scratch/repro.py"), "print(2)")?;
+
+        let project = dir.path().to_str().unwrap();
+        let diff = get_git_diff(project, None)?;
+        assert!(diff.contains("+++ b/file.txt"));
+        assert!(diff.contains("+++ b/trajectory.json"));
+        assert!(!diff.contains(".trae"), "{}", diff);
+
+        let excluded = [dir.path().join("trajectory.json"), PathBuf::from("/elsewhere/model.patch")];
+        let diff = get_git_diff_excluding(project, None, &excluded)?;
+        assert!(diff.contains("+++ b/file.txt"));
+        assert!(!diff.contains("trajectory.json"), "{}", diff);
+        Ok(())
+    }
+
+    #[test]
+    fn test_patch_includes_or_lists_binary_changes() -> Result<()> {
+        let dir = tempdir()?;
+        setup_git_repo(dir.path())?;
+        commit_file(dir.path(), ".gitattributes", "*.dat binary\n")?;
+        commit_file(dir.path(), "file.txt", "initial content\n")?;
+        commit_file(dir.path(), "table.dat", "1,2\n")?;
+        fs::write(dir.path().join("logo.bin"), [0u8, 1, 2, 3])?;
+        Command::new("git").args(["add", "logo.bin"]).current_dir(dir.path()).status()?;
+        Command::new("git").args(["commit", "-m", "add logo"]).current_dir(dir.path()).status()?;
+        fs::write(dir.path().join("file.txt"), "new content\n")?;
+        fs::write(dir.path().join("table.dat"), "3,4\n")?;
+        fs::write(dir.path().join("logo.bin"), [0u8, 9, 9, 9])?;
+
+        let project = dir.path().to_str().unwrap();
+        let patch = get_patch_excluding(project, None, &[], true)?;
+        assert!(patch.binary_files.is_empty());
+        assert_eq!(patch.diff.matches("GIT binary patch").count(), 2, "{}", patch.diff);
+
+        let patch = get_patch_excluding(project, None, &[], false)?;
+        assert_eq!(patch.binary_files, vec!["logo.bin", "table.dat"]);
+        assert!(patch.diff.starts_with("diff --git a/file.txt b/file.txt\n"), "{}", patch.diff);
+        assert!(!patch.diff.contains("Binary files"), "{}", patch.diff);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_repo_changes_cover_submodules_and_untracked_repos() -> Result<()> {
+        let dir = tempdir()?;
+        let project = dir.path();
+        setup_git_repo(project)?;
+        commit_file(project, "README.md", "outer\n")?;
+        // A submodule (a gitlink listed in .gitmodules) and an untracked nested repository.
+        for inner in ["vendor/lib", "scratch"] {
+            fs::create_dir_all(project.join(inner))?;
+            setup_git_repo(&project.join(inner))?;
+            commit_file(&project.join(inner), "lib.txt", "v1\n")?;
+        }
+        fs::write(project.join(".gitmodules"), "[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = ./lib\n")?;
+        Command::new("git").args(["add", ".gitmodules", "vendor/lib"]).current_dir(project).output()?;
+        Command::new("git").args(["commit", "-q", "-m", "add lib"]).current_dir(project).status()?;
+        let recorded = git_output(&project.join("vendor/lib"), &["rev-parse", "HEAD"])?.trim().to_string();
+        let path = project.to_str().unwrap();
+        assert!(nested_repo_changes(path, None)?.is_empty());
+
+        // The submodule gets a commit and an uncommitted change; the nested repository one change.
+        commit_file(&project.join("vendor/lib"), "lib.txt", "v2\n")?;
+        fs::write(project.join("vendor/lib/new.txt"), "new\n")?;
+        Command::new("git").args(["add", "new.txt"]).current_dir(project.join("vendor/lib")).status()?;
+        fs::write(project.join("scratch/lib.txt"), "edited\n")?;
+
+        let changes = nested_repo_changes(path, None)?;
+        assert_eq!(changes.len(), 2, "{:?}", changes);
+        let submodule = &changes[0];
+        assert_eq!((submodule.path.as_str(), submodule.kind), ("vendor/lib", NestedRepoKind::Submodule));
+        assert_eq!(submodule.recorded_commit.as_deref(), Some(recorded.as_str()));
+        assert!(submodule.pointer_changed);
+        assert_eq!(submodule.files_changed, 2, "committed and uncommitted changes since the recorded commit");
+        assert!(submodule.diff.contains("+v2"), "{}", submodule.diff);
+
+        let nested = &changes[1];
+        assert_eq!((nested.path.as_str(), nested.kind, nested.pointer_changed), ("scratch", NestedRepoKind::Nested, false));
+        assert!(nested.diff.contains("+edited"), "{}", nested.diff);
+        Ok(())
+    }
+
     #[test]
     fn test_remove_patches_to_tests_simple() {
         let patch = r#"
@@ -316,4 +890,90 @@ index e69de29..067929c 100644
         .trim();
         assert_eq!(remove_patches_to_tests(patch).trim(), "");
     }
+
+    #[test]
+    fn test_list_changed_files_includes_untracked() -> Result<()> {
+        let dir = tempdir()?;
+        setup_git_repo(dir.path())?;
+        commit_file(dir.path(), "tracked.txt", "v1")?;
+        fs::write(dir.path().join("tracked.txt"), "v2")?;
+        fs::write(dir.path().join("new.txt"), "new")?;
+
+        let changed = list_changed_files(dir.path().to_str().unwrap())?;
+        assert_eq!(
+            changed,
+            vec![dir.path().join("new.txt"), dir.path().join("tracked.txt")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_files_returns_them_to_head() -> Result<()> {
+        let dir = tempdir()?;
+        setup_git_repo(dir.path())?;
+        commit_file(dir.path(), "tracked.txt", "v1")?;
+        commit_file(dir.path(), "gone.txt", "kept")?;
+        fs::write(dir.path().join("tracked.txt"), "v2")?;
+        fs::remove_file(dir.path().join("gone.txt"))?;
+        fs::write(dir.path().join("new.txt"), "new")?;
+        fs::write(dir.path().join("staged.txt"), "staged")?;
+        Command::new("git").args(["add", "staged.txt"]).current_dir(dir.path()).status()?;
+
+        let project = dir.path().to_str().unwrap();
+        restore_files(project, &list_changed_files(project)?)?;
+        assert!(list_changed_files(project)?.is_empty());
+        assert_eq!(fs::read_to_string(dir.path().join("tracked.txt"))?, "v1");
+        assert!(dir.path().join("gone.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_stat_counts_tracked_and_untracked_changes() -> Result<()> {
+        let dir = tempdir()?;
+        setup_git_repo(dir.path())?;
+        commit_file(dir.path(), "a.txt", "one\ntwo\nthree\n")?;
+        let project = dir.path().to_str().unwrap();
+        assert_eq!(DiffStat::total(&file_diff_stats(project, None)?), DiffStat::default());
+
+        fs::write(dir.path().join("a.txt"), "one\n2\nthree\nfour\n")?;
+        fs::write(dir.path().join("b.txt"), "new\nfile\n")?;
+        let stat = DiffStat::total(&file_diff_stats(project, None)?);
+        assert_eq!(
+            stat,
+            DiffStat {
+                files_changed: 2,
+                insertions: 4,
+                deletions: 1
+            }
+        );
+        assert_eq!(stat.to_string(), "2 files changed, +4 -1");
+        assert_eq!(DiffStat::total(&parse_numstat_files("-\t-\timage.png\n")).files_changed, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_changes_between_file_stats() -> Result<()> {
+        let dir = tempdir()?;
+        setup_git_repo(dir.path())?;
+        commit_file(dir.path(), "parser.rs", "a\nb\nc\n")?;
+        commit_file(dir.path(), "lexer.rs", "x\n")?;
+        let project = dir.path().to_str().unwrap();
+
+        fs::write(dir.path().join("parser.rs"), "a\nB\nc\nd\n")?;
+        fs::write(dir.path().join("lexer.rs"), "y\n")?;
+        let first = file_diff_stats(project, None)?;
+
+        fs::write(dir.path().join("parser.rs"), "a\nb\nc\nd\ne\n")?;
+        fs::write(dir.path().join("lexer.rs"), "x\n")?;
+        fs::write(dir.path().join("notes.md"), "one\ntwo\n")?;
+        let second = file_diff_stats(project, None)?;
+
+        let changes: Vec<String> = step_changes(&first, &second).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            changes,
+            vec!["reverted lexer.rs +1/-1", "created notes.md +2/-0", "modified parser.rs +1/-0"]
+        );
+        assert!(step_changes(&second, &second).is_empty());
+        Ok(())
+    }
 }
@@ -0,0 +1,327 @@
+//! # Write Guards
+//!
+//! Restrictions applied to file-modifying tool calls. A `WriteGuard` combines
+//! protected paths (never writable), an optional allow-list (only these paths are
+//! writable), and a change budget capping how many distinct files and write
+//! operations the agent may perform during a run.
+//!
+//! Guards are enforced by `ToolExecutor` for the file editing tools, and by
+//! `restore_checkpoint` for every file a restore writes back or removes. Commands run
+//! through `bash` cannot be inspected this way; callers that need a hard guarantee
+//! should additionally verify the resulting diff with `out_of_scope_changes`.
+
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Limits on how much the agent may change during a guarded run.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeBudget {
+    /// Maximum number of distinct files that may be modified.
+    pub max_files: Option<usize>,
+    /// Maximum number of individual write operations (edits, creates, inserts).
+    pub max_writes: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct GuardUsage {
+    touched_files: HashSet<PathBuf>,
+    writes: usize,
+}
+
+/// Enforces protected paths, an optional allow-list, and a change budget for file writes.
+#[derive(Debug, Default)]
+pub struct WriteGuard {
+    protected_paths: Vec<PathBuf>,
+    allowed_paths: Option<HashSet<PathBuf>>,
+    budget: ChangeBudget,
+    usage: Mutex<GuardUsage>,
+}
+
+impl WriteGuard {
+    /// Creates a guard with no restrictions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks a file or directory as protected. Writes to it (or below it) are always rejected.
+    pub fn with_protected_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.protected_paths.push(normalize_path(path.as_ref()));
+        self
+    }
+
+    /// Restricts writes to exactly the given files.
+    pub fn with_allowed_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        self.allowed_paths = Some(
+            paths
+                .into_iter()
+                .map(|p| normalize_path(p.as_ref()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Sets the change budget for this guard.
+    pub fn with_budget(mut self, budget: ChangeBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Checks whether a write to `path` is permitted and, if so, records it against the budget.
+    ///
+    /// # Returns
+    /// `Ok(())` if the write may proceed, or an error message suitable for returning to the LLM.
+    pub fn check_write(&self, path: &Path) -> Result<(), String> {
+        self.evaluate_write(path, true)
+    }
+
+    /// Checks writes to all of `paths` as one operation: either every write is permitted and
+    /// recorded against the budget, or none is recorded.
+    ///
+    /// Used for tools that change several files at once, such as restoring a checkpoint.
+    pub fn check_writes(&self, paths: &[PathBuf]) -> Result<(), String> {
+        for path in paths {
+            self.evaluate_write(path, false)?;
+        }
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let files: HashSet<PathBuf> = paths.iter().map(|p| normalize_path(p)).collect();
+        let new_files = files.difference(&usage.touched_files).count();
+        if let Some(max_files) = self.budget.max_files {
+            if usage.touched_files.len() + new_files > max_files {
+                return Err(format!(
+                    "Writing {} files rejected: change budget of {} files would be exceeded.",
+                    files.len(),
+                    max_files
+                ));
+            }
+        }
+        if let Some(max_writes) = self.budget.max_writes {
+            if usage.writes + paths.len() > max_writes {
+                return Err(format!(
+                    "Writing {} files rejected: change budget of {} write operations would be exceeded.",
+                    paths.len(),
+                    max_writes
+                ));
+            }
+        }
+        usage.writes += paths.len();
+        usage.touched_files.extend(files);
+        Ok(())
+    }
+
+    /// Checks whether a write to `path` would be permitted, without recording it.
+    ///
+    /// Used for preflight validation while a tool call's arguments are still streaming in.
+    pub fn preview_write(&self, path: &Path) -> Result<(), String> {
+        self.evaluate_write(path, false)
+    }
+
+    fn evaluate_write(&self, path: &Path, record: bool) -> Result<(), String> {
+        let path = normalize_path(path);
+
+        if let Some(protected) = self.protected_paths.iter().find(|p| path.starts_with(p)) {
+            return Err(format!(
+                "Write to {} rejected: {} is a protected path.",
+                path.display(),
+                protected.display()
+            ));
+        }
+
+        if let Some(allowed) = &self.allowed_paths {
+            if !allowed.contains(&path) {
+                let mut allowed_list: Vec<String> =
+                    allowed.iter().map(|p| p.display().to_string()).collect();
+                allowed_list.sort();
+                return Err(format!(
+                    "Write to {} rejected: only the following files may be modified in this run: {}",
+                    path.display(),
+                    allowed_list.join(", ")
+                ));
+            }
+        }
+
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let is_new_file = !usage.touched_files.contains(&path);
+        if let Some(max_files) = self.budget.max_files {
+            if is_new_file && usage.touched_files.len() >= max_files {
+                return Err(format!(
+                    "Write to {} rejected: change budget of {} files already used.",
+                    path.display(),
+                    max_files
+                ));
+            }
+        }
+        if let Some(max_writes) = self.budget.max_writes {
+            if usage.writes >= max_writes {
+                return Err(format!(
+                    "Write to {} rejected: change budget of {} write operations already used.",
+                    path.display(),
+                    max_writes
+                ));
+            }
+        }
+
+        if record {
+            usage.writes += 1;
+            usage.touched_files.insert(path);
+        }
+        Ok(())
+    }
+
+    /// Returns the files written through this guard so far, sorted.
+    pub fn touched_files(&self) -> Vec<PathBuf> {
+        let usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let mut files: Vec<PathBuf> = usage.touched_files.iter().cloned().collect();
+        files.sort();
+        files
+    }
+
+    /// Returns the subset of `changed_files` that this guard would not have allowed.
+    ///
+    /// Used to detect changes made outside the file editing tools (e.g., via `bash`).
+    pub fn out_of_scope_changes(&self, changed_files: &[PathBuf]) -> Vec<PathBuf> {
+        changed_files
+            .iter()
+            .map(|p| normalize_path(p))
+            .filter(|p| {
+                self.protected_paths.iter().any(|prot| p.starts_with(prot))
+                    || self
+                        .allowed_paths
+                        .as_ref()
+                        .is_some_and(|allowed| !allowed.contains(p))
+            })
+            .collect()
+    }
+}
+
+/// Extracts the target path of a file-modifying tool call, if the call writes to disk.
+///
+/// Read-only invocations (e.g., `view`, `outline`) return `None`.
+pub fn write_target_for_tool_call(tool_name: &str, arguments: &Value) -> Option<PathBuf> {
+    match tool_name {
+        "str_replace_based_edit_tool" => {
+            let command = arguments.get("command")?.as_str()?;
+            if matches!(command, "view" | "outline" | "view_symbol") {
+                return None;
+            }
+            arguments.get("path")?.as_str().map(PathBuf::from)
+        }
+        "json_edit_tool" => {
+            let operation = arguments.get("operation")?.as_str()?;
+            if operation.eq_ignore_ascii_case("view") {
+                return None;
+            }
+            arguments.get("file_path")?.as_str().map(PathBuf::from)
+        }
+        _ => None,
+    }
+}
+
+/// Lexically normalizes a path (resolving `.` and `..`) without touching the file system,
+/// so that paths to files that do not exist yet can still be compared.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_protected_path_rejects_nested_files() {
+        let guard = WriteGuard::new().with_protected_path("/repo/.git");
+        assert!(guard.check_write(Path::new("/repo/.git/config")).is_err());
+        assert!(guard.check_write(Path::new("/repo/src/main.rs")).is_ok());
+    }
+
+    #[test]
+    fn test_allow_list_only_permits_listed_files() {
+        let guard = WriteGuard::new().with_allowed_paths(["/repo/Cargo.toml", "/repo/src/lib.rs"]);
+        assert!(guard.check_write(Path::new("/repo/src/../Cargo.toml")).is_ok());
+        let err = guard.check_write(Path::new("/repo/src/main.rs")).unwrap_err();
+        assert!(err.contains("only the following files"));
+    }
+
+    #[test]
+    fn test_budget_limits_distinct_files_and_writes() {
+        let guard = WriteGuard::new().with_budget(ChangeBudget {
+            max_files: Some(1),
+            max_writes: Some(2),
+        });
+        assert!(guard.check_write(Path::new("/a.rs")).is_ok());
+        assert!(guard.check_write(Path::new("/b.rs")).is_err());
+        assert!(guard.check_write(Path::new("/a.rs")).is_ok());
+        assert!(guard.check_write(Path::new("/a.rs")).is_err());
+        assert_eq!(guard.touched_files(), vec![PathBuf::from("/a.rs")]);
+    }
+
+    #[test]
+    fn test_check_writes_records_all_or_nothing() {
+        let guard = WriteGuard::new()
+            .with_protected_path("/repo/.env")
+            .with_budget(ChangeBudget {
+                max_files: Some(2),
+                max_writes: None,
+            });
+        let protected = [PathBuf::from("/repo/a.rs"), PathBuf::from("/repo/.env")];
+        assert!(guard.check_writes(&protected).unwrap_err().contains("protected"));
+        let too_many = [PathBuf::from("/a.rs"), PathBuf::from("/b.rs"), PathBuf::from("/c.rs")];
+        assert!(guard.check_writes(&too_many).is_err());
+        assert!(guard.touched_files().is_empty());
+        assert!(guard.check_writes(&too_many[..2]).is_ok());
+        assert_eq!(guard.touched_files().len(), 2);
+    }
+
+    #[test]
+    fn test_preview_write_does_not_use_budget() {
+        let guard = WriteGuard::new().with_budget(ChangeBudget {
+            max_files: Some(1),
+            max_writes: None,
+        });
+        assert!(guard.preview_write(Path::new("/a.rs")).is_ok());
+        assert!(guard.preview_write(Path::new("/b.rs")).is_ok());
+        assert!(guard.touched_files().is_empty());
+        assert!(guard.check_write(Path::new("/b.rs")).is_ok());
+        assert!(guard.preview_write(Path::new("/a.rs")).is_err());
+    }
+
+    #[test]
+    fn test_write_target_for_tool_call() {
+        let view = json!({"command": "view", "path": "/x.rs"});
+        let edit = json!({"command": "str_replace", "path": "/x.rs"});
+        let json_set = json!({"operation": "set", "file_path": "/x.json"});
+        assert_eq!(write_target_for_tool_call("str_replace_based_edit_tool", &view), None);
+        assert_eq!(
+            write_target_for_tool_call("str_replace_based_edit_tool", &edit),
+            Some(PathBuf::from("/x.rs"))
+        );
+        assert_eq!(
+            write_target_for_tool_call("json_edit_tool", &json_set),
+            Some(PathBuf::from("/x.json"))
+        );
+        assert_eq!(write_target_for_tool_call("bash", &edit), None);
+    }
+
+    #[test]
+    fn test_out_of_scope_changes() {
+        let guard = WriteGuard::new().with_allowed_paths(["/repo/Cargo.toml"]);
+        let changed = vec![PathBuf::from("/repo/Cargo.toml"), PathBuf::from("/repo/build.rs")];
+        assert_eq!(guard.out_of_scope_changes(&changed), vec![PathBuf::from("/repo/build.rs")]);
+    }
+}
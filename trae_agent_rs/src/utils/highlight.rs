@@ -0,0 +1,257 @@
+//! # Console Highlighting
+//!
+//! Syntax highlighting (syntect) and width-aware wrapping of what the CLI prints: file views
+//! and diffs in the progress output, code blocks in assistant messages in interactive mode and
+//! run summaries. Colors are only used on a terminal, and never with `--no-color` or the
+//! `NO_COLOR` environment variable; wrapping also only applies on a terminal, so piped output
+//! is unchanged.
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+
+const THEME: &str = "base16-ocean.dark";
+const RESET: &str = "\x1b[0m";
+/// Lines of a file view or diff shown in progress output.
+pub const PREVIEW_LINES: usize = 20;
+
+static COLOR_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Output stream something is printed to; colors and wrapping depend on whether it is a
+/// terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    fn is_terminal(self) -> bool {
+        match self {
+            Stream::Stdout => std::io::stdout().is_terminal(),
+            Stream::Stderr => std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// Whether colors are used on this stream.
+    pub fn color(self) -> bool {
+        !COLOR_DISABLED.load(Ordering::Relaxed) && std::env::var_os("NO_COLOR").is_none() && self.is_terminal()
+    }
+
+    /// Width to wrap prose at, if the stream is a terminal.
+    pub fn width(self) -> Option<usize> {
+        if !self.is_terminal() {
+            return None;
+        }
+        let size = match self {
+            Stream::Stdout => terminal_size::terminal_size_of(std::io::stdout()),
+            Stream::Stderr => terminal_size::terminal_size_of(std::io::stderr()),
+        };
+        size.map(|(width, _)| usize::from(width.0)).filter(|w| *w >= 20)
+    }
+}
+
+/// Turns colors off for the whole process (`--no-color`).
+pub fn disable_color() {
+    COLOR_DISABLED.store(true, Ordering::Relaxed);
+}
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    &THEMES.get_or_init(ThemeSet::load_defaults).themes[THEME]
+}
+
+/// The syntax for a fence language (`rust`, `py`) or a file path's extension.
+fn find_syntax(language_or_path: &str) -> Option<&'static SyntaxReference> {
+    let set = syntaxes();
+    let token = language_or_path.rsplit('.').next().unwrap_or(language_or_path);
+    set.find_syntax_by_token(language_or_path.trim())
+        .or_else(|| set.find_syntax_by_extension(token))
+}
+
+/// Highlights `code` as `language_or_path` with terminal escapes. Unknown languages are
+/// returned unchanged.
+fn highlight_lines(code: &str, language_or_path: &str) -> String {
+    let Some(syntax) = find_syntax(language_or_path) else {
+        return code.to_string();
+    };
+    let mut highlighter = HighlightLines::new(syntax, theme());
+    let mut out = String::with_capacity(code.len() * 2);
+    for line in LinesWithEndings::from(code) {
+        match highlighter.highlight_line(line, syntaxes()) {
+            Ok(ranges) => out.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+            Err(_) => out.push_str(line),
+        }
+    }
+    out.push_str(RESET);
+    out
+}
+
+/// Highlights `code` for `stream`.
+pub fn highlight_code(code: &str, language_or_path: &str, stream: Stream) -> String {
+    if stream.color() {
+        highlight_lines(code, language_or_path)
+    } else {
+        code.to_string()
+    }
+}
+
+/// Highlights a unified diff for `stream`.
+pub fn highlight_diff(diff: &str, stream: Stream) -> String {
+    highlight_code(diff, "diff", stream)
+}
+
+/// The first `PREVIEW_LINES` lines of a file view (`<header>\n   1\t<code>...`, as the edit
+/// tool prints it) with the code highlighted as `path`.
+pub fn preview_file_view(view: &str, path: &str, stream: Stream) -> String {
+    render_file_view(view, path, stream.color())
+}
+
+fn render_file_view(view: &str, path: &str, color: bool) -> String {
+    let lines: Vec<&str> = view.lines().collect();
+    let Some((header, body)) = lines.split_first() else {
+        return String::new();
+    };
+    let shown = &body[..body.len().min(PREVIEW_LINES)];
+    let (numbers, code): (Vec<&str>, Vec<&str>) = shown
+        .iter()
+        .map(|line| line.split_once('\t').unwrap_or(("", line)))
+        .unzip();
+    let code = code.join("\n") + "\n";
+    let code = if color { highlight_lines(&code, path) } else { code };
+    let mut out = format!("{}\n", header);
+    for (number, line) in numbers.iter().zip(code.lines()) {
+        out.push_str(&format!("{}\t{}\n", number, line));
+    }
+    if color {
+        out.push_str(RESET);
+    }
+    if body.len() > shown.len() {
+        out.push_str(&format!("... ({} more lines)\n", body.len() - shown.len()));
+    }
+    out
+}
+
+/// Renders an assistant message for `stream`: fenced code blocks are highlighted and prose is
+/// wrapped to the terminal's width.
+pub fn render_markdown(text: &str, stream: Stream) -> String {
+    render_markdown_with(text, stream.color(), stream.width())
+}
+
+fn render_markdown_with(text: &str, color: bool, width: Option<usize>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut fence: Option<(String, String)> = None; // (language, code so far)
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match fence.as_mut() {
+            Some((language, code)) if trimmed.starts_with("```") => {
+                out.push_str(&if color { highlight_lines(code, language) } else { code.clone() });
+                out.push_str(line);
+                out.push('\n');
+                fence = None;
+            }
+            Some((_, code)) => {
+                code.push_str(line);
+                code.push('\n');
+            }
+            None if trimmed.starts_with("```") => {
+                out.push_str(line);
+                out.push('\n');
+                fence = Some((trimmed.trim_start_matches('`').trim().to_string(), String::new()));
+            }
+            None => {
+                match width {
+                    Some(width) => out.push_str(&wrap_line(line, width)),
+                    None => out.push_str(line),
+                }
+                out.push('\n');
+            }
+        }
+    }
+    // An unterminated block is still code.
+    if let Some((language, code)) = fence {
+        out.push_str(&if color { highlight_lines(&code, &language) } else { code });
+    }
+    if !text.ends_with('\n') && out.ends_with('\n') {
+        out.pop();
+    }
+    out
+}
+
+/// Wraps `line` at word boundaries to `width` columns, indenting continuation lines like the
+/// first (and past a list marker).
+fn wrap_line(line: &str, width: usize) -> String {
+    if line.chars().count() <= width {
+        return line.to_string();
+    }
+    let indent_len = line.len() - line.trim_start().len();
+    let rest = &line[indent_len..];
+    let marker_len = ["- ", "* "]
+        .iter()
+        .find(|m| rest.starts_with(*m))
+        .map_or(0, |m| m.len());
+    let indent = " ".repeat(indent_len + marker_len);
+
+    let mut out = String::new();
+    let mut current = line[..indent_len + marker_len].to_string();
+    let mut current_len = current.chars().count();
+    let mut empty = true;
+    for word in rest[marker_len..].split_whitespace() {
+        let word_len = word.chars().count();
+        if !empty && current_len + 1 + word_len > width {
+            out.push_str(&current);
+            out.push('\n');
+            current = indent.clone();
+            current_len = indent.len();
+            empty = true;
+        }
+        if !empty {
+            current.push(' ');
+            current_len += 1;
+        }
+        current.push_str(word);
+        current_len += word_len;
+        empty = false;
+    }
+    out.push_str(&current);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_blocks_highlighted_and_prose_wrapped() {
+        let message = "The fix changes the loop bound so the last page is included:\n```rust\nfor page in 0..=last {}\n```\nDone.";
+        let plain = render_markdown_with(message, false, Some(30));
+        assert_eq!(
+            plain,
+            "The fix changes the loop bound\nso the last page is included:\n```rust\nfor page in 0..=last {}\n```\nDone."
+        );
+        assert_eq!(render_markdown_with(message, false, None), message);
+
+        let colored = render_markdown_with(message, true, None);
+        assert!(colored.contains("\x1b[38;2;"), "{:?}", colored);
+        assert!(colored.contains("```rust\n"));
+        assert!(colored.ends_with("```\nDone."));
+
+        assert_eq!(wrap_line("  - one two three four", 12), "  - one two\n    three\n    four");
+
+        let view = "Here's the content of /repo/lib.py:\n     1\tdef a():\n     2\t    return 1";
+        assert_eq!(render_file_view(view, "/repo/lib.py", false), format!("{}\n", view));
+        let colored = render_file_view(view, "/repo/lib.py", true);
+        assert!(colored.starts_with("Here's the content of /repo/lib.py:\n     1\t\x1b["));
+        let long: String = (1..=30).map(|i| format!("\n{:6}\tline {}", i, i)).collect();
+        assert!(render_file_view(&format!("Header:{}", long), "a.txt", false).ends_with("... (10 more lines)\n"));
+    }
+}
@@ -0,0 +1,301 @@
+//! # HTTP Client Construction
+//!
+//! Builds `reqwest` clients that honour the proxy, CA bundle and timeout settings in
+//! `NetworkConfig`. Every component that talks HTTP should obtain its client here so
+//! that enterprise network settings apply uniformly.
+//!
+//! LLM clients share pooled clients (`shared_http_client`): every client built with the
+//! same network settings reuses one connection pool, so the many short Lakeview calls and
+//! concurrent runs keep their connections (and HTTP/2 sessions) alive instead of opening
+//! new ones. Requests sent through `send` are counted in `http_metrics`.
+
+use crate::config::NetworkConfig;
+use anyhow::{Context, Result};
+use reqwest::header::HeaderMap;
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy, RequestBuilder, Response};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Keep-alive probe interval used when `keep_alive_secs` is not set.
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Pooled clients, one per distinct network configuration.
+static POOLS: OnceLock<Mutex<HashMap<NetworkConfig, Client>>> = OnceLock::new();
+
+static METRICS: HttpCounters = HttpCounters::new();
+
+/// Counters behind `http_metrics`.
+struct HttpCounters {
+    pools_created: AtomicU64,
+    pool_reuses: AtomicU64,
+    requests: AtomicU64,
+    failed_requests: AtomicU64,
+    in_flight: AtomicU64,
+    total_wait_ms: AtomicU64,
+}
+
+impl HttpCounters {
+    const fn new() -> Self {
+        Self {
+            pools_created: AtomicU64::new(0),
+            pool_reuses: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            failed_requests: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            total_wait_ms: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A snapshot of the connection-pool and request counters of this process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HttpMetrics {
+    /// Connection pools created by `shared_http_client`.
+    pub pools_created: u64,
+    /// Times `shared_http_client` handed out an existing pool.
+    pub pool_reuses: u64,
+    /// Requests sent through `send`.
+    pub requests: u64,
+    /// Requests that failed without a response (connection errors, timeouts).
+    pub failed_requests: u64,
+    /// Requests currently waiting for their response headers.
+    pub in_flight: u64,
+    /// Total time spent waiting for response headers, in milliseconds.
+    pub total_wait_ms: u64,
+}
+
+impl fmt::Display for HttpMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} request(s), {} failed, over {} connection pool(s) reused {} time(s)",
+            self.requests, self.failed_requests, self.pools_created, self.pool_reuses
+        )?;
+        let answered = self.requests.saturating_sub(self.failed_requests + self.in_flight);
+        if let Some(average) = self.total_wait_ms.checked_div(answered) {
+            write!(f, "; {} ms average wait for response headers", average)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the current connection-pool and request counters.
+pub fn http_metrics() -> HttpMetrics {
+    HttpMetrics {
+        pools_created: METRICS.pools_created.load(Ordering::Relaxed),
+        pool_reuses: METRICS.pool_reuses.load(Ordering::Relaxed),
+        requests: METRICS.requests.load(Ordering::Relaxed),
+        failed_requests: METRICS.failed_requests.load(Ordering::Relaxed),
+        in_flight: METRICS.in_flight.load(Ordering::Relaxed),
+        total_wait_ms: METRICS.total_wait_ms.load(Ordering::Relaxed),
+    }
+}
+
+/// Returns the pooled client for `network`, building it on first use.
+///
+/// The client has no default headers; callers add their own (e.g., authorization) to each
+/// request, so that clients with different credentials can share the pool.
+pub fn shared_http_client(network: &NetworkConfig) -> Result<Client> {
+    let mut pools = POOLS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(client) = pools.get(network) {
+        METRICS.pool_reuses.fetch_add(1, Ordering::Relaxed);
+        return Ok(client.clone());
+    }
+    let client = build_http_client(network, HeaderMap::new())?;
+    METRICS.pools_created.fetch_add(1, Ordering::Relaxed);
+    pools.insert(network.clone(), client.clone());
+    Ok(client)
+}
+
+/// Sends `request`, recording it in `http_metrics`.
+pub async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    METRICS.requests.fetch_add(1, Ordering::Relaxed);
+    METRICS.in_flight.fetch_add(1, Ordering::Relaxed);
+    let started = Instant::now();
+    let result = request.send().await;
+    METRICS.in_flight.fetch_sub(1, Ordering::Relaxed);
+    match &result {
+        Ok(_) => {
+            let waited = started.elapsed().as_millis() as u64;
+            METRICS.total_wait_ms.fetch_add(waited, Ordering::Relaxed);
+        }
+        Err(_) => {
+            METRICS.failed_requests.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    result
+}
+
+/// Builds an HTTP client from `network` settings.
+///
+/// # Arguments
+/// * `network`: Proxy, CA bundle and timeout settings.
+/// * `default_headers`: Headers sent with every request (e.g., authorization).
+///
+/// # Returns
+/// The configured client, or an error if a proxy URL is invalid or the CA bundle cannot be
+/// read or parsed.
+pub fn build_http_client(network: &NetworkConfig, default_headers: HeaderMap) -> Result<Client> {
+    let mut builder = Client::builder().default_headers(default_headers);
+
+    let no_proxy = network.no_proxy.as_deref().and_then(NoProxy::from_string);
+    builder = with_proxy(builder, network.proxy.as_deref(), |u| Proxy::all(u), &no_proxy)?;
+    builder = with_proxy(builder, network.http_proxy.as_deref(), |u| Proxy::http(u), &no_proxy)?;
+    builder = with_proxy(builder, network.https_proxy.as_deref(), |u| Proxy::https(u), &no_proxy)?;
+
+    if let Some(ca_bundle) = &network.ca_bundle {
+        let pem = std::fs::read(ca_bundle)
+            .with_context(|| format!("Failed to read CA bundle: {}", ca_bundle))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Failed to parse CA bundle: {}", ca_bundle))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    if let Some(secs) = network.connect_timeout_secs {
+        builder = builder.connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = network.request_timeout_secs {
+        builder = builder.timeout(Duration::from_secs(secs));
+    }
+
+    if let Some(max_idle) = network.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(secs) = network.pool_idle_timeout_secs {
+        builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+    }
+    let keep_alive = network.keep_alive_secs.map_or(DEFAULT_KEEP_ALIVE, Duration::from_secs);
+    builder = builder
+        .tcp_keepalive(keep_alive)
+        .http2_keep_alive_interval(keep_alive)
+        .http2_keep_alive_while_idle(true);
+
+    builder.build().context("Failed to build HTTP client")
+}
+
+fn with_proxy(
+    builder: ClientBuilder,
+    url: Option<&str>,
+    make_proxy: impl Fn(&str) -> reqwest::Result<Proxy>,
+    no_proxy: &Option<NoProxy>,
+) -> Result<ClientBuilder> {
+    match url.filter(|u| !u.trim().is_empty()) {
+        Some(url) => {
+            let proxy = make_proxy(url).with_context(|| format!("Invalid proxy URL: {}", url))?;
+            Ok(builder.proxy(proxy.no_proxy(no_proxy.clone())))
+        }
+        None => Ok(builder),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_invalid_settings_are_reported() {
+        let network = NetworkConfig {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(build_http_client(&network, HeaderMap::new()).is_err());
+
+        let network = NetworkConfig {
+            ca_bundle: Some("/nonexistent/ca.pem".to_string()),
+            ..Default::default()
+        };
+        let err = build_http_client(&network, HeaderMap::new()).unwrap_err();
+        assert!(err.to_string().contains("Failed to read CA bundle"));
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_proxy() {
+        let proxy = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("via proxy"))
+            .mount(&proxy)
+            .await;
+
+        let network = NetworkConfig {
+            proxy: Some(proxy.uri()),
+            ..Default::default()
+        };
+        let client = build_http_client(&network, HeaderMap::new()).unwrap();
+        // The target host does not exist; only the proxy can answer.
+        let body = client
+            .get("http://llm-gateway.invalid/v1/models")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "via proxy");
+    }
+
+    #[tokio::test]
+    async fn test_shared_clients_reuse_one_pool_per_network_config() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        // Settings no other test uses, so this test owns the pool.
+        let network = NetworkConfig {
+            pool_idle_timeout_secs: Some(4242),
+            ..Default::default()
+        };
+        let before = http_metrics();
+        let first = shared_http_client(&network).unwrap();
+        let second = shared_http_client(&network).unwrap();
+        send(first.get(server.uri())).await.unwrap();
+        send(second.get(server.uri())).await.unwrap();
+        // Counters are process-wide, and other tests may run concurrently.
+        let after = http_metrics();
+        assert!(after.pools_created > before.pools_created);
+        assert!(after.pool_reuses > before.pool_reuses);
+        assert!(after.requests >= before.requests + 2);
+
+        let metrics = HttpMetrics {
+            pools_created: 1,
+            pool_reuses: 3,
+            requests: 4,
+            failed_requests: 1,
+            in_flight: 1,
+            total_wait_ms: 500,
+        };
+        assert_eq!(
+            metrics.to_string(),
+            "4 request(s), 1 failed, over 1 connection pool(s) reused 3 time(s); 250 ms average wait for response headers"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(3)))
+            .mount(&server)
+            .await;
+
+        let network = NetworkConfig {
+            request_timeout_secs: Some(1),
+            ..Default::default()
+        };
+        let client = build_http_client(&network, HeaderMap::new()).unwrap();
+        let err = client.get(server.uri()).send().await.unwrap_err();
+        assert!(err.is_timeout());
+    }
+}
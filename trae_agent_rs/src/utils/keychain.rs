@@ -0,0 +1,66 @@
+//! # Keychain
+//!
+//! Provider API keys kept in the OS credential store (macOS Keychain, Windows Credential
+//! Manager, the Secret Service on Linux) instead of in plaintext config files.
+//! `trae auth login <provider>` stores a key; `Config::load` falls back to it when neither
+//! the command line, the config file nor the environment provides one.
+
+use anyhow::{Context, Result};
+use keyring::Entry;
+use tracing::debug;
+
+/// Service name the keys are stored under; the account is the provider name.
+pub const KEYCHAIN_SERVICE: &str = "trae-agent";
+/// When set (to anything), the keychain is never consulted.
+pub const NO_KEYCHAIN_ENV: &str = "TRAE_NO_KEYCHAIN";
+
+fn entry(provider: &str) -> keyring::Result<Entry> {
+    Entry::new(KEYCHAIN_SERVICE, provider)
+}
+
+fn disabled() -> bool {
+    std::env::var_os(NO_KEYCHAIN_ENV).is_some()
+}
+
+/// Stores `api_key` for `provider`, replacing any key stored before.
+pub fn store_api_key(provider: &str, api_key: &str) -> Result<()> {
+    entry(provider)
+        .and_then(|entry| entry.set_password(api_key))
+        .with_context(|| format!("Failed to store the API key for '{}' in the OS keychain", provider))
+}
+
+/// Looks up the key stored for `provider`.
+///
+/// # Returns
+/// `Ok(None)` if no key is stored or the keychain is disabled, an error if the keychain
+/// cannot be reached.
+pub fn lookup_api_key(provider: &str) -> Result<Option<String>> {
+    if disabled() {
+        return Ok(None);
+    }
+    match entry(provider).and_then(|entry| entry.get_password()) {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read the API key for '{}' from the OS keychain", provider)),
+    }
+}
+
+/// The key stored for `provider`, if any; an unreachable keychain counts as no key.
+pub fn api_key(provider: &str) -> Option<String> {
+    lookup_api_key(provider).unwrap_or_else(|e| {
+        debug!("{:#}", e);
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_key_is_none() {
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        assert_eq!(lookup_api_key("no-such-provider").unwrap(), None);
+        assert!(store_api_key("openai", "sk-test").is_ok());
+    }
+}
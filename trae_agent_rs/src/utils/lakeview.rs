@@ -16,11 +16,14 @@ use crate::llm::{
 // Removed LLMResponse, LLMResponseChoice, LLMUsage as they are unused in this file.
 use crate::llm::LLMClient;
 
-use std::collections::HashMap;
+use crate::config::{LakeviewConfig, LakeviewTag};
+use anyhow::Context;
+use std::path::Path;
 use std::sync::Arc;
 use tracing::{info, warn}; // Removed error
                            // use serde_json::Value; // Not directly used here now, but LLM response might be Value
 
+#[cfg(test)]
 const MAX_SUMMARY_TOKENS: u32 = 500; // Restored for test usage
 const MAX_LAKEVIEW_RETRIES: u32 = 3;
 
@@ -46,14 +49,7 @@ Output your answer by choosing the applicable tags in the below list for the cur
 If it is performing multiple tasks in one step, choose ALL applicable tags, separated by a comma.
 
 <tags>
-WRITE_TEST: It writes a test script to reproduce the bug, or modifies a non-working test script to fix problems found in testing.
-VERIFY_TEST: It runs the reproduction test script to verify the testing environment is working.
-EXAMINE_CODE: It views, searches, or explores the code repository to understand the cause of the bug.
-WRITE_FIX: It modifies the source code to fix the identified bug.
-VERIFY_FIX: It runs the reproduction test or existing tests to verify the fix indeed solves the bug.
-REPORT: It reports to the user that the job is completed or some progress has been made.
-THINK: It analyzes the bug through thinking, but does not perform concrete actions right now.
-OUTLIER: A major part in this step does not fit into any tag above, such as running a shell command to install dependencies.
+{tags}
 </tags>
 
 <examples>
@@ -65,17 +61,91 @@ If the agent is merely thinking about the root cause of the bug without other ac
 Output only the tags with no other commentary. The format should be <tags>...</tags>
 "#;
 
-fn get_known_tags() -> HashMap<&'static str, &'static str> {
-    let mut tags = HashMap::new();
-    tags.insert("WRITE_TEST", "☑️");
-    tags.insert("VERIFY_TEST", "✅");
-    tags.insert("EXAMINE_CODE", "👁️");
-    tags.insert("WRITE_FIX", "📝");
-    tags.insert("VERIFY_FIX", "🔥");
-    tags.insert("REPORT", "📣");
-    tags.insert("THINK", "🧠");
-    tags.insert("OUTLIER", "⁉️");
-    tags
+/// Built-in step tags: name, emoji and description.
+const DEFAULT_TAGS: &[(&str, &str, &str)] = &[
+    ("WRITE_TEST", "☑️", "It writes a test script to reproduce the bug, or modifies a non-working test script to fix problems found in testing."),
+    ("VERIFY_TEST", "✅", "It runs the reproduction test script to verify the testing environment is working."),
+    ("EXAMINE_CODE", "👁️", "It views, searches, or explores the code repository to understand the cause of the bug."),
+    ("WRITE_FIX", "📝", "It modifies the source code to fix the identified bug."),
+    ("VERIFY_FIX", "🔥", "It runs the reproduction test or existing tests to verify the fix indeed solves the bug."),
+    ("REPORT", "📣", "It reports to the user that the job is completed or some progress has been made."),
+    ("THINK", "🧠", "It analyzes the bug through thinking, but does not perform concrete actions right now."),
+    ("OUTLIER", "⁉️", "A major part in this step does not fit into any tag above, such as running a shell command to install dependencies."),
+];
+
+/// The step tags and prompts Lakeview uses: the built-in ones, or those of `lakeview_config`.
+#[derive(Debug, Clone)]
+pub struct LakeviewSettings {
+    tags: Vec<LakeviewTag>,
+    extractor_prompt: String,
+    tagger_prompt: String,
+}
+
+impl Default for LakeviewSettings {
+    fn default() -> Self {
+        Self {
+            tags: DEFAULT_TAGS
+                .iter()
+                .map(|(name, emoji, description)| LakeviewTag {
+                    name: name.to_string(),
+                    emoji: emoji.to_string(),
+                    description: description.to_string(),
+                })
+                .collect(),
+            extractor_prompt: EXTRACTOR_PROMPT.to_string(),
+            tagger_prompt: TAGGER_PROMPT.to_string(),
+        }
+    }
+}
+
+impl LakeviewSettings {
+    /// Applies the tags and prompt templates of `config` to the built-in settings.
+    pub fn from_config(config: &LakeviewConfig) -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+        if config.replace_default_tags {
+            settings.tags.clear();
+        }
+        for tag in &config.tags {
+            let name = tag.name.trim().to_uppercase();
+            if name.is_empty() || name.contains(',') || name.contains(char::is_whitespace) {
+                anyhow::bail!("Invalid Lakeview tag name '{}': use a single word such as MIGRATION", tag.name);
+            }
+            let tag = LakeviewTag { name, ..tag.clone() };
+            match settings.tags.iter_mut().find(|known| known.name == tag.name) {
+                Some(known) => *known = tag,
+                None => settings.tags.push(tag),
+            }
+        }
+        if settings.tags.is_empty() {
+            anyhow::bail!("Lakeview has no step tags: replace_default_tags is set but no tags are configured");
+        }
+        if let Some(path) = &config.extractor_prompt_file {
+            settings.extractor_prompt = read_template(path)?;
+        }
+        if let Some(path) = &config.tagger_prompt_file {
+            settings.tagger_prompt = read_template(path)?;
+        }
+        Ok(settings)
+    }
+
+    fn emoji(&self, tag: &str) -> Option<&str> {
+        self.tags.iter().find(|known| known.name == tag).map(|known| known.emoji.as_str())
+    }
+
+    /// The tagger prompt, with the tag list filled in.
+    fn tagger_prompt(&self) -> String {
+        let tags = self
+            .tags
+            .iter()
+            .map(|tag| format!("{}: {}", tag.name, tag.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.tagger_prompt.replace("{tags}", &tags)
+    }
+}
+
+fn read_template(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read Lakeview prompt template {}", path.display()))
 }
 
 #[derive(Debug, Clone)]
@@ -84,6 +154,18 @@ pub struct LakeViewStepInfo {
     pub desc_task: String,
     pub desc_details: String,
     pub tags_emoji: String,
+    /// One-line diffstat of the files the step changed; empty when it changed none.
+    pub changes: String,
+}
+
+// Lists the files a step changed, e.g. "modified src/parser.rs +24/-3, created notes.md +5/-0"
+fn format_step_changes(agent_step: &crate::agent::base_agent::AgentStep) -> String {
+    agent_step
+        .file_changes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 // Helper to format an AgentStep into a string representation for Lakeview LLM calls
@@ -122,14 +204,23 @@ fn format_agent_step_for_lakeview(
 async fn extract_task_in_step(
     llm_client: Arc<dyn LLMClient>,
     model_params: &LLMModelParameters,
+    settings: &LakeviewSettings,
     prev_step_str: &str,
     current_step_str: &str,
+    output_language: Option<&str>,
 ) -> Result<(String, String), LLMError> {
-    let prompt = format!(
+    let mut prompt = format!(
         "The following is an excerpt of the steps trying to solve a software bug by an AI agent: \
         <previous_step>{}</previous_step><this_step>{}</this_step>\n\n{}",
-        prev_step_str, current_step_str, EXTRACTOR_PROMPT
+        prev_step_str, current_step_str, settings.extractor_prompt
     );
+    if let Some(language) = output_language {
+        // Only the free text is localized; the XML tags must stay as-is for parsing.
+        prompt.push_str(&format!(
+            "\n\nWrite the text inside <task> and <details> in {}, keeping the tags themselves unchanged.",
+            language
+        ));
+    }
 
     let messages = vec![
         LLMMessage {
@@ -221,12 +312,13 @@ async fn extract_task_in_step(
 async fn extract_tags_in_step(
     llm_client: Arc<dyn LLMClient>,
     model_params: &LLMModelParameters,
+    settings: &LakeviewSettings,
     trajectory_so_far_str: &str,
     current_step_str: &str,
 ) -> Result<Vec<String>, LLMError> {
     let prompt = format!(
         "Below is the trajectory of an AI agent solving a software bug until the current step. Each step is marked within a <step> tag.\n\n{}\n\n<current_step>{}</current_step>\n\n{}",
-        trajectory_so_far_str, current_step_str, TAGGER_PROMPT
+        trajectory_so_far_str, current_step_str, settings.tagger_prompt()
     );
 
     let messages = vec![
@@ -248,7 +340,6 @@ async fn extract_tags_in_step(
 
     let mut specific_params = model_params.clone();
     specific_params.temperature = 0.1;
-    let known_tags_map = get_known_tags();
 
     for _attempt in 0..MAX_LAKEVIEW_RETRIES {
         // See TODO in extract_task_in_step about model_params per call
@@ -276,7 +367,7 @@ async fn extract_tags_in_step(
                 if !parsed_tags.is_empty()
                     && parsed_tags
                         .iter()
-                        .all(|tag| known_tags_map.contains_key(tag.as_str()))
+                        .all(|tag| settings.emoji(tag).is_some())
                 {
                     return Ok(parsed_tags);
                 } else if !parsed_tags.is_empty() {
@@ -312,6 +403,8 @@ async fn extract_tags_in_step(
 /// * `agent_execution`: A reference to the `AgentExecution` struct.
 /// * `llm_client`: An `Arc<dyn LLMClient>` for making LLM calls for extraction/tagging.
 /// * `summary_model_params`: `ModelParameters` for the LLM calls made by Lakeview functions.
+/// * `settings`: The step tags and prompts to use.
+/// * `output_language`: Optional language for the step descriptions (tags are always fixed).
 ///
 /// # Returns
 /// A `Result` containing the structured summary string or an `AgentError`.
@@ -319,6 +412,8 @@ pub async fn generate_summary(
     agent_execution: &AgentExecution,
     llm_client: Arc<dyn LLMClient>,
     summary_model_params: &LLMModelParameters,
+    settings: &LakeviewSettings,
+    output_language: Option<&str>,
 ) -> Result<String, AgentError> {
     info!(
         "Generating enhanced Lakeview summary for task: {}",
@@ -332,7 +427,6 @@ pub async fn generate_summary(
 
     let mut lakeview_steps_info: Vec<LakeViewStepInfo> = Vec::new();
     let mut trajectory_so_far_buffer = String::new();
-    let known_tags_map = get_known_tags();
 
     for (i, step) in agent_execution.steps.iter().enumerate() {
         let prev_step_str = if i > 0 {
@@ -350,6 +444,7 @@ pub async fn generate_summary(
                 desc_task: "Internal or empty step".to_string(),
                 desc_details: "".to_string(),
                 tags_emoji: "".to_string(),
+                changes: format_step_changes(step),
             });
             trajectory_so_far_buffer.push_str(&format!(
                 "<step id=\"{}\">\n{}\n</step>\n\n",
@@ -361,8 +456,10 @@ pub async fn generate_summary(
         let task_details_result = extract_task_in_step(
             llm_client.clone(),
             summary_model_params,
+            settings,
             &prev_step_str,
             &current_step_str,
+            output_language,
         )
         .await;
         let (desc_task, desc_details) = match task_details_result {
@@ -382,6 +479,7 @@ pub async fn generate_summary(
         let tags_result = extract_tags_in_step(
             llm_client.clone(),
             summary_model_params,
+            settings,
             &trajectory_so_far_buffer,
             &current_step_str,
         )
@@ -389,7 +487,7 @@ pub async fn generate_summary(
         let tags_emoji = match tags_result {
             Ok(tags) => tags
                 .iter()
-                .map(|tag| known_tags_map.get(tag.as_str()).copied().unwrap_or("❓"))
+                .map(|tag| settings.emoji(tag).unwrap_or("❓"))
                 .collect::<Vec<&str>>()
                 .join(" "),
             Err(e) => {
@@ -406,6 +504,7 @@ pub async fn generate_summary(
             desc_task,
             desc_details,
             tags_emoji,
+            changes: format_step_changes(step),
         });
         trajectory_so_far_buffer.push_str(&format!(
             "<step id=\"{}\">\n{}\n</step>\n\n",
@@ -421,6 +520,9 @@ pub async fn generate_summary(
             "Step {}: {} {}\n  Details: {}\n",
             lv_step.step_number, lv_step.tags_emoji, lv_step.desc_task, lv_step.desc_details
         ));
+        if !lv_step.changes.is_empty() {
+            final_summary.push_str(&format!("  Changes: {}\n", lv_step.changes));
+        }
     }
     final_summary.push_str("------------------------------------\n");
     final_summary.push_str(&format!(
@@ -449,7 +551,7 @@ mod tests {
     use crate::llm::OpenAIClient;
 
     use serde_json::json;
-    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::matchers::{body_partial_json, body_string_contains, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate}; // Removed Times
 
     fn create_dummy_execution() -> AgentExecution {
@@ -494,12 +596,19 @@ mod tests {
                             finish_reason: Some("tool_calls".to_string()),
                         }],
                         usage: None,
+                        rate_limit: None,
                     }),
                     tool_calls_made: None,
                     tool_results: None,
                     reflection: None,
                     error: None,
                     duration_ms: 100,
+                    route: None,
+                    validation_error: None,
+                    diff_stat: None,
+                    file_changes: Vec::new(),
+                    llm_duration_ms: None,
+                    step_extensions: Vec::new(),
                 },
                 AgentStep {
                     // Add a second step for more comprehensive summary testing
@@ -523,6 +632,7 @@ mod tests {
                             finish_reason: Some("stop".to_string()),
                         }],
                         usage: None,
+                        rate_limit: None,
                     }),
                     tool_calls_made: None,
                     tool_results: Some(vec![crate::tools::AgentToolResult {
@@ -530,10 +640,18 @@ mod tests {
                         success: true,
                         result: Some("Tool output".to_string()),
                         error: None,
+                        duration_ms: None,
+                        approval: None,
                     }]),
                     reflection: None,
                     error: None,
                     duration_ms: 50,
+                    route: None,
+                    validation_error: None,
+                    diff_stat: None,
+                    file_changes: Vec::new(),
+                    llm_duration_ms: None,
+                    step_extensions: Vec::new(),
                 },
             ],
             final_result: Some("Task done.".to_string()),
@@ -544,6 +662,9 @@ mod tests {
                 total_tokens: 50,
             }),
             error_message: None,
+            error_hint: None,
+            subagents: Vec::new(),
+            final_report: None,
         }
     }
 
@@ -561,6 +682,12 @@ mod tests {
             api_version: None,
             candidate_count: None,
             stop_sequences: None,
+            seed: None,
+            extra_headers: None,
+            requests_per_minute: None,
+            tokens_per_minute: None,
+            openrouter: None,
+            network: Default::default(),
         }
     }
 
@@ -600,7 +727,7 @@ mod tests {
         // current_step_str will be step1_current_step_str_formatted_for_mock.
         let step1_tag_extraction_prompt_for_mock = format!(
             "Below is the trajectory of an AI agent solving a software bug until the current step. Each step is marked within a <step> tag.\n\n\n\n<current_step>{}</current_step>\n\n{}",
-            step1_current_step_str_formatted_for_mock, TAGGER_PROMPT
+            step1_current_step_str_formatted_for_mock, LakeviewSettings::default().tagger_prompt()
         );
         Mock::given(method("POST")).and(path("/chat/completions"))
              .and(body_partial_json(json!({
@@ -649,7 +776,7 @@ mod tests {
         );
         let step2_tag_extraction_prompt_for_mock = format!(
             "Below is the trajectory of an AI agent solving a software bug until the current step. Each step is marked within a <step> tag.\n\n{}\n\n<current_step>{}</current_step>\n\n{}",
-            step2_trajectory_so_far_for_mock, step2_current_step_str_for_mock, TAGGER_PROMPT
+            step2_trajectory_so_far_for_mock, step2_current_step_str_for_mock, LakeviewSettings::default().tagger_prompt()
         );
         Mock::given(method("POST")).and(path("/chat/completions"))
             .and(body_partial_json(json!({
@@ -679,7 +806,7 @@ mod tests {
             .unwrap(),
         );
 
-        let summary_result = generate_summary(&exec, llm_client, &model_params).await;
+        let summary_result = generate_summary(&exec, llm_client, &model_params, &LakeviewSettings::default(), None).await;
         assert!(
             summary_result.is_ok(),
             "generate_summary failed: {:?}",
@@ -697,6 +824,52 @@ mod tests {
         assert!(summary.contains("Final Agent Message: Task done."));
     }
 
+    /// An execution whose only step changed files and has nothing for the LLM to summarize.
+    fn create_execution_with_file_changes() -> AgentExecution {
+        use crate::utils::git_utils::{FileChange, FileChangeKind};
+
+        let mut exec = create_dummy_execution();
+        let mut step = exec.steps.remove(0);
+        step.llm_response = None;
+        step.file_changes = vec![
+            FileChange {
+                path: "src/parser.rs".to_string(),
+                kind: FileChangeKind::Modified,
+                insertions: 24,
+                deletions: 3,
+            },
+            FileChange {
+                path: "notes.md".to_string(),
+                kind: FileChangeKind::Created,
+                insertions: 5,
+                deletions: 0,
+            },
+        ];
+        exec.steps = vec![step];
+        exec
+    }
+
+    #[tokio::test]
+    async fn test_generate_summary_lists_step_changes() {
+        let exec = create_execution_with_file_changes();
+        let model_params = get_lakeview_model_params();
+        let server = MockServer::start().await;
+        let llm_client = Arc::new(
+            OpenAIClient::new(Some("dummykey".to_string()), Some(server.uri()), model_params.clone())
+                .await
+                .unwrap(),
+        );
+        let summary = generate_summary(&exec, llm_client, &model_params, &LakeviewSettings::default(), None)
+            .await
+            .unwrap();
+        assert!(
+            summary.contains("  Changes: modified src/parser.rs +24/-3, created notes.md +5/-0\n"),
+            "{}",
+            summary
+        );
+        assert_eq!(summary.matches("Changes:").count(), 1);
+    }
+
     #[tokio::test]
     async fn test_generate_summary_empty_steps() {
         let mut exec = create_dummy_execution();
@@ -712,7 +885,7 @@ mod tests {
             .await
             .unwrap(),
         );
-        let result = generate_summary(&exec, llm_client_for_empty_test, &model_params).await;
+        let result = generate_summary(&exec, llm_client_for_empty_test, &model_params, &LakeviewSettings::default(), None).await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "No actions taken by the agent.");
     }
@@ -742,8 +915,10 @@ mod tests {
         let result = extract_task_in_step(
             llm_client,
             &model_params,
+            &LakeviewSettings::default(),
             "Previous step info",
             "Current step info",
+            None,
         )
         .await;
         assert!(result.is_ok());
@@ -755,6 +930,41 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_extract_task_in_step_requests_output_language() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).and(path("/chat/completions"))
+            .and(body_string_contains("Write the text inside <task> and <details> in Chinese"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "extract_resp", "object": "chat.completion", "created": 124, "model": "gpt-test",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "正在查看代码。</task><details>查看 file.py。</details>"}, "finish_reason": "stop"}]
+            })))
+            .mount(&server).await;
+
+        let model_params = get_lakeview_model_params();
+        let llm_client = Arc::new(
+            OpenAIClient::new(
+                model_params.api_key.clone(),
+                Some(server.uri()),
+                model_params.clone(),
+            )
+            .await
+            .unwrap(),
+        );
+
+        let (_, details) = extract_task_in_step(
+            llm_client,
+            &model_params,
+            &LakeviewSettings::default(),
+            "Previous step info",
+            "Current step info",
+            Some("Chinese"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(details, "查看 file.py。");
+    }
+
     #[tokio::test]
     async fn test_extract_tags_in_step_parsing() {
         let server = MockServer::start().await;
@@ -780,6 +990,7 @@ mod tests {
         let result = extract_tags_in_step(
             llm_client,
             &model_params,
+            &LakeviewSettings::default(),
             "Trajectory so far...",
             "Current step info",
         )
@@ -792,4 +1003,62 @@ mod tests {
         let tags = result.unwrap();
         assert_eq!(tags, vec!["EXAMINE_CODE".to_string(), "THINK".to_string()]);
     }
+
+    #[tokio::test]
+    async fn test_configured_tags_and_tagger_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("tagger.txt");
+        std::fs::write(&template, "Pick the tags of the current step.\n<tags>\n{tags}\n</tags>\n").unwrap();
+        let config = LakeviewConfig {
+            model_provider: "openai".to_string(),
+            model_name: "gpt-test".to_string(),
+            tags: vec![
+                LakeviewTag {
+                    name: "migration".to_string(),
+                    emoji: "🗄️".to_string(),
+                    description: "It writes or runs a database migration.".to_string(),
+                },
+                LakeviewTag {
+                    name: "THINK".to_string(),
+                    emoji: "💭".to_string(),
+                    description: "It reasons without acting.".to_string(),
+                },
+            ],
+            replace_default_tags: false,
+            extractor_prompt_file: None,
+            tagger_prompt_file: Some(template),
+        };
+        let settings = LakeviewSettings::from_config(&config).unwrap();
+        assert_eq!(settings.emoji("MIGRATION"), Some("🗄️"));
+        assert_eq!(settings.emoji("THINK"), Some("💭"));
+        assert_eq!(settings.emoji("WRITE_FIX"), Some("📝"));
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST")).and(path("/chat/completions"))
+            .and(body_string_contains("Pick the tags of the current step."))
+            .and(body_string_contains("MIGRATION: It writes or runs a database migration."))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "tags_resp", "object": "chat.completion", "created": 124, "model": "gpt-test",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "MIGRATION,THINK</tags>"}, "finish_reason": "stop"}]
+            })))
+            .mount(&server).await;
+        let model_params = get_lakeview_model_params();
+        let llm_client = Arc::new(
+            OpenAIClient::new(model_params.api_key.clone(), Some(server.uri()), model_params.clone())
+                .await
+                .unwrap(),
+        );
+        let tags = extract_tags_in_step(llm_client, &model_params, &settings, "", "Current step info")
+            .await
+            .unwrap();
+        assert_eq!(tags, vec!["MIGRATION".to_string(), "THINK".to_string()]);
+
+        let config = LakeviewConfig {
+            tags: Vec::new(),
+            replace_default_tags: true,
+            tagger_prompt_file: None,
+            ..config
+        };
+        assert!(LakeviewSettings::from_config(&config).is_err());
+    }
 }
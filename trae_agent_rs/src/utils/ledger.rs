@@ -0,0 +1,172 @@
+//! # Usage Ledger
+//!
+//! Every `trae run` appends one line to a JSON-lines ledger: when it ran, the model, whether it
+//! succeeded, its steps, tokens and estimated cost, and its labels (`--label team=infra`).
+//! Several users can share one ledger (`TRAE_USAGE_LEDGER` pointing at a shared file), and
+//! `trae runs list --label ...` filters it for cost and outcome reporting per team or project.
+
+use crate::agent::base_agent::AgentExecution;
+use crate::utils::usage::UsageTracker;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the ledger location.
+pub const LEDGER_ENV: &str = "TRAE_USAGE_LEDGER";
+
+/// One run in the ledger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Unix timestamp of the start of the run.
+    pub timestamp: u64,
+    pub task: String,
+    pub provider: String,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    pub success: bool,
+    pub steps: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated cost in US dollars, if the model's price is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trajectory: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl LedgerEntry {
+    /// The ledger entry of a finished run.
+    pub fn for_run(execution: &AgentExecution, provider: &str, model: &str, labels: BTreeMap<String, String>) -> Self {
+        let mut usage = UsageTracker::new();
+        if let Some(tokens) = &execution.total_tokens_used {
+            usage.record(model, tokens);
+        }
+        let total = usage.total();
+        Self {
+            timestamp: execution.start_time,
+            task: execution.task.clone(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            project: None,
+            success: execution.success,
+            steps: execution.steps.len(),
+            prompt_tokens: total.prompt_tokens,
+            completion_tokens: total.completion_tokens,
+            cost: total.cost,
+            trajectory: None,
+            labels,
+        }
+    }
+
+    /// Whether the entry has every label in `filters`.
+    pub fn has_labels(&self, filters: &[(String, String)]) -> bool {
+        filters
+            .iter()
+            .all(|(key, value)| self.labels.get(key).is_some_and(|v| v == value))
+    }
+}
+
+/// The ledger location: `TRAE_USAGE_LEDGER`, else `~/.trae/usage_ledger.jsonl`.
+pub fn default_ledger_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(LEDGER_ENV).filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let home = std::env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".trae").join("usage_ledger.jsonl"))
+}
+
+/// Appends `entry` to the ledger at `path`, creating it if needed.
+pub fn append(path: &Path, entry: &LedgerEntry) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    // One write per entry, so concurrent runs appending to a shared ledger do not interleave.
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open usage ledger {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write usage ledger {}", path.display()))
+}
+
+/// Reads the ledger at `path`; a missing ledger is empty. Lines that cannot be parsed are
+/// skipped with a warning.
+pub fn read(path: &Path) -> Result<Vec<LedgerEntry>> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read usage ledger {}", path.display())),
+    };
+    Ok(text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                tracing::warn!("Skipping line {} of {}: {}", i + 1, path.display(), e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Parses a `key=value` label.
+pub fn parse_label(label: &str) -> Result<(String, String), String> {
+    match label.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.trim().to_string())),
+        _ => Err(format!("Invalid label '{}': expected key=value", label)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(model: &str, success: bool, labels: &[(&str, &str)]) -> LedgerEntry {
+        LedgerEntry {
+            timestamp: 1_700_000_000,
+            task: "Fix the parser".to_string(),
+            provider: "openai".to_string(),
+            model: model.to_string(),
+            project: None,
+            success,
+            steps: 4,
+            prompt_tokens: 1000,
+            completion_tokens: 100,
+            cost: None,
+            trajectory: None,
+            labels: labels.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_ledger_append_read_and_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shared/ledger.jsonl");
+        assert!(read(&path).unwrap().is_empty());
+        append(&path, &entry("gpt-4o", true, &[("team", "infra"), ("ticket", "OPS-12")])).unwrap();
+        append(&path, &entry("gpt-4o-mini", false, &[("team", "web")])).unwrap();
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"not json\n").unwrap();
+
+        let entries = read(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        let infra = vec![parse_label("team=infra").unwrap()];
+        let matching: Vec<_> = entries.iter().filter(|e| e.has_labels(&infra)).collect();
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].labels["ticket"], "OPS-12");
+        assert!(entries.iter().all(|e| e.has_labels(&[])));
+
+        assert_eq!(parse_label(" team = infra ").unwrap(), ("team".to_string(), "infra".to_string()));
+        assert!(parse_label("team").is_err());
+        assert!(parse_label("=infra").is_err());
+    }
+}
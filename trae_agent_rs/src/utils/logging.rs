@@ -0,0 +1,123 @@
+//! # Logging
+//!
+//! Sets up diagnostics output. Agent-facing output (task results, summaries, JSON reports)
+//! is written to stdout by the command handlers; everything produced through `tracing`, as
+//! well as progress notices, goes to stderr so stdout can be piped safely.
+//!
+//! Console verbosity is controlled by `--quiet`/`--verbose` (or `RUST_LOG`, which takes
+//! precedence). With `--log-file`, a daily-rotated file additionally receives detailed logs
+//! regardless of the console level.
+
+use anyhow::{Context, Result};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+/// Filter used for the log file when `RUST_LOG` is not set.
+const FILE_LOG_FILTER: &str = "info,trae_rust_agent=debug";
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Logging options collected from the global command-line flags.
+#[derive(Debug, Clone, Default)]
+pub struct LogOptions {
+    /// Only report errors on stderr and suppress progress notices.
+    pub quiet: bool,
+    /// Number of `--verbose` flags given (0 = warnings, 1 = info, 2 = debug, 3+ = trace).
+    pub verbose: u8,
+    /// Optional log file; rotated daily, with the date appended to the file name.
+    pub log_file: Option<String>,
+    /// Never use colors on the console (`--no-color`).
+    pub no_color: bool,
+}
+
+/// Returns the console filter directive for the given options.
+pub fn console_filter_directive(options: &LogOptions) -> &'static str {
+    if options.quiet {
+        return "error";
+    }
+    match options.verbose {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    }
+}
+
+/// Whether progress notices should be suppressed (`--quiet`).
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Installs the global tracing subscriber.
+///
+/// # Arguments
+/// * `options`: The logging options from the command line.
+///
+/// # Returns
+/// A guard that must be kept alive for the duration of the program when logging to a file,
+/// so buffered lines are flushed on exit.
+pub fn init(options: &LogOptions) -> Result<Option<WorkerGuard>> {
+    QUIET.store(options.quiet, Ordering::Relaxed);
+
+    let console_filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(console_filter_directive(options)));
+    let console_layer = fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(!options.no_color && std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal())
+        .with_filter(console_filter);
+
+    let (file_layer, guard) = match &options.log_file {
+        Some(log_file) => {
+            let path = Path::new(log_file);
+            let file_name = path
+                .file_name()
+                .with_context(|| format!("Invalid log file path: {}", log_file))?;
+            let directory = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            std::fs::create_dir_all(directory).with_context(|| {
+                format!("Failed to create log directory: {}", directory.display())
+            })?;
+            let appender = tracing_appender::rolling::daily(directory, file_name);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let file_filter = EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new(FILE_LOG_FILTER));
+            let layer = fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false)
+                .with_filter(file_filter);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(console_layer)
+        .with(file_layer)
+        .try_init()
+        .context("Failed to initialize logging")?;
+    Ok(guard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_console_filter_directive() {
+        let mut options = LogOptions::default();
+        assert_eq!(console_filter_directive(&options), "warn");
+        options.verbose = 2;
+        assert_eq!(console_filter_directive(&options), "debug");
+        options.verbose = 7;
+        assert_eq!(console_filter_directive(&options), "trace");
+        options.quiet = true;
+        assert_eq!(console_filter_directive(&options), "error");
+    }
+}
@@ -0,0 +1,441 @@
+//! # Minimal LSP Client
+//!
+//! A small JSON-RPC-over-stdio client for language servers, sufficient for issuing
+//! `textDocument/rename` requests and applying the resulting `WorkspaceEdit` to disk.
+//! It is not a general purpose LSP implementation: server-initiated requests are
+//! acknowledged with a `null` result and notifications are ignored.
+//! `LspLauncher` lets a `Supervisor` restart a server that crashes.
+
+use crate::utils::supervisor::{BackendHandle, BackendLauncher};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tracing::{debug, warn};
+
+/// How long to wait for a single response from the language server.
+const LSP_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A single location changed by an edit, reported 1-based for display.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EditSite {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Picks a language server for the project based on its marker files, if one is installed.
+///
+/// # Returns
+/// The server command and its arguments, or `None` if no suitable server is on `PATH`.
+pub fn detect_language_server(project_root: &Path) -> Option<(String, Vec<String>)> {
+    let candidates: [(&str, &str, &[&str]); 4] = [
+        ("Cargo.toml", "rust-analyzer", &[]),
+        ("pyproject.toml", "pylsp", &[]),
+        ("setup.py", "pylsp", &[]),
+        ("package.json", "typescript-language-server", &["--stdio"]),
+    ];
+    candidates
+        .iter()
+        .find(|(marker, server, _)| project_root.join(marker).exists() && is_on_path(server))
+        .map(|(_, server, args)| {
+            (
+                server.to_string(),
+                args.iter().map(|a| a.to_string()).collect(),
+            )
+        })
+}
+
+fn is_on_path(binary: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(binary).is_file()))
+        .unwrap_or(false)
+}
+
+/// Converts an absolute path to a `file://` URI.
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Converts a `file://` URI back to a path, decoding percent escapes.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(decoded).ok()?))
+}
+
+/// Encodes a JSON-RPC message with the LSP `Content-Length` header.
+pub fn encode_message(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    let mut bytes = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    bytes.extend_from_slice(body.as_bytes());
+    bytes
+}
+
+/// Reads one `Content-Length` framed JSON-RPC message.
+pub async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Value> {
+    let mut content_length: Option<usize> = None;
+    loop {
+        let mut header = String::new();
+        let read = reader.read_line(&mut header).await?;
+        if read == 0 {
+            return Err(anyhow::anyhow!("Language server closed its output"));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(len) = header.strip_prefix("Content-Length:") {
+            content_length = Some(len.trim().parse().context("Invalid Content-Length header")?);
+        }
+    }
+    let len = content_length.ok_or_else(|| anyhow::anyhow!("Missing Content-Length header"))?;
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).await?;
+    serde_json::from_slice(&body).context("Language server sent invalid JSON")
+}
+
+/// Converts an LSP position (0-based line, UTF-16 character) into a byte offset in `text`.
+fn position_to_offset(text: &str, position: &Value) -> Option<usize> {
+    let line = position.get("line")?.as_u64()? as usize;
+    let character = position.get("character")?.as_u64()? as usize;
+    let mut offset = 0;
+    for (index, line_text) in text.split_inclusive('\n').enumerate() {
+        if index == line {
+            let mut utf16_count = 0;
+            for (byte_index, ch) in line_text.char_indices() {
+                if utf16_count >= character {
+                    return Some(offset + byte_index);
+                }
+                utf16_count += ch.len_utf16();
+            }
+            return Some(offset + line_text.trim_end_matches('\n').len());
+        }
+        offset += line_text.len();
+    }
+    (line == text.split_inclusive('\n').count()).then_some(text.len())
+}
+
+/// Applies the text edits of an LSP `WorkspaceEdit` to files on disk.
+///
+/// Both the `changes` and `documentChanges` forms are supported; resource operations
+/// (file create/rename/delete) are skipped with a warning.
+///
+/// # Returns
+/// Every site that was edited, sorted by path and position.
+pub fn apply_workspace_edit(edit: &Value) -> Result<Vec<EditSite>> {
+    let mut edits_by_file: BTreeMap<PathBuf, Vec<Value>> = BTreeMap::new();
+
+    if let Some(changes) = edit.get("changes").and_then(Value::as_object) {
+        for (uri, edits) in changes {
+            let path = uri_to_path(uri).ok_or_else(|| anyhow::anyhow!("Unsupported URI: {}", uri))?;
+            if let Some(edits) = edits.as_array() {
+                edits_by_file.entry(path).or_default().extend(edits.iter().cloned());
+            }
+        }
+    }
+    if let Some(document_changes) = edit.get("documentChanges").and_then(Value::as_array) {
+        for change in document_changes {
+            let (Some(uri), Some(edits)) = (
+                change.pointer("/textDocument/uri").and_then(Value::as_str),
+                change.get("edits").and_then(Value::as_array),
+            ) else {
+                warn!("Skipping unsupported resource operation in workspace edit: {}", change);
+                continue;
+            };
+            let path = uri_to_path(uri).ok_or_else(|| anyhow::anyhow!("Unsupported URI: {}", uri))?;
+            edits_by_file.entry(path).or_default().extend(edits.iter().cloned());
+        }
+    }
+
+    let mut sites = Vec::new();
+    for (path, edits) in edits_by_file {
+        let mut text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut resolved = Vec::new();
+        for edit in &edits {
+            let start = edit.pointer("/range/start").ok_or_else(|| anyhow::anyhow!("Edit without range"))?;
+            let end = edit.pointer("/range/end").ok_or_else(|| anyhow::anyhow!("Edit without range"))?;
+            let new_text = edit.get("newText").and_then(Value::as_str).unwrap_or_default();
+            let start_offset = position_to_offset(&text, start)
+                .ok_or_else(|| anyhow::anyhow!("Edit position out of range in {}", path.display()))?;
+            let end_offset = position_to_offset(&text, end)
+                .ok_or_else(|| anyhow::anyhow!("Edit position out of range in {}", path.display()))?;
+            sites.push(EditSite {
+                path: path.clone(),
+                line: start.get("line").and_then(Value::as_u64).unwrap_or(0) as usize + 1,
+                column: start.get("character").and_then(Value::as_u64).unwrap_or(0) as usize + 1,
+            });
+            resolved.push((start_offset, end_offset, new_text.to_string()));
+        }
+        // Apply from the end of the file so earlier offsets stay valid.
+        resolved.sort_by_key(|edit| std::cmp::Reverse(edit.0));
+        for (start, end, new_text) in resolved {
+            text.replace_range(start..end, &new_text);
+        }
+        std::fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    sites.sort();
+    Ok(sites)
+}
+
+/// A language server process speaking LSP over stdio.
+pub struct LspClient {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: i64,
+}
+
+impl LspClient {
+    /// Spawns the server and performs the `initialize` handshake for `root`.
+    pub async fn start(command: &str, args: &[String], root: &Path) -> Result<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start language server '{}'", command))?;
+        let stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("No stdin for language server"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("No stdout for language server"))?;
+        let mut client = Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            next_id: 1,
+        };
+
+        client
+            .request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "rootUri": path_to_uri(root),
+                    "capabilities": {
+                        "workspace": { "workspaceEdit": { "documentChanges": true } }
+                    },
+                }),
+            )
+            .await?;
+        client.notify("initialized", json!({})).await?;
+        Ok(client)
+    }
+
+    /// Sends a notification (no response expected).
+    pub async fn notify(&mut self, method: &str, params: Value) -> Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        self.stdin.write_all(&encode_message(&message)).await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+
+    /// Sends a request and waits for its response, answering server requests in between.
+    ///
+    /// # Returns
+    /// The `result` of the response, or an error if the server returned an error.
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        self.stdin.write_all(&encode_message(&message)).await?;
+        self.stdin.flush().await?;
+
+        loop {
+            let incoming = tokio::time::timeout(LSP_REQUEST_TIMEOUT, read_message(&mut self.stdout))
+                .await
+                .map_err(|_| anyhow::anyhow!("Timed out waiting for '{}' response", method))??;
+
+            if incoming.get("method").is_some() {
+                // A request from the server (it has an id) needs an answer; notifications do not.
+                if let Some(server_id) = incoming.get("id") {
+                    debug!("Acknowledging server request: {}", incoming["method"]);
+                    let reply = json!({ "jsonrpc": "2.0", "id": server_id, "result": Value::Null });
+                    self.stdin.write_all(&encode_message(&reply)).await?;
+                    self.stdin.flush().await?;
+                }
+                continue;
+            }
+            if incoming.get("id").and_then(Value::as_i64) != Some(id) {
+                continue;
+            }
+            if let Some(error) = incoming.get("error") {
+                return Err(anyhow::anyhow!("'{}' failed: {}", method, error));
+            }
+            return Ok(incoming.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    /// Opens `path` in the server so that requests about it can be answered.
+    pub async fn open_document(&mut self, path: &Path, language_id: &str) -> Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": path_to_uri(path),
+                    "languageId": language_id,
+                    "version": 1,
+                    "text": text,
+                }
+            }),
+        )
+        .await
+    }
+
+    /// Requests a rename of the symbol at a 0-based `line`/`character` position.
+    ///
+    /// # Returns
+    /// The `WorkspaceEdit` produced by the server, or `None` if it declined.
+    pub async fn rename(
+        &mut self,
+        path: &Path,
+        line: usize,
+        character: usize,
+        new_name: &str,
+    ) -> Result<Option<Value>> {
+        let result = self
+            .request(
+                "textDocument/rename",
+                json!({
+                    "textDocument": { "uri": path_to_uri(path) },
+                    "position": { "line": line, "character": character },
+                    "newName": new_name,
+                }),
+            )
+            .await?;
+        Ok((!result.is_null()).then_some(result))
+    }
+
+    /// Shuts the server down politely, killing it if it does not exit.
+    pub async fn shutdown(mut self) {
+        if self.request("shutdown", Value::Null).await.is_ok() {
+            let _ = self.notify("exit", Value::Null).await;
+        }
+        if tokio::time::timeout(Duration::from_secs(5), self.child.wait())
+            .await
+            .is_err()
+        {
+            let _ = self.child.kill().await;
+        }
+    }
+}
+
+impl BackendHandle for LspClient {
+    fn is_healthy(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+/// Starts a language server and opens a fixed set of documents in it, so a restarted server
+/// is in the same state as the original.
+pub struct LspLauncher {
+    pub command: String,
+    pub args: Vec<String>,
+    pub root: PathBuf,
+    /// Documents to open after the handshake, with their language ids.
+    pub documents: Vec<(PathBuf, String)>,
+}
+
+#[async_trait]
+impl BackendLauncher for LspLauncher {
+    type Handle = LspClient;
+
+    fn name(&self) -> String {
+        format!("language server '{}'", self.command)
+    }
+
+    async fn launch(&self) -> Result<LspClient> {
+        let mut client = LspClient::start(&self.command, &self.args, &self.root).await?;
+        for (path, language_id) in &self.documents {
+            client.open_document(path, language_id).await?;
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_message_round_trip() {
+        let message = json!({"jsonrpc": "2.0", "id": 1, "result": {"ok": true}});
+        let bytes = encode_message(&message);
+        let mut reader = BufReader::new(bytes.as_slice());
+        assert_eq!(read_message(&mut reader).await.unwrap(), message);
+    }
+
+    #[test]
+    fn test_uri_round_trip_with_spaces() {
+        let path = Path::new("/tmp/my project/src/lib.rs");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///tmp/my%20project/src/lib.rs");
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+    }
+
+    #[test]
+    fn test_apply_workspace_edit_both_forms() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.rs");
+        let b = dir.path().join("b.rs");
+        std::fs::write(&a, "fn old() {}\nfn main() { old(); }\n").unwrap();
+        std::fs::write(&b, "use crate::old;\n").unwrap();
+
+        let range = |line: u64, start: u64, end: u64| {
+            json!({"start": {"line": line, "character": start}, "end": {"line": line, "character": end}})
+        };
+        let edit = json!({
+            "changes": {
+                path_to_uri(&a): [
+                    {"range": range(0, 3, 6), "newText": "new"},
+                    {"range": range(1, 12, 15), "newText": "new"}
+                ]
+            },
+            "documentChanges": [
+                {"textDocument": {"uri": path_to_uri(&b), "version": 1}, "edits": [
+                    {"range": range(0, 11, 14), "newText": "new"}
+                ]}
+            ]
+        });
+
+        let sites = apply_workspace_edit(&edit).unwrap();
+        assert_eq!(sites.len(), 3);
+        assert_eq!(sites[0], EditSite { path: a.clone(), line: 1, column: 4 });
+        assert_eq!(
+            std::fs::read_to_string(&a).unwrap(),
+            "fn new() {}\nfn main() { new(); }\n"
+        );
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "use crate::new;\n");
+    }
+}
@@ -3,7 +3,51 @@
 //! Provides various helper functions and utilities used across the Trae Rust Agent.
 //! This includes git utilities, summarization logic (Lakeview), etc.
 
+pub mod approval;
+pub mod attachments;
+pub mod audit_log;
+pub mod auto_commit;
+pub mod changelog;
+pub mod bundle;
+pub mod checkpoints;
+pub mod ckg;
+pub mod cleanup;
+pub mod clipboard;
+pub mod clock;
+pub mod cost;
+pub mod crash;
+pub mod dependency_upgrade;
+pub mod diff_explainer;
+pub mod environment;
+pub mod file_integrity;
+pub mod gha;
 pub mod git_utils;
+pub mod guards;
+pub mod highlight;
+pub mod http;
+pub mod keychain;
 pub mod lakeview; // Added
+pub mod ledger;
+pub mod logging;
+pub mod lsp;
+pub mod offline;
+pub mod outline;
+pub mod patch_format;
+pub mod permissions;
+pub mod post_mortem;
+pub mod project_inference;
+pub mod refactor;
+pub mod replay;
+pub mod reproduction;
+pub mod result_store;
+pub mod scratch;
+pub mod self_update;
+pub mod setup_hooks;
+pub mod snippet_store;
+pub mod supervisor;
+pub mod throwaway_repo;
+pub mod tool_activity;
+pub mod trajectory_import;
 pub mod trajectory_recorder;
+pub mod usage;
 // pub mod cli_console;
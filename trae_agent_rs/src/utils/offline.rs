@@ -0,0 +1,152 @@
+//! # Offline Mode
+//!
+//! `--offline` guarantees that a run talks to nothing but a local LLM endpoint: at startup
+//! every provider the run may use (the default one, the routing and Lakeview ones) must have a
+//! `base_url` on this machine or the local network, tools that need the network are removed,
+//! the update check is skipped, and shell commands run in a network namespace without
+//! interfaces where `unshare` is available (Linux with unprivileged user namespaces).
+
+use crate::config::Config;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Turns offline mode on for the whole process.
+pub fn enable() {
+    OFFLINE.store(true, Ordering::Relaxed);
+}
+
+/// Whether the process runs with `--offline`.
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Whether `url` points at this machine or the local network: `localhost`, an `.local` or
+/// `.localhost` name, or a loopback, private or link-local address.
+pub fn is_local_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => is_local_ip(ip),
+        Err(_) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local")
+        }
+    }
+}
+
+fn is_local_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            // Unique local (fc00::/7) and link-local (fe80::/10) addresses.
+            let first = ip.segments()[0];
+            ip.is_loopback() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// Checks that every provider the run may use has a local `base_url`.
+pub fn check_config(config: &Config) -> Result<(), String> {
+    let mut providers = vec![("default provider", config.default_provider.as_str())];
+    if let Some(routing) = &config.routing {
+        providers.push(("routing provider", routing.cheap_provider.as_str()));
+    }
+    if config.enable_lakeview {
+        if let Some(lakeview) = &config.lakeview_config {
+            providers.push(("Lakeview provider", lakeview.model_provider.as_str()));
+        }
+    }
+    for (role, name) in providers {
+        let base_url = config.model_providers.get(name).and_then(|p| p.base_url.as_deref());
+        match base_url {
+            Some(url) if is_local_url(url) => {}
+            Some(url) => {
+                return Err(format!(
+                    "--offline: the {} '{}' has base_url {}, which is not a local endpoint",
+                    role, name, url
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "--offline: the {} '{}' has no base_url and would use its public API; set base_url to a \
+                    local endpoint (e.g. http://localhost:11434/v1)",
+                    role, name
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether shell commands can be cut off from the network (`unshare --net` works here).
+pub fn network_isolation_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        cfg!(target_os = "linux")
+            && std::process::Command::new("unshare")
+                .args(ISOLATION_ARGS)
+                .arg("true")
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+    })
+}
+
+/// Arguments of `unshare` that run a command in new user and network namespaces; the network
+/// namespace has only a loopback interface, which is down.
+pub const ISOLATION_ARGS: &[&str] = &["--user", "--map-root-user", "--net", "--"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LakeviewConfig;
+    use serde_json::json;
+
+    #[test]
+    fn test_local_endpoints_and_config_check() {
+        for url in [
+            "http://localhost:11434/v1",
+            "http://127.0.0.1:8080",
+            "http://192.168.1.20:8000/v1",
+            "http://[::1]:8080",
+            "http://gpu-box.local/v1",
+        ] {
+            assert!(is_local_url(url), "{}", url);
+        }
+        for url in ["https://api.openai.com/v1", "http://8.8.8.8", "not a url", "http://[2001:db8::1]"] {
+            assert!(!is_local_url(url), "{}", url);
+        }
+
+        let mut config: Config = serde_json::from_value(json!({
+            "default_provider": "local",
+            "enable_lakeview": false,
+            "model_providers": {
+                "local": {"model": "qwen2.5-coder", "base_url": "http://localhost:11434/v1"},
+                "openai": {"model": "gpt-4o"}
+            }
+        }))
+        .unwrap();
+        assert_eq!(check_config(&config), Ok(()));
+
+        config.enable_lakeview = true;
+        config.lakeview_config = Some(LakeviewConfig {
+            model_provider: "openai".to_string(),
+            model_name: "gpt-4o".to_string(),
+            tags: Vec::new(),
+            replace_default_tags: false,
+            extractor_prompt_file: None,
+            tagger_prompt_file: None,
+        });
+        let error = check_config(&config).unwrap_err();
+        assert!(error.contains("Lakeview provider 'openai' has no base_url"), "{}", error);
+    }
+}
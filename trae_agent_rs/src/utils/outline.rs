@@ -0,0 +1,310 @@
+//! # File Outlines
+//!
+//! Extracts the functions, types and other top-level definitions of a source file with
+//! tree-sitter, with their line ranges, so the agent can view just the part of a large file
+//! it needs. Supported: Rust, Python, JavaScript, TypeScript and Go.
+
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// Maximum length of a signature in an outline.
+const MAX_SIGNATURE_CHARS: usize = 160;
+
+/// A source language with an outline grammar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlineLanguage {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+}
+
+impl OutlineLanguage {
+    /// Detects the language from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Self::Rust),
+            "py" | "pyi" => Some(Self::Python),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Self::JavaScript),
+            "ts" | "mts" | "cts" => Some(Self::TypeScript),
+            "tsx" => Some(Self::Tsx),
+            "go" => Some(Self::Go),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Python => "python",
+            Self::JavaScript => "javascript",
+            Self::TypeScript | Self::Tsx => "typescript",
+            Self::Go => "go",
+        }
+    }
+
+    pub(crate) fn grammar(self) -> tree_sitter::Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+
+    /// Whether a node of this kind is listed in the outline.
+    fn is_symbol(self, node: &Node) -> bool {
+        let kind = node.kind();
+        match self {
+            Self::Rust => matches!(
+                kind,
+                "function_item"
+                    | "function_signature_item"
+                    | "struct_item"
+                    | "enum_item"
+                    | "union_item"
+                    | "trait_item"
+                    | "impl_item"
+                    | "mod_item"
+                    | "type_item"
+                    | "const_item"
+                    | "static_item"
+                    | "macro_definition"
+            ),
+            Self::Python => matches!(kind, "function_definition" | "class_definition"),
+            Self::JavaScript | Self::TypeScript | Self::Tsx => {
+                matches!(
+                    kind,
+                    "function_declaration"
+                        | "generator_function_declaration"
+                        | "class_declaration"
+                        | "abstract_class_declaration"
+                        | "method_definition"
+                        | "interface_declaration"
+                        | "type_alias_declaration"
+                        | "enum_declaration"
+                ) || (kind == "variable_declarator"
+                    && node.child_by_field_name("value").is_some_and(|v| {
+                        matches!(v.kind(), "arrow_function" | "function_expression" | "function")
+                    }))
+            }
+            Self::Go => matches!(kind, "function_declaration" | "method_declaration" | "type_spec"),
+        }
+    }
+}
+
+/// One entry of an outline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    /// Node kind, e.g. "function_item" or "class_definition".
+    pub kind: String,
+    /// Name of the symbol, if it has one (impl blocks do not).
+    pub name: Option<String>,
+    /// Declaration up to its body, on one line (e.g. "pub fn new(path: &Path) -> Self").
+    pub signature: String,
+    /// First line, 1-indexed.
+    pub start_line: usize,
+    /// Last line, 1-indexed, inclusive.
+    pub end_line: usize,
+    /// Number of enclosing symbols (0 for top-level definitions).
+    pub depth: usize,
+}
+
+/// Parses `source` and returns its symbols in source order.
+pub fn outline(language: OutlineLanguage, source: &str) -> anyhow::Result<Vec<Symbol>> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&language.grammar())
+        .map_err(|e| anyhow::anyhow!("Failed to load the {} grammar: {}", language.name(), e))?;
+    let tree = parser
+        .parse(source, None)
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse the file as {}", language.name()))?;
+
+    let mut symbols = Vec::new();
+    collect(language, tree.root_node(), source, 0, &mut symbols);
+    Ok(symbols)
+}
+
+fn collect(language: OutlineLanguage, node: Node, source: &str, depth: usize, out: &mut Vec<Symbol>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if language.is_symbol(&child) {
+            out.push(Symbol {
+                kind: child.kind().to_string(),
+                name: child
+                    .child_by_field_name("name")
+                    .and_then(|n| n.utf8_text(source.as_bytes()).ok())
+                    .map(str::to_string),
+                signature: signature(&child, source),
+                start_line: child.start_position().row + 1,
+                end_line: child.end_position().row + 1,
+                depth,
+            });
+            collect(language, child, source, depth + 1, out);
+        } else {
+            collect(language, child, source, depth, out);
+        }
+    }
+}
+
+/// The text of `node` up to its body, with whitespace collapsed.
+fn signature(node: &Node, source: &str) -> String {
+    // A declarator's signature includes its `const`/`let` keyword.
+    let start = match node.parent() {
+        Some(parent) if node.kind() == "variable_declarator" => parent.start_byte(),
+        _ => node.start_byte(),
+    };
+    let body = node.child_by_field_name("body").or_else(|| {
+        node.child_by_field_name("value")
+            .and_then(|value| value.child_by_field_name("body"))
+    });
+    let text = match body {
+        Some(body) => source.get(start..body.start_byte()).unwrap_or_default(),
+        // Bodiless items (consts, type aliases, ...) are shown by their first line.
+        None => source
+            .get(start..node.end_byte())
+            .unwrap_or_default()
+            .lines()
+            .next()
+            .unwrap_or_default(),
+    };
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed.strip_suffix("=>").unwrap_or(&collapsed).trim_end();
+    let trimmed = trimmed.trim_end_matches(['{', ':']).trim_end();
+    if trimmed.chars().count() > MAX_SIGNATURE_CHARS {
+        let cut: String = trimmed.chars().take(MAX_SIGNATURE_CHARS).collect();
+        format!("{}...", cut)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Finds the symbol named `name` in an outline. A qualified name (`Point::new`, `Cache.get`)
+/// matches a symbol whose enclosing definition mentions the qualifier, e.g. `impl Point` or
+/// `class Cache`.
+pub fn find_symbol<'a>(symbols: &'a [Symbol], name: &str) -> Option<&'a Symbol> {
+    let segments: Vec<&str> = name.split("::").flat_map(|s| s.split('.')).collect();
+    let (last, qualifiers) = segments.split_last()?;
+    symbols.iter().enumerate().find_map(|(i, symbol)| {
+        if symbol.name.as_deref() != Some(*last) {
+            return None;
+        }
+        let Some(qualifier) = qualifiers.last() else {
+            return Some(symbol);
+        };
+        let parent = symbols[..i].iter().rev().find(|s| {
+            s.depth + 1 == symbol.depth && s.start_line <= symbol.start_line && s.end_line >= symbol.end_line
+        })?;
+        parent
+            .signature
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .any(|word| word == *qualifier)
+            .then_some(symbol)
+    })
+}
+
+/// Whether a symbol of this kind defines a type (struct, class, interface, ...).
+pub fn is_type_definition(symbol: &Symbol) -> bool {
+    matches!(
+        symbol.kind.as_str(),
+        "struct_item"
+            | "enum_item"
+            | "union_item"
+            | "trait_item"
+            | "type_item"
+            | "class_definition"
+            | "class_declaration"
+            | "abstract_class_declaration"
+            | "interface_declaration"
+            | "type_alias_declaration"
+            | "enum_declaration"
+            | "type_spec"
+    )
+}
+
+/// The first line (1-indexed) of the doc comments, attributes and decorators directly above
+/// the definition starting at `start_line`.
+pub fn leading_comment_start(source: &str, start_line: usize) -> usize {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut first = start_line;
+    while first > 1 {
+        let line = lines.get(first - 2).map_or("", |l| l.trim());
+        let attached = ["//", "#", "@", "/*", "*"].iter().any(|prefix| line.starts_with(prefix));
+        if !attached {
+            break;
+        }
+        first -= 1;
+    }
+    first
+}
+
+/// Formats an outline as line-numbered entries indented by nesting depth.
+pub fn format_outline(symbols: &[Symbol]) -> String {
+    symbols
+        .iter()
+        .map(|s| {
+            format!(
+                "{:>6}-{:<6} {}{}",
+                s.start_line,
+                s.end_line,
+                "  ".repeat(s.depth),
+                s.signature
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_outline() {
+        let source = "use std::fmt;\n\npub struct Point {\n    x: i32,\n}\n\nimpl Point {\n    pub fn new(\n        x: i32,\n    ) -> Self {\n        Point { x }\n    }\n}\n\nconst MAX: usize = 3;\n";
+        let symbols = outline(OutlineLanguage::Rust, source).unwrap();
+        let summary: Vec<(&str, usize, usize, usize)> = symbols
+            .iter()
+            .map(|s| (s.signature.as_str(), s.start_line, s.end_line, s.depth))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("pub struct Point", 3, 5, 0),
+                ("impl Point", 7, 13, 0),
+                ("pub fn new( x: i32, ) -> Self", 8, 12, 1),
+                ("const MAX: usize = 3;", 15, 15, 0),
+            ]
+        );
+        assert_eq!(symbols[2].name.as_deref(), Some("new"));
+        assert!(format_outline(&symbols).contains("     8-12       pub fn new( x: i32, ) -> Self"));
+    }
+
+    #[test]
+    fn test_python_and_typescript_outlines() {
+        let py = "class Cache:\n    def get(self, key: str) -> int:\n        return 1\n\ndef main():\n    pass\n";
+        let symbols = outline(OutlineLanguage::Python, py).unwrap();
+        let signatures: Vec<&str> = symbols.iter().map(|s| s.signature.as_str()).collect();
+        assert_eq!(
+            signatures,
+            vec!["class Cache", "def get(self, key: str) -> int", "def main()"]
+        );
+
+        let ts = "export interface Opts { a: number }\nexport const run = async (o: Opts): Promise<void> => {\n  return;\n};\n";
+        let symbols = outline(OutlineLanguage::TypeScript, ts).unwrap();
+        let names: Vec<Option<&str>> = symbols.iter().map(|s| s.name.as_deref()).collect();
+        assert_eq!(names, vec![Some("Opts"), Some("run")]);
+        assert_eq!(symbols[1].signature, "const run = async (o: Opts): Promise<void>");
+    }
+
+    #[test]
+    fn test_language_from_path() {
+        assert_eq!(OutlineLanguage::from_path(Path::new("/a/b.rs")), Some(OutlineLanguage::Rust));
+        assert_eq!(OutlineLanguage::from_path(Path::new("/a/b.tsx")), Some(OutlineLanguage::Tsx));
+        assert_eq!(OutlineLanguage::from_path(Path::new("/a/README.md")), None);
+    }
+}
@@ -0,0 +1,257 @@
+//! # Patch Formats
+//!
+//! The patch saved by `trae run --patch-path` is written in the format `--patch-format`
+//! selects: `git` (the output of `git diff`, the default), `unified` (the same hunks without
+//! git's extended headers, for `patch` and review tools that do not know them) or `json`
+//! (each file's status and paths with its hunks' line ranges and before/after text, for
+//! downstream tooling).
+
+use serde::Serialize;
+
+/// Format of the saved patch.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum PatchFormat {
+    /// `git diff` output, with extended headers (modes, renames, binary markers).
+    #[default]
+    Git,
+    /// Plain unified diff: `---`/`+++` headers and hunks only.
+    Unified,
+    /// Per-file JSON with each hunk's before and after text.
+    Json,
+}
+
+/// How a file changed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+}
+
+/// One hunk of a file's diff.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Text after the second `@@`, usually the enclosing function, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    /// The lines the hunk replaces: its context and removed lines.
+    pub before: String,
+    /// The lines that replace them: its context and added lines.
+    pub after: String,
+    /// The hunk body as it appears in the diff.
+    #[serde(skip)]
+    body: Vec<String>,
+}
+
+/// The diff of one file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FilePatch {
+    /// Path after the change (before it, for deleted files).
+    pub path: String,
+    /// Path before the change, when the file was renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+    /// Whether git reported the file as binary (no hunks are available).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+    pub hunks: Vec<Hunk>,
+    /// The `---`/`+++` header lines, as they appear in the diff.
+    #[serde(skip)]
+    header: Vec<String>,
+}
+
+/// Parses `@@ -a,b +c,d @@ section` into the hunk's ranges and section.
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |range: &str| -> Option<(u32, u32)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = range(old)?;
+    let (new_start, new_lines) = range(new)?;
+    let section = section.trim();
+    Some(Hunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        section: (!section.is_empty()).then(|| section.to_string()),
+        before: String::new(),
+        after: String::new(),
+        body: Vec::new(),
+    })
+}
+
+/// Splits the output of `git diff` into per-file patches.
+pub fn parse_git_diff(diff: &str) -> Vec<FilePatch> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    // Inside a hunk, `---`/`+++` are ordinary removed/added lines, not file headers.
+    let mut in_hunk = false;
+    for line in diff.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let (old, new) = rest.split_once(" b/").unwrap_or((rest, rest));
+            files.push(FilePatch {
+                path: new.to_string(),
+                old_path: Some(old.strip_prefix("a/").unwrap_or(old).to_string()),
+                status: FileStatus::Modified,
+                binary: false,
+                hunks: Vec::new(),
+                header: Vec::new(),
+            });
+            in_hunk = false;
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@ ") {
+            if let Some(hunk) = parse_hunk_header(line) {
+                file.hunks.push(hunk);
+                in_hunk = true;
+                continue;
+            }
+        }
+        if in_hunk {
+            let Some(hunk) = file.hunks.last_mut() else {
+                continue;
+            };
+            hunk.body.push(line.to_string());
+            match line.as_bytes().first() {
+                Some(b'-') => push_line(&mut hunk.before, &line[1..]),
+                Some(b'+') => push_line(&mut hunk.after, &line[1..]),
+                Some(b' ') => {
+                    push_line(&mut hunk.before, &line[1..]);
+                    push_line(&mut hunk.after, &line[1..]);
+                }
+                // `\ No newline at end of file`, or an empty context line.
+                _ if line.is_empty() => {
+                    push_line(&mut hunk.before, "");
+                    push_line(&mut hunk.after, "");
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if line.starts_with("new file mode") {
+            file.status = FileStatus::Added;
+        } else if line.starts_with("deleted file mode") {
+            file.status = FileStatus::Deleted;
+        } else if line.starts_with("rename from ") {
+            file.status = FileStatus::Renamed;
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.binary = true;
+        } else if line.starts_with("--- ") || line.starts_with("+++ ") {
+            file.header.push(line.to_string());
+        }
+    }
+    for file in &mut files {
+        if file.status == FileStatus::Deleted {
+            file.path = file.old_path.clone().unwrap_or_default();
+        }
+        if file.status != FileStatus::Renamed {
+            file.old_path = None;
+        }
+    }
+    files
+}
+
+fn push_line(text: &mut String, line: &str) {
+    text.push_str(line);
+    text.push('\n');
+}
+
+/// Renders the output of `git diff` in `format`.
+pub fn render(diff: &str, format: PatchFormat) -> String {
+    match format {
+        PatchFormat::Git => diff.to_string(),
+        PatchFormat::Unified => {
+            let mut out = String::new();
+            for file in parse_git_diff(diff) {
+                if file.binary {
+                    out.push_str(&format!("Binary file {} differs\n", file.path));
+                    continue;
+                }
+                for line in &file.header {
+                    push_line(&mut out, line);
+                }
+                for hunk in &file.hunks {
+                    let section = hunk.section.as_deref().map(|s| format!(" {}", s)).unwrap_or_default();
+                    out.push_str(&format!(
+                        "@@ -{},{} +{},{} @@{}\n",
+                        hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines, section
+                    ));
+                    for line in &hunk.body {
+                        push_line(&mut out, line);
+                    }
+                }
+            }
+            out
+        }
+        PatchFormat::Json => {
+            let files = parse_git_diff(diff);
+            serde_json::to_string_pretty(&serde_json::json!({ "files": files })).unwrap_or_default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@ fn main() {
+ let a = 1;
+--- old comment
++++ new comment
+ let b = 2;
+diff --git a/notes.txt b/notes.txt
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/notes.txt
+@@ -0,0 +1 @@
++hello
+diff --git a/logo.png b/logo.png
+index 4444444..5555555 100644
+Binary files a/logo.png and b/logo.png differ
+";
+
+    #[test]
+    fn test_git_diff_renders_as_unified_and_json() {
+        let files = parse_git_diff(DIFF);
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].hunks[0].before, "let a = 1;\n-- old comment\nlet b = 2;\n");
+        assert_eq!(files[0].hunks[0].after, "let a = 1;\n++ new comment\nlet b = 2;\n");
+        assert_eq!(files[0].hunks[0].section.as_deref(), Some("fn main() {"));
+        assert_eq!((files[1].status, files[1].hunks[0].new_lines), (FileStatus::Added, 1));
+        assert!(files[2].binary);
+
+        assert_eq!(
+            render(DIFF, PatchFormat::Unified),
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,3 @@ fn main() {\n let a = 1;\n--- old comment\n\
+             +++ new comment\n let b = 2;\n--- /dev/null\n+++ b/notes.txt\n@@ -0,0 +1,1 @@\n+hello\n\
+             Binary file logo.png differs\n"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&render(DIFF, PatchFormat::Json)).unwrap();
+        assert_eq!(json["files"][1]["path"], "notes.txt");
+        assert_eq!(json["files"][1]["status"], "added");
+        assert_eq!(json["files"][1]["hunks"][0]["after"], "hello\n");
+        assert_eq!(json["files"][2]["binary"], true);
+        assert_eq!(render(DIFF, PatchFormat::Git), DIFF);
+    }
+}
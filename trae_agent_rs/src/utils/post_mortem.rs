@@ -0,0 +1,298 @@
+//! # Failure Post-mortems
+//!
+//! When a run ends without completing its task — out of steps, or with its completion
+//! rejected by validation — one LLM pass over the trajectory explains why it failed and what
+//! to try next. Unlike Lakeview, which tags every step, this produces a single short report.
+
+use crate::agent::base_agent::{AgentError, AgentExecution, AgentStep};
+use crate::config::output_language_instruction;
+use crate::llm::base_client::{LLMClient, LLMMessage, MessageRole};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Maximum length of the trajectory digest sent to the LLM, in characters. Older steps are
+/// dropped first.
+const MAX_DIGEST_CHARS: usize = 24_000;
+/// Maximum length of a tool argument, result or error in the digest, in characters.
+const MAX_FIELD_CHARS: usize = 400;
+
+const SYSTEM_PROMPT: &str = "You review failed runs of a software engineering agent. Given the \
+task and a digest of the agent's steps, write a short post-mortem in Markdown with two sections: \
+\"## Why it failed\" (the root cause, not the symptoms, in a few sentences) and \"## What to try \
+next\" (two to four concrete suggestions, such as a different approach, a file to look at or a \
+configuration change). Do not retell every step. Keep it under 250 words.";
+
+/// How an unsuccessful run ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailureKind {
+    /// The run used all its steps.
+    MaxStepsReached,
+    /// The agent signalled completion but validation rejected it; carries the last rejection.
+    ValidationFailed(String),
+}
+
+/// Returns how `execution` failed, if it is a failure a post-mortem can explain. Runs that
+/// stopped on an LLM or infrastructure error are not.
+pub fn failure_kind(execution: &AgentExecution) -> Option<FailureKind> {
+    if execution.success {
+        return None;
+    }
+    if let Some(rejection) = execution
+        .steps
+        .iter()
+        .rev()
+        .find_map(|step| step.validation_error.as_deref())
+    {
+        return Some(FailureKind::ValidationFailed(rejection.to_string()));
+    }
+    // Matches the message of `AgentError::MaxStepsReached`.
+    let out_of_steps = execution
+        .error_message
+        .as_deref()
+        .is_some_and(|message| message.starts_with("Agent reached maximum steps"));
+    out_of_steps.then_some(FailureKind::MaxStepsReached)
+}
+
+/// Where the post-mortem of a run is saved: next to its trajectory, as `<name>.postmortem.md`.
+pub fn post_mortem_path(trajectory_path: &Path) -> PathBuf {
+    trajectory_path.with_extension("postmortem.md")
+}
+
+/// Asks `llm_client` for the post-mortem of `execution`.
+pub async fn generate_post_mortem(
+    execution: &AgentExecution,
+    kind: &FailureKind,
+    llm_client: Arc<dyn LLMClient>,
+    output_language: Option<&str>,
+) -> Result<String, AgentError> {
+    let mut system_prompt = SYSTEM_PROMPT.to_string();
+    if let Some(language) = output_language {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(&output_language_instruction(language));
+    }
+    let outcome = match kind {
+        FailureKind::MaxStepsReached => format!(
+            "The run stopped after using all {} steps without completing the task.",
+            execution.steps.len()
+        ),
+        FailureKind::ValidationFailed(rejection) => format!(
+            "The agent signalled completion, but validation rejected it: {}",
+            clip(rejection)
+        ),
+    };
+    let messages = vec![
+        text_message(MessageRole::System, system_prompt),
+        text_message(
+            MessageRole::User,
+            format!(
+                "Task:\n{}\n\nOutcome: {}\n\nSteps:\n{}",
+                execution.task,
+                outcome,
+                trajectory_digest(&execution.steps)
+            ),
+        ),
+    ];
+    let response = llm_client.chat(messages, None, None).await?;
+    response
+        .choices
+        .first()
+        .and_then(|choice| choice.message.content.clone())
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+        .ok_or_else(|| AgentError::LogicError("The post-mortem response was empty".to_string()))
+}
+
+/// Condenses the steps into text: the tool calls of each step with their results, errors and
+/// rejected completions. Keeps the most recent steps when the digest gets too long.
+fn trajectory_digest(steps: &[AgentStep]) -> String {
+    let mut blocks: Vec<String> = Vec::new();
+    let mut total = 0;
+    for step in steps.iter().rev() {
+        let block = format_step(step);
+        if total + block.len() > MAX_DIGEST_CHARS && !blocks.is_empty() {
+            blocks.push(format!("(steps 1-{} omitted)", step.step_number));
+            break;
+        }
+        total += block.len();
+        blocks.push(block);
+    }
+    blocks.reverse();
+    blocks.join("\n")
+}
+
+fn format_step(step: &AgentStep) -> String {
+    let mut lines = vec![format!("Step {}:", step.step_number)];
+    if let Some(text) = step
+        .llm_response
+        .as_ref()
+        .and_then(|r| r.choices.first())
+        .and_then(|c| c.message.content.as_deref())
+        .filter(|t| !t.trim().is_empty())
+    {
+        lines.push(format!("  Said: {}", clip(text.trim())));
+    }
+    for call in step.tool_calls_made.iter().flatten() {
+        lines.push(format!("  Called {}({})", call.function.name, clip(&call.function.arguments)));
+        let result = step
+            .tool_results
+            .iter()
+            .flatten()
+            .find(|r| r.tool_call_id == call.id);
+        if let Some(result) = result {
+            match (&result.result, &result.error) {
+                (_, Some(error)) => lines.push(format!("    Error: {}", clip(error))),
+                (Some(output), None) => lines.push(format!("    Result: {}", clip(output))),
+                (None, None) => {}
+            }
+        }
+    }
+    if let Some(rejection) = &step.validation_error {
+        lines.push(format!("  Completion rejected: {}", clip(rejection)));
+    }
+    if let Some(error) = &step.error {
+        lines.push(format!("  Step error: {}", clip(error)));
+    }
+    lines.join("\n")
+}
+
+fn clip(text: &str) -> String {
+    if text.chars().count() > MAX_FIELD_CHARS {
+        let cut: String = text.chars().take(MAX_FIELD_CHARS).collect();
+        format!("{}...", cut)
+    } else {
+        text.to_string()
+    }
+}
+
+fn text_message(role: MessageRole, content: String) -> LLMMessage {
+    LLMMessage {
+        role,
+        content: Some(content),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::base_agent::AgentState;
+    use crate::config::ModelParameters;
+    use crate::llm::base_client::{ToolCall, ToolCallFunction};
+    use crate::llm::OpenAIClient;
+    use crate::tools::AgentToolResult;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn step(number: u32, validation_error: Option<&str>) -> AgentStep {
+        AgentStep {
+            step_number: number,
+            state: AgentState::ProcessingToolResult,
+            messages_to_llm: None,
+            llm_response: None,
+            tool_calls_made: Some(vec![ToolCall {
+                id: format!("call{}", number),
+                tool_type: "function".to_string(),
+                function: ToolCallFunction {
+                    name: "bash".to_string(),
+                    arguments: json!({"command": "pytest"}).to_string(),
+                },
+            }]),
+            tool_results: Some(vec![AgentToolResult {
+                tool_call_id: format!("call{}", number),
+                success: false,
+                result: None,
+                error: Some("1 failed".to_string()),
+                duration_ms: None,
+                approval: None,
+            }]),
+            reflection: None,
+            error: None,
+            duration_ms: 10,
+            route: None,
+            validation_error: validation_error.map(str::to_string),
+            diff_stat: None,
+            file_changes: Vec::new(),
+            llm_duration_ms: None,
+            step_extensions: Vec::new(),
+        }
+    }
+
+    fn failed_execution(steps: Vec<AgentStep>, error_message: &str) -> AgentExecution {
+        AgentExecution {
+            task: "Fix the failing test".to_string(),
+            start_time: 0,
+            end_time: Some(1),
+            steps,
+            final_result: None,
+            success: false,
+            total_tokens_used: None,
+            error_message: Some(error_message.to_string()),
+            error_hint: None,
+            subagents: Vec::new(),
+            final_report: None,
+        }
+    }
+
+    #[test]
+    fn test_failure_kind() {
+        let max_steps = AgentError::MaxStepsReached(2).to_string();
+        let execution = failed_execution(vec![step(1, None), step(2, None)], &max_steps);
+        assert_eq!(failure_kind(&execution), Some(FailureKind::MaxStepsReached));
+
+        let execution = failed_execution(
+            vec![step(1, Some("ERROR! Your Patch is empty.")), step(2, None)],
+            &max_steps,
+        );
+        assert_eq!(
+            failure_kind(&execution),
+            Some(FailureKind::ValidationFailed("ERROR! Your Patch is empty.".to_string()))
+        );
+
+        let execution = failed_execution(vec![step(1, None)], "LLM interaction failed: timeout");
+        assert_eq!(failure_kind(&execution), None);
+        assert_eq!(
+            post_mortem_path(Path::new("/t/run.json")),
+            PathBuf::from("/t/run.postmortem.md")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_post_mortem() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_string_contains("Called bash"))
+            .and(body_string_contains("Completion rejected: ERROR! Your Patch is empty."))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "chatcmpl-pm",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "gpt-4o",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "## Why it failed\nNo edits.\n"},
+                    "finish_reason": "stop"
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let params: ModelParameters = serde_json::from_value(json!({"model": "gpt-4o"})).unwrap();
+        let client = OpenAIClient::new(Some("key".to_string()), Some(server.uri()), params)
+            .await
+            .unwrap();
+
+        let execution = failed_execution(
+            vec![step(1, Some("ERROR! Your Patch is empty."))],
+            &AgentError::MaxStepsReached(1).to_string(),
+        );
+        let kind = failure_kind(&execution).unwrap();
+        let report = generate_post_mortem(&execution, &kind, Arc::new(client), None)
+            .await
+            .unwrap();
+        assert_eq!(report, "## Why it failed\nNo edits.");
+    }
+}
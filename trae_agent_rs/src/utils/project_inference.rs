@@ -0,0 +1,136 @@
+//! # Project Directory Inference
+//!
+//! When no `--working-dir` is given, the project is inferred instead of silently using the
+//! process's current directory: a directory mentioned in the task text comes first (the
+//! repository containing it, if any), then the git repository containing the current
+//! directory. In confirm mode the user is asked before the inferred directory is used.
+
+use super::permissions::ask_on_terminal;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where an inferred project directory comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InferenceSource {
+    /// A path mentioned in the task text.
+    TaskText,
+    /// The git repository containing the current directory.
+    GitRoot,
+}
+
+impl fmt::Display for InferenceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InferenceSource::TaskText => write!(f, "a path in the task"),
+            InferenceSource::GitRoot => write!(f, "the git repository of the current directory"),
+        }
+    }
+}
+
+/// An inferred project directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InferredProject {
+    pub path: PathBuf,
+    pub source: InferenceSource,
+}
+
+/// Infers the project directory from `task` (if any) and the current directory `cwd`.
+pub fn infer_project_path(task: Option<&str>, cwd: &Path) -> Option<InferredProject> {
+    if let Some(path) = task.and_then(|task| path_in_task(task, cwd)) {
+        return Some(InferredProject {
+            path: git_root(&path).unwrap_or(path),
+            source: InferenceSource::TaskText,
+        });
+    }
+    git_root(cwd).map(|path| InferredProject {
+        path,
+        source: InferenceSource::GitRoot,
+    })
+}
+
+/// The first existing directory mentioned in `task`, as an absolute path. A mentioned file
+/// stands for its directory. Relative paths count only with a `./` or `../` prefix, resolved
+/// against `cwd`, so ordinary words are never taken for paths.
+fn path_in_task(task: &str, cwd: &Path) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    task.split_whitespace()
+        .map(|word| {
+            word.trim_start_matches(|c: char| "`'\"([<".contains(c))
+                .trim_end_matches(|c: char| "`'\")]>,;:.!?".contains(c))
+        })
+        .filter_map(|word| {
+            if word.starts_with('/') {
+                Some(PathBuf::from(word))
+            } else if let Some(rest) = word.strip_prefix("~/") {
+                home.as_ref().map(|home| home.join(rest))
+            } else if word.starts_with("./") || word.starts_with("../") {
+                Some(cwd.join(word))
+            } else {
+                None
+            }
+        })
+        .find_map(|path| {
+            let dir = if path.is_file() { path.parent()?.to_path_buf() } else { path };
+            // The root directory is never the project, even if a task mentions `/`.
+            (dir.is_dir() && dir.parent().is_some()).then(|| dir.canonicalize().unwrap_or(dir))
+        })
+}
+
+/// The top of the git work tree containing `dir`, if any.
+fn git_root(dir: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let root = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!root.is_empty()).then(|| PathBuf::from(root))
+}
+
+/// Asks on the terminal whether to use `inferred` as the project directory. An empty answer
+/// accepts it.
+pub fn confirm_inferred(inferred: &InferredProject) -> bool {
+    let question = format!(
+        "No working directory was given. Use {} (from {}) as the project directory? [Y/n]: ",
+        inferred.path.display(),
+        inferred.source
+    );
+    ask_on_terminal(&question).is_some_and(|reply| matches!(reply.trim().to_lowercase().as_str(), "" | "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_is_inferred_from_task_paths_then_git_root() {
+        let repo = tempfile::tempdir().unwrap();
+        let root = repo.path().canonicalize().unwrap();
+        let status = Command::new("git").current_dir(&root).args(["init", "-q"]).status().unwrap();
+        assert!(status.success());
+        std::fs::create_dir_all(root.join("src/parser")).unwrap();
+        std::fs::write(root.join("src/parser/lexer.rs"), "").unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let elsewhere_path = elsewhere.path().canonicalize().unwrap();
+
+        // A file mentioned in the task leads to the repository containing it.
+        let task = format!("Fix the panic in `{}`.", root.join("src/parser/lexer.rs").display());
+        let inferred = infer_project_path(Some(&task), &elsewhere_path).unwrap();
+        assert_eq!(inferred, InferredProject { path: root.clone(), source: InferenceSource::TaskText });
+
+        // A directory outside any repository is used as it is.
+        let task = format!("Add a README to {}", elsewhere_path.display());
+        assert_eq!(infer_project_path(Some(&task), &root).unwrap().path, elsewhere_path);
+
+        // Without usable paths, the git root of the current directory.
+        let inferred = infer_project_path(Some("Fix the lexer in src/parser"), &root.join("src/parser")).unwrap();
+        assert_eq!(inferred, InferredProject { path: root.clone(), source: InferenceSource::GitRoot });
+        let inferred = infer_project_path(Some("Fix ./parser"), &root.join("src")).unwrap();
+        assert_eq!(inferred.source, InferenceSource::TaskText);
+        assert_eq!(inferred.path, root);
+    }
+}
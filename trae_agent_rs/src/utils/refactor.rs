@@ -0,0 +1,307 @@
+//! # Refactoring Utilities
+//!
+//! Helpers for the `refactor` command: parsing rename specifications, locating
+//! identifier occurrences across a project, and describing the remaining work to
+//! the agent when a language server rename is not available.
+
+use crate::utils::lsp::EditSite;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Directories never scanned for occurrences.
+const SKIPPED_DIRS: &[&str] = &[".git", "target", "node_modules", "__pycache__", ".venv", ".trae"];
+
+/// Maximum number of sites listed in the agent prompt.
+const MAX_SITES_IN_PROMPT: usize = 200;
+
+/// Parses an `OldName=NewName` rename specification.
+///
+/// # Returns
+/// The old and new identifiers, or an error if either side is empty or not a valid identifier.
+pub fn parse_rename_spec(spec: &str) -> Result<(String, String)> {
+    let (old, new) = spec
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Rename must be given as OldName=NewName, got '{}'", spec))?;
+    let (old, new) = (old.trim(), new.trim());
+    for name in [old, new] {
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid {
+            return Err(anyhow::anyhow!("'{}' is not a valid identifier", name));
+        }
+    }
+    if old == new {
+        return Err(anyhow::anyhow!("Old and new names are identical"));
+    }
+    Ok((old.to_string(), new.to_string()))
+}
+
+/// Returns the LSP language id for a source file, if it is a language we rename in.
+pub fn language_id_for(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()? {
+        "rs" => Some("rust"),
+        "py" => Some("python"),
+        "ts" | "tsx" => Some("typescript"),
+        "js" | "jsx" => Some("javascript"),
+        "go" => Some("go"),
+        _ => None,
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Finds whole-word occurrences of `name` in `text`.
+///
+/// # Returns
+/// 0-based `(line, byte_column)` pairs.
+pub fn find_in_text(text: &str, name: &str) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let mut search_from = 0;
+        while let Some(pos) = line[search_from..].find(name) {
+            let start = search_from + pos;
+            let end = start + name.len();
+            let before_ok = !line[..start].chars().next_back().is_some_and(is_identifier_char);
+            let after_ok = !line[end..].chars().next().is_some_and(is_identifier_char);
+            if before_ok && after_ok {
+                found.push((line_index, start));
+            }
+            search_from = end;
+        }
+    }
+    found
+}
+
+/// Recursively finds whole-word occurrences of `name` in the project's source files.
+///
+/// # Returns
+/// 1-based sites sorted by path and position.
+pub fn find_occurrences(root: &Path, name: &str) -> Vec<EditSite> {
+    let mut sites = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                let skipped = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| SKIPPED_DIRS.contains(&n));
+                if !skipped {
+                    stack.push(path);
+                }
+            } else if language_id_for(&path).is_some() {
+                let Ok(text) = std::fs::read_to_string(&path) else {
+                    continue;
+                };
+                sites.extend(find_in_text(&text, name).into_iter().map(|(line, column)| {
+                    EditSite {
+                        path: path.clone(),
+                        line: line + 1,
+                        column: column + 1,
+                    }
+                }));
+            }
+        }
+    }
+    sites.sort();
+    sites
+}
+
+/// Formats sites as `path:line:column`, relative to `root` where possible.
+pub fn format_sites(sites: &[EditSite], root: &Path) -> Vec<String> {
+    sites
+        .iter()
+        .map(|site| {
+            let path = site.path.strip_prefix(root).unwrap_or(&site.path);
+            format!("{}:{}:{}", path.display(), site.line, site.column)
+        })
+        .collect()
+}
+
+/// Builds the task description for the agent-driven rename fallback.
+pub fn build_rename_prompt(
+    old_name: &str,
+    new_name: &str,
+    remaining: &[EditSite],
+    root: &Path,
+    build_failure: Option<&str>,
+) -> String {
+    let mut listed = format_sites(remaining, root);
+    let omitted = listed.len().saturating_sub(MAX_SITES_IN_PROMPT);
+    listed.truncate(MAX_SITES_IN_PROMPT);
+    let mut prompt = format!(
+        "Rename the identifier `{old_name}` to `{new_name}` throughout the project.\n\
+        Only rename occurrences that refer to the same symbol; leave unrelated identifiers, \
+        string literals and comments about other things untouched. Update declarations, \
+        uses, imports and re-exports consistently.\n\n\
+        Remaining occurrences of `{old_name}` (path:line:column):\n{}",
+        listed.join("\n")
+    );
+    if omitted > 0 {
+        prompt.push_str(&format!("\n... and {} more", omitted));
+    }
+    if let Some(output) = build_failure {
+        prompt.push_str(&format!(
+            "\n\nThe project currently fails to build:\n```\n{}\n```",
+            output
+        ));
+    }
+    prompt.push_str("\n\nWhen the rename is complete and the project builds, call `task_done`.");
+    prompt
+}
+
+/// Reads the files of `sites`, so `changed_sites` can later compare them with their new text.
+pub fn snapshot_files(sites: &[EditSite]) -> HashMap<PathBuf, String> {
+    let mut files = HashMap::new();
+    for site in sites {
+        if !files.contains_key(&site.path) {
+            if let Ok(text) = std::fs::read_to_string(&site.path) {
+                files.insert(site.path.clone(), text);
+            }
+        }
+    }
+    files
+}
+
+/// Returns the sites from `before` that were renamed, comparing the files as they were
+/// (`before_files`, from `snapshot_files`) with their text now.
+///
+/// Sites are matched per line by occurrence index: the k-th occurrence of `old_name` or
+/// `new_name` on a line is the same site before and after, even when an earlier rename on
+/// the line shifted its column. A site is changed if that occurrence is no longer
+/// `old_name`. If the line gained or lost occurrences, it is changed if `old_name` is no
+/// longer at its column.
+pub fn changed_sites(
+    before: &[EditSite],
+    before_files: &HashMap<PathBuf, String>,
+    old_name: &str,
+    new_name: &str,
+) -> Vec<EditSite> {
+    let mut after_files: HashMap<&Path, Option<String>> = HashMap::new();
+    before
+        .iter()
+        .filter(|site| {
+            let after = after_files
+                .entry(site.path.as_path())
+                .or_insert_with(|| std::fs::read_to_string(&site.path).ok());
+            let line_of = |text: &str| text.lines().nth(site.line - 1).map(str::to_string);
+            let (Some(before_line), Some(after_line)) = (
+                before_files.get(&site.path).and_then(|text| line_of(text)),
+                after.as_deref().and_then(line_of),
+            ) else {
+                return true;
+            };
+            let before_names = names_in_line(&before_line, old_name, new_name);
+            let after_names = names_in_line(&after_line, old_name, new_name);
+            let index = before_names.iter().position(|&(column, _)| column + 1 == site.column);
+            match index {
+                Some(index) if before_names.len() == after_names.len() => !after_names[index].1,
+                _ => !find_in_text(&after_line, old_name).iter().any(|&(_, column)| column + 1 == site.column),
+            }
+        })
+        .cloned()
+        .collect()
+}
+
+/// Byte columns of the occurrences of `old_name` and `new_name` in `line`, in order, each
+/// with whether it is `old_name`.
+fn names_in_line(line: &str, old_name: &str, new_name: &str) -> Vec<(usize, bool)> {
+    let mut names: Vec<(usize, bool)> = find_in_text(line, old_name)
+        .into_iter()
+        .map(|(_, column)| (column, true))
+        .chain(find_in_text(line, new_name).into_iter().map(|(_, column)| (column, false)))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Picks the occurrence to hand to the language server: prefer a likely definition site.
+pub fn pick_rename_anchor<'a>(sites: &'a [EditSite], old_name: &str) -> Option<&'a EditSite> {
+    const DEFINITION_KEYWORDS: &[&str] = &[
+        "fn ", "struct ", "enum ", "trait ", "type ", "const ", "static ", "mod ", "def ",
+        "class ", "function ", "interface ", "let ",
+    ];
+    sites
+        .iter()
+        .find(|site| {
+            std::fs::read_to_string(&site.path)
+                .ok()
+                .and_then(|text| text.lines().nth(site.line - 1).map(str::to_string))
+                .is_some_and(|line| {
+                    DEFINITION_KEYWORDS
+                        .iter()
+                        .any(|kw| line.contains(&format!("{}{}", kw, old_name)))
+                })
+        })
+        .or_else(|| sites.first())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_rename_spec() {
+        assert_eq!(
+            parse_rename_spec("OldName=NewName").unwrap(),
+            ("OldName".to_string(), "NewName".to_string())
+        );
+        assert!(parse_rename_spec("OldName").is_err());
+        assert!(parse_rename_spec("Old=1New").is_err());
+        assert!(parse_rename_spec("Same=Same").is_err());
+    }
+
+    #[test]
+    fn test_find_in_text_matches_whole_words_only() {
+        let text = "let foo = foo_bar(foo);\nfoo();";
+        assert_eq!(find_in_text(text, "foo"), vec![(0, 4), (0, 18), (1, 0)]);
+    }
+
+    #[test]
+    fn test_find_occurrences_skips_target_and_non_source() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "struct Widget;\nfn f(_: Widget) {}\n").unwrap();
+        std::fs::write(dir.path().join("target/gen.rs"), "Widget").unwrap();
+        std::fs::write(dir.path().join("README.md"), "Widget").unwrap();
+
+        let sites = find_occurrences(dir.path(), "Widget");
+        assert_eq!(
+            format_sites(&sites, dir.path()),
+            vec!["src/lib.rs:1:8", "src/lib.rs:2:9"]
+        );
+        assert_eq!(pick_rename_anchor(&sites, "Widget"), sites.first());
+
+        let before_files = snapshot_files(&sites);
+        std::fs::write(dir.path().join("src/lib.rs"), "struct Gadget;\nfn f(_: Widget) {}\n").unwrap();
+        assert_eq!(changed_sites(&sites, &before_files, "Widget", "Gadget"), vec![sites[0].clone()]);
+    }
+
+    #[test]
+    fn test_changed_sites_ignore_columns_shifted_by_earlier_renames() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "fn f(a: Widget, b: Widget, c: Widget) {}\n").unwrap();
+        let sites = find_occurrences(dir.path(), "Widget");
+        let before_files = snapshot_files(&sites);
+
+        // Only the first and last are renamed; the middle one moves but is untouched.
+        std::fs::write(&path, "fn f(a: LongerGadget, b: Widget, c: LongerGadget) {}\n").unwrap();
+        assert_eq!(
+            changed_sites(&sites, &before_files, "Widget", "LongerGadget"),
+            vec![sites[0].clone(), sites[2].clone()]
+        );
+    }
+}
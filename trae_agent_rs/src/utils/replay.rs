@@ -0,0 +1,266 @@
+//! # Re-executing Replays
+//!
+//! `trae replay --re-execute` replays the tool calls of a recorded run without asking the model
+//! again, against a copy of the project. Calls of deterministic tools (see `ToolDeterminism`)
+//! run again; side-effecting calls — shell commands, tools that reach outside the project —
+//! return their recorded result instead. Comparing the re-run results with the recorded ones
+//! shows where a changed project would have led the run somewhere else.
+//!
+//! A tool is only re-run if both its current contract and the contract recorded in the
+//! trajectory allow it. Paths under the recorded project are rewritten to the copy.
+
+use crate::tools::{AgentToolResult, ToolContext, ToolDeterminism, ToolExecutor, ToolRegistry};
+use crate::utils::trajectory_recorder::Trajectory;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Where the result of a replayed call comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaySource {
+    /// The call ran again against the project copy.
+    ReExecuted,
+    /// The recorded result was returned without running the call.
+    Recorded,
+}
+
+/// One tool call of the recorded run, as replayed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayedCall {
+    pub step: u32,
+    pub tool: String,
+    pub tool_call_id: String,
+    pub source: ReplaySource,
+    pub result: AgentToolResult,
+    /// Whether a re-executed call's result differs from the recorded one.
+    pub diverged: bool,
+}
+
+/// Whether a call may run again, given the contract recorded for its tool (if any) and the
+/// tool's current contract.
+pub fn may_re_execute(recorded: Option<ToolDeterminism>, current: ToolDeterminism) -> bool {
+    current == ToolDeterminism::Deterministic && recorded != Some(ToolDeterminism::SideEffecting)
+}
+
+/// Replays every tool call of `trajectory` in order.
+///
+/// # Arguments
+/// * `registry`: The tools available for re-running calls; calls of other tools are recorded.
+/// * `recorded_root`: The project the run was recorded in, if known.
+/// * `project_root`: The copy of the project the calls run against.
+pub async fn re_execute(
+    trajectory: &Trajectory,
+    registry: &ToolRegistry,
+    recorded_root: Option<&str>,
+    project_root: &str,
+) -> Vec<ReplayedCall> {
+    let executor = ToolExecutor::new(registry.get_all_tools_arc());
+    let context = ToolContext::for_project(project_root);
+    let rebase = recorded_root.filter(|root| *root != project_root);
+    let mut replayed = Vec::new();
+
+    for step in &trajectory.steps {
+        let results = step.tool_results.as_deref().unwrap_or_default();
+        for call in step.tool_calls_made.iter().flatten() {
+            let tool_name = &call.function.name;
+            let recorded = results.iter().find(|r| r.tool_call_id == call.id).cloned();
+            let deterministic = registry.get_tool(tool_name).is_some_and(|tool| {
+                may_re_execute(trajectory.header.tool_contracts.get(tool_name).copied(), tool.determinism())
+            });
+
+            let (source, result, diverged) = if deterministic {
+                let mut call = call.clone();
+                if let Some(from) = rebase {
+                    call.function.arguments = rebase_arguments(&call.function.arguments, from, project_root);
+                }
+                let result = executor.execute_tool_call(&call, &context).await;
+                let diverged = match (&recorded, rebase) {
+                    (Some(recorded), Some(from)) => !same_result(recorded, &with_root(&result, project_root, from)),
+                    (Some(recorded), None) => !same_result(recorded, &result),
+                    (None, _) => true,
+                };
+                (ReplaySource::ReExecuted, result, diverged)
+            } else {
+                let result = recorded.unwrap_or_else(|| AgentToolResult {
+                    tool_call_id: call.id.clone(),
+                    success: false,
+                    result: None,
+                    error: Some("No result was recorded for this call.".to_string()),
+                    duration_ms: None,
+                    approval: None,
+                });
+                (ReplaySource::Recorded, result, false)
+            };
+            replayed.push(ReplayedCall {
+                step: step.step_number,
+                tool: tool_name.clone(),
+                tool_call_id: call.id.clone(),
+                source,
+                result,
+                diverged,
+            });
+        }
+    }
+    replayed
+}
+
+/// Rewrites string arguments that are paths under `from` to the same paths under `to`.
+fn rebase_arguments(arguments: &str, from: &str, to: &str) -> String {
+    fn rebase(value: &mut Value, from: &str, to: &str) {
+        match value {
+            Value::String(s) => {
+                if let Some(rest) = s.strip_prefix(from) {
+                    if rest.is_empty() || rest.starts_with('/') {
+                        *s = format!("{}{}", to, rest);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| rebase(item, from, to)),
+            Value::Object(map) => map.values_mut().for_each(|item| rebase(item, from, to)),
+            _ => {}
+        }
+    }
+    match serde_json::from_str::<Value>(arguments) {
+        Ok(mut value) => {
+            rebase(&mut value, from, to);
+            value.to_string()
+        }
+        Err(_) => arguments.to_string(),
+    }
+}
+
+/// `result` with the project copy's root written as the recorded root, for comparison.
+fn with_root(result: &AgentToolResult, copy: &str, recorded: &str) -> AgentToolResult {
+    let swap = |text: &Option<String>| text.as_ref().map(|t| t.replace(copy, recorded));
+    AgentToolResult {
+        tool_call_id: result.tool_call_id.clone(),
+        success: result.success,
+        result: swap(&result.result),
+        error: swap(&result.error),
+        duration_ms: result.duration_ms,
+        approval: result.approval.clone(),
+    }
+}
+
+fn same_result(a: &AgentToolResult, b: &AgentToolResult) -> bool {
+    a.success == b.success && a.result == b.result && a.error == b.error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::base_agent::{AgentState, AgentStep};
+    use crate::llm::base_client::ToolCall;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn tool_call(id: &str, name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            tool_type: "function".to_string(),
+            function: crate::llm::base_client::ToolCallFunction {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+
+    fn recorded(id: &str, success: bool, output: &str) -> AgentToolResult {
+        AgentToolResult {
+            tool_call_id: id.to_string(),
+            success,
+            result: success.then(|| output.to_string()),
+            error: (!success).then(|| output.to_string()),
+            duration_ms: None,
+            approval: None,
+        }
+    }
+
+    fn step(step_number: u32, calls: Vec<ToolCall>, results: Vec<AgentToolResult>) -> AgentStep {
+        AgentStep {
+            step_number,
+            state: AgentState::Completed,
+            messages_to_llm: None,
+            llm_response: None,
+            tool_calls_made: Some(calls),
+            tool_results: Some(results),
+            reflection: None,
+            error: None,
+            duration_ms: 0,
+            route: None,
+            validation_error: None,
+            diff_stat: None,
+            file_changes: Vec::new(),
+            llm_duration_ms: None,
+            step_extensions: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_calls_rerun_and_side_effects_are_recorded() {
+        let copy = tempdir().unwrap();
+        let root = copy.path().to_str().unwrap();
+        let mut trajectory: Trajectory = serde_json::from_value(json!({
+            "header": {
+                "version": "1.0",
+                "task": "Fix the bug",
+                "provider": "openai",
+                "model": "gpt-4o",
+                "max_steps": 30,
+                "timestamp": 0,
+                "extra_args": null,
+                "tool_contracts": {"bash": "side_effecting", "str_replace_based_edit_tool": "deterministic"}
+            },
+            "steps": [],
+            "success": true,
+            "final_result": null,
+            "total_tokens": null
+        }))
+        .unwrap();
+        trajectory.steps = vec![
+            step(
+                1,
+                vec![
+                    tool_call("c1", "bash", json!({"command": "touch marker"})),
+                    tool_call(
+                        "c2",
+                        "str_replace_based_edit_tool",
+                        json!({"command": "create", "path": "/recorded/notes.txt", "file_text": "hello\n"}),
+                    ),
+                ],
+                vec![
+                    recorded("c1", true, "ran"),
+                    recorded("c2", true, "File created successfully at: /recorded/notes.txt"),
+                ],
+            ),
+            step(
+                2,
+                vec![tool_call(
+                    "c3",
+                    "str_replace_based_edit_tool",
+                    json!({"command": "str_replace", "path": "/recorded/notes.txt", "old_str": "hello", "new_str": "bye"}),
+                )],
+                vec![recorded("c3", false, "old_str not found")],
+            ),
+        ];
+
+        let calls = re_execute(&trajectory, &ToolRegistry::default(), Some("/recorded"), root).await;
+        let summary: Vec<(&str, ReplaySource, bool)> =
+            calls.iter().map(|c| (c.tool_call_id.as_str(), c.source, c.diverged)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("c1", ReplaySource::Recorded, false),
+                ("c2", ReplaySource::ReExecuted, false),
+                ("c3", ReplaySource::ReExecuted, true),
+            ]
+        );
+        assert_eq!(calls[0].result.result.as_deref(), Some("ran"));
+        assert!(!copy.path().join("marker").exists());
+        assert_eq!(std::fs::read_to_string(copy.path().join("notes.txt")).unwrap(), "bye\n");
+
+        // A contract recorded as side-effecting is honoured even if the tool changed since.
+        assert!(!may_re_execute(Some(ToolDeterminism::SideEffecting), ToolDeterminism::Deterministic));
+        assert!(may_re_execute(None, ToolDeterminism::Deterministic));
+    }
+}
@@ -0,0 +1,232 @@
+//! # Issue Reproduction
+//!
+//! Optional first phase of a bug-fixing run: before touching the code, the agent writes a
+//! script that reproduces the issue in a scratch directory, runs it, and records the command
+//! with the `record_reproduction` tool. When the agent later signals completion, the recorded
+//! command is re-run as part of verification and must now succeed.
+//!
+//! This mirrors the WRITE_TEST / VERIFY_TEST / VERIFY_FIX steps Lakeview tags in trajectories.
+//! Every run's output is kept as a log file in the scratch directory. The command runs where
+//! the run's other commands do, in the sandbox container if there is one.
+
+use crate::tools::execution::ExecutionEnvironment;
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long a single run of the reproduction command may take before it is killed.
+const REPRODUCTION_TIMEOUT: Duration = Duration::from_secs(300);
+/// Maximum number of output bytes quoted back to the agent.
+const MAX_QUOTED_OUTPUT: usize = 4000;
+
+/// The outcome of one run of the reproduction command.
+#[derive(Debug, Clone)]
+pub struct ReproductionRun {
+    /// Exit code of the command, or `None` if it was killed or terminated by a signal.
+    pub exit_code: Option<i32>,
+    /// Whether the command was killed after exceeding the timeout.
+    pub timed_out: bool,
+    /// Combined stdout and stderr, truncated to the last few thousand bytes.
+    pub output: String,
+    /// Log file holding the complete output.
+    pub log_path: PathBuf,
+}
+
+impl ReproductionRun {
+    /// Whether the run indicates the issue is fixed (exit status 0).
+    pub fn passed(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// State of the reproduction phase for one task.
+#[derive(Debug)]
+pub struct Reproduction {
+    project_path: PathBuf,
+    scratch_dir: PathBuf,
+    command: Mutex<Option<String>>,
+    runs: Mutex<usize>,
+}
+
+impl Reproduction {
+    /// Creates the reproduction phase for a project, with a fresh scratch directory outside
+    /// the repository so reproduction scripts never end up in the patch.
+    pub fn new(project_path: impl Into<PathBuf>) -> Result<Self> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let scratch_dir = std::env::temp_dir().join(format!(
+            "trae-reproduction-{}-{}",
+            std::process::id(),
+            stamp
+        ));
+        Self::in_dir(project_path, scratch_dir)
+    }
+
+    /// Creates the reproduction phase with its scripts and logs in `scratch_dir`, e.g. below
+    /// the run's scratch directory (see `utils::scratch`), which git ignores.
+    pub fn in_dir(project_path: impl Into<PathBuf>, scratch_dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&scratch_dir).with_context(|| {
+            format!("Failed to create scratch directory {}", scratch_dir.display())
+        })?;
+        Ok(Self {
+            project_path: project_path.into(),
+            scratch_dir,
+            command: Mutex::new(None),
+            runs: Mutex::new(0),
+        })
+    }
+
+    /// The directory reproduction scripts should be written to.
+    pub fn scratch_dir(&self) -> &Path {
+        &self.scratch_dir
+    }
+
+    /// The recorded reproduction command, if the agent has recorded one.
+    pub fn command(&self) -> Option<String> {
+        self.command.lock().unwrap().clone()
+    }
+
+    /// Records (or replaces) the reproduction command.
+    pub fn record(&self, command: &str) {
+        *self.command.lock().unwrap() = Some(command.trim().to_string());
+    }
+
+    /// Log files of all runs of the reproduction command so far, in run order.
+    pub fn log_files(&self) -> Vec<PathBuf> {
+        let runs = *self.runs.lock().unwrap();
+        (1..=runs)
+            .map(|n| self.scratch_dir.join(format!("reproduction-run-{}.log", n)))
+            .filter(|p| p.is_file())
+            .collect()
+    }
+
+    /// Instructions for the agent describing the reproduction phase.
+    pub fn instructions(&self) -> String {
+        format!(
+            "[Reproduction phase]: Before changing any project file, write a script that reproduces the issue \
+            in the scratch directory {scratch} (not inside the repository). The script must exit with status 0 \
+            only when the issue is fixed and with a non-zero status while it is present. Run it to confirm it \
+            fails, then record the command that runs it with the `record_reproduction` tool (commands run from \
+            the project root). Only then start working on the fix. When you signal completion, the recorded \
+            command is run again and must succeed.",
+            scratch = self.scratch_dir.display()
+        )
+    }
+
+    /// Runs `command` from the project root in `execution`, killing it after the timeout. The
+    /// complete output is written to a numbered log file in the scratch directory.
+    pub fn run(&self, command: &str, execution: &dyn ExecutionEnvironment) -> Result<ReproductionRun> {
+        let run_number = {
+            let mut runs = self.runs.lock().unwrap();
+            *runs += 1;
+            *runs
+        };
+        let log_path = self
+            .scratch_dir
+            .join(format!("reproduction-run-{}.log", run_number));
+        let log = File::create(&log_path)
+            .with_context(|| format!("Failed to create {}", log_path.display()))?;
+
+        let mut shell = execution.shell_command(command, Some(&self.project_path), &[]);
+        let mut child = shell
+            .as_std_mut()
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .spawn()
+            .with_context(|| format!("Failed to run reproduction command '{}'", command))?;
+
+        let started = Instant::now();
+        let mut timed_out = false;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+            if started.elapsed() >= REPRODUCTION_TIMEOUT {
+                timed_out = true;
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+
+        let output = std::fs::read(&log_path).unwrap_or_default();
+        let tail_start = output.len().saturating_sub(MAX_QUOTED_OUTPUT);
+        Ok(ReproductionRun {
+            exit_code: status.and_then(|s| s.code()),
+            timed_out,
+            output: String::from_utf8_lossy(&output[tail_start..]).into_owned(),
+            log_path,
+        })
+    }
+
+    /// Re-runs the recorded command in `execution` as part of verifying a fix.
+    ///
+    /// # Returns
+    /// The passing run, or a message for the agent explaining why verification failed.
+    pub fn verify(&self, execution: &dyn ExecutionEnvironment) -> std::result::Result<ReproductionRun, String> {
+        let command = self.command().ok_or_else(|| {
+            "ERROR! No reproduction command has been recorded. Write a script that reproduces the issue in \
+            the scratch directory, run it, and record its command with the `record_reproduction` tool before \
+            signaling completion."
+                .to_string()
+        })?;
+        let run = self.run(&command, execution).map_err(|e| {
+            format!("ERROR! Could not re-run the reproduction command `{}`: {:#}", command, e)
+        })?;
+        if run.passed() {
+            return Ok(run);
+        }
+        let status = if run.timed_out {
+            format!("timed out after {}s", REPRODUCTION_TIMEOUT.as_secs())
+        } else {
+            run.exit_code
+                .map_or("was terminated by a signal".to_string(), |c| format!("exited with status {}", c))
+        };
+        Err(format!(
+            "ERROR! The reproduction command `{}` {}, so the issue is not fixed yet. Output:\n{}",
+            command, status, run.output
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::execution::HostEnvironment;
+
+    #[test]
+    fn test_verify_requires_recorded_command() {
+        let project = tempfile::tempdir().unwrap();
+        let reproduction = Reproduction::new(project.path()).unwrap();
+        assert!(reproduction.scratch_dir().is_dir());
+        assert!(reproduction.instructions().contains("record_reproduction"));
+        let err = reproduction.verify(&HostEnvironment).unwrap_err();
+        assert!(err.contains("No reproduction command"));
+        std::fs::remove_dir_all(reproduction.scratch_dir()).unwrap();
+    }
+
+    #[test]
+    fn test_verify_reruns_command_from_project_root() {
+        let project = tempfile::tempdir().unwrap();
+        let reproduction = Reproduction::new(project.path()).unwrap();
+        reproduction.record("test -f fixed.txt || { echo still broken; exit 3; }");
+
+        let err = reproduction.verify(&HostEnvironment).unwrap_err();
+        assert!(err.contains("exited with status 3"), "{}", err);
+        assert!(err.contains("still broken"));
+
+        std::fs::write(project.path().join("fixed.txt"), "").unwrap();
+        let run = reproduction.verify(&HostEnvironment).unwrap();
+        assert!(run.passed());
+        assert!(run.log_path.ends_with("reproduction-run-2.log"));
+        assert_eq!(reproduction.log_files().len(), 2);
+        std::fs::remove_dir_all(reproduction.scratch_dir()).unwrap();
+    }
+}
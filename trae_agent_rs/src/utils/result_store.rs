@@ -0,0 +1,134 @@
+//! # Tool Result Store
+//!
+//! Keeps the complete output of tool results that were too long to show the LLM in full.
+//! Each stored output gets a handle (`result_id`) that the `read_more` tool pages through,
+//! so the truncated tail is not lost.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default number of characters returned per `read_more` page.
+pub const DEFAULT_PAGE_CHARS: usize = 8000;
+
+/// One page of a stored result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultPage {
+    /// The text of the page.
+    pub text: String,
+    /// Character offset of the page within the result.
+    pub offset: usize,
+    /// Character offset just past the page; equal to `total` on the last page.
+    pub end: usize,
+    /// Total length of the result, in characters.
+    pub total: usize,
+}
+
+/// Directory-backed storage of full tool outputs for one run.
+#[derive(Debug)]
+pub struct ResultStore {
+    dir: PathBuf,
+    next_id: Mutex<u32>,
+}
+
+impl ResultStore {
+    /// Creates a store writing to `dir`, which is created if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create result directory {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            next_id: Mutex::new(1),
+        })
+    }
+
+    /// Creates a store in a fresh directory: next to `trajectory_path` when the run records a
+    /// trajectory (`<name>.results/`), otherwise in the system temp directory.
+    pub fn for_run(trajectory_path: Option<&Path>) -> Result<Self> {
+        Self::new(run_dir(trajectory_path, "results"))
+    }
+
+    /// The directory the store writes to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Stores `content` and returns its handle (e.g., "r1").
+    pub fn store(&self, content: &str) -> Result<String> {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            let id = format!("r{}", *next_id);
+            *next_id += 1;
+            id
+        };
+        let path = self.path_for(&id);
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to store tool result in {}", path.display()))?;
+        Ok(id)
+    }
+
+    /// Reads up to `limit` characters of the result `id`, starting at character `offset`.
+    pub fn read(&self, id: &str, offset: usize, limit: usize) -> Result<ResultPage> {
+        if !id.starts_with('r') || id.len() < 2 || !id[1..].bytes().all(|b| b.is_ascii_digit()) {
+            anyhow::bail!("Invalid result_id '{}'", id);
+        }
+        let content = std::fs::read_to_string(self.path_for(id))
+            .with_context(|| format!("No stored result with result_id '{}'", id))?;
+        let total = content.chars().count();
+        let offset = offset.min(total);
+        let text: String = content.chars().skip(offset).take(limit.max(1)).collect();
+        let end = offset + text.chars().count();
+        Ok(ResultPage {
+            text,
+            offset,
+            end,
+            total,
+        })
+    }
+
+    fn path_for(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.txt", id))
+    }
+}
+
+/// Directory for per-run data of the given kind: `<trajectory>.<kind>/` next to the
+/// trajectory file, or a fresh `trae-<kind>-<pid>-<ms>` directory in the system temp
+/// directory when the run records no trajectory.
+pub fn run_dir(trajectory_path: Option<&Path>, kind: &str) -> PathBuf {
+    match trajectory_path {
+        Some(path) => path.with_extension(kind),
+        None => {
+            let stamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            std::env::temp_dir().join(format!("trae-{}-{}-{}", kind, std::process::id(), stamp))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_page_through_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ResultStore::new(dir.path().join("results")).unwrap();
+        let id = store.store("héllo wörld").unwrap();
+        assert_eq!(id, "r1");
+
+        let page = store.read(&id, 0, 5).unwrap();
+        assert_eq!(page.text, "héllo");
+        assert_eq!((page.offset, page.end, page.total), (0, 5, 11));
+        let last = store.read(&id, 6, 100).unwrap();
+        assert_eq!(last.text, "wörld");
+        assert_eq!(last.end, last.total);
+
+        assert!(store.read("r2", 0, 5).is_err());
+        assert!(store.read("../secret", 0, 5).is_err());
+        assert!(dir.path().join("results/r1.txt").is_file());
+    }
+}
@@ -0,0 +1,89 @@
+//! # Run Scratch Directory
+//!
+//! Every `trae run` gets `.trae/runs/<id>/scratch/` in the project, and the agent is told to
+//! put reproduction scripts, temporary files and notes there instead of in the repository.
+//! The run directory holds a `.gitignore` that ignores everything in it, so the scratch files
+//! never show up in `git status` or the generated patch; writes there are not counted against
+//! the file write cap either.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory of the per-run directories, relative to the project root.
+pub const RUNS_DIR: &str = ".trae/runs";
+
+/// The scratch directory of one run.
+#[derive(Debug, Clone)]
+pub struct RunScratch {
+    id: String,
+    dir: PathBuf,
+}
+
+impl RunScratch {
+    /// Creates `<project_root>/.trae/runs/<run_id>/scratch/`.
+    pub fn create(project_root: &Path, run_id: &str) -> Result<Self> {
+        let run_dir = project_root.join(RUNS_DIR).join(run_id);
+        let dir = run_dir.join("scratch");
+        std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create scratch directory {}", dir.display()))?;
+        let gitignore = run_dir.join(".gitignore");
+        std::fs::write(&gitignore, "# Created by trae: run artifacts are never part of the project.\n*\n")
+            .with_context(|| format!("Failed to write {}", gitignore.display()))?;
+        Ok(Self { id: run_id.to_string(), dir })
+    }
+
+    /// The run's identifier (see `run_id`).
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Paragraph of the system prompt telling the agent about the directory.
+    pub fn prompt_note(&self) -> String {
+        format!(
+            "Your scratch directory for this run is {}. Put reproduction scripts, temporary files, \
+            logs and notes there instead of in the repository; it is excluded from the patch.",
+            self.dir.display()
+        )
+    }
+}
+
+/// Identifier of a run for its directory: the trajectory file's name, if there is one, else
+/// the start time and process id.
+pub fn run_id(trajectory_path: Option<&Path>) -> String {
+    if let Some(stem) = trajectory_path.and_then(Path::file_stem).and_then(|s| s.to_str()) {
+        return stem.to_string();
+    }
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    format!("run-{}-{}", secs, std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_scratch_directory_is_ignored_by_git() {
+        let dir = tempfile::tempdir().unwrap();
+        Command::new("git").arg("init").arg("-q").current_dir(dir.path()).status().unwrap();
+        assert_eq!(run_id(Some(Path::new("/tmp/trajectories/fix-parser.json"))), "fix-parser");
+        assert!(run_id(None).starts_with("run-"));
+
+        let scratch = RunScratch::create(dir.path(), "fix-parser").unwrap();
+        assert_eq!(scratch.dir(), dir.path().join(".trae/runs/fix-parser/scratch"));
+        assert!(scratch.prompt_note().contains(&scratch.dir().display().to_string()));
+        std::fs::write(scratch.dir().join("repro.py"), "print(1)\n").unwrap();
+        std::fs::write(dir.path().join("main.py"), "print(2)\n").unwrap();
+
+        let status = Command::new("git")
+            .args(["status", "--porcelain", "--untracked-files=all"])
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&status.stdout), "?? main.py\n");
+    }
+}
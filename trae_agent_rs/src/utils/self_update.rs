@@ -0,0 +1,459 @@
+//! # Self-Update
+//!
+//! `trae self-update` replaces the running binary with the latest GitHub release, for users who
+//! installed a release binary rather than through cargo. The archive for this platform is
+//! downloaded together with its published SHA-256 checksum and only installed if they match.
+//! The checksum comes from the same release as the archive, so it catches corrupted or
+//! truncated downloads but not a tampered release: there is no signature to check against a
+//! key built into the binary. Install through cargo or a package manager if that matters.
+//!
+//! With `check_for_updates` in the config, runs also print a notice when a newer release
+//! exists. The check is made at most once a day; its result is cached in
+//! `~/.trae/update_check.json`.
+
+use crate::config::NetworkConfig;
+use crate::utils::http::build_http_client;
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Latest release of the project, in the GitHub REST API.
+pub const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/sxhxliang/trae-agent/releases/latest";
+/// Names the binary may have inside a release archive.
+const BINARY_NAMES: &[&str] = &["trae", "trae_rust_agent", "trae-agent"];
+/// The C library the running binary was built for, as in the last part of a target triple.
+pub const TARGET_ENV: &str = if cfg!(target_env = "musl") {
+    "musl"
+} else if cfg!(target_env = "msvc") {
+    "msvc"
+} else {
+    "gnu"
+};
+/// How often the passive notice looks for a new release.
+const CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// The version of the running binary.
+pub fn current_version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+/// A GitHub release.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+/// A file attached to a release.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// The release version without a leading `v`.
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    /// Whether the release is newer than the running binary.
+    pub fn is_newer(&self) -> bool {
+        is_newer(self.version(), current_version())
+    }
+
+    /// The archive or binary built for `os` and `arch` (as in `std::env::consts`) and `env`
+    /// (see `TARGET_ENV`). Its name must end in the full target triple, such as
+    /// `x86_64-unknown-linux-musl`, or in `{os}-{arch}`, before an archive or `.exe` extension;
+    /// signatures and other files are never picked.
+    pub fn asset_for(&self, os: &str, arch: &str, env: &str) -> Option<&ReleaseAsset> {
+        let targets = match os {
+            "linux" => vec![format!("{}-unknown-linux-{}", arch, env), format!("linux-{}", arch)],
+            "macos" => vec![format!("{}-apple-darwin", arch), format!("macos-{}", arch), format!("darwin-{}", arch)],
+            "windows" => vec![format!("{}-pc-windows-{}", arch, env), format!("windows-{}", arch)],
+            other => vec![format!("{}-{}", other, arch)],
+        };
+        self.assets.iter().find(|asset| {
+            let name = asset.name.to_lowercase();
+            asset_kind(&name).is_some_and(|(_, stem)| {
+                targets.iter().any(|target| {
+                    stem.strip_suffix(target.as_str())
+                        .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with(['-', '_', '.']))
+                })
+            })
+        })
+    }
+
+    /// The checksum file covering `asset`: `<asset>.sha256`, else a combined `SHA256SUMS`-style file.
+    fn checksum_asset_for(&self, asset: &ReleaseAsset) -> Option<&ReleaseAsset> {
+        let own = format!("{}.sha256", asset.name);
+        self.assets.iter().find(|a| a.name == own).or_else(|| {
+            self.assets.iter().find(|a| {
+                let name = a.name.to_lowercase();
+                is_checksum_file(&name) && !name.ends_with(".sha256")
+            })
+        })
+    }
+}
+
+/// How a release asset holds the binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AssetKind {
+    TarGz,
+    Zip,
+    /// The executable itself.
+    Binary,
+}
+
+/// The kind of the asset named `name`, with the name without its extension. A name without an
+/// extension is a bare executable; unknown extensions (`.sig`, `.tar.xz`, ...) give `None`.
+fn asset_kind(name: &str) -> Option<(AssetKind, &str)> {
+    let extensions = [
+        (".tar.gz", AssetKind::TarGz),
+        (".tgz", AssetKind::TarGz),
+        (".zip", AssetKind::Zip),
+        (".exe", AssetKind::Binary),
+    ];
+    for (extension, kind) in extensions {
+        if let Some(stem) = name.strip_suffix(extension) {
+            return Some((kind, stem));
+        }
+    }
+    let last_part = name.rsplit('-').next().unwrap_or(name);
+    (!last_part.contains('.')).then_some((AssetKind::Binary, name))
+}
+
+fn is_checksum_file(name: &str) -> bool {
+    name.ends_with(".sha256") || name.contains("sha256sums") || name.contains("checksums")
+}
+
+/// Compares dotted versions numerically (`0.10.0` > `0.9.3`); pre-release suffixes are ignored.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    fn parts(version: &str) -> Vec<u64> {
+        version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|p| p.parse().unwrap_or(0))
+            .collect()
+    }
+    parts(candidate) > parts(current)
+}
+
+/// An HTTP client for GitHub with the configured network settings. GitHub rejects requests
+/// without a `User-Agent`.
+pub fn http_client(network: &NetworkConfig) -> Result<Client> {
+    let mut headers = HeaderMap::new();
+    let user_agent = format!("trae-agent/{}", current_version());
+    headers.insert(USER_AGENT, HeaderValue::from_str(&user_agent)?);
+    build_http_client(network, headers)
+}
+
+/// Fetches the release described at `url` (normally `LATEST_RELEASE_URL`).
+pub async fn fetch_release(client: &Client, url: &str) -> Result<Release> {
+    let response = client
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .with_context(|| format!("Failed to query {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Failed to query {}", url))?;
+    response.json().await.context("Unexpected release metadata")
+}
+
+/// Downloads `asset` and its published checksum, and returns the binary it contains once the
+/// checksum matches. Only the checksum is checked; whoever can publish the release can also
+/// publish a matching checksum (see the module docs).
+pub async fn download_verified(client: &Client, release: &Release, asset: &ReleaseAsset) -> Result<Vec<u8>> {
+    let checksum_asset = release.checksum_asset_for(asset).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Release {} publishes no SHA-256 checksum for {}; refusing to install an unverified binary",
+            release.tag_name,
+            asset.name
+        )
+    })?;
+    let checksums = String::from_utf8(download(client, &checksum_asset.browser_download_url).await?)
+        .context("Checksum file is not text")?;
+    let expected = expected_checksum(&checksums, &asset.name)
+        .ok_or_else(|| anyhow::anyhow!("{} has no checksum for {}", checksum_asset.name, asset.name))?;
+
+    let archive = download(client, &asset.browser_download_url).await?;
+    let actual = format!("{:x}", Sha256::digest(&archive));
+    if !actual.eq_ignore_ascii_case(&expected) {
+        anyhow::bail!(
+            "Checksum mismatch for {}: expected {}, got {}; the download was not installed",
+            asset.name,
+            expected,
+            actual
+        );
+    }
+    extract_binary(&asset.name, archive)
+}
+
+async fn download(client: &Client, url: &str) -> Result<Vec<u8>> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to download {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Failed to download {}", url))?;
+    Ok(response.bytes().await.with_context(|| format!("Failed to download {}", url))?.to_vec())
+}
+
+/// The checksum for `file_name` in a checksum file: either a bare hash, or `<hash>  <name>` lines.
+fn expected_checksum(checksums: &str, file_name: &str) -> Option<String> {
+    let lines: Vec<Vec<&str>> = checksums
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|fields| !fields.is_empty())
+        .collect();
+    if let [fields] = lines.as_slice() {
+        if fields.len() == 1 {
+            return Some(fields[0].to_string());
+        }
+    }
+    lines
+        .iter()
+        .find(|fields| fields.get(1).is_some_and(|name| name.trim_start_matches('*') == file_name))
+        .map(|fields| fields[0].to_string())
+}
+
+/// The binary in a downloaded asset: the `trae` executable of a `.tar.gz` or `.zip` archive,
+/// or the asset itself if it is a bare executable. Other archive types are refused.
+fn extract_binary(asset_name: &str, data: Vec<u8>) -> Result<Vec<u8>> {
+    let is_binary = |path: &Path| {
+        path.file_stem()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| BINARY_NAMES.contains(&n))
+    };
+    match asset_kind(&asset_name.to_lowercase()).map(|(kind, _)| kind) {
+        Some(AssetKind::Binary) => return Ok(data),
+        Some(AssetKind::TarGz) => {
+            let mut archive = tar::Archive::new(GzDecoder::new(data.as_slice()));
+            for entry in archive.entries().context("Invalid release archive")? {
+                let mut entry = entry.context("Invalid release archive")?;
+                if is_binary(&entry.path()?) && entry.header().entry_type().is_file() {
+                    let mut binary = Vec::new();
+                    entry.read_to_end(&mut binary)?;
+                    return Ok(binary);
+                }
+            }
+        }
+        Some(AssetKind::Zip) => {
+            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).context("Invalid release archive")?;
+            for index in 0..archive.len() {
+                let mut entry = archive.by_index(index).context("Invalid release archive")?;
+                if is_binary(Path::new(entry.name())) && entry.is_file() && !entry.is_symlink() {
+                    let mut binary = Vec::new();
+                    entry.read_to_end(&mut binary)?;
+                    return Ok(binary);
+                }
+            }
+        }
+        None => anyhow::bail!("{} is not an archive type trae can unpack; install it by hand", asset_name),
+    }
+    anyhow::bail!("{} does not contain a trae binary", asset_name)
+}
+
+/// Replaces the executable at `target` with `binary`. The new file is written next to it and
+/// renamed over it, so an interrupted update leaves the old binary in place.
+pub fn install_binary(binary: &[u8], target: &Path) -> Result<()> {
+    let staged = target.with_extension("new");
+    std::fs::write(&staged, binary).with_context(|| format!("Failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+    // Windows cannot replace a running executable, but it can rename it out of the way.
+    #[cfg(windows)]
+    {
+        let old = target.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(target, &old).with_context(|| format!("Failed to move {} aside", target.display()))?;
+    }
+    std::fs::rename(&staged, target).with_context(|| format!("Failed to replace {}", target.display()))
+}
+
+/// Result of the last passive update check.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UpdateCheckCache {
+    checked_at: u64,
+    latest_version: Option<String>,
+}
+
+fn cache_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".trae").join("update_check.json"))
+}
+
+/// The notice to print if a newer release exists. Queries GitHub at most once a day, with a
+/// short timeout; any failure means no notice.
+pub async fn update_notice(client: &Client) -> Option<String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let path = cache_path()?;
+    let cached: Option<UpdateCheckCache> = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok());
+    let latest = match cached {
+        Some(cache) if now.saturating_sub(cache.checked_at) < CHECK_INTERVAL_SECS => cache.latest_version,
+        _ => {
+            let release = tokio::time::timeout(Duration::from_secs(3), fetch_release(client, LATEST_RELEASE_URL))
+                .await
+                .ok()
+                .and_then(Result::ok);
+            let cache = UpdateCheckCache {
+                checked_at: now,
+                latest_version: release.map(|r| r.version().to_string()),
+            };
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            let _ = std::fs::write(&path, serde_json::to_string(&cache).unwrap_or_default());
+            cache.latest_version
+        }
+    }?;
+    is_newer(&latest, current_version()).then(|| {
+        format!(
+            "A new version of trae is available: {} (current: {}). Run `trae self-update` to install it.",
+            latest,
+            current_version()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn tar_gz(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, name, contents).unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn release(names: &[&str]) -> Release {
+        Release {
+            tag_name: "v9.0.0".to_string(),
+            html_url: String::new(),
+            assets: names
+                .iter()
+                .map(|name| ReleaseAsset {
+                    name: name.to_string(),
+                    browser_download_url: format!("https://example.com/{}", name),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_asset_for_matches_the_target_triple() {
+        let release = release(&[
+            "SHA256SUMS",
+            "trae-v9.0.0-x86_64-unknown-linux-gnu.tar.gz.sig",
+            "trae-v9.0.0-x86_64-unknown-linux-musl.tar.gz",
+            "trae-v9.0.0-x86_64-unknown-linux-gnu.tar.gz",
+            "trae-v9.0.0-aarch64-apple-darwin.tar.gz.asc",
+            "trae-v9.0.0-aarch64-apple-darwin",
+            "trae-windows-x86_64.zip",
+        ]);
+        let name = |os, arch, env| release.asset_for(os, arch, env).map(|asset| asset.name.as_str());
+        // Whichever comes first, the C library of the running binary decides.
+        assert_eq!(name("linux", "x86_64", "gnu"), Some("trae-v9.0.0-x86_64-unknown-linux-gnu.tar.gz"));
+        assert_eq!(name("linux", "x86_64", "musl"), Some("trae-v9.0.0-x86_64-unknown-linux-musl.tar.gz"));
+        // Signatures are skipped for the bare binary after them.
+        assert_eq!(name("macos", "aarch64", ""), Some("trae-v9.0.0-aarch64-apple-darwin"));
+        assert_eq!(name("windows", "x86_64", "msvc"), Some("trae-windows-x86_64.zip"));
+        assert_eq!(name("linux", "aarch64", "gnu"), None);
+    }
+
+    #[tokio::test]
+    async fn test_download_verified_checks_checksum() {
+        let server = MockServer::start().await;
+        let archive = tar_gz("trae-v9.0.0/trae", b"new binary");
+        let asset_name = "trae-v9.0.0-x86_64-unknown-linux-gnu.tar.gz";
+        let checksum = format!("{:x}  {}\n", Sha256::digest(&archive), asset_name);
+        Mock::given(method("GET")).and(path("/releases/latest")).respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "tag_name": "v9.0.0",
+            "html_url": "https://example.com/releases/v9.0.0",
+            "assets": [
+                {"name": "SHA256SUMS", "browser_download_url": format!("{}/SHA256SUMS", server.uri())},
+                {"name": asset_name, "browser_download_url": format!("{}/linux", server.uri())}
+            ]
+        }))).mount(&server).await;
+        Mock::given(method("GET")).and(path("/SHA256SUMS")).respond_with(ResponseTemplate::new(200).set_body_string(checksum)).mount(&server).await;
+        Mock::given(method("GET")).and(path("/linux")).respond_with(ResponseTemplate::new(200).set_body_bytes(archive)).mount(&server).await;
+        Mock::given(method("GET")).and(path("/tampered")).respond_with(ResponseTemplate::new(200).set_body_bytes(b"evil".to_vec())).mount(&server).await;
+
+        let client = Client::new();
+        let release = fetch_release(&client, &format!("{}/releases/latest", server.uri())).await.unwrap();
+        assert!(release.is_newer());
+        let asset = release.assets[1].clone();
+        assert_eq!(download_verified(&client, &release, &asset).await.unwrap(), b"new binary");
+
+        let tampered = ReleaseAsset {
+            browser_download_url: format!("{}/tampered", server.uri()),
+            ..asset
+        };
+        let error = download_verified(&client, &release, &tampered).await.unwrap_err();
+        assert!(error.to_string().contains("Checksum mismatch"), "{}", error);
+    }
+
+    #[test]
+    fn test_extract_binary_unpacks_known_archives_only() {
+        let tar = tar_gz("trae-v9.0.0/trae", b"from tar");
+        assert_eq!(extract_binary("trae-linux-x86_64.tar.gz", tar).unwrap(), b"from tar");
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("README.md", options).unwrap();
+        std::io::Write::write_all(&mut zip, b"docs").unwrap();
+        zip.start_file("trae-v9.0.0/trae.exe", options).unwrap();
+        std::io::Write::write_all(&mut zip, b"from zip").unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+        assert_eq!(extract_binary("trae-windows-x86_64.zip", zip).unwrap(), b"from zip");
+
+        assert_eq!(extract_binary("trae-x86_64-pc-windows-msvc.exe", b"raw".to_vec()).unwrap(), b"raw");
+        assert_eq!(extract_binary("trae-aarch64-apple-darwin", b"raw".to_vec()).unwrap(), b"raw");
+        let error = extract_binary("trae-linux-x86_64.tar.xz", b"xz".to_vec()).unwrap_err();
+        assert!(error.to_string().contains("not an archive type"), "{}", error);
+        let error = extract_binary("trae-linux-x86_64.tar.gz", tar_gz("README.md", b"docs")).unwrap_err();
+        assert!(error.to_string().contains("does not contain a trae binary"), "{}", error);
+    }
+
+    #[test]
+    fn test_install_binary_replaces_the_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("trae");
+        std::fs::write(&target, b"old binary").unwrap();
+        install_binary(b"new binary", &target).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"new binary");
+        assert!(!target.with_extension("new").exists());
+    }
+
+    #[test]
+    fn test_versions_and_checksum_files() {
+        assert!(is_newer("0.10.0", "0.9.3"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert_eq!(expected_checksum("abc123\n", "anything").as_deref(), Some("abc123"));
+        assert_eq!(expected_checksum("abc  a.tar.gz\ndef *b.zip\n", "b.zip").as_deref(), Some("def"));
+    }
+}
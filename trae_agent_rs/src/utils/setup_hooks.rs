@@ -0,0 +1,118 @@
+//! # Environment Setup Hooks
+//!
+//! Runs the project's `setup_commands` (e.g., `pip install -e .`, `npm ci`) once before the
+//! agent starts, and condenses their outcome into a note for the system prompt, so the agent
+//! does not spend its first steps working out how to install dependencies. The same runner is
+//! used for `teardown_commands` at the end of a run (see `utils::cleanup`). Both run where the
+//! agent's commands do, in the sandbox container if the run has one.
+
+use crate::tools::execution::ExecutionEnvironment;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// Number of trailing output lines kept for a failed command.
+const FAILURE_OUTPUT_LINES: usize = 15;
+
+/// The result of one setup or teardown command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetupOutcome {
+    pub command: String,
+    pub success: bool,
+    /// Exit code, `None` if the command could not be started, was killed or timed out.
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    /// Combined stdout and stderr, or the reason the command did not run to completion.
+    pub output: String,
+}
+
+/// Runs `commands` in order with `sh -c` in `working_dir` of `execution`. A failing command
+/// does not stop the ones after it; each is given at most `timeout`.
+pub async fn run_hook_commands(
+    commands: &[String],
+    working_dir: &Path,
+    timeout: Duration,
+    execution: &dyn ExecutionEnvironment,
+) -> Vec<SetupOutcome> {
+    let mut outcomes = Vec::with_capacity(commands.len());
+    for command in commands {
+        let start = Instant::now();
+        let child = execution
+            .shell_command(command, Some(working_dir), &[])
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        let (success, exit_code, output) = match tokio::time::timeout(timeout, child).await {
+            Ok(Ok(output)) => {
+                let mut combined = String::from_utf8_lossy(&output.stdout).to_string();
+                combined.push_str(&String::from_utf8_lossy(&output.stderr));
+                (output.status.success(), output.status.code(), combined)
+            }
+            Ok(Err(e)) => (false, None, format!("could not be started: {}", e)),
+            Err(_) => (false, None, format!("timed out after {}s", timeout.as_secs())),
+        };
+        outcomes.push(SetupOutcome {
+            command: command.clone(),
+            success,
+            exit_code,
+            duration: start.elapsed(),
+            output,
+        });
+    }
+    outcomes
+}
+
+/// Summarizes `outcomes` for the system prompt: one line per command, with the end of the
+/// output for failures.
+pub fn setup_summary(outcomes: &[SetupOutcome]) -> String {
+    let mut summary = String::from(
+        "Environment setup: these commands were run in the project before you started. \
+        Do not repeat the successful ones; work around or fix the failed ones only if the task needs them.",
+    );
+    for outcome in outcomes {
+        let seconds = outcome.duration.as_secs();
+        if outcome.success {
+            summary.push_str(&format!("\n- `{}` succeeded ({}s)", outcome.command, seconds));
+            continue;
+        }
+        let status = match outcome.exit_code {
+            Some(code) => format!("failed with exit code {}", code),
+            None => "failed".to_string(),
+        };
+        summary.push_str(&format!("\n- `{}` {} ({}s). End of output:", outcome.command, status, seconds));
+        let lines: Vec<&str> = outcome.output.lines().filter(|l| !l.trim().is_empty()).collect();
+        for line in &lines[lines.len().saturating_sub(FAILURE_OUTPUT_LINES)..] {
+            summary.push_str("\n    ");
+            summary.push_str(line);
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::execution::HostEnvironment;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_setup_commands_run_in_order_and_are_summarized() {
+        let dir = tempdir().unwrap();
+        let commands = vec![
+            "echo installed > marker.txt".to_string(),
+            "echo building; echo 'error: missing libssl' >&2; exit 3".to_string(),
+            "sleep 5".to_string(),
+        ];
+        let outcomes = run_hook_commands(&commands, dir.path(), Duration::from_secs(1), &HostEnvironment).await;
+        assert!(dir.path().join("marker.txt").exists());
+        assert_eq!(
+            outcomes.iter().map(|o| (o.success, o.exit_code)).collect::<Vec<_>>(),
+            vec![(true, Some(0)), (false, Some(3)), (false, None)]
+        );
+
+        let summary = setup_summary(&outcomes);
+        assert!(summary.contains("\n- `echo installed > marker.txt` succeeded (0s)"));
+        assert!(summary.contains("failed with exit code 3 (0s). End of output:\n    building\n    error: missing libssl"));
+        assert!(summary.contains("`sleep 5` failed (1s). End of output:\n    timed out after 1s"));
+    }
+}
@@ -0,0 +1,120 @@
+//! # Snippet Store
+//!
+//! Named text fragments the agent stashes during a run (an error trace, the original version
+//! of a function it is about to rewrite, ...) and recalls later with `get_snippet`, instead of
+//! keeping them in its context the whole time.
+
+use super::result_store::run_dir;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Maximum length of a snippet name.
+const MAX_NAME_LEN: usize = 64;
+
+/// Directory-backed storage of named snippets for one run.
+#[derive(Debug)]
+pub struct SnippetStore {
+    dir: PathBuf,
+}
+
+impl SnippetStore {
+    /// Creates a store writing to `dir`, which is created if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create snippet directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Creates a store next to `trajectory_path` (`<name>.snippets/`), or in the system temp
+    /// directory when the run records no trajectory.
+    pub fn for_run(trajectory_path: Option<&Path>) -> Result<Self> {
+        Self::new(run_dir(trajectory_path, "snippets"))
+    }
+
+    /// The directory the store writes to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Saves `content` under `name`, replacing any snippet of that name. Returns whether a
+    /// snippet was replaced.
+    pub fn save(&self, name: &str, content: &str) -> Result<bool> {
+        let path = self.path_for(name)?;
+        let replaced = path.exists();
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to save snippet '{}' to {}", name, path.display()))?;
+        Ok(replaced)
+    }
+
+    /// Returns the snippet saved under `name`.
+    pub fn get(&self, name: &str) -> Result<String> {
+        let path = self.path_for(name)?;
+        if !path.is_file() {
+            let names = self.names()?;
+            if names.is_empty() {
+                anyhow::bail!("No snippet named '{}'. No snippets have been saved yet.", name);
+            }
+            anyhow::bail!("No snippet named '{}'. Saved snippets: {}", name, names.join(", "));
+        }
+        std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read snippet '{}' from {}", name, path.display()))
+    }
+
+    /// Names of the saved snippets, sorted.
+    pub fn names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to list snippets in {}", self.dir.display()))?
+        {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "txt") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    names.push(stem.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn path_for(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAME_LEN
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid {
+            anyhow::bail!(
+                "Invalid snippet name '{}': use up to {} letters, digits, '_', '-' or '.'",
+                name,
+                MAX_NAME_LEN
+            );
+        }
+        Ok(self.dir.join(format!("{}.txt", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_get_and_list_snippets() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnippetStore::new(dir.path().join("snippets")).unwrap();
+        assert!(store.get("trace").unwrap_err().to_string().contains("No snippets have been saved"));
+
+        assert!(!store.save("trace", "Traceback ...").unwrap());
+        assert!(store.save("trace", "Traceback (most recent call last)").unwrap());
+        store.save("orig_parse.v1", "fn parse() {}").unwrap();
+        assert_eq!(store.get("trace").unwrap(), "Traceback (most recent call last)");
+        assert_eq!(store.names().unwrap(), vec!["orig_parse.v1", "trace"]);
+
+        let err = store.get("missing").unwrap_err().to_string();
+        assert!(err.contains("Saved snippets: orig_parse.v1, trace"), "{}", err);
+        assert!(store.save("../escape", "x").is_err());
+        assert!(store.save("", "x").is_err());
+    }
+}
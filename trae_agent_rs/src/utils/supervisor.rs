@@ -0,0 +1,256 @@
+//! # Backend Supervision
+//!
+//! Long-lived helper processes (language servers, tool servers) can crash in the middle of a
+//! run. A `Supervisor` owns one such backend: before each use it checks that the backend is
+//! still healthy, restarts it with exponential backoff when it is not, and gives up after a
+//! bounded number of restarts. Callers get a `ToolError::BackendUnavailable` instead of an
+//! opaque I/O error, so a crashed helper fails one tool call rather than the whole run.
+
+use crate::tools::ToolError;
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tracing::{info, warn};
+
+/// Starts a backend.
+#[async_trait]
+pub trait BackendLauncher: Send + Sync {
+    type Handle: BackendHandle;
+
+    /// Human-readable name used in logs and errors, e.g. "language server 'rust-analyzer'".
+    fn name(&self) -> String;
+
+    /// Starts the backend and brings it to a usable state.
+    async fn launch(&self) -> anyhow::Result<Self::Handle>;
+}
+
+/// A running backend.
+pub trait BackendHandle: Send {
+    /// Whether the backend can still serve requests (e.g., its process has not exited).
+    fn is_healthy(&mut self) -> bool;
+}
+
+/// How a `Supervisor` restarts its backend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartPolicy {
+    /// Restarts allowed over the supervisor's lifetime before the backend is given up on.
+    pub max_restarts: u32,
+    /// Delay before the first restart; doubled for each further restart.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between restarts.
+    pub max_backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before restart number `restart` (1-based).
+    pub fn backoff(&self, restart: u32) -> Duration {
+        let factor = 2u32.saturating_pow(restart.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+struct SupervisorState<H> {
+    handle: Option<H>,
+    /// Whether the backend has been launched successfully at least once.
+    started: bool,
+    restarts: u32,
+    last_error: Option<String>,
+}
+
+/// Owns one backend and keeps it running.
+pub struct Supervisor<L: BackendLauncher> {
+    launcher: L,
+    policy: RestartPolicy,
+    state: Mutex<SupervisorState<L::Handle>>,
+}
+
+impl<L: BackendLauncher> Supervisor<L> {
+    /// Creates a supervisor; the backend is started on first use.
+    pub fn new(launcher: L, policy: RestartPolicy) -> Self {
+        Self {
+            launcher,
+            policy,
+            state: Mutex::new(SupervisorState {
+                handle: None,
+                started: false,
+                restarts: 0,
+                last_error: None,
+            }),
+        }
+    }
+
+    /// Number of restarts performed so far.
+    pub async fn restarts(&self) -> u32 {
+        self.state.lock().await.restarts
+    }
+
+    /// Returns the running backend, starting or restarting it if needed.
+    ///
+    /// # Returns
+    /// Exclusive access to the backend, or `ToolError::BackendUnavailable` if it could not be
+    /// (re)started or has used up its restarts.
+    pub async fn acquire(&self) -> Result<MappedMutexGuard<'_, L::Handle>, ToolError> {
+        let mut state = self.state.lock().await;
+        if let Some(handle) = state.handle.as_mut() {
+            if !handle.is_healthy() {
+                warn!("The {} stopped responding; it will be restarted", self.launcher.name());
+                state.handle = None;
+                state.last_error = Some("the backend crashed".to_string());
+            }
+        }
+        if state.handle.is_none() {
+            if state.started || state.last_error.is_some() {
+                if state.restarts >= self.policy.max_restarts {
+                    return Err(self.unavailable(&state, "no restarts left"));
+                }
+                state.restarts += 1;
+                let delay = self.policy.backoff(state.restarts);
+                info!(
+                    "Restarting the {} in {:?} (restart {} of {})",
+                    self.launcher.name(),
+                    delay,
+                    state.restarts,
+                    self.policy.max_restarts
+                );
+                tokio::time::sleep(delay).await;
+            }
+            match self.launcher.launch().await {
+                Ok(handle) => {
+                    state.handle = Some(handle);
+                    state.started = true;
+                }
+                Err(e) => {
+                    state.last_error = Some(e.to_string());
+                    return Err(self.unavailable(&state, "it failed to start"));
+                }
+            }
+        }
+        Ok(MutexGuard::map(state, |state| {
+            state.handle.as_mut().expect("backend handle was just set")
+        }))
+    }
+
+    /// Stops supervising and hands back the running backend, if any, e.g. for a clean shutdown.
+    pub fn into_handle(self) -> Option<L::Handle> {
+        self.state.into_inner().handle
+    }
+
+    fn unavailable(&self, state: &SupervisorState<L::Handle>, why: &str) -> ToolError {
+        let mut reason = why.to_string();
+        if let Some(last_error) = &state.last_error {
+            reason.push_str(&format!(" (last error: {})", last_error));
+        }
+        ToolError::BackendUnavailable {
+            backend: self.launcher.name(),
+            reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug)]
+    struct FakeBackend {
+        healthy: Arc<AtomicU32>,
+        generation: u32,
+    }
+
+    impl BackendHandle for FakeBackend {
+        fn is_healthy(&mut self) -> bool {
+            self.healthy.load(Ordering::SeqCst) == 1
+        }
+    }
+
+    struct FakeLauncher {
+        launches: AtomicU32,
+        fail_launches: bool,
+        healthy: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl BackendLauncher for FakeLauncher {
+        type Handle = FakeBackend;
+
+        fn name(&self) -> String {
+            "fake server".to_string()
+        }
+
+        async fn launch(&self) -> anyhow::Result<FakeBackend> {
+            let generation = self.launches.fetch_add(1, Ordering::SeqCst) + 1;
+            if self.fail_launches {
+                anyhow::bail!("exec failed");
+            }
+            self.healthy.store(1, Ordering::SeqCst);
+            Ok(FakeBackend {
+                healthy: self.healthy.clone(),
+                generation,
+            })
+        }
+    }
+
+    fn supervisor(fail_launches: bool) -> (Supervisor<FakeLauncher>, Arc<AtomicU32>) {
+        let healthy = Arc::new(AtomicU32::new(0));
+        let launcher = FakeLauncher {
+            launches: AtomicU32::new(0),
+            fail_launches,
+            healthy: healthy.clone(),
+        };
+        let policy = RestartPolicy {
+            max_restarts: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+        (Supervisor::new(launcher, policy), healthy)
+    }
+
+    #[tokio::test]
+    async fn test_crashed_backend_is_restarted_until_restarts_run_out() {
+        let (supervisor, healthy) = supervisor(false);
+        assert_eq!(supervisor.acquire().await.unwrap().generation, 1);
+        assert_eq!(supervisor.acquire().await.unwrap().generation, 1);
+
+        healthy.store(0, Ordering::SeqCst); // crash
+        assert_eq!(supervisor.acquire().await.unwrap().generation, 2);
+        healthy.store(0, Ordering::SeqCst);
+        assert_eq!(supervisor.acquire().await.unwrap().generation, 3);
+        assert_eq!(supervisor.restarts().await, 2);
+
+        healthy.store(0, Ordering::SeqCst);
+        let err = supervisor.acquire().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Backend 'fake server' is unavailable: no restarts left (last error: the backend crashed)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_launch_failures_are_structured_errors() {
+        let (supervisor, _) = supervisor(true);
+        let err = supervisor.acquire().await.unwrap_err();
+        assert!(matches!(err, ToolError::BackendUnavailable { ref backend, .. } if backend == "fake server"));
+        assert!(err.to_string().contains("exec failed"));
+        supervisor.acquire().await.unwrap_err();
+        supervisor.acquire().await.unwrap_err();
+        assert!(supervisor.acquire().await.unwrap_err().to_string().contains("no restarts left"));
+        assert_eq!(supervisor.launcher.launches.load(Ordering::SeqCst), 3);
+
+        let policy = RestartPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_millis(500));
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), Duration::from_secs(8));
+    }
+}
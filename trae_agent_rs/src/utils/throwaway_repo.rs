@@ -0,0 +1,114 @@
+//! # Throwaway Repository
+//!
+//! Patch saving, `must_patch` and checkpoints work on git diffs, so they are unavailable in a
+//! project that is not a git repository, such as an unzipped source drop. `trae run --init-git`
+//! creates a temporary repository there for the run: `git init` and a baseline commit of the
+//! project as it was, against which the run's changes are diffed. The repository (its `.git`
+//! directory) is removed when the run ends, leaving the project as a plain directory again.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::{debug, warn};
+
+/// Whether `dir` is inside a git work tree. False as well if git is not installed.
+pub fn is_git_work_tree(dir: &Path) -> bool {
+    Command::new("git")
+        .current_dir(dir)
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .output()
+        .is_ok_and(|output| output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true")
+}
+
+/// A repository created for one run; removed when dropped.
+#[derive(Debug)]
+pub struct ThrowawayRepo {
+    git_dir: PathBuf,
+}
+
+impl ThrowawayRepo {
+    /// Initializes a repository in `project_root` and commits the project's current files
+    /// (honouring a `.gitignore`) as the baseline.
+    pub fn init(project_root: &Path) -> Result<Self> {
+        let git_dir = project_root.join(".git");
+        if git_dir.exists() {
+            anyhow::bail!("{} already exists", git_dir.display());
+        }
+        git(project_root, &["init", "-q"])?;
+        let repo = Self { git_dir };
+        git(project_root, &["add", "-A"])?;
+        git(
+            project_root,
+            &[
+                "-c",
+                "user.name=Trae Agent",
+                "-c",
+                "user.email=trae-agent@localhost",
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "-q",
+                "--no-verify",
+                "--allow-empty",
+                "-m",
+                "Baseline of the project before the run",
+            ],
+        )?;
+        Ok(repo)
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+}
+
+impl Drop for ThrowawayRepo {
+    fn drop(&mut self) {
+        match std::fs::remove_dir_all(&self.git_dir) {
+            Ok(()) => debug!("Removed the throwaway repository {}", self.git_dir.display()),
+            Err(e) => warn!("Failed to remove the throwaway repository {}: {}", self.git_dir.display(), e),
+        }
+    }
+}
+
+fn git(project_root: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(project_root)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to execute git {} in {}", args.join(" "), project_root.display()))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed with status {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::git_utils::get_git_diff;
+
+    #[test]
+    fn test_throwaway_repo_diffs_against_baseline_and_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.py"), "print('hi')\n").unwrap();
+        assert!(!is_git_work_tree(dir.path()));
+
+        let repo = ThrowawayRepo::init(dir.path()).unwrap();
+        assert!(is_git_work_tree(dir.path()));
+        assert!(ThrowawayRepo::init(dir.path()).is_err(), "a repository is never initialized twice");
+        std::fs::write(dir.path().join("main.py"), "print('hello')\n").unwrap();
+        let diff = get_git_diff(dir.path().to_str().unwrap(), None).unwrap();
+        assert!(diff.contains("+print('hello')"), "{}", diff);
+
+        let git_dir = repo.git_dir().to_path_buf();
+        drop(repo);
+        assert!(!git_dir.exists());
+        assert_eq!(std::fs::read_to_string(dir.path().join("main.py")).unwrap(), "print('hello')\n");
+    }
+}
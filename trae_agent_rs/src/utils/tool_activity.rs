@@ -0,0 +1,106 @@
+//! # Tool Activity Lines
+//!
+//! Short, one-line descriptions of the tool calls an agent makes and of their results, for
+//! showing what the agent is doing in interactive sessions as it happens.
+
+use crate::llm::base_client::ToolCall;
+use crate::tools::AgentToolResult;
+use serde_json::Value;
+
+/// Longest argument value or result line shown, in characters.
+const MAX_PREVIEW_CHARS: usize = 100;
+
+/// Describes a tool call: `$ <command>` for shell commands, else the tool name and its
+/// arguments, e.g. `str_replace_based_edit_tool(command=view, path=src/main.rs)`.
+pub fn describe_call(call: &ToolCall) -> String {
+    let arguments: Value = serde_json::from_str(&call.function.arguments).unwrap_or(Value::Null);
+    if call.function.name == "bash" {
+        if let Some(command) = arguments.get("command").and_then(Value::as_str) {
+            return format!("$ {}", truncate(command.trim()));
+        }
+    }
+    let arguments = match &arguments {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| match value {
+                Value::String(s) => format!("{}={}", key, s),
+                other => format!("{}={}", key, other),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => call.function.arguments.clone(),
+    };
+    format!("{}({})", call.function.name, truncate(&arguments))
+}
+
+/// Describes a tool result by its first non-empty line, noting how many lines follow.
+pub fn describe_result(result: &AgentToolResult) -> String {
+    let text = if result.success {
+        result.result.as_deref()
+    } else {
+        result.error.as_deref().or(result.result.as_deref())
+    }
+    .unwrap_or_default();
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.next().map(truncate).unwrap_or_else(|| "(no output)".to_string());
+    let more = lines.count();
+    let status = if result.success { "ok" } else { "failed" };
+    if more == 0 {
+        format!("{}: {}", status, first)
+    } else {
+        format!("{}: {} (+{} more line{})", status, first, more, if more == 1 { "" } else { "s" })
+    }
+}
+
+/// The first line of `text`, cut to `MAX_PREVIEW_CHARS` characters.
+fn truncate(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    let cut = line.chars().count() > MAX_PREVIEW_CHARS || line.len() < text.trim_end().len();
+    let mut preview: String = line.chars().take(MAX_PREVIEW_CHARS).collect();
+    if cut {
+        preview.push('…');
+    }
+    preview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::base_client::ToolCallFunction;
+
+    fn call(name: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            tool_type: "function".to_string(),
+            function: ToolCallFunction {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_describe_calls_and_results() {
+        assert_eq!(describe_call(&call("bash", r#"{"command": "cargo test\n"}"#)), "$ cargo test");
+        assert_eq!(describe_call(&call("bash", r#"{"command": "cd src\nls"}"#)), "$ cd src…");
+        assert_eq!(
+            describe_call(&call("str_replace_based_edit_tool", r#"{"command": "view", "path": "src/main.rs"}"#)),
+            "str_replace_based_edit_tool(command=view, path=src/main.rs)"
+        );
+        assert_eq!(describe_call(&call("wait", r#"{"seconds": 5}"#)), "wait(seconds=5)");
+        let long = format!(r#"{{"command": "{}"}}"#, "x".repeat(150));
+        assert_eq!(describe_call(&call("bash", &long)).chars().count(), 2 + MAX_PREVIEW_CHARS + 1);
+
+        let result = |success: bool, result: Option<&str>, error: Option<&str>| AgentToolResult {
+            tool_call_id: "call_1".to_string(),
+            success,
+            result: result.map(str::to_string),
+            error: error.map(str::to_string),
+            duration_ms: None,
+            approval: None,
+        };
+        assert_eq!(describe_result(&result(true, Some("\nline one\nline two\n"), None)), "ok: line one (+1 more line)");
+        assert_eq!(describe_result(&result(true, Some(""), None)), "ok: (no output)");
+        assert_eq!(describe_result(&result(false, None, Some("Permission denied"))), "failed: Permission denied");
+    }
+}
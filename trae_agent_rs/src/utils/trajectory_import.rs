@@ -0,0 +1,523 @@
+//! # Trajectory Import
+//!
+//! Converts trajectory files written by the Python implementation of Trae Agent into
+//! the schema used by the Rust `TrajectoryRecorder`, so tools that consume trajectories
+//! can work with files from either implementation during the migration.
+
+use crate::agent::base_agent::{AgentState, AgentStep};
+use crate::llm::base_client::{
+    LLMMessage, LLMResponse, LLMResponseChoice, LLMUsage, MessageRole, ToolCall, ToolCallFunction,
+};
+use crate::tools::AgentToolResult;
+use crate::utils::trajectory_recorder::{Trajectory, TrajectoryHeader};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Version string written into the header of imported trajectories.
+const IMPORTED_TRAJECTORY_VERSION: &str = "1.0";
+
+#[derive(Deserialize, Debug, Default)]
+struct PyTrajectory {
+    #[serde(default)]
+    task: String,
+    #[serde(default)]
+    start_time: String,
+    #[serde(default)]
+    end_time: String,
+    #[serde(default)]
+    provider: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    max_steps: u32,
+    #[serde(default)]
+    llm_interactions: Vec<PyLLMInteraction>,
+    #[serde(default)]
+    agent_steps: Vec<PyAgentStep>,
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    final_result: Option<String>,
+    #[serde(default)]
+    execution_time: f64,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyLLMInteraction {
+    response: PyLLMResponse,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyAgentStep {
+    step_number: u32,
+    #[serde(default)]
+    timestamp: String,
+    #[serde(default)]
+    state: String,
+    #[serde(default)]
+    llm_messages: Option<Vec<PyMessage>>,
+    #[serde(default)]
+    llm_response: Option<PyLLMResponse>,
+    #[serde(default)]
+    tool_calls: Option<Vec<PyToolCall>>,
+    #[serde(default)]
+    tool_results: Option<Vec<PyToolResult>>,
+    #[serde(default)]
+    reflection: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyMessage {
+    role: String,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_call: Option<PyToolCall>,
+    #[serde(default)]
+    tool_result: Option<PyToolResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyLLMResponse {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    finish_reason: Option<String>,
+    #[serde(default)]
+    usage: Option<PyUsage>,
+    #[serde(default)]
+    tool_calls: Option<Vec<PyToolCall>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyUsage {
+    #[serde(default)]
+    input_tokens: Option<u32>,
+    #[serde(default)]
+    output_tokens: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyToolCall {
+    call_id: String,
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct PyToolResult {
+    call_id: String,
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    result: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Parses an ISO-8601 timestamp as written by Python's `datetime.isoformat()` into
+/// milliseconds since the Unix epoch. Naive timestamps are treated as UTC.
+pub(crate) fn parse_iso_timestamp_ms(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.split_once('T')?;
+    let mut date_parts = date.split('-').map(|p| p.parse::<i64>());
+    let (year, month, day) = (
+        date_parts.next()?.ok()?,
+        date_parts.next()?.ok()?,
+        date_parts.next()?.ok()?,
+    );
+    // Drop any timezone suffix; the offset is ignored.
+    let time = time.split(['+', 'Z']).next().unwrap_or(time);
+    let time = time.rsplit_once('-').map_or(time, |(t, _)| t);
+    let (hms, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let mut hms_parts = hms.split(':').map(|p| p.parse::<i64>());
+    let (hour, minute, second) = (
+        hms_parts.next()?.ok()?,
+        hms_parts.next()?.ok()?,
+        hms_parts.next().unwrap_or(Ok(0)).ok()?,
+    );
+    let millis: i64 = format!("{:0<3}", &fraction[..fraction.len().min(3)]).parse().ok()?;
+
+    // Days from civil date (Howard Hinnant's algorithm).
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let total_ms = ((days * 86400 + hour * 3600 + minute * 60 + second) * 1000) + millis;
+    u64::try_from(total_ms).ok()
+}
+
+fn convert_state(state: &str) -> AgentState {
+    match state {
+        "thinking" => AgentState::Thinking,
+        "calling_tool" => AgentState::CallingTool,
+        "reflecting" => AgentState::Reflecting,
+        "completed" => AgentState::Completed,
+        "error" => AgentState::Failed,
+        _ => AgentState::Initializing,
+    }
+}
+
+fn convert_tool_call(call: &PyToolCall) -> ToolCall {
+    let arguments = match &call.arguments {
+        Value::String(s) => s.clone(),
+        Value::Null => "{}".to_string(),
+        other => other.to_string(),
+    };
+    ToolCall {
+        id: call.call_id.clone(),
+        tool_type: "function".to_string(),
+        function: ToolCallFunction {
+            name: call.name.clone(),
+            arguments,
+        },
+    }
+}
+
+fn convert_tool_result(result: &PyToolResult) -> AgentToolResult {
+    AgentToolResult {
+        tool_call_id: result.call_id.clone(),
+        success: result.success,
+        result: result.result.clone(),
+        error: result.error.clone(),
+        duration_ms: None,
+        approval: None,
+    }
+}
+
+fn convert_message(message: &PyMessage) -> LLMMessage {
+    if let Some(tool_result) = &message.tool_result {
+        return LLMMessage {
+            role: MessageRole::Tool,
+            content: tool_result
+                .result
+                .clone()
+                .or_else(|| tool_result.error.clone())
+                .or_else(|| message.content.clone()),
+            name: None,
+            tool_calls: None,
+            tool_call_id: Some(tool_result.call_id.clone()),
+        };
+    }
+    let role = match message.role.as_str() {
+        "system" => MessageRole::System,
+        "assistant" => MessageRole::Assistant,
+        "tool" => MessageRole::Tool,
+        _ => MessageRole::User,
+    };
+    LLMMessage {
+        role,
+        content: message.content.clone(),
+        name: None,
+        tool_calls: message
+            .tool_call
+            .as_ref()
+            .map(|call| vec![convert_tool_call(call)]),
+        tool_call_id: None,
+    }
+}
+
+fn convert_usage(usage: &PyUsage) -> LLMUsage {
+    let prompt_tokens = usage.input_tokens.unwrap_or(0);
+    let completion_tokens = usage.output_tokens.unwrap_or(0);
+    LLMUsage {
+        prompt_tokens,
+        completion_tokens: Some(completion_tokens),
+        total_tokens: prompt_tokens + completion_tokens,
+    }
+}
+
+fn convert_response(response: &PyLLMResponse, step_number: u32, created: u64) -> LLMResponse {
+    LLMResponse {
+        id: format!("imported-step-{}", step_number),
+        object: "chat.completion".to_string(),
+        created,
+        model: response.model.clone().unwrap_or_default(),
+        choices: vec![LLMResponseChoice {
+            index: 0,
+            message: LLMMessage {
+                role: MessageRole::Assistant,
+                content: response.content.clone(),
+                name: None,
+                tool_calls: response
+                    .tool_calls
+                    .as_ref()
+                    .map(|calls| calls.iter().map(convert_tool_call).collect()),
+                tool_call_id: None,
+            },
+            finish_reason: response.finish_reason.clone(),
+        }],
+        usage: response.usage.as_ref().map(convert_usage),
+        rate_limit: None,
+    }
+}
+
+fn convert_trajectory(py: PyTrajectory) -> Trajectory {
+    let start_ms = parse_iso_timestamp_ms(&py.start_time);
+    let end_ms = parse_iso_timestamp_ms(&py.end_time);
+    let step_times: Vec<Option<u64>> = py
+        .agent_steps
+        .iter()
+        .map(|s| parse_iso_timestamp_ms(&s.timestamp))
+        .collect();
+
+    let steps = py
+        .agent_steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let this_ms = step_times[i];
+            let next_ms = step_times.get(i + 1).copied().flatten().or(end_ms);
+            let duration_ms = match (this_ms, next_ms) {
+                (Some(a), Some(b)) if b >= a => (b - a) as u128,
+                _ => 0,
+            };
+            AgentStep {
+                step_number: step.step_number,
+                state: convert_state(&step.state),
+                messages_to_llm: step
+                    .llm_messages
+                    .as_ref()
+                    .map(|msgs| msgs.iter().map(convert_message).collect()),
+                llm_response: step.llm_response.as_ref().map(|r| {
+                    convert_response(r, step.step_number, this_ms.unwrap_or(0) / 1000)
+                }),
+                tool_calls_made: step
+                    .tool_calls
+                    .as_ref()
+                    .map(|calls| calls.iter().map(convert_tool_call).collect()),
+                tool_results: step
+                    .tool_results
+                    .as_ref()
+                    .map(|results| results.iter().map(convert_tool_result).collect()),
+                reflection: step.reflection.clone(),
+                error: step.error.clone(),
+                duration_ms,
+                route: None,
+                validation_error: None,
+                diff_stat: None,
+                file_changes: Vec::new(),
+                llm_duration_ms: None,
+                step_extensions: Vec::new(),
+            }
+        })
+        .collect();
+
+    // Every LLM call is listed in `llm_interactions`; fall back to the steps if it is absent.
+    let usages: Vec<&PyUsage> = if py.llm_interactions.is_empty() {
+        py.agent_steps
+            .iter()
+            .filter_map(|s| s.llm_response.as_ref()?.usage.as_ref())
+            .collect()
+    } else {
+        py.llm_interactions
+            .iter()
+            .filter_map(|i| i.response.usage.as_ref())
+            .collect()
+    };
+    let total_tokens = (!usages.is_empty()).then(|| {
+        usages.iter().map(|u| convert_usage(u)).fold(
+            LLMUsage {
+                prompt_tokens: 0,
+                completion_tokens: Some(0),
+                total_tokens: 0,
+            },
+            |acc, u| LLMUsage {
+                prompt_tokens: acc.prompt_tokens + u.prompt_tokens,
+                completion_tokens: Some(
+                    acc.completion_tokens.unwrap_or(0) + u.completion_tokens.unwrap_or(0),
+                ),
+                total_tokens: acc.total_tokens + u.total_tokens,
+            },
+        )
+    });
+
+    let mut extra_args = HashMap::new();
+    extra_args.insert("imported_from".to_string(), "python".to_string());
+    if !py.start_time.is_empty() {
+        extra_args.insert("start_time".to_string(), py.start_time.clone());
+    }
+    if !py.end_time.is_empty() {
+        extra_args.insert("end_time".to_string(), py.end_time.clone());
+    }
+    extra_args.insert("execution_time".to_string(), py.execution_time.to_string());
+
+    Trajectory {
+        header: TrajectoryHeader {
+            version: IMPORTED_TRAJECTORY_VERSION.to_string(),
+            task: py.task,
+            provider: py.provider,
+            model: py.model,
+            max_steps: py.max_steps,
+            timestamp: start_ms.unwrap_or(0) / 1000,
+            extra_args: Some(extra_args),
+            environment: None,
+            tool_contracts: BTreeMap::new(),
+            labels: BTreeMap::new(),
+        },
+        steps,
+        success: py.success,
+        final_result: py.final_result,
+        total_tokens,
+        cleanup: None,
+        cost: None,
+        status: None,
+    }
+}
+
+/// Returns true if `value` looks like a trajectory written by the Python implementation.
+pub fn is_python_trajectory(value: &Value) -> bool {
+    value.get("header").is_none()
+        && (value.get("agent_steps").is_some() || value.get("llm_interactions").is_some())
+}
+
+/// Converts a parsed Python trajectory document into the Rust schema.
+pub fn convert_python_trajectory(value: Value) -> Result<Trajectory> {
+    let py: PyTrajectory =
+        serde_json::from_value(value).context("Not a valid Python trajectory file")?;
+    Ok(convert_trajectory(py))
+}
+
+/// Loads a trajectory written by either implementation, converting Python files on the fly.
+///
+/// # Arguments
+/// * `path`: Path to a trajectory JSON file.
+///
+/// # Returns
+/// The trajectory in the Rust schema, or an error if the file cannot be read or parsed.
+pub fn load_any_trajectory(path: &Path) -> Result<Trajectory> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read trajectory file {}", path.display()))?;
+    let value: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Trajectory file {} is not valid JSON", path.display()))?;
+    if is_python_trajectory(&value) {
+        convert_python_trajectory(value)
+    } else {
+        serde_json::from_value(value)
+            .with_context(|| format!("Trajectory file {} has an unknown schema", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn python_trajectory() -> Value {
+        json!({
+            "task": "Fix the bug",
+            "start_time": "2025-07-01T10:00:00.000000",
+            "end_time": "2025-07-01T10:00:05.500000",
+            "provider": "anthropic",
+            "model": "claude-sonnet",
+            "max_steps": 20,
+            "llm_interactions": [
+                {"timestamp": "2025-07-01T10:00:01", "provider": "anthropic", "model": "claude-sonnet",
+                 "input_messages": [], "response": {"content": "a", "usage": {"input_tokens": 100, "output_tokens": 10}}},
+                {"timestamp": "2025-07-01T10:00:03", "provider": "anthropic", "model": "claude-sonnet",
+                 "input_messages": [], "response": {"content": "b", "usage": {"input_tokens": 150, "output_tokens": 20}}}
+            ],
+            "agent_steps": [
+                {
+                    "step_number": 1,
+                    "timestamp": "2025-07-01T10:00:01.000000",
+                    "state": "calling_tool",
+                    "llm_messages": [
+                        {"role": "system", "content": "You are an agent."},
+                        {"role": "user", "content": "Fix the bug"}
+                    ],
+                    "llm_response": {
+                        "content": "Let me look.",
+                        "model": "claude-sonnet",
+                        "finish_reason": "tool_use",
+                        "usage": {"input_tokens": 100, "output_tokens": 10},
+                        "tool_calls": [{"call_id": "c1", "name": "bash", "arguments": {"command": "ls"}, "id": null}]
+                    },
+                    "tool_calls": [{"call_id": "c1", "name": "bash", "arguments": {"command": "ls"}, "id": null}],
+                    "tool_results": [{"call_id": "c1", "success": true, "result": "src", "error": null, "id": null}],
+                    "reflection": null,
+                    "error": null
+                },
+                {
+                    "step_number": 2,
+                    "timestamp": "2025-07-01T10:00:03.250000",
+                    "state": "completed",
+                    "llm_messages": [
+                        {"role": "user", "content": null, "tool_result": {"call_id": "c1", "success": true, "result": "src", "error": null}}
+                    ],
+                    "llm_response": {"content": "Done", "model": "claude-sonnet", "finish_reason": "end_turn", "usage": null, "tool_calls": null},
+                    "tool_calls": null,
+                    "tool_results": null,
+                    "reflection": null,
+                    "error": null
+                }
+            ],
+            "success": true,
+            "final_result": "Done",
+            "execution_time": 5.5
+        })
+    }
+
+    #[test]
+    fn test_parse_iso_timestamp_ms() {
+        assert_eq!(parse_iso_timestamp_ms("1970-01-01T00:00:01.5"), Some(1500));
+        assert_eq!(
+            parse_iso_timestamp_ms("2025-07-01T10:00:00+08:00"),
+            Some(1_751_364_000_000)
+        );
+        assert_eq!(parse_iso_timestamp_ms("not a date"), None);
+    }
+
+    #[test]
+    fn test_convert_python_trajectory() {
+        let value = python_trajectory();
+        assert!(is_python_trajectory(&value));
+        let trajectory = convert_python_trajectory(value).unwrap();
+
+        assert_eq!(trajectory.header.task, "Fix the bug");
+        assert_eq!(trajectory.header.timestamp, 1_751_364_000);
+        assert_eq!(trajectory.steps.len(), 2);
+        let first = &trajectory.steps[0];
+        assert_eq!(first.state, AgentState::CallingTool);
+        assert_eq!(first.duration_ms, 2250);
+        let call = &first.tool_calls_made.as_ref().unwrap()[0];
+        assert_eq!(call.function.arguments, r#"{"command":"ls"}"#);
+        assert_eq!(first.tool_results.as_ref().unwrap()[0].result.as_deref(), Some("src"));
+        assert_eq!(trajectory.steps[1].state, AgentState::Completed);
+        assert_eq!(trajectory.steps[1].duration_ms, 2250);
+        let tool_msg = &trajectory.steps[1].messages_to_llm.as_ref().unwrap()[0];
+        assert_eq!(tool_msg.role, MessageRole::Tool);
+        assert_eq!(tool_msg.tool_call_id.as_deref(), Some("c1"));
+
+        let tokens = trajectory.total_tokens.unwrap();
+        assert_eq!(tokens.prompt_tokens, 250);
+        assert_eq!(tokens.completion_tokens, Some(30));
+        assert_eq!(tokens.total_tokens, 280);
+    }
+
+    #[test]
+    fn test_load_any_trajectory_reads_both_schemas() {
+        let dir = tempdir().unwrap();
+        let py_path = dir.path().join("py.json");
+        std::fs::write(&py_path, python_trajectory().to_string()).unwrap();
+        let converted = load_any_trajectory(&py_path).unwrap();
+
+        let rs_path = dir.path().join("rs.json");
+        std::fs::write(&rs_path, serde_json::to_string(&converted).unwrap()).unwrap();
+        let reloaded = load_any_trajectory(&rs_path).unwrap();
+        assert_eq!(reloaded.header.task, "Fix the bug");
+        assert_eq!(reloaded.steps.len(), 2);
+    }
+}
@@ -1,15 +1,20 @@
 // trae-agent-rust/src/utils/trajectory_recorder.rs
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::{SystemTime, UNIX_EPOCH};
 use anyhow::{Context, Result}; // Using anyhow for error handling
 
-use crate::agent::base_agent::AgentStep; // Removed AgentState
+use crate::agent::base_agent::{AgentExecution, AgentStep}; // Removed AgentState
 use crate::llm::base_client::LLMUsage; // Removed LLMMessage, LLMResponse
+use crate::tools::{FinalReport, ToolDeterminism};
+use crate::utils::cleanup::CleanupReport;
+use crate::utils::cost::CostBreakdown;
+use crate::utils::environment::RunEnvironment;
 
 // Mirroring Python's TrajectoryHeader
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -21,6 +26,15 @@ pub struct TrajectoryHeader {
     pub max_steps: u32,
     pub timestamp: u64, // Unix timestamp
     pub extra_args: Option<HashMap<String, String>>,
+    /// Where the run was executed (platform, toolchain, repository commit, config hash).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<RunEnvironment>,
+    /// Determinism contract of each tool available to the run (see `ToolDeterminism`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tool_contracts: BTreeMap<String, ToolDeterminism>,
+    /// Labels of the run (`--label key=value`), for filtering runs in reports.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
 }
 
 // Mirroring Python's Trajectory (simplified, as steps are recorded incrementally)
@@ -31,12 +45,61 @@ pub struct Trajectory {
     pub success: bool,
     pub final_result: Option<String>,
     pub total_tokens: Option<LLMUsage>, // Assuming LLMUsage holds token counts
+    /// Teardown commands, processes and temp files handled when the run ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleanup: Option<CleanupReport>,
+    /// Tokens and estimated cost of the run, by step and by model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<CostBreakdown>,
+    /// How the recording ended; absent in trajectories written before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<TrajectoryStatus>,
+}
+
+/// How a recording ended.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrajectoryStatus {
+    /// The agent finished, successfully or not.
+    Completed,
+    /// The process panicked; the trajectory holds the steps recorded until then.
+    Crashed,
+}
+
+impl Trajectory {
+    /// The recorded run as an `AgentExecution`, for summarizing it after the fact. The run's
+    /// error, which is not recorded separately, is taken from its last failed step.
+    pub fn to_execution(&self) -> AgentExecution {
+        let error_message = if self.success {
+            None
+        } else {
+            self.steps.iter().rev().find_map(|step| step.error.clone())
+        };
+        AgentExecution {
+            task: self.header.task.clone(),
+            start_time: self.header.timestamp,
+            end_time: None,
+            steps: self.steps.clone(),
+            final_result: self.final_result.clone(),
+            success: self.success,
+            total_tokens_used: self.total_tokens.clone(),
+            error_message,
+            error_hint: None,
+            subagents: Vec::new(),
+            final_report: FinalReport::from_steps(&self.steps).map(Box::new),
+        }
+    }
 }
 
 pub struct TrajectoryRecorder {
     trajectory_path: PathBuf,
-    trajectory: Option<Trajectory>, // Holds the current trajectory being built
+    /// The trajectory being built, shared with the panic handler (`utils::crash`) so a crash
+    /// can still save it.
+    trajectory: Arc<Mutex<Option<Trajectory>>>,
     writer: Option<BufWriter<File>>, // For writing incrementally if needed, or just at the end
+    environment: Option<RunEnvironment>,
+    tool_contracts: BTreeMap<String, ToolDeterminism>,
+    labels: BTreeMap<String, String>,
 }
 
 impl TrajectoryRecorder {
@@ -63,16 +126,43 @@ impl TrajectoryRecorder {
 
         Ok(Self {
             trajectory_path: path,
-            trajectory: None,
+            trajectory: Arc::new(Mutex::new(None)),
             writer: None, // Initialize writer later if needed for incremental writes
+            environment: None,
+            tool_contracts: BTreeMap::new(),
+            labels: BTreeMap::new(),
         })
     }
 
     /// Gets the path where the trajectory will be saved.
+    #[allow(dead_code)]
     pub fn get_trajectory_path(&self) -> &Path {
         &self.trajectory_path
     }
 
+    fn active(&self) -> MutexGuard<'_, Option<Trajectory>> {
+        self.trajectory.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sets the environment metadata written into the header of subsequent recordings
+    /// (and of the current one, if recording has started).
+    pub fn set_environment(&mut self, environment: RunEnvironment) {
+        if let Some(trajectory) = self.active().as_mut() {
+            trajectory.header.environment = Some(environment.clone());
+        }
+        self.environment = Some(environment);
+    }
+
+    /// Sets the tool determinism contracts written into the header of subsequent recordings.
+    pub fn set_tool_contracts(&mut self, contracts: BTreeMap<String, ToolDeterminism>) {
+        self.tool_contracts = contracts;
+    }
+
+    /// Sets the labels written into the header of subsequent recordings.
+    pub fn set_labels(&mut self, labels: BTreeMap<String, String>) {
+        self.labels = labels;
+    }
+
     /// Starts recording a new trajectory.
     pub fn start_recording(
         &mut self,
@@ -82,7 +172,7 @@ impl TrajectoryRecorder {
         max_steps: u32,
         extra_args: Option<HashMap<String, String>>,
     ) -> Result<()> {
-        if self.trajectory.is_some() {
+        if self.active().is_some() {
             // Finalize previous recording if any, though ideally this shouldn't happen without finalize_recording being called
             self.finalize_recording(false, None, None)?;
         }
@@ -98,15 +188,22 @@ impl TrajectoryRecorder {
                 .unwrap_or_default()
                 .as_secs(),
             extra_args,
+            environment: self.environment.clone(),
+            tool_contracts: self.tool_contracts.clone(),
+            labels: self.labels.clone(),
         };
 
-        self.trajectory = Some(Trajectory {
+        *self.active() = Some(Trajectory {
             header,
             steps: Vec::new(),
             success: false,
             final_result: None,
             total_tokens: None,
+            cleanup: None,
+            cost: None,
+            status: None,
         });
+        crate::utils::crash::watch_trajectory(&self.trajectory_path, &self.trajectory);
 
         // If we want to write incrementally, setup writer here.
         // For now, we'll write everything in finalize_recording.
@@ -118,7 +215,7 @@ impl TrajectoryRecorder {
     /// This should be adapted to take parameters similar to Python's `record_agent_step`.
     /// For now, it takes a pre-constructed AgentStep.
     pub fn record_agent_step(&mut self, step: AgentStep) {
-        if let Some(trajectory) = self.trajectory.as_mut() {
+        if let Some(trajectory) = self.active().as_mut() {
             trajectory.steps.push(step);
         } else {
             tracing::warn!("Attempted to record step, but trajectory recording was not started.");
@@ -133,10 +230,12 @@ impl TrajectoryRecorder {
         final_result: Option<String>,
         total_tokens: Option<LLMUsage>, // Or whatever type represents token usage
     ) -> Result<()> {
-        if let Some(mut trajectory) = self.trajectory.take() {
+        let finished = self.active().take();
+        if let Some(mut trajectory) = finished {
             trajectory.success = success;
             trajectory.final_result = final_result;
             trajectory.total_tokens = total_tokens;
+            trajectory.status = Some(TrajectoryStatus::Completed);
 
             let file = OpenOptions::new()
                 .write(true)
@@ -159,6 +258,32 @@ impl TrajectoryRecorder {
     }
 }
 
+/// Reads the trajectory saved at `trajectory_path`, applies `update` to it and saves it
+/// again. Used for what is only known after the recording was finalized.
+pub fn update_saved_trajectory(trajectory_path: &Path, update: impl FnOnce(&mut Trajectory)) -> Result<()> {
+    let content = std::fs::read_to_string(trajectory_path)
+        .with_context(|| format!("Failed to read trajectory file: {:?}", trajectory_path))?;
+    let mut trajectory: Trajectory = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse trajectory file: {:?}", trajectory_path))?;
+    update(&mut trajectory);
+    let json = serde_json::to_string_pretty(&trajectory)
+        .with_context(|| format!("Failed to serialize trajectory to JSON: {:?}", trajectory_path))?;
+    std::fs::write(trajectory_path, json)
+        .with_context(|| format!("Failed to write trajectory file: {:?}", trajectory_path))?;
+    Ok(())
+}
+
+/// Adds the end-of-run cleanup report to the trajectory saved at `trajectory_path`. Cleanup
+/// happens after the agent has finished, so it is written into the finalized file.
+pub fn record_cleanup(trajectory_path: &Path, cleanup: &CleanupReport) -> Result<()> {
+    update_saved_trajectory(trajectory_path, |trajectory| trajectory.cleanup = Some(cleanup.clone()))
+}
+
+/// Adds the run's cost breakdown to the trajectory saved at `trajectory_path`.
+pub fn record_cost(trajectory_path: &Path, cost: &CostBreakdown) -> Result<()> {
+    update_saved_trajectory(trajectory_path, |trajectory| trajectory.cost = Some(cost.clone()))
+}
+
 // Example AgentStep structure that might be in base_agent.rs
 // Ensure this matches the actual AgentStep definition used.
 /*
@@ -227,6 +352,7 @@ mod tests {
                     completion_tokens: Some(5),
                     total_tokens: 15,
                 }),
+                rate_limit: None,
                 // system_fingerprint: None, // Removed, does not exist on LLMResponse
             }),
             tool_calls_made: None,
@@ -234,6 +360,12 @@ mod tests {
             reflection: None,
             error: None,
             duration_ms: 100,
+            route: None,
+            validation_error: None,
+            diff_stat: None,
+            file_changes: Vec::new(),
+            llm_duration_ms: None,
+            step_extensions: Vec::new(),
         }
     }
 
@@ -269,6 +401,8 @@ mod tests {
             10,
             None,
         )?;
+        let environment = RunEnvironment::capture(None, None);
+        recorder.set_environment(environment.clone());
 
         recorder.record_agent_step(create_dummy_agent_step(1));
         recorder.record_agent_step(create_dummy_agent_step(2));
@@ -283,6 +417,7 @@ mod tests {
 
         assert_eq!(saved_trajectory.header.task, "Test Task");
         assert_eq!(saved_trajectory.header.provider, "test_provider");
+        assert_eq!(saved_trajectory.header.environment, Some(environment));
         assert_eq!(saved_trajectory.steps.len(), 2);
         assert_eq!(saved_trajectory.steps[0].step_number, 1);
         assert!(saved_trajectory.success);
@@ -292,6 +427,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_record_cleanup_updates_saved_trajectory() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let trajectory_file = dir.path().join("cleanup_trajectory.json");
+        let mut recorder = TrajectoryRecorder::new(Some(trajectory_file.clone()))?;
+        recorder.start_recording("Test Task".to_string(), "test_provider".to_string(), "test_model".to_string(), 10, None)?;
+        recorder.record_agent_step(create_dummy_agent_step(1));
+        recorder.finalize_recording(true, None, None)?;
+        let saved_trajectory: Trajectory = serde_json::from_str(&std::fs::read_to_string(&trajectory_file)?)?;
+        assert!(saved_trajectory.cleanup.is_none());
+
+        let cleanup = CleanupReport {
+            terminated_process_groups: vec![4242],
+            removed_paths: vec![PathBuf::from("/tmp/trae-results-1")],
+            ..Default::default()
+        };
+        record_cleanup(&trajectory_file, &cleanup)?;
+        let saved_trajectory: Trajectory = serde_json::from_str(&std::fs::read_to_string(&trajectory_file)?)?;
+        assert_eq!(saved_trajectory.cleanup, Some(cleanup));
+        assert_eq!(saved_trajectory.steps.len(), 1);
+
+        Ok(())
+    }
+
     #[test]
     fn test_trajectory_recorder_create_parent_dirs() -> Result<()> {
         let dir = tempdir().unwrap();
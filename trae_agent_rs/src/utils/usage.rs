@@ -0,0 +1,253 @@
+//! # Usage Tracking
+//!
+//! Accumulates token usage per model and estimates its cost from a built-in table of list
+//! prices, or from the `pricing` of the config file. Models missing from both are still
+//! counted; only their cost is unknown.
+
+use crate::llm::base_client::LLMUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// List prices of a model, in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// Known prices, keyed by model name prefix. Dated variants (e.g., "gpt-4o-2024-08-06")
+/// match their base name; the longest matching prefix wins.
+const PRICES: &[(&str, ModelPrice)] = &[
+    ("gpt-4o", ModelPrice { input_per_mtok: 2.5, output_per_mtok: 10.0 }),
+    ("gpt-4o-mini", ModelPrice { input_per_mtok: 0.15, output_per_mtok: 0.6 }),
+    ("gpt-4.1", ModelPrice { input_per_mtok: 2.0, output_per_mtok: 8.0 }),
+    ("gpt-4.1-mini", ModelPrice { input_per_mtok: 0.4, output_per_mtok: 1.6 }),
+    ("gpt-4.1-nano", ModelPrice { input_per_mtok: 0.1, output_per_mtok: 0.4 }),
+    ("o3", ModelPrice { input_per_mtok: 2.0, output_per_mtok: 8.0 }),
+    ("o4-mini", ModelPrice { input_per_mtok: 1.1, output_per_mtok: 4.4 }),
+    ("claude-opus-4", ModelPrice { input_per_mtok: 15.0, output_per_mtok: 75.0 }),
+    ("claude-sonnet-4", ModelPrice { input_per_mtok: 3.0, output_per_mtok: 15.0 }),
+    ("claude-3-7-sonnet", ModelPrice { input_per_mtok: 3.0, output_per_mtok: 15.0 }),
+    ("claude-3-5-sonnet", ModelPrice { input_per_mtok: 3.0, output_per_mtok: 15.0 }),
+    ("claude-3-5-haiku", ModelPrice { input_per_mtok: 0.8, output_per_mtok: 4.0 }),
+];
+
+/// Prices configured in the config file (`pricing`), keyed by model name prefix like `PRICES`.
+pub type Pricing = BTreeMap<String, ModelPrice>;
+
+/// Looks up the price of a model in `pricing`, then in the built-in list prices. Configured
+/// prices may name the model with or without its OpenRouter `vendor/` prefix.
+pub fn price_in(pricing: &Pricing, model: &str) -> Option<ModelPrice> {
+    // OpenRouter names models `vendor/model`.
+    let name = model.rsplit_once('/').map_or(model, |(_, name)| name);
+    let longest = |prices: &mut dyn Iterator<Item = (&str, ModelPrice)>| {
+        prices
+            .filter(|(prefix, _)| model.starts_with(prefix) || name.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| price)
+    };
+    longest(&mut pricing.iter().map(|(prefix, price)| (prefix.as_str(), *price)))
+        .or_else(|| longest(&mut PRICES.iter().map(|(prefix, price)| (*prefix, *price))))
+}
+
+/// Token usage of one model (or of several, when totalled).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelUsage {
+    /// Number of LLM requests.
+    pub requests: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated cost in US dollars, `None` if the price of a model involved is unknown.
+    pub cost: Option<f64>,
+}
+
+impl ModelUsage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    fn add(&mut self, other: &ModelUsage) {
+        self.cost = if self.requests == 0 {
+            other.cost
+        } else {
+            self.cost.zip(other.cost).map(|(a, b)| a + b)
+        };
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// Accumulates usage per model.
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    by_model: BTreeMap<String, ModelUsage>,
+    pricing: Pricing,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A tracker pricing models with `pricing` before the built-in list prices.
+    pub fn with_pricing(pricing: Pricing) -> Self {
+        Self {
+            by_model: BTreeMap::new(),
+            pricing,
+        }
+    }
+
+    /// Records the usage reported for one LLM response.
+    ///
+    /// # Returns
+    /// The usage as recorded, with its cost.
+    pub fn record(&mut self, model: &str, usage: &LLMUsage) -> ModelUsage {
+        let prompt_tokens = u64::from(usage.prompt_tokens);
+        let completion_tokens = u64::from(
+            usage
+                .completion_tokens
+                .unwrap_or(usage.total_tokens.saturating_sub(usage.prompt_tokens)),
+        );
+        let cost = price_in(&self.pricing, model).map(|price| {
+            (prompt_tokens as f64 * price.input_per_mtok
+                + completion_tokens as f64 * price.output_per_mtok)
+                / 1_000_000.0
+        });
+        let recorded = ModelUsage {
+            requests: 1,
+            prompt_tokens,
+            completion_tokens,
+            cost,
+        };
+        self.by_model.entry(model.to_string()).or_default().add(&recorded);
+        recorded
+    }
+
+    /// The configured prices the tracker uses.
+    pub fn pricing(&self) -> &Pricing {
+        &self.pricing
+    }
+
+    /// Usage of each model, by model name.
+    pub fn by_model(&self) -> &BTreeMap<String, ModelUsage> {
+        &self.by_model
+    }
+
+    /// Adds everything recorded by `other`.
+    pub fn merge(&mut self, other: &UsageTracker) {
+        for (model, usage) in &other.by_model {
+            self.by_model.entry(model.clone()).or_default().add(usage);
+        }
+    }
+
+    /// Usage summed over all models.
+    pub fn total(&self) -> ModelUsage {
+        let mut total = ModelUsage::default();
+        for usage in self.by_model.values() {
+            total.add(usage);
+        }
+        total
+    }
+
+    /// One-line summary, e.g. "1,234 tokens (1,000 in / 234 out), ~$0.0049".
+    pub fn format_summary(&self) -> String {
+        format_usage(&self.total())
+    }
+
+    /// Multi-line breakdown by model, ending with the total.
+    pub fn format_breakdown(&self) -> String {
+        if self.by_model.is_empty() {
+            return "No LLM usage recorded yet.".to_string();
+        }
+        let mut lines: Vec<String> = self
+            .by_model
+            .iter()
+            .map(|(model, usage)| {
+                format!(
+                    "  {}: {} request(s), {}",
+                    model,
+                    usage.requests,
+                    format_usage(usage)
+                )
+            })
+            .collect();
+        lines.push(format!("  Total: {}", self.format_summary()));
+        lines.join("\n")
+    }
+}
+
+/// E.g. "1,234 tokens (1,000 in / 234 out), ~$0.0049".
+pub fn format_usage(usage: &ModelUsage) -> String {
+    let cost = match usage.cost {
+        Some(cost) => format!("~${:.4}", cost),
+        None => "cost unknown".to_string(),
+    };
+    format!(
+        "{} tokens ({} in / {} out), {}",
+        group_thousands(usage.total_tokens()),
+        group_thousands(usage.prompt_tokens),
+        group_thousands(usage.completion_tokens),
+        cost
+    )
+}
+
+/// Formats `n` with commas between groups of three digits.
+pub fn group_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(prompt: u32, completion: u32) -> LLMUsage {
+        LLMUsage {
+            prompt_tokens: prompt,
+            completion_tokens: Some(completion),
+            total_tokens: prompt + completion,
+        }
+    }
+
+    #[test]
+    fn test_price_lookup_prefers_longest_prefix() {
+        let list_prices = Pricing::new();
+        assert_eq!(price_in(&list_prices, "gpt-4o-mini-2024-07-18").unwrap().input_per_mtok, 0.15);
+        assert_eq!(price_in(&list_prices, "gpt-4o-2024-08-06").unwrap().input_per_mtok, 2.5);
+        assert!(price_in(&list_prices, "my-local-model").is_none());
+        let configured = Pricing::from([(
+            "my-local".to_string(),
+            ModelPrice { input_per_mtok: 0.5, output_per_mtok: 1.0 },
+        )]);
+        assert_eq!(price_in(&configured, "vendor/my-local-model").unwrap().output_per_mtok, 1.0);
+    }
+
+    #[test]
+    fn test_tracker_totals_and_formatting() {
+        let mut turn = UsageTracker::new();
+        turn.record("gpt-4o", &usage(1_000_000, 100_000));
+        assert_eq!(turn.format_summary(), "1,100,000 tokens (1,000,000 in / 100,000 out), ~$3.5000");
+
+        let mut session = UsageTracker::new();
+        session.merge(&turn);
+        session.merge(&turn);
+        assert_eq!(session.by_model["gpt-4o"].requests, 2);
+        assert_eq!(session.total().cost, Some(7.0));
+
+        session.record("my-local-model", &usage(10, 5));
+        let total = session.total();
+        assert_eq!(total.total_tokens(), 2_200_015);
+        assert_eq!(total.cost, None);
+        let breakdown = session.format_breakdown();
+        assert!(breakdown.contains("my-local-model: 1 request(s), 15 tokens (10 in / 5 out), cost unknown"));
+        assert!(breakdown.ends_with("cost unknown"));
+    }
+}